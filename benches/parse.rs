@@ -0,0 +1,62 @@
+use std::hint::black_box;
+
+use ac_lib::{CarInfo, Event, HandshakeResponse, LapInfo, Name};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+fn car_info_packet() -> Vec<u8> {
+    CarInfo {
+        speed_kmh: 212.5,
+        gear: 5,
+        engine_rpm: 7200.0,
+        wheel_angular_speed: [91.0, 91.2, 93.4, 93.5],
+        car_coordinates: [-120.5, 3.2, 880.1],
+        ..CarInfo::default()
+    }
+    .to_bytes()
+    .to_vec()
+}
+
+fn parse_car_info(c: &mut Criterion) {
+    let packet = car_info_packet();
+
+    let mut group = c.benchmark_group("car_info");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("try_parse", |b| {
+        b.iter(|| Event::try_parse(black_box(&packet)))
+    });
+
+    let mut car_info = CarInfo::default();
+    group.bench_function("parse_into", |b| {
+        b.iter(|| car_info.parse_into(black_box(&packet)))
+    });
+    group.finish();
+}
+
+fn parse_names(c: &mut Criterion) {
+    let handshake = HandshakeResponse {
+        car_name: Name::from("ks_mazda_mx5_cup").unwrap(),
+        driver_name: Name::from("Driver").unwrap(),
+        track_name: Name::from("magione").unwrap(),
+        ..HandshakeResponse::default()
+    }
+    .to_bytes();
+    let lap_info = LapInfo {
+        car_name: Name::from("ks_mazda_mx5_cup").unwrap(),
+        driver_name: Name::from("Driver").unwrap(),
+        ..LapInfo::default()
+    }
+    .to_bytes();
+
+    let mut group = c.benchmark_group("names");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("handshake_response", |b| {
+        b.iter(|| Event::try_parse(black_box(&handshake)))
+    });
+    group.bench_function("lap_info", |b| {
+        b.iter(|| Event::try_parse(black_box(&lap_info)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_car_info, parse_names);
+criterion_main!(benches);
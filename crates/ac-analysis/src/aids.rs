@@ -0,0 +1,205 @@
+//! Where the electronic aids step in. ABS and traction control each report
+//! whether they're working at every sample; adding that up per corner shows
+//! which corners they're saving the driver in, and comparing laps run on
+//! different TC or ABS levels shows what a setting change did.
+
+use std::time::Duration;
+
+use crate::{
+    corners::{Corner, CornerRules},
+    trace::{Sample, Trace},
+};
+
+/// How much ABS and traction control worked over some samples.
+///
+/// * `abs_time`, `tc_time`: how long each was in action.
+/// * `abs_activations`, `tc_activations`: how many separate times each came in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AidUsage {
+    pub abs_time: Duration,
+    pub tc_time: Duration,
+    pub abs_activations: usize,
+    pub tc_activations: usize,
+}
+
+impl AidUsage {
+    /// folds the aid flags of some samples; each sample's flags hold until the
+    /// next. An aid already working at the first sample counts as coming in there.
+    ///
+    /// * `samples`: the samples, e.g. one corner.
+    pub fn of(samples: &[Sample]) -> Self {
+        let mut usage = AidUsage::default();
+        let (mut abs, mut tc) = (false, false);
+
+        for (i, sample) in samples.iter().enumerate() {
+            let car = &sample.car;
+            usage.abs_activations += usize::from(car.is_abs_in_action && !abs);
+            usage.tc_activations += usize::from(car.is_tc_in_action && !tc);
+            (abs, tc) = (car.is_abs_in_action, car.is_tc_in_action);
+
+            let held = samples
+                .get(i + 1)
+                .map_or(Duration::ZERO, |next| next.time.saturating_sub(sample.time));
+            if abs {
+                usage.abs_time += held;
+            }
+            if tc {
+                usage.tc_time += held;
+            }
+        }
+
+        usage
+    }
+}
+
+/// The ABS and traction control use of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `corners`: each corner with the aids' use through it.
+/// * `overall`: the aids' use over the whole lap, straights included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AidsReport {
+    pub lap: u32,
+    pub corners: Vec<(Corner, AidUsage)>,
+    pub overall: AidUsage,
+}
+
+impl AidsReport {
+    /// the ABS and traction control use through every corner of a lap. `None` for an empty lap.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn of(lap: &Trace, rules: &CornerRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let corners = Corner::find(lap, rules)
+            .into_iter()
+            .map(|corner| {
+                let usage = AidUsage::of(&samples[corner.samples.clone()]);
+                (corner, usage)
+            })
+            .collect();
+
+        Some(Self {
+            lap: first.car.lap_count,
+            corners,
+            overall: AidUsage::of(samples),
+        })
+    }
+}
+
+#[cfg(test)]
+mod aids_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        aids::{AidUsage, AidsReport},
+        corners::CornerRules,
+        trace::Trace,
+    };
+
+    fn car(accg_horizontal: f32, abs: bool, tc: bool) -> CarInfo {
+        CarInfo {
+            lap_count: 7,
+            accg_horizontal,
+            is_abs_in_action: abs,
+            is_tc_in_action: tc,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a lap sampled every 100 ms.
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+        lap
+    }
+
+    #[test]
+    fn no_samples_use_no_aids() {
+        assert_eq!(AidsReport::of(&Trace::new(), &CornerRules::default()), None);
+        assert_eq!(AidUsage::of(&[]), AidUsage::default());
+    }
+
+    #[test]
+    fn a_single_sample_comes_in_for_no_time() {
+        let usage = AidUsage::of(lap([car(0.0, true, true)]).samples());
+        assert_eq!((usage.abs_activations, usage.tc_activations), (1, 1));
+        assert_eq!(
+            (usage.abs_time, usage.tc_time),
+            (Duration::ZERO, Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn an_aid_on_all_lap_comes_in_once() {
+        let report = AidsReport::of(
+            &lap(vec![car(0.0, false, true); 6]),
+            &CornerRules::default(),
+        )
+        .expect("not empty");
+        assert!(report.corners.is_empty());
+        assert_eq!(report.overall.tc_activations, 1);
+        assert_eq!(report.overall.tc_time, Duration::from_millis(500));
+        assert_eq!(report.overall.abs_activations, 0);
+    }
+
+    #[test]
+    fn an_aid_working_into_a_corner_comes_in_there_too() {
+        let samples = [
+            car(0.0, true, false),
+            car(1.0, true, false),
+            car(1.0, true, false),
+            car(1.0, true, false),
+            car(1.0, true, false),
+            car(1.0, false, false),
+            car(1.0, false, false),
+            car(0.0, false, false),
+        ];
+        let report = AidsReport::of(&lap(samples), &CornerRules::default()).expect("not empty");
+
+        let [(_, corner)] = &report.corners[..] else {
+            panic!("expected one corner, got {:?}", report.corners);
+        };
+        assert_eq!(corner.abs_activations, 1);
+        assert_eq!(corner.abs_time, Duration::from_millis(400));
+        assert_eq!(
+            report.overall.abs_activations, 1,
+            "one activation on the lap"
+        );
+    }
+
+    #[test]
+    fn aid_time_is_added_up_per_corner() {
+        // ABS on the straight into the corner, then TC twice on the way out.
+        let samples = [
+            car(0.0, false, false),
+            car(0.0, true, false),
+            car(1.0, true, false),
+            car(1.0, false, false),
+            car(1.0, false, true),
+            car(1.0, false, false),
+            car(1.0, false, true),
+            car(1.0, false, true),
+            car(0.0, false, false),
+        ];
+
+        let report = AidsReport::of(&lap(samples), &CornerRules::default()).expect("not empty");
+        let [(_, corner)] = &report.corners[..] else {
+            panic!("expected one corner, got {:?}", report.corners);
+        };
+        let ms = Duration::from_millis;
+        assert_eq!((corner.abs_time, corner.abs_activations), (ms(100), 1));
+        // The last TC sample of the corner only counts up to the corner's end.
+        assert_eq!((corner.tc_time, corner.tc_activations), (ms(200), 2));
+
+        let overall = report.overall;
+        assert_eq!((overall.abs_time, overall.abs_activations), (ms(200), 1));
+        assert_eq!((overall.tc_time, overall.tc_activations), (ms(300), 2));
+    }
+}
@@ -0,0 +1,256 @@
+//! Which axle runs out of grip first, from slip angles through each corner: a
+//! front axle running more slip than the rear is the car understeering, the
+//! rear running more is it oversteering. A building block for setup advisors
+//! that turn "limited at the front in slow corners" into spring and bar changes.
+
+use ac_parser::{CarInfo, Wheel};
+
+use crate::{
+    corners::{Corner, CornerRules},
+    trace::{Sample, Trace},
+};
+
+/// How much more slip, in AC's slip angle units, one axle has to run on
+/// average before the car counts as limited by it.
+pub const BALANCE_MARGIN: f32 = 0.5;
+
+/// Which axle limits the car.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The front runs more slip: understeer.
+    Front,
+    /// The rear runs more slip: oversteer.
+    Rear,
+    /// Neither runs `BALANCE_MARGIN` more than the other.
+    Neutral,
+}
+
+/// Mean and peak slip angle of each axle, with left and right averaged and
+/// turning direction ignored.
+///
+/// * `front_mean`, `rear_mean`: the mean over the samples.
+/// * `front_peak`, `rear_peak`: the largest of any sample.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AxleSlip {
+    pub front_mean: f32,
+    pub rear_mean: f32,
+    pub front_peak: f32,
+    pub rear_peak: f32,
+}
+
+impl AxleSlip {
+    /// folds the slip angles of some samples, skipping any with a slip angle
+    /// that isn't a number.
+    ///
+    /// * `samples`: the samples, e.g. one corner.
+    fn of<'a>(samples: impl IntoIterator<Item = &'a Sample>) -> Self {
+        let axle = |car: &CarInfo, left: Wheel, right: Wheel| {
+            (car.slip_angle[left as usize].abs() + car.slip_angle[right as usize].abs()) / 2.0
+        };
+
+        let mut slip = AxleSlip::default();
+        let mut count = 0;
+        for sample in samples {
+            let front = axle(&sample.car, Wheel::FrontLeft, Wheel::FrontRight);
+            let rear = axle(&sample.car, Wheel::RearLeft, Wheel::RearRight);
+            if front.is_nan() || rear.is_nan() {
+                continue;
+            }
+            slip.front_mean += front;
+            slip.rear_mean += rear;
+            slip.front_peak = slip.front_peak.max(front);
+            slip.rear_peak = slip.rear_peak.max(rear);
+            count += 1;
+        }
+
+        if count > 0 {
+            slip.front_mean /= count as f32;
+            slip.rear_mean /= count as f32;
+        }
+        slip
+    }
+
+    /// front mean slip less rear: positive towards understeer.
+    pub fn balance(&self) -> f32 {
+        self.front_mean - self.rear_mean
+    }
+
+    /// which axle runs `BALANCE_MARGIN` more slip on average, if either does.
+    pub fn limit(&self) -> Limit {
+        match self.balance() {
+            balance if balance > BALANCE_MARGIN => Limit::Front,
+            balance if balance < -BALANCE_MARGIN => Limit::Rear,
+            _ => Limit::Neutral,
+        }
+    }
+}
+
+/// The axle balance of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `corners`: each corner with its slip; samples outside corners don't count.
+/// * `overall`: the slip over every corner together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceReport {
+    pub lap: u32,
+    pub corners: Vec<(Corner, AxleSlip)>,
+    pub overall: AxleSlip,
+}
+
+impl BalanceReport {
+    /// the balance through every corner of a lap. `None` for an empty lap.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn of(lap: &Trace, rules: &CornerRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let corners: Vec<_> = Corner::find(lap, rules)
+            .into_iter()
+            .map(|corner| {
+                let slip = AxleSlip::of(&samples[corner.samples.clone()]);
+                (corner, slip)
+            })
+            .collect();
+        let overall = AxleSlip::of(
+            corners
+                .iter()
+                .flat_map(|(corner, _)| &samples[corner.samples.clone()]),
+        );
+
+        Some(Self {
+            lap: first.car.lap_count,
+            corners,
+            overall,
+        })
+    }
+
+    /// the corners each axle limits: front-limited, then rear-limited, by corner number.
+    pub fn limited(&self) -> (Vec<usize>, Vec<usize>) {
+        let by = |limit| {
+            self.corners
+                .iter()
+                .filter(|(_, slip)| slip.limit() == limit)
+                .map(|(corner, _)| corner.number)
+                .collect()
+        };
+
+        (by(Limit::Front), by(Limit::Rear))
+    }
+}
+
+#[cfg(test)]
+mod balance_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        balance::{AxleSlip, BalanceReport, Limit},
+        corners::CornerRules,
+        trace::Trace,
+    };
+
+    fn car(accg_horizontal: f32, front: f32, rear: f32) -> CarInfo {
+        CarInfo {
+            lap_count: 4,
+            accg_horizontal,
+            slip_angle: [front, -front, rear, -rear],
+            ..CarInfo::default()
+        }
+    }
+
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(200 * i as u64), car);
+        }
+        lap
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_report() {
+        assert_eq!(
+            BalanceReport::of(&Trace::new(), &CornerRules::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn a_lap_without_corners_is_neutral() {
+        let straight = lap([car(0.1, 2.0, 0.0), car(0.2, 2.0, 0.0)]);
+        let report = BalanceReport::of(&straight, &CornerRules::default()).expect("not empty");
+
+        assert!(report.corners.is_empty());
+        assert_eq!(
+            report.overall,
+            AxleSlip::default(),
+            "the straight isn't counted"
+        );
+        assert_eq!(report.overall.limit(), Limit::Neutral);
+        assert_eq!(report.limited(), (vec![], vec![]));
+    }
+
+    #[test]
+    fn a_single_sample_corner_is_its_own_mean_and_peak() {
+        let rules = CornerRules {
+            min_duration: Duration::ZERO,
+            ..CornerRules::default()
+        };
+        let report = BalanceReport::of(&lap([car(0.0, 0.0, 0.0), car(1.0, 2.0, 5.0)]), &rules)
+            .expect("not empty");
+
+        let [(_, slip)] = report.corners[..] else {
+            panic!("expected one corner");
+        };
+        assert_eq!((slip.front_mean, slip.front_peak), (2.0, 2.0));
+        assert_eq!((slip.rear_mean, slip.rear_peak), (5.0, 5.0));
+        assert_eq!(slip.limit(), Limit::Rear);
+    }
+
+    #[test]
+    fn nan_slip_angles_are_skipped() {
+        let corner = lap([
+            car(1.0, 4.0, 2.0),
+            car(1.0, f32::NAN, 2.0),
+            car(1.0, 6.0, 2.0),
+            car(1.0, 5.0, 2.0),
+        ]);
+        let report = BalanceReport::of(&corner, &CornerRules::default()).expect("not empty");
+
+        assert_eq!(report.overall.front_mean, 5.0);
+        assert_eq!(report.overall.limit(), Limit::Front);
+    }
+
+    #[test]
+    fn slip_angles_show_which_axle_limits_each_corner() {
+        let mut lap = Trace::new();
+        // Corner 1 pushes at the front, corner 2 steps the rear out.
+        let samples = [
+            car(0.0, 9.0, 9.0),
+            car(1.0, 5.0, 3.0),
+            car(1.1, 7.0, 4.0),
+            car(1.0, 6.0, 3.0),
+            car(1.0, 6.0, 3.0),
+            car(0.0, 0.0, 0.0),
+            car(-1.0, 2.0, 4.0),
+            car(-1.0, 3.0, 6.0),
+            car(-1.0, 2.0, 4.0),
+            car(-1.0, 3.0, 4.0),
+            car(0.0, 0.0, 0.0),
+        ];
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(200 * i as u64), car);
+        }
+
+        let report = BalanceReport::of(&lap, &CornerRules::default()).expect("not empty");
+        assert_eq!(report.lap, 4);
+        assert_eq!(report.corners.len(), 2);
+        let first = report.corners[0].1;
+        assert_eq!((first.front_mean, first.rear_peak), (6.0, 4.0));
+        assert_eq!(first.limit(), Limit::Front);
+        assert_eq!(report.limited(), (vec![1], vec![2]));
+        assert_eq!(report.overall.limit(), Limit::Neutral);
+    }
+}
@@ -0,0 +1,239 @@
+//! Analysis of many recorded sessions at once, e.g. every file from a league
+//! night. Each recording is read and run through its own `Pipeline` on
+//! rayon's thread pool, so a folder of sessions takes about as long as the
+//! slowest one, and the results come back together in a `BatchReport` for
+//! aggregate questions: the fastest lap of the night, or a logbook of it.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+use ac_parser::Recording;
+use rayon::prelude::*;
+
+use crate::{
+    logbook::{Logbook, LogbookEntry},
+    pipeline::{LapTime, LapTimer, Output, Pipeline, PipelineBuilder},
+};
+
+/// Builds the pipeline each recording runs through.
+type PipelineFactory = Box<dyn Fn() -> Pipeline + Sync>;
+
+/// Runs a pipeline over many recordings in parallel.
+///
+/// * `pipeline`: makes a fresh pipeline for each recording.
+pub struct BatchAnalysis {
+    pipeline: PipelineFactory,
+}
+
+impl Default for BatchAnalysis {
+    /// times laps.
+    fn default() -> Self {
+        Self::new(|| PipelineBuilder::new().stage(LapTimer::new()).build())
+    }
+}
+
+impl BatchAnalysis {
+    /// * `pipeline`: makes the pipeline each recording runs through, e.g.
+    ///   `|| PipelineBuilder::coach(rules.clone()).build()`.
+    pub fn new<F>(pipeline: F) -> Self
+    where
+        F: Fn() -> Pipeline + Sync + 'static,
+    {
+        Self {
+            pipeline: Box::new(pipeline),
+        }
+    }
+
+    /// reads and analyses every recording, in parallel. Sessions come back
+    /// in the order of `paths`; files that can't be read or finished are
+    /// reported as failures instead.
+    ///
+    /// * `paths`: the recording files.
+    pub fn run<P>(&self, paths: &[P]) -> BatchReport
+    where
+        P: AsRef<Path> + Sync,
+    {
+        let results: Vec<_> = paths
+            .par_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let recording = Recording::read_from(BufReader::new(File::open(path)?))?;
+                self.analyse(path, &recording)
+            })
+            .collect();
+
+        let mut report = BatchReport::default();
+        for (path, result) in paths.iter().zip(results) {
+            match result {
+                Ok(session) => report.sessions.push(session),
+                Err(why) => report.failures.push((path.as_ref().to_path_buf(), why)),
+            }
+        }
+
+        report
+    }
+
+    /// analyses recordings already in memory, in parallel, in their order.
+    ///
+    /// * `recordings`: each recording, with the name to report it under.
+    pub fn run_recordings(&self, recordings: &[(PathBuf, Recording)]) -> BatchReport {
+        let results: Vec<_> = recordings
+            .par_iter()
+            .map(|(path, recording)| self.analyse(path, recording))
+            .collect();
+
+        let mut report = BatchReport::default();
+        for ((path, _), result) in recordings.iter().zip(results) {
+            match result {
+                Ok(session) => report.sessions.push(session),
+                Err(why) => report.failures.push((path.clone(), why)),
+            }
+        }
+
+        report
+    }
+
+    fn analyse(&self, path: &Path, recording: &Recording) -> io::Result<BatchSession> {
+        let mut outputs = (self.pipeline)().run(recording)?;
+        // Events are in the recording already; keeping them would hold every
+        // session in memory twice.
+        outputs.retain(|output| !matches!(output, Output::Event(_)));
+
+        Ok(BatchSession {
+            path: path.to_path_buf(),
+            entry: LogbookEntry::from_recording(recording),
+            outputs,
+        })
+    }
+}
+
+/// One analysed recording.
+///
+/// * `path`: where it came from.
+/// * `entry`: its track, car, best lap and distance, if it had a handshake
+///   and any CarInfo.
+/// * `outputs`: what the pipeline produced, without the events themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchSession {
+    pub path: PathBuf,
+    pub entry: Option<LogbookEntry>,
+    pub outputs: Vec<Output>,
+}
+
+impl BatchSession {
+    /// the laps the pipeline timed.
+    pub fn laps(&self) -> impl Iterator<Item = &LapTime> {
+        self.outputs.iter().filter_map(|output| match output {
+            Output::Lap(lap) => Some(lap),
+            _ => None,
+        })
+    }
+}
+
+/// Every session of a batch.
+///
+/// * `sessions`: the sessions analysed, in the order they were given.
+/// * `failures`: the files that couldn't be, and why.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub sessions: Vec<BatchSession>,
+    pub failures: Vec<(PathBuf, io::Error)>,
+}
+
+impl BatchReport {
+    /// the fastest valid lap across every session, and the session it's from.
+    pub fn fastest(&self) -> Option<(&BatchSession, &LapTime)> {
+        self.sessions
+            .iter()
+            .flat_map(|session| session.laps().map(move |lap| (session, lap)))
+            .filter(|(_, lap)| lap.validity.is_valid())
+            .min_by_key(|(_, lap)| lap.time)
+    }
+
+    /// every lap timed, across every session.
+    pub fn lap_count(&self) -> usize {
+        self.sessions
+            .iter()
+            .map(|session| session.laps().count())
+            .sum()
+    }
+
+    /// a logbook of the sessions that had a track and car.
+    pub fn logbook(&self) -> Logbook {
+        let mut logbook = Logbook::new();
+        self.sessions
+            .iter()
+            .filter_map(|session| session.entry.clone())
+            .for_each(|entry| logbook.add(entry));
+        logbook
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use ac_parser::{CarInfo, Event, HandshakeResponse, Name, Recording};
+
+    use crate::batch::BatchAnalysis;
+
+    /// a session at a track, completing a lap for every time given.
+    fn session(track: &str, laps: &[u32]) -> Recording {
+        let mut recording = Recording::new();
+        let handshake = HandshakeResponse {
+            track_name: Name::from(track).unwrap(),
+            ..HandshakeResponse::default()
+        };
+        recording.push(Duration::ZERO, &handshake.to_bytes());
+
+        let mut last_lap = 0;
+        for (lap, &time) in std::iter::once(&0).chain(laps).enumerate() {
+            if time > 0 {
+                last_lap = time;
+            }
+            let car = CarInfo {
+                lap_count: lap as u32,
+                last_lap,
+                ..CarInfo::default()
+            };
+            recording.push(
+                Duration::from_secs(100 * lap as u64 + 1),
+                &Event::CarInfo(car).to_bytes(),
+            );
+        }
+
+        recording
+    }
+
+    #[test]
+    fn recordings_are_analysed_together() {
+        let dir = std::env::temp_dir().join(format!("ac_lib-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for (name, laps) in [("spa", &[98_000, 95_500][..]), ("monza", &[93_250][..])] {
+            let path = dir.join(format!("{name}.acrc"));
+            let file = fs::File::create(&path).expect("created");
+            session(name, laps).write_to(file).expect("written");
+            paths.push(path);
+        }
+        paths.push(dir.join("missing.acrc"));
+
+        let report = BatchAnalysis::default().run(&paths);
+        fs::remove_dir_all(&dir).expect("cleaned up");
+
+        assert_eq!(report.sessions.len(), 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, paths[2]);
+        assert_eq!(report.lap_count(), 3);
+
+        let (session, lap) = report.fastest().expect("laps were timed");
+        assert_eq!(session.path, paths[1]);
+        assert_eq!(lap.time, Duration::from_millis(93_250));
+
+        let logbook = report.logbook();
+        assert_eq!(logbook.tracks(), ["monza", "spa"]);
+    }
+}
@@ -0,0 +1,270 @@
+//! Close fights between cars, for broadcast director tooling. Two cars are in a
+//! battle once they've stayed within `max_gap` of each other for `min_duration`;
+//! the battle ends when the gap opens up or they stop being seen together.
+
+use std::{collections::HashMap, time::Duration};
+
+use ac_parser::{Event, LapInfo};
+
+use crate::{
+    pipeline::{Output, Stage},
+    standings::Standings,
+};
+
+/// When two cars count as battling.
+///
+/// * `max_gap`: the widest gap between them that still counts as close.
+/// * `min_duration`: how long they must stay close before a battle starts.
+/// * `timeout`: how long a pair can go without being seen close before its
+///   battle ends anyway, e.g. once another car gets between them.
+/// * `segments`: how many equal segments the lap is split into for `Battle::segment`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BattleConfig {
+    pub max_gap: Duration,
+    pub min_duration: Duration,
+    pub timeout: Duration,
+    pub segments: u32,
+}
+
+impl Default for BattleConfig {
+    /// within a second for ten seconds, split into ten segments. Spot updates
+    /// only compare cars once a lap, so the timeout allows for two laps.
+    fn default() -> Self {
+        Self {
+            max_gap: Duration::from_secs(1),
+            min_duration: Duration::from_secs(10),
+            timeout: Duration::from_secs(6 * 60),
+            segments: 10,
+        }
+    }
+}
+
+/// Two cars fighting over a position.
+///
+/// * `ahead`: the car in front when the battle started.
+/// * `behind`: the car following it.
+/// * `since`: when they first came within `max_gap`.
+/// * `segment`: which segment of the lap they were in when it started.
+/// * `gap`: the gap between them when it started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Battle {
+    pub ahead: i32,
+    pub behind: i32,
+    pub since: Duration,
+    pub segment: u32,
+    pub gap: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleEvent {
+    BattleStarted(Battle),
+    /// The battle, and when it ended.
+    BattleEnded(Battle, Duration),
+}
+
+/// A pair of cars seen close together.
+///
+/// * `since`: when they first came within `max_gap`.
+/// * `last_seen`: when they were last seen close.
+/// * `battle`: the battle, once it has started.
+#[derive(Debug, Clone, Copy)]
+struct Close {
+    since: Duration,
+    last_seen: Duration,
+    battle: Option<Battle>,
+}
+
+/// Tracks which pairs of cars are close, and turns that into battle events.
+///
+/// * `config`: when two cars count as battling.
+/// * `pairs`: every pair currently close, by (lower id, higher id).
+/// * `standings`: the race order, for pairing cars when used as a pipeline stage.
+#[derive(Debug, Clone, Default)]
+pub struct BattleDetector {
+    config: BattleConfig,
+    pairs: HashMap<(i32, i32), Close>,
+    standings: Standings,
+}
+
+impl BattleDetector {
+    /// * `config`: when two cars count as battling.
+    pub fn new(config: BattleConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// records the gap between two cars, returning a battle event if it starts or ends one.
+    ///
+    /// * `at`: when the gap was measured, on any monotonic timeline.
+    /// * `ahead`: the car in front.
+    /// * `behind`: the car following it.
+    /// * `gap`: how far behind `ahead` the `behind` car is.
+    /// * `track_pos`: where on the lap it was measured, from 0 to 1.
+    pub fn observe(
+        &mut self,
+        at: Duration,
+        ahead: i32,
+        behind: i32,
+        gap: Duration,
+        track_pos: f32,
+    ) -> Option<BattleEvent> {
+        let key = (ahead.min(behind), ahead.max(behind));
+
+        if gap > self.config.max_gap {
+            let close = self.pairs.remove(&key)?;
+            return close
+                .battle
+                .map(|battle| BattleEvent::BattleEnded(battle, at));
+        }
+
+        let close = self.pairs.entry(key).or_insert(Close {
+            since: at,
+            last_seen: at,
+            battle: None,
+        });
+        close.last_seen = at;
+
+        if close.battle.is_some() || at.saturating_sub(close.since) < self.config.min_duration {
+            return None;
+        }
+
+        let segments = self.config.segments.max(1);
+        let battle = Battle {
+            ahead,
+            behind,
+            since: close.since,
+            segment: ((track_pos.clamp(0.0, 1.0) * segments as f32) as u32).min(segments - 1),
+            gap,
+        };
+        close.battle = Some(battle);
+
+        Some(BattleEvent::BattleStarted(battle))
+    }
+
+    /// ends battles between cars that haven't been seen close within the timeout.
+    ///
+    /// * `now`: the current time, on the same timeline as `observe`.
+    pub fn expire(&mut self, now: Duration) -> Vec<BattleEvent> {
+        let timeout = self.config.timeout;
+        let mut events = Vec::new();
+
+        self.pairs.retain(|_, close| {
+            let keep = now.saturating_sub(close.last_seen) <= timeout;
+            if let (false, Some(battle)) = (keep, close.battle) {
+                events.push(BattleEvent::BattleEnded(battle, now));
+            }
+            keep
+        });
+
+        events
+    }
+
+    /// every battle in progress.
+    pub fn battles(&self) -> impl Iterator<Item = &Battle> {
+        self.pairs
+            .values()
+            .filter_map(|close| close.battle.as_ref())
+    }
+
+    /// records a car crossing the line, comparing it with the car ahead of it.
+    ///
+    /// * `at`: when the LapInfo arrived, on any monotonic timeline.
+    /// * `info`: the LapInfo.
+    pub fn crossed_line(&mut self, at: Duration, info: &LapInfo) -> Vec<BattleEvent> {
+        self.standings.update(at, info);
+
+        let car = info.car_id_num;
+        let ahead = self
+            .standings
+            .position(car)
+            .and_then(|position| self.standings.car_at(position.checked_sub(1)?));
+
+        let mut events = Vec::new();
+        if let Some(ahead) = ahead
+            && let Some(gap) = self.standings.interval(ahead, car)
+        {
+            events.extend(self.observe(at, ahead, car, gap, 0.0));
+        }
+        events.extend(self.expire(at));
+
+        events
+    }
+}
+
+impl Stage for BattleDetector {
+    fn name(&self) -> &str {
+        "battles"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            outputs.extend(self.crossed_line(at, info).into_iter().map(Output::Battle));
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod battles_tests {
+    use std::time::Duration;
+
+    use ac_parser::LapInfo;
+
+    use crate::battles::{BattleConfig, BattleDetector, BattleEvent};
+
+    #[test]
+    fn close_pairs_become_battles_until_the_gap_opens() {
+        let secs = Duration::from_secs;
+        let config = BattleConfig {
+            max_gap: secs(1),
+            min_duration: secs(5),
+            ..BattleConfig::default()
+        };
+        let mut detector = BattleDetector::new(config);
+
+        assert_eq!(detector.observe(secs(0), 1, 2, secs(2), 0.1), None);
+        assert_eq!(detector.observe(secs(1), 1, 2, secs(1), 0.1), None);
+
+        let started = detector.observe(secs(7), 2, 1, Duration::from_millis(300), 0.45);
+        let Some(BattleEvent::BattleStarted(battle)) = started else {
+            panic!("expected a battle, got {started:?}");
+        };
+        assert_eq!((battle.since, battle.segment), (secs(1), 4));
+        assert_eq!(detector.battles().count(), 1);
+
+        let ended = detector.observe(secs(9), 2, 1, secs(3), 0.5);
+        assert_eq!(ended, Some(BattleEvent::BattleEnded(battle, secs(9))));
+        assert_eq!(detector.battles().count(), 0);
+    }
+
+    #[test]
+    fn line_crossings_pair_each_car_with_the_one_ahead() {
+        let secs = Duration::from_secs;
+        let mut detector = BattleDetector::new(BattleConfig::default());
+        let lap = |car_id_num, lap| LapInfo {
+            car_id_num,
+            lap,
+            ..LapInfo::default()
+        };
+
+        detector.crossed_line(secs(100), &lap(1, 1));
+        assert!(
+            detector
+                .crossed_line(secs(100) + Duration::from_millis(500), &lap(2, 1))
+                .is_empty()
+        );
+        detector.crossed_line(secs(190), &lap(1, 2));
+        let events = detector.crossed_line(secs(190) + Duration::from_millis(400), &lap(2, 2));
+
+        assert!(matches!(
+            events[..],
+            [BattleEvent::BattleStarted(battle)] if battle.ahead == 1 && battle.behind == 2
+        ));
+
+        let events = detector.expire(secs(1000));
+        assert!(matches!(events[..], [BattleEvent::BattleEnded(..)]));
+    }
+}
@@ -0,0 +1,415 @@
+//! The session's fastest lap and sector bests across every car. Lap times come
+//! from each car's LapInfo; sector times from `record_sector`, or from the
+//! player's CarInfo split into `Sectors` by track position, since the
+//! protocol only reports positions for the player's own car.
+
+use std::time::Duration;
+
+use ac_parser::{CarInfo, Event, LapInfo};
+
+use crate::{
+    pipeline::{Output, Stage},
+    sectors::Sectors,
+};
+
+/// A best time and who holds it.
+///
+/// * `car_id`: the holder's `car_id_num`.
+/// * `time`: the time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Best {
+    pub car_id: i32,
+    pub time: Duration,
+}
+
+/// A session best was beaten, or set for the first time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BestEvent {
+    /// A new fastest lap, and the one it beat.
+    FastestLap { best: Best, previous: Option<Best> },
+    /// A new best for a sector, counting from zero, and the one it beat.
+    PurpleSector {
+        sector: usize,
+        best: Best,
+        previous: Option<Best>,
+    },
+}
+
+/// Every session best and its holder.
+///
+/// * `fastest_lap`: the fastest lap by any car.
+/// * `sectors`: the best time for each sector by any car.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BestsSummary {
+    pub fastest_lap: Option<Best>,
+    pub sectors: Vec<Option<Best>>,
+}
+
+/// Splits the player's laps into sector times by track position.
+///
+/// * `lap_count`: the lap counter in the last CarInfo.
+/// * `sector`: the sector the car is in.
+/// * `split_at`: the lap time when the car entered that sector, unless it was
+///   first seen part way through it.
+#[derive(Debug, Clone, Default)]
+struct SectorSplitter {
+    lap_count: Option<u32>,
+    sector: usize,
+    split_at: Option<u32>,
+}
+
+impl SectorSplitter {
+    /// returns a sector and its time once the car leaves it. A CarInfo
+    /// without a track position is passed over, and one whose lap counter
+    /// went back, as on a restart, starts over part way through a sector.
+    ///
+    /// * `sectors`: the lap's sectors.
+    /// * `car`: the latest CarInfo.
+    fn observe(&mut self, sectors: &Sectors, car: &CarInfo) -> Option<(usize, Duration)> {
+        if car.car_pos_normalized.is_nan() {
+            return None;
+        }
+
+        let sector = sectors.sector(car.car_pos_normalized);
+        let Some(lap_count) = self.lap_count.replace(car.lap_count) else {
+            self.sector = sector;
+            return None;
+        };
+
+        if car.lap_count < lap_count {
+            self.sector = sector;
+            self.split_at = None;
+            return None;
+        }
+
+        if car.lap_count != lap_count {
+            let last = (self.sector + 1 == sectors.len()).then_some(car.last_lap);
+            let split = self.finish_sector(last);
+            self.sector = 0;
+            self.split_at = Some(0);
+            return split;
+        }
+
+        if sector == self.sector {
+            return None;
+        }
+
+        // a jump, or going backwards: the new sector's start wasn't seen.
+        if sector != self.sector + 1 {
+            self.sector = sector;
+            self.split_at = None;
+            return None;
+        }
+
+        let split = self.finish_sector(Some(car.lap_time));
+        self.sector = sector;
+        split
+    }
+
+    /// the time for the sector in progress, and starts timing the next one.
+    ///
+    /// * `lap_time`: the lap time the sector ended at, if known.
+    fn finish_sector(&mut self, lap_time: Option<u32>) -> Option<(usize, Duration)> {
+        let start = self.split_at.take();
+        let end = lap_time?;
+        self.split_at = Some(end);
+        let start = start?;
+
+        (end > start).then(|| (self.sector, Duration::from_millis((end - start).into())))
+    }
+}
+
+/// Tracks the session's fastest lap and purple sectors across cars.
+///
+/// * `player`: the `car_id_num` the player's CarInfo sectors are credited to.
+/// * `summary`: every best so far.
+/// * `sectors`: where the player's CarInfo is split into sectors.
+/// * `splitter`: sector timing for the player's CarInfo.
+#[derive(Debug, Clone)]
+pub struct SessionBests {
+    player: i32,
+    summary: BestsSummary,
+    sectors: Sectors,
+    splitter: SectorSplitter,
+}
+
+impl Default for SessionBests {
+    /// three sectors, credited to car 0.
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl SessionBests {
+    /// * `sectors`: how many equal sectors the lap is split into.
+    pub fn new(sectors: usize) -> Self {
+        Self::with_sectors(Sectors::equal(sectors))
+    }
+
+    /// splits the lap into a configured or detected layout, e.g. one from
+    /// `Sectors::from_corners`, rather than equal sectors.
+    ///
+    /// * `sectors`: the lap's sectors.
+    pub fn with_sectors(sectors: Sectors) -> Self {
+        Self {
+            player: 0,
+            summary: BestsSummary {
+                fastest_lap: None,
+                sectors: vec![None; sectors.len()],
+            },
+            sectors,
+            splitter: SectorSplitter::default(),
+        }
+    }
+
+    /// credits sectors timed from CarInfo to a car other than 0.
+    ///
+    /// * `car_id`: the player's `car_id_num`.
+    pub fn player(mut self, car_id: i32) -> Self {
+        self.player = car_id;
+        self
+    }
+
+    /// records a completed lap, returning an event if it's the new fastest.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    /// * `time`: its lap time.
+    pub fn record_lap(&mut self, car_id: i32, time: Duration) -> Option<BestEvent> {
+        let best = Best { car_id, time };
+        let previous = beat(&mut self.summary.fastest_lap, best)?;
+
+        Some(BestEvent::FastestLap { best, previous })
+    }
+
+    /// records a sector time, returning an event if it's purple.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    /// * `sector`: the sector, counting from zero.
+    /// * `time`: its time through the sector.
+    pub fn record_sector(
+        &mut self,
+        car_id: i32,
+        sector: usize,
+        time: Duration,
+    ) -> Option<BestEvent> {
+        let best = Best { car_id, time };
+        let previous = beat(self.summary.sectors.get_mut(sector)?, best)?;
+
+        Some(BestEvent::PurpleSector {
+            sector,
+            best,
+            previous,
+        })
+    }
+
+    /// records a LapInfo's lap time.
+    ///
+    /// * `info`: the LapInfo.
+    pub fn lap_info(&mut self, info: &LapInfo) -> Option<BestEvent> {
+        let time = u64::try_from(info.time).ok().filter(|&ms| ms > 0)?;
+        self.record_lap(info.car_id_num, Duration::from_millis(time))
+    }
+
+    /// times the player's sectors from CarInfo.
+    ///
+    /// * `car`: the latest CarInfo.
+    pub fn car_info(&mut self, car: &CarInfo) -> Option<BestEvent> {
+        let (sector, time) = self.splitter.observe(&self.sectors, car)?;
+        self.record_sector(self.player, sector, time)
+    }
+
+    pub fn fastest_lap(&self) -> Option<Best> {
+        self.summary.fastest_lap
+    }
+
+    /// the best time for a sector, counting from zero.
+    pub fn sector(&self, sector: usize) -> Option<Best> {
+        self.summary.sectors.get(sector).copied().flatten()
+    }
+
+    /// every best and its holder.
+    pub fn summary(&self) -> &BestsSummary {
+        &self.summary
+    }
+}
+
+impl Stage for SessionBests {
+    fn name(&self) -> &str {
+        "bests"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        let best = match event {
+            Event::LapInfo(info) => self.lap_info(info),
+            Event::CarInfo(car) => self.car_info(car),
+            _ => None,
+        };
+        outputs.extend(best.map(Output::Best));
+
+        true
+    }
+
+    fn reset(&mut self) {
+        let sectors = self.sectors.clone();
+        *self = Self::with_sectors(sectors).player(self.player);
+    }
+}
+
+/// replaces a best if the new time is faster. Returns the old best if it was beaten.
+///
+/// * `slot`: the current best.
+/// * `best`: the new time.
+fn beat(slot: &mut Option<Best>, best: Best) -> Option<Option<Best>> {
+    if slot.is_some_and(|current| current.time <= best.time) {
+        return None;
+    }
+
+    Some(slot.replace(best))
+}
+
+#[cfg(test)]
+mod bests_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, LapInfo};
+
+    use crate::bests::{Best, BestEvent, SessionBests};
+
+    fn lap(car_id_num: i32, time: i32) -> LapInfo {
+        LapInfo {
+            car_id_num,
+            time,
+            ..LapInfo::default()
+        }
+    }
+
+    fn best(car_id: i32, ms: u64) -> Best {
+        Best {
+            car_id,
+            time: Duration::from_millis(ms),
+        }
+    }
+
+    fn car(lap_count: u32, pos: f32, lap_time: u32, last_lap: u32) -> CarInfo {
+        CarInfo {
+            lap_count,
+            car_pos_normalized: pos,
+            lap_time,
+            last_lap,
+            ..CarInfo::default()
+        }
+    }
+
+    #[test]
+    fn a_new_session_has_no_bests() {
+        let mut bests = SessionBests::default();
+        assert_eq!(bests.fastest_lap(), None);
+        assert_eq!(bests.summary().sectors, [None; 3]);
+        assert_eq!(bests.sector(3), None);
+        assert_eq!(
+            bests.record_sector(0, 3, Duration::from_secs(1)),
+            None,
+            "no sector 3"
+        );
+        assert_eq!(bests.lap_info(&lap(1, -1)), None, "negative is no time");
+    }
+
+    #[test]
+    fn a_tied_lap_leaves_the_holder_alone() {
+        let mut bests = SessionBests::default();
+        bests.record_lap(1, Duration::from_secs(90));
+        assert_eq!(bests.record_lap(2, Duration::from_secs(90)), None);
+        assert_eq!(bests.fastest_lap(), Some(best(1, 90_000)));
+    }
+
+    #[test]
+    fn a_restart_mid_lap_isnt_timed_as_a_sector() {
+        let mut bests = SessionBests::new(2);
+        bests.car_info(&car(3, 0.0, 0, 80_000));
+        bests.car_info(&car(3, 0.6, 50_000, 80_000));
+
+        assert_eq!(
+            bests.car_info(&car(0, 0.7, 0, 80_000)),
+            None,
+            "lap counter went back"
+        );
+        assert_eq!(
+            bests.car_info(&car(1, 0.0, 0, 30_000)),
+            None,
+            "sector 1's start unseen"
+        );
+        assert_eq!(bests.summary().sectors, [None, None]);
+    }
+
+    #[test]
+    fn nan_positions_and_jumps_are_passed_over() {
+        let mut bests = SessionBests::new(3);
+        bests.car_info(&car(0, 0.9, 70_000, 0));
+        bests.car_info(&car(1, 0.0, 0, 0));
+        assert_eq!(bests.car_info(&car(1, f32::NAN, 10_000, 0)), None);
+
+        let time = |event: Option<BestEvent>| match event {
+            Some(BestEvent::PurpleSector { sector, best, .. }) => Some((sector, best.time)),
+            _ => None,
+        };
+        assert_eq!(
+            time(bests.car_info(&car(1, 0.4, 30_000, 0))),
+            Some((0, Duration::from_secs(30)))
+        );
+        // Back into the first sector, as when the car is reset to the pits.
+        assert_eq!(bests.car_info(&car(1, 0.1, 35_000, 0)), None);
+        assert_eq!(
+            bests.car_info(&car(1, 0.5, 45_000, 0)),
+            None,
+            "its start unseen"
+        );
+    }
+
+    #[test]
+    fn laps_from_any_car_set_the_fastest_lap() {
+        let mut bests = SessionBests::default();
+
+        assert_eq!(
+            bests.lap_info(&lap(3, 91_000)),
+            Some(BestEvent::FastestLap {
+                best: best(3, 91_000),
+                previous: None
+            })
+        );
+        assert_eq!(bests.lap_info(&lap(5, 92_000)), None);
+        assert_eq!(bests.lap_info(&lap(5, 0)), None, "no time yet");
+        assert_eq!(
+            bests.lap_info(&lap(5, 90_500)),
+            Some(BestEvent::FastestLap {
+                best: best(5, 90_500),
+                previous: Some(best(3, 91_000))
+            })
+        );
+        assert_eq!(bests.summary().fastest_lap, Some(best(5, 90_500)));
+    }
+
+    #[test]
+    fn player_car_info_is_split_into_sectors() {
+        let mut bests = SessionBests::new(2).player(7);
+
+        // joined part way through sector 1, so it isn't timed.
+        assert_eq!(bests.car_info(&car(0, 0.7, 0, 0)), None);
+        assert_eq!(bests.car_info(&car(1, 0.0, 0, 80_000)), None);
+        assert_eq!(bests.car_info(&car(1, 0.3, 20_000, 80_000)), None);
+
+        let purple = bests.car_info(&car(1, 0.5, 41_000, 80_000));
+        assert!(matches!(
+            purple,
+            Some(BestEvent::PurpleSector { sector: 0, best, previous: None })
+                if best.car_id == 7 && best.time == Duration::from_secs(41)
+        ));
+
+        let purple = bests.car_info(&car(2, 0.0, 0, 84_000));
+        assert!(matches!(
+            purple,
+            Some(BestEvent::PurpleSector { sector: 1, best, .. })
+                if best.time == Duration::from_secs(43)
+        ));
+        assert_eq!(bests.summary().sectors.len(), 2);
+    }
+}
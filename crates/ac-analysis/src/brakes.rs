@@ -0,0 +1,303 @@
+//! How hard each corner works the brakes, for brake duct and brake marker
+//! decisions. The telemetry has no car mass or brake temperatures to work
+//! from, so energy is given per kilogram: the kinetic energy shed between the
+//! brakes coming on and coming off. That's comparable between corners and
+//! laps of the same car, which is what duct sizing needs.
+
+use std::time::Duration;
+
+use crate::{
+    corners::{Corner, CornerRules},
+    trace::{Sample, Trace},
+};
+
+/// Brake input above which the car counts as braking, 0 to 1.
+pub const BRAKE_ON: f32 = 0.1;
+
+/// One stop on the brakes.
+///
+/// * `corner`: the number of the corner it leads into, if one follows in the lap.
+/// * `from`, `to`: where on track the brakes came on and off, 0 to 1; `to`
+///   is short of `from` for a zone across the line.
+/// * `duration`: how long the brakes were on.
+/// * `entry_kmh`, `exit_kmh`: the speed as the brakes came on and off.
+/// * `peak_g`: the most longitudinal G pulled while braking.
+/// * `energy`: the kinetic energy shed per kilogram of car, in J/kg.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BrakeZone {
+    pub corner: Option<usize>,
+    pub from: f32,
+    pub to: f32,
+    pub duration: Duration,
+    pub entry_kmh: f32,
+    pub exit_kmh: f32,
+    pub peak_g: f32,
+    pub energy: f32,
+}
+
+/// The braking of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `zones`: each stop on the brakes, in the order they happened.
+/// * `energy`: the energy of every zone together, in J/kg.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrakeReport {
+    pub lap: u32,
+    pub zones: Vec<BrakeZone>,
+    pub energy: f32,
+}
+
+impl BrakeReport {
+    /// every braking zone of a lap and the energy shed in it. `None` for an
+    /// empty lap. A brake reading that isn't a number leaves the brakes as
+    /// they were, and a speed that isn't one sheds no energy.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner, for saying which corner each zone leads into.
+    pub fn of(lap: &Trace, rules: &CornerRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+        let corners = Corner::find(lap, rules);
+
+        let mut zones = Vec::new();
+        let mut start = None;
+        for i in 0..=samples.len() {
+            let braking = match samples.get(i) {
+                Some(s) if s.car.brake.is_nan() => start.is_some(),
+                Some(s) => s.car.brake > BRAKE_ON,
+                None => false,
+            };
+            match (braking, start) {
+                (true, None) => start = Some(i),
+                (false, Some(from)) => {
+                    start = None;
+                    // The zone runs until the sample the brakes were seen off, if there is one.
+                    let end = i.min(samples.len() - 1);
+                    zones.push(zone(&samples[from..=end], &corners, from));
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            lap: first.car.lap_count,
+            energy: zones.iter().map(|zone| zone.energy).sum(),
+            zones,
+        })
+    }
+
+    /// the zones leading into a corner.
+    ///
+    /// * `corner`: the corner's number.
+    pub fn into_corner(&self, corner: usize) -> impl Iterator<Item = &BrakeZone> {
+        self.zones
+            .iter()
+            .filter(move |zone| zone.corner == Some(corner))
+    }
+
+    /// a zone's share of the lap's braking energy, 0 to 1.
+    pub fn share(&self, zone: &BrakeZone) -> f32 {
+        if self.energy > 0.0 {
+            zone.energy / self.energy
+        } else {
+            0.0
+        }
+    }
+}
+
+/// measures one braking zone.
+///
+/// * `samples`: from the brakes coming on to them coming off.
+/// * `corners`: the lap's corners.
+/// * `index`: where the zone starts among the lap's samples.
+fn zone(samples: &[Sample], corners: &[Corner], index: usize) -> BrakeZone {
+    let (first, last) = (&samples[0], &samples[samples.len() - 1]);
+    let (v0, v1) = (first.car.speed_ms, last.car.speed_ms);
+
+    BrakeZone {
+        // The corner being braked for is the first one that hasn't ended yet.
+        corner: corners
+            .iter()
+            .find(|corner| corner.samples.end > index)
+            .map(|corner| corner.number),
+        from: first.car.car_pos_normalized,
+        to: last.car.car_pos_normalized,
+        duration: last.time.saturating_sub(first.time),
+        entry_kmh: first.car.speed_kmh,
+        exit_kmh: last.car.speed_kmh,
+        peak_g: samples
+            .iter()
+            .map(|s| s.car.accg_frontal.abs())
+            .fold(0.0, f32::max),
+        energy: (0.5 * (v0 * v0 - v1 * v1)).max(0.0),
+    }
+}
+
+#[cfg(test)]
+mod brakes_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        brakes::{BRAKE_ON, BrakeReport},
+        corners::CornerRules,
+        trace::Trace,
+    };
+
+    /// a car at `speed_ms` on `brake`, pulling the given lateral and longitudinal G.
+    fn car((speed_ms, brake, accg_horizontal, accg_frontal): (f32, f32, f32, f32)) -> CarInfo {
+        CarInfo {
+            lap_count: 2,
+            speed_ms,
+            speed_kmh: speed_ms * 3.6,
+            brake,
+            accg_horizontal,
+            accg_frontal,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a lap sampled every 250 ms.
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(250 * i as u64), car);
+        }
+        lap
+    }
+
+    fn report(samples: impl IntoIterator<Item = CarInfo>) -> BrakeReport {
+        BrakeReport::of(&lap(samples), &CornerRules::default()).expect("not empty")
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_report() {
+        assert_eq!(
+            BrakeReport::of(&Trace::new(), &CornerRules::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn a_lap_off_the_brakes_has_no_zones() {
+        let report = report([(50.0, 0.0, 0.0, 0.0), (50.0, BRAKE_ON, 0.0, 0.0)].map(car));
+        assert_eq!((report.lap, report.zones.len(), report.energy), (2, 0, 0.0));
+    }
+
+    #[test]
+    fn a_single_sample_on_the_brakes_is_a_zone_without_length() {
+        let report = report([car((50.0, 1.0, 0.0, -1.0))]);
+        let [zone] = &report.zones[..] else {
+            panic!("expected one zone, got {:?}", report.zones);
+        };
+        assert_eq!(
+            (zone.duration, zone.energy, zone.peak_g),
+            (Duration::ZERO, 0.0, 1.0)
+        );
+        assert_eq!(report.share(zone), 0.0);
+    }
+
+    #[test]
+    fn braking_energy_is_credited_to_the_next_corner() {
+        // A big stop from 60 to 20 m/s into the hairpin, then a dab from 40
+        // to 30 m/s with no corner after it.
+        let report = report(
+            [
+                (60.0, 0.0, 0.0, 0.0),
+                (60.0, 1.0, 0.0, -2.5),
+                (40.0, 0.8, 0.0, -2.0),
+                (20.0, 0.0, 1.5, 0.0),
+                (20.0, 0.0, 1.5, 0.0),
+                (20.0, 0.0, 1.5, 0.0),
+                (40.0, 0.0, 0.0, 0.0),
+                (40.0, 0.5, 0.0, -1.0),
+                (30.0, 0.0, 0.0, 0.0),
+            ]
+            .map(car),
+        );
+
+        let [hairpin, dab] = &report.zones[..] else {
+            panic!("expected two zones, got {:?}", report.zones);
+        };
+        assert_eq!(hairpin.corner, Some(1));
+        assert_eq!(
+            (hairpin.entry_kmh, hairpin.exit_kmh),
+            (60.0 * 3.6, 20.0 * 3.6)
+        );
+        assert_eq!((hairpin.energy, hairpin.peak_g), (1600.0, 2.5));
+        assert_eq!(hairpin.duration, Duration::from_millis(500));
+
+        assert_eq!((dab.corner, dab.energy), (None, 350.0));
+        assert_eq!(report.into_corner(1).count(), 1);
+        assert_eq!(report.share(hairpin), 1600.0 / 1950.0);
+    }
+
+    #[test]
+    fn a_lap_ending_on_the_brakes_runs_to_the_last_sample() {
+        let report = report(
+            [
+                (40.0, 0.0, 0.0, 0.0),
+                (40.0, 1.0, 0.0, -1.0),
+                (30.0, 1.0, 0.0, -1.0),
+            ]
+            .map(car),
+        );
+        let [zone] = &report.zones[..] else {
+            panic!("expected one zone, got {:?}", report.zones);
+        };
+        assert_eq!(
+            (zone.duration, zone.energy),
+            (Duration::from_millis(250), 350.0)
+        );
+    }
+
+    #[test]
+    fn standing_on_the_brakes_sheds_nothing() {
+        let report = report([(0.0, 1.0, 0.0, 0.0); 4].map(car));
+        assert_eq!(report.zones.len(), 1);
+        assert_eq!((report.energy, report.share(&report.zones[0])), (0.0, 0.0));
+    }
+
+    #[test]
+    fn nan_readings_neither_split_zones_nor_poison_the_energy() {
+        let report = report(
+            [
+                (40.0, 1.0, 0.0, -1.0),
+                (35.0, f32::NAN, 0.0, f32::NAN),
+                (30.0, 1.0, 0.0, -1.5),
+                (30.0, 0.0, 0.0, 0.0),
+                (f32::NAN, 1.0, 0.0, -1.0),
+                (20.0, 0.0, 0.0, 0.0),
+            ]
+            .map(car),
+        );
+        let [whole, unknown] = &report.zones[..] else {
+            panic!("expected two zones, got {:?}", report.zones);
+        };
+        assert_eq!((whole.energy, whole.peak_g), (350.0, 1.5));
+        assert_eq!(whole.duration, Duration::from_millis(750));
+        assert_eq!(unknown.energy, 0.0);
+        assert_eq!(report.energy, 350.0);
+    }
+
+    #[test]
+    fn a_zone_across_the_line_ends_short_of_where_it_starts() {
+        let mut samples = [
+            (40.0, 1.0, 0.0, -1.0),
+            (30.0, 1.0, 0.0, -1.0),
+            (20.0, 0.0, 0.0, 0.0),
+        ]
+        .map(car);
+        for (car, position) in samples.iter_mut().zip([0.98, 0.99, 0.01]) {
+            car.car_pos_normalized = position;
+        }
+
+        let report = report(samples);
+        let [zone] = &report.zones[..] else {
+            panic!("expected one zone, got {:?}", report.zones);
+        };
+        assert_eq!((zone.from, zone.to), (0.98, 0.01));
+        assert_eq!(zone.energy, 600.0);
+    }
+}
@@ -0,0 +1,257 @@
+//! How much camber each wheel actually runs, for choosing static camber from
+//! UDP data alone. Through a corner the outside wheel carries the load and
+//! the body roll eats into its negative camber; what matters is where it ends
+//! up then, compared with where it sits on the straights. A wheel counts as
+//! outside while it carries more load than the other wheel on its axle.
+
+use ac_parser::{CarInfo, Wheel};
+
+use crate::{
+    columns::ColumnStats,
+    corners::{Corner, CornerRules},
+    trace::Trace,
+};
+
+/// Lateral G below which the car counts as running straight.
+const STRAIGHT_G: f32 = 0.2;
+
+/// One wheel's dynamic camber over a lap, in degrees.
+///
+/// * `straight`: on the straights.
+/// * `loaded`: in corners, as the outside wheel.
+/// * `unloaded`: in corners, as the inside wheel.
+/// * `gain_per_g`: how much the camber moves per G of lateral acceleration as
+///   the outside wheel; positive as roll pushes it towards positive camber.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WheelCamber {
+    pub straight: Option<ColumnStats>,
+    pub loaded: Option<ColumnStats>,
+    pub unloaded: Option<ColumnStats>,
+    pub gain_per_g: Option<f32>,
+}
+
+/// The dynamic camber of each wheel over one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `wheels`: each wheel's camber, indexed by `Wheel`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CamberReport {
+    pub lap: u32,
+    pub wheels: [WheelCamber; 4],
+}
+
+impl CamberReport {
+    /// the camber of every wheel through a lap. `None` for an empty lap.
+    /// Camber readings that aren't numbers are skipped.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn of(lap: &Trace, rules: &CornerRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let mut in_corner = vec![false; samples.len()];
+        for corner in Corner::find(lap, rules) {
+            in_corner[corner.samples].fill(true);
+        }
+
+        let wheels = Wheel::ALL.map(|wheel| {
+            let mut straight = Vec::new();
+            let mut loaded = Vec::new();
+            let mut unloaded = Vec::new();
+
+            for (sample, &cornering) in samples.iter().zip(&in_corner) {
+                let car = &sample.car;
+                let camber = car.camber_rad[wheel as usize].to_degrees();
+                let g = car.accg_horizontal.abs();
+                if camber.is_nan() {
+                    continue;
+                }
+
+                if cornering && is_outside(car, wheel) {
+                    loaded.push((g, camber));
+                } else if cornering {
+                    unloaded.push((g, camber));
+                } else if g < STRAIGHT_G {
+                    straight.push((g, camber));
+                }
+            }
+
+            WheelCamber {
+                straight: stats(&straight),
+                loaded: stats(&loaded),
+                unloaded: stats(&unloaded),
+                gain_per_g: slope(&loaded),
+            }
+        });
+
+        Some(Self {
+            lap: first.car.lap_count,
+            wheels,
+        })
+    }
+
+    /// one wheel's camber.
+    ///
+    /// * `wheel`: the wheel.
+    pub fn wheel(&self, wheel: Wheel) -> &WheelCamber {
+        &self.wheels[wheel as usize]
+    }
+}
+
+/// whether a wheel carries more load than the other on its axle.
+fn is_outside(car: &CarInfo, wheel: Wheel) -> bool {
+    let partner = match wheel {
+        Wheel::FrontLeft => Wheel::FrontRight,
+        Wheel::FrontRight => Wheel::FrontLeft,
+        Wheel::RearLeft => Wheel::RearRight,
+        Wheel::RearRight => Wheel::RearLeft,
+    };
+
+    car.load[wheel as usize] > car.load[partner as usize]
+}
+
+/// min, max and mean camber of `(g, camber)` points.
+fn stats(points: &[(f32, f32)]) -> Option<ColumnStats> {
+    if points.is_empty() {
+        return None;
+    }
+
+    let (min, max, sum) = points.iter().fold(
+        (f32::INFINITY, f32::NEG_INFINITY, 0.0),
+        |(min, max, sum), &(_, camber)| (min.min(camber), max.max(camber), sum + camber),
+    );
+    Some(ColumnStats {
+        min,
+        max,
+        mean: sum / points.len() as f32,
+    })
+}
+
+/// the least-squares slope of camber against G, if the G varies.
+fn slope(points: &[(f32, f32)]) -> Option<f32> {
+    let n = points.len() as f32;
+    let mean_g = points.iter().map(|&(g, _)| g).sum::<f32>() / n;
+    let mean_camber = points.iter().map(|&(_, camber)| camber).sum::<f32>() / n;
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(cov, var), &(g, camber)| {
+        let dg = g - mean_g;
+        (cov + dg * (camber - mean_camber), var + dg * dg)
+    });
+
+    (points.len() >= 2 && variance > f32::EPSILON).then(|| covariance / variance)
+}
+
+#[cfg(test)]
+mod camber_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Wheel};
+
+    use crate::{camber::CamberReport, corners::CornerRules, trace::Trace};
+
+    /// -3 degrees static; in a right-hander the left wheels are loaded and
+    /// roll 1 degree towards positive per G, the right wheels go more negative.
+    fn car(g: f32) -> CarInfo {
+        let (outside, inside) = (-3.0 + g, -3.0 - 0.5 * g);
+        CarInfo {
+            lap_count: 2,
+            accg_horizontal: g,
+            load: [4000.0 + 1000.0 * g, 4000.0, 4000.0 + 1000.0 * g, 4000.0],
+            camber_rad: [outside, inside, outside, inside].map(f32::to_radians),
+            ..CarInfo::default()
+        }
+    }
+
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(200 * i as u64), car);
+        }
+        lap
+    }
+
+    fn near(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_report() {
+        assert_eq!(
+            CamberReport::of(&Trace::new(), &CornerRules::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn a_single_straight_sample_has_no_corner_camber() {
+        let report = CamberReport::of(&lap([car(0.0)]), &CornerRules::default()).expect("one");
+        let left = report.wheel(Wheel::FrontLeft);
+
+        let straight = left.straight.expect("on the straight");
+        assert!(near(straight.min, -3.0) && near(straight.max, -3.0));
+        assert_eq!(
+            (left.loaded, left.unloaded, left.gain_per_g),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn constant_g_has_no_gain() {
+        let report =
+            CamberReport::of(&lap([1.0; 4].map(car)), &CornerRules::default()).expect("four");
+        let left = report.wheel(Wheel::FrontLeft);
+
+        assert!(near(left.loaded.expect("cornering").mean, -2.0));
+        assert_eq!(left.gain_per_g, None, "one G can't give a slope");
+        assert_eq!(left.straight, None);
+    }
+
+    #[test]
+    fn evenly_loaded_wheels_are_both_inside() {
+        let even = CarInfo {
+            load: [4000.0; 4],
+            ..car(1.0)
+        };
+        let report = CamberReport::of(&lap(vec![even; 4]), &CornerRules::default()).expect("four");
+
+        for wheel in Wheel::ALL {
+            assert_eq!(report.wheel(wheel).loaded, None);
+            assert!(report.wheel(wheel).unloaded.is_some());
+        }
+    }
+
+    #[test]
+    fn nan_camber_is_skipped() {
+        let mut broken = car(0.0);
+        broken.camber_rad[Wheel::FrontLeft as usize] = f32::NAN;
+        let report = CamberReport::of(&lap([car(0.0), broken, car(0.1)]), &CornerRules::default())
+            .expect("three");
+
+        let straight = report.wheel(Wheel::FrontLeft).straight.expect("two clean");
+        assert!(near(straight.mean, -2.95), "{straight:?}");
+        assert!(report.wheel(Wheel::FrontRight).straight.is_some());
+    }
+
+    #[test]
+    fn outside_wheels_report_their_loaded_camber() {
+        let lap = lap([0.0, 0.1, 1.0, 1.5, 2.0, 1.5, 1.0, 0.0].map(car));
+
+        let report = CamberReport::of(&lap, &CornerRules::default()).expect("not empty");
+        let left = report.wheel(Wheel::FrontLeft);
+        let loaded = left.loaded.expect("loaded in the corner");
+        assert!(
+            near(loaded.min, -2.0) && near(loaded.max, -1.0),
+            "{loaded:?}"
+        );
+        assert!(near(left.gain_per_g.expect("G varies"), 1.0));
+        assert!(near(
+            left.straight.expect("three straight samples").mean,
+            -8.9 / 3.0
+        ));
+        assert_eq!(left.unloaded, None);
+
+        let right = report.wheel(Wheel::RearRight);
+        assert!(near(right.unloaded.expect("inside").min, -4.0));
+        assert_eq!(right.loaded, None);
+    }
+}
@@ -0,0 +1,210 @@
+//! Per-car state for SubscribeSpot sessions, where LapInfo arrives for every car
+//! on track. Cars that stop reporting are first marked stale, then removed.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use ac_parser::{Event, LapInfo};
+
+use crate::pipeline::{Output, Stage};
+
+/// How long a car can go without a LapInfo before it's stale, by default.
+/// Spot updates only arrive once a lap, so this allows for a long lap.
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// How long a car can go without a LapInfo before it's removed, by default.
+const DEFAULT_REMOVE_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// The latest known state of one car.
+///
+/// * `info`: its most recent LapInfo.
+/// * `first_seen`: when its first LapInfo arrived.
+/// * `last_seen`: when its most recent LapInfo arrived.
+/// * `updates`: how many LapInfo have arrived for it.
+/// * `stale`: whether it has gone quiet for longer than the registry allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarState {
+    pub info: LapInfo,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    pub updates: u32,
+    pub stale: bool,
+}
+
+/// A change to the registry, keyed by `car_id_num`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarChange {
+    /// The first LapInfo for a car.
+    Joined(i32),
+    /// A newer LapInfo for a car already known, including one that had gone stale.
+    Updated(i32),
+    /// A car has gone quiet for longer than `stale_after`.
+    Stale(i32),
+    /// A car has gone quiet for longer than `remove_after` and was dropped.
+    Removed(i32),
+}
+
+/// The latest state of every car, by `car_id_num`.
+///
+/// * `cars`: each car's state.
+/// * `stale_after`: how long a car can go quiet before it's marked stale.
+/// * `remove_after`: how long a car can go quiet before it's removed.
+#[derive(Debug, Clone)]
+pub struct CarRegistry {
+    cars: BTreeMap<i32, CarState>,
+    stale_after: Duration,
+    remove_after: Duration,
+}
+
+impl Default for CarRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_STALE_AFTER, DEFAULT_REMOVE_AFTER)
+    }
+}
+
+impl CarRegistry {
+    /// * `stale_after`: how long a car can go quiet before it's marked stale.
+    /// * `remove_after`: how long a car can go quiet before it's removed.
+    pub fn new(stale_after: Duration, remove_after: Duration) -> Self {
+        Self {
+            cars: BTreeMap::new(),
+            stale_after,
+            remove_after,
+        }
+    }
+
+    /// records a car's LapInfo.
+    ///
+    /// * `at`: when it arrived, on any monotonic timeline.
+    /// * `info`: the LapInfo.
+    pub fn update(&mut self, at: Duration, info: &LapInfo) -> CarChange {
+        let id = info.car_id_num;
+
+        match self.cars.get_mut(&id) {
+            Some(car) => {
+                car.info = info.clone();
+                car.last_seen = at;
+                car.updates += 1;
+                car.stale = false;
+                CarChange::Updated(id)
+            }
+            None => {
+                self.cars.insert(
+                    id,
+                    CarState {
+                        info: info.clone(),
+                        first_seen: at,
+                        last_seen: at,
+                        updates: 1,
+                        stale: false,
+                    },
+                );
+                CarChange::Joined(id)
+            }
+        }
+    }
+
+    /// marks cars stale, and removes them, once they've been quiet too long.
+    ///
+    /// * `now`: the current time, on the same timeline as `update`.
+    pub fn expire(&mut self, now: Duration) -> Vec<CarChange> {
+        let mut changes = Vec::new();
+
+        self.cars.retain(|&id, car| {
+            let quiet = now.saturating_sub(car.last_seen);
+
+            if quiet >= self.remove_after {
+                changes.push(CarChange::Removed(id));
+                return false;
+            }
+
+            if quiet >= self.stale_after && !car.stale {
+                car.stale = true;
+                changes.push(CarChange::Stale(id));
+            }
+
+            true
+        });
+
+        changes
+    }
+
+    pub fn get(&self, car_id: i32) -> Option<&CarState> {
+        self.cars.get(&car_id)
+    }
+
+    /// every car's state, ordered by id.
+    pub fn cars(&self) -> impl Iterator<Item = &CarState> {
+        self.cars.values()
+    }
+
+    /// a copy of every car's state, ordered by id.
+    pub fn snapshot(&self) -> Vec<CarState> {
+        self.cars.values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cars.is_empty()
+    }
+}
+
+impl Stage for CarRegistry {
+    fn name(&self) -> &str {
+        "cars"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            outputs.push(Output::Car(self.update(at, info)));
+        }
+
+        outputs.extend(self.expire(at).into_iter().map(Output::Car));
+        true
+    }
+}
+
+#[cfg(test)]
+mod cars_tests {
+    use std::time::Duration;
+
+    use ac_parser::LapInfo;
+
+    use crate::cars::{CarChange, CarRegistry};
+
+    fn lap(car_id_num: i32, lap: i32) -> LapInfo {
+        LapInfo {
+            car_id_num,
+            lap,
+            ..LapInfo::default()
+        }
+    }
+
+    #[test]
+    fn quiet_cars_go_stale_then_disappear() {
+        let secs = Duration::from_secs;
+        let mut registry = CarRegistry::new(secs(10), secs(30));
+
+        assert_eq!(registry.update(secs(0), &lap(1, 1)), CarChange::Joined(1));
+        assert_eq!(registry.update(secs(0), &lap(2, 1)), CarChange::Joined(2));
+        assert_eq!(registry.update(secs(5), &lap(1, 2)), CarChange::Updated(1));
+        assert_eq!(registry.get(1).map(|car| car.info.lap), Some(2));
+
+        assert_eq!(registry.expire(secs(12)), [CarChange::Stale(2)]);
+        assert!(
+            registry.expire(secs(13)).is_empty(),
+            "stale is reported once"
+        );
+        assert!(registry.get(2).is_some_and(|car| car.stale));
+
+        assert_eq!(registry.update(secs(14), &lap(2, 2)), CarChange::Updated(2));
+        assert!(registry.get(2).is_some_and(|car| !car.stale));
+
+        assert_eq!(
+            registry.expire(secs(40)),
+            [CarChange::Removed(1), CarChange::Stale(2)]
+        );
+        assert_eq!(registry.snapshot().len(), 1);
+    }
+}
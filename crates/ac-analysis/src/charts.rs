@@ -0,0 +1,331 @@
+//! PNG charts of a lap, for quick visual output from a headless tool: the
+//! speed, throttle and brake traces stacked over the lap, with the time
+//! delta to a reference lap under them, and the G-G diagram of lateral
+//! against longitudinal G.
+//!
+//! Charts are drawn with `plotters`' bitmap backend into an RGB buffer, with
+//! the system's sans-serif font for titles and axis labels, and written out
+//! as compressed PNG by `image`. Gridlines split the lap into tenths, the
+//! trace panels run from zero to their highest value, at least 1, and the
+//! delta panel is centred on zero.
+
+use std::io::{self, Write};
+
+use ac_parser::{CarInfo, Units};
+use image::{ColorType, ImageEncoder, codecs::png::PngEncoder};
+use plotters::{coord::Shift, prelude::*};
+
+use crate::{delta_map::time_at, trace::Trace};
+
+/// Reads one charted channel off a packet.
+type ChannelFn = fn(&CarInfo) -> f32;
+
+/// The colour of the lap being charted, and of the reference lap behind it.
+const LAP: RGBColor = RGBColor(214, 39, 40);
+const REFERENCE: RGBColor = RGBColor(170, 170, 170);
+
+/// The font of titles and axis labels, and their sizes in pixels.
+const FONT: &str = "sans-serif";
+const TITLE_SIZE: u32 = 14;
+const LABEL_SIZE: u32 = 11;
+
+/// A chart drawn as RGB pixels, ready to write out as PNG.
+///
+/// * `width`, `height`: its size in pixels.
+/// * `pixels`: three bytes per pixel, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LapChart {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl LapChart {
+    /// a blank white chart.
+    ///
+    /// * `width`, `height`: its size in pixels.
+    fn blank(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![255; width as usize * height as usize * 3],
+        }
+    }
+
+    /// a lap's speed, throttle and brake traces stacked over the lap, and the
+    /// time delta to a reference lap under them if one is given. The
+    /// reference's traces are drawn in grey behind the lap's.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `reference`: the lap to compare against, e.g. the session best.
+    /// * `units`: what the speed trace is in, so its gridlines fall on round
+    ///   km/h or mph and its title names them.
+    /// * `width`, `height`: the chart's size in pixels.
+    pub fn traces(
+        lap: &Trace,
+        reference: Option<&Trace>,
+        units: Units,
+        width: u32,
+        height: u32,
+    ) -> io::Result<Self> {
+        let mut chart = Self::blank(width, height);
+        let speed_title = format!("Speed ({})", units.speed_unit());
+        let channels: [(&str, ChannelFn, f32); 3] = [
+            (&speed_title, |car| car.speed_kmh, units.speed(1.0)),
+            ("Throttle", |car| car.gas, 1.0),
+            ("Brake", |car| car.brake, 1.0),
+        ];
+
+        {
+            let root = chart.backend().into_drawing_area();
+            let rows = if reference.is_some() { 4 } else { 3 };
+            let panels = root.split_evenly((rows, 1));
+
+            for (&(title, channel, scale), panel) in channels.iter().zip(&panels) {
+                let ours = points(lap, channel, scale);
+                let theirs = reference
+                    .map(|reference| points(reference, channel, scale))
+                    .unwrap_or_default();
+                let top = ours
+                    .iter()
+                    .chain(&theirs)
+                    .map(|&(_, value)| value)
+                    .fold(1.0, f32::max);
+                panel_chart(
+                    panel,
+                    title,
+                    0.0..top,
+                    &[(&theirs, REFERENCE), (&ours, LAP)],
+                )?;
+            }
+
+            if let (Some(reference), Some(panel)) = (reference, panels.get(3)) {
+                let delta: Vec<(f32, f32)> = lap
+                    .samples()
+                    .iter()
+                    .map(|s| {
+                        let ahead = time_at(lap, s.distance).as_secs_f32()
+                            - time_at(reference, s.distance).as_secs_f32();
+                        (s.distance, ahead)
+                    })
+                    .collect();
+                let spread = delta
+                    .iter()
+                    .map(|&(_, seconds)| seconds.abs())
+                    .fold(0.1, f32::max);
+                panel_chart(panel, "Delta (s)", -spread..spread, &[(&delta, LAP)])?;
+            }
+
+            root.present().map_err(drawing_error)?;
+        }
+
+        Ok(chart)
+    }
+
+    /// a lap's G-G diagram: lateral G across, longitudinal G up, one dot per
+    /// sample, on equal axes centred on zero.
+    ///
+    /// * `lap`: the lap.
+    /// * `size`: the chart's width and height in pixels.
+    pub fn gg_diagram(lap: &Trace, size: u32) -> io::Result<Self> {
+        let mut chart = Self::blank(size, size);
+        let points: Vec<(f32, f32)> = lap
+            .samples()
+            .iter()
+            .map(|s| (s.car.accg_horizontal, s.car.accg_frontal))
+            .collect();
+        let reach = points
+            .iter()
+            .map(|&(x, y)| x.abs().max(y.abs()))
+            .fold(1.0, f32::max)
+            * 1.1;
+
+        {
+            let root = chart.backend().into_drawing_area();
+            let mut gg = ChartBuilder::on(&root)
+                .margin(10)
+                .caption("G-G diagram", (FONT, TITLE_SIZE))
+                .x_label_area_size(32)
+                .y_label_area_size(40)
+                .build_cartesian_2d(-reach..reach, -reach..reach)
+                .map_err(drawing_error)?;
+            gg.configure_mesh()
+                .x_labels(10)
+                .y_labels(4)
+                .x_desc("lateral G")
+                .y_desc("longitudinal G")
+                .label_style((FONT, LABEL_SIZE))
+                .draw()
+                .map_err(drawing_error)?;
+            gg.draw_series(
+                points
+                    .iter()
+                    .map(|&point| Circle::new(point, 2, LAP.mix(0.5).filled())),
+            )
+            .map_err(drawing_error)?;
+
+            root.present().map_err(drawing_error)?;
+        }
+
+        Ok(chart)
+    }
+
+    /// a `plotters` backend drawing into the chart's pixels.
+    fn backend(&mut self) -> BitMapBackend<'_> {
+        BitMapBackend::with_buffer(&mut self.pixels, (self.width, self.height))
+    }
+
+    /// the chart's width and height in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// one pixel's colour as RGB, if it's on the chart.
+    ///
+    /// * `x`, `y`: from the top left.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 3]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        Some([self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]])
+    }
+
+    /// writes the chart as a compressed PNG image.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_png<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        PngEncoder::new(&mut writer)
+            .write_image(&self.pixels, self.width, self.height, ColorType::Rgb8)
+            .map_err(io::Error::other)?;
+        writer.flush()
+    }
+}
+
+/// draws one panel of stacked traces, over the whole lap.
+///
+/// * `panel`: where on the chart it goes.
+/// * `title`: what's charted, above the panel.
+/// * `range`: the values it spans.
+/// * `series`: each trace and its colour, drawn in order.
+fn panel_chart(
+    panel: &DrawingArea<BitMapBackend<'_>, Shift>,
+    title: &str,
+    range: std::ops::Range<f32>,
+    series: &[(&Vec<(f32, f32)>, RGBColor)],
+) -> io::Result<()> {
+    let mut chart = ChartBuilder::on(panel)
+        .margin(6)
+        .caption(title, (FONT, TITLE_SIZE))
+        .x_label_area_size(18)
+        .y_label_area_size(40)
+        .build_cartesian_2d(0f32..1f32, range)
+        .map_err(drawing_error)?;
+    chart
+        .configure_mesh()
+        .x_labels(10)
+        .y_labels(4)
+        .x_label_formatter(&|position| format!("{:.0}%", position * 100.0))
+        .label_style((FONT, LABEL_SIZE))
+        .draw()
+        .map_err(drawing_error)?;
+
+    for (points, colour) in series {
+        chart
+            .draw_series(LineSeries::new(points.iter().copied(), colour))
+            .map_err(drawing_error)?;
+    }
+
+    Ok(())
+}
+
+/// one channel over a lap, against track position.
+///
+/// * `scale`: what each value is multiplied by, e.g. to convert units.
+fn points(lap: &Trace, value: ChannelFn, scale: f32) -> Vec<(f32, f32)> {
+    lap.samples()
+        .iter()
+        .map(|s| (s.distance, value(&s.car) * scale))
+        .collect()
+}
+
+fn drawing_error(error: impl std::error::Error) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+#[cfg(test)]
+mod charts_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Units};
+
+    use crate::{charts::LapChart, trace::Trace};
+
+    fn lap(seconds: f32) -> Trace {
+        let mut lap = Trace::new();
+        for i in 0..=20 {
+            let position = i as f32 / 20.0;
+            let braking = (8..12).contains(&i);
+            let car = CarInfo {
+                car_pos_normalized: position.min(0.999),
+                speed_kmh: if braking { 90.0 } else { 200.0 },
+                gas: if braking { 0.0 } else { 1.0 },
+                brake: if braking { 1.0 } else { 0.0 },
+                accg_frontal: if braking { -2.0 } else { 0.5 },
+                accg_horizontal: if braking { 1.0 } else { 0.0 },
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_secs_f32(seconds * position), car);
+        }
+
+        lap
+    }
+
+    #[test]
+    fn charts_are_drawn_titled_and_written_as_compressed_png() {
+        let chart = LapChart::traces(&lap(90.0), Some(&lap(91.0)), Units::Imperial, 320, 240)
+            .expect("drawn");
+        assert_eq!(chart.size(), (320, 240));
+        assert_eq!(chart.pixel(0, 0), Some([255, 255, 255]));
+        assert_eq!(chart.pixel(320, 0), None);
+        let red = (0..320)
+            .flat_map(|x| (0..240).map(move |y| (x, y)))
+            .filter(|&(x, y)| chart.pixel(x, y) == Some([214, 39, 40]))
+            .count();
+        assert!(red > 100, "only {red} pixels of the lap's colour");
+
+        // The speed panel's title is drawn above its plot, where nothing
+        // else is.
+        let title = (0..320)
+            .flat_map(|x| (0..20).map(move |y| (x, y)))
+            .filter(|&(x, y)| {
+                chart
+                    .pixel(x, y)
+                    .is_some_and(|[r, g, b]| r.max(g).max(b) < 128)
+            })
+            .count();
+        assert!(title > 20, "only {title} pixels of title text");
+
+        let mut png = Vec::new();
+        chart.write_png(&mut png).expect("written");
+        assert!(
+            png.len() < 320 * 240 * 3 / 4,
+            "{} bytes uncompressed",
+            png.len()
+        );
+        let decoded = image::load_from_memory(&png).expect("decodes").to_rgb8();
+        assert_eq!(decoded.dimensions(), (320, 240));
+        assert_eq!(
+            decoded.get_pixel(160, 120).0,
+            chart.pixel(160, 120).expect("on the chart")
+        );
+
+        let gg = LapChart::gg_diagram(&lap(90.0), 200).expect("drawn");
+        assert_eq!(gg.size(), (200, 200));
+        assert!((0..200).any(|x| (0..200).any(|y| gg.pixel(x, y) != Some([255, 255, 255]))));
+    }
+}
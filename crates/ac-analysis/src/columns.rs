@@ -0,0 +1,154 @@
+use ac_parser::Channel;
+
+use crate::trace::{Sample, Trace};
+
+/// Min, max and mean of a channel over a store.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ColumnStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+/// A structure-of-arrays copy of a trace: one contiguous `Vec<f32>` per channel over
+/// a shared time and distance axis, so analysis passes over long sessions walk
+/// memory linearly instead of hopping between whole packets.
+///
+/// * `time`: seconds since the start of the trace, per sample.
+/// * `distance`: laps since the start of the trace plus track position, per sample.
+/// * `columns`: the stored channels, each with one value per sample.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnStore {
+    time: Vec<f64>,
+    distance: Vec<f32>,
+    columns: Vec<(Channel, Vec<f32>)>,
+}
+
+impl ColumnStore {
+    /// creates an empty store holding the given channels.
+    ///
+    /// * `channels`: which channels to keep a column for.
+    pub fn new(channels: &[Channel]) -> Self {
+        Self {
+            time: Vec::new(),
+            distance: Vec::new(),
+            columns: channels.iter().map(|&c| (c, Vec::new())).collect(),
+        }
+    }
+
+    /// copies the given channels out of a trace.
+    ///
+    /// * `trace`: the trace to copy.
+    /// * `channels`: which channels to keep a column for.
+    pub fn from_trace(trace: &Trace, channels: &[Channel]) -> Self {
+        let mut store = Self::new(channels);
+        store.reserve(trace.samples().len());
+        trace.samples().iter().for_each(|s| store.push(s));
+        store
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.time.reserve(additional);
+        self.distance.reserve(additional);
+        self.columns
+            .iter_mut()
+            .for_each(|(_, values)| values.reserve(additional));
+    }
+
+    /// appends one sample to every column.
+    ///
+    /// * `sample`: the sample to append.
+    pub fn push(&mut self, sample: &Sample) {
+        self.time.push(sample.time.as_secs_f64());
+        self.distance.push(sample.distance);
+
+        for (channel, values) in &mut self.columns {
+            values.push(channel.value(&sample.car));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    pub fn time(&self) -> &[f64] {
+        &self.time
+    }
+
+    pub fn distance(&self) -> &[f32] {
+        &self.distance
+    }
+
+    /// a channel's column, if the store holds it.
+    ///
+    /// * `channel`: the channel to look up.
+    pub fn column(&self, channel: Channel) -> Option<&[f32]> {
+        self.columns
+            .iter()
+            .find(|(c, _)| *c == channel)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// min, max and mean of a channel, if the store holds it and isn't empty.
+    ///
+    /// * `channel`: the channel to summarize.
+    pub fn stats(&self, channel: Channel) -> Option<ColumnStats> {
+        let values = self.column(channel)?;
+        if values.is_empty() {
+            return None;
+        }
+
+        let (min, max, sum) = values.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY, 0.0f64),
+            |(min, max, sum), &v| (min.min(v), max.max(v), sum + f64::from(v)),
+        );
+
+        Some(ColumnStats {
+            min,
+            max,
+            mean: (sum / values.len() as f64) as f32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod columns_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Channel};
+
+    use crate::{columns::ColumnStore, trace::Trace};
+
+    #[test]
+    fn columns_share_one_time_axis() {
+        let mut trace = Trace::new();
+        for (i, speed) in [100.0, 150.0, 200.0].into_iter().enumerate() {
+            let car = CarInfo {
+                speed_kmh: speed,
+                gear: i as i32 + 2,
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_millis(100 * i as u64), car);
+        }
+
+        let store = ColumnStore::from_trace(&trace, &[Channel::SpeedKmh, Channel::Gear]);
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.time(), &[0.0, 0.1, 0.2]);
+        assert_eq!(
+            store.column(Channel::SpeedKmh),
+            Some(&[100.0, 150.0, 200.0][..])
+        );
+        assert_eq!(store.column(Channel::Gear), Some(&[2.0, 3.0, 4.0][..]));
+        assert_eq!(store.column(Channel::Brake), None);
+
+        let speed = store.stats(Channel::SpeedKmh).expect("column exists");
+        assert_eq!((speed.min, speed.max, speed.mean), (100.0, 200.0, 150.0));
+    }
+}
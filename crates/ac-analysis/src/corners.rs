@@ -0,0 +1,205 @@
+//! Finding the corners of the track in a lap. The telemetry carries no track
+//! map, so a corner is any stretch where the car pulls enough lateral G for
+//! long enough; the corners of a lap are numbered in the order they're driven.
+
+use std::{ops::Range, time::Duration};
+
+use crate::trace::Trace;
+
+/// What counts as a corner.
+///
+/// * `lateral_g`: lateral G the car has to pull, either way.
+/// * `min_duration`: how long it has to pull it, to skip quick direction changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CornerRules {
+    pub lateral_g: f32,
+    pub min_duration: Duration,
+}
+
+impl Default for CornerRules {
+    fn default() -> Self {
+        Self {
+            lateral_g: 0.6,
+            min_duration: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A corner of the track, found in one lap.
+///
+/// * `number`: its place in the lap, from 1.
+/// * `samples`: the lap's samples through it.
+/// * `entry`, `exit`: where it starts and ends on track, 0 to 1; a corner
+///   across the line enters further round than it exits.
+/// * `peak_g`: the most lateral G pulled, either way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corner {
+    pub number: usize,
+    pub samples: Range<usize>,
+    pub entry: f32,
+    pub exit: f32,
+    pub peak_g: f32,
+}
+
+impl Corner {
+    /// every corner in a lap, in the order they're driven.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn find(lap: &Trace, rules: &CornerRules) -> Vec<Corner> {
+        let samples = lap.samples();
+        let mut corners = Vec::new();
+        let mut start = None;
+
+        for i in 0..=samples.len() {
+            let cornering = samples
+                .get(i)
+                .is_some_and(|s| s.car.accg_horizontal.abs() >= rules.lateral_g);
+
+            match (cornering, start) {
+                (true, None) => start = Some(i),
+                (false, Some(from)) => {
+                    start = None;
+                    let (first, last) = (&samples[from], &samples[i - 1]);
+                    if last.time.saturating_sub(first.time) < rules.min_duration {
+                        continue;
+                    }
+
+                    corners.push(Corner {
+                        number: corners.len() + 1,
+                        samples: from..i,
+                        entry: first.car.car_pos_normalized,
+                        exit: last.car.car_pos_normalized,
+                        peak_g: samples[from..i]
+                            .iter()
+                            .map(|s| s.car.accg_horizontal.abs())
+                            .fold(0.0, f32::max),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        corners
+    }
+}
+
+#[cfg(test)]
+mod corners_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        corners::{Corner, CornerRules},
+        trace::Trace,
+    };
+
+    /// a lap sampled every 200 ms, at `(lateral G, position)`.
+    fn lap(samples: &[(f32, f32)]) -> Trace {
+        let mut lap = Trace::new();
+        for (i, &(accg_horizontal, car_pos_normalized)) in samples.iter().enumerate() {
+            let car = CarInfo {
+                accg_horizontal,
+                car_pos_normalized,
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_millis(200 * i as u64), car);
+        }
+        lap
+    }
+
+    fn found(corners: &[Corner]) -> Vec<(usize, std::ops::Range<usize>)> {
+        corners
+            .iter()
+            .map(|c| (c.number, c.samples.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn an_empty_or_straight_lap_has_no_corners() {
+        let rules = CornerRules::default();
+        assert!(Corner::find(&Trace::new(), &rules).is_empty());
+        assert!(Corner::find(&lap(&[(0.5, 0.1), (-0.5, 0.2), (0.0, 0.3)]), &rules).is_empty());
+    }
+
+    #[test]
+    fn a_corner_has_to_last_min_duration() {
+        let rules = CornerRules::default();
+        // One sample at 1 G lasts no time at all; 0.6 G for 0.4 s isn't long enough.
+        assert!(Corner::find(&lap(&[(0.0, 0.0), (1.0, 0.1), (0.0, 0.2)]), &rules).is_empty());
+        let short = [(0.6, 0.1), (0.6, 0.2), (0.6, 0.3)];
+        assert!(Corner::find(&lap(&short), &rules).is_empty());
+
+        let rules = CornerRules {
+            min_duration: Duration::ZERO,
+            ..rules
+        };
+        let corners = Corner::find(&lap(&[(0.0, 0.0), (1.0, 0.1), (0.0, 0.2)]), &rules);
+        assert_eq!(found(&corners), [(1, 1..2)]);
+    }
+
+    #[test]
+    fn a_corner_still_going_at_the_end_of_the_lap_is_kept() {
+        let corners = Corner::find(
+            &lap(&[
+                (0.0, 0.9),
+                (0.8, 0.95),
+                (0.9, 0.97),
+                (0.8, 0.99),
+                (0.7, 1.0),
+            ]),
+            &CornerRules::default(),
+        );
+        assert_eq!(found(&corners), [(1, 1..5)]);
+        assert_eq!(corners[0].exit, 1.0);
+    }
+
+    #[test]
+    fn a_corner_across_the_line_wraps_its_position() {
+        let corners = Corner::find(
+            &lap(&[
+                (1.0, 0.97),
+                (1.0, 0.99),
+                (1.0, 0.01),
+                (1.0, 0.03),
+                (0.0, 0.05),
+            ]),
+            &CornerRules::default(),
+        );
+        assert_eq!(found(&corners), [(1, 0..4)]);
+        assert_eq!((corners[0].entry, corners[0].exit), (0.97, 0.03));
+    }
+
+    #[test]
+    fn a_change_of_direction_without_letting_up_is_one_corner() {
+        let chicane = [(0.9, 0.1), (1.0, 0.2), (-1.0, 0.3), (-0.9, 0.4), (0.0, 0.5)];
+        let corners = Corner::find(&lap(&chicane), &CornerRules::default());
+        assert_eq!(found(&corners), [(1, 0..4)]);
+        assert_eq!(corners[0].peak_g, 1.0);
+    }
+
+    #[test]
+    fn sustained_lateral_g_makes_a_corner() {
+        // A long left at 1.2 G, a 0.2 s flick, then a right at 0.9 G.
+        let g = [
+            0.0, 1.0, 1.2, 1.1, 0.8, 0.0, -0.9, 0.0, -0.7, -0.9, -0.8, -0.7, 0.1,
+        ];
+        let samples: Vec<_> = g
+            .into_iter()
+            .enumerate()
+            .map(|(i, g)| (g, i as f32 / 12.0))
+            .collect();
+
+        let corners = Corner::find(&lap(&samples), &CornerRules::default());
+        let found: Vec<_> = corners
+            .iter()
+            .map(|c| (c.number, c.samples.clone(), c.peak_g))
+            .collect();
+        assert_eq!(found, [(1, 1..5, 1.2), (2, 8..12, 0.9)]);
+        assert_eq!(
+            (corners[1].entry, corners[1].exit),
+            (8.0 / 12.0, 11.0 / 12.0)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Semantic coaching cues for apps to voice or beep. The crate doesn't do
+//! audio: a `Cues` stage turns the car's inputs and the outputs of the timing,
+//! delta and alert stages into `Cue`s and hands each to a `CueSink`, so a phone
+//! app can attach a haptic tick and a desktop app a voice line to the same cue.
+
+use std::{mem, time::Duration};
+
+use ac_parser::{CarInfo, Event, Wheel};
+
+use crate::pipeline::{Output, Stage};
+
+/// Something worth telling the driver right now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cue {
+    /// Coming up on where the best lap started braking.
+    BrakeNow,
+    /// Near the top of the rev range on full throttle.
+    Shift,
+    /// The delta to the best lap improved by at least a step; `seconds` is the delta now.
+    DeltaGain { seconds: f32 },
+    /// The delta to the best lap got worse by at least a step; `seconds` is the delta now.
+    DeltaLoss { seconds: f32 },
+    /// A wheel locked under braking.
+    Lockup(Wheel),
+    /// An alert rule started firing.
+    Alert(String),
+}
+
+/// Receives cues, e.g. to play a sound for each.
+pub trait CueSink: Send {
+    /// * `at`: when the event behind the cue arrived.
+    /// * `cue`: the cue.
+    fn cue(&mut self, at: Duration, cue: &Cue);
+}
+
+impl<F> CueSink for F
+where
+    F: FnMut(Duration, &Cue) + Send,
+{
+    fn cue(&mut self, at: Duration, cue: &Cue) {
+        self(at, cue)
+    }
+}
+
+/// When cues are given.
+///
+/// * `shift_at`: the share of the highest RPM seen so far to call a shift at.
+/// * `brake_lead`: how far ahead of a braking point to call it, as a share of the lap.
+/// * `brake_on`: brake input that counts as braking.
+/// * `lockup_slip`: slip ratio below which a braking wheel counts as locked.
+/// * `delta_step`: seconds the delta has to move by between delta cues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CueRules {
+    pub shift_at: f32,
+    pub brake_lead: f32,
+    pub brake_on: f32,
+    pub lockup_slip: f32,
+    pub delta_step: f32,
+}
+
+impl Default for CueRules {
+    fn default() -> Self {
+        Self {
+            shift_at: 0.95,
+            brake_lead: 0.003,
+            brake_on: 0.3,
+            lockup_slip: -0.25,
+            delta_step: 0.1,
+        }
+    }
+}
+
+/// Calls a `CueSink` with coaching cues. Put it after the timing, delta and
+/// alert stages; it reads what they produced for the same event.
+///
+/// * `rules`: when cues are given.
+/// * `sink`: where cues go.
+/// * `max_rpm`: the highest RPM seen so far.
+/// * `lap_count`: the lap counter in the last CarInfo.
+/// * `braking`, `locked`, `shifting`: whether each was happening on the last CarInfo.
+/// * `brake_points`: where the lap in progress started braking.
+/// * `reference`: where the best lap started braking.
+/// * `next_brake`: the next of those to call on this lap.
+/// * `delta`: the delta at the last delta cue.
+pub struct Cues {
+    rules: CueRules,
+    sink: Box<dyn CueSink>,
+    max_rpm: f32,
+    lap_count: Option<u32>,
+    braking: bool,
+    locked: [bool; 4],
+    shifting: bool,
+    brake_points: Vec<f32>,
+    reference: Vec<f32>,
+    next_brake: usize,
+    delta: Option<f32>,
+}
+
+impl Cues {
+    /// * `sink`: where cues go.
+    pub fn new<S>(sink: S) -> Self
+    where
+        S: CueSink + 'static,
+    {
+        Self::with_rules(CueRules::default(), sink)
+    }
+
+    /// * `rules`: when cues are given.
+    /// * `sink`: where cues go.
+    pub fn with_rules<S>(rules: CueRules, sink: S) -> Self
+    where
+        S: CueSink + 'static,
+    {
+        Self {
+            rules,
+            sink: Box::new(sink),
+            max_rpm: 0.0,
+            lap_count: None,
+            braking: false,
+            locked: [false; 4],
+            shifting: false,
+            brake_points: Vec::new(),
+            reference: Vec::new(),
+            next_brake: 0,
+            delta: None,
+        }
+    }
+
+    /// the cues a CarInfo calls for.
+    ///
+    /// * `car`: the CarInfo.
+    /// * `best_lap`: whether the timing stage just reported the lap before it as the best.
+    fn car(&mut self, car: &CarInfo, best_lap: bool, cues: &mut Vec<Cue>) {
+        let rules = self.rules;
+        let position = car.car_pos_normalized;
+
+        if self.lap_count.is_some_and(|lap| car.lap_count > lap) {
+            let finished = mem::take(&mut self.brake_points);
+            if best_lap {
+                self.reference = finished;
+            }
+            self.next_brake = self.reference.partition_point(|&p| p < position);
+        }
+        self.lap_count = Some(car.lap_count);
+
+        if let Some(&point) = self.reference.get(self.next_brake)
+            && position >= point - rules.brake_lead
+        {
+            self.next_brake += 1;
+            cues.push(Cue::BrakeNow);
+        }
+
+        let braking = car.brake >= rules.brake_on;
+        if braking && !self.braking {
+            self.brake_points.push(position);
+        }
+        self.braking = braking;
+
+        for wheel in Wheel::ALL {
+            let locked = braking && car.slip_ratio[wheel as usize] <= rules.lockup_slip;
+            if locked && !self.locked[wheel as usize] {
+                cues.push(Cue::Lockup(wheel));
+            }
+            self.locked[wheel as usize] = locked;
+        }
+
+        self.max_rpm = self.max_rpm.max(car.engine_rpm);
+        let shifting = car.gas > 0.9 && car.engine_rpm >= self.max_rpm * rules.shift_at;
+        if shifting && !self.shifting && car.engine_rpm > 0.0 {
+            cues.push(Cue::Shift);
+        }
+        self.shifting = shifting;
+    }
+
+    /// the cue a delta calls for, if it has moved a step since the last one.
+    ///
+    /// * `seconds`: the delta to the best lap.
+    fn delta(&mut self, seconds: f32) -> Option<Cue> {
+        let last = *self.delta.get_or_insert(seconds);
+        if (seconds - last).abs() < self.rules.delta_step {
+            return None;
+        }
+
+        self.delta = Some(seconds);
+        Some(if seconds < last {
+            Cue::DeltaGain { seconds }
+        } else {
+            Cue::DeltaLoss { seconds }
+        })
+    }
+}
+
+impl Stage for Cues {
+    fn name(&self) -> &str {
+        "cues"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        let mut cues = Vec::new();
+
+        if let Event::CarInfo(car) = event {
+            let best_lap = outputs
+                .iter()
+                .any(|output| matches!(output, Output::Lap(lap) if lap.best));
+            self.car(car, best_lap, &mut cues);
+        }
+
+        for output in outputs.iter() {
+            match output {
+                Output::Delta(delta) => cues.extend(self.delta(delta.seconds)),
+                Output::Alert(alert) => cues.push(Cue::Alert(alert.rule.clone())),
+                _ => {}
+            }
+        }
+
+        for cue in &cues {
+            self.sink.cue(at, cue);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod cues_tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use ac_parser::{CarInfo, Event, Wheel};
+
+    use crate::{
+        cues::{Cue, Cues},
+        pipeline::{LapTimer, PipelineBuilder},
+    };
+
+    #[test]
+    fn cues_follow_the_best_lap_and_the_inputs() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&heard);
+        let mut pipeline = PipelineBuilder::new()
+            .stage(LapTimer::new())
+            .stage(Cues::new(move |_, cue: &Cue| {
+                sink.lock().expect("poisoned").push(cue.clone())
+            }))
+            .build();
+
+        // Lap 0 brakes at 40% with the front left locking; lap 1 should be told
+        // to brake just before 40%.
+        let car = |lap_count, position: f32, brake, slip| CarInfo {
+            lap_count,
+            last_lap: if lap_count > 0 { 90_000 } else { 0 },
+            car_pos_normalized: position,
+            brake,
+            slip_ratio: [slip, 0.0, 0.0, 0.0],
+            engine_rpm: 6000.0,
+            ..CarInfo::default()
+        };
+        let laps = [
+            car(0, 0.1, 0.0, 0.0),
+            car(0, 0.4, 0.8, -0.5),
+            car(0, 0.5, 0.0, 0.0),
+            car(1, 0.01, 0.0, 0.0),
+            car(1, 0.35, 0.0, 0.0),
+            car(1, 0.398, 0.0, 0.0),
+        ];
+        for (i, car) in laps.into_iter().enumerate() {
+            pipeline.push(Duration::from_secs(i as u64), &Event::CarInfo(car));
+        }
+
+        let heard = heard.lock().expect("poisoned");
+        assert_eq!(*heard, [Cue::Lockup(Wheel::FrontLeft), Cue::BrakeNow]);
+    }
+}
@@ -0,0 +1,235 @@
+//! How fast a stint's lap times are falling off, for fuel and tyre strategy
+//! in endurance races. Valid laps are fitted with a straight line of lap time
+//! against lap number. Traffic, a spin or a safety car leave laps far off the
+//! pace, so laps slower than `OUTLIER_SHARE` of the median are left out, and
+//! the fit is redone without laps that sit well off the first line.
+
+use std::time::Duration;
+
+use crate::{pipeline::LapTime, snapshot::SessionSnapshot};
+
+/// Laps slower than this share of the stint's median lap are left out.
+pub const OUTLIER_SHARE: f64 = 1.07;
+
+/// How many times the fit's RMS error a lap may sit from the first line before
+/// it's left out of the second.
+const RESIDUAL_LIMIT: f64 = 2.0;
+
+/// A straight-line fit of lap time against lap number.
+///
+/// * `per_lap`: seconds each lap is slower than the one before; negative while the car gets faster.
+/// * `intercept`: the fitted lap time at lap number zero, in seconds.
+/// * `laps_used`: how many laps the fit is over, after outliers were left out.
+/// * `rms`: how far the fitted laps sit from the line on average, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Degradation {
+    pub per_lap: f64,
+    pub intercept: f64,
+    pub laps_used: usize,
+    pub rms: f64,
+}
+
+impl Degradation {
+    /// fits the valid laps among `laps`, e.g. one stint. `None` with fewer
+    /// than three laps left to fit.
+    ///
+    /// * `laps`: the laps, in any order.
+    pub fn fit(laps: &[LapTime]) -> Option<Self> {
+        let mut points: Vec<(f64, f64)> = laps
+            .iter()
+            .filter(|lap| lap.validity.is_valid())
+            .map(|lap| (f64::from(lap.lap), lap.time.as_secs_f64()))
+            .collect();
+
+        let mut times: Vec<f64> = points.iter().map(|&(_, time)| time).collect();
+        times.sort_by(f64::total_cmp);
+        let median = *times.get(times.len() / 2)?;
+        points.retain(|&(_, time)| time <= median * OUTLIER_SHARE);
+
+        let first = line(&points)?;
+        let limit = (first.rms * RESIDUAL_LIMIT).max(1e-3);
+        points.retain(|&(lap, time)| (time - first.at(lap)).abs() <= limit);
+
+        line(&points)
+    }
+
+    /// the fitted time of a lap, e.g. to project the rest of a stint.
+    ///
+    /// * `lap`: the lap number.
+    pub fn projected(&self, lap: u32) -> Duration {
+        Duration::from_secs_f64(self.at(f64::from(lap)).max(0.0))
+    }
+
+    /// how much slower than `from` the fit projects `to`, e.g. the cost of
+    /// staying out on these tyres.
+    ///
+    /// * `from`, `to`: lap numbers.
+    pub fn loss(&self, from: u32, to: u32) -> f64 {
+        self.per_lap * (f64::from(to) - f64::from(from))
+    }
+
+    fn at(&self, lap: f64) -> f64 {
+        self.intercept + self.per_lap * lap
+    }
+}
+
+/// a least-squares line through `(lap, seconds)` points, if there are at least three.
+fn line(points: &[(f64, f64)]) -> Option<Degradation> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_lap = points.iter().map(|&(lap, _)| lap).sum::<f64>() / n;
+    let mean_time = points.iter().map(|&(_, time)| time).sum::<f64>() / n;
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(cov, var), &(lap, time)| {
+        let dl = lap - mean_lap;
+        (cov + dl * (time - mean_time), var + dl * dl)
+    });
+    if variance == 0.0 {
+        return None;
+    }
+
+    let per_lap = covariance / variance;
+    let mut fit = Degradation {
+        per_lap,
+        intercept: mean_time - per_lap * mean_lap,
+        laps_used: points.len(),
+        rms: 0.0,
+    };
+    let squares: f64 = points
+        .iter()
+        .map(|&(lap, time)| (time - fit.at(lap)).powi(2))
+        .sum();
+    fit.rms = (squares / n).sqrt();
+
+    Some(fit)
+}
+
+impl SessionSnapshot {
+    /// the degradation of each stint, in the order of `stints()`.
+    pub fn degradation(&self) -> Vec<Option<Degradation>> {
+        let mut start = 0;
+        self.stints()
+            .iter()
+            .map(|stint| {
+                let laps = &self.laps[start..start + stint.laps as usize];
+                start += stint.laps as usize;
+                Degradation::fit(laps)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod degradation_tests {
+    use std::time::Duration;
+
+    use crate::{
+        degradation::Degradation, pipeline::LapTime, snapshot::SessionSnapshot,
+        validity::LapValidity,
+    };
+
+    fn lap(lap: u32, ms: u64, validity: LapValidity) -> LapTime {
+        LapTime {
+            lap,
+            time: Duration::from_millis(ms),
+            best: false,
+            validity,
+        }
+    }
+
+    fn valid(laps: &[(u32, u64)]) -> Vec<LapTime> {
+        laps.iter()
+            .map(|&(n, ms)| lap(n, ms, LapValidity::Valid))
+            .collect()
+    }
+
+    #[test]
+    fn too_few_laps_fit_nothing() {
+        assert_eq!(Degradation::fit(&[]), None);
+        assert_eq!(Degradation::fit(&valid(&[(1, 100_000)])), None);
+        assert_eq!(
+            Degradation::fit(&valid(&[(1, 100_000), (2, 100_200)])),
+            None
+        );
+
+        let mut laps = valid(&[(1, 100_000), (2, 100_200)]);
+        laps.push(lap(3, 100_400, LapValidity::OutLap));
+        assert_eq!(Degradation::fit(&laps), None, "invalid laps don't count");
+
+        let snapshot = SessionSnapshot {
+            laps: Vec::new(),
+            lap_count: None,
+        };
+        assert!(snapshot.degradation().is_empty());
+    }
+
+    #[test]
+    fn laps_with_one_number_have_no_slope() {
+        // A lap counter that was reset can repeat a number.
+        let laps = valid(&[(4, 100_000), (4, 100_500), (4, 101_000)]);
+        assert_eq!(Degradation::fit(&laps), None);
+    }
+
+    #[test]
+    fn laps_fit_the_same_in_any_order() {
+        let laps = valid(&[(1, 100_000), (2, 100_300), (3, 100_600), (4, 100_900)]);
+        let mut reversed = laps.clone();
+        reversed.reverse();
+
+        let fit = Degradation::fit(&laps).expect("four laps");
+        let again = Degradation::fit(&reversed).expect("four laps");
+        assert!((fit.per_lap - again.per_lap).abs() < 1e-9);
+        assert!((fit.intercept - again.intercept).abs() < 1e-9);
+        assert!((fit.per_lap - 0.3).abs() < 1e-9, "{fit:?}");
+        assert!(fit.rms < 1e-9);
+    }
+
+    #[test]
+    fn identical_laps_keep_every_lap_and_no_trend() {
+        let fit =
+            Degradation::fit(&valid(&[(1, 90_000), (2, 90_000), (3, 90_000)])).expect("three laps");
+        assert_eq!((fit.per_lap, fit.rms, fit.laps_used), (0.0, 0.0, 3));
+        assert_eq!(fit.projected(50), Duration::from_secs(90));
+        assert_eq!(fit.loss(1, 50), 0.0);
+    }
+
+    #[test]
+    fn projections_stop_at_zero() {
+        // A second a lap faster projects below zero a hundred laps on.
+        let fit = Degradation::fit(&valid(&[(1, 100_000), (2, 99_000), (3, 98_000)]))
+            .expect("three laps");
+        assert_eq!(fit.projected(200), Duration::ZERO);
+    }
+
+    #[test]
+    fn stints_fit_a_line_through_their_clean_laps() {
+        // 0.2s a lap slower each lap, with a lap in traffic and a small lock-up.
+        let mut laps = vec![lap(0, 120_000, LapValidity::OutLap)];
+        for (n, extra) in [(1, 0), (2, 0), (3, 9_000), (4, 0), (5, 700), (6, 0), (7, 0)] {
+            laps.push(lap(
+                n,
+                100_000 + 200 * u64::from(n) + extra,
+                LapValidity::Valid,
+            ));
+        }
+        laps.push(lap(8, 130_000, LapValidity::OutLap));
+        laps.push(lap(9, 101_000, LapValidity::Valid));
+
+        let snapshot = SessionSnapshot {
+            laps,
+            lap_count: Some(10),
+        };
+        let [first, second] = &snapshot.degradation()[..] else {
+            panic!("expected two stints");
+        };
+        assert_eq!(*second, None, "one lap is no trend");
+
+        let fit = first.expect("seven valid laps");
+        assert_eq!(fit.laps_used, 5);
+        assert!((fit.per_lap - 0.2).abs() < 1e-6, "{fit:?}");
+        assert_eq!(fit.projected(10), Duration::from_millis(102_000));
+        assert!((fit.loss(7, 17) - 2.0).abs() < 1e-6);
+    }
+}
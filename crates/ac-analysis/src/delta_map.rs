@@ -0,0 +1,314 @@
+//! Where on track a lap gains and loses time against a reference lap. The
+//! lap is cut into segments, fixed-length buckets or corners and the
+//! straights between them, and each segment's time is set against the
+//! reference's over the same stretch. The result reads as a table, or as a
+//! heat value per segment for colouring a track map.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    sectors::Sectors,
+    trace::{Sample, Trace},
+};
+
+/// Time gained or lost over one segment.
+///
+/// * `segment`: which segment, from 0.
+/// * `from`, `to`: where it starts and ends on track, 0 to 1.
+/// * `reference`, `time`: how long the reference and the lap took through it.
+/// * `seconds`: time lost against the reference, negative when gained.
+/// * `heat`: `seconds` scaled by the segment that differed most, -1 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeltaSegment {
+    pub segment: usize,
+    pub from: f32,
+    pub to: f32,
+    pub reference: Duration,
+    pub time: Duration,
+    pub seconds: f32,
+    pub heat: f32,
+}
+
+/// A lap's time against a reference lap, segment by segment.
+///
+/// * `lap`, `reference_lap`: the lap counters of the two laps.
+/// * `segments`: each segment, in lap order.
+/// * `seconds`: time lost over the whole lap, negative when gained.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaMap {
+    pub lap: u32,
+    pub reference_lap: u32,
+    pub segments: Vec<DeltaSegment>,
+    pub seconds: f32,
+}
+
+impl DeltaMap {
+    /// compares a lap to a reference over some segments. `None` if either lap is empty.
+    ///
+    /// * `reference`: the lap to measure against, e.g. the best lap.
+    /// * `lap`: the lap measured.
+    /// * `segments`: how to cut the lap up, e.g. `Sectors::equal(20)` for
+    ///   fixed-length buckets or `Sectors::around_corners` for corners.
+    pub fn of(reference: &Trace, lap: &Trace, segments: &Sectors) -> Option<Self> {
+        let reference_lap = reference.samples().first()?.car.lap_count;
+        let lap_count = lap.samples().first()?.car.lap_count;
+
+        let starts = segments.starts();
+        let mut map = Self {
+            lap: lap_count,
+            reference_lap,
+            segments: Vec::with_capacity(starts.len()),
+            seconds: 0.0,
+        };
+
+        for (segment, &from) in starts.iter().enumerate() {
+            let to = starts.get(segment + 1).copied().unwrap_or(1.0);
+            let reference = time_at(reference, to).saturating_sub(time_at(reference, from));
+            let time = time_at(lap, to).saturating_sub(time_at(lap, from));
+            let seconds = time.as_secs_f32() - reference.as_secs_f32();
+
+            map.seconds += seconds;
+            map.segments.push(DeltaSegment {
+                segment,
+                from,
+                to,
+                reference,
+                time,
+                seconds,
+                heat: 0.0,
+            });
+        }
+
+        let most = map
+            .segments
+            .iter()
+            .map(|segment| segment.seconds.abs())
+            .fold(0.0, f32::max);
+        if most > 0.0 {
+            for segment in &mut map.segments {
+                segment.heat = segment.seconds / most;
+            }
+        }
+
+        Some(map)
+    }
+
+    /// the segment a track position is in, for colouring a track map point by point.
+    ///
+    /// * `position`: 0 to 1.
+    pub fn segment_at(&self, position: f32) -> Option<&DeltaSegment> {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.from <= position.clamp(0.0, 1.0));
+        self.segments.get(index.checked_sub(1)?)
+    }
+
+    /// writes the map as CSV, one row per segment.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "segment,from,to,reference_ms,time_ms,seconds,heat")?;
+        for s in &self.segments {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                s.segment,
+                s.from,
+                s.to,
+                s.reference.as_millis(),
+                s.time.as_millis(),
+                s.seconds,
+                s.heat
+            )?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// when a lap reached a track position, interpolating between samples.
+/// Positions before its first sample or after its last clamp to those samples,
+/// and samples whose position isn't a number are passed over.
+pub(crate) fn time_at(lap: &Trace, position: f32) -> Duration {
+    let samples = lap.samples();
+    let placed = |s: &&Sample| !s.distance.is_nan();
+
+    // A binary search for the first sample at or past `position`, stepping
+    // over unplaced samples wherever it lands on one.
+    let (mut low, mut high) = (0, samples.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match samples[mid..high].iter().position(|s| placed(&s)) {
+            Some(offset) if samples[mid + offset].distance < position => low = mid + offset + 1,
+            _ => high = mid,
+        }
+    }
+
+    match (
+        samples[..low].iter().rev().find(placed),
+        samples[low..].iter().find(placed),
+    ) {
+        (Some(before), Some(after)) if after.distance > before.distance => {
+            let share = (position - before.distance) / (after.distance - before.distance);
+            before.time + after.time.saturating_sub(before.time).mul_f32(share)
+        }
+        (_, Some(after)) => after.time,
+        (Some(before), None) => before.time,
+        (None, None) => Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod delta_map_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{delta_map::DeltaMap, sectors::Sectors, trace::Trace};
+
+    /// a lap sampled every tenth, each tenth taking the given seconds.
+    fn lap(lap_count: u32, tenths: [f32; 10]) -> Trace {
+        let mut trace = Trace::new();
+        let mut at = 0.0;
+        for i in 0..=10 {
+            let car = CarInfo {
+                lap_count,
+                car_pos_normalized: i as f32 / 10.0,
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_secs_f32(at), car);
+            at += tenths.get(i).copied().unwrap_or_default();
+        }
+
+        trace
+    }
+
+    /// a lap from `(seconds, lap_count, position)` samples.
+    fn samples(samples: &[(f32, u32, f32)]) -> Trace {
+        let mut trace = Trace::new();
+        for &(at, lap_count, car_pos_normalized) in samples {
+            let car = CarInfo {
+                lap_count,
+                car_pos_normalized,
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_secs_f32(at), car);
+        }
+
+        trace
+    }
+
+    fn seconds(map: &DeltaMap) -> Vec<f32> {
+        map.segments
+            .iter()
+            .map(|s| (s.seconds * 10.0).round() / 10.0)
+            .collect()
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_map() {
+        let full = lap(1, [10.0; 10]);
+        assert_eq!(DeltaMap::of(&Trace::new(), &full, &Sectors::equal(5)), None);
+        assert_eq!(DeltaMap::of(&full, &Trace::new(), &Sectors::equal(5)), None);
+    }
+
+    #[test]
+    fn single_samples_take_no_time_and_have_no_heat() {
+        let one = samples(&[(0.0, 1, 0.5)]);
+        let map = DeltaMap::of(&one, &one, &Sectors::equal(2)).expect("not empty");
+
+        assert_eq!(map.seconds, 0.0);
+        for segment in &map.segments {
+            assert_eq!(segment.time, Duration::ZERO);
+            assert_eq!(segment.heat, 0.0);
+        }
+    }
+
+    #[test]
+    fn standing_still_is_time_lost_where_the_car_stood() {
+        let reference = samples(&[(0.0, 1, 0.0), (10.0, 1, 0.5), (20.0, 1, 1.0)]);
+        let lap = samples(&[
+            (0.0, 1, 0.0),
+            (6.0, 1, 0.3),
+            (11.0, 1, 0.3),
+            (15.0, 1, 0.5),
+            (25.0, 1, 1.0),
+        ]);
+
+        let map = DeltaMap::of(&reference, &lap, &Sectors::equal(2)).expect("not empty");
+        assert_eq!(seconds(&map), [5.0, 0.0]);
+        assert_eq!(map.segments[1].heat, 0.0);
+    }
+
+    #[test]
+    fn nan_positions_are_passed_over() {
+        let reference = lap(1, [10.0; 10]);
+        let mut clean = vec![(0.0, 2, 0.0)];
+        let mut noisy = clean.clone();
+        for i in 1..=10 {
+            let position = (i as f32 / 10.0, i as f32 * 11.0);
+            clean.push((position.1, 2, position.0));
+            noisy.push((position.1 - 5.0, 2, f32::NAN));
+            noisy.push((position.1, 2, position.0));
+        }
+
+        let clean = DeltaMap::of(&reference, &samples(&clean), &Sectors::equal(5));
+        let noisy = DeltaMap::of(&reference, &samples(&noisy), &Sectors::equal(5));
+        assert_eq!(noisy, clean);
+        assert_eq!(seconds(&noisy.expect("not empty")), [2.0; 5]);
+    }
+
+    #[test]
+    fn a_lap_run_past_the_line_is_timed_to_it() {
+        let reference = samples(&[(0.0, 1, 0.0), (10.0, 1, 0.5), (20.0, 1, 1.0)]);
+        let lap = samples(&[
+            (0.0, 1, 0.0),
+            (10.0, 1, 0.5),
+            (19.0, 1, 0.9),
+            (21.0, 2, 0.1),
+            (25.0, 2, 0.3),
+        ]);
+
+        let map = DeltaMap::of(&reference, &lap, &Sectors::equal(2)).expect("not empty");
+        assert_eq!(seconds(&map), [0.0, 0.0]);
+        assert_eq!(map.segments[1].time, Duration::from_secs(10));
+        assert_eq!(map.segment_at(f32::NAN), None);
+        assert_eq!(map.segment_at(1.2).map(|s| s.segment), Some(1));
+    }
+
+    #[test]
+    fn time_is_gained_and_lost_per_segment() {
+        let reference = lap(3, [10.0; 10]);
+        // A second lost in the second tenth, in the first fifth; half a
+        // second gained in the ninth, in the last fifth.
+        let mut tenths = [10.0; 10];
+        tenths[1] = 11.0;
+        tenths[8] = 9.5;
+        let lap = lap(4, tenths);
+
+        let map = DeltaMap::of(&reference, &lap, &Sectors::equal(5)).expect("not empty");
+        assert_eq!((map.lap, map.reference_lap), (4, 3));
+        assert!((map.seconds - 0.5).abs() < 1e-3, "{map:?}");
+
+        assert_eq!(seconds(&map), [1.0, 0.0, 0.0, 0.0, -0.5]);
+        assert_eq!(map.segments[0].heat, 1.0);
+        assert!((map.segments[4].heat + 0.5).abs() < 1e-3);
+        assert_eq!(map.segment_at(0.85).map(|s| s.segment), Some(4));
+
+        let mut csv = Vec::new();
+        map.write_csv(&mut csv).expect("written");
+        let csv = String::from_utf8(csv).expect("utf-8");
+        assert_eq!(csv.lines().count(), 6);
+        assert!(
+            csv.lines()
+                .nth(1)
+                .is_some_and(|row| row.starts_with("0,0,0.2,20000,21000,"))
+        );
+    }
+}
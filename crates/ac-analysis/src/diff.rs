@@ -0,0 +1,177 @@
+use ac_parser::Channel;
+
+use crate::trace::{Sample, Trace};
+
+/// How many evenly spaced points both traces are resampled to before comparing.
+const RESAMPLE_POINTS: usize = 1000;
+
+/// What two traces are lined up by before comparing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// Compare values at the same time since the start of each trace.
+    Time,
+    /// Compare values at the same point on track, which is what you want for
+    /// comparing two drivers or two laps of different length.
+    Distance,
+}
+
+impl Alignment {
+    fn key(self, sample: &Sample) -> f32 {
+        match self {
+            Alignment::Time => sample.time.as_secs_f32(),
+            Alignment::Distance => sample.distance,
+        }
+    }
+}
+
+/// How one channel differs between the two traces.
+/// Deltas are `b - a`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelDiff {
+    pub channel: Channel,
+    pub mean_a: f32,
+    pub mean_b: f32,
+    pub mean_delta: f32,
+    pub max_abs_delta: f32,
+    pub rmse: f32,
+}
+
+/// The result of comparing two traces.
+///
+/// * `alignment`: what the traces were lined up by.
+/// * `from`, `to`: the overlapping range compared, in seconds or laps.
+/// * `channels`: one entry per compared channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    pub alignment: Alignment,
+    pub from: f32,
+    pub to: f32,
+    pub channels: Vec<ChannelDiff>,
+}
+
+/// compares two traces (two recordings, or two laps from `Trace::laps`) channel by channel
+/// over the range they overlap.
+/// Returns `None` if either trace is empty or they don't overlap.
+///
+/// * `a`: the baseline trace.
+/// * `b`: the trace compared against it.
+/// * `alignment`: whether to line the traces up by time or track distance.
+/// * `channels`: which channels to compare.
+pub fn diff(
+    a: &Trace,
+    b: &Trace,
+    alignment: Alignment,
+    channels: &[Channel],
+) -> Option<DiffReport> {
+    let key = |s: &Sample| alignment.key(s);
+
+    let from = key(a.samples().first()?).max(key(b.samples().first()?));
+    let to = key(a.samples().last()?).min(key(b.samples().last()?));
+    if to <= from {
+        return None;
+    }
+
+    let step = (to - from) / (RESAMPLE_POINTS - 1) as f32;
+    let points = (0..RESAMPLE_POINTS).map(|i| from + step * i as f32);
+
+    let channels = channels
+        .iter()
+        .map(|&channel| {
+            let mut sum_a = 0.0;
+            let mut sum_b = 0.0;
+            let mut sum_sq = 0.0;
+            let mut max_abs_delta: f32 = 0.0;
+
+            for at in points.clone() {
+                let va = a.interpolate(channel, key, at).unwrap_or_default();
+                let vb = b.interpolate(channel, key, at).unwrap_or_default();
+                let delta = vb - va;
+
+                sum_a += va;
+                sum_b += vb;
+                sum_sq += delta * delta;
+                max_abs_delta = max_abs_delta.max(delta.abs());
+            }
+
+            let n = RESAMPLE_POINTS as f32;
+            ChannelDiff {
+                channel,
+                mean_a: sum_a / n,
+                mean_b: sum_b / n,
+                mean_delta: (sum_b - sum_a) / n,
+                max_abs_delta,
+                rmse: (sum_sq / n).sqrt(),
+            }
+        })
+        .collect();
+
+    Some(DiffReport {
+        alignment,
+        from,
+        to,
+        channels,
+    })
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Channel};
+
+    use crate::{
+        diff::{Alignment, diff},
+        trace::Trace,
+    };
+
+    // A lap driven at a constant speed, sampled every 0.1 of a lap.
+    fn constant_speed_lap(speed_kmh: f32, lap_secs: f32) -> Trace {
+        let mut trace = Trace::new();
+
+        for i in 0..=10 {
+            let pos = i as f32 / 10.0;
+            let car = CarInfo {
+                car_pos_normalized: pos,
+                speed_kmh,
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_secs_f32(lap_secs * pos), car);
+        }
+
+        trace
+    }
+
+    #[test]
+    fn identical_traces_have_no_difference() {
+        let lap = constant_speed_lap(150.0, 100.0);
+        let report = diff(&lap, &lap, Alignment::Time, &[Channel::SpeedKmh]).expect("overlap");
+
+        assert_eq!(report.channels[0].mean_delta, 0.0);
+        assert_eq!(report.channels[0].rmse, 0.0);
+    }
+
+    #[test]
+    fn distance_alignment_covers_laps_of_different_length() {
+        let slow = constant_speed_lap(150.0, 100.0);
+        let fast = constant_speed_lap(160.0, 90.0);
+
+        let report =
+            diff(&slow, &fast, Alignment::Distance, &[Channel::SpeedKmh]).expect("overlap");
+        assert_eq!((report.from, report.to), (0.0, 1.0));
+
+        let speed = &report.channels[0];
+        assert_eq!(speed.mean_a, 150.0);
+        assert_eq!(speed.mean_b, 160.0);
+        assert_eq!(speed.mean_delta, 10.0);
+        assert_eq!(speed.max_abs_delta, 10.0);
+
+        let by_time = diff(&slow, &fast, Alignment::Time, &[Channel::SpeedKmh]).expect("overlap");
+        assert_eq!(by_time.to, 90.0, "Time alignment stops at the shorter lap");
+    }
+
+    #[test]
+    fn empty_trace_has_no_report() {
+        let lap = constant_speed_lap(150.0, 100.0);
+        assert!(diff(&lap, &Trace::new(), Alignment::Time, &[Channel::SpeedKmh]).is_none());
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use ac_parser::Event;
+
+/// The longest interval between kept CarInfo; slower rates keep one this
+/// often rather than overflowing the interval.
+const MAX_INTERVAL: Duration = Duration::from_secs(1000);
+
+/// Thins CarInfo updates to a maximum rate, for consumers (dashboards, loggers)
+/// that don't need every physics tick. Handshake and lap events always pass.
+///
+/// * `interval`: the least time between two kept CarInfo events.
+/// * `last`: when the last CarInfo event was kept.
+#[derive(Debug, Clone)]
+pub struct Downsample {
+    interval: Duration,
+    last: Option<Duration>,
+}
+
+impl Downsample {
+    /// keeps at most `hz` CarInfo events per second.
+    ///
+    /// * `hz`: the highest CarInfo rate to keep; zero, less or NaN keeps
+    ///   everything, and slower than one per `MAX_INTERVAL` keeps one per `MAX_INTERVAL`.
+    pub fn new(hz: f32) -> Self {
+        let interval = if hz > 0.0 {
+            Duration::try_from_secs_f32(1.0 / hz).map_or(MAX_INTERVAL, |i| i.min(MAX_INTERVAL))
+        } else {
+            Duration::ZERO
+        };
+
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// whether to keep an event.
+    ///
+    /// * `at`: when the event arrived, on any monotonic timeline.
+    /// * `event`: the event.
+    pub fn keep(&mut self, at: Duration, event: &Event) -> bool {
+        if !matches!(event, Event::CarInfo(_)) {
+            return true;
+        }
+
+        if let Some(last) = self.last
+            && at.saturating_sub(last) < self.interval
+        {
+            return false;
+        }
+
+        self.last = Some(at);
+        true
+    }
+}
+
+#[cfg(test)]
+mod downsample_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event, LapInfo};
+
+    use crate::downsample::{Downsample, MAX_INTERVAL};
+
+    #[test]
+    fn car_info_is_thinned_to_the_target_rate() {
+        let mut downsample = Downsample::new(10.0);
+        let car = Event::CarInfo(CarInfo::default());
+
+        let kept = (0..30)
+            .filter(|ms| downsample.keep(Duration::from_millis(ms * 10), &car))
+            .count();
+        assert_eq!(kept, 3);
+
+        let lap = Event::LapInfo(LapInfo::default());
+        assert!(downsample.keep(Duration::from_millis(291), &lap));
+
+        assert_eq!(Downsample::new(1e-30).interval, MAX_INTERVAL);
+        assert_eq!(Downsample::new(f32::MIN_POSITIVE).interval, MAX_INTERVAL);
+        assert_eq!(Downsample::new(f32::NAN).interval, Duration::ZERO);
+        assert_eq!(Downsample::new(f32::INFINITY).interval, Duration::ZERO);
+    }
+}
@@ -0,0 +1,486 @@
+//! A compact profile of how someone drives a track, for coaches following a
+//! driver's progress over weeks. A profile averages recorded laps into where
+//! the brakes go on and the minimum speed for each corner, the revs each
+//! upshift is taken at, and how smooth the steering and pedals are. Two
+//! profiles, two drivers or the same one a month apart, can be compared, and
+//! profiles are saved as short text files like session snapshots:
+//!
+//! ```text
+//! ac_lib profile 1
+//! laps 12
+//! upshift_rpm 7450
+//! steering 0.93 0.8
+//! pedals 0.02 0.05
+//! corner 1 0.112 84.5
+//! corner 2 - 131.2
+//! ```
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    brakes::BrakeReport,
+    corners::{Corner, CornerRules},
+    pedals::{PedalReport, PedalRules},
+    snapshot::{invalid, number},
+    steering::Smoothness,
+    trace::Trace,
+};
+
+/// The first line of every profile.
+const PROFILE_HEADER: &str = "ac_lib profile 1";
+
+/// How a driver takes one corner, averaged over laps.
+///
+/// * `number`: the corner's place in the lap, from 1.
+/// * `brake_point`: where the brakes go on for it, 0 to 1, if they do.
+/// * `min_kmh`: the lowest speed through it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CornerProfile {
+    pub number: usize,
+    pub brake_point: Option<f32>,
+    pub min_kmh: f32,
+}
+
+/// How a driver drives a track, averaged over laps.
+///
+/// * `laps`: how many laps it's built from.
+/// * `corners`: each corner, by number.
+/// * `upshift_rpm`: the revs upshifts are taken at, if there were any.
+/// * `smoothness`, `reversals_per_second`: the steering, as in `Smoothness`.
+/// * `overlap`, `coasting`: the pedals, as shares of the lap as in `PedalReport`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriverProfile {
+    pub laps: usize,
+    pub corners: Vec<CornerProfile>,
+    pub upshift_rpm: Option<f32>,
+    pub smoothness: f32,
+    pub reversals_per_second: f32,
+    pub overlap: f32,
+    pub coasting: f32,
+}
+
+/// How one corner differs between two profiles, the second less the first.
+///
+/// * `number`: the corner's number.
+/// * `brake_point`: how much later the second brakes, as a share of the lap,
+///   if both brake; the shorter way round, for brake points either side of the line.
+/// * `min_kmh`: how much more speed the second carries through it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CornerDiff {
+    pub number: usize,
+    pub brake_point: Option<f32>,
+    pub min_kmh: f32,
+}
+
+/// How two profiles differ, the second less the first.
+///
+/// * `corners`: each corner both profiles have.
+/// * `upshift_rpm`: how much higher the second shifts up, if both do.
+/// * `smoothness`, `reversals_per_second`, `overlap`, `coasting`: the difference in each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileDiff {
+    pub corners: Vec<CornerDiff>,
+    pub upshift_rpm: Option<f32>,
+    pub smoothness: f32,
+    pub reversals_per_second: f32,
+    pub overlap: f32,
+    pub coasting: f32,
+}
+
+/// A running mean.
+#[derive(Debug, Clone, Copy, Default)]
+struct Mean {
+    sum: f32,
+    count: usize,
+}
+
+impl Mean {
+    fn add(&mut self, value: f32) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn get(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.sum / self.count as f32)
+    }
+
+    /// adds a track position, taken the nearer way round from the mean so
+    /// far, so positions either side of the line average near it.
+    fn add_position(&mut self, position: f32) {
+        let position = match self.get() {
+            Some(mean) => mean + wrapped(position - mean),
+            None => position,
+        };
+        self.add(position);
+    }
+}
+
+/// a distance between two track positions the shorter way round, -0.5 to 0.5.
+fn wrapped(distance: f32) -> f32 {
+    (distance + 0.5).rem_euclid(1.0) - 0.5
+}
+
+impl DriverProfile {
+    /// a profile of some laps. `None` if none of them has any samples.
+    /// Speeds and revs that aren't numbers are passed over; a corner without
+    /// a speed in any lap is left out.
+    ///
+    /// * `laps`: laps of one track, e.g. the valid ones from `Trace::laps` of
+    ///   each recorded session.
+    /// * `rules`: what counts as a corner.
+    pub fn build(laps: &[Trace], rules: &CornerRules) -> Option<Self> {
+        let mut brake_points: Vec<Mean> = Vec::new();
+        let mut min_speeds: Vec<Mean> = Vec::new();
+        let mut upshift_rpm = Mean::default();
+        let (mut smoothness, mut reversals) = (Mean::default(), Mean::default());
+        let (mut overlap, mut coasting) = (Mean::default(), Mean::default());
+        let mut used = 0;
+
+        for lap in laps {
+            let samples = lap.samples();
+            let pedals = PedalReport::of(lap, &PedalRules::default());
+            let (Some(pedals), Some(brakes)) = (pedals, BrakeReport::of(lap, rules)) else {
+                continue;
+            };
+            used += 1;
+
+            let corners = Corner::find(lap, rules);
+            if min_speeds.len() < corners.len() {
+                min_speeds.resize(corners.len(), Mean::default());
+                brake_points.resize(corners.len(), Mean::default());
+            }
+            for corner in &corners {
+                let min_kmh = samples[corner.samples.clone()]
+                    .iter()
+                    .map(|s| s.car.speed_kmh)
+                    .filter(|kmh| !kmh.is_nan())
+                    .reduce(f32::min);
+                if let Some(min_kmh) = min_kmh {
+                    min_speeds[corner.number - 1].add(min_kmh);
+                }
+                if let Some(zone) = brakes.into_corner(corner.number).next() {
+                    brake_points[corner.number - 1].add_position(zone.from);
+                }
+            }
+
+            for pair in samples.windows(2) {
+                if pair[1].car.gear > pair[0].car.gear && !pair[0].car.engine_rpm.is_nan() {
+                    upshift_rpm.add(pair[0].car.engine_rpm);
+                }
+            }
+
+            let steering = Smoothness::of(samples);
+            smoothness.add(steering.smoothness);
+            reversals.add(steering.per_second);
+            overlap.add(pedals.overlap);
+            coasting.add(pedals.coasting);
+        }
+
+        if used == 0 {
+            return None;
+        }
+
+        let corners = min_speeds
+            .iter()
+            .zip(&brake_points)
+            .enumerate()
+            .filter_map(|(i, (min_kmh, brake_point))| {
+                Some(CornerProfile {
+                    number: i + 1,
+                    brake_point: brake_point.get().map(|at| at.rem_euclid(1.0)),
+                    min_kmh: min_kmh.get()?,
+                })
+            })
+            .collect();
+
+        Some(Self {
+            laps: used,
+            corners,
+            upshift_rpm: upshift_rpm.get(),
+            smoothness: smoothness.get().unwrap_or_default(),
+            reversals_per_second: reversals.get().unwrap_or_default(),
+            overlap: overlap.get().unwrap_or_default(),
+            coasting: coasting.get().unwrap_or_default(),
+        })
+    }
+
+    /// one corner's profile.
+    ///
+    /// * `number`: the corner's number.
+    pub fn corner(&self, number: usize) -> Option<&CornerProfile> {
+        self.corners.iter().find(|corner| corner.number == number)
+    }
+
+    /// how another profile differs from this one.
+    ///
+    /// * `other`: the profile compared, e.g. the same driver weeks later.
+    pub fn compare(&self, other: &DriverProfile) -> ProfileDiff {
+        let corners = self
+            .corners
+            .iter()
+            .filter_map(|ours| {
+                let theirs = other.corner(ours.number)?;
+                Some(CornerDiff {
+                    number: ours.number,
+                    brake_point: ours
+                        .brake_point
+                        .zip(theirs.brake_point)
+                        .map(|(ours, theirs)| wrapped(theirs - ours)),
+                    min_kmh: theirs.min_kmh - ours.min_kmh,
+                })
+            })
+            .collect();
+
+        ProfileDiff {
+            corners,
+            upshift_rpm: self
+                .upshift_rpm
+                .zip(other.upshift_rpm)
+                .map(|(ours, theirs)| theirs - ours),
+            smoothness: other.smoothness - self.smoothness,
+            reversals_per_second: other.reversals_per_second - self.reversals_per_second,
+            overlap: other.overlap - self.overlap,
+            coasting: other.coasting - self.coasting,
+        }
+    }
+
+    /// writes the profile as text.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "{PROFILE_HEADER}")?;
+        writeln!(writer, "laps {}", self.laps)?;
+        if let Some(rpm) = self.upshift_rpm {
+            writeln!(writer, "upshift_rpm {rpm}")?;
+        }
+        writeln!(
+            writer,
+            "steering {} {}",
+            self.smoothness, self.reversals_per_second
+        )?;
+        writeln!(writer, "pedals {} {}", self.overlap, self.coasting)?;
+
+        for corner in &self.corners {
+            let brake_point = corner
+                .brake_point
+                .map_or_else(|| "-".to_string(), |at| at.to_string());
+            writeln!(
+                writer,
+                "corner {} {brake_point} {}",
+                corner.number, corner.min_kmh
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    /// reads a profile written by `write_to`.
+    ///
+    /// * `reader`: where to read it from.
+    pub fn read_from<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(PROFILE_HEADER) {
+            return Err(invalid("not an ac_lib driver profile"));
+        }
+
+        let mut profile = Self::default();
+        for line in lines {
+            let line = line?;
+            let fields: Vec<_> = line.split_whitespace().collect();
+
+            match fields[..] {
+                [] => {}
+                ["laps", laps] => profile.laps = number(laps)?,
+                ["upshift_rpm", rpm] => profile.upshift_rpm = Some(number(rpm)?),
+                ["steering", smoothness, reversals] => {
+                    profile.smoothness = number(smoothness)?;
+                    profile.reversals_per_second = number(reversals)?;
+                }
+                ["pedals", overlap, coasting] => {
+                    profile.overlap = number(overlap)?;
+                    profile.coasting = number(coasting)?;
+                }
+                ["corner", corner, brake_point, min_kmh] => profile.corners.push(CornerProfile {
+                    number: number(corner)?,
+                    brake_point: match brake_point {
+                        "-" => None,
+                        at => Some(number(at)?),
+                    },
+                    min_kmh: number(min_kmh)?,
+                }),
+                _ => return Err(invalid("unrecognised profile line")),
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{corners::CornerRules, fingerprint::DriverProfile, trace::Trace};
+
+    /// a lap with one corner: braking at `brake_at`, a minimum of `min_kmh`,
+    /// and an upshift at `shift_rpm` on the way out.
+    fn lap(lap_count: u32, brake_at: usize, min_kmh: f32, shift_rpm: f32) -> Trace {
+        let mut trace = Trace::new();
+        for i in 0..10 {
+            let cornering = (4..7).contains(&i);
+            let car = CarInfo {
+                lap_count,
+                car_pos_normalized: i as f32 / 10.0,
+                speed_kmh: if cornering { min_kmh } else { 200.0 },
+                brake: if (brake_at..4).contains(&i) { 1.0 } else { 0.0 },
+                gas: if i < brake_at || i >= 4 { 1.0 } else { 0.0 },
+                accg_horizontal: if cornering { 1.2 } else { 0.0 },
+                gear: if i < 8 { 3 } else { 4 },
+                engine_rpm: if i == 7 { shift_rpm } else { 6000.0 },
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_millis(250 * i as u64), car);
+        }
+
+        trace
+    }
+
+    /// a lap moved round the track by `offset`, wrapping at the line.
+    fn shifted(lap: &Trace, offset: f32) -> Trace {
+        let mut trace = Trace::new();
+        for sample in lap.samples() {
+            let mut car = sample.car.clone();
+            car.car_pos_normalized = (car.car_pos_normalized + offset).rem_euclid(1.0);
+            trace.push(sample.time, car);
+        }
+
+        trace
+    }
+
+    fn round_trip(profile: &DriverProfile) -> DriverProfile {
+        let mut text = Vec::new();
+        profile.write_to(&mut text).expect("written");
+        DriverProfile::read_from(&text[..]).expect("read back")
+    }
+
+    #[test]
+    fn no_laps_with_samples_have_no_profile() {
+        let rules = CornerRules::default();
+        assert_eq!(DriverProfile::build(&[], &rules), None);
+        assert_eq!(
+            DriverProfile::build(&[Trace::new(), Trace::new()], &rules),
+            None
+        );
+    }
+
+    #[test]
+    fn a_single_sample_has_no_corners_or_shifts() {
+        let mut single = Trace::new();
+        single.push(Duration::ZERO, CarInfo::default());
+
+        let profile = DriverProfile::build(&[single, Trace::new()], &CornerRules::default())
+            .expect("one lap");
+        assert_eq!(profile.laps, 1);
+        assert_eq!((profile.corners.len(), profile.upshift_rpm), (0, None));
+        assert_eq!(round_trip(&profile), profile);
+    }
+
+    #[test]
+    fn profiles_average_laps() {
+        let laps = [lap(1, 2, 80.0, 7000.0), lap(2, 2, 90.0, 7400.0)];
+        let profile = DriverProfile::build(&laps, &CornerRules::default()).expect("two laps");
+        assert_eq!(profile.laps, 2);
+        let corner = profile.corner(1).expect("one corner");
+        assert_eq!((corner.brake_point, corner.min_kmh), (Some(0.2), 85.0));
+        assert_eq!(profile.upshift_rpm, Some(7200.0));
+        assert_eq!(profile.corner(2), None);
+    }
+
+    #[test]
+    fn profiles_round_trip_through_text() {
+        let profile = DriverProfile::build(&[lap(1, 2, 80.0, 7000.0)], &CornerRules::default())
+            .expect("one lap");
+        assert_eq!(round_trip(&profile), profile);
+
+        assert!(DriverProfile::read_from(&b"ac_lib session 1\n"[..]).is_err());
+        assert!(DriverProfile::read_from(&b""[..]).is_err());
+        assert!(DriverProfile::read_from(&b"ac_lib profile 1\nlaps x\n"[..]).is_err());
+        assert!(DriverProfile::read_from(&b"ac_lib profile 1\nwings 3\n"[..]).is_err());
+    }
+
+    #[test]
+    fn profiles_compare_corner_by_corner() {
+        let rules = CornerRules::default();
+        let before = DriverProfile::build(&[lap(1, 2, 85.0, 7200.0)], &rules).expect("one lap");
+
+        // Weeks later: braking a tenth of a lap later and carrying 10 km/h more.
+        let after = DriverProfile::build(&[lap(9, 3, 95.0, 7200.0)], &rules).expect("one lap");
+        let diff = before.compare(&after);
+        let [corner] = &diff.corners[..] else {
+            panic!("expected one corner, got {:?}", diff.corners);
+        };
+        assert!(
+            corner
+                .brake_point
+                .is_some_and(|later| (later - 0.1).abs() < 1e-6)
+        );
+        assert_eq!((corner.min_kmh, diff.upshift_rpm), (10.0, Some(0.0)));
+
+        // A profile without the corner or a shift has nothing to set them against.
+        let empty = DriverProfile::default();
+        let diff = before.compare(&empty);
+        assert_eq!((diff.corners.len(), diff.upshift_rpm), (0, None));
+    }
+
+    #[test]
+    fn nan_speeds_and_revs_are_passed_over() {
+        let rules = CornerRules::default();
+        let mut noisy = Trace::new();
+        for (i, sample) in lap(1, 2, 80.0, f32::NAN).samples().iter().enumerate() {
+            let mut car = sample.car.clone();
+            if i == 5 {
+                car.speed_kmh = f32::NAN;
+            }
+            noisy.push(sample.time, car);
+        }
+        let profile = DriverProfile::build(&[noisy], &rules).expect("one lap");
+        assert_eq!(profile.corner(1).map(|c| c.min_kmh), Some(80.0));
+        assert_eq!(profile.upshift_rpm, None);
+
+        // A corner that never has a speed is left out.
+        let profile =
+            DriverProfile::build(&[lap(1, 2, f32::NAN, 7000.0)], &rules).expect("one lap");
+        assert_eq!(profile.corner(1), None);
+        assert_eq!(profile.upshift_rpm, Some(7000.0));
+    }
+
+    #[test]
+    fn brake_points_either_side_of_the_line_average_near_it() {
+        let rules = CornerRules::default();
+        // Braking at 0.2 of the lap, moved to 0.98 and to 0.02.
+        let early = shifted(&lap(1, 2, 80.0, 7000.0), 0.78);
+        let late = shifted(&lap(2, 2, 80.0, 7000.0), -0.18);
+        let near_line = |at: f32| at.min(1.0 - at) < 1e-3;
+
+        let profile =
+            DriverProfile::build(&[early.clone(), late.clone()], &rules).expect("two laps");
+        let brake_point = profile.corner(1).and_then(|c| c.brake_point);
+        assert!(brake_point.is_some_and(near_line), "{brake_point:?}");
+
+        let before = DriverProfile::build(&[early], &rules).expect("one lap");
+        let after = DriverProfile::build(&[late], &rules).expect("one lap");
+        let later = before.compare(&after).corners[0].brake_point;
+        assert!(
+            later.is_some_and(|later| (later - 0.04).abs() < 1e-3),
+            "{later:?}"
+        );
+    }
+}
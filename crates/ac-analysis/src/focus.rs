@@ -0,0 +1,267 @@
+//! Which car an auto-director should be showing. `SpectatorFocus` follows the
+//! outputs of the battle, standings and bests stages and suggests a change of
+//! focus when a more interesting car comes up: the chasing car in the closest
+//! battle first, then whoever just went purple, then the leader.
+
+use std::time::Duration;
+
+use ac_parser::Event;
+
+use crate::{
+    battles::{Battle, BattleEvent},
+    bests::BestEvent,
+    pipeline::{Output, Stage},
+    standings::StandingsEvent,
+};
+
+/// How long a focus is held before another car can take it, by default.
+const DEFAULT_MIN_HOLD: Duration = Duration::from_secs(10);
+/// How long a purple sector or fastest lap stays interesting, by default.
+const DEFAULT_PURPLE_FOR: Duration = Duration::from_secs(15);
+
+/// Why a car is worth watching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusReason {
+    /// It's chasing `ahead` in the closest battle on track.
+    Battle { ahead: i32 },
+    /// It just set a purple sector, counting from zero.
+    PurpleSector(usize),
+    /// It just set the session's fastest lap.
+    FastestLap,
+    /// It's leading.
+    Leader,
+}
+
+/// The car to show, and why.
+///
+/// * `car_id`: the car's `car_id_num`.
+/// * `reason`: why it was picked.
+/// * `since`: when it was picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Focus {
+    pub car_id: i32,
+    pub reason: FocusReason,
+    pub since: Duration,
+}
+
+/// A suggestion to switch to another car.
+///
+/// * `from`: the car focused until now, if any.
+/// * `to`: the new focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FocusChange {
+    pub from: Option<i32>,
+    pub to: Focus,
+}
+
+/// Picks the most interesting car from what the other stages report.
+///
+/// * `min_hold`: how long a focus is held before another car can take it.
+/// * `purple_for`: how long a purple sector or fastest lap stays interesting.
+/// * `current`: the car focused now.
+/// * `battles`: battles in progress.
+/// * `purple`: the latest purple sector or fastest lap, its car and when it was set.
+/// * `leader`: the car leading.
+#[derive(Debug, Clone)]
+pub struct SpectatorFocus {
+    min_hold: Duration,
+    purple_for: Duration,
+    current: Option<Focus>,
+    battles: Vec<Battle>,
+    purple: Option<(i32, FocusReason, Duration)>,
+    leader: Option<i32>,
+}
+
+impl Default for SpectatorFocus {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_HOLD, DEFAULT_PURPLE_FOR)
+    }
+}
+
+impl SpectatorFocus {
+    /// * `min_hold`: how long a focus is held before another car can take it.
+    /// * `purple_for`: how long a purple sector or fastest lap stays interesting.
+    pub fn new(min_hold: Duration, purple_for: Duration) -> Self {
+        Self {
+            min_hold,
+            purple_for,
+            current: None,
+            battles: Vec::new(),
+            purple: None,
+            leader: None,
+        }
+    }
+
+    /// takes note of another stage's output.
+    ///
+    /// * `at`: when it was produced, on any monotonic timeline.
+    /// * `output`: the output.
+    pub fn note(&mut self, at: Duration, output: &Output) {
+        match output {
+            Output::Battle(BattleEvent::BattleStarted(battle)) => self.battles.push(*battle),
+            Output::Battle(BattleEvent::BattleEnded(ended, _)) => {
+                self.battles
+                    .retain(|battle| (battle.ahead, battle.behind) != (ended.ahead, ended.behind));
+            }
+            Output::Best(BestEvent::FastestLap { best, .. }) => {
+                self.purple = Some((best.car_id, FocusReason::FastestLap, at));
+            }
+            Output::Best(BestEvent::PurpleSector { sector, best, .. }) => {
+                self.purple = Some((best.car_id, FocusReason::PurpleSector(*sector), at));
+            }
+            Output::Standings(StandingsEvent::PositionGained { car_id, to: 1, .. }) => {
+                self.leader = Some(*car_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// sets the leader directly, e.g. from `Standings::car_at(1)`.
+    ///
+    /// * `car_id`: the leader's `car_id_num`.
+    pub fn set_leader(&mut self, car_id: i32) {
+        self.leader = Some(car_id);
+    }
+
+    /// picks the most interesting car now. Returns a change if it differs from
+    /// the current focus and that has been held long enough.
+    ///
+    /// * `now`: the current time, on the same timeline as `note`.
+    pub fn choose(&mut self, now: Duration) -> Option<FocusChange> {
+        let (car_id, reason) = self.candidate(now)?;
+
+        if let Some(current) = self.current
+            && (current.car_id == car_id || now.saturating_sub(current.since) < self.min_hold)
+        {
+            return None;
+        }
+
+        let to = Focus {
+            car_id,
+            reason,
+            since: now,
+        };
+        let from = self.current.replace(to).map(|focus| focus.car_id);
+
+        Some(FocusChange { from, to })
+    }
+
+    /// the car focused now.
+    pub fn current(&self) -> Option<Focus> {
+        self.current
+    }
+
+    /// the most interesting car and why, ignoring how long the focus has been held.
+    fn candidate(&self, now: Duration) -> Option<(i32, FocusReason)> {
+        if let Some(battle) = self.battles.iter().min_by_key(|battle| battle.gap) {
+            return Some((
+                battle.behind,
+                FocusReason::Battle {
+                    ahead: battle.ahead,
+                },
+            ));
+        }
+
+        if let Some((car_id, reason, at)) = self.purple
+            && now.saturating_sub(at) <= self.purple_for
+        {
+            return Some((car_id, reason));
+        }
+
+        self.leader.map(|car_id| (car_id, FocusReason::Leader))
+    }
+}
+
+impl Stage for SpectatorFocus {
+    fn name(&self) -> &str {
+        "focus"
+    }
+
+    /// reads the outputs earlier stages produced for this event, so it goes
+    /// after the battles, standings and bests stages.
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event
+            && self.leader.is_none()
+        {
+            self.leader = Some(info.car_id_num);
+        }
+
+        for output in outputs.iter() {
+            self.note(at, output);
+        }
+        outputs.extend(self.choose(at).map(Output::Focus));
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod focus_tests {
+    use std::time::Duration;
+
+    use crate::{
+        battles::{Battle, BattleEvent},
+        bests::{Best, BestEvent},
+        focus::{FocusReason, SpectatorFocus},
+        pipeline::Output,
+    };
+
+    #[test]
+    fn battles_beat_purple_sectors_beat_the_leader() {
+        let secs = Duration::from_secs;
+        let mut focus = SpectatorFocus::new(secs(10), secs(15));
+
+        focus.set_leader(1);
+        let change = focus.choose(secs(0)).expect("the leader to start with");
+        assert_eq!((change.from, change.to.car_id), (None, 1));
+        assert_eq!(focus.choose(secs(1)), None, "already focused");
+
+        let purple = BestEvent::PurpleSector {
+            sector: 2,
+            best: Best {
+                car_id: 4,
+                time: secs(30),
+            },
+            previous: None,
+        };
+        focus.note(secs(5), &Output::Best(purple));
+        assert_eq!(focus.choose(secs(5)), None, "the leader is held for 10s");
+        let change = focus.choose(secs(12)).expect("the purple sector");
+        assert_eq!(change.from, Some(1));
+        assert_eq!(change.to.reason, FocusReason::PurpleSector(2));
+
+        let battle = |ahead, behind, gap| Battle {
+            ahead,
+            behind,
+            since: secs(20),
+            segment: 0,
+            gap: Duration::from_millis(gap),
+        };
+        focus.note(
+            secs(25),
+            &Output::Battle(BattleEvent::BattleStarted(battle(5, 6, 800))),
+        );
+        focus.note(
+            secs(25),
+            &Output::Battle(BattleEvent::BattleStarted(battle(2, 3, 300))),
+        );
+        let change = focus.choose(secs(25)).expect("the closest battle");
+        assert_eq!(change.to.car_id, 3);
+        assert_eq!(change.to.reason, FocusReason::Battle { ahead: 2 });
+
+        focus.note(
+            secs(40),
+            &Output::Battle(BattleEvent::BattleEnded(battle(2, 3, 300), secs(40))),
+        );
+        assert_eq!(focus.choose(secs(40)).map(|c| c.to.car_id), Some(6));
+        focus.note(
+            secs(60),
+            &Output::Battle(BattleEvent::BattleEnded(battle(5, 6, 800), secs(60))),
+        );
+        assert_eq!(
+            focus.choose(secs(60)).map(|c| c.to.reason),
+            Some(FocusReason::Leader),
+            "the purple sector has gone stale"
+        );
+    }
+}
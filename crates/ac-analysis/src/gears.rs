@@ -0,0 +1,198 @@
+//! How long and how far each gear is used, over a lap and in each part of the
+//! track, for checking gearing against a circuit: a gear that's barely used,
+//! or one held on the limiter down a straight, shows up straight away.
+
+use std::time::Duration;
+
+use crate::trace::Trace;
+
+/// Time and distance in one gear.
+///
+/// * `gear`: the gear, as AC reports it: 0 is reverse, 1 neutral, 2 first.
+/// * `time`: how long it was in.
+/// * `distance`: how far the car went in it, in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GearUsage {
+    pub gear: i32,
+    pub time: Duration,
+    pub distance: f32,
+}
+
+/// The gear usage of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `gears`: the usage of each gear that was used, lowest gear first.
+/// * `segments`: the same for each equal segment of the lap, from the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GearReport {
+    pub lap: u32,
+    pub gears: Vec<GearUsage>,
+    pub segments: Vec<Vec<GearUsage>>,
+}
+
+impl GearReport {
+    /// the time and distance in each gear over a lap. `None` for an empty lap.
+    /// A sample without a track position counts towards the lap but no segment.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `segments`: how many equal segments to split the lap into, by track position.
+    pub fn of(lap: &Trace, segments: u32) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+        let count = segments.max(1);
+
+        let mut report = Self {
+            lap: first.car.lap_count,
+            gears: Vec::new(),
+            segments: vec![Vec::new(); count as usize],
+        };
+
+        // Each sample's gear holds until the next sample.
+        for pair in samples.windows(2) {
+            let (sample, next) = (&pair[0], &pair[1]);
+            let time = next.time.saturating_sub(sample.time);
+            let distance = sample.car.speed_ms.max(0.0) * time.as_secs_f32();
+            let gear = sample.car.gear;
+            add(&mut report.gears, gear, time, distance);
+
+            let position = sample.car.car_pos_normalized;
+            if !position.is_nan() {
+                let segment = (position.clamp(0.0, 1.0) * count as f32) as usize;
+                let segment = segment.min(count as usize - 1);
+                add(&mut report.segments[segment], gear, time, distance);
+            }
+        }
+
+        Some(report)
+    }
+
+    /// the usage of one gear over the lap, if it was used.
+    ///
+    /// * `gear`: the gear, as AC reports it.
+    pub fn gear(&self, gear: i32) -> Option<&GearUsage> {
+        self.gears.iter().find(|usage| usage.gear == gear)
+    }
+}
+
+/// adds time and distance to a gear, keeping `usage` sorted by gear.
+fn add(usage: &mut Vec<GearUsage>, gear: i32, time: Duration, distance: f32) {
+    match usage.binary_search_by_key(&gear, |usage| usage.gear) {
+        Ok(i) => {
+            usage[i].time += time;
+            usage[i].distance += distance;
+        }
+        Err(i) => usage.insert(
+            i,
+            GearUsage {
+                gear,
+                time,
+                distance,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod gears_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        gears::{GearReport, GearUsage},
+        trace::Trace,
+    };
+
+    fn car(position: f32, gear: i32, speed_ms: f32) -> CarInfo {
+        CarInfo {
+            lap_count: 2,
+            car_pos_normalized: position,
+            gear,
+            speed_ms,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a lap sampled once a second.
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_secs(i as u64), car);
+        }
+        lap
+    }
+
+    fn usage(usage: &[GearUsage]) -> Vec<(i32, u64, f32)> {
+        usage
+            .iter()
+            .map(|usage| (usage.gear, usage.time.as_secs(), usage.distance))
+            .collect()
+    }
+
+    #[test]
+    fn empty_and_single_sample_laps_use_no_gear() {
+        assert_eq!(GearReport::of(&Trace::new(), 4), None);
+
+        let report = GearReport::of(&lap([car(0.5, 3, 20.0)]), 4).expect("one sample");
+        assert!(report.gears.is_empty(), "a sample holds until the next");
+        assert_eq!(report.segments, vec![Vec::new(); 4]);
+
+        let report = GearReport::of(&lap([car(0.5, 3, 20.0)]), 0).expect("one sample");
+        assert_eq!(report.segments.len(), 1, "at least one segment");
+    }
+
+    #[test]
+    fn standing_still_takes_time_but_no_distance() {
+        let report = GearReport::of(
+            &lap([car(0.1, 1, 0.0), car(0.1, 1, f32::NAN), car(0.1, 2, 0.0)]),
+            1,
+        )
+        .expect("three samples");
+        assert_eq!(usage(&report.gears), [(1, 2, 0.0)]);
+    }
+
+    #[test]
+    fn the_line_and_nan_positions_are_handled() {
+        let samples = [
+            car(0.98, 5, 10.0),
+            car(1.0, 5, 10.0),
+            car(0.0, 5, 10.0),
+            car(f32::NAN, 5, 10.0),
+            car(0.5, 5, 10.0),
+        ];
+        let report = GearReport::of(&lap(samples), 2).expect("five samples");
+
+        assert_eq!(usage(&report.gears), [(5, 4, 40.0)]);
+        let segments: Vec<_> = report.segments.iter().map(|s| usage(s)).collect();
+        assert_eq!(segments, [vec![(5, 1, 10.0)], vec![(5, 2, 20.0)]]);
+    }
+
+    #[test]
+    fn time_and_distance_add_up_per_gear_and_segment() {
+        // One second per sample: two seconds in fourth at 40 m/s, down to
+        // second through the hairpin at 15 m/s, then third at 30 m/s.
+        let samples = [
+            car(0.0, 5, 40.0),
+            car(0.3, 5, 40.0),
+            car(0.6, 3, 15.0),
+            car(0.7, 4, 30.0),
+            car(0.9, 4, 30.0),
+        ];
+
+        let report = GearReport::of(&lap(samples), 2).expect("not empty");
+        assert_eq!(report.lap, 2);
+        assert_eq!(
+            usage(&report.gears),
+            [(3, 1, 15.0), (4, 1, 30.0), (5, 2, 80.0)]
+        );
+        assert_eq!(report.gear(5).map(|usage| usage.distance), Some(80.0));
+        assert_eq!(report.gear(2), None);
+
+        // The first half is all fourth; the second has a second of each of the rest.
+        let [first, second] = &report.segments[..] else {
+            panic!("expected two segments");
+        };
+        assert_eq!((first.len(), first[0].gear), (1, 5));
+        assert_eq!(second.len(), 2);
+    }
+}
@@ -0,0 +1,292 @@
+//! How much of a practice session's gain in pace is the track rubbering in
+//! and how much is the driver, as a rough guide for when to run a low-fuel
+//! lap. Each lap's cornering grip is the mean of its corners' peak lateral G,
+//! and its inputs are scored by steering smoothness. Lap time is set against
+//! grip over the session: the time grip change explains is put down to the
+//! track, the rest to the driver. The grip index compares the session's last
+//! third of laps with its first.
+
+use std::time::Duration;
+
+use crate::{
+    corners::{Corner, CornerRules},
+    steering::Smoothness,
+    trace::Trace,
+};
+
+/// One lap's pace, grip and inputs.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `time`: how long the lap took.
+/// * `corner_g`: the mean of its corners' peak lateral G.
+/// * `smoothness`: the lap's steering smoothness, 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LapGrip {
+    pub lap: u32,
+    pub time: Duration,
+    pub corner_g: f32,
+    pub smoothness: f32,
+}
+
+/// How pace and grip moved over a session.
+///
+/// * `laps`: each lap used, in the order given.
+/// * `per_lap`: seconds each lap is slower than the one before; negative as the pace improves.
+/// * `track`: the part of `per_lap` the change in grip explains.
+/// * `driver`: the rest of `per_lap`.
+/// * `index`: the last third of laps' grip over the first third's; above 1 as the track comes in.
+/// * `consistency`: the last third's steering smoothness less the first third's;
+///   positive as the driver's inputs settle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GripEvolution {
+    pub laps: Vec<LapGrip>,
+    pub per_lap: f32,
+    pub track: f32,
+    pub driver: f32,
+    pub index: f32,
+    pub consistency: f32,
+}
+
+impl GripEvolution {
+    /// the grip evolution over a session's laps. `None` with fewer than three
+    /// laps that have corners in them.
+    ///
+    /// * `laps`: complete laps in the order driven, e.g. the valid ones from `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn of(laps: &[Trace], rules: &CornerRules) -> Option<Self> {
+        let laps: Vec<LapGrip> = laps
+            .iter()
+            .filter_map(|lap| {
+                let first = lap.samples().first()?;
+                let corners = Corner::find(lap, rules);
+                if corners.is_empty() {
+                    return None;
+                }
+
+                Some(LapGrip {
+                    lap: first.car.lap_count,
+                    time: lap.duration(),
+                    corner_g: corners.iter().map(|c| c.peak_g).sum::<f32>() / corners.len() as f32,
+                    smoothness: Smoothness::of(lap.samples()).smoothness,
+                })
+            })
+            .collect();
+        if laps.len() < 3 {
+            return None;
+        }
+
+        let per_lap = slope(laps.iter().enumerate().map(|(i, l)| (i as f32, seconds(l))));
+        let grip_per_lap = slope(laps.iter().enumerate().map(|(i, l)| (i as f32, l.corner_g)));
+        let per_g = slope(laps.iter().map(|l| (l.corner_g, seconds(l))));
+
+        // Grip can't explain more than the whole change, nor a change the other way.
+        let track = (per_g * grip_per_lap).clamp(per_lap.min(0.0), per_lap.max(0.0));
+
+        let third = (laps.len() / 3).max(1);
+        let (early, late) = (&laps[..third], &laps[laps.len() - third..]);
+        let mean = |laps: &[LapGrip], value: fn(&LapGrip) -> f32| {
+            laps.iter().map(value).sum::<f32>() / laps.len() as f32
+        };
+        let early_g = mean(early, |l| l.corner_g);
+
+        Some(Self {
+            per_lap,
+            track,
+            driver: per_lap - track,
+            index: if early_g > 0.0 {
+                mean(late, |l| l.corner_g) / early_g
+            } else {
+                1.0
+            },
+            consistency: mean(late, |l| l.smoothness) - mean(early, |l| l.smoothness),
+            laps,
+        })
+    }
+}
+
+/// a lap's time in seconds.
+fn seconds(lap: &LapGrip) -> f32 {
+    lap.time.as_secs_f32()
+}
+
+/// the least-squares slope of y against x, 0 if x doesn't vary.
+fn slope(points: impl Iterator<Item = (f32, f32)> + Clone) -> f32 {
+    let n = points.clone().count() as f32;
+    let (sum_x, sum_y) = points
+        .clone()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+    let (covariance, variance) = points.fold((0.0, 0.0), |(cov, var), (x, y)| {
+        let dx = x - mean_x;
+        (cov + dx * (y - mean_y), var + dx * dx)
+    });
+
+    if variance > f32::EPSILON {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod grip_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{corners::CornerRules, grip::GripEvolution, trace::Trace};
+
+    /// a lap with one corner at `g`, lasting `seconds`.
+    fn lap(lap_count: u32, g: f32, seconds: f32) -> Trace {
+        let mut trace = Trace::new();
+        for (i, accg_horizontal) in [0.0, g, g, g, g, 0.0].into_iter().enumerate() {
+            let car = CarInfo {
+                lap_count,
+                accg_horizontal,
+                steer: accg_horizontal * 30.0,
+                ..CarInfo::default()
+            };
+            let at = seconds * i as f32 / 5.0;
+            trace.push(Duration::from_secs_f32(at), car);
+        }
+
+        trace
+    }
+
+    fn near(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    /// six laps, each `g_step` G more and `time_step` seconds slower than the last.
+    fn session(g_step: f32, time_step: f32) -> Vec<Trace> {
+        (0..6)
+            .map(|i| lap(i + 1, 1.5 + g_step * i as f32, 100.0 + time_step * i as f32))
+            .collect()
+    }
+
+    #[test]
+    fn fewer_than_three_cornering_laps_have_no_evolution() {
+        let rules = CornerRules::default();
+        assert_eq!(GripEvolution::of(&[], &rules), None);
+
+        let laps = session(0.0, 0.0);
+        assert_eq!(GripEvolution::of(&laps[..2], &rules), None);
+
+        // A straight lap, an empty one and a single sample have no corners to count.
+        let mut single = Trace::new();
+        single.push(Duration::ZERO, CarInfo::default());
+        let with_gaps = [
+            laps[0].clone(),
+            lap(2, 0.2, 100.0),
+            Trace::new(),
+            single,
+            laps[1].clone(),
+        ];
+        assert_eq!(GripEvolution::of(&with_gaps, &rules), None);
+    }
+
+    #[test]
+    fn laps_without_corners_are_left_out() {
+        let mut laps = session(0.0, -0.4);
+        laps.insert(2, lap(99, 0.2, 50.0));
+
+        let evolution = GripEvolution::of(&laps, &CornerRules::default()).expect("six laps");
+        let numbers: Vec<u32> = evolution.laps.iter().map(|l| l.lap).collect();
+        assert_eq!(numbers, [1, 2, 3, 4, 5, 6]);
+        assert!(near(evolution.per_lap, -0.4), "{evolution:?}");
+    }
+
+    #[test]
+    fn a_steady_session_has_no_evolution_to_explain() {
+        let evolution =
+            GripEvolution::of(&session(0.0, 0.0), &CornerRules::default()).expect("six laps");
+        assert_eq!(
+            (evolution.per_lap, evolution.track, evolution.driver),
+            (0.0, 0.0, 0.0)
+        );
+        assert_eq!((evolution.index, evolution.consistency), (1.0, 0.0));
+    }
+
+    #[test]
+    fn pace_that_follows_grip_is_put_down_to_the_track() {
+        // Each lap pulls 0.02 G more and is 0.4 s quicker: 20 s per G.
+        let evolution =
+            GripEvolution::of(&session(0.02, -0.4), &CornerRules::default()).expect("six laps");
+        assert_eq!(evolution.laps.len(), 6);
+        assert!(near(evolution.per_lap, -0.4), "{evolution:?}");
+        assert!(near(evolution.track, -0.4) && near(evolution.driver, 0.0));
+        assert!(near(evolution.index, 1.59 / 1.51), "{evolution:?}");
+        assert!(near(evolution.consistency, 0.0));
+    }
+
+    #[test]
+    fn pace_without_a_change_in_grip_is_the_driver() {
+        let evolution =
+            GripEvolution::of(&session(0.0, -0.4), &CornerRules::default()).expect("six laps");
+        assert!(near(evolution.track, 0.0) && near(evolution.driver, -0.4));
+        assert_eq!(evolution.index, 1.0);
+    }
+
+    #[test]
+    fn grip_cant_explain_more_than_the_whole_change() {
+        // The grip steps up a second a lap, but the last lap gives half of it back.
+        let laps: Vec<_> = [
+            (1.5, 100.0),
+            (1.5, 100.0),
+            (1.6, 99.0),
+            (1.6, 99.0),
+            (1.6, 99.0),
+            (1.6, 99.5),
+        ]
+        .into_iter()
+        .zip(1..)
+        .map(|((g, seconds), lap_count)| lap(lap_count, g, seconds))
+        .collect();
+
+        let evolution = GripEvolution::of(&laps, &CornerRules::default()).expect("six laps");
+        assert!(near(evolution.per_lap, -1.1 / 7.0), "{evolution:?}");
+        assert_eq!(
+            (evolution.track, evolution.driver),
+            (evolution.per_lap, 0.0)
+        );
+    }
+
+    #[test]
+    fn nan_readings_dont_reach_the_evolution() {
+        let mut laps = session(0.02, -0.4);
+        for lap in &mut laps {
+            let mut noisy = Trace::new();
+            for (i, sample) in lap.samples().iter().enumerate() {
+                let mut car = sample.car.clone();
+                if i == 0 {
+                    car.accg_horizontal = f32::NAN;
+                    car.steer = f32::NAN;
+                }
+                noisy.push(sample.time, car);
+            }
+            *lap = noisy;
+        }
+
+        let evolution = GripEvolution::of(&laps, &CornerRules::default()).expect("six laps");
+        assert!(near(evolution.per_lap, -0.4), "{evolution:?}");
+        assert!(near(evolution.index, 1.59 / 1.51), "{evolution:?}");
+        assert!(evolution.consistency.is_finite());
+    }
+
+    #[test]
+    fn laps_split_from_a_session_trace_are_timed_from_their_line() {
+        let mut trace = Trace::new();
+        let mut at = Duration::ZERO;
+        for lap in session(0.0, -0.4) {
+            for sample in lap.samples() {
+                trace.push(at + sample.time, sample.car.clone());
+            }
+            at += lap.duration() + Duration::from_millis(10);
+        }
+
+        let evolution =
+            GripEvolution::of(&trace.laps(), &CornerRules::default()).expect("six laps");
+        assert_eq!(evolution.laps[0].time, Duration::from_secs(100));
+        assert!(near(evolution.per_lap, -0.4), "{evolution:?}");
+    }
+}
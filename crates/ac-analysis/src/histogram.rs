@@ -0,0 +1,356 @@
+//! How much of the time a channel spends in each range: throttle position
+//! distribution, steering angle spread, RPM residency. A `Histogram` counts one
+//! channel into evenly sized bins; `LapHistograms` keeps one per lap and one
+//! per stint, and a `Summarizer` can carry them in its summaries.
+
+use std::io::{self, Write};
+
+use ac_parser::{CarInfo, Channel, Event};
+
+/// Counts of a channel's values in evenly sized bins.
+///
+/// * `channel`: the channel counted.
+/// * `min`, `width`: where the first bin starts and how wide each is.
+/// * `counts`: samples per bin.
+/// * `below`, `above`: samples outside every bin.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Histogram {
+    channel: Channel,
+    min: f32,
+    width: f32,
+    counts: Vec<u64>,
+    below: u64,
+    above: u64,
+}
+
+impl Histogram {
+    /// * `channel`: the channel to count, e.g. `Channel::Gas`.
+    /// * `min`, `max`: the range the bins cover, e.g. 0 and 1 for pedals.
+    /// * `bins`: how many bins the range splits into; at least one.
+    pub fn new(channel: Channel, min: f32, max: f32, bins: usize) -> Self {
+        let bins = bins.max(1);
+        Self {
+            channel,
+            min,
+            width: (max - min) / bins as f32,
+            counts: vec![0; bins],
+            below: 0,
+            above: 0,
+        }
+    }
+
+    /// pedal position in tenths.
+    ///
+    /// * `channel`: `Channel::Gas`, `Channel::Brake` or `Channel::Clutch`.
+    pub fn pedal(channel: Channel) -> Self {
+        Self::new(channel, 0.0, 1.0, 10)
+    }
+
+    /// an empty histogram with the same channel and bins.
+    pub fn cleared(&self) -> Self {
+        Self {
+            counts: vec![0; self.counts.len()],
+            below: 0,
+            above: 0,
+            ..self.clone()
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// counts a value. NaN, from a field that didn't read as a number, isn't counted.
+    ///
+    /// * `value`: a value of the channel.
+    pub fn add(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+
+        // A range of zero width divides zero by zero at its only value.
+        let bin = ((value - self.min) / self.width).floor();
+        if value < self.min {
+            self.below += 1;
+        } else if bin.is_nan() || bin as usize >= self.counts.len() {
+            // The top edge belongs to the last bin.
+            let top = self.min + self.width * self.counts.len() as f32;
+            match self.counts.last_mut() {
+                Some(last) if value <= top => *last += 1,
+                _ => self.above += 1,
+            }
+        } else {
+            self.counts[bin as usize] += 1;
+        }
+    }
+
+    /// counts the channel's value in a CarInfo.
+    ///
+    /// * `car`: the CarInfo.
+    pub fn add_car(&mut self, car: &CarInfo) {
+        self.add(self.channel.value(car));
+    }
+
+    /// adds another histogram's counts, e.g. to sum laps into a stint. Does
+    /// nothing unless both have the same channel and bins.
+    ///
+    /// * `other`: the histogram to add.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.cleared() != self.cleared() {
+            return;
+        }
+
+        self.counts
+            .iter_mut()
+            .zip(&other.counts)
+            .for_each(|(count, other)| *count += other);
+        self.below += other.below;
+        self.above += other.above;
+    }
+
+    /// every bin as `(from, to, count)`.
+    pub fn bins(&self) -> impl Iterator<Item = (f32, f32, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| {
+            let from = self.min + self.width * i as f32;
+            (from, from + self.width, count)
+        })
+    }
+
+    /// samples below and above the bins.
+    pub fn outside(&self) -> (u64, u64) {
+        (self.below, self.above)
+    }
+
+    /// every sample counted, inside the bins or not.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum::<u64>() + self.below + self.above
+    }
+
+    /// the share of samples in each bin, 0 to 1.
+    pub fn shares(&self) -> Vec<f32> {
+        let total = self.total().max(1) as f32;
+        self.counts
+            .iter()
+            .map(|&count| count as f32 / total)
+            .collect()
+    }
+
+    /// writes the bins as CSV with a `from,to,count` header.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "from,to,count")?;
+        for (from, to, count) in self.bins() {
+            writeln!(writer, "{from},{to},{count}")?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// Histograms of the same channels per lap and per stint. A stint ends when
+/// the car enters the pits.
+///
+/// * `templates`: the histograms to fill, empty.
+/// * `laps`: each lap's lap counter and histograms, oldest first.
+/// * `stints`: each stint's histograms, oldest first; the last is the stint in progress.
+/// * `in_pit`: whether the car was in the pits on the last CarInfo.
+#[derive(Debug, Clone)]
+pub struct LapHistograms {
+    templates: Vec<Histogram>,
+    laps: Vec<(u32, Vec<Histogram>)>,
+    stints: Vec<Vec<Histogram>>,
+    in_pit: bool,
+}
+
+impl LapHistograms {
+    /// * `histograms`: the channels and bins to count, e.g.
+    ///   `[Histogram::pedal(Channel::Gas), Histogram::new(Channel::EngineRpm, 0.0, 9000.0, 18)]`.
+    pub fn new(histograms: &[Histogram]) -> Self {
+        Self {
+            templates: histograms.iter().map(Histogram::cleared).collect(),
+            laps: Vec::new(),
+            stints: Vec::new(),
+            in_pit: false,
+        }
+    }
+
+    /// counts a CarInfo into its lap and stint; other events are ignored.
+    ///
+    /// * `event`: the event.
+    pub fn update(&mut self, event: &Event) {
+        let Event::CarInfo(car) = event else {
+            return;
+        };
+
+        if self
+            .laps
+            .last()
+            .is_none_or(|(lap, _)| *lap != car.lap_count)
+        {
+            self.laps.push((car.lap_count, self.templates.clone()));
+        }
+        if self.stints.is_empty() || (car.is_in_pit && !self.in_pit) {
+            self.stints.push(self.templates.clone());
+        }
+        self.in_pit = car.is_in_pit;
+
+        let (_, lap) = self.laps.last_mut().expect("just pushed");
+        let stint = self.stints.last_mut().expect("just pushed");
+        for histogram in lap.iter_mut().chain(stint) {
+            histogram.add_car(car);
+        }
+    }
+
+    /// each lap's lap counter and histograms, oldest first.
+    pub fn laps(&self) -> &[(u32, Vec<Histogram>)] {
+        &self.laps
+    }
+
+    /// each stint's histograms, oldest first.
+    pub fn stints(&self) -> &[Vec<Histogram>] {
+        &self.stints
+    }
+}
+
+#[cfg(test)]
+mod histogram_tests {
+    use ac_parser::{CarInfo, Channel, Event, LapInfo};
+
+    use crate::histogram::{Histogram, LapHistograms};
+
+    fn counts(histogram: &Histogram) -> Vec<u64> {
+        histogram.bins().map(|(_, _, count)| count).collect()
+    }
+
+    fn car(lap_count: u32, gas: f32, is_in_pit: bool) -> Event {
+        Event::CarInfo(CarInfo {
+            lap_count,
+            gas,
+            is_in_pit,
+            ..CarInfo::default()
+        })
+    }
+
+    #[test]
+    fn an_empty_histogram_has_bins_but_no_shares() {
+        let gas = Histogram::pedal(Channel::Gas);
+        assert_eq!(gas.total(), 0);
+        assert_eq!(gas.shares(), [0.0; 10], "no division by zero");
+
+        let mut csv = Vec::new();
+        gas.write_csv(&mut csv).expect("failed to write");
+        let csv = String::from_utf8(csv).expect("not UTF-8");
+        assert_eq!(csv.lines().count(), 11, "{csv}");
+        assert!(csv.starts_with("from,to,count\n0,0.1,0\n"), "{csv}");
+
+        assert_eq!(Histogram::new(Channel::Gas, 0.0, 1.0, 0).bins().count(), 1);
+    }
+
+    #[test]
+    fn edges_belong_to_the_bin_above_except_the_top() {
+        let mut gas = Histogram::pedal(Channel::Gas);
+        for value in [0.0, 0.5, 1.0, -0.1, 1.2] {
+            gas.add(value);
+        }
+
+        assert_eq!(counts(&gas), [1, 0, 0, 0, 0, 1, 0, 0, 0, 1]);
+        assert_eq!(gas.outside(), (1, 1));
+        assert_eq!(gas.total(), 5);
+    }
+
+    #[test]
+    fn a_single_sample_takes_every_share() {
+        let mut rpm = Histogram::new(Channel::EngineRpm, 0.0, 9000.0, 9);
+        rpm.add(4500.0);
+
+        assert_eq!(counts(&rpm), [0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(rpm.shares()[4], 1.0);
+    }
+
+    #[test]
+    fn nan_and_zero_width_ranges_are_handled() {
+        let mut gas = Histogram::pedal(Channel::Gas);
+        gas.add(f32::NAN);
+        assert_eq!(gas.total(), 0, "NaN isn't counted anywhere");
+
+        let mut flat = Histogram::new(Channel::Gear, 1.0, 1.0, 4);
+        for value in [0.0, 1.0, 2.0] {
+            flat.add(value);
+        }
+        assert_eq!(counts(&flat), [0, 0, 0, 1]);
+        assert_eq!(flat.outside(), (1, 1));
+    }
+
+    #[test]
+    fn only_matching_histograms_merge() {
+        let mut stint = Histogram::pedal(Channel::Gas);
+        let mut lap = Histogram::pedal(Channel::Gas);
+        lap.add(0.25);
+        stint.merge(&lap);
+        stint.merge(&lap);
+        assert_eq!(stint.total(), 2);
+
+        let mut brake = Histogram::pedal(Channel::Brake);
+        brake.add(0.25);
+        stint.merge(&brake);
+        stint.merge(&Histogram::new(Channel::Gas, 0.0, 1.0, 4));
+        assert_eq!(stint.total(), 2, "other channels and bins are left out");
+    }
+
+    #[test]
+    fn no_car_info_means_no_laps_or_stints() {
+        let mut histograms = LapHistograms::new(&[Histogram::pedal(Channel::Gas)]);
+        histograms.update(&Event::LapInfo(LapInfo::default()));
+
+        assert!(histograms.laps().is_empty());
+        assert!(histograms.stints().is_empty());
+    }
+
+    #[test]
+    fn a_restarted_lap_counter_starts_a_new_lap() {
+        let mut histograms = LapHistograms::new(&[Histogram::pedal(Channel::Gas)]);
+        for event in [
+            car(3, 1.0, false),
+            car(4, 1.0, false),
+            car(0, 0.5, false),
+            car(0, 0.5, false),
+        ] {
+            histograms.update(&event);
+        }
+
+        let laps: Vec<_> = histograms
+            .laps()
+            .iter()
+            .map(|(lap, histograms)| (*lap, histograms[0].total()))
+            .collect();
+        assert_eq!(laps, [(3, 1), (4, 1), (0, 2)]);
+        assert_eq!(histograms.stints().len(), 1);
+    }
+
+    #[test]
+    fn stints_split_when_the_car_enters_the_pits() {
+        let mut histograms = LapHistograms::new(&[Histogram::pedal(Channel::Gas)]);
+        for event in [
+            car(0, 1.0, false),
+            car(1, 0.0, true),
+            car(1, 0.0, true),
+            car(1, 0.45, false),
+            car(2, 0.0, true),
+        ] {
+            histograms.update(&event);
+        }
+
+        let stints: Vec<_> = histograms
+            .stints()
+            .iter()
+            .map(|h| (h[0].total(), h[0].shares()[9]))
+            .collect();
+        assert_eq!(stints, [(1, 1.0), (3, 0.0), (1, 0.0)]);
+    }
+}
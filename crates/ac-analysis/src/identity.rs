@@ -0,0 +1,203 @@
+//! Who is driving which car. Spot updates only identify cars by `car_id_num`,
+//! so leaderboards and gap displays look names up here: learned from each
+//! LapInfo, or seeded from the server's `entry_list.ini` before the first lap.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use ac_parser::{Event, LapInfo, Name, Redactor};
+
+use crate::pipeline::{Output, Stage};
+
+/// The names attached to a car.
+///
+/// * `driver_name`: who is driving it; empty if unknown.
+/// * `car_name`: the car model; empty if unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub driver_name: Name,
+    pub car_name: Name,
+}
+
+/// Names for every car seen so far, by `car_id_num`.
+#[derive(Debug, Clone, Default)]
+pub struct IdentityRegistry {
+    cars: BTreeMap<i32, Identity>,
+}
+
+impl IdentityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// seeds the registry from the contents of an AC server `entry_list.ini`,
+    /// where `[CAR_n]` holds the entry for car id `n`.
+    ///
+    /// * `ini`: the file's contents.
+    pub fn from_entry_list(ini: &str) -> Self {
+        let mut registry = Self::new();
+        registry.load_entry_list(ini);
+        registry
+    }
+
+    /// adds the entries of an `entry_list.ini`, keeping names already learned
+    /// from LapInfo.
+    ///
+    /// * `ini`: the file's contents.
+    pub fn load_entry_list(&mut self, ini: &str) {
+        let mut car = None;
+
+        for line in ini.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                car = section
+                    .strip_prefix("CAR_")
+                    .and_then(|id| id.parse::<i32>().ok());
+                continue;
+            }
+
+            let (Some(id), Some((key, value))) = (car, line.split_once('=')) else {
+                continue;
+            };
+            let identity = self.cars.entry(id).or_default();
+            let field = match key.trim() {
+                "DRIVERNAME" => &mut identity.driver_name,
+                "MODEL" => &mut identity.car_name,
+                _ => continue,
+            };
+
+            if field.is_empty() {
+                *field = truncated(value.trim());
+            }
+        }
+    }
+
+    /// learns the names in a LapInfo. Returns whether they changed what's known about the car.
+    ///
+    /// * `info`: the LapInfo.
+    pub fn learn(&mut self, info: &LapInfo) -> bool {
+        let identity = self.cars.entry(info.car_id_num).or_default();
+        let mut changed = false;
+
+        for (field, name) in [
+            (&mut identity.driver_name, &info.driver_name),
+            (&mut identity.car_name, &info.car_name),
+        ] {
+            if !name.is_empty() && field != name {
+                *field = *name;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// a copy with every driver's name redacted, for leaderboards and reports
+    /// that get shared. Pass the redactor the recordings went through so the
+    /// pseudonyms match.
+    ///
+    /// * `redactor`: how names are redacted.
+    pub fn redacted(&self, redactor: &mut Redactor) -> Self {
+        let cars = self
+            .cars
+            .iter()
+            .map(|(&id, identity)| {
+                let identity = Identity {
+                    driver_name: redactor.driver(&identity.driver_name),
+                    car_name: identity.car_name,
+                };
+                (id, identity)
+            })
+            .collect();
+
+        Self { cars }
+    }
+
+    pub fn get(&self, car_id: i32) -> Option<&Identity> {
+        self.cars.get(&car_id)
+    }
+
+    /// the driver's name, or `Car <id>` if it isn't known yet.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    pub fn display_name(&self, car_id: i32) -> String {
+        match self.get(car_id) {
+            Some(identity) if !identity.driver_name.is_empty() => identity.driver_name.to_string(),
+            _ => format!("Car {car_id}"),
+        }
+    }
+
+    /// every known car and its names, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &Identity)> {
+        self.cars.iter().map(|(&id, identity)| (id, identity))
+    }
+
+    pub fn len(&self) -> usize {
+        self.cars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cars.is_empty()
+    }
+}
+
+impl Stage for IdentityRegistry {
+    fn name(&self) -> &str {
+        "identities"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            self.learn(info);
+        }
+
+        true
+    }
+}
+
+/// a name, cut short at a char boundary if it won't fit.
+///
+/// * `name`: the name.
+fn truncated(name: &str) -> Name {
+    let mut end = name.len().min(Name::new().capacity());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    Name::from(&name[..end]).expect("cut to capacity")
+}
+
+#[cfg(test)]
+mod identity_tests {
+    use ac_parser::{LapInfo, Name, Redaction, Redactor};
+
+    use crate::identity::IdentityRegistry;
+
+    #[test]
+    fn lap_info_names_fill_in_and_override_the_entry_list() {
+        let mut registry = IdentityRegistry::from_entry_list(
+            "[CAR_0]\nMODEL=ks_mazda_mx5_cup\nDRIVERNAME=Ana\n\n[CAR_1]\nMODEL=bmw_m3_e30\nDRIVERNAME=\n\n[SERVER]\nNAME=ignored\n",
+        );
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.display_name(0), "Ana");
+        assert_eq!(registry.display_name(1), "Car 1");
+
+        let lap = LapInfo {
+            car_id_num: 1,
+            driver_name: Name::from("Ben").expect("short name"),
+            ..LapInfo::default()
+        };
+        assert!(registry.learn(&lap));
+        assert!(!registry.learn(&lap), "nothing new the second time");
+        assert_eq!(registry.display_name(1), "Ben");
+        assert_eq!(
+            registry.get(1).map(|car| car.car_name.as_str()),
+            Some("bmw_m3_e30")
+        );
+
+        registry.load_entry_list("[CAR_1]\nDRIVERNAME=Someone Else\n");
+        assert_eq!(registry.display_name(1), "Ben", "LapInfo names are kept");
+
+        let redacted = registry.redacted(&mut Redactor::new(Redaction::Pseudonymize));
+        assert_eq!(redacted.display_name(0), "Driver 1");
+        assert_eq!(redacted.display_name(1), "Driver 2");
+    }
+}
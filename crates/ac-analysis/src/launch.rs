@@ -0,0 +1,284 @@
+//! Standing starts, for race starts and drag testing alike. A launch is the
+//! car pulling away after sitting still outside the pits; from there to the
+//! target speed it reports the time taken, how much the driven wheels spun,
+//! how far the revs bogged down, and when each gear went in.
+
+use std::time::Duration;
+
+use ac_parser::{CarInfo, Wheel};
+
+use crate::trace::{Sample, Trace};
+
+/// Below this speed the car counts as standing still, in km/h.
+const STANDING_KMH: f32 = 1.0;
+
+/// What counts as a launch and how far it's followed.
+///
+/// * `target_kmh`: the speed the launch is timed to, e.g. 100 km/h.
+/// * `min_stop`: how long the car has to stand still first.
+/// * `window`: how long after pulling away the launch is followed if it never reaches the target.
+/// * `spin_slip`: rear slip ratio above which the wheels count as spinning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchRules {
+    pub target_kmh: f32,
+    pub min_stop: Duration,
+    pub window: Duration,
+    pub spin_slip: f32,
+}
+
+impl Default for LaunchRules {
+    fn default() -> Self {
+        Self {
+            target_kmh: 100.0,
+            min_stop: Duration::from_secs(1),
+            window: Duration::from_secs(10),
+            spin_slip: 0.15,
+        }
+    }
+}
+
+/// One standing start.
+///
+/// * `start`: the last moment the car stood still, on the trace's timeline.
+/// * `position`: where on track, 0 to 1.
+/// * `to_target`: how long it took to reach the target speed, if it did within the window.
+/// * `launch_rpm`: the revs as the car pulled away.
+/// * `bog_rpm`: how far the revs fell below that before the first upshift.
+/// * `peak_slip`: the most the rear wheels slipped, as a slip ratio.
+/// * `spin_time`: how long the rear wheels spun for.
+/// * `shifts`: each gear change as `(gear, time since start)`, gears as AC reports them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Launch {
+    pub start: Duration,
+    pub position: f32,
+    pub to_target: Option<Duration>,
+    pub launch_rpm: f32,
+    pub bog_rpm: f32,
+    pub peak_slip: f32,
+    pub spin_time: Duration,
+    pub shifts: Vec<(i32, Duration)>,
+}
+
+impl Launch {
+    /// every standing start in a trace. A speed that isn't a number neither
+    /// stops the car nor pulls it away.
+    ///
+    /// * `trace`: a session or a lap.
+    /// * `rules`: what counts as a launch.
+    pub fn find(trace: &Trace, rules: &LaunchRules) -> Vec<Launch> {
+        let samples = trace.samples();
+        let mut launches = Vec::new();
+        // When the car stopped, and the last sample it was still at.
+        let mut stopped: Option<(Duration, usize)> = None;
+
+        for (i, sample) in samples.iter().enumerate() {
+            let car = &sample.car;
+            if car.speed_kmh.is_nan() {
+                continue;
+            }
+            if car.speed_kmh < STANDING_KMH {
+                let since = stopped.map_or(sample.time, |(since, _)| since);
+                stopped = Some((since, i));
+                continue;
+            }
+
+            if let Some((since, last)) = stopped.take()
+                && samples[last].time.saturating_sub(since) >= rules.min_stop
+                && !car.is_in_pit
+            {
+                launches.push(Self::follow(&samples[last..], rules));
+            }
+        }
+
+        launches
+    }
+
+    /// measures a launch.
+    ///
+    /// * `samples`: from the last sample standing still onwards.
+    fn follow(samples: &[Sample], rules: &LaunchRules) -> Self {
+        let first = &samples[0];
+        let mut launch = Launch {
+            start: first.time,
+            position: first.car.car_pos_normalized,
+            to_target: None,
+            launch_rpm: first.car.engine_rpm,
+            bog_rpm: 0.0,
+            peak_slip: 0.0,
+            spin_time: Duration::ZERO,
+            shifts: Vec::new(),
+        };
+
+        let mut gear = first.car.gear;
+        let mut upshifted = false;
+        for pair in samples.windows(2) {
+            let (before, sample) = (&pair[0], &pair[1]);
+            let since = sample.time.saturating_sub(first.time);
+            if since > rules.window {
+                break;
+            }
+
+            let car = &sample.car;
+            if car.gear != gear {
+                upshifted |= car.gear > gear;
+                launch.shifts.push((car.gear, since));
+                gear = car.gear;
+            }
+            if !upshifted {
+                launch.bog_rpm = launch.bog_rpm.max(launch.launch_rpm - car.engine_rpm);
+            }
+
+            let slip = rear_slip(car);
+            launch.peak_slip = launch.peak_slip.max(slip);
+            if slip > rules.spin_slip {
+                launch.spin_time += sample.time.saturating_sub(before.time);
+            }
+
+            if car.speed_kmh >= rules.target_kmh {
+                let (v0, v1) = (before.car.speed_kmh, car.speed_kmh);
+                let share = (rules.target_kmh - v0) / (v1 - v0).max(f32::EPSILON);
+                let dt = sample.time.saturating_sub(before.time);
+                let reached = before.time.saturating_sub(first.time) + dt.mul_f32(share);
+                launch.to_target = Some(reached);
+                break;
+            }
+        }
+
+        launch
+    }
+}
+
+/// the slip ratio of the rear wheels, averaged.
+fn rear_slip(car: &CarInfo) -> f32 {
+    (car.slip_ratio[Wheel::RearLeft as usize] + car.slip_ratio[Wheel::RearRight as usize]) / 2.0
+}
+
+#[cfg(test)]
+mod launch_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        launch::{Launch, LaunchRules},
+        trace::Trace,
+    };
+
+    fn car(speed_kmh: f32, engine_rpm: f32, gear: i32, slip: f32) -> CarInfo {
+        CarInfo {
+            speed_kmh,
+            engine_rpm,
+            gear,
+            slip_ratio: [0.0, 0.0, slip, slip],
+            ..CarInfo::default()
+        }
+    }
+
+    /// a trace sampled every 500 ms at these speeds, in first at 6000 rpm.
+    fn trace(speeds: &[f32]) -> Trace {
+        let mut trace = Trace::new();
+        for (i, &speed) in speeds.iter().enumerate() {
+            trace.push(
+                Duration::from_millis(500 * i as u64),
+                car(speed, 6000.0, 2, 0.0),
+            );
+        }
+        trace
+    }
+
+    #[test]
+    fn no_launch_without_a_stop_and_a_pull_away() {
+        let rules = LaunchRules::default();
+        assert!(Launch::find(&Trace::new(), &rules).is_empty());
+        assert!(Launch::find(&trace(&[0.0]), &rules).is_empty());
+        assert!(
+            Launch::find(&trace(&[0.0; 10]), &rules).is_empty(),
+            "never moves"
+        );
+        assert!(
+            Launch::find(&trace(&[50.0, 60.0, 70.0]), &rules).is_empty(),
+            "rolling"
+        );
+    }
+
+    #[test]
+    fn a_nan_speed_neither_stops_nor_starts_the_car() {
+        let rules = LaunchRules::default();
+        let stutter = [0.0, 0.0, f32::NAN, 0.0, 0.0, 20.0];
+        let [launch] = &Launch::find(&trace(&stutter), &rules)[..] else {
+            panic!("expected one launch");
+        };
+        assert_eq!(
+            launch.start,
+            Duration::from_millis(2000),
+            "the stop carries on"
+        );
+
+        let glitch = [50.0, f32::NAN, 60.0];
+        assert!(Launch::find(&trace(&glitch), &rules).is_empty());
+    }
+
+    #[test]
+    fn a_launch_short_of_the_target_is_followed_for_the_window() {
+        let mut slow = vec![0.0; 3];
+        slow.extend((1..40).map(|i| i as f32 * 2.0));
+        let [launch] = &Launch::find(&trace(&slow), &LaunchRules::default())[..] else {
+            panic!("expected one launch");
+        };
+        assert_eq!(launch.to_target, None);
+    }
+
+    #[test]
+    fn pit_lane_starts_are_not_launches() {
+        let mut trace = Trace::new();
+        for (i, speed) in [0.0, 0.0, 0.0, 20.0].into_iter().enumerate() {
+            let car = CarInfo {
+                is_in_pit: true,
+                ..car(speed, 6000.0, 2, 0.0)
+            };
+            trace.push(Duration::from_millis(500 * i as u64), car);
+        }
+        assert!(Launch::find(&trace, &LaunchRules::default()).is_empty());
+    }
+
+    #[test]
+    fn a_standing_start_is_timed_to_100() {
+        // Sitting on 6000 rpm in first, bogging to 4500 rpm, spinning for a second,
+        // into second at 1.5 s and hitting 100 km/h between 3 s and 3.5 s.
+        let samples = [
+            car(0.0, 6000.0, 2, 0.0),
+            car(0.0, 6000.0, 2, 0.0),
+            car(0.0, 6000.0, 2, 0.0),
+            car(10.0, 4500.0, 2, 0.4),
+            car(30.0, 5500.0, 2, 0.2),
+            car(50.0, 7000.0, 3, 0.05),
+            car(70.0, 6000.0, 3, 0.0),
+            car(80.0, 6500.0, 3, 0.0),
+            car(90.0, 7000.0, 3, 0.0),
+            car(110.0, 7500.0, 3, 0.0),
+        ];
+
+        let mut trace = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            trace.push(Duration::from_millis(500 * i as u64), car);
+        }
+
+        let [launch] = &Launch::find(&trace, &LaunchRules::default())[..] else {
+            panic!("expected one launch");
+        };
+        assert_eq!(launch.start, Duration::from_millis(1000));
+        assert_eq!(launch.to_target, Some(Duration::from_millis(3250)));
+        assert_eq!((launch.launch_rpm, launch.bog_rpm), (6000.0, 1500.0));
+        assert_eq!(
+            (launch.peak_slip, launch.spin_time),
+            (0.4, Duration::from_secs(1))
+        );
+        assert_eq!(launch.shifts, [(3, Duration::from_millis(1500))]);
+
+        let fussy = LaunchRules {
+            min_stop: Duration::from_secs(2),
+            ..LaunchRules::default()
+        };
+        assert!(Launch::find(&trace, &fussy).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+//! Analysis over parsed telemetry, live or recorded: channels laid out over
+//! time and distance, lap splitting, diffs between laps or drivers, the
+//! analysis pipeline, and replaying recordings.
+
+mod aids;
+mod balance;
+#[cfg(feature = "rayon")]
+mod batch;
+mod battles;
+mod bests;
+mod brakes;
+mod camber;
+mod cars;
+#[cfg(feature = "plotters")]
+mod charts;
+mod clock;
+mod columns;
+mod corners;
+mod cues;
+mod degradation;
+mod delta_map;
+mod diff;
+mod downsample;
+mod fingerprint;
+mod focus;
+mod gears;
+mod grip;
+mod histogram;
+mod identity;
+mod launch;
+mod logbook;
+#[cfg(feature = "sqlite")]
+mod logbook_db;
+mod pause;
+mod pedals;
+mod pipeline;
+mod progress;
+mod racechrono;
+mod racing_line;
+mod replay;
+mod report;
+mod restart;
+mod ride_height;
+mod rolling;
+#[cfg(feature = "scripting")]
+mod script;
+mod sectors;
+mod slip_map;
+mod snapshot;
+mod spectrum;
+mod standings;
+mod steering;
+mod straights;
+mod summary;
+mod trace;
+mod trend;
+mod validity;
+mod video;
+#[cfg(feature = "xlsx")]
+mod workbook;
+
+pub use aids::{AidUsage, AidsReport};
+pub use balance::{AxleSlip, BALANCE_MARGIN, BalanceReport, Limit};
+#[cfg(feature = "rayon")]
+pub use batch::{BatchAnalysis, BatchReport, BatchSession};
+pub use battles::{Battle, BattleConfig, BattleDetector, BattleEvent};
+pub use bests::{Best, BestEvent, BestsSummary, SessionBests};
+pub use brakes::{BRAKE_ON, BrakeReport, BrakeZone};
+pub use camber::{CamberReport, WheelCamber};
+pub use cars::{CarChange, CarRegistry, CarState};
+#[cfg(feature = "plotters")]
+pub use charts::LapChart;
+pub use clock::{Clock, SystemClock, VirtualClock};
+pub use columns::{ColumnStats, ColumnStore};
+pub use corners::{Corner, CornerRules};
+pub use cues::{Cue, CueRules, CueSink, Cues};
+pub use degradation::{Degradation, OUTLIER_SHARE};
+pub use delta_map::{DeltaMap, DeltaSegment};
+pub use diff::{Alignment, ChannelDiff, DiffReport, diff};
+pub use downsample::Downsample;
+pub use fingerprint::{CornerDiff, CornerProfile, DriverProfile, ProfileDiff};
+pub use focus::{Focus, FocusChange, FocusReason, SpectatorFocus};
+pub use gears::{GearReport, GearUsage};
+pub use grip::{GripEvolution, LapGrip};
+pub use histogram::{Histogram, LapHistograms};
+pub use identity::{Identity, IdentityRegistry};
+pub use launch::{Launch, LaunchRules};
+pub use logbook::{Logbook, LogbookEntry, ProgressPoint, TrackBest};
+#[cfg(feature = "sqlite")]
+pub use logbook_db::LogbookDb;
+pub use pause::{PauseDetector, PauseEvent, PauseRules};
+pub use pedals::{PedalReport, PedalRules, PedalZone};
+pub use pipeline::{
+    Alert, AlertRule, Alerts, Delta, DeltaTimer, Filter, LapTime, LapTimer, Output, Pipeline,
+    PipelineBuilder, Record, Stage, Threshold,
+};
+pub use progress::{CarProgress, RaceLength, RaceMilestone, RaceProgress};
+pub use racechrono::RaceChronoExport;
+pub use racing_line::{LinePoint, RacingLine};
+pub use replay::{REPLAY_SPEEDS, Replayer};
+pub use report::SessionReport;
+pub use restart::{RestartCause, RestartDetector, SessionRestarted};
+pub use ride_height::{Bottoming, BottomingRules, RideHeightReport};
+pub use rolling::{Rolling, Window};
+#[cfg(feature = "scripting")]
+pub use script::Script;
+pub use sectors::Sectors;
+pub use slip_map::{SlipCell, SlipMap};
+pub use snapshot::{SessionSnapshot, Stint};
+pub use spectrum::{Peak, Spectrum, SpectrumConfig};
+pub use standings::{Standing, Standings, StandingsEvent};
+pub use steering::{REVERSAL_GAP, Smoothness, SteeringReport};
+pub use straights::{Straight, StraightRules, StraightSpeeds};
+#[cfg(feature = "schema")]
+pub use summary::summary_schema;
+pub use summary::{Summarizer, Summary};
+pub use trace::{Sample, Trace};
+pub use trend::{GapTrends, Trend};
+pub use validity::{InvalidReason, LapValidity, ValidityRules};
+pub use video::VideoSync;
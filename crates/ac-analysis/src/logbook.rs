@@ -0,0 +1,354 @@
+//! A logbook across every session driven, for career stats: which tracks and
+//! cars, personal bests, how far and how long. Each session is summed up from
+//! its recording into one entry, and queries run over the entries in memory;
+//! even years of track days stay a few thousand lines.
+//!
+//! It's kept as a line-based text file; `LogbookDb`, with the `sqlite`
+//! feature, keeps the same entries in SQLite and answers the same queries in
+//! SQL. The text file is versioned like session snapshots, and its fields are
+//! separated by tabs since track and car names hold spaces:
+//!
+//! ```text
+//! ac_lib logbook 1
+//! session <start ns> <track> <car> <laps> <best ms> <distance m> <duration ms>
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+use ac_parser::{Event, Recording, Timestamp};
+
+use crate::{
+    snapshot::{invalid, number},
+    trace::Trace,
+};
+
+/// The first line of every logbook.
+const LOGBOOK_HEADER: &str = "ac_lib logbook 1";
+
+/// One session in the logbook.
+///
+/// * `started_at`: when the session was recorded, if known.
+/// * `track`: the track, with its layout after a `/` if it has one.
+/// * `car`: the car's model name.
+/// * `laps`: laps completed.
+/// * `best`: AC's best lap of the session.
+/// * `distance_m`: how far the car went, from its speed.
+/// * `duration`: how long the session was recorded for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogbookEntry {
+    pub started_at: Option<Timestamp>,
+    pub track: String,
+    pub car: String,
+    pub laps: u32,
+    pub best: Option<Duration>,
+    pub distance_m: f32,
+    pub duration: Duration,
+}
+
+impl LogbookEntry {
+    /// sums up a recorded session. `None` without a handshake response to
+    /// name the track and car, or without any CarInfo.
+    ///
+    /// * `recording`: the session, as recorded by `Config::connect`.
+    pub fn from_recording(recording: &Recording) -> Option<Self> {
+        let handshake =
+            recording
+                .frames()
+                .iter()
+                .find_map(|frame| match Event::try_parse(&frame.packet) {
+                    Ok(Event::HandshakeResponse(handshake)) => Some(handshake),
+                    _ => None,
+                })?;
+        let track = match handshake.track_config.as_str() {
+            "" => handshake.track_name.to_string(),
+            layout => format!("{}/{layout}", handshake.track_name),
+        };
+
+        let trace = Trace::from_recording(recording);
+        let samples = trace.samples();
+        let (first, last) = (samples.first()?, samples.last()?);
+        let distance_m = samples
+            .windows(2)
+            .map(|pair| pair[0].car.speed_ms * (pair[1].time - pair[0].time).as_secs_f32())
+            .sum();
+
+        Some(Self {
+            started_at: trace.wall_clock(first),
+            track,
+            car: handshake.car_name.to_string(),
+            laps: last.car.lap_count.saturating_sub(first.car.lap_count),
+            best: (last.car.best_lap > 0).then(|| Duration::from_millis(last.car.best_lap.into())),
+            distance_m,
+            duration: trace.duration(),
+        })
+    }
+}
+
+/// The fastest lap driven at a track.
+///
+/// * `track`, `car`: where, and in what.
+/// * `time`: the lap time.
+/// * `set_at`: when the session it was set in was recorded, if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackBest {
+    pub track: String,
+    pub car: String,
+    pub time: Duration,
+    pub set_at: Option<Timestamp>,
+}
+
+/// One session's place in a driver's progress at a track in a car.
+///
+/// * `started_at`: when the session was recorded, if known.
+/// * `best`: the session's best lap, if it had one.
+/// * `personal_best`: the best lap up to and including this session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressPoint {
+    pub started_at: Option<Timestamp>,
+    pub best: Option<Duration>,
+    pub personal_best: Option<Duration>,
+}
+
+/// Every session driven, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Logbook {
+    entries: Vec<LogbookEntry>,
+}
+
+impl Logbook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a session, keeping the entries in the order they were driven.
+    /// Sessions without a start time go first.
+    ///
+    /// * `entry`: the session.
+    pub fn add(&mut self, entry: LogbookEntry) {
+        let index = self
+            .entries
+            .partition_point(|other| other.started_at <= entry.started_at);
+        self.entries.insert(index, entry);
+    }
+
+    pub fn entries(&self) -> &[LogbookEntry] {
+        &self.entries
+    }
+
+    /// every track driven, in name order.
+    pub fn tracks(&self) -> Vec<&str> {
+        let tracks: BTreeSet<&str> = self.entries.iter().map(|e| e.track.as_str()).collect();
+        tracks.into_iter().collect()
+    }
+
+    /// every car driven, in name order.
+    pub fn cars(&self) -> Vec<&str> {
+        let cars: BTreeSet<&str> = self.entries.iter().map(|e| e.car.as_str()).collect();
+        cars.into_iter().collect()
+    }
+
+    /// how far has been driven in all, in kilometres.
+    pub fn mileage_km(&self) -> f32 {
+        self.entries.iter().map(|e| e.distance_m).sum::<f32>() / 1000.0
+    }
+
+    /// how long has been driven in all.
+    pub fn time_driven(&self) -> Duration {
+        self.entries.iter().map(|e| e.duration).sum()
+    }
+
+    /// the fastest lap at each track, in whatever car, in track order.
+    pub fn bests_by_track(&self) -> Vec<TrackBest> {
+        self.tracks()
+            .into_iter()
+            .filter_map(|track| {
+                self.entries
+                    .iter()
+                    .filter(|e| e.track == track)
+                    .filter_map(|e| Some((e, e.best?)))
+                    .min_by_key(|&(_, time)| time)
+                    .map(|(e, time)| TrackBest {
+                        track: track.to_string(),
+                        car: e.car.clone(),
+                        time,
+                        set_at: e.started_at,
+                    })
+            })
+            .collect()
+    }
+
+    /// each session at a track in a car, oldest first, with the personal best as it stood.
+    ///
+    /// * `track`: the track, as in `LogbookEntry::track`.
+    /// * `car`: the car's model name.
+    pub fn progress(&self, track: &str, car: &str) -> Vec<ProgressPoint> {
+        let mut personal_best: Option<Duration> = None;
+
+        self.entries
+            .iter()
+            .filter(|e| e.track == track && e.car == car)
+            .map(|e| {
+                if let Some(best) = e.best {
+                    personal_best = Some(personal_best.map_or(best, |pb| pb.min(best)));
+                }
+                ProgressPoint {
+                    started_at: e.started_at,
+                    best: e.best,
+                    personal_best,
+                }
+            })
+            .collect()
+    }
+
+    /// writes the logbook as text.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "{LOGBOOK_HEADER}")?;
+        for e in &self.entries {
+            let started_at = e
+                .started_at
+                .map_or("-".to_string(), |at| at.unix_nanos().to_string());
+            let best = e
+                .best
+                .map_or("-".to_string(), |best| best.as_millis().to_string());
+            writeln!(
+                writer,
+                "session\t{started_at}\t{}\t{}\t{}\t{best}\t{}\t{}",
+                e.track,
+                e.car,
+                e.laps,
+                e.distance_m.round(),
+                e.duration.as_millis()
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    /// reads a logbook written by `write_to`.
+    ///
+    /// * `reader`: where to read it from.
+    pub fn read_from<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(LOGBOOK_HEADER) {
+            return Err(invalid("not an ac_lib logbook"));
+        }
+
+        let mut logbook = Self::default();
+        for line in lines {
+            let line = line?;
+            let fields: Vec<_> = line.split('\t').collect();
+
+            match fields[..] {
+                [""] => {}
+                [
+                    "session",
+                    started_at,
+                    track,
+                    car,
+                    laps,
+                    best,
+                    distance,
+                    duration,
+                ] => logbook.add(LogbookEntry {
+                    started_at: optional(started_at)?.map(Timestamp::from_unix_nanos),
+                    track: track.to_string(),
+                    car: car.to_string(),
+                    laps: number(laps)?,
+                    best: optional(best)?.map(Duration::from_millis),
+                    distance_m: number(distance)?,
+                    duration: Duration::from_millis(number(duration)?),
+                }),
+                _ => return Err(invalid("unrecognised logbook line")),
+            }
+        }
+
+        Ok(logbook)
+    }
+}
+
+/// a number, or `-` for none.
+fn optional(field: &str) -> io::Result<Option<u64>> {
+    match field {
+        "-" => Ok(None),
+        field => number(field).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod logbook_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, HandshakeResponse, Name, Recording, Timestamp};
+
+    use crate::logbook::{Logbook, LogbookEntry};
+
+    #[test]
+    fn sessions_are_logged_and_queried() {
+        let session = |day: u64, track: &str, car: &str, best_ms: u32| {
+            let mut recording = Recording::new();
+            recording.set_started_at(Timestamp::from_unix_nanos(day * 86_400_000_000_000));
+            let handshake = HandshakeResponse {
+                car_name: Name::from(car).expect("fits"),
+                track_name: Name::from(track).expect("fits"),
+                ..HandshakeResponse::default()
+            };
+            recording.push(Duration::ZERO, &handshake.to_bytes());
+            // Ten minutes at 50 m/s, three laps.
+            for (secs, lap_count) in [(0, 0), (300, 1), (600, 3)] {
+                let car = CarInfo {
+                    speed_ms: 50.0,
+                    lap_count,
+                    best_lap: if lap_count > 0 { best_ms } else { 0 },
+                    ..CarInfo::default()
+                };
+                recording.push(Duration::from_secs(secs), &car.to_bytes());
+            }
+            LogbookEntry::from_recording(&recording).expect("a session")
+        };
+
+        let mut logbook = Logbook::new();
+        logbook.add(session(3, "monza", "gt3 rs", 107_000));
+        logbook.add(session(1, "monza", "gt3 rs", 108_000));
+        logbook.add(session(2, "spa", "gt3 rs", 138_000));
+        logbook.add(session(4, "monza", "f2004", 81_000));
+
+        let mut text = Vec::new();
+        logbook.write_to(&mut text).expect("written");
+        let logbook = Logbook::read_from(text.as_slice()).expect("read");
+
+        assert_eq!(logbook.tracks(), ["monza", "spa"]);
+        assert_eq!(logbook.cars(), ["f2004", "gt3 rs"]);
+        assert_eq!(logbook.mileage_km(), 120.0);
+        assert_eq!(logbook.time_driven(), Duration::from_secs(2400));
+        assert_eq!(logbook.entries()[0].laps, 3);
+
+        let bests = logbook.bests_by_track();
+        assert_eq!(bests.len(), 2);
+        assert_eq!(
+            (bests[0].car.as_str(), bests[0].time),
+            ("f2004", Duration::from_secs(81))
+        );
+
+        let progress = logbook.progress("monza", "gt3 rs");
+        let pbs: Vec<_> = progress.iter().map(|p| p.personal_best).collect();
+        assert_eq!(
+            pbs,
+            [
+                Some(Duration::from_secs(108)),
+                Some(Duration::from_secs(107))
+            ]
+        );
+    }
+}
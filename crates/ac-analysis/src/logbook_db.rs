@@ -0,0 +1,302 @@
+//! The logbook in an SQLite database rather than a text file, for logbooks
+//! that are written to by several tools or have grown past reading whole:
+//! each session is a row of `sessions`, and the career queries run as SQL
+//! over indexes on track and car instead of scanning every entry.
+//!
+//! SQLite is built into the binary by `rusqlite`, so nothing needs to be
+//! installed. The schema is versioned with `user_version`, as the text
+//! logbook is with its header.
+
+use std::{path::Path, time::Duration};
+
+use ac_parser::Timestamp;
+use rusqlite::{Connection, Row, ffi, params};
+
+use crate::logbook::{Logbook, LogbookEntry, ProgressPoint, TrackBest};
+
+/// The schema version in `user_version`.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        id INTEGER PRIMARY KEY,
+        started_at INTEGER,
+        track TEXT NOT NULL,
+        car TEXT NOT NULL,
+        laps INTEGER NOT NULL,
+        best_ms INTEGER,
+        distance_m REAL NOT NULL,
+        duration_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS sessions_by_track_best
+        ON sessions (track, best_ms, started_at);
+    CREATE INDEX IF NOT EXISTS sessions_by_track_car
+        ON sessions (track, car, started_at);
+";
+
+/// Each session at a track in a car with the best lap up to it, picked out
+/// by `sessions_by_track_car` in the order it's already sorted in.
+const PROGRESS: &str = "
+    SELECT started_at, best_ms, MIN(best_ms) OVER (
+        ORDER BY started_at, id ROWS UNBOUNDED PRECEDING
+    )
+    FROM sessions WHERE track = ?1 AND car = ?2
+    ORDER BY started_at, id
+";
+
+/// The columns a `LogbookEntry` is read from, in order.
+const ENTRY_COLUMNS: &str = "started_at, track, car, laps, best_ms, distance_m, duration_ms";
+
+/// Every session driven, in an SQLite database.
+#[derive(Debug)]
+pub struct LogbookDb {
+    connection: Connection,
+}
+
+impl LogbookDb {
+    /// opens a logbook database, creating it if it doesn't exist. Fails on a
+    /// database from a newer version of the crate.
+    ///
+    /// * `path`: the database file.
+    pub fn open<P>(path: P) -> rusqlite::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// a logbook database held in memory, gone when it's dropped.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> rusqlite::Result<Self> {
+        let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISMATCH),
+                Some(format!(
+                    "logbook schema {version} is newer than {SCHEMA_VERSION}"
+                )),
+            ));
+        }
+
+        connection.execute_batch(SCHEMA)?;
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { connection })
+    }
+
+    /// adds a session.
+    ///
+    /// * `entry`: the session.
+    pub fn add(&self, entry: &LogbookEntry) -> rusqlite::Result<()> {
+        insert(&self.connection, entry)
+    }
+
+    /// adds every session of a text logbook, all or none of them.
+    ///
+    /// * `logbook`: the sessions, e.g. from `Logbook::read_from`.
+    pub fn import(&mut self, logbook: &Logbook) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for entry in logbook.entries() {
+            insert(&transaction, entry)?;
+        }
+        transaction.commit()
+    }
+
+    /// every session, oldest first; sessions without a start time go first.
+    pub fn entries(&self) -> rusqlite::Result<Vec<LogbookEntry>> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM sessions ORDER BY started_at, id"
+        ))?;
+        statement.query_map([], entry)?.collect()
+    }
+
+    /// every track driven, in name order.
+    pub fn tracks(&self) -> rusqlite::Result<Vec<String>> {
+        self.strings("SELECT DISTINCT track FROM sessions ORDER BY track")
+    }
+
+    /// every car driven, in name order.
+    pub fn cars(&self) -> rusqlite::Result<Vec<String>> {
+        self.strings("SELECT DISTINCT car FROM sessions ORDER BY car")
+    }
+
+    /// how far has been driven in all, in kilometres.
+    pub fn mileage_km(&self) -> rusqlite::Result<f32> {
+        self.connection.query_row(
+            "SELECT TOTAL(distance_m) / 1000.0 FROM sessions",
+            [],
+            |row| row.get::<_, f64>(0).map(|km| km as f32),
+        )
+    }
+
+    /// how long has been driven in all.
+    pub fn time_driven(&self) -> rusqlite::Result<Duration> {
+        self.connection.query_row(
+            "SELECT COALESCE(SUM(duration_ms), 0) FROM sessions",
+            [],
+            |row| row.get(0).map(Duration::from_millis),
+        )
+    }
+
+    /// the fastest lap at each track, in whatever car, in track order. Of
+    /// equal laps, the one driven first.
+    pub fn bests_by_track(&self) -> rusqlite::Result<Vec<TrackBest>> {
+        let mut statement = self.connection.prepare(
+            "SELECT track, car, best_ms, started_at FROM (
+                 SELECT track, car, best_ms, started_at, ROW_NUMBER() OVER (
+                     PARTITION BY track ORDER BY best_ms, started_at, id
+                 ) AS rank
+                 FROM sessions WHERE best_ms IS NOT NULL
+             )
+             WHERE rank = 1 ORDER BY track",
+        )?;
+        statement
+            .query_map([], |row| {
+                Ok(TrackBest {
+                    track: row.get(0)?,
+                    car: row.get(1)?,
+                    time: Duration::from_millis(row.get(2)?),
+                    set_at: row
+                        .get::<_, Option<u64>>(3)?
+                        .map(Timestamp::from_unix_nanos),
+                })
+            })?
+            .collect()
+    }
+
+    /// each session at a track in a car, oldest first, with the personal best as it stood.
+    ///
+    /// * `track`: the track, as in `LogbookEntry::track`.
+    /// * `car`: the car's model name.
+    pub fn progress(&self, track: &str, car: &str) -> rusqlite::Result<Vec<ProgressPoint>> {
+        let mut statement = self.connection.prepare(PROGRESS)?;
+        statement
+            .query_map(params![track, car], |row| {
+                Ok(ProgressPoint {
+                    started_at: row
+                        .get::<_, Option<u64>>(0)?
+                        .map(Timestamp::from_unix_nanos),
+                    best: row.get::<_, Option<u64>>(1)?.map(Duration::from_millis),
+                    personal_best: row.get::<_, Option<u64>>(2)?.map(Duration::from_millis),
+                })
+            })?
+            .collect()
+    }
+
+    fn strings(&self, sql: &str) -> rusqlite::Result<Vec<String>> {
+        let mut statement = self.connection.prepare(sql)?;
+        statement.query_map([], |row| row.get(0))?.collect()
+    }
+}
+
+fn insert(connection: &Connection, entry: &LogbookEntry) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO sessions (started_at, track, car, laps, best_ms, distance_m, duration_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entry.started_at.map(Timestamp::unix_nanos),
+            entry.track,
+            entry.car,
+            entry.laps,
+            entry.best.map(millis),
+            entry.distance_m,
+            millis(entry.duration),
+        ],
+    )?;
+    Ok(())
+}
+
+/// a row of `ENTRY_COLUMNS` as an entry.
+fn entry(row: &Row<'_>) -> rusqlite::Result<LogbookEntry> {
+    Ok(LogbookEntry {
+        started_at: row
+            .get::<_, Option<u64>>(0)?
+            .map(Timestamp::from_unix_nanos),
+        track: row.get(1)?,
+        car: row.get(2)?,
+        laps: row.get(3)?,
+        best: row.get::<_, Option<u64>>(4)?.map(Duration::from_millis),
+        distance_m: row.get(5)?,
+        duration: Duration::from_millis(row.get(6)?),
+    })
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod logbook_db_tests {
+    use std::time::Duration;
+
+    use ac_parser::Timestamp;
+
+    use crate::{
+        logbook::{Logbook, LogbookEntry},
+        logbook_db::{LogbookDb, PROGRESS},
+    };
+
+    #[test]
+    fn queries_match_the_text_logbook() {
+        let session =
+            |day: Option<u64>, track: &str, car: &str, best_ms: Option<u64>| LogbookEntry {
+                started_at: day.map(|day| Timestamp::from_unix_nanos(day * 86_400_000_000_000)),
+                track: track.to_string(),
+                car: car.to_string(),
+                laps: 3,
+                best: best_ms.map(Duration::from_millis),
+                distance_m: 30_000.0,
+                duration: Duration::from_secs(600),
+            };
+        let mut logbook = Logbook::new();
+        logbook.add(session(Some(3), "monza", "gt3 rs", Some(107_000)));
+        logbook.add(session(Some(1), "monza", "gt3 rs", Some(108_000)));
+        logbook.add(session(None, "monza", "gt3 rs", None));
+        logbook.add(session(Some(2), "spa", "gt3 rs", Some(138_000)));
+        logbook.add(session(Some(4), "monza", "f2004", Some(81_000)));
+        logbook.add(session(Some(5), "monza", "gt3 rs", Some(109_000)));
+
+        let path = std::env::temp_dir().join(format!("ac_lib_logbook_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut db = LogbookDb::open(&path).expect("opens");
+        db.import(&logbook).expect("imports");
+        drop(db);
+        let db = LogbookDb::open(&path).expect("reopens");
+
+        assert_eq!(db.entries().expect("queries"), logbook.entries());
+        assert_eq!(db.tracks().expect("queries"), logbook.tracks());
+        assert_eq!(db.cars().expect("queries"), logbook.cars());
+        assert_eq!(db.mileage_km().expect("queries"), logbook.mileage_km());
+        assert_eq!(db.time_driven().expect("queries"), logbook.time_driven());
+        assert_eq!(
+            db.bests_by_track().expect("queries"),
+            logbook.bests_by_track()
+        );
+        assert_eq!(
+            db.progress("monza", "gt3 rs").expect("queries"),
+            logbook.progress("monza", "gt3 rs")
+        );
+
+        let plan: Vec<String> = db
+            .connection
+            .prepare(&format!("EXPLAIN QUERY PLAN {PROGRESS}"))
+            .and_then(|mut plan| {
+                plan.query_map(["monza", "gt3 rs"], |row| row.get(3))?
+                    .collect()
+            })
+            .expect("explains");
+        assert!(
+            plan.iter()
+                .any(|step| step.contains("sessions_by_track_car"))
+        );
+
+        db.connection
+            .pragma_update(None, "user_version", 2)
+            .expect("bumps");
+        drop(db);
+        assert!(LogbookDb::open(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,179 @@
+//! Telling when the sim is paused or in its menus. AC doesn't say: while
+//! paused it keeps sending the same CarInfo with the lap clock frozen, and in
+//! some menus it stops sending at all. A `PauseDetector` watches for both and
+//! emits `Output::Pause`, so a timer can stop its wall clock and a recorder
+//! can skip the dead air. Silence is only noticed when something happens, so
+//! a live consumer should call `tick` now and then as well.
+
+use std::time::Duration;
+
+use ac_parser::Event;
+
+use crate::pipeline::{Output, Stage};
+
+/// What counts as paused.
+///
+/// * `frozen`: how long CarInfo has to keep arriving with the lap clock stopped.
+/// * `silence`: how long without any event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseRules {
+    pub frozen: Duration,
+    pub silence: Duration,
+}
+
+impl Default for PauseRules {
+    fn default() -> Self {
+        Self {
+            frozen: Duration::from_millis(500),
+            silence: Duration::from_secs(2),
+        }
+    }
+}
+
+/// The sim paused or carried on.
+///
+/// * `at`: when it stopped, or started again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseEvent {
+    Paused { at: Duration },
+    Resumed { at: Duration },
+}
+
+/// Watches CarInfo for a frozen lap clock and the stream for silence.
+///
+/// * `rules`: what counts as paused.
+/// * `last`: when the last event arrived.
+/// * `lap_time`: the last CarInfo's lap time, and when it was first seen.
+/// * `paused`: whether the sim is paused.
+#[derive(Debug, Clone, Default)]
+pub struct PauseDetector {
+    rules: PauseRules,
+    last: Option<Duration>,
+    lap_time: Option<(u32, Duration)>,
+    paused: bool,
+}
+
+impl PauseDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// * `rules`: what counts as paused.
+    pub fn with_rules(rules: PauseRules) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    /// checks an event, returning a change if it pauses or resumes the sim.
+    ///
+    /// * `at`: when the event arrived.
+    /// * `event`: the event.
+    pub fn observe(&mut self, at: Duration, event: &Event) -> Option<PauseEvent> {
+        let silent = self.tick(at);
+        self.last = Some(at);
+
+        let Event::CarInfo(car) = event else {
+            return silent;
+        };
+        let since = match self.lap_time {
+            Some((lap_time, since)) if lap_time == car.lap_time => since,
+            _ => {
+                self.lap_time = Some((car.lap_time, at));
+                at
+            }
+        };
+
+        if self.paused && since == at {
+            self.paused = false;
+            return Some(PauseEvent::Resumed { at });
+        }
+        if !self.paused && at.saturating_sub(since) >= self.rules.frozen {
+            self.paused = true;
+            return Some(PauseEvent::Paused { at: since });
+        }
+
+        silent
+    }
+
+    /// notices a stream that's gone quiet, returning `Paused` from the last
+    /// event once it's been silent long enough.
+    ///
+    /// * `now`: the time, on the same clock as events.
+    pub fn tick(&mut self, now: Duration) -> Option<PauseEvent> {
+        let last = self.last?;
+        if self.paused || now.saturating_sub(last) < self.rules.silence {
+            return None;
+        }
+
+        self.paused = true;
+        Some(PauseEvent::Paused { at: last })
+    }
+
+    /// whether the sim is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+impl Stage for PauseDetector {
+    fn name(&self) -> &str {
+        "pause"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        outputs.extend(self.observe(at, event).map(Output::Pause));
+        true
+    }
+}
+
+#[cfg(test)]
+mod pause_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event};
+
+    use crate::pause::{PauseDetector, PauseEvent};
+
+    #[test]
+    fn frozen_lap_clocks_and_silence_pause() {
+        let car = |lap_time| {
+            Event::CarInfo(CarInfo {
+                lap_time,
+                ..CarInfo::default()
+            })
+        };
+        let ms = Duration::from_millis;
+        let mut detector = PauseDetector::new();
+
+        // Driving, then the clock stops at 1000 ms until it runs again at 1700.
+        let mut changes = Vec::new();
+        for at in (0..=2000).step_by(100) {
+            let lap_time = match at {
+                0..1000 => at,
+                1000..1600 => 1000,
+                _ => at - 600,
+            };
+            changes.extend(detector.observe(ms(at as u64), &car(lap_time)));
+        }
+        assert_eq!(
+            changes,
+            [
+                PauseEvent::Paused { at: ms(1000) },
+                PauseEvent::Resumed { at: ms(1700) }
+            ]
+        );
+
+        assert_eq!(detector.tick(ms(3000)), None);
+        assert_eq!(
+            detector.tick(ms(4000)),
+            Some(PauseEvent::Paused { at: ms(2000) })
+        );
+        assert!(detector.is_paused());
+        assert_eq!(
+            detector.observe(ms(9000), &car(1500)),
+            Some(PauseEvent::Resumed { at: ms(9000) })
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! How cleanly the pedals are used, the driver-efficiency numbers coaches ask
+//! for first: how much of a lap is spent on throttle and brake at once, and
+//! how much on neither, coasting. Both are reported as shares of the lap and
+//! as zones placed on track, so they can be drawn on a track map.
+
+use std::time::Duration;
+
+use ac_parser::CarInfo;
+
+use crate::trace::{Sample, Trace};
+
+/// When a pedal counts as applied and which zones are worth reporting.
+///
+/// * `applied`: pedal input above which a pedal counts as pressed, 0 to 1.
+/// * `min_zone`: how long an overlap or coast has to last to be reported as a zone;
+///   shorter ones still count towards the lap's shares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PedalRules {
+    pub applied: f32,
+    pub min_zone: Duration,
+}
+
+impl Default for PedalRules {
+    fn default() -> Self {
+        Self {
+            applied: 0.05,
+            min_zone: Duration::from_millis(200),
+        }
+    }
+}
+
+/// A stretch of track spent overlapping or coasting.
+///
+/// * `start`: when it began, in the lap.
+/// * `duration`: how long it lasted.
+/// * `from`, `to`: where on track it began and ended, 0 to 1.
+/// * `coordinates`: where in the world it began, as `car_coordinates`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PedalZone {
+    pub start: Duration,
+    pub duration: Duration,
+    pub from: f32,
+    pub to: f32,
+    pub coordinates: [f32; 3],
+}
+
+/// The pedal use of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `overlap`: the share of the lap with both pedals applied, 0 to 1.
+/// * `coasting`: the share of the lap with neither applied, 0 to 1.
+/// * `overlaps`: each overlap zone, in the order they happened.
+/// * `coasts`: each coasting zone, in the order they happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PedalReport {
+    pub lap: u32,
+    pub overlap: f32,
+    pub coasting: f32,
+    pub overlaps: Vec<PedalZone>,
+    pub coasts: Vec<PedalZone>,
+}
+
+/// What the pedals are doing at one sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pedals {
+    Overlap,
+    Coasting,
+    Other,
+}
+
+impl Pedals {
+    /// a pedal reading that isn't a number says nothing, so it's neither.
+    fn of(car: &CarInfo, rules: &PedalRules) -> Self {
+        if car.gas.is_nan() || car.brake.is_nan() {
+            return Pedals::Other;
+        }
+
+        match (car.gas > rules.applied, car.brake > rules.applied) {
+            (true, true) => Pedals::Overlap,
+            (false, false) => Pedals::Coasting,
+            _ => Pedals::Other,
+        }
+    }
+}
+
+impl PedalReport {
+    /// the overlap and coasting of a lap. `None` for an empty lap.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: when a pedal counts as applied.
+    pub fn of(lap: &Trace, rules: &PedalRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let mut report = Self {
+            lap: first.car.lap_count,
+            overlap: 0.0,
+            coasting: 0.0,
+            overlaps: Vec::new(),
+            coasts: Vec::new(),
+        };
+
+        let mut overlap = Duration::ZERO;
+        let mut coasting = Duration::ZERO;
+        // Each sample's state holds until the next sample; the last one holds for no time.
+        let mut zone_start: &Sample = first;
+        let mut state = Pedals::of(&first.car, rules);
+        for pair in samples.windows(2) {
+            let (sample, next) = (&pair[0], &pair[1]);
+            let held = next.time.saturating_sub(sample.time);
+            match state {
+                Pedals::Overlap => overlap += held,
+                Pedals::Coasting => coasting += held,
+                Pedals::Other => {}
+            }
+
+            let next_state = Pedals::of(&next.car, rules);
+            if next_state != state {
+                report.close(state, zone_start, next, rules);
+                zone_start = next;
+                state = next_state;
+            }
+        }
+        let last = samples.last().unwrap_or(first);
+        report.close(state, zone_start, last, rules);
+
+        let total = last.time.saturating_sub(first.time).as_secs_f32();
+        if total > 0.0 {
+            report.overlap = overlap.as_secs_f32() / total;
+            report.coasting = coasting.as_secs_f32() / total;
+        }
+
+        Some(report)
+    }
+
+    /// records a zone that ran from `start` until `end`, if it's worth reporting.
+    fn close(&mut self, state: Pedals, start: &Sample, end: &Sample, rules: &PedalRules) {
+        let duration = end.time.saturating_sub(start.time);
+        if duration < rules.min_zone {
+            return;
+        }
+
+        let zone = PedalZone {
+            start: start.time,
+            duration,
+            from: start.car.car_pos_normalized,
+            to: end.car.car_pos_normalized,
+            coordinates: start.car.car_coordinates,
+        };
+        match state {
+            Pedals::Overlap => self.overlaps.push(zone),
+            Pedals::Coasting => self.coasts.push(zone),
+            Pedals::Other => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod pedals_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        pedals::{PedalReport, PedalRules},
+        trace::Trace,
+    };
+
+    fn car(position: f32, gas: f32, brake: f32) -> CarInfo {
+        CarInfo {
+            lap_count: 3,
+            car_pos_normalized: position,
+            gas,
+            brake,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a lap sampled every 100 ms.
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+        lap
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_report() {
+        assert_eq!(PedalReport::of(&Trace::new(), &PedalRules::default()), None);
+    }
+
+    #[test]
+    fn a_single_sample_lasts_no_time() {
+        let report = PedalReport::of(&lap([car(0.5, 1.0, 1.0)]), &PedalRules::default())
+            .expect("one sample");
+        assert_eq!((report.overlap, report.coasting), (0.0, 0.0));
+        assert!(report.overlaps.is_empty() && report.coasts.is_empty());
+    }
+
+    #[test]
+    fn a_coast_across_the_line_runs_to_the_end_of_the_lap() {
+        let samples = [0.96, 0.98, 0.0, 0.02].map(|position| car(position, 0.0, 0.0));
+        let report = PedalReport::of(&lap(samples), &PedalRules::default()).expect("four");
+
+        assert_eq!(report.coasting, 1.0);
+        let [coast] = &report.coasts[..] else {
+            panic!("expected one coast, got {:?}", report.coasts);
+        };
+        assert_eq!((coast.from, coast.to), (0.96, 0.02));
+        assert_eq!(coast.duration, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn nan_pedals_are_neither_coasting_nor_overlap() {
+        let samples = [
+            car(0.1, f32::NAN, 0.0),
+            car(0.2, f32::NAN, 0.0),
+            car(0.3, 0.0, f32::NAN),
+            car(0.4, 1.0, 0.0),
+        ];
+        let report = PedalReport::of(&lap(samples), &PedalRules::default()).expect("four");
+
+        assert_eq!((report.overlap, report.coasting), (0.0, 0.0));
+        assert!(report.coasts.is_empty());
+    }
+
+    #[test]
+    fn zones_as_long_as_min_zone_are_reported() {
+        let lap = lap([
+            car(0.1, 1.0, 0.0),
+            car(0.2, 1.0, 1.0),
+            car(0.3, 1.0, 1.0),
+            car(0.4, 0.0, 1.0),
+        ]);
+        let report = PedalReport::of(&lap, &PedalRules::default()).expect("four");
+        assert_eq!(report.overlaps.len(), 1, "200 ms is enough");
+
+        let strict = PedalRules {
+            min_zone: Duration::from_millis(201),
+            ..PedalRules::default()
+        };
+        let report = PedalReport::of(&lap, &strict).expect("four");
+        assert!(report.overlaps.is_empty());
+        assert!(report.overlap > 0.0, "still counted in the share");
+    }
+
+    #[test]
+    fn overlap_and_coasting_are_timed_and_placed() {
+        // Ten 100 ms samples: flat out, a 300 ms coast, braking with a 200 ms
+        // overlap of a left-foot trail, a blip of overlap too short to be a
+        // zone, then back on the power.
+        let samples = [
+            car(0.0, 1.0, 0.0),
+            car(0.1, 0.0, 0.0),
+            car(0.2, 0.02, 0.0),
+            car(0.3, 0.0, 0.01),
+            car(0.4, 0.0, 1.0),
+            car(0.5, 0.3, 0.6),
+            car(0.6, 0.3, 0.2),
+            car(0.7, 0.0, 0.1),
+            car(0.8, 0.5, 0.5),
+            car(0.9, 1.0, 0.0),
+        ];
+
+        let report = PedalReport::of(&lap(samples), &PedalRules::default()).expect("not empty");
+        assert_eq!(report.lap, 3);
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-6;
+        assert!(near(report.overlap, 0.3 / 0.9), "{report:?}");
+        assert!(near(report.coasting, 0.3 / 0.9), "{report:?}");
+
+        let [coast] = &report.coasts[..] else {
+            panic!("expected one coast, got {:?}", report.coasts);
+        };
+        assert_eq!((coast.from, coast.to), (0.1, 0.4));
+        assert_eq!(coast.duration, Duration::from_millis(300));
+
+        let [overlap] = &report.overlaps[..] else {
+            panic!("expected one overlap zone, got {:?}", report.overlaps);
+        };
+        assert_eq!(
+            (overlap.start, overlap.from),
+            (Duration::from_millis(500), 0.5)
+        );
+    }
+}
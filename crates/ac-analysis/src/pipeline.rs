@@ -0,0 +1,793 @@
+//! Composable analysis over a stream of events. A `Pipeline` runs each event
+//! through its stages in order; stages can drop events (filtering), emit their
+//! own outputs (lap times, deltas, alerts), or have side effects (recording).
+//! `PipelineBuilder` has presets for the usual setups, and custom stages slot
+//! in anywhere by name:
+//!
+//! ```
+//! # use ac_analysis::{AlertRule, Filter, PipelineBuilder};
+//! # use ac_parser::Channel;
+//! let rules = vec![AlertRule::above("over-rev", Channel::EngineRpm, 8500.0)];
+//! let pipeline = PipelineBuilder::coach(rules)
+//!     .insert_before("timing", Filter::new(|_| true))
+//!     .build();
+//! ```
+
+use std::{io, mem, time::Duration, vec::Drain};
+
+use ac_parser::{CarInfo, Channel, Event, Recording, RecordingWriter};
+
+use crate::{
+    battles::BattleEvent,
+    bests::BestEvent,
+    cars::CarChange,
+    downsample::Downsample,
+    focus::FocusChange,
+    pause::PauseEvent,
+    progress::RaceMilestone,
+    restart::SessionRestarted,
+    standings::StandingsEvent,
+    summary::Summary,
+    validity::{LapValidity, LapWatch, ValidityRules},
+};
+
+/// The CarInfo rate the dashboard preset thins to.
+const DASHBOARD_HZ: f32 = 30.0;
+
+/// Something a pipeline produced.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// An event that made it through every stage.
+    Event(Event),
+    Lap(LapTime),
+    Delta(Delta),
+    Alert(Alert),
+    Car(CarChange),
+    Standings(StandingsEvent),
+    Battle(BattleEvent),
+    Race(RaceMilestone),
+    Best(BestEvent),
+    Focus(FocusChange),
+    Summary(Summary),
+    Restart(SessionRestarted),
+    Pause(PauseEvent),
+}
+
+/// A completed lap.
+///
+/// * `lap`: the lap's number, counting from zero.
+/// * `time`: how long it took.
+/// * `best`: whether it's the fastest valid lap so far.
+/// * `validity`: how the lap was judged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LapTime {
+    pub lap: u32,
+    pub time: Duration,
+    pub best: bool,
+    pub validity: LapValidity,
+}
+
+/// How the current lap compares to the best one at the same point on track.
+///
+/// * `lap`: the current lap's number.
+/// * `position`: how far around the lap, from 0 to 1.
+/// * `seconds`: time lost against the best lap, negative when ahead of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delta {
+    pub lap: u32,
+    pub position: f32,
+    pub seconds: f32,
+}
+
+/// An alert rule that started firing.
+///
+/// * `rule`: the rule's name.
+/// * `value`: the channel value that set it off.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub value: f32,
+}
+
+/// One step of a pipeline.
+pub trait Stage: Send {
+    /// a short name, for finding the stage with `PipelineBuilder::insert_before` and friends.
+    fn name(&self) -> &str;
+
+    /// handles an event. Returns whether later stages should see it.
+    ///
+    /// * `at`: when the event arrived, on any monotonic timeline.
+    /// * `event`: the event.
+    /// * `outputs`: where to put anything the stage produces.
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool;
+
+    /// forgets the session so far, called when an earlier stage reports it
+    /// was restarted. Stages that keep no session state needn't bother.
+    fn reset(&mut self) {}
+
+    /// called once the stream ends, e.g. to flush what the stage has written.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Stage for Downsample {
+    fn name(&self) -> &str {
+        "downsample"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+        self.keep(at, event)
+    }
+}
+
+/// Keeps only the events a predicate accepts.
+pub struct Filter<F> {
+    predicate: F,
+}
+
+impl<F> Filter<F>
+where
+    F: FnMut(&Event) -> bool + Send,
+{
+    /// * `predicate`: whether to keep an event.
+    pub fn new(predicate: F) -> Self {
+        Self { predicate }
+    }
+}
+
+impl<F> Stage for Filter<F>
+where
+    F: FnMut(&Event) -> bool + Send,
+{
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+        (self.predicate)(event)
+    }
+}
+
+/// Emits a `LapTime` whenever the player's lap counter goes up, judged by
+/// `ValidityRules`.
+///
+/// * `lap_count`: the lap counter in the last CarInfo.
+/// * `best`: the fastest valid lap so far.
+/// * `rules`: the thresholds laps are judged by.
+/// * `watch`: what has happened on the lap in progress.
+#[derive(Debug, Clone, Default)]
+pub struct LapTimer {
+    lap_count: Option<u32>,
+    best: Option<Duration>,
+    rules: ValidityRules,
+    watch: LapWatch,
+}
+
+impl LapTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// * `rules`: the thresholds laps are judged by.
+    pub fn with_rules(rules: ValidityRules) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    /// a timer carrying on from a `SessionSnapshot`.
+    ///
+    /// * `lap_count`: the lap counter when the snapshot was taken.
+    /// * `best`: the fastest valid lap so far.
+    pub(crate) fn resume(lap_count: Option<u32>, best: Option<Duration>) -> Self {
+        Self {
+            lap_count,
+            best,
+            ..Self::default()
+        }
+    }
+
+    /// the lap counter in the last CarInfo.
+    pub fn lap_count(&self) -> Option<u32> {
+        self.lap_count
+    }
+}
+
+impl Stage for LapTimer {
+    fn name(&self) -> &str {
+        "timing"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        let Event::CarInfo(car) = event else {
+            return true;
+        };
+
+        if let Some(lap) = self.lap_count
+            && car.lap_count > lap
+            && car.last_lap > 0
+        {
+            let time = Duration::from_millis(car.last_lap.into());
+            let validity = self.watch.finish(&self.rules, time, self.best);
+            let best = validity.is_valid() && self.best.is_none_or(|best| time <= best);
+            if best {
+                self.best = Some(time);
+            }
+
+            outputs.push(Output::Lap(LapTime {
+                lap,
+                time,
+                best,
+                validity,
+            }));
+        }
+
+        self.watch.observe(&self.rules, car);
+        self.lap_count = Some(car.lap_count);
+        true
+    }
+
+    fn reset(&mut self) {
+        *self = Self::with_rules(self.rules);
+    }
+}
+
+/// Emits a `Delta` against the best lap so far for every CarInfo once a lap
+/// has been completed.
+///
+/// * `lap_count`: the lap counter in the last CarInfo.
+/// * `current`: (position, lap time in ms) points of the lap in progress.
+/// * `reference`: the same points for the best lap, and its time.
+#[derive(Debug, Clone, Default)]
+pub struct DeltaTimer {
+    lap_count: Option<u32>,
+    current: Vec<(f32, u32)>,
+    reference: Option<(Vec<(f32, u32)>, u32)>,
+}
+
+impl DeltaTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the best lap's time in ms at a point on track, interpolated between its points.
+    ///
+    /// * `position`: how far around the lap, from 0 to 1.
+    fn reference_at(&self, position: f32) -> Option<f32> {
+        let (points, _) = self.reference.as_ref()?;
+        let idx = points.partition_point(|&(p, _)| p < position);
+        let (p1, t1) = *points.get(idx).or(points.last())?;
+        let Some(&(p0, t0)) = idx.checked_sub(1).and_then(|i| points.get(i)) else {
+            return Some(t1 as f32);
+        };
+
+        if p1 <= p0 {
+            return Some(t1 as f32);
+        }
+
+        Some(t0 as f32 + (t1 as f32 - t0 as f32) * (position - p0) / (p1 - p0))
+    }
+
+    /// starts a new lap, keeping the one just finished if it's the fastest.
+    ///
+    /// * `car`: the first CarInfo of the new lap.
+    fn next_lap(&mut self, car: &CarInfo) {
+        let finished = mem::take(&mut self.current);
+
+        let faster = self
+            .reference
+            .as_ref()
+            .is_none_or(|&(_, best)| car.last_lap < best);
+        if car.last_lap > 0 && faster && !finished.is_empty() {
+            self.reference = Some((finished, car.last_lap));
+        }
+    }
+}
+
+impl Stage for DeltaTimer {
+    fn name(&self) -> &str {
+        "delta"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        let Event::CarInfo(car) = event else {
+            return true;
+        };
+
+        if self.lap_count.is_some_and(|lap| car.lap_count > lap) {
+            self.next_lap(car);
+        }
+        self.lap_count = Some(car.lap_count);
+
+        // Just after the line the position can still read as the end of the last
+        // lap, so a lap only starts being tracked from its first half.
+        let position = car.car_pos_normalized;
+        let tracked = match self.current.last() {
+            Some(&(last, _)) => position >= last,
+            None => position < 0.5,
+        };
+        if tracked {
+            self.current.push((position, car.lap_time));
+        }
+
+        if let Some(reference) = self.reference_at(position) {
+            outputs.push(Output::Delta(Delta {
+                lap: car.lap_count,
+                position,
+                seconds: (car.lap_time as f32 - reference) / 1000.0,
+            }));
+        }
+
+        true
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// When an alert rule fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Above(f32),
+    Below(f32),
+}
+
+/// Fires when a channel crosses a threshold.
+///
+/// * `name`: what the alert is called in `Alert::rule`.
+/// * `channel`: the channel to watch.
+/// * `threshold`: when to fire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub name: String,
+    pub channel: Channel,
+    pub threshold: Threshold,
+}
+
+impl AlertRule {
+    pub fn above<S>(name: S, channel: Channel, value: f32) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            channel,
+            threshold: Threshold::Above(value),
+        }
+    }
+
+    pub fn below<S>(name: S, channel: Channel, value: f32) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            channel,
+            threshold: Threshold::Below(value),
+        }
+    }
+
+    fn fires(&self, value: f32) -> bool {
+        match self.threshold {
+            Threshold::Above(threshold) => value > threshold,
+            Threshold::Below(threshold) => value < threshold,
+        }
+    }
+}
+
+/// Emits an `Alert` each time a rule starts firing. A rule that keeps firing
+/// doesn't alert again until it has cleared.
+///
+/// * `rules`: the rules, each with whether it was firing on the last CarInfo.
+#[derive(Debug, Clone, Default)]
+pub struct Alerts {
+    rules: Vec<(AlertRule, bool)>,
+}
+
+impl Alerts {
+    /// * `rules`: the rules to check every CarInfo against.
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules: rules.into_iter().map(|rule| (rule, false)).collect(),
+        }
+    }
+}
+
+impl Stage for Alerts {
+    fn name(&self) -> &str {
+        "alerts"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        let Event::CarInfo(car) = event else {
+            return true;
+        };
+
+        for (rule, firing) in &mut self.rules {
+            let value = rule.channel.value(car);
+            let fires = rule.fires(value);
+
+            if fires && !*firing {
+                outputs.push(Output::Alert(Alert {
+                    rule: rule.name.clone(),
+                    value,
+                }));
+            }
+            *firing = fires;
+        }
+
+        true
+    }
+}
+
+/// Records every event it sees, re-encoded as the packet the server sent.
+/// A write that fails stops the recording, since what followed couldn't be
+/// read back, and `finish` returns why.
+///
+/// * `writer`: where the recording goes.
+/// * `failed`: the first write that failed, if one has.
+pub struct Record<W> {
+    writer: RecordingWriter<W>,
+    failed: Option<io::Error>,
+}
+
+impl<W> Record<W>
+where
+    W: io::Write,
+{
+    /// * `writer`: where the recording goes.
+    pub fn new(writer: RecordingWriter<W>) -> Self {
+        Self {
+            writer,
+            failed: None,
+        }
+    }
+}
+
+impl<W> Stage for Record<W>
+where
+    W: io::Write + Send,
+{
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+        if self.failed.is_none()
+            && let Err(why) = self.writer.write_frame(at, &event.to_bytes())
+        {
+            self.failed = Some(why);
+        }
+
+        true
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.failed.take() {
+            Some(why) => Err(why),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Assembles a `Pipeline` from stages, starting empty or from a preset.
+#[derive(Default)]
+pub struct PipelineBuilder {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// for live displays: CarInfo thinned to 30 Hz, lap times and the delta to the best lap.
+    pub fn dashboard() -> Self {
+        Self::new()
+            .stage(Downsample::new(DASHBOARD_HZ))
+            .stage(LapTimer::new())
+            .stage(DeltaTimer::new())
+    }
+
+    /// for keeping sessions: every event recorded, plus lap times.
+    ///
+    /// * `writer`: where the recording goes.
+    pub fn logger<W>(writer: RecordingWriter<W>) -> Self
+    where
+        W: io::Write + Send + 'static,
+    {
+        Self::new()
+            .stage(Record::new(writer))
+            .stage(LapTimer::new())
+    }
+
+    /// for driver feedback: lap times, the delta to the best lap, and alerts.
+    ///
+    /// * `rules`: what to alert on.
+    pub fn coach(rules: Vec<AlertRule>) -> Self {
+        Self::new()
+            .stage(LapTimer::new())
+            .stage(DeltaTimer::new())
+            .stage(Alerts::new(rules))
+    }
+
+    /// adds a stage at the end.
+    ///
+    /// * `stage`: the stage.
+    pub fn stage<S>(mut self, stage: S) -> Self
+    where
+        S: Stage + 'static,
+    {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// adds a stage just before the first stage called `name`, or at the end if there's none.
+    ///
+    /// * `name`: the stage to insert before.
+    /// * `stage`: the stage.
+    pub fn insert_before<S>(mut self, name: &str, stage: S) -> Self
+    where
+        S: Stage + 'static,
+    {
+        let idx = self.position(name).unwrap_or(self.stages.len());
+        self.stages.insert(idx, Box::new(stage));
+        self
+    }
+
+    /// adds a stage just after the first stage called `name`, or at the end if there's none.
+    ///
+    /// * `name`: the stage to insert after.
+    /// * `stage`: the stage.
+    pub fn insert_after<S>(mut self, name: &str, stage: S) -> Self
+    where
+        S: Stage + 'static,
+    {
+        let idx = self.position(name).map_or(self.stages.len(), |idx| idx + 1);
+        self.stages.insert(idx, Box::new(stage));
+        self
+    }
+
+    /// removes every stage called `name`.
+    ///
+    /// * `name`: the stage to remove.
+    pub fn without(mut self, name: &str) -> Self {
+        self.stages.retain(|stage| stage.name() != name);
+        self
+    }
+
+    pub fn build(self) -> Pipeline {
+        Pipeline {
+            stages: self.stages,
+            outputs: Vec::new(),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.stages.iter().position(|stage| stage.name() == name)
+    }
+}
+
+/// Runs events through a sequence of stages.
+///
+/// * `stages`: the stages, in order.
+/// * `outputs`: what the last event produced, reused between events.
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+    outputs: Vec<Output>,
+}
+
+impl Pipeline {
+    /// the stages' names, in order.
+    pub fn stages(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|stage| stage.name())
+    }
+
+    /// runs an event through the stages, yielding what they produced, then the
+    /// event itself if no stage dropped it. A stage reporting a restart has
+    /// every stage after it reset before they see the event.
+    ///
+    /// * `at`: when the event arrived, on any monotonic timeline.
+    /// * `event`: the event.
+    pub fn push(&mut self, at: Duration, event: &Event) -> Drain<'_, Output> {
+        self.outputs.clear();
+
+        let mut kept = true;
+        for idx in 0..self.stages.len() {
+            let produced = self.outputs.len();
+            kept = self.stages[idx].process(at, event, &mut self.outputs);
+
+            let restarted = self.outputs[produced..]
+                .iter()
+                .any(|output| matches!(output, Output::Restart(_)));
+            if restarted {
+                self.stages[idx + 1..]
+                    .iter_mut()
+                    .for_each(|stage| stage.reset());
+            }
+            if !kept {
+                break;
+            }
+        }
+        if kept {
+            self.outputs.push(Output::Event(event.clone()));
+        }
+
+        self.outputs.drain(..)
+    }
+
+    /// runs every frame of a recording through the pipeline, skipping packets
+    /// that fail to parse, then finishes it. Fails if a stage does on finishing,
+    /// e.g. a `Record` whose writes failed.
+    ///
+    /// * `recording`: the recording.
+    pub fn run(&mut self, recording: &Recording) -> io::Result<Vec<Output>> {
+        let mut outputs = Vec::new();
+
+        for frame in recording.frames() {
+            if let Ok(event) = Event::try_parse(&frame.packet) {
+                outputs.extend(self.push(frame.at, &event));
+            }
+        }
+
+        self.finish()?;
+        Ok(outputs)
+    }
+
+    /// tells every stage the stream has ended.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.stages.iter_mut().try_for_each(|stage| stage.finish())
+    }
+}
+
+#[cfg(test)]
+mod pipeline_tests {
+    use std::{io, time::Duration};
+
+    use ac_parser::{CarInfo, Channel, Event, LapInfo, Recording, RecordingWriter};
+
+    use crate::pipeline::{AlertRule, Filter, Output, PipelineBuilder};
+
+    fn car(lap_count: u32, pos: f32, lap_time: u32, last_lap: u32, rpm: f32) -> Event {
+        Event::CarInfo(CarInfo {
+            lap_count,
+            car_pos_normalized: pos,
+            lap_time,
+            last_lap,
+            engine_rpm: rpm,
+            ..CarInfo::default()
+        })
+    }
+
+    #[test]
+    fn presets_wire_their_stages() {
+        let names = |builder: PipelineBuilder| {
+            builder
+                .build()
+                .stages()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(PipelineBuilder::dashboard()),
+            ["downsample", "timing", "delta"]
+        );
+        assert_eq!(
+            names(PipelineBuilder::coach(Vec::new())),
+            ["timing", "delta", "alerts"]
+        );
+
+        let writer = RecordingWriter::new(Vec::new()).expect("failed to write header");
+        let logger = PipelineBuilder::logger(writer)
+            .insert_after("recording", Filter::new(|_: &Event| true))
+            .insert_before("nonexistent", Filter::new(|_: &Event| true))
+            .without("timing");
+        assert_eq!(names(logger), ["recording", "filter", "filter"]);
+    }
+
+    #[test]
+    fn coach_reports_laps_deltas_and_alerts() {
+        let mut recording = Recording::new();
+        let mut at = Duration::ZERO;
+        let mut push = |event: Event| {
+            recording.push(at, &event.to_bytes());
+            at += Duration::from_millis(10);
+        };
+
+        push(car(0, 0.0, 0, 0, 5000.0));
+        push(car(0, 0.5, 50_000, 0, 9000.0));
+        push(car(1, 0.0, 0, 100_000, 9000.0));
+        push(car(1, 0.5, 48_000, 100_000, 5000.0));
+        push(Event::LapInfo(LapInfo::default()));
+
+        let rules = vec![AlertRule::above("over-rev", Channel::EngineRpm, 8500.0)];
+        let outputs = PipelineBuilder::coach(rules)
+            .stage(Filter::new(|event: &Event| {
+                !matches!(event, Event::LapInfo(_))
+            }))
+            .build()
+            .run(&recording)
+            .expect("pipeline failed");
+
+        let laps: Vec<_> = outputs
+            .iter()
+            .filter_map(|o| match o {
+                Output::Lap(lap) => Some(*lap),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(laps.len(), 1);
+        assert_eq!(laps[0].time, Duration::from_secs(100));
+        assert!(laps[0].best);
+
+        let deltas: Vec<_> = outputs
+            .iter()
+            .filter_map(|o| match o {
+                Output::Delta(delta) => Some(delta.seconds),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, [0.0, -2.0]);
+
+        let alerts = outputs
+            .iter()
+            .filter(|o| matches!(o, Output::Alert(_)))
+            .count();
+        assert_eq!(alerts, 1, "a rule that stays firing alerts once");
+
+        let events = outputs
+            .iter()
+            .filter(|o| matches!(o, Output::Event(_)))
+            .count();
+        assert_eq!(events, 4, "the custom filter drops LapInfo");
+    }
+
+    /// takes `room` bytes, then fails every write.
+    struct Full {
+        room: usize,
+    }
+
+    impl io::Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+            }
+            let len = buf.len().min(self.room);
+            self.room -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_recording_writes_are_returned_on_finishing() {
+        let mut recording = Recording::new();
+        recording.push(Duration::ZERO, &car(0, 0.0, 0, 0, 5000.0).to_bytes());
+        recording.push(
+            Duration::from_millis(10),
+            &car(0, 0.1, 10, 0, 5000.0).to_bytes(),
+        );
+
+        let writer = RecordingWriter::new(Full { room: 64 }).expect("failed to write header");
+        let why = PipelineBuilder::logger(writer)
+            .build()
+            .run(&recording)
+            .expect_err("the recording ran out of room");
+        assert_eq!(why.kind(), io::ErrorKind::StorageFull);
+
+        let writer = RecordingWriter::new(Full { room: 4096 }).expect("failed to write header");
+        let outputs = PipelineBuilder::logger(writer)
+            .build()
+            .run(&recording)
+            .expect("fits");
+        assert_eq!(outputs.len(), 2);
+    }
+}
@@ -0,0 +1,351 @@
+//! How far a race has to go, for league broadcast overlays. Spot LapInfo marks
+//! each car crossing the line; with the configured race length that gives laps
+//! remaining, an estimated finish time per car, and milestone events. Once the
+//! leader takes the flag, every other car finishes at its next crossing.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use ac_parser::{Event, LapInfo};
+
+use crate::{
+    pipeline::{Output, Stage},
+    standings::Standings,
+};
+
+/// How long a race is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaceLength {
+    Laps(u32),
+    /// The leader finishes at the first crossing once this much time has passed.
+    Time(Duration),
+}
+
+/// A point in the race worth showing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaceMilestone {
+    /// The leader has covered half the race.
+    Halfway,
+    /// The leader has started the last lap.
+    LastLap,
+    /// A car took the flag, in this position.
+    Finished { car_id: i32, position: usize },
+}
+
+/// One car's progress through the race.
+///
+/// * `car_id`: the car's `car_id_num`.
+/// * `laps`: laps completed.
+/// * `laps_remaining`: laps to go, or `None` in a timed race before any lap time is known.
+/// * `finish_eta`: when the car should finish at its average lap time, on the
+///   same timeline as `update`.
+/// * `finished`: whether the car has taken the flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarProgress {
+    pub car_id: i32,
+    pub laps: i32,
+    pub laps_remaining: Option<u32>,
+    pub finish_eta: Option<Duration>,
+    pub finished: bool,
+}
+
+/// A car's crossings, as far as progress needs them.
+///
+/// * `laps`: laps completed.
+/// * `last_at`: when it last crossed the line.
+/// * `lap_total`: the sum of its reported lap times.
+/// * `timed_laps`: how many lap times went into `lap_total`.
+#[derive(Debug, Clone, Copy, Default)]
+struct CarLaps {
+    laps: i32,
+    last_at: Duration,
+    lap_total: Duration,
+    timed_laps: u32,
+}
+
+impl CarLaps {
+    fn average_lap(&self) -> Option<Duration> {
+        (self.timed_laps > 0).then(|| self.lap_total / self.timed_laps)
+    }
+}
+
+/// Tracks a race against its length.
+///
+/// * `length`: how long the race is.
+/// * `start`: when the race started, if set or worked out from the first crossing.
+/// * `standings`: the race order.
+/// * `cars`: each car's crossings.
+/// * `halfway`, `last_lap`: whether those milestones have been emitted.
+/// * `finished`: cars that have taken the flag.
+#[derive(Debug, Clone)]
+pub struct RaceProgress {
+    length: RaceLength,
+    start: Option<Duration>,
+    standings: Standings,
+    cars: HashMap<i32, CarLaps>,
+    halfway: bool,
+    last_lap: bool,
+    finished: HashSet<i32>,
+}
+
+impl RaceProgress {
+    /// * `length`: how long the race is.
+    pub fn new(length: RaceLength) -> Self {
+        Self {
+            length,
+            start: None,
+            standings: Standings::new(),
+            cars: HashMap::new(),
+            halfway: false,
+            last_lap: false,
+            finished: HashSet::new(),
+        }
+    }
+
+    /// sets when the race started. Without it, the start is worked out from the
+    /// first crossing and its lap time.
+    ///
+    /// * `at`: the start, on the same timeline as `update`.
+    pub fn started_at(mut self, at: Duration) -> Self {
+        self.start = Some(at);
+        self
+    }
+
+    /// records a car crossing the line. Returns the milestones it reached.
+    ///
+    /// * `at`: when the LapInfo arrived, on any monotonic timeline.
+    /// * `info`: the LapInfo.
+    pub fn update(&mut self, at: Duration, info: &LapInfo) -> Vec<RaceMilestone> {
+        let lap_time = Duration::from_millis(info.time.max(0) as u64);
+        let start = *self
+            .start
+            .get_or_insert_with(|| at.saturating_sub(lap_time));
+
+        self.standings.update(at, info);
+        let car_id = info.car_id_num;
+        let car = self.cars.entry(car_id).or_default();
+        car.laps = car.laps.max(info.lap);
+        car.last_at = at;
+        if info.time > 0 {
+            car.lap_total += lap_time;
+            car.timed_laps += 1;
+        }
+        let car = *car;
+
+        let mut milestones = Vec::new();
+        if self.finished.contains(&car_id) {
+            return milestones;
+        }
+
+        let leader_finished = !self.finished.is_empty();
+        let leading = self.standings.car_at(1) == Some(car_id);
+        if leader_finished || (leading && self.done(start, at, car.laps)) {
+            self.finished.insert(car_id);
+            milestones.push(RaceMilestone::Finished {
+                car_id,
+                position: self.standings.position(car_id).unwrap_or_default(),
+            });
+            return milestones;
+        }
+
+        if !leading {
+            return milestones;
+        }
+
+        let (halfway, last_lap) = match self.length {
+            RaceLength::Laps(laps) => {
+                let laps = laps as i32;
+                (car.laps * 2 >= laps, car.laps + 1 >= laps)
+            }
+            RaceLength::Time(length) => (
+                at.saturating_sub(start) >= length / 2,
+                car.average_lap()
+                    .is_some_and(|lap| at + lap >= start + length),
+            ),
+        };
+
+        if halfway && !self.halfway {
+            self.halfway = true;
+            milestones.push(RaceMilestone::Halfway);
+        }
+        if last_lap && !self.last_lap {
+            self.last_lap = true;
+            milestones.push(RaceMilestone::LastLap);
+        }
+
+        milestones
+    }
+
+    /// one car's progress.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    pub fn progress(&self, car_id: i32) -> Option<CarProgress> {
+        let car = self.cars.get(&car_id)?;
+        let finished = self.finished.contains(&car_id);
+
+        let laps_remaining = if finished {
+            Some(0)
+        } else if !self.finished.is_empty() {
+            Some(1)
+        } else {
+            self.leader_remaining()
+        };
+        let finish_eta = match (laps_remaining, car.average_lap()) {
+            (Some(0), _) => Some(car.last_at),
+            (Some(laps), Some(lap)) => Some(car.last_at + lap * laps),
+            _ => None,
+        };
+
+        Some(CarProgress {
+            car_id,
+            laps: car.laps,
+            laps_remaining,
+            finish_eta,
+            finished,
+        })
+    }
+
+    /// every car's progress, in race order.
+    pub fn progress_all(&self) -> Vec<CarProgress> {
+        self.standings
+            .standings()
+            .iter()
+            .filter_map(|standing| self.progress(standing.car_id))
+            .collect()
+    }
+
+    /// whether the leader has taken the flag.
+    pub fn is_finished(&self) -> bool {
+        !self.finished.is_empty()
+    }
+
+    /// whether the leader finishes on crossing the line now.
+    ///
+    /// * `start`: when the race started.
+    /// * `at`: the crossing.
+    /// * `laps`: laps the leader has completed.
+    fn done(&self, start: Duration, at: Duration, laps: i32) -> bool {
+        match self.length {
+            RaceLength::Laps(length) => laps >= length as i32,
+            RaceLength::Time(length) => at >= start + length,
+        }
+    }
+
+    /// laps the leader has to go before the flag.
+    fn leader_remaining(&self) -> Option<u32> {
+        let Some(leader) = self.standings.car_at(1).and_then(|id| self.cars.get(&id)) else {
+            return match self.length {
+                RaceLength::Laps(laps) => Some(laps),
+                RaceLength::Time(_) => None,
+            };
+        };
+
+        match self.length {
+            RaceLength::Laps(laps) => Some((laps as i32 - leader.laps).max(1) as u32),
+            RaceLength::Time(length) => {
+                let end = self.start? + length;
+                let left = end.saturating_sub(leader.last_at);
+                let lap = leader.average_lap()?;
+                Some((left.as_secs_f64() / lap.as_secs_f64()).ceil().max(1.0) as u32)
+            }
+        }
+    }
+}
+
+impl Stage for RaceProgress {
+    fn name(&self) -> &str {
+        "progress"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            outputs.extend(self.update(at, info).into_iter().map(Output::Race));
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use std::time::Duration;
+
+    use ac_parser::LapInfo;
+
+    use crate::progress::{RaceLength, RaceMilestone, RaceProgress};
+
+    fn lap(car_id_num: i32, lap: i32, time: i32) -> LapInfo {
+        LapInfo {
+            car_id_num,
+            lap,
+            time,
+            ..LapInfo::default()
+        }
+    }
+
+    #[test]
+    fn lap_races_reach_milestones_and_finish_everyone_behind_the_leader() {
+        let secs = Duration::from_secs;
+        let mut race = RaceProgress::new(RaceLength::Laps(4)).started_at(Duration::ZERO);
+        let mut milestones = Vec::new();
+
+        for n in 1..=4 {
+            let at = secs(100 * n as u64);
+            milestones.extend(race.update(at, &lap(1, n, 100_000)));
+            if n == 1 {
+                let leader = race.progress(1).expect("leader known");
+                assert_eq!(leader.laps_remaining, Some(3));
+                assert_eq!(leader.finish_eta, Some(secs(400)));
+            }
+            if n < 4 {
+                milestones.extend(race.update(at + secs(5), &lap(2, n, 105_000)));
+            }
+        }
+
+        assert_eq!(
+            milestones,
+            [
+                RaceMilestone::Halfway,
+                RaceMilestone::LastLap,
+                RaceMilestone::Finished {
+                    car_id: 1,
+                    position: 1
+                },
+            ]
+        );
+        assert!(race.is_finished());
+        assert_eq!(race.progress(2).and_then(|car| car.laps_remaining), Some(1));
+
+        // car 2 never starts a fifth lap: it finishes at its next crossing.
+        assert_eq!(
+            race.update(secs(405), &lap(2, 4, 105_000)),
+            [RaceMilestone::Finished {
+                car_id: 2,
+                position: 2
+            }]
+        );
+        assert!(race.progress_all().iter().all(|car| car.finished));
+    }
+
+    #[test]
+    fn timed_races_finish_at_the_first_crossing_after_time_runs_out() {
+        let secs = Duration::from_secs;
+        let mut race = RaceProgress::new(RaceLength::Time(secs(250)));
+
+        assert!(race.update(secs(100), &lap(1, 1, 100_000)).is_empty());
+        assert_eq!(race.progress(1).and_then(|car| car.laps_remaining), Some(2));
+        assert_eq!(
+            race.update(secs(200), &lap(1, 2, 100_000)),
+            [RaceMilestone::Halfway, RaceMilestone::LastLap]
+        );
+        assert_eq!(
+            race.update(secs(300), &lap(1, 3, 100_000)),
+            [RaceMilestone::Finished {
+                car_id: 1,
+                position: 1
+            }]
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Sessions for RaceChrono, so laps from the sim sit next to real track days
+//! in the same app. RaceChrono imports Racelogic VBO files, a plain-text
+//! format of GPS fixes with extra channels alongside. AC has no GPS, so the
+//! car's world coordinates are laid onto the globe around an origin, in
+//! metres, as if the track were there. RaceChrono's live DIY datalogger
+//! protocol runs over Bluetooth LE, which this library doesn't speak; a file
+//! imported after the session is the supported route.
+
+use std::{
+    f64::consts::PI,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::trace::Trace;
+
+/// Metres per degree of latitude, near enough everywhere.
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// The channels written after the GPS ones, with their VBO column names and units.
+const CHANNELS: [(&str, &str); 7] = [
+    ("rpm", "rpm"),
+    ("gear", ""),
+    ("throttle", "%"),
+    ("brake", "%"),
+    ("steering", "rad"),
+    ("lat_g", "g"),
+    ("long_g", "g"),
+];
+
+/// Writes a session as a VBO file for RaceChrono to import.
+///
+/// * `trace`: the session, or one lap of it.
+/// * `origin`: the latitude and longitude, in degrees, the world origin is placed at.
+pub struct RaceChronoExport<'a> {
+    trace: &'a Trace,
+    origin: (f64, f64),
+}
+
+impl<'a> RaceChronoExport<'a> {
+    /// * `trace`: the session to export.
+    pub fn new(trace: &'a Trace) -> Self {
+        Self {
+            trace,
+            origin: (0.0, 0.0),
+        }
+    }
+
+    /// places the track's world origin on the globe, e.g. at the real track
+    /// so RaceChrono's own track library finds it. Defaults to 0° N, 0° E.
+    ///
+    /// * `latitude`, `longitude`: in degrees, north and east positive.
+    pub fn origin(mut self, latitude: f64, longitude: f64) -> Self {
+        self.origin = (latitude, longitude);
+        self
+    }
+
+    /// where a world position lands, in degrees, taking x as east and z as north.
+    fn project(&self, [x, _, z]: [f32; 3]) -> (f64, f64) {
+        let (latitude, longitude) = self.origin;
+        let latitude = latitude + f64::from(z) / METRES_PER_DEGREE;
+        let longitude =
+            longitude + f64::from(x) / (METRES_PER_DEGREE * (latitude * PI / 180.0).cos());
+
+        (latitude, longitude)
+    }
+
+    /// writes the session as VBO: a fix per sample, timed by the wall clock if
+    /// the recording's start is known, or else from midnight.
+    ///
+    /// * `writer`: where it goes, e.g. a `.vbo` file.
+    pub fn write_vbo<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let samples = self.trace.samples();
+        let start_nanos = samples
+            .first()
+            .and_then(|first| self.trace.wall_clock(first))
+            .map_or(0, |wall| wall.unix_nanos());
+        let (year, month, day) = civil_date(start_nanos / 86_400_000_000_000);
+        let (hours, minutes, seconds) = time_of_day(Duration::from_nanos(start_nanos));
+
+        writeln!(
+            writer,
+            "File created on {day:02}/{month:02}/{year} @ {hours:02}:{minutes:02}:{:02}",
+            seconds as u32
+        )?;
+        writeln!(writer)?;
+        writeln!(writer, "[header]")?;
+        for name in [
+            "satellites",
+            "time",
+            "latitude",
+            "longitude",
+            "velocity kmh",
+            "heading",
+            "height",
+        ]
+        .into_iter()
+        .chain(CHANNELS.iter().map(|&(name, _)| name))
+        {
+            writeln!(writer, "{name}")?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "[channel units]")?;
+        for (_, unit) in CHANNELS {
+            writeln!(writer, "{unit}")?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "[comments]")?;
+        writeln!(
+            writer,
+            "Assetto Corsa telemetry, positions projected from world coordinates"
+        )?;
+        writeln!(writer)?;
+        writeln!(writer, "[column names]")?;
+        let columns: Vec<&str> = [
+            "sats", "time", "lat", "long", "velocity", "heading", "height",
+        ]
+        .into_iter()
+        .chain(CHANNELS.iter().map(|&(name, _)| name))
+        .collect();
+        writeln!(writer, "{}", columns.join(" "))?;
+        writeln!(writer)?;
+        writeln!(writer, "[data]")?;
+
+        let mut heading = 0.0;
+        for (i, sample) in samples.iter().enumerate() {
+            let car = &sample.car;
+            let (latitude, longitude) = self.project(car.car_coordinates);
+
+            // Heading comes from the way the car moved since the last sample;
+            // it's kept while the car is stationary.
+            if let Some(previous) = i.checked_sub(1).map(|i| &samples[i].car) {
+                let [x0, _, z0] = previous.car_coordinates;
+                let [x1, _, z1] = car.car_coordinates;
+                if (x1 - x0).hypot(z1 - z0) > 0.01 {
+                    heading = (x1 - x0).atan2(z1 - z0).to_degrees().rem_euclid(360.0);
+                }
+            }
+
+            let wall = Duration::from_nanos(start_nanos) + sample.time;
+            let (hours, minutes, seconds) = time_of_day(wall);
+            // VBO holds positions in minutes, with longitude positive to the west.
+            writeln!(
+                writer,
+                "{:03} {hours:02}{minutes:02}{seconds:05.2} {:+012.5} {:+012.5} {:07.3} {:06.2} {:+09.2} {:.0} {} {:.1} {:.1} {:.4} {:.3} {:.3}",
+                8,
+                latitude * 60.0,
+                -longitude * 60.0,
+                car.speed_kmh,
+                heading,
+                car.car_coordinates[1],
+                car.engine_rpm,
+                car.gear - 1,
+                car.gas * 100.0,
+                car.brake * 100.0,
+                car.steer,
+                car.accg_horizontal,
+                car.accg_frontal,
+            )?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// hours, minutes and seconds into the UTC day, the seconds cut to the
+/// hundredths VBO holds so they never print as 60.
+fn time_of_day(since_epoch: Duration) -> (u64, u64, f64) {
+    let secs = since_epoch.as_secs() % 86_400;
+    let seconds = (secs % 60) as f64 + f64::from(since_epoch.subsec_millis() / 10) / 100.0;
+
+    (secs / 3600, secs / 60 % 60, seconds)
+}
+
+/// the year, month and day of a day counted from the Unix epoch.
+fn civil_date(days: u64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil, run backwards.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod racechrono_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Recording, Timestamp};
+
+    use crate::{racechrono::RaceChronoExport, trace::Trace};
+
+    #[test]
+    fn vbo_fixes_are_projected_around_the_origin() {
+        let mut recording = Recording::new();
+        // 2024-03-01 12:00:00 UTC.
+        recording.set_started_at(Timestamp::from_unix_nanos(1_709_294_400_000_000_000));
+        for tick in 0..3u32 {
+            let car = CarInfo {
+                speed_kmh: 36.0,
+                gear: 3,
+                // Heading north at 10 m/s.
+                car_coordinates: [0.0, 1.0, 10.0 * tick as f32],
+                ..CarInfo::default()
+            };
+            recording.push(Duration::from_secs(u64::from(tick)), &car.to_bytes());
+        }
+        let trace = Trace::from_recording(&recording);
+
+        let mut vbo = Vec::new();
+        RaceChronoExport::new(&trace)
+            .origin(45.0, 9.0)
+            .write_vbo(&mut vbo)
+            .expect("written");
+        let vbo = String::from_utf8(vbo).expect("utf-8");
+
+        assert!(vbo.starts_with("File created on 01/03/2024 @ 12:00:00\n"));
+        let data: Vec<&str> = vbo
+            .split("[data]\n")
+            .nth(1)
+            .expect("data section")
+            .lines()
+            .collect();
+        assert_eq!(data.len(), 3);
+        // 20 m north of 45° N is 0.0108' further; 9° E is 540' west-negative.
+        assert_eq!(
+            data[2],
+            "008 120002.00 +02700.01078 -00540.00000 036.000 000.00 +00001.00 0 2 0.0 0.0 0.0000 0.000 0.000"
+        );
+    }
+}
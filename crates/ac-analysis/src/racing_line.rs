@@ -0,0 +1,325 @@
+//! A lap's driven line, for overlays that draw the classic speed-coloured
+//! racing line. Each point carries where the car was on the ground plane with
+//! its speed, gear and brake there; the line is written as JSON for overlays
+//! that do their own drawing, or as SVG ready to show.
+
+use std::io::{self, Write};
+
+use crate::trace::Trace;
+
+/// How far the SVG keeps the line from its edges, in metres.
+const SVG_MARGIN: f32 = 10.0;
+
+/// One point of the driven line.
+///
+/// * `x`, `z`: where the car was on the ground plane, from `car_coordinates`.
+/// * `position`: where on track, 0 to 1.
+/// * `speed_kmh`: the speed there.
+/// * `gear`: the gear, as AC reports it.
+/// * `brake`: brake input, 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinePoint {
+    pub x: f32,
+    pub z: f32,
+    pub position: f32,
+    pub speed_kmh: f32,
+    pub gear: i32,
+    pub brake: f32,
+}
+
+/// The line driven on one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `points`: a point per sample, in the order they were driven.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RacingLine {
+    pub lap: u32,
+    pub points: Vec<LinePoint>,
+}
+
+impl RacingLine {
+    /// the line a lap took. `None` for an empty lap.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`, or downsampled first for a lighter line.
+    pub fn of(lap: &Trace) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let points = samples
+            .iter()
+            .map(|sample| {
+                let car = &sample.car;
+                let [x, _, z] = car.car_coordinates;
+                LinePoint {
+                    x,
+                    z,
+                    position: car.car_pos_normalized,
+                    speed_kmh: car.speed_kmh,
+                    gear: car.gear,
+                    brake: car.brake,
+                }
+            })
+            .collect();
+
+        Some(Self {
+            lap: first.car.lap_count,
+            points,
+        })
+    }
+
+    /// the slowest and fastest speed on the line, in km/h. Speeds that
+    /// aren't numbers are passed over.
+    pub fn speed_range(&self) -> (f32, f32) {
+        self.points
+            .iter()
+            .fold((f32::INFINITY, 0.0), |(min, max), point| {
+                (min.min(point.speed_kmh), max.max(point.speed_kmh))
+            })
+    }
+
+    /// writes the line as JSON: the lap and an array of points with every field.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_json<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "{{\"lap\":{},\"points\":[", self.lap)?;
+        for (i, point) in self.points.iter().enumerate() {
+            let comma = if i == 0 { "" } else { "," };
+            write!(
+                writer,
+                "{comma}{{\"x\":{},\"z\":{},\"position\":{},\"speed_kmh\":{},\"gear\":{},\"brake\":{}}}",
+                number(point.x),
+                number(point.z),
+                number(point.position),
+                number(point.speed_kmh),
+                point.gear,
+                number(point.brake),
+            )?;
+        }
+        writeln!(writer, "]}}")?;
+
+        writer.flush()
+    }
+
+    /// writes the line as SVG, seen from above with `z` running down the
+    /// page, one metre to a user unit. Each stretch between two points is
+    /// coloured by its speed, from blue at the slowest to red at the fastest,
+    /// or grey where the speed isn't a number. Points without a position on
+    /// the ground plane are left out.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_svg<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let placed: Vec<&LinePoint> = self
+            .points
+            .iter()
+            .filter(|p| p.x.is_finite() && p.z.is_finite())
+            .collect();
+        let (min_x, max_x, min_z, max_z) = placed.iter().fold(
+            (
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+            ),
+            |(min_x, max_x, min_z, max_z), p| {
+                (
+                    min_x.min(p.x),
+                    max_x.max(p.x),
+                    min_z.min(p.z),
+                    max_z.max(p.z),
+                )
+            },
+        );
+        let (left, top) = if placed.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (min_x - SVG_MARGIN, min_z - SVG_MARGIN)
+        };
+        let width = (max_x - min_x).max(0.0) + 2.0 * SVG_MARGIN;
+        let height = (max_z - min_z).max(0.0) + 2.0 * SVG_MARGIN;
+
+        writeln!(
+            writer,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{left} {top} {width} {height}\">"
+        )?;
+        writeln!(
+            writer,
+            "<g fill=\"none\" stroke-width=\"3\" stroke-linecap=\"round\">"
+        )?;
+
+        let (slowest, fastest) = self.speed_range();
+        let spread = (fastest - slowest).max(f32::EPSILON);
+        for pair in placed.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let share = ((a.speed_kmh + b.speed_kmh) / 2.0 - slowest) / spread;
+            let stroke = if share.is_nan() {
+                "grey".to_string()
+            } else {
+                let hue = (240.0 * (1.0 - share.clamp(0.0, 1.0))).round();
+                format!("hsl({hue},100%,50%)")
+            };
+            writeln!(
+                writer,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{stroke}\"/>",
+                a.x, a.z, b.x, b.z
+            )?;
+        }
+
+        writeln!(writer, "</g>\n</svg>")?;
+        writer.flush()
+    }
+}
+
+/// a float as a JSON number; JSON has no NaN or infinity, so those are null.
+fn number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod racing_line_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{racing_line::RacingLine, trace::Trace};
+
+    /// a car at `x` metres along, `position` round the lap, doing `speed_kmh`.
+    fn car((x, position, speed_kmh): (f32, f32, f32)) -> CarInfo {
+        CarInfo {
+            lap_count: 1,
+            car_coordinates: [x, 0.0, 5.0],
+            car_pos_normalized: position,
+            speed_kmh,
+            gear: 4,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a lap sampled every 100 ms.
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+        lap
+    }
+
+    fn svg(line: &RacingLine) -> String {
+        let mut svg = Vec::new();
+        line.write_svg(&mut svg).expect("written");
+        String::from_utf8(svg).expect("utf-8")
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_line() {
+        assert_eq!(RacingLine::of(&Trace::new()), None);
+
+        let line = RacingLine {
+            lap: 0,
+            points: Vec::new(),
+        };
+        assert!(svg(&line).contains(r#"viewBox="0 0 20 20""#));
+        assert!(!svg(&line).contains("<line"));
+    }
+
+    #[test]
+    fn a_single_sample_is_a_point_without_stretches() {
+        let line = RacingLine::of(&lap([car((30.0, 0.5, 120.0))])).expect("not empty");
+        assert_eq!(line.points.len(), 1);
+        assert_eq!(line.speed_range(), (120.0, 120.0));
+
+        let svg = svg(&line);
+        assert!(svg.contains(r#"viewBox="20 -5 20 20""#), "{svg}");
+        assert!(!svg.contains("<line"));
+    }
+
+    #[test]
+    fn the_line_is_written_as_json_with_null_for_nan() {
+        let mut samples = [(0.0, 0.0, 100.0), (20.0, 0.25, 200.0), (40.0, 0.5, 100.0)].map(car);
+        samples[2].brake = f32::NAN;
+        let line = RacingLine::of(&lap(samples)).expect("not empty");
+
+        let mut json = Vec::new();
+        line.write_json(&mut json).expect("written");
+        let json = String::from_utf8(json).expect("utf-8");
+        assert!(json.starts_with(
+            r#"{"lap":1,"points":[{"x":0,"z":5,"position":0,"speed_kmh":100,"gear":4,"brake":0},"#
+        ));
+        assert!(json.ends_with("\"brake\":null}]}\n"), "{json}");
+    }
+
+    #[test]
+    fn stretches_are_coloured_from_slowest_to_fastest() {
+        let line = RacingLine::of(&lap([
+            (0.0, 0.0, 100.0),
+            (20.0, 0.25, 100.0),
+            (40.0, 0.5, 200.0),
+            (60.0, 0.75, 200.0),
+        ]
+        .map(car)))
+        .expect("not empty");
+        assert_eq!(line.speed_range(), (100.0, 200.0));
+
+        let svg = svg(&line);
+        assert!(svg.contains(r#"viewBox="-10 -5 80 20""#), "{svg}");
+        assert_eq!(svg.matches("stroke=\"hsl(240,100%,50%)\"").count(), 1);
+        assert_eq!(svg.matches("stroke=\"hsl(120,100%,50%)\"").count(), 1);
+        assert_eq!(svg.matches("stroke=\"hsl(0,100%,50%)\"").count(), 1);
+    }
+
+    #[test]
+    fn a_standing_car_is_drawn_at_the_slow_end() {
+        let line = RacingLine::of(&lap(
+            [(0.0, 0.0, 0.0), (0.0, 0.0, 0.0), (1.0, 0.0, 0.0)].map(car)
+        ))
+        .expect("not empty");
+        assert_eq!(line.speed_range(), (0.0, 0.0));
+        assert_eq!(
+            svg(&line).matches("stroke=\"hsl(240,100%,50%)\"").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn nan_speeds_are_grey_and_nan_positions_left_out() {
+        let line = RacingLine::of(&lap([
+            car((0.0, 0.0, 100.0)),
+            car((10.0, 0.1, f32::NAN)),
+            car((f32::NAN, 0.2, 150.0)),
+            car((20.0, 0.3, 200.0)),
+        ]))
+        .expect("not empty");
+        assert_eq!(line.speed_range(), (100.0, 200.0));
+
+        let svg = svg(&line);
+        assert!(!svg.contains("NaN"), "{svg}");
+        assert!(svg.contains(r#"viewBox="-10 -5 40 20""#), "{svg}");
+        assert_eq!(svg.matches("<line").count(), 2);
+        assert_eq!(svg.matches("stroke=\"grey\"").count(), 2);
+    }
+
+    #[test]
+    fn a_lap_across_the_line_keeps_the_order_driven() {
+        let line = RacingLine::of(&lap([
+            (0.0, 0.9, 100.0),
+            (10.0, 0.95, 100.0),
+            (20.0, 0.0, 100.0),
+            (30.0, 0.05, 100.0),
+        ]
+        .map(car)))
+        .expect("not empty");
+        assert_eq!(line.lap, 1);
+        let positions: Vec<f32> = line.points.iter().map(|p| p.position).collect();
+        assert_eq!(positions, [0.9, 0.95, 0.0, 0.05]);
+        assert_eq!(svg(&line).matches("<line").count(), 3);
+    }
+}
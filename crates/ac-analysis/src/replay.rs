@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use ac_parser::{Event, ParserError, Recording};
+
+use crate::clock::{Clock, SystemClock};
+
+/// The slowest and fastest playback speeds.
+pub const REPLAY_SPEEDS: (f32, f32) = (0.25, 16.0);
+
+/// Plays a recording back as parsed events, paced by the recorded arrival times.
+///
+/// Pacing goes through a `Clock`: with a `SystemClock` events arrive in real time,
+/// with a `VirtualClock` a whole session replays instantly and deterministically.
+/// Like a video, playback can be sped up or slowed down, paused, stepped frame
+/// by frame either way, and moved to a time or a lap.
+///
+/// * `recording`: the recording being played.
+/// * `clock`: the clock used to wait between frames.
+/// * `next`: index of the next frame to play.
+/// * `position`, `anchored_at`: the playhead's time in the recording, as of
+///   the clock's time when it was last moved or the speed changed.
+/// * `speed`: recording time played per unit of clock time.
+/// * `paused`: whether playback has stopped.
+pub struct Replayer<'a, C = SystemClock> {
+    recording: &'a Recording,
+    clock: C,
+    next: usize,
+    position: Duration,
+    anchored_at: Duration,
+    speed: f32,
+    paused: bool,
+}
+
+impl<'a> Replayer<'a> {
+    /// creates a replayer that plays back in real time.
+    ///
+    /// * `recording`: the recording to play.
+    pub fn new(recording: &'a Recording) -> Self {
+        Self::with_clock(recording, SystemClock::new())
+    }
+}
+
+impl<'a, C> Replayer<'a, C>
+where
+    C: Clock,
+{
+    /// creates a replayer paced by the given clock.
+    ///
+    /// * `recording`: the recording to play.
+    /// * `clock`: the clock to wait on between frames.
+    pub fn with_clock(recording: &'a Recording, clock: C) -> Self {
+        let anchored_at = clock.now();
+
+        Self {
+            recording,
+            clock,
+            next: 0,
+            position: Duration::ZERO,
+            anchored_at,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// where the playhead is in the recording.
+    pub fn playhead(&self) -> Duration {
+        if self.paused {
+            return self.position;
+        }
+
+        let played = self.clock.now().saturating_sub(self.anchored_at);
+        self.position + played.mul_f32(self.speed)
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// plays faster or slower from here on.
+    ///
+    /// * `speed`: e.g. 2 for double speed; clamped to `REPLAY_SPEEDS`.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.anchor(self.playhead());
+        self.speed = speed.clamp(REPLAY_SPEEDS.0, REPLAY_SPEEDS.1);
+    }
+
+    /// stops playback: until `resume`, iterating yields nothing and only
+    /// `step` and `step_back` move through the recording.
+    pub fn pause(&mut self) {
+        self.anchor(self.playhead());
+        self.paused = true;
+    }
+
+    /// carries on playing from the playhead.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.anchor(self.position);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// moves the playhead, so the next frame played is the first at or after `at`.
+    ///
+    /// * `at`: a time in the recording.
+    pub fn seek(&mut self, at: Duration) {
+        self.next = self.recording.frames().partition_point(|f| f.at < at);
+        self.anchor(at);
+    }
+
+    /// moves the playhead to the first CarInfo of a lap, returning whether
+    /// the recording has one.
+    ///
+    /// * `lap`: the lap count, as CarInfo reports it.
+    pub fn seek_to_lap(&mut self, lap: u32) -> bool {
+        let frames = self.recording.frames();
+        let Some(i) = frames.iter().position(|frame| {
+            matches!(Event::try_parse(&frame.packet), Ok(Event::CarInfo(car)) if car.lap_count == lap)
+        }) else {
+            return false;
+        };
+
+        self.seek(frames[i].at);
+        true
+    }
+
+    /// plays the next frame straight away, without waiting for it, paused or
+    /// not, and moves the playhead to it.
+    pub fn step(&mut self) -> Option<Result<Event, ParserError>> {
+        let frame = self.recording.frames().get(self.next)?;
+        self.next += 1;
+        self.anchor(frame.at);
+
+        Some(Event::try_parse(&frame.packet))
+    }
+
+    /// plays the frame before the last one played and moves the playhead back
+    /// to it, so playing on repeats what came after it.
+    pub fn step_back(&mut self) -> Option<Result<Event, ParserError>> {
+        let before = self.next.checked_sub(2)?;
+        let frame = &self.recording.frames()[before];
+        self.next = before + 1;
+        self.anchor(frame.at);
+
+        Some(Event::try_parse(&frame.packet))
+    }
+
+    /// * `position`: where the playhead is, as of now.
+    fn anchor(&mut self, position: Duration) {
+        self.position = position;
+        self.anchored_at = self.clock.now();
+    }
+}
+
+impl<C> Iterator for Replayer<'_, C>
+where
+    C: Clock,
+{
+    type Item = Result<Event, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.paused {
+            return None;
+        }
+        let frame = self.recording.frames().get(self.next)?;
+        self.next += 1;
+
+        let ahead = frame.at.saturating_sub(self.position).div_f32(self.speed);
+        let due = self.anchored_at + ahead;
+        let now = self.clock.now();
+        if due > now {
+            self.clock.sleep(due - now);
+        }
+
+        Some(Event::try_parse(&frame.packet))
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event, HandshakeResponse, Recording};
+
+    use crate::{
+        clock::{Clock, VirtualClock},
+        replay::Replayer,
+    };
+
+    #[test]
+    fn replay_on_virtual_clock_keeps_recorded_timing() {
+        let mut recording = Recording::new();
+        recording.push(
+            Duration::from_secs(1),
+            &HandshakeResponse::default().to_bytes(),
+        );
+        recording.push(Duration::from_secs(90), &[0u8; 3]);
+
+        let clock = VirtualClock::new();
+        let mut replayer = Replayer::with_clock(&recording, clock.clone());
+
+        let first = replayer.next().expect("first frame");
+        assert!(matches!(first, Ok(Event::HandshakeResponse(_))));
+        assert_eq!(clock.now(), Duration::from_secs(1));
+
+        let second = replayer.next().expect("second frame");
+        assert!(second.is_err(), "3 bytes is no event");
+        assert_eq!(clock.now(), Duration::from_secs(90));
+
+        assert!(replayer.next().is_none());
+    }
+
+    #[test]
+    fn playback_seeks_pauses_steps_and_changes_speed() {
+        // A CarInfo a second, with lap 1 starting at 5 s.
+        let mut recording = Recording::new();
+        for second in 0..10u32 {
+            let car = CarInfo {
+                lap_count: second / 5,
+                lap_time: second * 1000,
+                ..CarInfo::default()
+            };
+            recording.push(Duration::from_secs(second.into()), &car.to_bytes());
+        }
+        let lap_time = |played: Option<Result<Event, _>>| match played {
+            Some(Ok(Event::CarInfo(car))) => car.lap_time,
+            other => panic!("expected CarInfo, got {other:?}"),
+        };
+
+        let clock = VirtualClock::new();
+        let mut replayer = Replayer::with_clock(&recording, clock.clone());
+        replayer.set_speed(2.0);
+        assert_eq!(lap_time(replayer.next()), 0);
+        assert_eq!(lap_time(replayer.next()), 1000);
+        assert_eq!(clock.now(), Duration::from_millis(500));
+
+        assert!(replayer.seek_to_lap(1));
+        assert_eq!(lap_time(replayer.next()), 5000);
+        assert_eq!(
+            clock.now(),
+            Duration::from_millis(500),
+            "no wait after seeking"
+        );
+        assert_eq!(lap_time(replayer.next()), 6000);
+        assert_eq!(clock.now(), Duration::from_secs(1));
+        assert!(!replayer.seek_to_lap(7));
+
+        replayer.pause();
+        assert!(replayer.next().is_none());
+        assert_eq!(lap_time(replayer.step()), 7000);
+        assert_eq!(lap_time(replayer.step_back()), 6000);
+        assert_eq!(replayer.playhead(), Duration::from_secs(6));
+        assert_eq!(clock.now(), Duration::from_secs(1), "stepping doesn't wait");
+
+        replayer.resume();
+        replayer.set_speed(100.0);
+        assert_eq!(replayer.speed(), 16.0);
+        replayer.seek(Duration::from_millis(8500));
+        assert_eq!(lap_time(replayer.next()), 9000);
+        assert_eq!(
+            clock.now(),
+            Duration::from_secs(1) + Duration::from_millis(500) / 16
+        );
+        assert!(replayer.next().is_none());
+    }
+}
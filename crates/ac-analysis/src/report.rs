@@ -0,0 +1,751 @@
+//! A session report as one self-contained HTML file, for sharing results
+//! without any other tooling: a lap table with sector times, a chart of each
+//! sector's time lap by lap, every lap's speed trace over the lap, and the
+//! fastest lap's racing line. Charts are inline SVG and the styling is
+//! inline CSS, so the file opens anywhere and needs nothing else.
+//!
+//! The same report's tables also come as Markdown, which Discord, forums and
+//! GitHub all render, for posting results straight into a league thread, and,
+//! with the `xlsx` feature, as a workbook for league organizers who keep
+//! results in spreadsheets.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+#[cfg(feature = "xlsx")]
+use ac_parser::CarInfo;
+use ac_parser::{Recording, Units};
+
+#[cfg(feature = "xlsx")]
+use crate::{
+    brakes::BRAKE_ON,
+    pipeline::LapTime,
+    snapshot::SessionSnapshot,
+    validity::{LapValidity, ValidityRules},
+    workbook::{Cell, Sheet, write_xlsx},
+};
+use crate::{delta_map::time_at, racing_line::RacingLine, sectors::Sectors, trace::Trace};
+
+/// Throttle at or above which the car counts as flat out, 0 to 1.
+#[cfg(feature = "xlsx")]
+const FULL_THROTTLE: f32 = 0.98;
+
+/// How wide and tall the charts are, in pixels.
+const CHART_WIDTH: f32 = 720.0;
+const CHART_HEIGHT: f32 = 240.0;
+
+/// Colours handed out to chart series in turn.
+const PALETTE: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+];
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse}th,td{padding:4px 10px;text-align:right;border-bottom:1px solid #ddd}\
+tr.best{font-weight:bold;color:#7b1fa2}tr.partial{color:#999}svg{max-width:100%;height:auto}";
+
+/// One lap of the session.
+///
+/// * `trace`: its samples.
+/// * `lap`: its lap counter.
+/// * `time`: its lap time, from the next lap's `last_lap` where there is one.
+/// * `complete`: whether it was driven from the line to the line.
+#[derive(Debug, Clone)]
+struct ReportLap {
+    trace: Trace,
+    lap: u32,
+    time: Duration,
+    complete: bool,
+}
+
+/// A session report, written as HTML or Markdown.
+///
+/// * `title`: the report's heading.
+/// * `laps`: the session's laps.
+/// * `sectors`: how the lap table and sector chart split laps.
+/// * `units`: what speeds are shown in, if not the preferred units.
+#[derive(Debug, Clone)]
+pub struct SessionReport {
+    title: String,
+    laps: Vec<ReportLap>,
+    sectors: Sectors,
+    units: Option<Units>,
+}
+
+impl SessionReport {
+    /// a report of a session's laps, with equal thirds as sectors.
+    ///
+    /// * `session`: the session, e.g. from `Trace::from_recording`.
+    pub fn new(session: &Trace) -> Self {
+        let traces = session.laps();
+        let laps = traces
+            .iter()
+            .enumerate()
+            .filter_map(|(i, trace)| {
+                let (first, last) = (trace.samples().first()?, trace.samples().last()?);
+                let reported = traces
+                    .get(i + 1)
+                    .and_then(|next| next.samples().first())
+                    .map(|next| next.car.last_lap)
+                    .filter(|&ms| ms > 0);
+
+                Some(ReportLap {
+                    trace: trace.clone(),
+                    lap: first.car.lap_count,
+                    time: reported
+                        .map(|ms| Duration::from_millis(ms.into()))
+                        .unwrap_or_else(|| trace.duration()),
+                    complete: first.distance <= 0.05 && last.distance >= 0.95,
+                })
+            })
+            .collect();
+
+        Self {
+            title: "Session report".to_string(),
+            laps,
+            sectors: Sectors::default(),
+            units: None,
+        }
+    }
+
+    /// a report of a recording's CarInfo.
+    ///
+    /// * `recording`: the recording.
+    pub fn from_recording(recording: &Recording) -> Self {
+        Self::new(&Trace::from_recording(recording))
+    }
+
+    /// sets the report's heading.
+    ///
+    /// * `title`: e.g. the track, car and date.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// splits laps into these sectors rather than equal thirds.
+    ///
+    /// * `sectors`: e.g. from `Sectors::from_corners`.
+    pub fn sectors(mut self, sectors: Sectors) -> Self {
+        self.sectors = sectors;
+        self
+    }
+
+    /// shows speeds in these units rather than `Units::preferred()`.
+    ///
+    /// * `units`: e.g. `Units::Imperial`.
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    fn units_now(&self) -> Units {
+        self.units.unwrap_or_else(Units::preferred)
+    }
+
+    /// the fastest complete lap, if there is one.
+    fn best(&self) -> Option<&ReportLap> {
+        self.laps
+            .iter()
+            .filter(|lap| lap.complete)
+            .min_by_key(|lap| lap.time)
+    }
+
+    /// each sector's time through a lap.
+    fn sector_times(&self, lap: &Trace) -> Vec<Duration> {
+        let starts = self.sectors.starts();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &from)| {
+                let to = starts.get(i + 1).copied().unwrap_or(1.0);
+                time_at(lap, to).saturating_sub(time_at(lap, from))
+            })
+            .collect()
+    }
+
+    /// the fastest time through each sector over the complete laps, with the
+    /// lap it was set on.
+    fn best_sectors(&self) -> Vec<Option<(Duration, u32)>> {
+        let mut bests = vec![None; self.sectors.len()];
+        for lap in self.laps.iter().filter(|lap| lap.complete) {
+            for (best, time) in bests.iter_mut().zip(self.sector_times(&lap.trace)) {
+                if best.is_none_or(|(fastest, _)| time < fastest) {
+                    *best = Some((time, lap.lap));
+                }
+            }
+        }
+
+        bests
+    }
+
+    /// writes the report as a single HTML page.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_html<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let title = escape(&self.title);
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(
+            writer,
+            "<html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}</style></head><body>"
+        )?;
+        writeln!(writer, "<h1>{title}</h1>")?;
+
+        self.write_table(&mut writer)?;
+        self.write_sector_chart(&mut writer)?;
+        self.write_speed_chart(&mut writer)?;
+
+        if let Some(line) = self.best().and_then(|best| RacingLine::of(&best.trace)) {
+            writeln!(writer, "<h2>Track map</h2>")?;
+            line.write_svg(&mut writer)?;
+        }
+
+        writeln!(writer, "</body></html>")?;
+        writer.flush()
+    }
+
+    fn write_table<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let units = self.units_now();
+        writeln!(writer, "<h2>Laps</h2>\n<table>")?;
+        write!(writer, "<tr><th>Lap</th><th>Time</th>")?;
+        for sector in 1..=self.sectors.len() {
+            write!(writer, "<th>S{sector}</th>")?;
+        }
+        writeln!(writer, "<th>Top speed</th></tr>")?;
+
+        let best = self.best().map(|best| best.lap);
+        for lap in &self.laps {
+            let class = match (lap.complete, Some(lap.lap) == best) {
+                (false, _) => " class=\"partial\"",
+                (true, true) => " class=\"best\"",
+                (true, false) => "",
+            };
+            let time = if lap.complete {
+                lap_time(lap.time)
+            } else {
+                "-".to_string()
+            };
+            write!(writer, "<tr{class}><td>{}</td><td>{time}</td>", lap.lap)?;
+
+            for sector in self.sector_times(&lap.trace) {
+                if lap.complete {
+                    write!(writer, "<td>{:.3}</td>", sector.as_secs_f32())?;
+                } else {
+                    write!(writer, "<td>-</td>")?;
+                }
+            }
+
+            writeln!(
+                writer,
+                "<td>{}</td></tr>",
+                units.format_speed(top_speed(&lap.trace))
+            )?;
+        }
+
+        writeln!(writer, "</table>")
+    }
+
+    fn write_sector_chart<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let complete: Vec<_> = self.laps.iter().filter(|lap| lap.complete).collect();
+        let times: Vec<Vec<f32>> = complete
+            .iter()
+            .map(|lap| {
+                self.sector_times(&lap.trace)
+                    .iter()
+                    .map(Duration::as_secs_f32)
+                    .collect()
+            })
+            .collect();
+
+        let series: Vec<Series> = (0..self.sectors.len())
+            .map(|sector| Series {
+                label: format!("S{}", sector + 1),
+                colour: PALETTE[sector % PALETTE.len()],
+                width: 2.0,
+                points: complete
+                    .iter()
+                    .zip(&times)
+                    .map(|(lap, times)| (lap.lap as f32, times[sector]))
+                    .collect(),
+            })
+            .collect();
+
+        writeln!(writer, "<h2>Sector times</h2>")?;
+        chart(writer, &series, "lap", "seconds")
+    }
+
+    fn write_speed_chart<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let units = self.units_now();
+        let best = self.best().map(|best| best.lap);
+        let mut series: Vec<Series> = self
+            .laps
+            .iter()
+            .filter(|lap| lap.complete)
+            .map(|lap| {
+                let is_best = Some(lap.lap) == best;
+                Series {
+                    label: format!("Lap {}", lap.lap),
+                    colour: if is_best { "#d62728" } else { "#bbbbbb" },
+                    width: if is_best { 2.0 } else { 1.0 },
+                    points: lap
+                        .trace
+                        .samples()
+                        .iter()
+                        .map(|s| (s.distance, units.speed(s.car.speed_kmh)))
+                        .collect(),
+                }
+            })
+            .collect();
+        // The best lap goes last, so it's drawn on top.
+        series.sort_by_key(|series| series.width > 1.0);
+
+        writeln!(writer, "<h2>Speed</h2>")?;
+        chart(writer, &series, "lap distance", units.speed_unit())
+    }
+
+    /// writes the report as an xlsx workbook with four sheets: laps, sector
+    /// times, stints between pit visits, and per-lap stats. Times are in
+    /// seconds, so the spreadsheet can sum and sort them; speeds are bare
+    /// numbers in the report's units.
+    ///
+    /// * `writer`: where it goes.
+    #[cfg(feature = "xlsx")]
+    pub fn write_xlsx<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let units = self.units_now();
+        let rules = ValidityRules::default();
+        let validity: Vec<LapValidity> = self
+            .laps
+            .iter()
+            .map(|lap| rules.judge(&lap.trace, None))
+            .collect();
+        let best = self.best().map(|best| best.lap);
+
+        let mut laps = Sheet::new(
+            "Laps",
+            &[
+                "Lap",
+                "Time",
+                "Seconds",
+                "Complete",
+                "Validity",
+                "Top speed",
+            ],
+        );
+        for (lap, &validity) in self.laps.iter().zip(&validity) {
+            let (time, seconds) = if lap.complete {
+                (lap_time(lap.time).into(), lap.time.as_secs_f32().into())
+            } else {
+                (Cell::Empty, Cell::Empty)
+            };
+            laps.push(vec![
+                lap.lap.into(),
+                time,
+                seconds,
+                if lap.complete { "yes" } else { "no" }.into(),
+                validity_cell(validity),
+                units.speed(top_speed(&lap.trace)).into(),
+            ]);
+        }
+
+        let header: Vec<String> = (1..=self.sectors.len()).map(|s| format!("S{s}")).collect();
+        let header: Vec<&str> = ["Lap"]
+            .into_iter()
+            .chain(header.iter().map(String::as_str))
+            .collect();
+        let mut sectors = Sheet::new("Sectors", &header);
+        for lap in self.laps.iter().filter(|lap| lap.complete) {
+            let mut row = vec![lap.lap.into()];
+            row.extend(
+                self.sector_times(&lap.trace)
+                    .iter()
+                    .map(|time| time.as_secs_f32().into()),
+            );
+            sectors.push(row);
+        }
+
+        // Stints come from the snapshot's rules: each out-lap starts one.
+        let snapshot = SessionSnapshot {
+            laps: self
+                .laps
+                .iter()
+                .zip(&validity)
+                .filter(|(lap, validity)| lap.complete || **validity == LapValidity::OutLap)
+                .map(|(lap, &validity)| LapTime {
+                    lap: lap.lap,
+                    time: lap.time,
+                    best: Some(lap.lap) == best,
+                    validity,
+                })
+                .collect(),
+            lap_count: None,
+        };
+        let mut stints = Sheet::new("Stints", &["Stint", "First lap", "Laps", "Best"]);
+        for (i, stint) in snapshot.stints().iter().enumerate() {
+            stints.push(vec![
+                (i as u32 + 1).into(),
+                stint.first_lap.into(),
+                stint.laps.into(),
+                stint
+                    .best
+                    .map_or(Cell::Empty, |best| best.as_secs_f32().into()),
+            ]);
+        }
+
+        let mut stats = Sheet::new(
+            "Stats",
+            &[
+                "Lap",
+                "Mean speed",
+                "Top speed",
+                "Max RPM",
+                "Full throttle",
+                "Braking",
+                "Peak lateral G",
+                "Peak braking G",
+            ],
+        );
+        for lap in &self.laps {
+            let samples = lap.trace.samples();
+            let n = samples.len().max(1) as f32;
+            let share =
+                |on: fn(&CarInfo) -> bool| samples.iter().filter(|s| on(&s.car)).count() as f32 / n;
+            let peak = |value: fn(&CarInfo) -> f32| {
+                samples.iter().map(|s| value(&s.car)).fold(0.0, f32::max)
+            };
+
+            stats.push(vec![
+                lap.lap.into(),
+                units
+                    .speed(samples.iter().map(|s| s.car.speed_kmh).sum::<f32>() / n)
+                    .into(),
+                units.speed(top_speed(&lap.trace)).into(),
+                peak(|car| car.engine_rpm).into(),
+                share(|car| car.gas >= FULL_THROTTLE).into(),
+                share(|car| car.brake > BRAKE_ON).into(),
+                peak(|car| car.accg_horizontal.abs()).into(),
+                peak(|car| -car.accg_frontal).into(),
+            ]);
+        }
+
+        write_xlsx(writer, &[laps, sectors, stints, stats])
+    }
+
+    /// writes the report's lap and best tables as Markdown, leaving the charts out.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_markdown<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let units = self.units_now();
+        writeln!(writer, "# {}\n", self.title)?;
+
+        writeln!(writer, "## Laps\n")?;
+        write!(writer, "| Lap | Time |")?;
+        for sector in 1..=self.sectors.len() {
+            write!(writer, " S{sector} |")?;
+        }
+        writeln!(writer, " Top speed |")?;
+        writeln!(
+            writer,
+            "|---:|---:|{}---:|",
+            "---:|".repeat(self.sectors.len())
+        )?;
+
+        let best = self.best().map(|best| best.lap);
+        for lap in &self.laps {
+            // The best lap is in bold.
+            let mark = if Some(lap.lap) == best { "**" } else { "" };
+            let time = if lap.complete {
+                lap_time(lap.time)
+            } else {
+                "-".to_string()
+            };
+            write!(writer, "| {mark}{}{mark} | {mark}{time}{mark} |", lap.lap)?;
+
+            for sector in self.sector_times(&lap.trace) {
+                if lap.complete {
+                    write!(writer, " {:.3} |", sector.as_secs_f32())?;
+                } else {
+                    write!(writer, " - |")?;
+                }
+            }
+            writeln!(writer, " {} |", units.format_speed(top_speed(&lap.trace)))?;
+        }
+
+        writeln!(writer, "\n## Bests\n")?;
+        let Some(best) = self.best() else {
+            writeln!(writer, "No complete laps.")?;
+            return writer.flush();
+        };
+        writeln!(writer, "| | Time | Lap |\n|---|---:|---:|")?;
+        writeln!(writer, "| Lap | {} | {} |", lap_time(best.time), best.lap)?;
+
+        let sectors = self.best_sectors();
+        for (sector, (time, lap)) in sectors.iter().flatten().enumerate() {
+            writeln!(
+                writer,
+                "| S{} | {:.3} | {lap} |",
+                sector + 1,
+                time.as_secs_f32()
+            )?;
+        }
+        let ideal: Duration = sectors.iter().flatten().map(|(time, _)| *time).sum();
+        writeln!(writer, "| Ideal | {} | |", lap_time(ideal))?;
+
+        writer.flush()
+    }
+}
+
+/// a lap's validity, as a workbook cell.
+#[cfg(feature = "xlsx")]
+fn validity_cell(validity: LapValidity) -> Cell {
+    let name = match validity {
+        LapValidity::Valid => "valid",
+        LapValidity::Invalid(reason) => return Cell::Text(format!("invalid ({reason:?})")),
+        LapValidity::InLap => "in-lap",
+        LapValidity::OutLap => "out-lap",
+    };
+
+    Cell::from(name)
+}
+
+/// the fastest a lap went, in km/h.
+fn top_speed(lap: &Trace) -> f32 {
+    lap.samples()
+        .iter()
+        .map(|s| s.car.speed_kmh)
+        .fold(0.0, f32::max)
+}
+
+/// One line on a chart.
+struct Series {
+    label: String,
+    colour: &'static str,
+    width: f32,
+    points: Vec<(f32, f32)>,
+}
+
+/// writes an SVG line chart of some series, scaled to fit them all.
+fn chart<W: Write>(
+    writer: &mut W,
+    series: &[Series],
+    x_label: &str,
+    y_label: &str,
+) -> io::Result<()> {
+    let points = series.iter().flat_map(|series| &series.points);
+    let (min_x, max_x, min_y, max_y) = points.fold(
+        (
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(min_x, max_x, min_y, max_y), &(x, y)| {
+            (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+        },
+    );
+    if !min_x.is_finite() {
+        return writeln!(writer, "<p>No complete laps.</p>");
+    }
+
+    let (margin, width, height) = (40.0, CHART_WIDTH, CHART_HEIGHT);
+    let spread_x = (max_x - min_x).max(f32::EPSILON);
+    let spread_y = (max_y - min_y).max(f32::EPSILON);
+    let x = |value: f32| margin + (value - min_x) / spread_x * (width - 2.0 * margin);
+    let y = |value: f32| height - margin - (value - min_y) / spread_y * (height - 2.0 * margin);
+
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" width=\"{width}\" height=\"{height}\" font-size=\"11\">"
+    )?;
+    writeln!(
+        writer,
+        "<rect x=\"{margin}\" y=\"{margin}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#ccc\"/>",
+        width - 2.0 * margin,
+        height - 2.0 * margin
+    )?;
+    writeln!(
+        writer,
+        "<text x=\"{margin}\" y=\"{}\">{min_y:.1}</text><text x=\"{margin}\" y=\"{}\">{max_y:.1} {y_label}</text>",
+        height - margin + 14.0,
+        margin - 4.0
+    )?;
+    writeln!(
+        writer,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{x_label}</text>",
+        width - margin,
+        height - margin + 14.0
+    )?;
+
+    for series in series {
+        let path: Vec<String> = series
+            .points
+            .iter()
+            .map(|&(px, py)| format!("{:.1},{:.1}", x(px), y(py)))
+            .collect();
+        writeln!(
+            writer,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" points=\"{}\"><title>{}</title></polyline>",
+            series.colour,
+            series.width,
+            path.join(" "),
+            escape(&series.label)
+        )?;
+    }
+
+    writeln!(writer, "</svg>")
+}
+
+/// a lap time as `m:ss.mmm`.
+fn lap_time(time: Duration) -> String {
+    let ms = time.as_millis();
+    format!("{}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+/// text made safe to put in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod report_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Units};
+
+    use crate::{report::SessionReport, trace::Trace};
+
+    /// two full laps of 90 s and 88.5 s, then the start of a third.
+    fn session() -> Trace {
+        let mut session = Trace::new();
+        let mut at = Duration::ZERO;
+        for (lap, seconds, last_lap) in [(1, 90.0, 0), (2, 88.5, 90_000), (3, 10.0, 88_500)] {
+            let steps = if lap == 3 { 1 } else { 10 };
+            for i in 0..=steps {
+                let position = if lap == 3 {
+                    0.1 * i as f32
+                } else {
+                    i as f32 / 10.0
+                };
+                let car = CarInfo {
+                    lap_count: lap,
+                    last_lap,
+                    car_pos_normalized: position.min(0.999),
+                    car_coordinates: [100.0 * position, 0.0, 0.0],
+                    speed_kmh: 150.0 + 10.0 * lap as f32,
+                    ..CarInfo::default()
+                };
+                session.push(
+                    at + Duration::from_secs_f32(seconds * i as f32 / steps as f32),
+                    car,
+                );
+            }
+            at += Duration::from_secs_f32(seconds + 0.1);
+        }
+
+        session
+    }
+
+    #[test]
+    fn a_session_is_reported_as_one_html_page() {
+        let report = SessionReport::new(&session()).title("Spa <GT3>");
+        let mut html = Vec::new();
+        report.write_html(&mut html).expect("written");
+        let html = String::from_utf8(html).expect("utf-8");
+
+        assert!(html.contains("<h1>Spa &lt;GT3&gt;</h1>"));
+        assert!(html.contains("<tr><td>1</td><td>1:30.000</td>"), "{html}");
+        assert!(html.contains("<tr class=\"best\"><td>2</td><td>1:28.500</td>"));
+        assert!(html.contains("<tr class=\"partial\"><td>3</td><td>-</td>"));
+        // The sector chart, the speed chart and the track map.
+        assert_eq!(html.matches("<svg").count(), 3);
+        assert!(html.ends_with("</body></html>\n"));
+    }
+
+    #[test]
+    fn the_tables_are_written_as_markdown() {
+        let report = SessionReport::new(&session()).title("Spa GT3");
+        let mut markdown = Vec::new();
+        report.write_markdown(&mut markdown).expect("written");
+        let markdown = String::from_utf8(markdown).expect("utf-8");
+
+        assert!(markdown.starts_with("# Spa GT3\n"));
+        assert!(markdown.contains(
+            "| Lap | Time | S1 | S2 | S3 | Top speed |\n|---:|---:|---:|---:|---:|---:|\n"
+        ));
+        assert!(markdown.contains("| 1 | 1:30.000 | 30.000 |"), "{markdown}");
+        assert!(markdown.contains("| **2** | **1:28.500** | 29.500 |"));
+        assert!(markdown.contains("| 3 | - | - | - | - | 180.0 km/h |"));
+        assert!(markdown.contains("| Lap | 1:28.500 | 2 |"));
+        assert!(markdown.contains("| S1 | 29.500 | 2 |"));
+        assert!(markdown.contains("| Ideal | 1:28.500 | |"));
+
+        let mut imperial = Vec::new();
+        report
+            .units(Units::Imperial)
+            .write_markdown(&mut imperial)
+            .expect("written");
+        let imperial = String::from_utf8(imperial).expect("utf-8");
+        assert!(imperial.contains("| 3 | - | - | - | - | 111.8 mph |"));
+    }
+
+    #[test]
+    #[cfg(feature = "xlsx")]
+    fn the_tables_are_written_as_an_xlsx_workbook() {
+        use std::io::Cursor;
+
+        use calamine::{Data, Reader, Xlsx};
+
+        let mut xlsx = Vec::new();
+        SessionReport::new(&session())
+            .write_xlsx(&mut xlsx)
+            .expect("written");
+
+        let mut workbook = Xlsx::new(Cursor::new(xlsx)).expect("opens");
+        assert_eq!(
+            workbook.sheet_names(),
+            ["Laps", "Sectors", "Stints", "Stats"]
+        );
+
+        let laps = workbook.worksheet_range("Laps").expect("has laps");
+        assert_eq!(laps.get_value((0, 1)), Some(&Data::String("Time".into())));
+        assert_eq!(
+            laps.get_value((2, 1)),
+            Some(&Data::String("1:28.500".into()))
+        );
+        assert_eq!(laps.get_value((2, 2)), Some(&Data::Float(88.5)));
+        assert_eq!(laps.get_value((2, 4)), Some(&Data::String("valid".into())));
+        // The unfinished third lap has no time.
+        assert_eq!(laps.get_value((3, 1)), Some(&Data::Empty));
+        assert_eq!(laps.get_value((3, 3)), Some(&Data::String("no".into())));
+
+        let sectors = workbook.worksheet_range("Sectors").expect("has sectors");
+        assert_eq!(sectors.get_value((1, 1)), Some(&Data::Float(30.0)));
+        assert_eq!(sectors.height(), 3);
+
+        // Both full laps make one stint, its best lap 88.5 s.
+        let stints = workbook.worksheet_range("Stints").expect("has stints");
+        let stint: Vec<&Data> = (0..4).filter_map(|c| stints.get_value((1, c))).collect();
+        assert_eq!(
+            stint,
+            [
+                &Data::Float(1.0),
+                &Data::Float(1.0),
+                &Data::Float(2.0),
+                &Data::Float(88.5)
+            ]
+        );
+        assert_eq!(stints.height(), 2);
+    }
+}
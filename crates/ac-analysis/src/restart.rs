@@ -0,0 +1,188 @@
+//! Noticing when the driver restarts the session. AC doesn't say so; the lap
+//! counter drops back, the lap clock jumps back on the same lap, or the
+//! server answers a new handshake with a different track. Without a reset,
+//! bests and deltas from the abandoned session would carry on as if nothing
+//! had happened.
+//!
+//! A `RestartDetector` emits `Output::Restart` when it sees one, and a
+//! `Pipeline` then resets every stage after it, so it belongs first:
+//!
+//! ```
+//! # use ac_analysis::{PipelineBuilder, RestartDetector};
+//! let pipeline = PipelineBuilder::dashboard()
+//!     .insert_before("downsample", RestartDetector::new())
+//!     .build();
+//! ```
+
+use std::time::Duration;
+
+use ac_parser::{Event, Name};
+
+use crate::pipeline::{Output, Stage};
+
+/// How far the lap clock must jump back on the same lap to count as a
+/// restart rather than a packet arriving out of order.
+const RESTART_MS: u32 = 1000;
+
+/// What gave a restart away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartCause {
+    /// The lap counter went down.
+    LapReset,
+    /// The lap clock went back on the same lap.
+    TimeJump,
+    /// A new handshake named a different track or layout.
+    TrackChanged,
+}
+
+/// The session was restarted.
+///
+/// * `at`: when the first event of the new session arrived.
+/// * `cause`: what gave it away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionRestarted {
+    pub at: Duration,
+    pub cause: RestartCause,
+}
+
+/// Watches the player's CarInfo and handshakes for a restarted session.
+///
+/// * `track`: the track and layout of the last handshake.
+/// * `lap`: the lap counter and lap time of the last CarInfo.
+/// * `restarts`: how many restarts have been seen.
+#[derive(Debug, Clone, Default)]
+pub struct RestartDetector {
+    track: Option<(Name, Name)>,
+    lap: Option<(u32, u32)>,
+    restarts: u64,
+}
+
+impl RestartDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// checks an event, returning a restart if it starts a new session.
+    ///
+    /// * `at`: when the event arrived.
+    /// * `event`: the event.
+    pub fn observe(&mut self, at: Duration, event: &Event) -> Option<SessionRestarted> {
+        let cause = match event {
+            Event::HandshakeResponse(handshake) => {
+                let track = (handshake.track_name, handshake.track_config);
+                let previous = self.track.replace(track);
+                self.lap = None;
+                previous
+                    .is_some_and(|previous| previous != track)
+                    .then_some(RestartCause::TrackChanged)
+            }
+            Event::CarInfo(car) => match self.lap.replace((car.lap_count, car.lap_time)) {
+                Some((lap, _)) if car.lap_count < lap => Some(RestartCause::LapReset),
+                Some((lap, time)) if car.lap_count == lap && time > car.lap_time + RESTART_MS => {
+                    Some(RestartCause::TimeJump)
+                }
+                _ => None,
+            },
+            Event::LapInfo(_) => None,
+        }?;
+
+        self.restarts += 1;
+        Some(SessionRestarted { at, cause })
+    }
+
+    /// how many restarts have been seen.
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+}
+
+impl Stage for RestartDetector {
+    fn name(&self) -> &str {
+        "restart"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        outputs.extend(self.observe(at, event).map(Output::Restart));
+        true
+    }
+}
+
+#[cfg(test)]
+mod restart_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event, HandshakeResponse, Name};
+
+    use crate::{
+        pipeline::{LapTimer, Output, PipelineBuilder},
+        restart::{RestartCause, RestartDetector},
+    };
+
+    fn car(lap_count: u32, lap_time: u32, last_lap: u32) -> Event {
+        Event::CarInfo(CarInfo {
+            lap_count,
+            lap_time,
+            last_lap,
+            ..CarInfo::default()
+        })
+    }
+
+    #[test]
+    fn restarts_are_detected_and_reset_later_stages() {
+        let track = |track| {
+            Event::HandshakeResponse(HandshakeResponse {
+                track_name: Name::from(track).expect("short name"),
+                ..HandshakeResponse::default()
+            })
+        };
+        let mut detector = RestartDetector::new();
+        let causes: Vec<_> = [
+            track("monza"),
+            car(0, 20_000, 0),
+            car(0, 19_990, 0),
+            car(0, 500, 0),
+            car(2, 500, 90_000),
+            car(0, 600, 0),
+            track("monza"),
+            track("spa"),
+        ]
+        .iter()
+        .filter_map(|event| detector.observe(Duration::ZERO, event))
+        .map(|restart| restart.cause)
+        .collect();
+        assert_eq!(
+            causes,
+            [
+                RestartCause::TimeJump,
+                RestartCause::LapReset,
+                RestartCause::TrackChanged
+            ]
+        );
+        assert_eq!(detector.restarts(), 3);
+
+        // A 100 s lap, then a restart and a slower one: without the reset the
+        // slower lap couldn't be the best.
+        let mut pipeline = PipelineBuilder::new()
+            .stage(RestartDetector::new())
+            .stage(LapTimer::new())
+            .build();
+        let mut bests = Vec::new();
+        for event in [
+            car(0, 0, 0),
+            car(1, 0, 100_000),
+            car(0, 0, 0),
+            car(1, 0, 110_000),
+        ] {
+            for output in pipeline.push(Duration::ZERO, &event) {
+                match output {
+                    Output::Lap(lap) => bests.push(lap.best),
+                    Output::Restart(restart) => {
+                        assert_eq!(restart.cause, RestartCause::LapReset)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(bests, [true, true]);
+    }
+}
@@ -0,0 +1,230 @@
+//! Where the car runs lowest and where it probably hits the ground. The
+//! telemetry has no floor contact flag, so a bottoming is inferred: a sudden
+//! jump in vertical G while the car sits within a few millimetres of the
+//! lowest it got all lap. Each is placed by track position and world
+//! coordinates, so it can be drawn on a track map.
+
+use std::time::Duration;
+
+use ac_parser::Wheel;
+
+use crate::trace::Trace;
+
+/// What counts as a bottoming.
+///
+/// * `g_jump`: how much vertical G has to change from one sample to the next.
+/// * `cg_margin`: how close to the lap's lowest centre of gravity height the car has to be, in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BottomingRules {
+    pub g_jump: f32,
+    pub cg_margin: f32,
+}
+
+impl Default for BottomingRules {
+    fn default() -> Self {
+        Self {
+            g_jump: 1.5,
+            cg_margin: 0.005,
+        }
+    }
+}
+
+/// A probable bottoming.
+///
+/// * `time`: when in the lap it happened.
+/// * `position`: where on track, 0 to 1.
+/// * `coordinates`: where in the world, as `car_coordinates`.
+/// * `cg_height`: the centre of gravity height then, in metres.
+/// * `g_jump`: the jump in vertical G.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bottoming {
+    pub time: Duration,
+    pub position: f32,
+    pub coordinates: [f32; 3],
+    pub cg_height: f32,
+    pub g_jump: f32,
+}
+
+/// The ride height of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `min_cg_height`: the lowest centre of gravity height, in metres.
+/// * `min_suspension`: the lowest suspension height of each wheel, indexed by `Wheel`.
+/// * `bottoming`: each probable bottoming, in the order they happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RideHeightReport {
+    pub lap: u32,
+    pub min_cg_height: f32,
+    pub min_suspension: [f32; 4],
+    pub bottoming: Vec<Bottoming>,
+}
+
+impl RideHeightReport {
+    /// the ride heights and bottomings of a lap. `None` for an empty lap.
+    /// Heights and G that aren't numbers are passed over.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a bottoming.
+    pub fn of(lap: &Trace, rules: &BottomingRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let mut min_cg_height = f32::INFINITY;
+        let mut min_suspension = [f32::INFINITY; 4];
+        for sample in samples {
+            min_cg_height = min_cg_height.min(sample.car.cg_height);
+            for wheel in Wheel::ALL {
+                let height = sample.car.suspension_height[wheel as usize];
+                min_suspension[wheel as usize] = min_suspension[wheel as usize].min(height);
+            }
+        }
+
+        let mut bottoming = Vec::new();
+        let mut hitting = false;
+        for pair in samples.windows(2) {
+            let (before, sample) = (&pair[0].car, &pair[1].car);
+            let g_jump = (sample.accg_vertical - before.accg_vertical).abs();
+            let hit = g_jump >= rules.g_jump && sample.cg_height <= min_cg_height + rules.cg_margin;
+
+            // One hit often spans a couple of samples; count it once.
+            if hit && !hitting {
+                bottoming.push(Bottoming {
+                    time: pair[1].time,
+                    position: sample.car_pos_normalized,
+                    coordinates: sample.car_coordinates,
+                    cg_height: sample.cg_height,
+                    g_jump,
+                });
+            }
+            hitting = hit;
+        }
+
+        Some(Self {
+            lap: first.car.lap_count,
+            min_cg_height,
+            min_suspension,
+            bottoming,
+        })
+    }
+}
+
+#[cfg(test)]
+mod ride_height_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        ride_height::{BottomingRules, RideHeightReport},
+        trace::Trace,
+    };
+
+    fn car(position: f32, cg_height: f32, accg_vertical: f32) -> CarInfo {
+        CarInfo {
+            lap_count: 1,
+            car_pos_normalized: position,
+            car_coordinates: [position * 100.0, 0.0, 0.0],
+            cg_height,
+            accg_vertical,
+            suspension_height: [cg_height - 0.2, cg_height - 0.2, cg_height - 0.18, 0.1],
+            ..CarInfo::default()
+        }
+    }
+
+    fn lap(samples: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+        lap
+    }
+
+    fn hits(report: &RideHeightReport) -> Vec<f32> {
+        report.bottoming.iter().map(|hit| hit.position).collect()
+    }
+
+    #[test]
+    fn an_empty_lap_has_no_report() {
+        assert_eq!(
+            RideHeightReport::of(&Trace::new(), &BottomingRules::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn a_single_sample_is_the_lowest_and_never_a_hit() {
+        let report = RideHeightReport::of(&lap([car(0.5, 0.25, 3.0)]), &BottomingRules::default())
+            .expect("one sample");
+
+        assert_eq!(report.min_cg_height, 0.25);
+        assert!((report.min_suspension[0] - 0.05).abs() < 1e-6);
+        assert!(
+            report.bottoming.is_empty(),
+            "a jump needs a sample before it"
+        );
+    }
+
+    #[test]
+    fn nan_readings_are_passed_over() {
+        let samples = [
+            car(0.1, 0.26, 0.0),
+            car(0.2, f32::NAN, 3.0),
+            car(0.3, 0.26, f32::NAN),
+            car(0.4, 0.27, 0.0),
+        ];
+        let report =
+            RideHeightReport::of(&lap(samples), &BottomingRules::default()).expect("four samples");
+
+        assert_eq!(report.min_cg_height, 0.26);
+        assert!(report.bottoming.is_empty());
+    }
+
+    #[test]
+    fn hits_apart_count_twice_and_a_long_one_once() {
+        let samples = [
+            car(0.1, 0.26, 0.0),
+            car(0.2, 0.26, 2.0),
+            car(0.3, 0.26, 0.0),
+            car(0.4, 0.26, 2.0),
+            car(0.5, 0.26, 0.0),
+            car(0.6, 0.29, 0.0),
+            car(0.7, 0.26, 0.0),
+            car(0.8, 0.26, 2.0),
+        ];
+        let report =
+            RideHeightReport::of(&lap(samples), &BottomingRules::default()).expect("eight samples");
+
+        assert_eq!(
+            hits(&report),
+            [0.2, 0.8],
+            "0.2 to 0.5 is one hit rattling on"
+        );
+    }
+
+    #[test]
+    fn g_spikes_at_the_lowest_ride_height_are_bottoming() {
+        let samples = [
+            car(0.1, 0.300, 0.0),
+            // A kerb: a big spike, but riding high.
+            car(0.2, 0.310, 2.5),
+            car(0.3, 0.300, 0.0),
+            // Compression at the bottom of a dip: the floor hits, over two samples.
+            car(0.5, 0.262, 0.0),
+            car(0.6, 0.260, 2.2),
+            car(0.61, 0.261, 0.1),
+            car(0.8, 0.290, 0.0),
+        ];
+
+        let report =
+            RideHeightReport::of(&lap(samples), &BottomingRules::default()).expect("not empty");
+        assert_eq!(report.min_cg_height, 0.260);
+        assert!((report.min_suspension[0] - 0.06).abs() < 1e-6);
+        assert_eq!(report.min_suspension[3], 0.1);
+
+        let [hit] = &report.bottoming[..] else {
+            panic!("expected one bottoming, got {:?}", report.bottoming);
+        };
+        assert_eq!((hit.time, hit.position), (Duration::from_millis(400), 0.6));
+        assert_eq!(hit.coordinates[0], 0.6 * 100.0);
+    }
+}
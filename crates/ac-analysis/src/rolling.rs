@@ -0,0 +1,192 @@
+//! Rolling statistics for dashboard readouts like "max G this lap" or "mean
+//! speed over the last 5 s". `Rolling` keeps the values of the channels it's
+//! asked to track over a window, either a stretch of time or the current lap,
+//! and answers min, max, mean and percentile queries on them at any moment.
+
+use std::{collections::VecDeque, time::Duration};
+
+use ac_parser::{Channel, Event};
+
+use crate::columns::ColumnStats;
+
+/// What a rolling window covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// the trailing stretch of time, e.g. the last 5 s.
+    Last(Duration),
+    /// everything since the current lap started.
+    Lap,
+}
+
+/// The values of one channel over one window.
+///
+/// * `channel`: the channel tracked.
+/// * `window`: what the window covers.
+/// * `values`: when each value arrived, and the value, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+struct Tracked {
+    channel: Channel,
+    window: Window,
+    values: VecDeque<(Duration, f32)>,
+}
+
+/// Channels tracked over rolling windows.
+///
+/// * `tracked`: every channel and window pair asked for.
+/// * `lap`: the lap count of the last CarInfo.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rolling {
+    tracked: Vec<Tracked>,
+    lap: Option<u32>,
+}
+
+impl Rolling {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a channel to track over a window.
+    ///
+    /// * `channel`: the channel, e.g. `Channel::AccgHorizontal`.
+    /// * `window`: what to keep it for, e.g. `Window::Lap`.
+    pub fn track(mut self, channel: Channel, window: Window) -> Self {
+        if self.find(channel, window).is_none() {
+            self.tracked.push(Tracked {
+                channel,
+                window,
+                values: VecDeque::new(),
+            });
+        }
+        self
+    }
+
+    /// adds a CarInfo's values to every window, dropping those that have
+    /// fallen out of them. Other events are ignored.
+    ///
+    /// * `at`: when the event arrived.
+    /// * `event`: the event.
+    pub fn observe(&mut self, at: Duration, event: &Event) {
+        let Event::CarInfo(car) = event else {
+            return;
+        };
+        let new_lap = self.lap.is_some_and(|lap| lap != car.lap_count);
+        self.lap = Some(car.lap_count);
+
+        for tracked in &mut self.tracked {
+            match tracked.window {
+                Window::Last(length) => {
+                    let start = at.saturating_sub(length);
+                    while tracked.values.front().is_some_and(|(t, _)| *t < start) {
+                        tracked.values.pop_front();
+                    }
+                }
+                Window::Lap if new_lap => tracked.values.clear(),
+                Window::Lap => {}
+            }
+            tracked.values.push_back((at, tracked.channel.value(car)));
+        }
+    }
+
+    /// min, max and mean of a channel over a window, if it's tracked and the
+    /// window isn't empty.
+    ///
+    /// * `channel`: the channel.
+    /// * `window`: the window it's tracked over.
+    pub fn stats(&self, channel: Channel, window: Window) -> Option<ColumnStats> {
+        let values = &self.find(channel, window)?.values;
+        if values.is_empty() {
+            return None;
+        }
+
+        let (min, max, sum) = values.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY, 0.0f64),
+            |(min, max, sum), &(_, v)| (min.min(v), max.max(v), sum + f64::from(v)),
+        );
+
+        Some(ColumnStats {
+            min,
+            max,
+            mean: (sum / values.len() as f64) as f32,
+        })
+    }
+
+    /// the value a given share of the window is at or below, by nearest rank,
+    /// if it's tracked and the window isn't empty.
+    ///
+    /// * `channel`: the channel.
+    /// * `window`: the window it's tracked over.
+    /// * `percent`: from 0 to 100, e.g. 95; clamped to that range.
+    pub fn percentile(&self, channel: Channel, window: Window, percent: f32) -> Option<f32> {
+        let tracked = self.find(channel, window)?;
+        let mut values: Vec<f32> = tracked.values.iter().map(|&(_, v)| v).collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(f32::total_cmp);
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f32).round();
+        Some(values[rank as usize])
+    }
+
+    /// empties every window, e.g. after a session restart.
+    pub fn clear(&mut self) {
+        self.lap = None;
+        self.tracked.iter_mut().for_each(|t| t.values.clear());
+    }
+
+    fn find(&self, channel: Channel, window: Window) -> Option<&Tracked> {
+        self.tracked
+            .iter()
+            .find(|t| t.channel == channel && t.window == window)
+    }
+}
+
+#[cfg(test)]
+mod rolling_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Channel, Event};
+
+    use crate::rolling::{Rolling, Window};
+
+    #[test]
+    fn windows_roll_by_time_and_reset_by_lap() {
+        let five_seconds = Window::Last(Duration::from_secs(5));
+        let mut rolling = Rolling::new()
+            .track(Channel::SpeedKmh, five_seconds)
+            .track(Channel::SpeedKmh, Window::Lap);
+        assert!(rolling.stats(Channel::SpeedKmh, Window::Lap).is_none());
+        assert!(rolling.stats(Channel::Gear, Window::Lap).is_none());
+
+        // One sample a second at 10, 20, ... km/h, with lap 1 starting at 8 s.
+        for second in 0..10u32 {
+            let car = CarInfo {
+                speed_kmh: (second + 1) as f32 * 10.0,
+                lap_count: u32::from(second >= 8),
+                ..CarInfo::default()
+            };
+            rolling.observe(Duration::from_secs(second.into()), &Event::CarInfo(car));
+        }
+
+        let last = rolling
+            .stats(Channel::SpeedKmh, five_seconds)
+            .expect("tracked");
+        assert_eq!((last.min, last.max, last.mean), (50.0, 100.0, 75.0));
+        assert_eq!(
+            rolling.percentile(Channel::SpeedKmh, five_seconds, 0.0),
+            Some(50.0)
+        );
+        assert_eq!(
+            rolling.percentile(Channel::SpeedKmh, five_seconds, 80.0),
+            Some(90.0)
+        );
+
+        let lap = rolling
+            .stats(Channel::SpeedKmh, Window::Lap)
+            .expect("tracked");
+        assert_eq!((lap.min, lap.max), (90.0, 100.0));
+
+        rolling.clear();
+        assert!(rolling.stats(Channel::SpeedKmh, five_seconds).is_none());
+    }
+}
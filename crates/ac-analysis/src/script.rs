@@ -0,0 +1,469 @@
+//! Custom channels, alerts and export transforms from Rhai scripts loaded at
+//! runtime, so an app built on the crate takes new readouts without being
+//! recompiled. A script registers closures when it's loaded:
+//!
+//! ```text
+//! // slip of the front left tyre, in percent
+//! channel("slip_fl", |car| abs(car.slip_ratio[0]) * 100.0);
+//! alert("over_rev", |car| car.engine_rpm > 8500.0 && car.gear < 6, |car| car.engine_rpm);
+//! alert("front_lockup", |car| car.brake > 0.8 && car.slip_fl > 20.0);
+//! on_export(|row| {
+//!     row.speed_mph = row.speed_kmh * 0.621371;
+//!     if row.is_in_pit { () } else { row }
+//! });
+//! ```
+//!
+//! `car` is a map of the CarInfo fields, named as the `serde` feature
+//! serializes them, plus the script's channels so far. A channel returns a
+//! number, or a bool as 1 or 0; an alert's condition returns a bool, and its
+//! optional second closure the value to report, 1 otherwise. The `on_export`
+//! closure gets each exported row, a car map with the sample's `time` and
+//! `distance`, and returns the row to write, or `()` to leave it out.
+//!
+//! A `Script` is a `Stage` emitting `Output::Alert` as `Alerts` does. Scripts
+//! run with an operation limit, so a runaway loop fails instead of hanging
+//! the pipeline.
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    mem,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use ac_parser::{CarInfo, Event};
+use rhai::{AST, Dynamic, Engine, FnPtr, Map};
+
+use crate::{
+    pipeline::{Alert, Output, Stage},
+    snapshot::invalid,
+    trace::{Sample, Trace},
+};
+
+/// The most operations one call into a script may take.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// An alert a script registered.
+///
+/// * `condition`: whether it holds for a car.
+/// * `value`: what it reports when it starts holding, if not 1.
+/// * `firing`: whether it held on the last CarInfo checked.
+#[derive(Debug, Clone)]
+struct AlertHook {
+    name: String,
+    condition: FnPtr,
+    value: Option<FnPtr>,
+    firing: bool,
+}
+
+/// What a script registers while it's loaded.
+#[derive(Debug, Clone, Default)]
+struct Hooks {
+    channels: Vec<(String, FnPtr)>,
+    alerts: Vec<AlertHook>,
+    export: Option<FnPtr>,
+}
+
+/// A loaded script's channels, alerts and export transform.
+///
+/// * `engine`, `ast`: the engine the script runs on, and the script.
+/// * `hooks`: the closures it registered.
+/// * `error`: why the script last failed as a pipeline stage, if it has.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    hooks: Hooks,
+    error: Option<String>,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script")
+            .field("hooks", &self.hooks)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Script {
+    /// compiles and runs a script, collecting what it registers. Fails with
+    /// `InvalidData` and the line and position on a script that doesn't
+    /// compile or run, or a channel named like a CarInfo field or another
+    /// channel.
+    ///
+    /// * `src`: the script.
+    pub fn parse(src: &str) -> io::Result<Self> {
+        let registered = Arc::new(Mutex::new(Hooks::default()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let hooks = Arc::clone(&registered);
+        engine.register_fn("channel", move |name: &str, channel: FnPtr| {
+            lock(&hooks).channels.push((name.to_string(), channel));
+        });
+        let hooks = Arc::clone(&registered);
+        engine.register_fn("alert", move |name: &str, condition: FnPtr| {
+            lock(&hooks).alerts.push(AlertHook {
+                name: name.to_string(),
+                condition,
+                value: None,
+                firing: false,
+            });
+        });
+        let hooks = Arc::clone(&registered);
+        engine.register_fn(
+            "alert",
+            move |name: &str, condition: FnPtr, value: FnPtr| {
+                lock(&hooks).alerts.push(AlertHook {
+                    name: name.to_string(),
+                    condition,
+                    value: Some(value),
+                    firing: false,
+                });
+            },
+        );
+        let hooks = Arc::clone(&registered);
+        engine.register_fn("on_export", move |transform: FnPtr| {
+            lock(&hooks).export = Some(transform);
+        });
+
+        let ast = engine
+            .compile(src)
+            .map_err(|why| invalid(&why.to_string()))?;
+        engine
+            .run_ast(&ast)
+            .map_err(|why| invalid(&why.to_string()))?;
+        let hooks = mem::take(&mut *lock(&registered));
+
+        let fields = car_map(&CarInfo::default())?;
+        for (i, (name, _)) in hooks.channels.iter().enumerate() {
+            let taken = fields.contains_key(name.as_str())
+                || hooks.channels[..i]
+                    .iter()
+                    .any(|(earlier, _)| earlier == name);
+            if taken {
+                return Err(invalid(&format!("channel {name} already exists")));
+            }
+        }
+
+        Ok(Self {
+            engine,
+            ast,
+            hooks,
+            error: None,
+        })
+    }
+
+    /// reads and loads a script file.
+    ///
+    /// * `path`: the file.
+    pub fn load<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// the names of the script's channels, in the order it registered them.
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.hooks.channels.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// every custom channel's value for a packet, in order.
+    ///
+    /// * `car`: the packet.
+    pub fn values(&self, car: &CarInfo) -> io::Result<Vec<(&str, f32)>> {
+        let (_, values) = self.derive(car_map(car)?)?;
+        Ok(self.channels().zip(values).collect())
+    }
+
+    /// checks a packet against every alert, returning those that just
+    /// started firing.
+    ///
+    /// * `car`: the packet.
+    pub fn check(&mut self, car: &CarInfo) -> io::Result<Vec<Alert>> {
+        let (car, _) = self.derive(car_map(car)?)?;
+
+        let mut alerts = Vec::new();
+        for alert in &mut self.hooks.alerts {
+            let condition = call(&self.engine, &self.ast, &alert.condition, &car)?;
+            let fires = condition
+                .as_bool()
+                .map_err(|kind| invalid(&format!("alert {} returned {kind}", alert.name)))?;
+
+            if fires && !alert.firing {
+                let value = match &alert.value {
+                    Some(value) => {
+                        number(&alert.name, call(&self.engine, &self.ast, value, &car)?)?
+                    }
+                    None => 1.0,
+                };
+                alerts.push(Alert {
+                    rule: alert.name.clone(),
+                    value,
+                });
+            }
+            alert.firing = fires;
+        }
+
+        Ok(alerts)
+    }
+
+    /// why the script last failed as a pipeline stage, if it has. Packets it
+    /// fails on are passed along without alerts.
+    pub fn last_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// writes a trace as CSV, one row per sample of its `time`, `distance`,
+    /// CarInfo fields and custom channels, through the script's `on_export`
+    /// transform if it has one. Columns are the first row's, in name order;
+    /// per-wheel and other arrays get a column per element, suffixed `_0`,
+    /// `_1` and so on.
+    ///
+    /// * `trace`: the samples to export.
+    /// * `writer`: where it goes.
+    pub fn write_csv<W>(&self, trace: &Trace, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut columns: Option<Vec<String>> = None;
+
+        for sample in trace.samples() {
+            let Some(row) = self.export_row(sample)? else {
+                continue;
+            };
+            let cells = flatten(&row);
+
+            let columns = match &columns {
+                Some(columns) => columns,
+                None => {
+                    let names: Vec<String> = cells.iter().map(|(name, _)| name.clone()).collect();
+                    writeln!(writer, "{}", names.join(","))?;
+                    columns.insert(names)
+                }
+            };
+
+            let line: Vec<String> = columns
+                .iter()
+                .map(|column| {
+                    cells
+                        .iter()
+                        .find(|(name, _)| name == column)
+                        .map(|(_, cell)| csv_cell(cell))
+                        .unwrap_or_default()
+                })
+                .collect();
+            writeln!(writer, "{}", line.join(","))?;
+        }
+
+        writer.flush()
+    }
+
+    /// a sample as an export row, or `None` if the transform leaves it out.
+    fn export_row(&self, sample: &Sample) -> io::Result<Option<Map>> {
+        let (mut row, _) = self.derive(car_map(&sample.car)?)?;
+        row.insert(
+            "time".into(),
+            Dynamic::from_float(sample.time.as_secs_f64()),
+        );
+        row.insert(
+            "distance".into(),
+            Dynamic::from_float(sample.distance.into()),
+        );
+
+        let Some(transform) = &self.hooks.export else {
+            return Ok(Some(row));
+        };
+        let row = call(&self.engine, &self.ast, transform, &row)?;
+        if row.is_unit() {
+            return Ok(None);
+        }
+
+        row.try_cast::<Map>()
+            .map(Some)
+            .ok_or_else(|| invalid("on_export returned something other than a row or ()"))
+    }
+
+    /// a car map with every custom channel added, and the channels' values.
+    fn derive(&self, mut car: Map) -> io::Result<(Map, Vec<f32>)> {
+        let mut values = Vec::with_capacity(self.hooks.channels.len());
+
+        for (name, channel) in &self.hooks.channels {
+            let value = number(name, call(&self.engine, &self.ast, channel, &car)?)?;
+            car.insert(name.into(), Dynamic::from_float(value.into()));
+            values.push(value);
+        }
+
+        Ok((car, values))
+    }
+}
+
+impl Stage for Script {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    fn process(&mut self, _at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::CarInfo(car) = event {
+            match self.check(car) {
+                Ok(alerts) => outputs.extend(alerts.into_iter().map(Output::Alert)),
+                Err(why) => self.error = Some(why.to_string()),
+            }
+        }
+        true
+    }
+}
+
+fn lock(hooks: &Mutex<Hooks>) -> MutexGuard<'_, Hooks> {
+    hooks
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// a CarInfo as the map scripts see.
+fn car_map(car: &CarInfo) -> io::Result<Map> {
+    rhai::serde::to_dynamic(car)
+        .ok()
+        .and_then(|car| car.try_cast::<Map>())
+        .ok_or_else(|| invalid("CarInfo doesn't serialize to a map"))
+}
+
+/// calls one of the script's closures with a map.
+fn call(engine: &Engine, ast: &AST, hook: &FnPtr, map: &Map) -> io::Result<Dynamic> {
+    hook.call(engine, ast, (Dynamic::from_map(map.clone()),))
+        .map_err(|why| invalid(&why.to_string()))
+}
+
+/// what a channel or alert value returned, as a number.
+///
+/// * `name`: the channel or alert, for the error.
+/// * `value`: what it returned.
+fn number(name: &str, value: Dynamic) -> io::Result<f32> {
+    if let Ok(truth) = value.as_bool() {
+        return Ok(if truth { 1.0 } else { 0.0 });
+    }
+    match (value.as_float(), value.as_int()) {
+        (Ok(value), _) => Ok(value as f32),
+        (_, Ok(value)) => Ok(value as f32),
+        _ => Err(invalid(&format!(
+            "{name} returned {}, not a number",
+            value.type_name()
+        ))),
+    }
+}
+
+/// a row's cells by column name, arrays spread over a column per element.
+fn flatten(row: &Map) -> Vec<(String, String)> {
+    let mut cells = Vec::with_capacity(row.len());
+
+    for (name, value) in row {
+        match value.clone().try_cast::<rhai::Array>() {
+            Some(items) => cells.extend(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| (format!("{name}_{i}"), item.to_string())),
+            ),
+            None => cells.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    cells
+}
+
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{script::Script, trace::Trace};
+
+    #[test]
+    fn scripts_define_channels_alerts_and_exports() {
+        let mut script = Script::parse(
+            r#"
+            // front left slip, in percent
+            channel("slip_fl", |car| abs(car.slip_ratio[0]) * 100.0);
+            channel("braking", |car| car.brake > 0.5);
+
+            alert("over_rev", |car| car.engine_rpm > 8500.0, |car| car.engine_rpm);
+            alert("lockup", |car| car.braking == 1.0 && car.slip_fl >= 20.0);
+
+            on_export(|row| {
+                if row.gear == 0 { return (); }
+                #{ time: row.time, gear: row.gear, slip_fl: row.slip_fl }
+            });
+            "#,
+        )
+        .expect("should load");
+        assert_eq!(
+            script.channels().collect::<Vec<_>>(),
+            ["slip_fl", "braking"]
+        );
+
+        let car = |rpm, brake, slip: f32| CarInfo {
+            engine_rpm: rpm,
+            gear: 4,
+            brake,
+            slip_ratio: [-slip, 0.0, 0.0, 0.0],
+            ..CarInfo::default()
+        };
+        assert_eq!(
+            script.values(&car(6000.0, 0.9, 0.25)).expect("runs"),
+            [("slip_fl", 25.0), ("braking", 1.0)]
+        );
+
+        let mut fired = |car| {
+            script
+                .check(&car)
+                .expect("runs")
+                .into_iter()
+                .map(|alert| (alert.rule, alert.value))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fired(car(8600.0, 0.9, 0.25)),
+            [
+                ("over_rev".to_string(), 8600.0),
+                ("lockup".to_string(), 1.0)
+            ]
+        );
+        assert!(fired(car(8700.0, 0.9, 0.25)).is_empty());
+        assert!(fired(car(6000.0, 0.0, 0.0)).is_empty());
+
+        let mut trace = Trace::new();
+        for (secs, gear) in [(0, 3), (1, 0), (2, 4)] {
+            let car = CarInfo {
+                gear,
+                slip_ratio: [0.5, 0.0, 0.0, 0.0],
+                ..CarInfo::default()
+            };
+            trace.push(Duration::from_secs(secs), car);
+        }
+        let mut csv = Vec::new();
+        script.write_csv(&trace, &mut csv).expect("exports");
+        assert_eq!(
+            String::from_utf8(csv).expect("UTF-8"),
+            "gear,slip_fl,time\n3,50.0,0.0\n4,50.0,2.0\n"
+        );
+
+        let clash = Script::parse(r#"channel("gear", |car| 1)"#).unwrap_err();
+        assert_eq!(clash.to_string(), "channel gear already exists");
+        assert!(Script::parse("channel(").is_err());
+        let runaway = Script::parse(r#"channel("spin", |car| { loop {} })"#).expect("loads");
+        assert!(runaway.values(&CarInfo::default()).is_err());
+    }
+}
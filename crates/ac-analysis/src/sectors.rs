@@ -0,0 +1,224 @@
+//! Where a lap's sectors start. AC's UDP feed carries no sector layout, and
+//! mods rarely document one, so sectors either come from the user, are equal
+//! thirds of the lap, or are worked out from the track map: splits placed on
+//! the straights between corners, nearest to where the equal splits would be,
+//! so no sector boundary falls in the middle of a corner.
+
+use crate::{
+    corners::{Corner, CornerRules},
+    trace::Trace,
+};
+
+/// The sector layout of a track, by track position.
+///
+/// * `starts`: where each sector starts, 0 to 1; the first is always 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sectors {
+    starts: Vec<f32>,
+}
+
+impl Default for Sectors {
+    /// equal thirds.
+    fn default() -> Self {
+        Self::equal(3)
+    }
+}
+
+impl Sectors {
+    /// sectors split at the given track positions.
+    ///
+    /// * `splits`: where each sector after the first starts, 0 to 1; any out
+    ///   of range are left out and the rest put in order.
+    pub fn at(splits: &[f32]) -> Self {
+        let mut starts = vec![0.0];
+        starts.extend(splits.iter().copied().filter(|&at| at > 0.0 && at < 1.0));
+        starts.sort_by(f32::total_cmp);
+        starts.dedup();
+
+        Self { starts }
+    }
+
+    /// sectors of equal length by track position.
+    ///
+    /// * `count`: how many sectors.
+    pub fn equal(count: usize) -> Self {
+        let count = count.max(1);
+        Self {
+            starts: (0..count).map(|i| i as f32 / count as f32).collect(),
+        }
+    }
+
+    /// sectors split on the straights of a lap's track map, each split the
+    /// middle of the gap between two corners nearest where equal sectors
+    /// would split, short of the next equal split. Where there's no such gap
+    /// the equal split is used.
+    ///
+    /// * `lap`: a lap, ideally a clean one.
+    /// * `rules`: what counts as a corner.
+    /// * `count`: how many sectors.
+    pub fn from_corners(lap: &Trace, rules: &CornerRules, count: usize) -> Self {
+        let corners = Corner::find(lap, rules);
+        let gaps: Vec<f32> = corners
+            .windows(2)
+            .map(|pair| (pair[0].exit + pair[1].entry) / 2.0)
+            .collect();
+
+        let mut targets = Self::equal(count).starts;
+        targets.push(1.0);
+
+        let mut splits = Vec::new();
+        let mut after = 0.0;
+        for pair in targets[1..].windows(2) {
+            let (target, next) = (pair[0], pair[1]);
+            // Splits stay in order: each is past the last, and short of the next target.
+            let split = gaps
+                .iter()
+                .copied()
+                .filter(|&gap| gap > after && gap < next)
+                .min_by(|a, b| (a - target).abs().total_cmp(&(b - target).abs()))
+                .unwrap_or(target);
+            splits.push(split);
+            after = split;
+        }
+
+        Self::at(&splits)
+    }
+
+    /// segments that each hold one corner of a lap or one stretch between
+    /// two, split at every corner's entry and exit.
+    ///
+    /// * `lap`: a lap, ideally a clean one.
+    /// * `rules`: what counts as a corner.
+    pub fn around_corners(lap: &Trace, rules: &CornerRules) -> Self {
+        let splits: Vec<f32> = Corner::find(lap, rules)
+            .iter()
+            .flat_map(|corner| [corner.entry, corner.exit])
+            .collect();
+
+        Self::at(&splits)
+    }
+
+    /// how many sectors the lap has.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// always false: a lap has at least one sector.
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// where each sector starts, 0 to 1.
+    pub fn starts(&self) -> &[f32] {
+        &self.starts
+    }
+
+    /// the sector a track position is in, counting from zero.
+    ///
+    /// * `position`: 0 to 1.
+    pub fn sector(&self, position: f32) -> usize {
+        let position = position.clamp(0.0, 1.0);
+        self.starts
+            .partition_point(|&start| start <= position)
+            .saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod sectors_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{corners::CornerRules, sectors::Sectors, trace::Trace};
+
+    #[test]
+    fn a_lap_always_has_one_sector() {
+        for sectors in [
+            Sectors::equal(0),
+            Sectors::at(&[]),
+            Sectors::at(&[0.0, 1.0, f32::NAN]),
+        ] {
+            assert_eq!(sectors.starts(), [0.0]);
+            assert!(!sectors.is_empty());
+            assert_eq!(sectors.sector(0.99), 0);
+        }
+    }
+
+    #[test]
+    fn splits_are_sorted_and_positions_clamped() {
+        assert_eq!(Sectors::equal(4).starts(), [0.0, 0.25, 0.5, 0.75]);
+        assert_eq!(Sectors::at(&[0.6, 1.5, 0.2, 0.6]).starts(), [0.0, 0.2, 0.6]);
+
+        let thirds = Sectors::default();
+        assert_eq!(thirds.sector(0.5), 1);
+        assert_eq!(thirds.sector(1.0 / 3.0), 1, "a split starts its sector");
+        assert_eq!((thirds.sector(-0.1), thirds.sector(1.2)), (0, 2));
+    }
+
+    #[test]
+    fn a_lap_without_corners_splits_equally() {
+        let rules = CornerRules::default();
+        assert_eq!(
+            Sectors::from_corners(&Trace::new(), &rules, 3),
+            Sectors::equal(3)
+        );
+        assert_eq!(Sectors::around_corners(&Trace::new(), &rules).len(), 1);
+    }
+
+    #[test]
+    fn a_corner_across_the_line_splits_where_it_enters_and_exits() {
+        let mut lap = Trace::new();
+        for (i, (position, g)) in [
+            (0.9, 0.0),
+            (0.97, 1.0),
+            (0.99, 1.0),
+            (0.01, 1.0),
+            (0.03, 1.0),
+            (0.1, 0.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let car = CarInfo {
+                car_pos_normalized: position,
+                accg_horizontal: g,
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_millis(200 * i as u64), car);
+        }
+
+        let sectors = Sectors::around_corners(&lap, &CornerRules::default());
+        assert_eq!(sectors.starts(), [0.0, 0.03, 0.97]);
+    }
+
+    #[test]
+    fn sectors_split_between_corners() {
+        // Corners at 0.1-0.2, 0.3-0.4 and 0.7-0.8 of the lap.
+        let mut lap = Trace::new();
+        for i in 0..=100 {
+            let position = i as f32 / 100.0;
+            let cornering = [(10, 20), (30, 40), (70, 80)]
+                .iter()
+                .any(|&(from, to)| (from..=to).contains(&i));
+            let car = CarInfo {
+                car_pos_normalized: position,
+                accg_horizontal: if cornering { 1.0 } else { 0.0 },
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+
+        let sectors = Sectors::from_corners(&lap, &CornerRules::default(), 3);
+        let near = |a: &[f32], b: &[f32]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6)
+        };
+        assert!(near(sectors.starts(), &[0.0, 0.25, 0.55]), "{sectors:?}");
+
+        // With more sectors than gaps between corners, the rest split equally.
+        let sectors = Sectors::from_corners(&lap, &CornerRules::default(), 6);
+        let sixths = |n: f32| n / 6.0;
+        let expected = [0.0, 0.25, sixths(2.0), 0.55, sixths(4.0), sixths(5.0)];
+        assert!(near(sectors.starts(), &expected), "{sectors:?}");
+    }
+}
@@ -0,0 +1,281 @@
+//! Where on track each tyre is worked hardest. `tyre_slip` and the
+//! normalized `nd_slip` of every wheel are averaged into buckets of track
+//! position, giving a grid of position by wheel that draws as a heatmap
+//! along the track. An `nd_slip` above 1 is past the tyre's peak grip, so
+//! how often each bucket goes over it shows where a tyre is overworked.
+
+use std::io::{self, Write};
+
+use ac_parser::{CarInfo, Wheel};
+
+use crate::trace::Trace;
+
+/// One wheel's slip in one bucket of track position.
+///
+/// * `samples`: how many samples fell in it.
+/// * `tyre_slip`, `nd_slip`: the mean of each.
+/// * `nd_peak`: the highest `nd_slip`.
+/// * `over_limit`: how many samples had an `nd_slip` above 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SlipCell {
+    pub samples: u32,
+    pub tyre_slip: f32,
+    pub nd_slip: f32,
+    pub nd_peak: f32,
+    pub over_limit: u32,
+}
+
+impl SlipCell {
+    fn add(&mut self, tyre_slip: f32, nd_slip: f32) {
+        if tyre_slip.is_nan() || nd_slip.is_nan() {
+            return;
+        }
+
+        self.samples += 1;
+        let n = self.samples as f32;
+        self.tyre_slip += (tyre_slip - self.tyre_slip) / n;
+        self.nd_slip += (nd_slip - self.nd_slip) / n;
+        self.nd_peak = self.nd_peak.max(nd_slip);
+        self.over_limit += u32::from(nd_slip > 1.0);
+    }
+
+    /// the share of samples past the tyre's peak grip, 0 to 1.
+    pub fn over_limit_share(&self) -> f32 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.over_limit as f32 / self.samples as f32
+        }
+    }
+}
+
+/// Tyre slip by track position and wheel, over as many laps as are added.
+///
+/// * `cells`: each bucket's cells, indexed by `Wheel`, from the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlipMap {
+    cells: Vec<[SlipCell; 4]>,
+}
+
+impl SlipMap {
+    /// * `buckets`: how many equal buckets the lap is split into by track position.
+    pub fn new(buckets: usize) -> Self {
+        Self {
+            cells: vec![[SlipCell::default(); 4]; buckets.max(1)],
+        }
+    }
+
+    /// a map of a whole trace, e.g. one lap or a stint.
+    ///
+    /// * `trace`: the trace.
+    /// * `buckets`: how many equal buckets the lap is split into.
+    pub fn of(trace: &Trace, buckets: usize) -> Self {
+        let mut map = Self::new(buckets);
+        for sample in trace.samples() {
+            map.add(&sample.car);
+        }
+
+        map
+    }
+
+    /// adds a CarInfo's slip to the bucket it's in. A CarInfo without a
+    /// track position is left out, as is a wheel whose slip isn't a number.
+    ///
+    /// * `car`: the CarInfo.
+    pub fn add(&mut self, car: &CarInfo) {
+        if car.car_pos_normalized.is_nan() {
+            return;
+        }
+
+        let count = self.cells.len();
+        let position = car.car_pos_normalized.clamp(0.0, 1.0);
+        let bucket = ((position * count as f32) as usize).min(count - 1);
+
+        for wheel in Wheel::ALL {
+            let i = wheel as usize;
+            self.cells[bucket][i].add(car.tyre_slip[i], car.nd_slip[i]);
+        }
+    }
+
+    /// how many buckets the lap is split into.
+    pub fn buckets(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// one wheel's slip in a bucket.
+    ///
+    /// * `bucket`: the bucket, from the line.
+    /// * `wheel`: the wheel.
+    pub fn cell(&self, bucket: usize, wheel: Wheel) -> Option<&SlipCell> {
+        self.cells.get(bucket).map(|cells| &cells[wheel as usize])
+    }
+
+    /// one wheel's mean `nd_slip` along the lap, a row of the heatmap.
+    ///
+    /// * `wheel`: the wheel.
+    pub fn row(&self, wheel: Wheel) -> Vec<f32> {
+        self.cells
+            .iter()
+            .map(|cells| cells[wheel as usize].nd_slip)
+            .collect()
+    }
+
+    /// the bucket where a wheel spends the biggest share of samples past peak grip, if it ever does.
+    ///
+    /// * `wheel`: the wheel.
+    pub fn most_overworked(&self, wheel: Wheel) -> Option<usize> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(bucket, cells)| (bucket, cells[wheel as usize].over_limit_share()))
+            .filter(|&(_, share)| share > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(bucket, _)| bucket)
+    }
+
+    /// writes the map as CSV, one row per bucket and wheel.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let count = self.cells.len() as f32;
+        writeln!(
+            writer,
+            "from,to,wheel,samples,tyre_slip,nd_slip,nd_peak,over_limit"
+        )?;
+        for (bucket, cells) in self.cells.iter().enumerate() {
+            let (from, to) = (bucket as f32 / count, (bucket + 1) as f32 / count);
+            for wheel in Wheel::ALL {
+                let cell = &cells[wheel as usize];
+                writeln!(
+                    writer,
+                    "{from},{to},{wheel:?},{},{},{},{},{}",
+                    cell.samples, cell.tyre_slip, cell.nd_slip, cell.nd_peak, cell.over_limit
+                )?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod slip_map_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Wheel};
+
+    use crate::{slip_map::SlipMap, trace::Trace};
+
+    /// a car at `position` with the front left at `front_left` and the rest steady.
+    fn car((position, front_left): (f32, f32)) -> CarInfo {
+        CarInfo {
+            car_pos_normalized: position,
+            tyre_slip: [front_left * 2.0, 0.1, 0.1, 0.1],
+            nd_slip: [front_left, 0.2, 0.2, 0.2],
+            ..CarInfo::default()
+        }
+    }
+
+    fn map(buckets: usize, samples: impl IntoIterator<Item = CarInfo>) -> SlipMap {
+        let mut map = SlipMap::new(buckets);
+        for car in samples {
+            map.add(&car);
+        }
+        map
+    }
+
+    #[test]
+    fn an_empty_map_has_nothing_overworked() {
+        let map = SlipMap::of(&Trace::new(), 0);
+        assert_eq!(map.buckets(), 1);
+        assert_eq!(map.row(Wheel::FrontLeft), [0.0]);
+        assert_eq!(map.most_overworked(Wheel::FrontLeft), None);
+        assert_eq!(
+            map.cell(0, Wheel::FrontLeft).map(|c| c.over_limit_share()),
+            Some(0.0)
+        );
+        assert_eq!(map.cell(1, Wheel::FrontLeft), None);
+    }
+
+    #[test]
+    fn a_single_sample_fills_one_bucket() {
+        let mut trace = Trace::new();
+        trace.push(Duration::ZERO, car((0.3, 1.2)));
+        let map = SlipMap::of(&trace, 2);
+
+        let cell = map.cell(0, Wheel::FrontLeft).expect("in range");
+        assert_eq!((cell.samples, cell.over_limit, cell.nd_peak), (1, 1, 1.2));
+        assert_eq!(map.most_overworked(Wheel::FrontLeft), Some(0));
+        assert_eq!(map.cell(1, Wheel::FrontLeft).map(|c| c.samples), Some(0));
+    }
+
+    #[test]
+    fn slip_is_bucketed_by_position_and_wheel() {
+        // The front left is pushed past its limit in the third quarter.
+        let map = map(
+            4,
+            [(0.1, 0.5), (0.2, 0.7), (0.6, 1.4), (0.7, 0.8), (0.9, 0.4)].map(car),
+        );
+
+        let hot = map.cell(2, Wheel::FrontLeft).expect("in range");
+        assert_eq!((hot.samples, hot.over_limit), (2, 1));
+        assert!((hot.nd_slip - 1.1).abs() < 1e-6 && hot.nd_peak == 1.4);
+        assert!((hot.tyre_slip - 2.2).abs() < 1e-6);
+        assert_eq!(hot.over_limit_share(), 0.5);
+
+        assert_eq!(map.most_overworked(Wheel::FrontLeft), Some(2));
+        assert_eq!(map.most_overworked(Wheel::RearRight), None);
+        assert_eq!(map.row(Wheel::RearLeft), [0.2, 0.0, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn the_csv_has_a_row_per_bucket_and_wheel() {
+        let map = map(4, [(0.6, 1.4), (0.7, 0.8)].map(car));
+
+        let mut csv = Vec::new();
+        map.write_csv(&mut csv).expect("written");
+        let csv = String::from_utf8(csv).expect("utf-8");
+        assert_eq!(csv.lines().count(), 1 + 4 * 4);
+        assert!(csv.contains("\n0.5,0.75,FrontLeft,2,"), "{csv}");
+        assert!(csv.contains("\n0,0.25,RearRight,0,0,0,0,0\n"), "{csv}");
+    }
+
+    #[test]
+    fn nan_positions_and_slips_are_left_out() {
+        let map = map(
+            2,
+            [(f32::NAN, 1.5), (0.2, f32::NAN), (0.3, 0.5), (0.4, 0.7)].map(car),
+        );
+
+        let front_left = map.cell(0, Wheel::FrontLeft).expect("in range");
+        assert_eq!((front_left.samples, front_left.over_limit), (2, 0));
+        assert!((front_left.nd_slip - 0.6).abs() < 1e-6);
+        assert_eq!(map.cell(0, Wheel::RearLeft).map(|c| c.samples), Some(3));
+        assert_eq!(map.most_overworked(Wheel::FrontLeft), None);
+    }
+
+    #[test]
+    fn a_lap_across_the_line_fills_both_ends() {
+        let map = map(
+            4,
+            [
+                (0.95, 1.2),
+                (1.0, 1.2),
+                (0.0, 0.5),
+                (0.05, 0.5),
+                (-0.01, 0.5),
+            ]
+            .map(car),
+        );
+
+        let samples: Vec<u32> = (0..4)
+            .filter_map(|bucket| map.cell(bucket, Wheel::FrontLeft))
+            .map(|cell| cell.samples)
+            .collect();
+        assert_eq!(samples, [3, 0, 0, 2]);
+        assert_eq!(map.most_overworked(Wheel::FrontLeft), Some(3));
+    }
+}
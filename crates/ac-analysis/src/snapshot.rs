@@ -0,0 +1,262 @@
+//! Saving the timing state of a session and picking it up again. A mobile app
+//! the OS kills mid-session writes a `SessionSnapshot` on the way out and
+//! restores it on relaunch, keeping its lap history, bests and stints.
+//!
+//! The snapshot is a short line-based text file, versioned by its first line:
+//!
+//! ```text
+//! ac_lib session 1
+//! lap_count 3
+//! lap 0 97250 - out-lap
+//! lap 1 95100 best valid
+//! lap 2 96020 - off-track
+//! ```
+
+use std::{
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+use crate::{
+    pipeline::{LapTime, LapTimer},
+    validity::{InvalidReason, LapValidity},
+};
+
+/// The first line of every snapshot.
+const SNAPSHOT_HEADER: &str = "ac_lib session 1";
+
+/// Laps driven between two visits to the pits.
+///
+/// * `first_lap`: the stint's first lap.
+/// * `laps`: how many laps it has.
+/// * `best`: its fastest valid lap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stint {
+    pub first_lap: u32,
+    pub laps: u32,
+    pub best: Option<Duration>,
+}
+
+/// Everything needed to carry a session's timing on where it left off.
+///
+/// * `laps`: every lap completed, oldest first.
+/// * `lap_count`: the player's lap counter when the snapshot was taken.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSnapshot {
+    pub laps: Vec<LapTime>,
+    pub lap_count: Option<u32>,
+}
+
+impl SessionSnapshot {
+    /// the fastest valid lap.
+    pub fn best_lap(&self) -> Option<Duration> {
+        self.laps
+            .iter()
+            .filter(|lap| lap.validity.is_valid())
+            .map(|lap| lap.time)
+            .min()
+    }
+
+    /// the laps split into stints; each out-lap starts a new one.
+    pub fn stints(&self) -> Vec<Stint> {
+        let mut stints: Vec<Stint> = Vec::new();
+
+        for lap in &self.laps {
+            let stint = match stints.last_mut() {
+                Some(stint) if lap.validity != LapValidity::OutLap => stint,
+                _ => {
+                    stints.push(Stint {
+                        first_lap: lap.lap,
+                        laps: 0,
+                        best: None,
+                    });
+                    stints.last_mut().expect("just pushed")
+                }
+            };
+
+            stint.laps += 1;
+            if lap.validity.is_valid() {
+                stint.best = Some(stint.best.map_or(lap.time, |best| best.min(lap.time)));
+            }
+        }
+
+        stints
+    }
+
+    /// a lap timer that carries on from the snapshot.
+    pub fn lap_timer(&self) -> LapTimer {
+        LapTimer::resume(self.lap_count, self.best_lap())
+    }
+
+    /// writes the snapshot as text.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_to<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "{SNAPSHOT_HEADER}")?;
+        if let Some(lap_count) = self.lap_count {
+            writeln!(writer, "lap_count {lap_count}")?;
+        }
+
+        for lap in &self.laps {
+            let best = if lap.best { "best" } else { "-" };
+            writeln!(
+                writer,
+                "lap {} {} {best} {}",
+                lap.lap,
+                lap.time.as_millis(),
+                validity_name(lap.validity)
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    /// reads a snapshot written by `write_to`.
+    ///
+    /// * `reader`: where to read it from.
+    pub fn read_from<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(SNAPSHOT_HEADER) {
+            return Err(invalid("not an ac_lib session snapshot"));
+        }
+
+        let mut snapshot = Self::default();
+        for line in lines {
+            let line = line?;
+            let fields: Vec<_> = line.split_whitespace().collect();
+
+            match fields[..] {
+                [] => {}
+                ["lap_count", count] => snapshot.lap_count = Some(number(count)?),
+                ["lap", lap, time, best, validity] => snapshot.laps.push(LapTime {
+                    lap: number(lap)?,
+                    time: Duration::from_millis(number(time)?),
+                    best: best == "best",
+                    validity: parse_validity(validity)?,
+                }),
+                _ => return Err(invalid("unrecognised snapshot line")),
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+fn validity_name(validity: LapValidity) -> &'static str {
+    match validity {
+        LapValidity::Valid => "valid",
+        LapValidity::Invalid(InvalidReason::OffTrack) => "off-track",
+        LapValidity::Invalid(InvalidReason::TimeAnomaly) => "time-anomaly",
+        LapValidity::InLap => "in-lap",
+        LapValidity::OutLap => "out-lap",
+    }
+}
+
+fn parse_validity(name: &str) -> io::Result<LapValidity> {
+    Ok(match name {
+        "valid" => LapValidity::Valid,
+        "off-track" => LapValidity::Invalid(InvalidReason::OffTrack),
+        "time-anomaly" => LapValidity::Invalid(InvalidReason::TimeAnomaly),
+        "in-lap" => LapValidity::InLap,
+        "out-lap" => LapValidity::OutLap,
+        _ => return Err(invalid("unknown lap validity")),
+    })
+}
+
+pub(crate) fn number<T>(field: &str) -> io::Result<T>
+where
+    T: std::str::FromStr,
+{
+    field.parse().map_err(|_| invalid("malformed number"))
+}
+
+pub(crate) fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event};
+
+    use crate::{
+        pipeline::{LapTime, Output, Stage},
+        snapshot::{SessionSnapshot, Stint},
+        validity::{InvalidReason, LapValidity},
+    };
+
+    #[test]
+    fn snapshots_round_trip_and_resume_timing() {
+        let lap = |lap, ms, best, validity| LapTime {
+            lap,
+            time: Duration::from_millis(ms),
+            best,
+            validity,
+        };
+        let snapshot = SessionSnapshot {
+            laps: vec![
+                lap(0, 97_250, false, LapValidity::OutLap),
+                lap(1, 95_100, true, LapValidity::Valid),
+                lap(
+                    2,
+                    96_020,
+                    false,
+                    LapValidity::Invalid(InvalidReason::OffTrack),
+                ),
+                lap(3, 99_000, false, LapValidity::OutLap),
+                lap(4, 96_500, false, LapValidity::Valid),
+            ],
+            lap_count: Some(5),
+        };
+
+        let mut text = Vec::new();
+        snapshot.write_to(&mut text).expect("failed to write");
+        let restored = SessionSnapshot::read_from(&text[..]).expect("failed to read");
+        assert_eq!(restored, snapshot);
+        assert!(SessionSnapshot::read_from(&b"lap 1 2 - valid\n"[..]).is_err());
+
+        let best = |ms| Some(Duration::from_millis(ms));
+        assert_eq!(
+            restored.stints(),
+            [
+                Stint {
+                    first_lap: 0,
+                    laps: 3,
+                    best: best(95_100),
+                },
+                Stint {
+                    first_lap: 3,
+                    laps: 2,
+                    best: best(96_500),
+                },
+            ]
+        );
+
+        // A 95.5s lap 5 doesn't beat the restored best.
+        let mut timer = restored.lap_timer();
+        let mut outputs = Vec::new();
+        for (lap_count, last_lap) in [(5, 96_500), (6, 95_500)] {
+            let car = CarInfo {
+                lap_count,
+                last_lap,
+                ..CarInfo::default()
+            };
+            timer.process(Duration::ZERO, &Event::CarInfo(car), &mut outputs);
+        }
+        assert!(matches!(
+            outputs[..],
+            [Output::Lap(LapTime {
+                lap: 5,
+                best: false,
+                ..
+            })]
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+//! Frequency analysis of recorded channels, for setup tools. Suspension that
+//! hops, a damper that lets the body ring, or a kerb struck every lap each
+//! leave a peak in the spectrum of suspension height or wheel speed that's
+//! hard to see in the time trace.
+//!
+//! Packets don't arrive evenly, so a channel is first resampled at a fixed
+//! rate by interpolating the trace, then split into half-overlapping
+//! Hann-windowed segments whose power spectra are averaged (Welch's method).
+//! The transforms are done by `rustfft`.
+
+use std::f32::consts::PI;
+
+use ac_parser::Channel;
+use rustfft::{FftPlanner, num_complex::Complex};
+
+use crate::trace::Trace;
+
+/// How a spectrum is taken.
+///
+/// * `sample_rate`: how often the channel is resampled, in Hz. Frequencies up to half of it show.
+/// * `window`: samples per segment, a power of two. Longer segments resolve
+///   frequencies more finely but average fewer segments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumConfig {
+    pub sample_rate: f32,
+    pub window: usize,
+}
+
+impl Default for SpectrumConfig {
+    /// 100 Hz in 256 sample segments: 0.39 Hz bins up to 50 Hz.
+    fn default() -> Self {
+        Self {
+            sample_rate: 100.0,
+            window: 256,
+        }
+    }
+}
+
+/// A frequency that stands out in a spectrum.
+///
+/// * `frequency`: in Hz.
+/// * `power`: its power spectral density, in the channel's unit squared per Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    pub frequency: f32,
+    pub power: f32,
+}
+
+/// The power spectral density of a channel.
+///
+/// * `resolution`: Hz between neighbouring bins.
+/// * `psd`: power per Hz of each bin, from 0 Hz up to half the sample rate.
+/// * `segments`: how many segments were averaged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    pub resolution: f32,
+    pub psd: Vec<f32>,
+    pub segments: usize,
+}
+
+impl Spectrum {
+    /// the spectrum of one channel over a trace, e.g. a lap. `None` if the
+    /// trace is shorter than one segment or `config.window` isn't a power of two.
+    ///
+    /// * `trace`: the samples to analyse.
+    /// * `channel`: the channel, e.g. `Channel::SuspensionHeight(Wheel::FrontLeft)`.
+    /// * `config`: sample rate and segment length.
+    pub fn of(trace: &Trace, channel: Channel, config: SpectrumConfig) -> Option<Self> {
+        let seconds = trace.duration().as_secs_f32();
+        let count = (seconds * config.sample_rate) as usize + 1;
+        let values: Vec<f32> = (0..count)
+            .filter_map(|i| {
+                let at = i as f32 / config.sample_rate;
+                trace.interpolate(channel, |s| s.time.as_secs_f32(), at)
+            })
+            .collect();
+
+        Self::from_samples(&values, config)
+    }
+
+    /// the spectrum of evenly spaced values. `None` if there are fewer than
+    /// one segment's worth or `config.window` isn't a power of two.
+    ///
+    /// * `values`: the channel, sampled at `config.sample_rate`.
+    /// * `config`: sample rate and segment length.
+    pub fn from_samples(values: &[f32], config: SpectrumConfig) -> Option<Self> {
+        let window = config.window;
+        if window < 2 || !window.is_power_of_two() || values.len() < window {
+            return None;
+        }
+
+        let hann: Vec<f32> = (0..window)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window as f32).cos())
+            .collect();
+        let scale = config.sample_rate * hann.iter().map(|w| w * w).sum::<f32>();
+
+        let mut psd = vec![0.0; window / 2 + 1];
+        let mut segments = 0;
+        let fft = FftPlanner::new().plan_fft_forward(window);
+        let mut bins = vec![Complex::default(); window];
+        for start in (0..=values.len() - window).step_by(window / 2) {
+            let segment = &values[start..start + window];
+            let mean = segment.iter().sum::<f32>() / window as f32;
+            for ((bin, value), w) in bins.iter_mut().zip(segment).zip(&hann) {
+                *bin = Complex::new((value - mean) * w, 0.0);
+            }
+            fft.process(&mut bins);
+
+            for (i, power) in psd.iter_mut().enumerate() {
+                // Every bin but DC and Nyquist also stands for its negative frequency.
+                let one_sided = if i == 0 || i == window / 2 { 1.0 } else { 2.0 };
+                *power += one_sided * bins[i].norm_sqr() / scale;
+            }
+            segments += 1;
+        }
+
+        psd.iter_mut().for_each(|power| *power /= segments as f32);
+        Some(Self {
+            resolution: config.sample_rate / window as f32,
+            psd,
+            segments,
+        })
+    }
+
+    /// the frequency of each bin, in Hz.
+    pub fn frequencies(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.psd.len()).map(|i| i as f32 * self.resolution)
+    }
+
+    /// the strongest local maxima, strongest first, leaving out 0 Hz.
+    ///
+    /// * `count`: how many to return at most.
+    pub fn dominant(&self, count: usize) -> Vec<Peak> {
+        let mut peaks: Vec<Peak> = (1..self.psd.len())
+            .filter(|&i| {
+                let power = self.psd[i];
+                power > self.psd[i - 1] && self.psd.get(i + 1).is_none_or(|&next| power >= next)
+            })
+            .map(|i| Peak {
+                frequency: i as f32 * self.resolution,
+                power: self.psd[i],
+            })
+            .collect();
+
+        peaks.sort_by(|a, b| b.power.total_cmp(&a.power));
+        peaks.truncate(count);
+        peaks
+    }
+
+    /// the power between two frequencies, e.g. wheel hop's 10-20 Hz band.
+    ///
+    /// * `from`, `to`: the band's edges, in Hz.
+    pub fn band_power(&self, from: f32, to: f32) -> f32 {
+        self.frequencies()
+            .zip(&self.psd)
+            .filter(|&(frequency, _)| (from..=to).contains(&frequency))
+            .map(|(_, power)| power * self.resolution)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod spectrum_tests {
+    use std::{f32::consts::PI, time::Duration};
+
+    use ac_parser::{CarInfo, Channel, Wheel};
+
+    use crate::{
+        spectrum::{Spectrum, SpectrumConfig},
+        trace::Trace,
+    };
+
+    #[test]
+    fn a_hopping_wheel_shows_as_a_peak() {
+        // 12 Hz hop on the front left, sampled unevenly at roughly 333 Hz.
+        let mut trace = Trace::new();
+        for i in 0..3000u64 {
+            let at = Duration::from_micros(i * 3000 + (i % 3) * 400);
+            let t = at.as_secs_f32();
+            let mut car = CarInfo::default();
+            car.suspension_height[Wheel::FrontLeft as usize] =
+                0.05 + 0.01 * (2.0 * PI * 12.0 * t).sin() + 0.002 * (2.0 * PI * 3.0 * t).sin();
+            trace.push(at, car);
+        }
+
+        let channel = Channel::SuspensionHeight(Wheel::FrontLeft);
+        let spectrum = Spectrum::of(&trace, channel, SpectrumConfig::default()).expect("too short");
+        assert_eq!(spectrum.psd.len(), 129);
+        assert_eq!(spectrum.segments, 6);
+
+        let peaks = spectrum.dominant(2);
+        assert!((peaks[0].frequency - 12.0).abs() <= spectrum.resolution);
+        assert!((peaks[1].frequency - 3.0).abs() <= spectrum.resolution);
+        // Power of a sine is half its amplitude squared.
+        let hop = spectrum.band_power(10.0, 14.0);
+        assert!((hop - 0.5e-4).abs() < 0.1e-4, "{hop}");
+
+        let odd = SpectrumConfig {
+            window: 100,
+            ..SpectrumConfig::default()
+        };
+        assert!(Spectrum::of(&trace, channel, odd).is_none());
+    }
+
+    #[test]
+    fn a_pure_tone_has_the_power_of_a_sine_in_its_bin() {
+        // 0.01 amplitude at 12.5 Hz, exactly on bin 32 of a 256 sample window at 100 Hz.
+        let config = SpectrumConfig::default();
+        let (amplitude, frequency) = (0.01, 12.5);
+        let values: Vec<f32> = (0..1024)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f32 / config.sample_rate).sin())
+            .collect();
+
+        let spectrum = Spectrum::from_samples(&values, config).expect("four windows");
+        assert_eq!((spectrum.segments, spectrum.resolution), (7, 0.390625));
+        let peaks = spectrum.dominant(1);
+        assert_eq!(peaks[0].frequency, frequency);
+
+        // A Hann window puts A² N / 3 fs of density in the tone's bin and a
+        // quarter of that in each neighbour, together the sine's A² / 2.
+        let window = config.window as f32;
+        let expected = amplitude * amplitude * window / (3.0 * config.sample_rate);
+        let near = |power: f32, expected: f32| (power - expected).abs() < expected * 1e-3;
+        assert!(
+            near(spectrum.psd[32], expected),
+            "{:?}",
+            &spectrum.psd[30..35]
+        );
+        assert!(near(spectrum.psd[31], expected / 4.0) && near(spectrum.psd[33], expected / 4.0));
+        assert!(near(
+            spectrum.band_power(12.0, 13.0),
+            amplitude * amplitude / 2.0
+        ));
+        assert!(spectrum.psd[35] < expected * 1e-6, "{}", spectrum.psd[35]);
+    }
+}
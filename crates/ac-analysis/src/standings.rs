@@ -0,0 +1,297 @@
+//! Race order inferred from spot updates. Each LapInfo marks a car crossing the
+//! line, so cars are ordered by laps completed, then by who completed their last
+//! lap first. Gaps are measured at the line against the leader's crossing of the
+//! same lap.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use ac_parser::{Event, LapInfo};
+
+use crate::pipeline::{Output, Stage};
+
+/// One car's place in the race.
+///
+/// * `car_id`: the car's `car_id_num`.
+/// * `position`: its place, starting at 1 for the leader.
+/// * `laps`: laps completed.
+/// * `gap`: how far behind the leader it crossed the line on its last lap,
+///   or `None` for the leader, and for a car that completed that lap before
+///   the leader did but has been passed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Standing {
+    pub car_id: i32,
+    pub position: usize,
+    pub laps: i32,
+    pub gap: Option<Duration>,
+}
+
+/// A change to the race order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandingsEvent {
+    PositionGained {
+        car_id: i32,
+        from: usize,
+        to: usize,
+    },
+    PositionLost {
+        car_id: i32,
+        from: usize,
+        to: usize,
+    },
+    /// `car_id` moved ahead of `passed`, and now holds `position`.
+    Overtake {
+        car_id: i32,
+        passed: i32,
+        position: usize,
+    },
+}
+
+/// Every car's line crossings, and the race order they imply.
+///
+/// * `crossings`: per car, when it completed each lap.
+/// * `order`: car ids from the leader back.
+#[derive(Debug, Clone, Default)]
+pub struct Standings {
+    crossings: HashMap<i32, BTreeMap<i32, Duration>>,
+    order: Vec<i32>,
+}
+
+impl Standings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// records a car completing a lap and re-orders the race.
+    /// Returns how positions changed.
+    ///
+    /// * `at`: when the LapInfo arrived, on any monotonic timeline.
+    /// * `info`: the LapInfo.
+    pub fn update(&mut self, at: Duration, info: &LapInfo) -> Vec<StandingsEvent> {
+        self.crossings
+            .entry(info.car_id_num)
+            .or_default()
+            .entry(info.lap)
+            .or_insert(at);
+
+        let before = positions(&self.order);
+        self.reorder();
+        let after = positions(&self.order);
+
+        changes(&before, &after)
+    }
+
+    /// forgets a car, e.g. once it has left the server. Cars behind it move up
+    /// without position events.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    pub fn remove(&mut self, car_id: i32) {
+        self.crossings.remove(&car_id);
+        self.order.retain(|&id| id != car_id);
+    }
+
+    /// the race order, from the leader back.
+    pub fn standings(&self) -> Vec<Standing> {
+        let leader = self.order.first().and_then(|id| self.crossings.get(id));
+
+        self.order
+            .iter()
+            .enumerate()
+            .map(|(idx, &car_id)| {
+                let (laps, last) = self.last_crossing(car_id).unwrap_or_default();
+                let gap = match leader.and_then(|leader| leader.get(&laps)) {
+                    Some(&leader_at) if idx > 0 && last >= leader_at => Some(last - leader_at),
+                    _ => None,
+                };
+
+                Standing {
+                    car_id,
+                    position: idx + 1,
+                    laps,
+                    gap,
+                }
+            })
+            .collect()
+    }
+
+    /// a car's place, starting at 1 for the leader.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    pub fn position(&self, car_id: i32) -> Option<usize> {
+        self.order
+            .iter()
+            .position(|&id| id == car_id)
+            .map(|idx| idx + 1)
+    }
+
+    /// the car in a place.
+    ///
+    /// * `position`: the place, starting at 1 for the leader.
+    pub fn car_at(&self, position: usize) -> Option<i32> {
+        self.order.get(position.checked_sub(1)?).copied()
+    }
+
+    /// when a car completed a lap.
+    ///
+    /// * `car_id`: the car's `car_id_num`.
+    /// * `lap`: the lap.
+    pub fn crossed_at(&self, car_id: i32, lap: i32) -> Option<Duration> {
+        self.crossings.get(&car_id)?.get(&lap).copied()
+    }
+
+    /// how long after `ahead` the `behind` car crossed the line, on the last
+    /// lap `behind` completed. `None` if `ahead` hasn't completed that lap.
+    ///
+    /// * `ahead`: the car in front.
+    /// * `behind`: the car following it.
+    pub fn interval(&self, ahead: i32, behind: i32) -> Option<Duration> {
+        let (lap, at) = self.last_crossing(behind)?;
+        let ahead_at = *self.crossings.get(&ahead)?.get(&lap)?;
+
+        at.checked_sub(ahead_at)
+    }
+
+    /// the last lap a car completed, and when.
+    fn last_crossing(&self, car_id: i32) -> Option<(i32, Duration)> {
+        self.crossings
+            .get(&car_id)?
+            .last_key_value()
+            .map(|(&lap, &at)| (lap, at))
+    }
+
+    fn reorder(&mut self) {
+        let mut order: Vec<_> = self
+            .crossings
+            .keys()
+            .filter_map(|&id| self.last_crossing(id).map(|(laps, at)| (id, laps, at)))
+            .collect();
+
+        order.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)).then(a.0.cmp(&b.0)));
+        self.order = order.into_iter().map(|(id, ..)| id).collect();
+    }
+}
+
+impl Stage for Standings {
+    fn name(&self) -> &str {
+        "standings"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            outputs.extend(self.update(at, info).into_iter().map(Output::Standings));
+        }
+
+        true
+    }
+}
+
+/// each car's place in an order, starting at 1.
+fn positions(order: &[i32]) -> HashMap<i32, usize> {
+    order
+        .iter()
+        .enumerate()
+        .map(|(idx, &id)| (id, idx + 1))
+        .collect()
+}
+
+/// the position events between two orders. Cars new to the order have none.
+///
+/// * `before`: positions before the update.
+/// * `after`: positions after it.
+fn changes(before: &HashMap<i32, usize>, after: &HashMap<i32, usize>) -> Vec<StandingsEvent> {
+    let mut moved: Vec<_> = after
+        .iter()
+        .filter_map(|(&id, &to)| before.get(&id).map(|&from| (id, from, to)))
+        .filter(|&(_, from, to)| from != to)
+        .collect();
+    moved.sort_by_key(|&(_, _, to)| to);
+
+    let mut events = Vec::new();
+
+    for &(car_id, from, to) in &moved {
+        if to < from {
+            events.push(StandingsEvent::PositionGained { car_id, from, to });
+
+            for (&passed, &was) in before {
+                if was < from && after.get(&passed).is_some_and(|&now| now > to) {
+                    events.push(StandingsEvent::Overtake {
+                        car_id,
+                        passed,
+                        position: to,
+                    });
+                }
+            }
+        } else {
+            events.push(StandingsEvent::PositionLost { car_id, from, to });
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod standings_tests {
+    use std::time::Duration;
+
+    use ac_parser::LapInfo;
+
+    use crate::standings::{Standings, StandingsEvent};
+
+    fn lap(car_id_num: i32, lap: i32) -> LapInfo {
+        LapInfo {
+            car_id_num,
+            lap,
+            ..LapInfo::default()
+        }
+    }
+
+    #[test]
+    fn order_and_gaps_follow_line_crossings() {
+        let secs = Duration::from_secs;
+        let mut standings = Standings::new();
+
+        assert!(standings.update(secs(100), &lap(7, 1)).is_empty());
+        assert!(standings.update(secs(102), &lap(3, 1)).is_empty());
+        assert!(standings.update(secs(105), &lap(5, 1)).is_empty());
+
+        let order = standings.standings();
+        assert_eq!(
+            order.iter().map(|s| s.car_id).collect::<Vec<_>>(),
+            [7, 3, 5]
+        );
+        assert_eq!(order[0].gap, None);
+        assert_eq!(order[2].gap, Some(secs(5)));
+
+        // Car 5 gets past both others before the line on lap 2.
+        let events = standings.update(secs(199), &lap(5, 2));
+        assert_eq!(
+            events[0],
+            StandingsEvent::PositionGained {
+                car_id: 5,
+                from: 3,
+                to: 1
+            }
+        );
+        let overtakes = events
+            .iter()
+            .filter(|e| matches!(e, StandingsEvent::Overtake { car_id: 5, .. }))
+            .count();
+        assert_eq!(overtakes, 2);
+        assert!(events.contains(&StandingsEvent::PositionLost {
+            car_id: 7,
+            from: 1,
+            to: 2
+        }));
+        assert_eq!(standings.position(5), Some(1));
+
+        standings.update(secs(201), &lap(7, 2));
+        assert_eq!(standings.standings()[1].gap, Some(secs(2)));
+        assert_eq!(
+            standings.standings()[2].gap,
+            None,
+            "car 3 led car 5 over the line last time"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! How smooth the steering is, so scrappy inputs can be put in numbers and
+//! compared between sessions. Every time the wheel changes direction by more
+//! than a small gap is a reversal; a tidy corner has one or two, a sawing one
+//! many. Travel wasted on corrections shows up in the smoothness score too.
+
+use crate::{
+    corners::{Corner, CornerRules},
+    trace::{Sample, Trace},
+};
+
+/// How far the wheel has to come back, in the units of `steer`, before a
+/// change of direction counts as a reversal rather than noise.
+pub const REVERSAL_GAP: f32 = 2.0;
+
+/// The steering through a stretch of samples.
+///
+/// * `reversals`: how many times the wheel changed direction by at least `REVERSAL_GAP`.
+/// * `per_second`: reversals per second.
+/// * `travel`: how far the wheel moved in total, in the units of `steer`.
+/// * `smoothness`: the share of that travel between reversals, 0 to 1; the
+///   rest went on wiggles too small to count as one. 1 for a still wheel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothness {
+    pub reversals: usize,
+    pub per_second: f32,
+    pub travel: f32,
+    pub smoothness: f32,
+}
+
+impl Smoothness {
+    /// folds the steering of some samples, skipping any whose `steer` isn't a number.
+    ///
+    /// * `samples`: the samples, e.g. one corner.
+    pub fn of(samples: &[Sample]) -> Self {
+        let mut reversals = 0;
+        let mut travel = 0.0;
+        let mut kept = 0.0;
+
+        let mut steers = samples
+            .iter()
+            .map(|sample| sample.car.steer)
+            .filter(|steer| !steer.is_nan());
+        if let Some(first) = steers.next() {
+            // The last reversal, the furthest the wheel has gone since, and
+            // which way it's going: at least REVERSAL_GAP either side of `turn`.
+            let mut turn = first;
+            let mut extreme = turn;
+            let mut rising: Option<bool> = None;
+            let mut previous = turn;
+
+            for steer in steers {
+                travel += (steer - previous).abs();
+                previous = steer;
+
+                match rising {
+                    None if (steer - turn).abs() >= REVERSAL_GAP => {
+                        rising = Some(steer > turn);
+                        extreme = steer;
+                    }
+                    None => {}
+                    Some(up) if (up && steer > extreme) || (!up && steer < extreme) => {
+                        extreme = steer;
+                    }
+                    Some(_) if (steer - extreme).abs() >= REVERSAL_GAP => {
+                        reversals += 1;
+                        kept += (extreme - turn).abs();
+                        turn = extreme;
+                        extreme = steer;
+                        rising = rising.map(|up| !up);
+                    }
+                    Some(_) => {}
+                }
+            }
+            if rising.is_some() {
+                kept += (extreme - turn).abs();
+            }
+        }
+
+        let seconds = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => last.time.saturating_sub(first.time).as_secs_f32(),
+            _ => 0.0,
+        };
+        Self {
+            reversals,
+            per_second: if seconds > 0.0 {
+                reversals as f32 / seconds
+            } else {
+                0.0
+            },
+            travel,
+            smoothness: if travel > 0.0 { kept / travel } else { 1.0 },
+        }
+    }
+}
+
+/// The steering smoothness of one lap.
+///
+/// * `lap`: the lap counter while it was driven.
+/// * `corners`: each corner with its steering.
+/// * `overall`: the steering over the whole lap, straights included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteeringReport {
+    pub lap: u32,
+    pub corners: Vec<(Corner, Smoothness)>,
+    pub overall: Smoothness,
+}
+
+impl SteeringReport {
+    /// the steering smoothness through every corner of a lap. `None` for an empty lap.
+    ///
+    /// * `lap`: the lap, e.g. one of `Trace::laps`.
+    /// * `rules`: what counts as a corner.
+    pub fn of(lap: &Trace, rules: &CornerRules) -> Option<Self> {
+        let samples = lap.samples();
+        let first = samples.first()?;
+
+        let corners = Corner::find(lap, rules)
+            .into_iter()
+            .map(|corner| {
+                let smoothness = Smoothness::of(&samples[corner.samples.clone()]);
+                (corner, smoothness)
+            })
+            .collect();
+
+        Some(Self {
+            lap: first.car.lap_count,
+            corners,
+            overall: Smoothness::of(samples),
+        })
+    }
+}
+
+#[cfg(test)]
+mod steering_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        corners::CornerRules,
+        steering::{Smoothness, SteeringReport},
+        trace::Trace,
+    };
+
+    fn car(accg_horizontal: f32, steer: f32) -> CarInfo {
+        CarInfo {
+            lap_count: 5,
+            accg_horizontal,
+            steer,
+            ..CarInfo::default()
+        }
+    }
+
+    /// a straight sampled every 250 ms at these steering angles.
+    fn steering(steers: &[f32]) -> Smoothness {
+        let mut lap = Trace::new();
+        for (i, &steer) in steers.iter().enumerate() {
+            lap.push(Duration::from_millis(250 * i as u64), car(0.0, steer));
+        }
+        Smoothness::of(lap.samples())
+    }
+
+    #[test]
+    fn no_steering_is_perfectly_smooth() {
+        assert_eq!(
+            SteeringReport::of(&Trace::new(), &CornerRules::default()),
+            None
+        );
+
+        let still = Smoothness {
+            reversals: 0,
+            per_second: 0.0,
+            travel: 0.0,
+            smoothness: 1.0,
+        };
+        assert_eq!(Smoothness::of(&[]), still);
+        assert_eq!(steering(&[45.0]), still);
+        assert_eq!(steering(&[0.0; 8]), still);
+    }
+
+    #[test]
+    fn one_sweep_has_no_reversal_and_no_waste() {
+        let sweep = steering(&[0.0, 20.0, 40.0, 60.0]);
+        assert_eq!(
+            (sweep.reversals, sweep.travel, sweep.smoothness),
+            (0, 60.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn wiggles_under_the_gap_are_all_waste() {
+        let wiggles = steering(&[0.0, 1.0, 0.0, 1.0, 0.0]);
+        assert_eq!((wiggles.reversals, wiggles.travel), (0, 4.0));
+        assert_eq!(wiggles.smoothness, 0.0);
+    }
+
+    #[test]
+    fn nan_steering_is_skipped() {
+        let glitchy = steering(&[f32::NAN, 0.0, 30.0, f32::NAN, 60.0, 30.0]);
+        assert_eq!(glitchy.reversals, 1);
+        assert_eq!((glitchy.travel, glitchy.smoothness), (90.0, 1.0));
+
+        let only_nan = steering(&[f32::NAN, f32::NAN]);
+        assert_eq!((only_nan.travel, only_nan.smoothness), (0.0, 1.0));
+    }
+
+    #[test]
+    fn sawing_at_the_wheel_shows_as_reversals() {
+        // A clean right-hander: in, hold, out. Then a left-hander sawed at
+        // with 5 unit corrections; wobbles of 1 unit are too small to count.
+        let samples = [
+            car(0.0, 0.0),
+            car(1.0, 30.0),
+            car(1.0, 60.0),
+            car(1.0, 60.0),
+            car(1.0, 30.0),
+            car(0.0, 0.0),
+            car(0.0, 1.0),
+            car(0.0, 0.0),
+            car(-1.0, -40.0),
+            car(-1.0, -35.0),
+            car(-1.0, -40.0),
+            car(-1.0, -39.0),
+            car(-1.0, -40.0),
+            car(-1.0, -30.0),
+            car(0.0, 0.0),
+        ];
+
+        let mut lap = Trace::new();
+        for (i, car) in samples.into_iter().enumerate() {
+            lap.push(Duration::from_millis(250 * i as u64), car);
+        }
+
+        let report = SteeringReport::of(&lap, &CornerRules::default()).expect("not empty");
+        let [(_, clean), (_, sawed)] = &report.corners[..] else {
+            panic!("expected two corners, got {:?}", report.corners);
+        };
+        assert_eq!(
+            (clean.reversals, clean.travel, clean.smoothness),
+            (1, 60.0, 1.0)
+        );
+        assert_eq!((sawed.reversals, sawed.travel), (2, 22.0));
+        assert_eq!(sawed.per_second, 2.0 / 1.25);
+        assert!((sawed.smoothness - 20.0 / 22.0).abs() < 1e-6);
+
+        // Up to 60, down to -40, two corrections and back to 0; the wobbles waste 4 units.
+        let overall = report.overall;
+        assert_eq!((overall.reversals, overall.travel), (4, 214.0));
+        assert!((overall.smoothness - 210.0 / 214.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,330 @@
+//! The main straights of a track and how fast each lap gets down them, for
+//! comparing gearing and wing levels. Straights are found on the track map
+//! traced by `car_coordinates`: stretches long enough where the path barely
+//! bends. Each lap is then measured in each of them for its top speed, its
+//! speed where the brakes came on, and its average acceleration up to there.
+
+use std::f32::consts::{PI, TAU};
+
+use ac_parser::CarInfo;
+
+use crate::trace::{Sample, Trace};
+
+/// How far apart, in metres, the points are that the track's curvature is measured between.
+const STEP: f32 = 10.0;
+
+/// What counts as a straight.
+///
+/// * `max_curvature`: the most the path may bend, in radians per metre; 1/300 is a 300 m radius.
+/// * `min_length`: how long it has to be, in metres.
+/// * `brake_on`: brake input that counts as the braking point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StraightRules {
+    pub max_curvature: f32,
+    pub min_length: f32,
+    pub brake_on: f32,
+}
+
+impl Default for StraightRules {
+    fn default() -> Self {
+        Self {
+            max_curvature: 1.0 / 300.0,
+            min_length: 200.0,
+            brake_on: 0.3,
+        }
+    }
+}
+
+/// A straight, found on one lap's track map.
+///
+/// * `number`: its place in the lap, from 1.
+/// * `from`, `to`: where it starts and ends on track, 0 to 1.
+/// * `length`: how long it is, in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Straight {
+    pub number: usize,
+    pub from: f32,
+    pub to: f32,
+    pub length: f32,
+}
+
+/// How one lap went down a straight.
+///
+/// * `straight`: the straight's number.
+/// * `top_kmh`: the highest speed on it.
+/// * `braking_kmh`: the speed where the brakes came on, if they did before its end.
+/// * `acceleration`: the average acceleration from its start to the braking point or end, in m/s².
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StraightSpeeds {
+    pub straight: usize,
+    pub top_kmh: f32,
+    pub braking_kmh: Option<f32>,
+    pub acceleration: f32,
+}
+
+impl Straight {
+    /// the straights of a lap's track map, in the order they're driven.
+    ///
+    /// * `lap`: a lap, ideally a clean one.
+    /// * `rules`: what counts as a straight.
+    pub fn find(lap: &Trace, rules: &StraightRules) -> Vec<Straight> {
+        // Points at least STEP apart, so heading noise over tiny moves doesn't count as bending.
+        let mut points: Vec<&Sample> = Vec::new();
+        for sample in lap.samples() {
+            if points
+                .last()
+                .is_none_or(|last| distance(&last.car, &sample.car) >= STEP)
+            {
+                points.push(sample);
+            }
+        }
+
+        let mut straights = Vec::new();
+        let mut run: Option<(usize, f32)> = None;
+        for i in 1..points.len() {
+            let step = distance(&points[i - 1].car, &points[i].car);
+            let bend = points
+                .get(i + 1)
+                .map(|next| turn(&points[i - 1].car, &points[i].car, &next.car) / step);
+            let straight = bend.is_some_and(|bend| bend <= rules.max_curvature);
+
+            match (straight, run.as_mut()) {
+                (true, Some((_, length))) => *length += step,
+                (true, None) => run = Some((i - 1, step)),
+                (false, Some(&mut (start, length))) => {
+                    run = None;
+                    if length + step >= rules.min_length {
+                        straights.push(Straight {
+                            number: straights.len() + 1,
+                            from: points[start].car.car_pos_normalized,
+                            to: points[i].car.car_pos_normalized,
+                            length: length + step,
+                        });
+                    }
+                }
+                (false, None) => {}
+            }
+        }
+
+        straights
+    }
+
+    /// whether a track position is on the straight, allowing for one that
+    /// runs over the start/finish line.
+    ///
+    /// * `position`: 0 to 1.
+    pub fn contains(&self, position: f32) -> bool {
+        if self.from <= self.to {
+            (self.from..=self.to).contains(&position)
+        } else {
+            position >= self.from || position <= self.to
+        }
+    }
+
+    /// how a lap went down this straight. `None` if the lap never drove it.
+    ///
+    /// * `lap`: the lap.
+    /// * `rules`: what counts as the braking point.
+    pub fn speeds(&self, lap: &Trace, rules: &StraightRules) -> Option<StraightSpeeds> {
+        let on: Vec<&Sample> = lap
+            .samples()
+            .iter()
+            .filter(|s| self.contains(s.car.car_pos_normalized))
+            .collect();
+        let first = on.first()?;
+
+        let braking = on.iter().find(|s| s.car.brake >= rules.brake_on);
+        let until = braking.or(on.last())?;
+        let seconds = until.time.saturating_sub(first.time).as_secs_f32();
+        let acceleration = if seconds > 0.0 {
+            (until.car.speed_ms - first.car.speed_ms) / seconds
+        } else {
+            0.0
+        };
+
+        Some(StraightSpeeds {
+            straight: self.number,
+            top_kmh: on.iter().map(|s| s.car.speed_kmh).fold(0.0, f32::max),
+            braking_kmh: braking.map(|s| s.car.speed_kmh),
+            acceleration,
+        })
+    }
+}
+
+/// the ground distance between two cars' positions, in metres.
+fn distance(a: &CarInfo, b: &CarInfo) -> f32 {
+    let [ax, _, az] = a.car_coordinates;
+    let [bx, _, bz] = b.car_coordinates;
+    (bx - ax).hypot(bz - az)
+}
+
+/// how far the heading turns at `b` between `a` and `c`, in radians.
+fn turn(a: &CarInfo, b: &CarInfo, c: &CarInfo) -> f32 {
+    let heading = |from: &CarInfo, to: &CarInfo| {
+        let ([fx, _, fz], [tx, _, tz]) = (from.car_coordinates, to.car_coordinates);
+        (tz - fz).atan2(tx - fx)
+    };
+
+    let delta = heading(b, c) - heading(a, b);
+    // Wrap into -PI..PI so crossing the atan2 seam isn't a full turn.
+    ((delta + PI).rem_euclid(TAU) - PI).abs()
+}
+
+#[cfg(test)]
+mod straights_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        straights::{Straight, StraightRules},
+        trace::Trace,
+    };
+
+    /// a lap driven east along z = 0 every `spacing` metres for `length`,
+    /// at 30 m/s, starting at track position `from`.
+    fn line(length: u32, spacing: usize, from: f32) -> Trace {
+        let mut lap = Trace::new();
+        let points = (length as usize / spacing) as f32;
+        for (i, m) in (0..=length).step_by(spacing).enumerate() {
+            let car = CarInfo {
+                car_coordinates: [m as f32, 0.0, 0.0],
+                car_pos_normalized: (from + 0.2 * i as f32 / points).fract(),
+                speed_ms: 30.0,
+                speed_kmh: 108.0,
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_millis(100 * i as u64), car);
+        }
+        lap
+    }
+
+    #[test]
+    fn empty_and_parked_laps_have_no_straights() {
+        let rules = StraightRules::default();
+        assert!(Straight::find(&Trace::new(), &rules).is_empty());
+        assert!(
+            Straight::find(&line(0, 5, 0.0), &rules).is_empty(),
+            "one sample"
+        );
+
+        let mut parked = Trace::new();
+        for i in 0..100 {
+            parked.push(Duration::from_millis(100 * i), CarInfo::default());
+        }
+        assert!(
+            Straight::find(&parked, &rules).is_empty(),
+            "no distance covered"
+        );
+    }
+
+    #[test]
+    fn a_straight_to_the_end_of_the_lap_is_kept() {
+        let rules = StraightRules::default();
+        let [straight] = Straight::find(&line(300, 5, 0.0), &rules)[..] else {
+            panic!("expected one straight");
+        };
+        assert!((straight.length - 300.0).abs() <= 10.0, "{straight:?}");
+        assert!(
+            Straight::find(&line(150, 5, 0.0), &rules).is_empty(),
+            "too short"
+        );
+    }
+
+    #[test]
+    fn nan_coordinates_are_stepped_over() {
+        let mut lap = Trace::new();
+        for (i, sample) in line(300, 5, 0.0).samples().iter().enumerate() {
+            let mut car = sample.car.clone();
+            if i % 7 == 3 {
+                car.car_coordinates = [f32::NAN; 3];
+            }
+            lap.push(sample.time, car);
+        }
+
+        let straights = Straight::find(&lap, &StraightRules::default());
+        assert_eq!(straights.len(), 1, "{straights:?}");
+    }
+
+    #[test]
+    fn a_straight_across_the_line_wraps() {
+        let rules = StraightRules::default();
+        let lap = line(300, 5, 0.9);
+        let [straight] = Straight::find(&lap, &rules)[..] else {
+            panic!("expected one straight");
+        };
+        assert!(straight.from > straight.to, "{straight:?}");
+        assert!(straight.contains(0.95) && straight.contains(0.05));
+        assert!(!straight.contains(0.5));
+
+        let speeds = straight.speeds(&lap, &rules).expect("drove it");
+        assert_eq!((speeds.top_kmh, speeds.braking_kmh), (108.0, None));
+        assert_eq!(speeds.acceleration, 0.0);
+    }
+
+    #[test]
+    fn a_lap_that_misses_the_straight_has_no_speeds() {
+        let rules = StraightRules::default();
+        let straight = Straight {
+            number: 1,
+            from: 0.5,
+            to: 0.6,
+            length: 300.0,
+        };
+        assert_eq!(straight.speeds(&line(300, 5, 0.0), &rules), None);
+
+        let mut one = Trace::new();
+        let car = CarInfo {
+            car_pos_normalized: 0.55,
+            speed_kmh: 200.0,
+            ..CarInfo::default()
+        };
+        one.push(Duration::ZERO, car);
+        let speeds = straight.speeds(&one, &rules).expect("one sample on it");
+        assert_eq!((speeds.top_kmh, speeds.acceleration), (200.0, 0.0));
+    }
+
+    #[test]
+    fn straights_are_found_on_the_map_and_measured() {
+        // 500 m straight east, a tight 90 degree left of 50 m radius, then 150 m north.
+        let mut points = Vec::new();
+        for m in (0..=500).step_by(5) {
+            points.push([m as f32, 0.0]);
+        }
+        for step in 1..=16 {
+            let angle = std::f32::consts::FRAC_PI_2 * step as f32 / 16.0;
+            points.push([500.0 + 50.0 * angle.sin(), 50.0 - 50.0 * angle.cos()]);
+        }
+        for m in (5..=150).step_by(5) {
+            points.push([550.0, 50.0 + m as f32]);
+        }
+
+        let total = points.len() as f32;
+        let mut lap = Trace::new();
+        for (i, [x, z]) in points.into_iter().enumerate() {
+            // 20 m/s, gaining 0.5 m/s every sample until braking 100 m before the corner.
+            let speed_ms = 20.0 + 0.5 * i.min(80) as f32;
+            let car = CarInfo {
+                car_coordinates: [x, 0.0, z],
+                car_pos_normalized: i as f32 / total,
+                speed_ms,
+                speed_kmh: speed_ms * 3.6,
+                brake: if (80..101).contains(&i) { 1.0 } else { 0.0 },
+                ..CarInfo::default()
+            };
+            lap.push(Duration::from_millis(250 * i as u64), car);
+        }
+
+        let rules = StraightRules::default();
+        let straights = Straight::find(&lap, &rules);
+        let [straight] = &straights[..] else {
+            panic!("expected only the long straight, got {straights:?}");
+        };
+        assert!((straight.length - 500.0).abs() <= 10.0, "{straight:?}");
+
+        let speeds = straight.speeds(&lap, &rules).expect("drove it");
+        assert_eq!(speeds.top_kmh, 60.0 * 3.6);
+        assert_eq!(speeds.braking_kmh, Some(60.0 * 3.6));
+        assert!((speeds.acceleration - 2.0).abs() < 1e-4, "{speeds:?}");
+    }
+}
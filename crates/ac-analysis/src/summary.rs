@@ -0,0 +1,372 @@
+//! Compact periodic summaries in place of raw packets, for consumers on a
+//! battery or bandwidth budget. Every CarInfo in a window is folded into the
+//! min, max and mean of a few key channels, optionally histograms of others,
+//! and the latest LapInfo rides along.
+
+use std::time::Duration;
+
+use ac_parser::{Channel, Event, LapInfo, Units};
+
+use crate::{
+    columns::ColumnStats,
+    histogram::Histogram,
+    pipeline::{Output, Stage},
+};
+
+/// How often a summary is produced, by default.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One window of telemetry, summarized.
+///
+/// * `from`, `to`: the first and last CarInfo folded in.
+/// * `samples`: how many CarInfo packets were folded in.
+/// * `channels`: min, max and mean of each summarized channel, leaving out
+///   any that only read NaN.
+/// * `histograms`: the window's histograms, if the summarizer keeps any.
+/// * `lap`: the latest LapInfo seen so far, if any.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Summary {
+    pub from: Duration,
+    pub to: Duration,
+    pub samples: usize,
+    pub channels: Vec<(Channel, ColumnStats)>,
+    pub histograms: Vec<Histogram>,
+    pub lap: Option<LapInfo>,
+}
+
+impl Summary {
+    /// min, max and mean of a channel, if it's summarized.
+    ///
+    /// * `channel`: the channel to look up.
+    pub fn stats(&self, channel: Channel) -> Option<ColumnStats> {
+        self.channels
+            .iter()
+            .find(|(c, _)| *c == channel)
+            .map(|&(_, stats)| stats)
+    }
+
+    /// the histogram of a channel, if one is kept.
+    ///
+    /// * `channel`: the channel to look up.
+    pub fn histogram(&self, channel: Channel) -> Option<&Histogram> {
+        self.histograms.iter().find(|h| h.channel() == channel)
+    }
+
+    /// the window in a line for people to read, e.g. "60 samples over 1.0 s,
+    /// 120.0 to 180.0 km/h (mean 150.0 km/h), lap 3". The speed is left out
+    /// unless it's summarized in km/h or m/s, and the lap without a LapInfo.
+    ///
+    /// * `units`: what the speed is shown in.
+    pub fn describe(&self, units: Units) -> String {
+        let span = self.to.saturating_sub(self.from).as_secs_f32();
+        let plural = if self.samples == 1 { "" } else { "s" };
+        let mut line = format!("{} sample{plural} over {span:.1} s", self.samples);
+
+        let speed = [(Channel::SpeedKmh, 1.0), (Channel::SpeedMs, 3.6)]
+            .into_iter()
+            .find_map(|(channel, to_kmh)| Some((self.stats(channel)?, to_kmh)));
+        if let Some((speed, to_kmh)) = speed {
+            let [min, max, mean] = [speed.min, speed.max, speed.mean].map(|kmh| kmh * to_kmh);
+            line += &format!(
+                ", {:.1} to {} (mean {})",
+                units.speed(min),
+                units.format_speed(max),
+                units.format_speed(mean)
+            );
+        }
+        if let Some(lap) = &self.lap {
+            line += &format!(", lap {}", lap.lap);
+        }
+
+        line
+    }
+}
+
+/// the JSON Schema of `Summary` as the `serde` feature serializes it. Like
+/// `event_schema`, it's checked in, as `schema/summary.schema.json`.
+#[cfg(feature = "schema")]
+pub fn summary_schema() -> schemars::Schema {
+    schemars::schema_for!(Summary)
+}
+
+/// Folds CarInfo into a summary per interval.
+///
+/// * `interval`: how much time each summary covers.
+/// * `channels`: the channels summarized, with their running min, max, sum
+///   and count, NaN readings skipped.
+/// * `histograms`: the histograms being filled for this window.
+/// * `window`: the first and last CarInfo of the window being filled, and how many.
+/// * `lap`: the latest LapInfo.
+#[derive(Debug, Clone)]
+pub struct Summarizer {
+    interval: Duration,
+    channels: Vec<(Channel, f32, f32, f64, usize)>,
+    histograms: Vec<Histogram>,
+    window: Option<(Duration, Duration, usize)>,
+    lap: Option<LapInfo>,
+}
+
+impl Default for Summarizer {
+    /// the key channels, once a second.
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL, &Summarizer::KEY_CHANNELS)
+    }
+}
+
+impl Summarizer {
+    /// what a dashboard on a budget usually shows.
+    pub const KEY_CHANNELS: [Channel; 5] = [
+        Channel::SpeedKmh,
+        Channel::EngineRpm,
+        Channel::Gear,
+        Channel::Gas,
+        Channel::Brake,
+    ];
+
+    /// * `interval`: how much time each summary covers.
+    /// * `channels`: the channels to summarize.
+    pub fn new(interval: Duration, channels: &[Channel]) -> Self {
+        let mut summarizer = Self {
+            interval,
+            channels: channels.iter().map(|&c| (c, 0.0, 0.0, 0.0, 0)).collect(),
+            histograms: Vec::new(),
+            window: None,
+            lap: None,
+        };
+        summarizer.reset();
+
+        summarizer
+    }
+
+    /// also keeps a histogram per window, e.g. of throttle position.
+    ///
+    /// * `histogram`: the channel and bins to count.
+    pub fn histogram(mut self, histogram: Histogram) -> Self {
+        self.histograms.push(histogram.cleared());
+        self
+    }
+
+    /// folds in an event. Returns the previous window's summary once an event
+    /// lands past its end.
+    ///
+    /// * `at`: when the event arrived, on any monotonic timeline.
+    /// * `event`: the event.
+    pub fn update(&mut self, at: Duration, event: &Event) -> Option<Summary> {
+        let car = match event {
+            Event::CarInfo(car) => car,
+            Event::LapInfo(info) => {
+                self.lap = Some(info.clone());
+                return None;
+            }
+            Event::HandshakeResponse(_) => return None,
+        };
+
+        let summary = match self.window {
+            Some((from, _, _)) if at.saturating_sub(from) >= self.interval => self.flush(),
+            _ => None,
+        };
+
+        let (from, _, samples) = self.window.unwrap_or((at, at, 0));
+        self.window = Some((from, at, samples + 1));
+        for (channel, min, max, sum, count) in &mut self.channels {
+            let value = channel.value(car);
+            if value.is_nan() {
+                continue;
+            }
+            *min = min.min(value);
+            *max = max.max(value);
+            *sum += f64::from(value);
+            *count += 1;
+        }
+        self.histograms.iter_mut().for_each(|h| h.add_car(car));
+
+        summary
+    }
+
+    /// summarizes the window filled so far and starts a new one, e.g. when the
+    /// session ends. Returns `None` if no CarInfo has come in since the last one.
+    pub fn flush(&mut self) -> Option<Summary> {
+        let (from, to, samples) = self.window?;
+        let channels = self
+            .channels
+            .iter()
+            .filter(|&&(.., count)| count > 0)
+            .map(|&(channel, min, max, sum, count)| {
+                let stats = ColumnStats {
+                    min,
+                    max,
+                    mean: (sum / count as f64) as f32,
+                };
+                (channel, stats)
+            })
+            .collect();
+        let histograms = self.histograms.iter().map(Histogram::cleared).collect();
+        let histograms = std::mem::replace(&mut self.histograms, histograms);
+        self.reset();
+
+        Some(Summary {
+            from,
+            to,
+            samples,
+            channels,
+            histograms,
+            lap: self.lap.clone(),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.window = None;
+        for (_, min, max, sum, count) in &mut self.channels {
+            (*min, *max, *sum, *count) = (f32::INFINITY, f32::NEG_INFINITY, 0.0, 0);
+        }
+    }
+}
+
+impl Stage for Summarizer {
+    fn name(&self) -> &str {
+        "summary"
+    }
+
+    /// swallows every event: later stages only see the summaries.
+    fn process(&mut self, at: Duration, event: &Event, outputs: &mut Vec<Output>) -> bool {
+        outputs.extend(self.update(at, event).map(Output::Summary));
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod summary_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Channel, Event, LapInfo, Units};
+
+    use crate::{histogram::Histogram, summary::Summarizer};
+
+    fn car(speed_kmh: f32) -> Event {
+        Event::CarInfo(CarInfo {
+            speed_kmh,
+            ..CarInfo::default()
+        })
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn no_car_info_means_no_summary() {
+        let mut summarizer = Summarizer::default();
+        assert_eq!(summarizer.flush(), None);
+
+        let lap = Event::LapInfo(LapInfo::default());
+        assert_eq!(summarizer.update(ms(0), &lap), None);
+        assert_eq!(summarizer.update(ms(5000), &lap), None);
+        assert_eq!(summarizer.flush(), None, "a LapInfo alone isn't a window");
+    }
+
+    #[test]
+    fn a_single_sample_is_its_own_min_max_and_mean() {
+        let mut summarizer = Summarizer::default();
+        summarizer.update(ms(400), &car(0.0));
+
+        let summary = summarizer.flush().expect("one car");
+        assert_eq!(
+            (summary.from, summary.to, summary.samples),
+            (ms(400), ms(400), 1)
+        );
+        let speed = summary.stats(Channel::SpeedKmh).expect("a key channel");
+        assert_eq!((speed.min, speed.max, speed.mean), (0.0, 0.0, 0.0));
+        assert_eq!(
+            summary.describe(Units::Metric),
+            "1 sample over 0.0 s, 0.0 to 0.0 km/h (mean 0.0 km/h)"
+        );
+    }
+
+    #[test]
+    fn nan_readings_are_left_out_of_the_stats() {
+        let mut summarizer = Summarizer::new(Duration::from_secs(1), &[Channel::SpeedKmh]);
+        for speed in [100.0, f32::NAN, 200.0] {
+            summarizer.update(ms(0), &car(speed));
+        }
+        let summary = summarizer.flush().expect("three cars");
+        assert_eq!(summary.samples, 3);
+        let speed = summary.stats(Channel::SpeedKmh).expect("summarized");
+        assert_eq!((speed.min, speed.max, speed.mean), (100.0, 200.0, 150.0));
+
+        summarizer.update(ms(0), &car(f32::NAN));
+        let summary = summarizer.flush().expect("one car");
+        assert_eq!(summary.stats(Channel::SpeedKmh), None);
+        assert_eq!(summary.describe(Units::Metric), "1 sample over 0.0 s");
+    }
+
+    #[test]
+    fn windows_fold_into_min_max_and_mean() {
+        let mut summarizer =
+            Summarizer::default().histogram(Histogram::new(Channel::SpeedKmh, 0.0, 300.0, 3));
+
+        assert_eq!(summarizer.update(ms(0), &car(100.0)), None);
+        let lap = LapInfo {
+            lap: 3,
+            ..LapInfo::default()
+        };
+        assert_eq!(summarizer.update(ms(300), &Event::LapInfo(lap)), None);
+        assert_eq!(summarizer.update(ms(500), &car(200.0)), None);
+        assert_eq!(summarizer.update(ms(900), &car(180.0)), None);
+
+        let summary = summarizer
+            .update(ms(1000), &car(50.0))
+            .expect("a second has passed");
+        assert_eq!(
+            (summary.from, summary.to, summary.samples),
+            (ms(0), ms(900), 3)
+        );
+        let speed = summary.stats(Channel::SpeedKmh).expect("a key channel");
+        assert_eq!((speed.min, speed.max, speed.mean), (100.0, 200.0, 160.0));
+        assert_eq!(summary.lap.as_ref().map(|info| info.lap), Some(3));
+        assert_eq!(summary.stats(Channel::CgHeight), None);
+        let speeds = summary.histogram(Channel::SpeedKmh).expect("kept");
+        let counts: Vec<_> = speeds.bins().map(|(_, _, count)| count).collect();
+        assert_eq!(counts, [0, 2, 1]);
+        assert_eq!(
+            summary.describe(Units::Imperial),
+            "3 samples over 0.9 s, 62.1 to 124.3 mph (mean 99.4 mph), lap 3"
+        );
+
+        let rest = summarizer.flush().expect("the car at 1s");
+        assert_eq!((rest.samples, rest.from), (1, ms(1000)));
+        assert_eq!(summarizer.flush(), None);
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn checked_in_schema_matches_summaries() {
+        use std::{env, fs, path::Path};
+
+        use crate::summary::{Summary, summary_schema};
+
+        let schema = serde_json::to_string_pretty(&summary_schema()).expect("serializes") + "\n";
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../schema/summary.schema.json");
+        if env::var_os("AC_LIB_WRITE_SCHEMA").is_some() {
+            fs::write(&path, &schema).expect("failed to write schema");
+        }
+
+        let checked_in = fs::read_to_string(&path).expect("no checked-in schema");
+        assert_eq!(
+            checked_in, schema,
+            "summary.schema.json is stale; rerun with AC_LIB_WRITE_SCHEMA=1"
+        );
+
+        let mut summarizer =
+            Summarizer::default().histogram(Histogram::new(Channel::Gas, 0.0, 1.0, 4));
+        summarizer.update(Duration::ZERO, &Event::CarInfo(CarInfo::default()));
+        let summary = summarizer.flush().expect("one car");
+        let json = serde_json::to_string(&summary).expect("serializes");
+        assert_eq!(
+            serde_json::from_str::<Summary>(&json).expect("deserializes"),
+            summary
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use ac_parser::{CarInfo, Channel, Event, Recording, Timestamp};
+
+/// A CarInfo packet positioned in time and along the track.
+///
+/// * `time`: time since the start of the trace.
+/// * `distance`: laps since the start of the trace, plus the normalized track position.
+/// * `car`: the packet itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub time: Duration,
+    pub distance: f32,
+    pub car: CarInfo,
+}
+
+/// An ordered run of CarInfo samples: a whole session, or a single lap.
+///
+/// * `recorded_at`: the wall-clock time arrival times are relative to, if known.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    samples: Vec<Sample>,
+    started_at: Duration,
+    first_lap: u32,
+    recorded_at: Option<Timestamp>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// builds a trace from every CarInfo packet in a recording, skipping other
+    /// events and packets that fail to parse.
+    ///
+    /// * `recording`: the recording to read.
+    pub fn from_recording(recording: &Recording) -> Self {
+        let mut trace = Trace::new();
+        trace.recorded_at = recording.started_at();
+
+        for frame in recording.frames() {
+            if let Ok(Event::CarInfo(car)) = Event::try_parse(&frame.packet) {
+                trace.push(frame.at, car);
+            }
+        }
+
+        trace
+    }
+
+    /// appends a packet, positioning it relative to the first one in the trace.
+    ///
+    /// * `at`: when the packet arrived.
+    /// * `car`: the packet.
+    pub fn push(&mut self, at: Duration, car: CarInfo) {
+        if self.samples.is_empty() {
+            self.started_at = at;
+            self.first_lap = car.lap_count;
+        }
+
+        let laps = i64::from(car.lap_count) - i64::from(self.first_lap);
+
+        self.samples.push(Sample {
+            time: at.saturating_sub(self.started_at),
+            distance: laps as f32 + car.car_pos_normalized,
+            car,
+        });
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// when a sample arrived in wall-clock time, for a trace built from a recording
+    /// whose start is known.
+    ///
+    /// * `sample`: a sample of this trace.
+    pub fn wall_clock(&self, sample: &Sample) -> Option<Timestamp> {
+        self.recorded_at
+            .map(|start| start.offset(self.started_at + sample.time))
+    }
+
+    /// time between the first and last sample.
+    pub fn duration(&self) -> Duration {
+        self.samples.last().map(|s| s.time).unwrap_or_default()
+    }
+
+    /// splits the trace wherever the lap counter changes. Each lap is its own
+    /// trace, so its time starts at zero and its distance runs from 0 to 1.
+    pub fn laps(&self) -> Vec<Trace> {
+        let mut laps: Vec<Trace> = Vec::new();
+
+        for sample in &self.samples {
+            let same_lap = laps
+                .last()
+                .and_then(|lap| lap.samples.last())
+                .is_some_and(|last| last.car.lap_count == sample.car.lap_count);
+
+            if !same_lap {
+                laps.push(Trace {
+                    recorded_at: self.recorded_at,
+                    ..Trace::new()
+                });
+            }
+
+            if let Some(lap) = laps.last_mut() {
+                lap.push(self.started_at + sample.time, sample.car.clone());
+            }
+        }
+
+        laps
+    }
+
+    /// linearly interpolates a channel at a point along the trace.
+    /// Points before the first or after the last sample clamp to those samples.
+    ///
+    /// * `channel`: the channel to read.
+    /// * `key`: what positions a sample, e.g. its time or distance.
+    /// * `at`: the position to read the channel at.
+    pub fn interpolate<K>(&self, channel: Channel, key: K, at: f32) -> Option<f32>
+    where
+        K: Fn(&Sample) -> f32,
+    {
+        let idx = self.samples.partition_point(|s| key(s) < at);
+
+        let after = match self.samples.get(idx) {
+            Some(after) => after,
+            None => return self.samples.last().map(|s| channel.value(&s.car)),
+        };
+
+        let Some(before) = idx.checked_sub(1).and_then(|i| self.samples.get(i)) else {
+            return Some(channel.value(&after.car));
+        };
+
+        let (x0, x1) = (key(before), key(after));
+        let (y0, y1) = (channel.value(&before.car), channel.value(&after.car));
+
+        if x1 <= x0 {
+            return Some(y1);
+        }
+
+        Some(y0 + (y1 - y0) * (at - x0) / (x1 - x0))
+    }
+}
+
+#[cfg(test)]
+mod trace_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Channel, Recording, Timestamp};
+
+    use crate::trace::Trace;
+
+    fn car(lap_count: u32, pos: f32, speed_kmh: f32) -> CarInfo {
+        CarInfo {
+            lap_count,
+            car_pos_normalized: pos,
+            speed_kmh,
+            ..CarInfo::default()
+        }
+    }
+
+    #[test]
+    fn laps_split_on_lap_counter_and_rebase() {
+        let mut trace = Trace::new();
+        trace.push(Duration::from_secs(10), car(0, 0.5, 100.0));
+        trace.push(Duration::from_secs(11), car(0, 0.9, 110.0));
+        trace.push(Duration::from_secs(12), car(1, 0.1, 120.0));
+
+        assert_eq!(trace.samples()[2].distance, 1.1);
+
+        let laps = trace.laps();
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[1].samples()[0].time, Duration::ZERO);
+        assert_eq!(laps[1].samples()[0].distance, 0.1);
+    }
+
+    #[test]
+    fn samples_map_to_wall_clock_time_through_laps() {
+        let mut recording = Recording::new();
+        recording.set_started_at(Timestamp::from_unix_nanos(5_000_000_000));
+        recording.push(Duration::from_secs(1), &car(0, 0.9, 100.0).to_bytes());
+        recording.push(Duration::from_secs(2), &car(1, 0.1, 100.0).to_bytes());
+
+        let trace = Trace::from_recording(&recording);
+        let lap = &trace.laps()[1];
+        assert_eq!(
+            lap.wall_clock(&lap.samples()[0]).map(Timestamp::unix_nanos),
+            Some(7_000_000_000)
+        );
+        assert_eq!(Trace::new().wall_clock(&trace.samples()[0]), None);
+    }
+
+    #[test]
+    fn interpolate_is_linear_between_samples() {
+        let mut trace = Trace::new();
+        trace.push(Duration::ZERO, car(0, 0.0, 100.0));
+        trace.push(Duration::from_secs(2), car(0, 0.5, 200.0));
+
+        let by_time = |s: &crate::trace::Sample| s.time.as_secs_f32();
+        assert_eq!(
+            trace.interpolate(Channel::SpeedKmh, by_time, 1.0),
+            Some(150.0)
+        );
+        assert_eq!(
+            trace.interpolate(Channel::SpeedKmh, by_time, 9.0),
+            Some(200.0)
+        );
+        assert_eq!(
+            trace.interpolate(Channel::SpeedKmh, by_time, -1.0),
+            Some(100.0)
+        );
+    }
+}
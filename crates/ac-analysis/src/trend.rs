@@ -0,0 +1,228 @@
+//! Whether a car is catching the one ahead, the classic pit-wall question. The
+//! gap between each watched pair is measured at the line once both have
+//! completed a lap, and a line fitted through the last few laps gives the
+//! seconds per lap gained or lost.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use ac_parser::{Event, LapInfo};
+
+use crate::{
+    pipeline::{Output, Stage},
+    standings::Standings,
+};
+
+/// Laps of gap history kept per pair, by default.
+const DEFAULT_WINDOW: usize = 5;
+
+/// How the gap between two cars is changing.
+///
+/// * `ahead`, `behind`: the pair, as watched.
+/// * `gap`: the latest gap in seconds, negative once `behind` has got ahead.
+/// * `per_lap`: seconds per lap the gap is changing by; negative when `behind` is catching.
+/// * `laps_to_catch`: laps until the gap closes at that rate, if it's closing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trend {
+    pub ahead: i32,
+    pub behind: i32,
+    pub gap: f32,
+    pub per_lap: f32,
+    pub laps_to_catch: Option<f32>,
+}
+
+/// Rolling gap histories for selected pairs of cars.
+///
+/// * `window`: how many laps of history to keep per pair.
+/// * `pairs`: (lap, gap in seconds) per watched (ahead, behind) pair.
+/// * `standings`: line crossings, when used as a pipeline stage.
+#[derive(Debug, Clone)]
+pub struct GapTrends {
+    window: usize,
+    pairs: HashMap<(i32, i32), VecDeque<(i32, f32)>>,
+    standings: Standings,
+}
+
+impl Default for GapTrends {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl GapTrends {
+    /// * `window`: how many laps of history to keep per pair; at least two.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(2),
+            pairs: HashMap::new(),
+            standings: Standings::new(),
+        }
+    }
+
+    /// starts keeping the gap between two cars.
+    ///
+    /// * `ahead`: the car in front.
+    /// * `behind`: the car chasing it.
+    pub fn watch(&mut self, ahead: i32, behind: i32) {
+        self.pairs.entry((ahead, behind)).or_default();
+    }
+
+    /// stops keeping a pair's gap and forgets its history.
+    pub fn unwatch(&mut self, ahead: i32, behind: i32) {
+        self.pairs.remove(&(ahead, behind));
+    }
+
+    /// records a watched pair's gap on a lap. Ignored for pairs not watched.
+    ///
+    /// * `ahead`, `behind`: the pair.
+    /// * `lap`: the lap the gap was measured on.
+    /// * `gap`: seconds from `ahead` to `behind`, negative if `behind` is in front.
+    pub fn record(&mut self, ahead: i32, behind: i32, lap: i32, gap: f32) {
+        let Some(history) = self.pairs.get_mut(&(ahead, behind)) else {
+            return;
+        };
+
+        if history.back().is_some_and(|&(last, _)| last >= lap) {
+            return;
+        }
+        if history.len() == self.window {
+            history.pop_front();
+        }
+        history.push_back((lap, gap));
+    }
+
+    /// records a car crossing the line, measuring the gap for every watched
+    /// pair it's part of once both cars have completed the lap.
+    ///
+    /// * `at`: when the LapInfo arrived, on any monotonic timeline.
+    /// * `info`: the LapInfo.
+    pub fn update(&mut self, at: Duration, info: &LapInfo) {
+        self.standings.update(at, info);
+
+        let car = info.car_id_num;
+        let pairs: Vec<_> = self
+            .pairs
+            .keys()
+            .copied()
+            .filter(|&(ahead, behind)| ahead == car || behind == car)
+            .collect();
+
+        for (ahead, behind) in pairs {
+            let (Some(ahead_at), Some(behind_at)) = (
+                self.standings.crossed_at(ahead, info.lap),
+                self.standings.crossed_at(behind, info.lap),
+            ) else {
+                continue;
+            };
+
+            let gap = behind_at.as_secs_f32() - ahead_at.as_secs_f32();
+            self.record(ahead, behind, info.lap, gap);
+        }
+    }
+
+    /// how a watched pair's gap is changing, once it has two laps of history.
+    ///
+    /// * `ahead`, `behind`: the pair.
+    pub fn trend(&self, ahead: i32, behind: i32) -> Option<Trend> {
+        let history = self.pairs.get(&(ahead, behind))?;
+        let &(_, gap) = history.back()?;
+        let per_lap = slope(history)?;
+        let laps_to_catch = (gap > 0.0 && per_lap < 0.0).then(|| gap / -per_lap);
+
+        Some(Trend {
+            ahead,
+            behind,
+            gap,
+            per_lap,
+            laps_to_catch,
+        })
+    }
+
+    /// the trend for every watched pair with enough history.
+    pub fn trends(&self) -> Vec<Trend> {
+        self.pairs
+            .keys()
+            .filter_map(|&(ahead, behind)| self.trend(ahead, behind))
+            .collect()
+    }
+}
+
+impl Stage for GapTrends {
+    fn name(&self) -> &str {
+        "trends"
+    }
+
+    fn process(&mut self, at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+        if let Event::LapInfo(info) = event {
+            self.update(at, info);
+        }
+
+        true
+    }
+}
+
+/// the least-squares slope of gap against lap.
+///
+/// * `history`: (lap, gap) points.
+fn slope(history: &VecDeque<(i32, f32)>) -> Option<f32> {
+    if history.len() < 2 {
+        return None;
+    }
+
+    let n = history.len() as f32;
+    let mean_lap = history.iter().map(|&(lap, _)| lap as f32).sum::<f32>() / n;
+    let mean_gap = history.iter().map(|&(_, gap)| gap).sum::<f32>() / n;
+
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for &(lap, gap) in history {
+        let dx = lap as f32 - mean_lap;
+        covariance += dx * (gap - mean_gap);
+        variance += dx * dx;
+    }
+
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+#[cfg(test)]
+mod trend_tests {
+    use std::time::Duration;
+
+    use ac_parser::LapInfo;
+
+    use crate::trend::GapTrends;
+
+    #[test]
+    fn closing_gaps_give_laps_to_catch() {
+        let mut trends = GapTrends::new(3);
+        trends.watch(1, 2);
+
+        // car 2 starts 4s back and takes half a second a lap.
+        for (lap, gap) in (1..=5).zip([4.0, 3.5, 3.0, 2.5, 2.0]) {
+            let at = Duration::from_secs(100 * lap as u64);
+            let lap_info = |car_id_num| LapInfo {
+                car_id_num,
+                lap,
+                ..LapInfo::default()
+            };
+            trends.update(at, &lap_info(1));
+            assert_eq!(
+                trends.trend(1, 2).map(|t| t.gap),
+                (lap > 2).then_some(gap + 0.5)
+            );
+            trends.update(at + Duration::from_secs_f32(gap), &lap_info(2));
+        }
+
+        let trend = trends.trend(1, 2).expect("enough history");
+        assert!((trend.gap - 2.0).abs() < 1e-3);
+        assert!((trend.per_lap + 0.5).abs() < 1e-3);
+        assert!(
+            trend
+                .laps_to_catch
+                .is_some_and(|laps| (laps - 4.0).abs() < 1e-2)
+        );
+        assert_eq!(trends.trend(2, 1), None, "not watched");
+        assert_eq!(trends.trends().len(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+//! Whether a lap counts. The remote telemetry has no official cut flag, so
+//! laps are judged by heuristics: tyres picking up dirt means the car went off
+//! track, the pit flag marks in- and out-laps, and a lap far slower or faster
+//! than the best one is an anomaly (a spin, a reset, a teleport to the pits).
+
+use std::time::Duration;
+
+use ac_parser::CarInfo;
+
+use crate::trace::Trace;
+
+/// How a lap was judged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LapValidity {
+    #[default]
+    Valid,
+    Invalid(InvalidReason),
+    /// The car entered the pits during the lap.
+    InLap,
+    /// The lap started in the pits.
+    OutLap,
+}
+
+impl LapValidity {
+    /// whether the lap should count towards best laps and comparisons.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, LapValidity::Valid)
+    }
+}
+
+/// Why a lap was judged invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidReason {
+    /// A tyre picked up dirt, so the car left the track.
+    OffTrack,
+    /// The lap time was far from the reference lap.
+    TimeAnomaly,
+}
+
+/// The thresholds laps are judged by.
+///
+/// * `dirt_rise`: how much any tyre's dirt level may rise over a lap before
+///   it counts as off track.
+/// * `slowest`: the slowest valid lap, as a multiple of the reference lap.
+/// * `fastest`: the fastest valid lap, as a multiple of the reference lap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidityRules {
+    pub dirt_rise: f32,
+    pub slowest: f32,
+    pub fastest: f32,
+}
+
+impl Default for ValidityRules {
+    fn default() -> Self {
+        Self {
+            dirt_rise: 0.05,
+            slowest: 1.5,
+            fastest: 0.8,
+        }
+    }
+}
+
+impl ValidityRules {
+    /// judges a single lap, e.g. one of `Trace::laps()`.
+    ///
+    /// * `lap`: the lap's samples.
+    /// * `reference`: the lap time to compare against, usually the best valid lap.
+    pub fn judge(&self, lap: &Trace, reference: Option<Duration>) -> LapValidity {
+        let mut watch = LapWatch::default();
+        for sample in lap.samples() {
+            watch.observe(self, &sample.car);
+        }
+
+        watch.finish(self, lap.duration(), reference)
+    }
+}
+
+/// What has happened so far on the lap in progress.
+///
+/// * `started_in_pit`: whether the first CarInfo of the lap was in the pits.
+/// * `entered_pit`: whether the car has been in the pits since.
+/// * `least_dirt`: each tyre's lowest dirt level this lap.
+/// * `off_track`: whether any tyre's dirt has risen past the rule.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LapWatch {
+    started_in_pit: Option<bool>,
+    entered_pit: bool,
+    least_dirt: Option<[f32; 4]>,
+    off_track: bool,
+}
+
+impl LapWatch {
+    /// * `rules`: the thresholds.
+    /// * `car`: a CarInfo from the lap in progress.
+    pub(crate) fn observe(&mut self, rules: &ValidityRules, car: &CarInfo) {
+        let started_in_pit = *self.started_in_pit.get_or_insert(car.is_in_pit);
+        self.entered_pit |= car.is_in_pit && !started_in_pit;
+
+        let least = self.least_dirt.get_or_insert(car.tyre_dirty_level);
+        for (least, &dirt) in least.iter_mut().zip(&car.tyre_dirty_level) {
+            *least = least.min(dirt);
+            self.off_track |= dirt - *least > rules.dirt_rise;
+        }
+    }
+
+    /// judges the lap and starts watching the next one.
+    ///
+    /// * `rules`: the thresholds.
+    /// * `time`: the lap's time.
+    /// * `reference`: the lap time to compare against.
+    pub(crate) fn finish(
+        &mut self,
+        rules: &ValidityRules,
+        time: Duration,
+        reference: Option<Duration>,
+    ) -> LapValidity {
+        let watch = std::mem::take(self);
+        let secs = time.as_secs_f32();
+        let anomaly = reference.is_some_and(|reference| {
+            let reference = reference.as_secs_f32();
+            secs > reference * rules.slowest || secs < reference * rules.fastest
+        });
+
+        if watch.started_in_pit == Some(true) {
+            LapValidity::OutLap
+        } else if watch.entered_pit {
+            LapValidity::InLap
+        } else if watch.off_track {
+            LapValidity::Invalid(InvalidReason::OffTrack)
+        } else if anomaly {
+            LapValidity::Invalid(InvalidReason::TimeAnomaly)
+        } else {
+            LapValidity::Valid
+        }
+    }
+}
+
+#[cfg(test)]
+mod validity_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::{
+        trace::Trace,
+        validity::{InvalidReason, LapValidity, ValidityRules},
+    };
+
+    fn lap(cars: impl IntoIterator<Item = CarInfo>) -> Trace {
+        let mut trace = Trace::new();
+        for (idx, car) in cars.into_iter().enumerate() {
+            trace.push(Duration::from_secs(idx as u64 * 30), car);
+        }
+        trace
+    }
+
+    #[test]
+    fn pit_flag_dirt_and_anomalies_mark_laps() {
+        let rules = ValidityRules::default();
+        let clean = CarInfo::default();
+        let in_pit = CarInfo {
+            is_in_pit: true,
+            ..CarInfo::default()
+        };
+        let dirty = CarInfo {
+            tyre_dirty_level: [0.0, 0.3, 0.0, 0.0],
+            ..CarInfo::default()
+        };
+        let reference = Some(Duration::from_secs(90));
+
+        let valid = lap([clean.clone(), clean.clone(), clean.clone(), clean.clone()]);
+        assert_eq!(rules.judge(&valid, reference), LapValidity::Valid);
+        assert_eq!(rules.judge(&valid, None), LapValidity::Valid);
+
+        let out = lap([in_pit.clone(), clean.clone(), clean.clone()]);
+        assert_eq!(rules.judge(&out, None), LapValidity::OutLap);
+
+        let inlap = lap([clean.clone(), clean.clone(), in_pit]);
+        assert_eq!(rules.judge(&inlap, None), LapValidity::InLap);
+
+        let off = lap([clean.clone(), dirty, clean.clone()]);
+        assert_eq!(
+            rules.judge(&off, None),
+            LapValidity::Invalid(InvalidReason::OffTrack)
+        );
+
+        let slow = lap(std::iter::repeat_n(clean, 6));
+        assert_eq!(
+            rules.judge(&slow, Some(Duration::from_secs(60))),
+            LapValidity::Invalid(InvalidReason::TimeAnomaly)
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Lining telemetry up with video of the same session, for drawing data over
+//! the footage. A sync point pairs a moment on the recording's timeline with
+//! the same moment in the video, usually a sync mark set while flashing the
+//! headlights at the camera. One point gives an offset; with two or more far
+//! apart, the camera's clock running slightly fast or slow is corrected too.
+
+use std::time::Duration;
+
+use ac_parser::Recording;
+
+/// Maps telemetry time to video time and timecode.
+///
+/// * `fps`: the video's frame rate, for timecode frames.
+/// * `points`: telemetry and video times of the same moments, in telemetry order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoSync {
+    fps: f32,
+    points: Vec<(Duration, Duration)>,
+}
+
+impl VideoSync {
+    /// * `fps`: the video's frame rate; 29.97 and the like are fine.
+    pub fn new(fps: f32) -> Self {
+        Self {
+            fps,
+            points: Vec::new(),
+        }
+    }
+
+    /// pairs a recording's sync marks with when they show in the video, in
+    /// order. Extra marks or video times are left unpaired.
+    ///
+    /// * `recording`: the recording with the sync marks.
+    /// * `video`: when each mark shows in the video.
+    /// * `fps`: the video's frame rate.
+    pub fn from_marks(recording: &Recording, video: &[Duration], fps: f32) -> Self {
+        recording
+            .sync_marks()
+            .zip(video)
+            .fold(Self::new(fps), |sync, ((at, _), &shown)| {
+                sync.align(at, shown)
+            })
+    }
+
+    /// adds a sync point.
+    ///
+    /// * `telemetry`: a time on the recording's timeline.
+    /// * `video`: the same moment in the video.
+    pub fn align(mut self, telemetry: Duration, video: Duration) -> Self {
+        let index = self.points.partition_point(|&(at, _)| at <= telemetry);
+        self.points.insert(index, (telemetry, video));
+        self
+    }
+
+    /// how many video seconds pass per telemetry second, fitted from the
+    /// first and last sync points; 1 with fewer than two.
+    pub fn rate(&self) -> f64 {
+        match (self.points.first(), self.points.last()) {
+            (Some(&(t0, v0)), Some(&(t1, v1))) if t1 > t0 => {
+                (v1.as_secs_f64() - v0.as_secs_f64()) / (t1 - t0).as_secs_f64()
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// when a telemetry time shows in the video, or `None` without sync points
+    /// or if it's before the video starts.
+    ///
+    /// * `at`: a time on the recording's timeline.
+    pub fn video_time(&self, at: Duration) -> Option<Duration> {
+        let &(t0, v0) = self.points.first()?;
+        let seconds = v0.as_secs_f64() + (at.as_secs_f64() - t0.as_secs_f64()) * self.rate();
+
+        (seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+    }
+
+    /// a telemetry time as video timecode, `HH:MM:SS:FF`, counting frames
+    /// without drop-frame, as editors do for 29.97 unless told otherwise.
+    ///
+    /// * `at`: a time on the recording's timeline.
+    pub fn timecode(&self, at: Duration) -> Option<String> {
+        let video = self.video_time(at)?;
+        let nominal = self.fps.round().max(1.0) as u64;
+        // The nudge keeps a time landing exactly on a frame from rounding to the one before.
+        let frames = (video.as_secs_f64() * f64::from(self.fps) + 1e-6).floor() as u64;
+        let seconds = frames / nominal;
+
+        Some(format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            frames % nominal
+        ))
+    }
+}
+
+#[cfg(test)]
+mod video_tests {
+    use std::time::Duration;
+
+    use ac_parser::{MarkKind, Recording};
+
+    use crate::video::VideoSync;
+
+    #[test]
+    fn sync_marks_map_telemetry_to_timecode() {
+        let mut recording = Recording::new();
+        recording.push_mark(Duration::from_secs(10), MarkKind::Sync("start".into()));
+        recording.push_mark(Duration::from_secs(610), MarkKind::Sync("end".into()));
+
+        // The camera started 4 s after the recording, and its clock runs 0.1% fast.
+        let video = [Duration::from_secs(6), Duration::from_millis(606_600)];
+        let sync = VideoSync::from_marks(&recording, &video, 30.0);
+
+        assert!((sync.rate() - 1.001).abs() < 1e-9);
+        assert_eq!(sync.video_time(Duration::from_secs(1)), None);
+        assert_eq!(
+            sync.timecode(Duration::from_secs(310)).as_deref(),
+            Some("00:05:06:09")
+        );
+        assert_eq!(VideoSync::new(30.0).timecode(Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Tables for spreadsheets, as an xlsx workbook written by `rust_xlsxwriter`:
+//! one worksheet per table, numbers as numbers so they sum and sort, and
+//! empty cells left out.
+
+use std::io::{self, Write};
+
+use rust_xlsxwriter::{Workbook, XlsxError};
+
+/// One spreadsheet cell.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cell {
+    Number(f32),
+    Text(String),
+    Empty,
+}
+
+impl From<f32> for Cell {
+    fn from(value: f32) -> Self {
+        if value.is_finite() {
+            Cell::Number(value)
+        } else {
+            Cell::Empty
+        }
+    }
+}
+
+impl From<u32> for Cell {
+    /// exact for counts and lap numbers, up to 2^24.
+    fn from(value: u32) -> Self {
+        Cell::Number(value as f32)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+/// One worksheet: its tab's name and its rows, from the top.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Sheet {
+    pub(crate) name: String,
+    pub(crate) rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    /// * `name`: the tab's name.
+    /// * `header`: the first row's titles.
+    pub(crate) fn new(name: &str, header: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            rows: vec![header.iter().map(|&title| Cell::from(title)).collect()],
+        }
+    }
+
+    pub(crate) fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+}
+
+/// writes sheets as an xlsx workbook, tabs in the order given.
+///
+/// * `writer`: where it goes.
+/// * `sheets`: the workbook's sheets.
+pub(crate) fn write_xlsx<W: Write>(mut writer: W, sheets: &[Sheet]) -> io::Result<()> {
+    let mut workbook = Workbook::new();
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&sheet.name).map_err(xlsx_error)?;
+        for (r, row) in sheet.rows.iter().enumerate() {
+            let r = u32::try_from(r).map_err(io::Error::other)?;
+            for (c, cell) in row.iter().enumerate() {
+                let c = u16::try_from(c).map_err(io::Error::other)?;
+                match cell {
+                    // Through the shortest decimal that reads back as the
+                    // same f32, so 0.1 isn't written as 0.10000000149.
+                    Cell::Number(value) => worksheet.write_number(
+                        r,
+                        c,
+                        value
+                            .to_string()
+                            .parse::<f64>()
+                            .unwrap_or(f64::from(*value)),
+                    ),
+                    Cell::Text(text) => worksheet.write_string(r, c, text),
+                    Cell::Empty => continue,
+                }
+                .map_err(xlsx_error)?;
+            }
+        }
+    }
+
+    writer.write_all(&workbook.save_to_buffer().map_err(xlsx_error)?)?;
+    writer.flush()
+}
+
+fn xlsx_error(error: XlsxError) -> io::Error {
+    match error {
+        XlsxError::IoError(error) => error,
+        error => io::Error::other(error),
+    }
+}
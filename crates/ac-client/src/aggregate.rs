@@ -0,0 +1,412 @@
+//! Several clients merged into one stream, for split-screen events or two rigs
+//! in one household. Every event is tagged with the client it came from.
+//! Clients whose servers report the same track and layout are treated as one
+//! session: their spot LapInfo describes the same cars, so repeats are dropped,
+//! and car ids are namespaced per session so they don't collide across servers.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    },
+    time::Duration,
+};
+
+use ac_parser::{Event, HandshakeResponse, Name, Operation};
+
+use crate::{
+    AcError, Client, DropPolicy, EventReceiver, HANDSHAKE_TIMEOUT,
+    delivery::{self, POLL_INTERVAL},
+};
+
+/// How far apart each session's car ids are in the merged stream.
+pub const CAR_ID_STRIDE: i32 = 1000;
+/// How many merged events are queued before new ones are dropped, by default.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// An event from one of the merged clients.
+///
+/// * `source`: which client it came from, in the order they were added.
+/// * `session`: which session that client is in.
+/// * `event`: the event, with LapInfo car ids namespaced by session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tagged {
+    pub source: usize,
+    pub session: usize,
+    pub event: Event,
+}
+
+/// One merged client.
+///
+/// * `name`: what it was added as.
+/// * `handshake`: its server's handshake response.
+/// * `session`: which session it's in; clients on the same track and layout share one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub name: String,
+    pub handshake: HandshakeResponse,
+    pub session: usize,
+}
+
+/// the id a car has in the merged stream.
+///
+/// * `session`: the session the car is in.
+/// * `car_id`: its `car_id_num` on that session's servers.
+pub fn namespaced_car_id(session: usize, car_id: i32) -> i32 {
+    session as i32 * CAR_ID_STRIDE + car_id
+}
+
+/// the session and server-side car id behind a merged car id.
+///
+/// * `car_id`: the id in the merged stream.
+pub fn split_car_id(car_id: i32) -> (usize, i32) {
+    ((car_id / CAR_ID_STRIDE) as usize, car_id % CAR_ID_STRIDE)
+}
+
+/// What the clients' receive threads share while merging.
+///
+/// * `laps`: the last lap forwarded per merged car id.
+/// * `sessions`: the track and layout of each session.
+#[derive(Debug, Default)]
+struct Merge {
+    laps: HashMap<i32, i32>,
+    sessions: Vec<(Name, Name)>,
+}
+
+/// Collects the clients to merge.
+pub struct AggregatorBuilder {
+    clients: Vec<(String, Client)>,
+    subscription: Operation,
+    capacity: usize,
+}
+
+impl Default for AggregatorBuilder {
+    fn default() -> Self {
+        Self {
+            clients: Vec::new(),
+            subscription: Operation::SubscribeSpot,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl AggregatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a client.
+    ///
+    /// * `name`: what to call it, e.g. the rig.
+    /// * `client`: the client, not yet handshaken.
+    pub fn source(mut self, name: impl Into<String>, client: Client) -> Self {
+        self.clients.push((name.into(), client));
+        self
+    }
+
+    /// what every client subscribes to; `SubscribeSpot` by default.
+    ///
+    /// * `subscription`: the subscription.
+    pub fn subscription(mut self, subscription: Operation) -> Self {
+        self.subscription = subscription;
+        self
+    }
+
+    /// how many merged events are queued before new ones are dropped.
+    ///
+    /// * `capacity`: the most events queued at once.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// handshakes and subscribes every client, then starts merging.
+    pub fn start(self) -> Result<Aggregator, AcError> {
+        let (sender, merged) = mpsc::sync_channel(self.capacity);
+        let merge = Arc::new(Mutex::new(Merge::default()));
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let mut sources = Vec::new();
+        let mut receivers = Vec::new();
+
+        for (source, (name, client)) in self.clients.into_iter().enumerate() {
+            let (_, handshake) = client.handshake_packet(HANDSHAKE_TIMEOUT)?;
+            client.send_message(self.subscription)?;
+
+            let session = lock(&merge).session(&handshake);
+            sources.push(Source {
+                name,
+                handshake,
+                session,
+            });
+
+            let (unused, receiver) =
+                delivery::channel(1, DropPolicy::DropNewest, Arc::clone(&client.stats));
+            let forward = Forward {
+                source,
+                session,
+                sender: sender.clone(),
+                merge: Arc::clone(&merge),
+                dropped: Arc::clone(&dropped),
+            };
+            let thread = client.spawn_delivery(unused, move |_, event| {
+                if let Some(event) = event {
+                    forward.send(event);
+                }
+
+                false
+            })?;
+
+            receivers.push(receiver.with_thread(thread));
+        }
+
+        Ok(Aggregator {
+            sources,
+            merged,
+            receivers,
+            dropped,
+        })
+    }
+}
+
+impl Merge {
+    /// the session a server belongs to, adding one for a new track and layout.
+    ///
+    /// * `handshake`: the server's handshake response.
+    fn session(&mut self, handshake: &HandshakeResponse) -> usize {
+        let key = (handshake.track_name, handshake.track_config);
+
+        match self.sessions.iter().position(|session| *session == key) {
+            Some(session) => session,
+            None => {
+                self.sessions.push(key);
+                self.sessions.len() - 1
+            }
+        }
+    }
+}
+
+/// Forwards one client's events into the merged stream.
+struct Forward {
+    source: usize,
+    session: usize,
+    sender: SyncSender<Tagged>,
+    merge: Arc<Mutex<Merge>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Forward {
+    /// namespaces and deduplicates an event, then queues it if there's room.
+    ///
+    /// * `event`: an event from this client.
+    fn send(&self, event: &Event) {
+        let mut event = event.clone();
+
+        match &mut event {
+            Event::LapInfo(info) => {
+                info.car_id_num = namespaced_car_id(self.session, info.car_id_num);
+
+                let mut merge = lock(&self.merge);
+                let last = merge.laps.entry(info.car_id_num).or_insert(i32::MIN);
+                if info.lap <= *last {
+                    return;
+                }
+                *last = info.lap;
+            }
+            // A repeat handshake, e.g. after a resubscribe, only matters if the
+            // server moved to a track no session is on yet.
+            Event::HandshakeResponse(handshake) => {
+                let mut merge = lock(&self.merge);
+                let known = merge.sessions.len();
+                if merge.session(handshake) < known {
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        let tagged = Tagged {
+            source: self.source,
+            session: self.session,
+            event,
+        };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(tagged) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Several clients' events merged into one tagged stream.
+///
+/// * `sources`: each client, in the order they were added.
+/// * `merged`: the merged events.
+/// * `receivers`: each client's receive thread.
+/// * `dropped`: events dropped because the merged queue was full.
+pub struct Aggregator {
+    sources: Vec<Source>,
+    merged: Receiver<Tagged>,
+    receivers: Vec<EventReceiver>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Aggregator {
+    pub fn builder() -> AggregatorBuilder {
+        AggregatorBuilder::new()
+    }
+
+    /// every merged client, in the order they were added.
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// how many distinct sessions the clients are in.
+    pub fn sessions(&self) -> usize {
+        self.sources
+            .iter()
+            .map(|source| source.session + 1)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// waits for the next merged event. Returns `None` once every receive thread has stopped.
+    pub fn recv(&self) -> Option<Tagged> {
+        loop {
+            match self.merged.recv_timeout(POLL_INTERVAL) {
+                Ok(tagged) => return Some(tagged),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+
+    /// the next merged event, if one arrives within `timeout`.
+    ///
+    /// * `timeout`: the longest to wait.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Tagged> {
+        self.merged.recv_timeout(timeout).ok()
+    }
+
+    /// takes the next merged event, if one is queued.
+    pub fn try_recv(&self) -> Option<Tagged> {
+        self.merged.try_recv().ok()
+    }
+
+    /// every merged event as it arrives, blocking until the next one.
+    pub fn events(&self) -> impl Iterator<Item = Tagged> + '_ {
+        std::iter::from_fn(|| self.recv())
+    }
+
+    /// how many events were dropped because the merged queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// stops every client, returning the first socket error that stopped one early.
+    pub fn close(self) -> Result<(), AcError> {
+        let mut first = Ok(());
+        for receiver in self.receivers {
+            let closed = receiver.close();
+            if first.is_ok() {
+                first = closed;
+            }
+        }
+
+        first
+    }
+}
+
+fn lock(merge: &Mutex<Merge>) -> std::sync::MutexGuard<'_, Merge> {
+    // A panic mid-update leaves at worst a lap forwarded twice.
+    merge
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod aggregate_tests {
+    use std::{thread, time::Duration};
+
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, LapInfo, Name, Operation};
+
+    use crate::{
+        Client,
+        aggregate::{Aggregator, split_car_id},
+        testing::MockServer,
+    };
+
+    /// a server on `track` that sends car 3 finishing lap 1, then `car_info`.
+    fn server(track: &'static str, car_info: Option<CarInfo>) -> (Client, thread::JoinHandle<()>) {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+
+        let thread = thread::spawn(move || {
+            let (_, client) = server.recv_operation().expect("no handshake");
+            let handshake = HandshakeResponse {
+                track_name: Name::from(track).expect("short name"),
+                ..HandshakeResponse::default()
+            };
+            server
+                .send_event(&Event::HandshakeResponse(handshake), client)
+                .expect("failed to send");
+
+            let (operation, _) = server.recv_operation().expect("no subscription");
+            assert!(matches!(operation, Operation::SubscribeSpot));
+
+            let lap = LapInfo {
+                car_id_num: 3,
+                lap: 1,
+                ..LapInfo::default()
+            };
+            server
+                .send_event(&Event::LapInfo(lap), client)
+                .expect("failed to send");
+            if let Some(car) = car_info {
+                server
+                    .send_event(&Event::CarInfo(car), client)
+                    .expect("failed to send");
+            }
+        });
+
+        let client = Client::new(addr, Device::default()).expect("failed to connect");
+        (client, thread)
+    }
+
+    #[test]
+    fn merges_tags_and_deduplicates_sessions() {
+        let (rig_a, a) = server("monza", Some(CarInfo::default()));
+        let (rig_b, b) = server("monza", Some(CarInfo::default()));
+        let (rig_c, c) = server("spa", None);
+
+        let aggregator = Aggregator::builder()
+            .source("rig a", rig_a)
+            .source("rig b", rig_b)
+            .source("rig c", rig_c)
+            .start()
+            .expect("failed to start");
+        assert_eq!(aggregator.sessions(), 2);
+        assert_eq!(aggregator.sources()[1].session, 0);
+        assert_eq!(aggregator.sources()[2].session, 1);
+
+        let mut laps = Vec::new();
+        let mut cars = Vec::new();
+        while let Some(tagged) = aggregator.recv_timeout(Duration::from_millis(500)) {
+            match tagged.event {
+                Event::LapInfo(info) => laps.push(split_car_id(info.car_id_num)),
+                Event::CarInfo(_) => cars.push(tagged.source),
+                _ => {}
+            }
+        }
+        for server in [a, b, c] {
+            server.join().expect("mock server panicked");
+        }
+
+        laps.sort();
+        cars.sort();
+        assert_eq!(laps, [(0, 3), (1, 3)], "rig b's lap repeats rig a's");
+        assert_eq!(cars, [0, 1]);
+        assert_eq!(aggregator.dropped(), 0);
+        aggregator.close().expect("aggregator failed");
+    }
+}
@@ -0,0 +1,210 @@
+//! Data usage per second, in and out. Mobile apps show it next to the
+//! connection status, and forwarders check it against a bandwidth budget.
+//! Every datagram the client sends or receives is counted into one-second
+//! buckets, so both the average rate and the busiest second are on hand.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A per-second rate.
+///
+/// * `average`: per second since the first packet.
+/// * `peak`: in the busiest whole or current second.
+/// * `last`: in the last whole second, what's coming in now.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub average: f64,
+    pub peak: u64,
+    pub last: u64,
+}
+
+/// A snapshot of a client's traffic.
+///
+/// * `bytes_in`, `bytes_out`: payload bytes received and sent per second.
+/// * `packets_in`, `packets_out`: datagrams received and sent per second.
+/// * `total_bytes_in`, `total_bytes_out`: payload bytes received and sent so far.
+/// * `elapsed`: how long since the first packet.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bandwidth {
+    pub bytes_in: Rate,
+    pub bytes_out: Rate,
+    pub packets_in: Rate,
+    pub packets_out: Rate,
+    pub total_bytes_in: u64,
+    pub total_bytes_out: u64,
+    pub elapsed: Duration,
+}
+
+/// Bytes and datagrams each way.
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    bytes_in: u64,
+    bytes_out: u64,
+    packets_in: u64,
+    packets_out: u64,
+}
+
+impl Counts {
+    fn max(self, other: Counts) -> Counts {
+        Counts {
+            bytes_in: self.bytes_in.max(other.bytes_in),
+            bytes_out: self.bytes_out.max(other.bytes_out),
+            packets_in: self.packets_in.max(other.packets_in),
+            packets_out: self.packets_out.max(other.packets_out),
+        }
+    }
+}
+
+/// The running totals behind `Bandwidth`.
+///
+/// * `started`: when the first packet was counted.
+/// * `second`: which second since then `current` counts.
+/// * `current`, `last`, `peak`, `total`: this second, the one before it, the
+///   busiest one before it, and everything.
+#[derive(Debug, Default)]
+struct Traffic {
+    started: Option<Instant>,
+    second: u64,
+    current: Counts,
+    last: Counts,
+    peak: Counts,
+    total: Counts,
+}
+
+/// Counts a client's traffic; shared with whatever is delivering its events.
+#[derive(Debug, Default)]
+pub(crate) struct TrafficMeter {
+    traffic: Mutex<Traffic>,
+}
+
+impl TrafficMeter {
+    /// counts datagrams received.
+    ///
+    /// * `packets`: how many.
+    /// * `bytes`: their payload bytes together.
+    pub(crate) fn received(&self, packets: u64, bytes: usize) {
+        self.count(Instant::now(), |counts| {
+            counts.packets_in += packets;
+            counts.bytes_in += bytes as u64;
+        });
+    }
+
+    /// counts a datagram sent.
+    ///
+    /// * `bytes`: its payload bytes.
+    pub(crate) fn sent(&self, bytes: usize) {
+        self.count(Instant::now(), |counts| {
+            counts.packets_out += 1;
+            counts.bytes_out += bytes as u64;
+        });
+    }
+
+    pub(crate) fn snapshot(&self) -> Bandwidth {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn count(&self, now: Instant, add: impl Fn(&mut Counts)) {
+        let mut traffic = self.lock();
+        traffic.roll(now);
+        add(&mut traffic.current);
+        add(&mut traffic.total);
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Bandwidth {
+        let mut traffic = self.lock();
+        let Some(started) = traffic.started else {
+            return Bandwidth::default();
+        };
+        traffic.roll(now);
+
+        let elapsed = now.saturating_duration_since(started);
+        let seconds = elapsed.as_secs_f64().max(1.0);
+        let (total, peak, last) = (
+            traffic.total,
+            traffic.peak.max(traffic.current),
+            traffic.last,
+        );
+        let rate = |total: u64, peak: u64, last: u64| Rate {
+            average: total as f64 / seconds,
+            peak,
+            last,
+        };
+
+        Bandwidth {
+            bytes_in: rate(total.bytes_in, peak.bytes_in, last.bytes_in),
+            bytes_out: rate(total.bytes_out, peak.bytes_out, last.bytes_out),
+            packets_in: rate(total.packets_in, peak.packets_in, last.packets_in),
+            packets_out: rate(total.packets_out, peak.packets_out, last.packets_out),
+            total_bytes_in: total.bytes_in,
+            total_bytes_out: total.bytes_out,
+            elapsed,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Traffic> {
+        // A panic mid-count leaves at worst one packet uncounted.
+        self.traffic
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Traffic {
+    /// moves `current` on to the second `now` falls in.
+    fn roll(&mut self, now: Instant) {
+        let started = *self.started.get_or_insert(now);
+        let second = now.saturating_duration_since(started).as_secs();
+
+        if second != self.second {
+            self.peak = self.peak.max(self.current);
+            // A silent second in between leaves nothing coming in.
+            self.last = if second == self.second + 1 {
+                self.current
+            } else {
+                Counts::default()
+            };
+            self.current = Counts::default();
+            self.second = second;
+        }
+    }
+}
+
+#[cfg(test)]
+mod bandwidth_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::bandwidth::TrafficMeter;
+
+    #[test]
+    fn rates_are_averaged_and_peaked_per_second() {
+        let meter = TrafficMeter::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // 3 packets of 100 bytes in the first second, 1 in the second.
+        for ms in [0, 200, 900, 1500] {
+            meter.count(at(ms), |counts| {
+                counts.packets_in += 1;
+                counts.bytes_in += 100;
+            });
+        }
+        meter.count(at(1600), |counts| {
+            counts.packets_out += 1;
+            counts.bytes_out += 8;
+        });
+
+        let bandwidth = meter.snapshot_at(at(2000));
+        assert_eq!(bandwidth.total_bytes_in, 400);
+        assert_eq!(bandwidth.bytes_in.peak, 300);
+        assert_eq!(bandwidth.packets_in.peak, 3);
+        assert_eq!(bandwidth.packets_in.last, 1);
+        assert!((bandwidth.bytes_in.average - 200.0).abs() < 1e-9);
+        assert_eq!(
+            (bandwidth.packets_out.peak, bandwidth.total_bytes_out),
+            (1, 8)
+        );
+        assert_eq!(bandwidth.elapsed, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,286 @@
+//! A black box for incidents: the last few seconds of raw packets are kept in
+//! memory, and when the car hits something, loses a lot of speed at once or
+//! goes off, that window and the seconds after it are written out as a
+//! recording to replay later. Watching for incidents only looks at CarInfo,
+//! but every packet lands in the file, so a replay sees the whole picture.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use ac_parser::{CarInfo, Event, Frame, RecordingWriter, Timestamp};
+
+use crate::AcError;
+
+/// What makes an incident, and how much around it is kept.
+///
+/// * `g_spike`: combined lateral and longitudinal G above which the car has hit something.
+/// * `speed_loss_kmh`: a drop in speed within `window` that counts as sudden.
+/// * `dirt_rise`: how much any tyre's dirt level may rise within `window` before the car counts as off track.
+/// * `window`: how far back speed and dirt are compared.
+/// * `before`, `after`: how much is written from before and after the incident.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IncidentRules {
+    pub g_spike: f32,
+    pub speed_loss_kmh: f32,
+    pub dirt_rise: f32,
+    pub window: Duration,
+    pub before: Duration,
+    pub after: Duration,
+}
+
+impl Default for IncidentRules {
+    fn default() -> Self {
+        Self {
+            g_spike: 5.0,
+            speed_loss_kmh: 80.0,
+            dirt_rise: 0.05,
+            window: Duration::from_secs(1),
+            before: Duration::from_secs(10),
+            after: Duration::from_secs(5),
+        }
+    }
+}
+
+/// What set off an incident.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IncidentKind {
+    /// Combined G past the rule, e.g. a wall; holds the G.
+    GSpike(f32),
+    /// Speed fell past the rule within the window; holds the km/h lost.
+    SpeedLoss(f32),
+    /// A tyre picked up dirt.
+    OffTrack,
+}
+
+impl IncidentKind {
+    fn name(&self) -> &'static str {
+        match self {
+            IncidentKind::GSpike(_) => "g-spike",
+            IncidentKind::SpeedLoss(_) => "speed-loss",
+            IncidentKind::OffTrack => "off-track",
+        }
+    }
+}
+
+/// An incident written to disk.
+///
+/// * `kind`: what set it off.
+/// * `at`: when, on the black box's timeline.
+/// * `path`: the recording it was written to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub at: Duration,
+    pub path: PathBuf,
+}
+
+/// Keeps the last few seconds of packets and writes them out around incidents.
+///
+/// * `dir`: where incident recordings are written.
+/// * `rules`: what makes an incident.
+/// * `started_at`: the wall-clock time arrival times are relative to.
+/// * `frames`: the packets kept, oldest first.
+/// * `recent`: each recent CarInfo's arrival, speed and dirtiest tyre, over `rules.window`.
+/// * `open`: the incident whose after-window is still being filled, if any.
+pub struct BlackBox {
+    dir: PathBuf,
+    rules: IncidentRules,
+    started_at: Timestamp,
+    frames: VecDeque<Frame>,
+    recent: VecDeque<(Duration, f32, f32)>,
+    open: Option<(IncidentKind, Duration)>,
+}
+
+impl BlackBox {
+    /// creates the incident directory if needed. Arrival times passed to
+    /// `record` are taken as relative to now.
+    ///
+    /// * `dir`: where to write incident recordings.
+    /// * `rules`: what makes an incident.
+    pub fn new<P>(dir: P, rules: IncidentRules) -> Result<Self, AcError>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            rules,
+            started_at: Timestamp::now(),
+            frames: VecDeque::new(),
+            recent: VecDeque::new(),
+            open: None,
+        })
+    }
+
+    /// keeps a packet, watching for incidents. Returns an incident once its
+    /// after-window is over and it has been written out.
+    ///
+    /// * `at`: when the packet arrived, relative to when the black box was made.
+    /// * `packet`: the raw datagram, trimmed to the bytes received.
+    pub fn record(&mut self, at: Duration, packet: &[u8]) -> Result<Option<Incident>, AcError> {
+        self.frames.push_back(Frame {
+            at,
+            packet: packet.to_vec(),
+        });
+
+        if let Ok(Event::CarInfo(car)) = Event::try_parse(packet) {
+            let incident = self.watch(at, &car);
+            if self.open.is_none() {
+                self.open = incident.map(|kind| (kind, at));
+            }
+        }
+
+        let written = match self.open {
+            Some((kind, trigger)) if at.saturating_sub(trigger) >= self.rules.after => {
+                self.open = None;
+                let path = self.write(kind, trigger)?;
+                Some(Incident {
+                    kind,
+                    at: trigger,
+                    path,
+                })
+            }
+            _ => None,
+        };
+
+        // Keep what a future incident might need, and all of an open one.
+        let since = self.open.map_or(at, |(_, trigger)| trigger);
+        let keep_from = since.saturating_sub(self.rules.before);
+        while self
+            .frames
+            .front()
+            .is_some_and(|frame| frame.at < keep_from)
+        {
+            self.frames.pop_front();
+        }
+
+        Ok(written)
+    }
+
+    /// whether a CarInfo is an incident, against the window of CarInfo before it.
+    fn watch(&mut self, at: Duration, car: &CarInfo) -> Option<IncidentKind> {
+        let dirt = car.tyre_dirty_level.iter().copied().fold(0.0, f32::max);
+        let window_start = at.saturating_sub(self.rules.window);
+        while self
+            .recent
+            .front()
+            .is_some_and(|&(seen, ..)| seen < window_start)
+        {
+            self.recent.pop_front();
+        }
+
+        let fastest = self
+            .recent
+            .iter()
+            .map(|&(_, speed, _)| speed)
+            .fold(0.0, f32::max);
+        let cleanest = self
+            .recent
+            .iter()
+            .map(|&(_, _, dirt)| dirt)
+            .fold(f32::INFINITY, f32::min);
+        self.recent.push_back((at, car.speed_kmh, dirt));
+
+        let g = car.accg_horizontal.hypot(car.accg_frontal);
+        if g > self.rules.g_spike {
+            Some(IncidentKind::GSpike(g))
+        } else if fastest - car.speed_kmh > self.rules.speed_loss_kmh {
+            Some(IncidentKind::SpeedLoss(fastest - car.speed_kmh))
+        } else if dirt - cleanest > self.rules.dirt_rise {
+            Some(IncidentKind::OffTrack)
+        } else {
+            None
+        }
+    }
+
+    /// writes the kept packets around an incident to a new recording.
+    fn write(&self, kind: IncidentKind, trigger: Duration) -> io::Result<PathBuf> {
+        let from = trigger.saturating_sub(self.rules.before);
+        let first = self
+            .frames
+            .iter()
+            .find(|frame| frame.at >= from)
+            .map_or(trigger, |frame| frame.at);
+        let start = self.started_at.offset(first);
+
+        let path = incident_path(&self.dir, start, kind);
+        let file = BufWriter::new(File::create(&path)?);
+        let mut writer = RecordingWriter::with_start(file, Some(start))?;
+        for frame in self.frames.iter().filter(|frame| frame.at >= first) {
+            writer.write_frame(frame.at - first, &frame.packet)?;
+        }
+        writer.flush()?;
+
+        Ok(path)
+    }
+}
+
+/// where an incident is written, named for when its recording starts.
+///
+/// * `dir`: the incident directory.
+/// * `start`: the wall-clock time of its first packet.
+/// * `kind`: what set it off.
+fn incident_path(dir: &Path, start: Timestamp, kind: IncidentKind) -> PathBuf {
+    let millis = start.unix_nanos() / 1_000_000;
+    dir.join(format!("incident-{millis}-{}.acrc", kind.name()))
+}
+
+#[cfg(test)]
+mod blackbox_tests {
+    use std::{fs, time::Duration};
+
+    use ac_parser::{CarInfo, Recording};
+
+    use crate::blackbox::{BlackBox, IncidentKind, IncidentRules};
+
+    #[test]
+    fn an_impact_is_written_with_the_seconds_around_it() {
+        let dir = std::env::temp_dir().join(format!("ac_lib-blackbox-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let rules = IncidentRules {
+            before: Duration::from_secs(2),
+            after: Duration::from_secs(1),
+            ..IncidentRules::default()
+        };
+        let mut black_box = BlackBox::new(&dir, rules).expect("dir created");
+
+        // 200 km/h for 5 s, into a wall at 5 s, stopped after.
+        let mut incidents = Vec::new();
+        for tick in 0..80 {
+            let at = Duration::from_millis(100 * tick);
+            let car = CarInfo {
+                speed_kmh: if tick < 50 { 200.0 } else { 0.0 },
+                accg_frontal: if tick == 50 { -12.0 } else { 0.0 },
+                ..CarInfo::default()
+            };
+            let written = black_box.record(at, &car.to_bytes()).expect("written");
+            incidents.extend(written);
+        }
+
+        let [incident] = &incidents[..] else {
+            panic!("expected one incident, got {incidents:?}");
+        };
+        assert_eq!(incident.kind, IncidentKind::GSpike(12.0));
+        assert_eq!(incident.at, Duration::from_secs(5));
+
+        // 2 s before the impact and 1 s after it, a packet every 100 ms.
+        let recording =
+            Recording::read_from(fs::File::open(&incident.path).expect("file")).expect("read");
+        let frames = recording.frames();
+        assert_eq!(frames.len(), 31);
+        assert_eq!(
+            frames.last().map(|frame| frame.at),
+            Some(Duration::from_secs(3))
+        );
+        assert!(recording.started_at().is_some());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
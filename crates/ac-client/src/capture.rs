@@ -0,0 +1,184 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::AcError;
+
+/// Number of bytes shown per hex dump line.
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes packets that failed to parse into a capture directory as hex dumps,
+/// so they can be attached to bug reports or used to extend the parser.
+///
+/// * `dir`: the directory capture files are written to.
+/// * `max_bytes`: total size budget for the directory; captures past it are dropped.
+/// * `used_bytes`: bytes already taken up by capture files in `dir`.
+pub struct PacketCapture {
+    dir: PathBuf,
+    max_bytes: u64,
+    used_bytes: u64,
+    seq: u64,
+}
+
+impl PacketCapture {
+    /// creates the capture directory if needed, counting any earlier captures
+    /// in it against the size budget.
+    ///
+    /// * `dir`: where to write capture files.
+    /// * `max_bytes`: upper bound on the total size of capture files in `dir`.
+    pub fn new<P>(dir: P, max_bytes: u64) -> Result<Self, AcError>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let used_bytes = existing_capture_bytes(&dir)?;
+
+        Ok(Self {
+            dir,
+            max_bytes,
+            used_bytes,
+            seq: 0,
+        })
+    }
+
+    /// writes a packet to a new capture file.
+    /// Returns the file path, or `None` if the size budget is used up.
+    ///
+    /// * `packet`: the raw datagram, trimmed to the bytes received.
+    /// * `context`: why the packet was captured, e.g. the parse error.
+    pub fn record(&mut self, packet: &[u8], context: &str) -> Result<Option<PathBuf>, AcError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut contents = String::new();
+        let _ = writeln!(contents, "# timestamp_ms: {}", timestamp.as_millis());
+        let _ = writeln!(contents, "# size: {}", packet.len());
+        let _ = writeln!(contents, "# context: {}", context.replace('\n', " "));
+        contents.push_str(&hex_dump(packet));
+
+        let len = contents.len() as u64;
+        if self.used_bytes + len > self.max_bytes {
+            return Ok(None);
+        }
+
+        let path = self.dir.join(format!(
+            "malformed-{}-{}.hex",
+            timestamp.as_millis(),
+            self.seq
+        ));
+        fs::write(&path, contents)?;
+
+        self.seq += 1;
+        self.used_bytes += len;
+
+        Ok(Some(path))
+    }
+}
+
+/// sums the size of the capture files already in a directory.
+///
+/// * `dir`: the capture directory.
+fn existing_capture_bytes(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.path().extension().is_some_and(|ext| ext == "hex") {
+            total += entry.metadata()?.len();
+        }
+    }
+
+    Ok(total)
+}
+
+/// formats bytes as a classic `offset  hex  |ascii|` dump.
+///
+/// * `buf`: the bytes to format.
+fn hex_dump(buf: &[u8]) -> String {
+    let mut out = String::new();
+
+    for (line, chunk) in buf.chunks(BYTES_PER_LINE).enumerate() {
+        let _ = write!(out, "{:08x} ", line * BYTES_PER_LINE);
+
+        for i in 0..BYTES_PER_LINE {
+            match chunk.get(i) {
+                Some(byte) => {
+                    let _ = write!(out, " {byte:02x}");
+                }
+                None => out.push_str("   "),
+            }
+        }
+
+        let ascii = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect::<String>();
+        let _ = writeln!(out, "  |{ascii}|");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::capture::{PacketCapture, hex_dump};
+
+    fn temp_capture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ac_lib-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn hex_dump_formats_offsets_and_ascii() {
+        let dump = hex_dump(b"AC\x00\x01");
+        assert_eq!(
+            dump,
+            "00000000  41 43 00 01                                      |AC..|\n"
+        );
+    }
+
+    #[test]
+    fn record_writes_header_and_dump() {
+        let dir = temp_capture_dir("record");
+        let mut capture = PacketCapture::new(&dir, 4096).expect("failed to create capture dir");
+
+        let path = capture
+            .record(&[0xde, 0xad], "No matching event for packet of size: 2")
+            .expect("failed to write capture")
+            .expect("capture should fit in budget");
+
+        let contents = fs::read_to_string(path).expect("failed to read capture");
+        assert!(contents.contains("# size: 2"));
+        assert!(contents.contains("# context: No matching event for packet of size: 2"));
+        assert!(contents.contains(" de ad "));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn record_stops_once_budget_is_used() {
+        let dir = temp_capture_dir("budget");
+        let mut capture = PacketCapture::new(&dir, 256).expect("failed to create capture dir");
+
+        let first = capture.record(&[0u8; 16], "first").expect("write failed");
+        let second = capture.record(&[0u8; 16], "second").expect("write failed");
+        assert!(first.is_some(), "First capture fits in the budget");
+        assert!(second.is_none(), "Second capture exceeds the budget");
+
+        // Reopening the directory counts the earlier capture against the budget.
+        let mut reopened = PacketCapture::new(&dir, 256).expect("failed to reopen capture dir");
+        let third = reopened.record(&[0u8; 16], "third").expect("write failed");
+        assert!(third.is_none(), "Existing captures still count");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,567 @@
+//! Deployment settings loaded from a TOML file, so services don't hard-code
+//! where the server is or how events are delivered:
+//!
+//! ```toml
+//! remote = "192.168.1.20:9996"
+//! local_port = 9997
+//! device = "android_tablet"
+//! subscription = "spot"
+//! downsample_hz = 30.0
+//! units = "imperial"
+//!
+//! [delivery]
+//! capacity = 512
+//! policy = "latest_only"
+//!
+//! [recording]
+//! path = "session.acrc"
+//!
+//! [capture]
+//! dir = "malformed"
+//! max_bytes = 1_000_000
+//!
+//! [[sinks]]
+//! kind = "relay"
+//! addr = "relay.example.com:9000"
+//! session = "rig-1"
+//!
+//! [[sinks]]
+//! kind = "udp"
+//! addr = "127.0.0.1:9998"
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use ac_analysis::Downsample;
+use ac_parser::{Device, Event, Operation, RecordingWriter, Timestamp, Units};
+
+use crate::{
+    AcError, Client, Exponential, SocketConfig, Uplink,
+    capture::PacketCapture,
+    delivery::{self, DropPolicy, EventReceiver},
+    stats::StatsCounters,
+};
+
+/// How long `Config::connect` waits for the server to answer each handshake attempt.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// module errors
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// If the config file can't be read
+    #[error("Failed to read config: {0}")]
+    Io(#[from] io::Error),
+
+    /// If the config isn't valid TOML or is missing required settings
+    #[error("Invalid config: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// Which updates to subscribe to after the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subscription {
+    /// CarInfo for the player's car at the physics rate.
+    #[default]
+    Update,
+    /// LapInfo for every car as laps complete.
+    Spot,
+}
+
+impl Subscription {
+    pub fn operation(self) -> Operation {
+        match self {
+            Subscription::Update => Operation::SubscribeUpdate,
+            Subscription::Spot => Operation::SubscribeSpot,
+        }
+    }
+}
+
+/// How events are queued for the consumer.
+///
+/// * `capacity`: the most events queued at once.
+/// * `policy`: what to do with new events when full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeliveryConfig {
+    pub capacity: usize,
+    pub policy: DropPolicy,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            policy: DropPolicy::default(),
+        }
+    }
+}
+
+/// Where to record every received packet.
+///
+/// * `path`: the recording file, overwritten if it exists.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingConfig {
+    pub path: PathBuf,
+}
+
+/// Where to capture packets that fail to parse.
+///
+/// * `dir`: the capture directory.
+/// * `max_bytes`: the most the captures may take up on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CaptureConfig {
+    pub dir: PathBuf,
+    #[serde(default = "CaptureConfig::default_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl CaptureConfig {
+    fn default_max_bytes() -> u64 {
+        10 * 1024 * 1024
+    }
+}
+
+/// Somewhere else every packet that parses is sent as it arrives, before
+/// downsampling, so other tools can be fed from the one client.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SinkConfig {
+    /// pushes packets up to a `Relay` for remote coaches.
+    ///
+    /// * `addr`: where the relay listens, as `host:port`.
+    /// * `session`: the name coaches subscribe to.
+    Relay { addr: String, session: String },
+    /// forwards packets unchanged to another UDP listener, e.g. an app that
+    /// would otherwise need the server to itself.
+    ///
+    /// * `addr`: where the app listens, as `host:port`.
+    Udp { addr: String },
+}
+
+impl SinkConfig {
+    fn open(&self) -> Result<Bridge, AcError> {
+        match self {
+            SinkConfig::Relay { addr, session } => {
+                Ok(Bridge::Relay(Uplink::connect(addr.as_str(), session)?))
+            }
+            SinkConfig::Udp { addr } => {
+                let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "the host resolved to no addresses")
+                })?;
+                let local: SocketAddr = match addr {
+                    SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+                    SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(addr)?;
+                Ok(Bridge::Udp(socket))
+            }
+        }
+    }
+}
+
+/// An opened `SinkConfig`.
+enum Bridge {
+    Relay(Uplink),
+    Udp(UdpSocket),
+}
+
+impl Bridge {
+    fn send(&mut self, packet: &[u8]) -> Result<(), AcError> {
+        match self {
+            Bridge::Relay(uplink) => uplink.send(packet),
+            Bridge::Udp(socket) => Ok(socket.send(packet).map(drop)?),
+        }
+    }
+}
+
+/// Everything needed to connect to a server and start receiving.
+///
+/// * `remote`: the AC server's telemetry address, as `host:port`.
+/// * `local_port`: the local port to bind, if it must be fixed; otherwise the OS picks one.
+/// * `device`: the device this client reports itself as.
+/// * `subscription`: which updates to subscribe to.
+/// * `downsample_hz`: the highest CarInfo rate to deliver, if thinned.
+/// * `delivery`: how events are queued for the consumer.
+/// * `recording`: where to record received packets, if anywhere.
+/// * `capture`: where to capture malformed packets, if anywhere.
+/// * `sinks`: where else to send packets.
+/// * `units`: the units to make the process-wide preference on connecting, if set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub remote: String,
+    pub local_port: Option<u16>,
+    #[serde(default)]
+    pub device: Device,
+    #[serde(default)]
+    pub subscription: Subscription,
+    pub downsample_hz: Option<f32>,
+    #[serde(default)]
+    pub delivery: DeliveryConfig,
+    pub recording: Option<RecordingConfig>,
+    pub capture: Option<CaptureConfig>,
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    pub units: Option<Units>,
+}
+
+impl Config {
+    /// reads a config from a TOML file.
+    ///
+    /// * `path`: the config file.
+    pub fn from_path<P>(path: P) -> Result<Self, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// connects to the configured server, handshakes, subscribes, and starts
+    /// delivering events on a receive thread, recording, forwarding to the
+    /// sinks and downsampling as configured. The handshake response is the
+    /// first event delivered. Packets the recording or a sink fails to take
+    /// are counted in `ClientStats::write_errors`; the recording stops at its
+    /// first failure, as what follows couldn't be read back.
+    pub fn connect(&self) -> Result<EventReceiver, AcError> {
+        if let Some(units) = self.units {
+            units.set_preferred();
+        }
+
+        let socket = match self.local_port {
+            Some(port) => SocketConfig::new().local_port(port),
+            None => SocketConfig::default(),
+        };
+        let mut client = Client::with_socket_config(
+            self.remote.as_str(),
+            self.device,
+            Exponential::default(),
+            socket,
+        )?;
+
+        if let Some(capture) = &self.capture {
+            client.capture_malformed(PacketCapture::new(&capture.dir, capture.max_bytes)?);
+        }
+
+        let file = match &self.recording {
+            Some(recording) => Some(BufWriter::new(File::create(&recording.path)?)),
+            None => None,
+        };
+        let mut sinks = self
+            .sinks
+            .iter()
+            .map(SinkConfig::open)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut downsample = self.downsample_hz.map(Downsample::new);
+
+        let (packet, handshake) = client.handshake_packet(HANDSHAKE_TIMEOUT)?;
+        let start = Instant::now();
+        let started_at = Timestamp::now();
+
+        let mut recording = match file {
+            Some(file) => Some(RecordingWriter::with_start(file, Some(started_at))?),
+            None => None,
+        };
+        if let Some(writer) = &mut recording {
+            writer.write_frame(Duration::ZERO, &packet)?;
+        }
+        for sink in &mut sinks {
+            sink.send(&packet)?;
+        }
+        drop(packet);
+
+        let (sender, receiver) = delivery::channel(
+            self.delivery.capacity,
+            self.delivery.policy,
+            Arc::clone(&client.stats),
+        );
+        sender.send(Event::HandshakeResponse(handshake));
+
+        client.send_message(self.subscription.operation())?;
+
+        // Timestamps have the link's queueing taken off, so laggy Wi-Fi
+        // doesn't bunch packets up in the recording or the downsampling.
+        let stats = Arc::clone(&client.stats);
+        let thread = client.spawn_delivery(sender, move |packet, event| {
+            let at = start.elapsed().saturating_sub(stats.latency());
+
+            if let Some(writer) = &mut recording
+                && writer.write_frame(at, packet).is_err()
+            {
+                StatsCounters::add(&stats.write_errors, 1);
+                recording = None;
+            }
+            if event.is_some() {
+                for sink in &mut sinks {
+                    if sink.send(packet).is_err() {
+                        StatsCounters::add(&stats.write_errors, 1);
+                    }
+                }
+            }
+
+            match (event, &mut downsample) {
+                (Some(event), Some(downsample)) => downsample.keep(at, event),
+                _ => true,
+            }
+        })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use std::{
+        fs,
+        net::UdpSocket,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, Operation, Recording, Units};
+
+    use crate::{
+        Downlink, Relay,
+        config::{Config, SinkConfig, Subscription},
+        delivery::DropPolicy,
+        testing::MockServer,
+    };
+
+    /// serves a handshake, then `cars` CarInfo once the client subscribes.
+    fn serve(server: MockServer, cars: u32) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let (operation, client) = server.recv_operation().expect("no handshake");
+            assert!(matches!(operation, Operation::Handshake));
+            let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+            server
+                .send_event(&handshake, client)
+                .expect("failed to send");
+
+            let (operation, client) = server.recv_operation().expect("no subscribe");
+            assert!(matches!(operation, Operation::SubscribeUpdate));
+            for lap_count in 0..cars {
+                let car = Event::CarInfo(CarInfo {
+                    lap_count,
+                    ..CarInfo::default()
+                });
+                server.send_event(&car, client).expect("failed to send");
+            }
+        })
+    }
+
+    /// waits until the receiver has seen `packets` packets.
+    fn wait_for(receiver: &crate::EventReceiver, packets: u64) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while receiver.stats().packets < packets && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn settings_default_when_omitted() {
+        let config: Config = r#"remote = "127.0.0.1:9996""#.parse().expect("should parse");
+
+        assert!(matches!(config.device, Device::IPhone));
+        assert_eq!(config.subscription, Subscription::Update);
+        assert_eq!(config.delivery.capacity, 256);
+        assert!(config.recording.is_none());
+        assert_eq!(config.local_port, None);
+        assert_eq!(config.units, None);
+        assert!(config.sinks.is_empty());
+
+        let config: Config = r#"
+            remote = "10.0.0.2:9996"
+            local_port = 9997
+            device = "android_tablet"
+            subscription = "spot"
+            units = "imperial"
+
+            [delivery]
+            policy = "latest_only"
+
+            [capture]
+            dir = "malformed"
+
+            [[sinks]]
+            kind = "relay"
+            addr = "relay.example.com:9000"
+            session = "rig-1"
+
+            [[sinks]]
+            kind = "udp"
+            addr = "127.0.0.1:9998"
+        "#
+        .parse()
+        .expect("should parse");
+
+        assert_eq!(config.local_port, Some(9997));
+        assert!(matches!(config.device, Device::AndroidTablet));
+        assert_eq!(config.subscription, Subscription::Spot);
+        assert_eq!(config.units, Some(Units::Imperial));
+        assert_eq!(config.delivery.policy, DropPolicy::LatestOnly);
+        assert_eq!(config.capture.map(|c| c.max_bytes), Some(10 * 1024 * 1024));
+        assert_eq!(
+            config.sinks,
+            [
+                SinkConfig::Relay {
+                    addr: "relay.example.com:9000".to_string(),
+                    session: "rig-1".to_string(),
+                },
+                SinkConfig::Udp {
+                    addr: "127.0.0.1:9998".to_string(),
+                },
+            ]
+        );
+
+        assert!("remote = 1".parse::<Config>().is_err());
+        assert!("".parse::<Config>().is_err(), "remote is required");
+        let unknown = "remote = \"127.0.0.1:9996\"\n[[sinks]]\nkind = \"mqtt\"\naddr = \"x\"\n";
+        assert!(unknown.parse::<Config>().is_err());
+        let missing = "remote = \"127.0.0.1:9996\"\n[[sinks]]\nkind = \"relay\"\naddr = \"x\"\n";
+        assert!(
+            missing.parse::<Config>().is_err(),
+            "a relay needs a session"
+        );
+    }
+
+    #[test]
+    fn connect_wires_recording_and_downsampling() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let server_addr = server.local_addr().expect("no local addr");
+        let path = std::env::temp_dir().join(format!("ac_lib-config-{}.acrc", std::process::id()));
+
+        let config_path = path.with_extension("toml");
+        fs::write(
+            &config_path,
+            format!(
+                "remote = \"{server_addr}\"\ndownsample_hz = 1.0\n[recording]\npath = {:?}\n",
+                path
+            ),
+        )
+        .expect("failed to write config");
+        let config = Config::from_path(&config_path).expect("should load");
+
+        let server_thread = serve(server, 3);
+
+        let receiver = config.connect().expect("failed to connect");
+        server_thread.join().expect("mock server panicked");
+
+        let first = receiver.recv_timeout(Duration::from_secs(5));
+        assert!(matches!(first, Some(Event::HandshakeResponse(_))));
+        let second = receiver.recv_timeout(Duration::from_secs(5));
+        assert!(matches!(second, Some(Event::CarInfo(_))));
+
+        wait_for(&receiver, 4);
+        assert!(receiver.is_empty(), "later CarInfo is downsampled away");
+        receiver.close().expect("delivery failed");
+
+        let recording = Recording::read_from(fs::File::open(&path).expect("no recording"))
+            .expect("failed to read recording");
+        assert_eq!(recording.frames().len(), 4);
+        assert!(recording.started_at().is_some(), "stamped at the handshake");
+
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(config_path);
+    }
+
+    #[test]
+    fn connect_forwards_every_packet_to_the_sinks() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let server_addr = server.local_addr().expect("no local addr");
+        let app = UdpSocket::bind("127.0.0.1:0").expect("failed to bind app");
+        app.set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("failed to set timeout");
+        let relay = Relay::bind("127.0.0.1:0").expect("failed to bind relay");
+        let relay_addr = relay.local_addr().expect("no local addr");
+        thread::spawn(move || relay.serve());
+        let mut coach = Downlink::connect(relay_addr, "rig").expect("failed to subscribe");
+        // The relay registers the coach on the connection's own thread.
+        thread::sleep(Duration::from_millis(100));
+
+        let config: Config = format!(
+            "remote = \"{server_addr}\"\ndownsample_hz = 1.0\n\
+             [[sinks]]\nkind = \"udp\"\naddr = \"{}\"\n\
+             [[sinks]]\nkind = \"relay\"\naddr = \"{relay_addr}\"\nsession = \"rig\"\n",
+            app.local_addr().expect("no local addr"),
+        )
+        .parse()
+        .expect("should parse");
+
+        let server_thread = serve(server, 3);
+        let receiver = config.connect().expect("failed to connect");
+        server_thread.join().expect("mock server panicked");
+
+        let mut buf = [0u8; 512];
+        let len = app.recv(&mut buf).expect("no handshake forwarded");
+        assert!(matches!(
+            Event::try_parse(&buf[..len]),
+            Ok(Event::HandshakeResponse(_))
+        ));
+        assert!(matches!(
+            coach.recv().expect("failed to receive"),
+            Some(Event::HandshakeResponse(_))
+        ));
+        for lap_count in 0..3 {
+            let len = app.recv(&mut buf).expect("no CarInfo forwarded");
+            let forwarded = Event::try_parse(&buf[..len]);
+            assert!(
+                matches!(forwarded, Ok(Event::CarInfo(car)) if car.lap_count == lap_count),
+                "sinks see every packet, before downsampling"
+            );
+            let relayed = coach.recv().expect("failed to receive");
+            assert!(matches!(relayed, Some(Event::CarInfo(car)) if car.lap_count == lap_count));
+        }
+
+        wait_for(&receiver, 4);
+        assert_eq!(receiver.stats().write_errors, 0);
+        receiver.close().expect("delivery failed");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_recording_writes_are_counted() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let server_addr = server.local_addr().expect("no local addr");
+        // Writes to /dev/full fail once the recording's buffer fills.
+        let config: Config =
+            format!("remote = \"{server_addr}\"\n[recording]\npath = \"/dev/full\"\n")
+                .parse()
+                .expect("should parse");
+
+        let server_thread = serve(server, 40);
+        let receiver = config.connect().expect("failed to connect");
+        server_thread.join().expect("mock server panicked");
+
+        wait_for(&receiver, 41);
+        assert_eq!(receiver.stats().packets, 41);
+        assert_eq!(
+            receiver.stats().write_errors,
+            1,
+            "the recording stops at its first failure"
+        );
+        assert_eq!(receiver.len(), 41, "delivery carries on");
+        receiver.close().expect("delivery failed");
+    }
+}
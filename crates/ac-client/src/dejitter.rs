@@ -0,0 +1,243 @@
+//! Undoing what UDP does to a stream on a busy network: duplicated datagrams
+//! and ones overtaken by the packet after them. CarInfo is held for a short
+//! window and released in lap order, by lap count then `lap_time`, so a late
+//! packet slots back in where it belongs; one identical to a packet already
+//! seen within the window is dropped. Anything later than the window is
+//! dropped too, since timing consumers would rather miss a sample than see
+//! time run backwards. LapInfo and handshake responses pass straight through.
+//!
+//! While the sim is paused CarInfo repeats unchanged, so those repeats
+//! collapse to one packet per window; nothing in them is lost.
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ac_parser::{CarInfo, Event};
+
+use crate::{
+    AcError, Client,
+    delivery::{self, DropPolicy, EventReceiver},
+};
+
+/// Reorders slightly late CarInfo and drops duplicates.
+///
+/// * `window`: how long a CarInfo is held for a late one to overtake it.
+/// * `held`: CarInfo waiting out the window with their arrival, in lap order.
+/// * `seen`: CarInfo released within the last window, for spotting duplicates.
+/// * `released`: the lap order of the last CarInfo released.
+/// * `duplicates`, `late`: what was dropped, counted.
+#[derive(Debug, Clone)]
+pub struct Dejitter {
+    window: Duration,
+    held: VecDeque<(Duration, CarInfo)>,
+    seen: VecDeque<(Duration, CarInfo)>,
+    released: Option<(u32, u32)>,
+    duplicates: u64,
+    late: u64,
+}
+
+impl Dejitter {
+    /// * `window`: how long to hold CarInfo; a few packet intervals, e.g. 30 ms
+    ///   at AC's 333 Hz or 50 ms for a server throttled to 60 Hz.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            held: VecDeque::new(),
+            seen: VecDeque::new(),
+            released: None,
+            duplicates: 0,
+            late: 0,
+        }
+    }
+
+    /// takes an event as it arrives, appending whatever is ready to `out`.
+    ///
+    /// * `at`: when it arrived.
+    /// * `event`: the event.
+    /// * `out`: events ready for delivery, in order.
+    pub fn push(&mut self, at: Duration, event: Event, out: &mut Vec<Event>) {
+        self.release(at, out);
+
+        let Event::CarInfo(car) = event else {
+            out.push(event);
+            return;
+        };
+
+        let order = lap_order(&car);
+        let repeated = |(_, other): &(Duration, CarInfo)| *other == car;
+        if self.held.iter().any(repeated) || self.seen.iter().any(repeated) {
+            self.duplicates += 1;
+        } else if self
+            .released
+            .is_some_and(|released| is_late(order, released))
+        {
+            self.late += 1;
+        } else {
+            let index = self
+                .held
+                .partition_point(|(_, held)| lap_order(held) <= order);
+            self.held.insert(index, (at, car));
+        }
+    }
+
+    /// releases the CarInfo that have waited out the window, e.g. when no
+    /// packet has arrived for a while.
+    ///
+    /// * `now`: the time, on the same clock as arrivals.
+    /// * `out`: events ready for delivery, in order.
+    pub fn release(&mut self, now: Duration, out: &mut Vec<Event>) {
+        let waited = |at: Duration| at + self.window <= now;
+        while self.seen.front().is_some_and(|&(at, _)| waited(at)) {
+            self.seen.pop_front();
+        }
+
+        // Released in lap order, so a packet that's waited its window out
+        // takes any earlier one held behind it along.
+        if let Some(last) = self.held.iter().rposition(|&(at, _)| waited(at)) {
+            for (at, car) in self.held.drain(..=last) {
+                self.released = Some(lap_order(&car));
+                self.seen.push_back((at, car.clone()));
+                out.push(Event::CarInfo(car));
+            }
+        }
+    }
+
+    /// releases everything still held, e.g. when the stream ends.
+    ///
+    /// * `out`: events ready for delivery, in order.
+    pub fn flush(&mut self, out: &mut Vec<Event>) {
+        for (_, car) in self.held.drain(..) {
+            self.released = Some(lap_order(&car));
+            out.push(Event::CarInfo(car));
+        }
+    }
+
+    /// duplicates dropped so far.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// packets dropped for arriving after the window so far.
+    pub fn late(&self) -> u64 {
+        self.late
+    }
+}
+
+/// where a CarInfo falls in the session.
+fn lap_order(car: &CarInfo) -> (u32, u32) {
+    (car.lap_count, car.lap_time)
+}
+
+/// whether a packet is a straggler behind what's been released, rather than
+/// the start of a restarted session, which goes back further.
+///
+/// * `order`, `released`: the packet's and the last released packet's lap order.
+fn is_late(order: (u32, u32), released: (u32, u32)) -> bool {
+    const RESTART_MS: u32 = 1000;
+
+    match (order, released) {
+        ((lap, time), (released_lap, released_time)) if lap == released_lap => {
+            time < released_time && released_time - time <= RESTART_MS
+        }
+        ((lap, _), (released_lap, _)) => lap + 1 == released_lap,
+    }
+}
+
+impl Client {
+    /// like `deliver`, with CarInfo passed through a `Dejitter` first, for
+    /// consumers that time things and can't take duplicates or packets out of
+    /// order. Adds up to `window` of latency.
+    ///
+    /// * `capacity`: the most events queued at once.
+    /// * `policy`: what to do with new events when full.
+    /// * `window`: how long to hold CarInfo for late packets to overtake.
+    pub fn deliver_dejittered(
+        mut self,
+        capacity: usize,
+        policy: DropPolicy,
+        window: Duration,
+    ) -> Result<EventReceiver, AcError> {
+        let (sender, receiver) = delivery::channel(capacity, policy, Arc::clone(&self.stats));
+        // Wake at least once a window so held packets go out in a lull.
+        self.set_read_timeout(Some(window.max(Duration::from_millis(1))))?;
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-dejitter".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                let mut dejitter = Dejitter::new(window);
+                let mut ready = Vec::new();
+                let mut failures = 0;
+
+                while !sender.is_closed() {
+                    let received = self.recv_next(&mut failures)?;
+                    let now = start.elapsed();
+                    match received.and_then(|received| received.event) {
+                        Some(event) => dejitter.push(now, event, &mut ready),
+                        None => dejitter.release(now, &mut ready),
+                    }
+
+                    for event in ready.drain(..) {
+                        sender.send(event);
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+#[cfg(test)]
+mod dejitter_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Event};
+
+    use crate::dejitter::Dejitter;
+
+    #[test]
+    fn late_packets_are_reordered_and_duplicates_dropped() {
+        let car = |lap_time| {
+            Event::CarInfo(CarInfo {
+                lap_time,
+                ..CarInfo::default()
+            })
+        };
+        let ms = Duration::from_millis;
+        let mut dejitter = Dejitter::new(ms(20));
+        let mut out = Vec::new();
+
+        // 10 overtakes 5, 10 is duplicated, 1 turns up long after, and then
+        // the session restarts at 0 after 5 s.
+        let arrivals = [
+            (0, 0),
+            (3, 10),
+            (4, 5),
+            (6, 10),
+            (9, 15),
+            (60, 1),
+            (61, 5000),
+            (90, 0),
+        ];
+        for (at, lap_time) in arrivals {
+            dejitter.push(ms(at), car(lap_time), &mut out);
+        }
+        dejitter.flush(&mut out);
+
+        let order: Vec<u32> = out
+            .iter()
+            .map(|event| match event {
+                Event::CarInfo(car) => car.lap_time,
+                _ => unreachable!("only CarInfo was pushed"),
+            })
+            .collect();
+        assert_eq!(order, [0, 5, 10, 15, 5000, 0]);
+        assert_eq!((dejitter.duplicates(), dejitter.late()), (1, 1));
+    }
+}
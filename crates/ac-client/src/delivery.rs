@@ -0,0 +1,525 @@
+//! A bounded event channel with a choice of what to do when the consumer stalls,
+//! and `Client::deliver`, which runs a client on its own thread feeding one.
+
+use std::{
+    collections::VecDeque,
+    io, mem,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use ac_parser::Event;
+
+use crate::{
+    AcError, Client,
+    bandwidth::Bandwidth,
+    stats::{ClientStats, StatsCounters},
+};
+
+/// How often a receive thread wakes to check whether its consumer went away.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The packet rate an adaptive channel is sized for until it's been measured.
+const ASSUMED_RATE: u64 = 120;
+/// The fewest and most events an adaptive channel queues.
+const ADAPTIVE_CAPACITY: (usize, usize) = (8, 8192);
+
+/// What a full channel does with a new event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DropPolicy {
+    /// Discards the oldest queued event to make room.
+    #[default]
+    DropOldest,
+    /// Discards the new event.
+    DropNewest,
+    /// Keeps only the latest event of each kind, replacing a queued one of the same
+    /// kind instead of queueing behind it. Holds at most one event per kind.
+    LatestOnly,
+}
+
+/// What both ends of a channel share.
+///
+/// * `state`: the queue and whether either end has gone away.
+/// * `ready`: signalled when an event is queued or the sender goes away.
+/// * `stats`: where drops and coalesces are counted.
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+    stats: Arc<StatsCounters>,
+}
+
+#[derive(Default)]
+struct State {
+    events: VecDeque<Event>,
+    sender_gone: bool,
+    receiver_gone: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic mid-push leaves at worst a dropped event behind.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// creates a bounded event channel.
+///
+/// * `capacity`: the most events queued at once; at least one.
+/// * `policy`: what to do with new events when full.
+pub fn bounded(capacity: usize, policy: DropPolicy) -> (EventSender, EventReceiver) {
+    channel(capacity, policy, Arc::default())
+}
+
+pub(crate) fn channel(
+    capacity: usize,
+    policy: DropPolicy,
+    stats: Arc<StatsCounters>,
+) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::default(),
+        ready: Condvar::new(),
+        stats,
+    });
+
+    let sender = EventSender {
+        shared: Arc::clone(&shared),
+        capacity: capacity.max(1),
+        policy,
+    };
+    let receiver = EventReceiver {
+        shared,
+        thread: None,
+    };
+
+    (sender, receiver)
+}
+
+/// The sending end of a bounded channel.
+pub struct EventSender {
+    shared: Arc<Shared>,
+    capacity: usize,
+    policy: DropPolicy,
+}
+
+impl EventSender {
+    /// queues an event, applying the channel's drop policy if it's full.
+    /// Returns false once the receiver is gone.
+    ///
+    /// * `event`: the event to deliver.
+    pub fn send(&self, event: Event) -> bool {
+        let mut state = self.shared.lock();
+        if state.receiver_gone {
+            return false;
+        }
+
+        let stats = &self.shared.stats;
+        match self.policy {
+            DropPolicy::LatestOnly => {
+                let queued = state
+                    .events
+                    .iter_mut()
+                    .find(|queued| mem::discriminant(*queued) == mem::discriminant(&event));
+
+                match queued {
+                    Some(queued) => {
+                        *queued = event;
+                        StatsCounters::add(&stats.coalesced, 1);
+                    }
+                    None => state.events.push_back(event),
+                }
+            }
+            DropPolicy::DropOldest => {
+                // More than one goes if the channel has just been shrunk.
+                while state.events.len() >= self.capacity {
+                    state.events.pop_front();
+                    StatsCounters::add(&stats.dropped, 1);
+                }
+                state.events.push_back(event);
+            }
+            DropPolicy::DropNewest => {
+                if state.events.len() >= self.capacity {
+                    StatsCounters::add(&stats.dropped, 1);
+                } else {
+                    state.events.push_back(event);
+                }
+            }
+        }
+
+        self.shared.ready.notify_one();
+        true
+    }
+
+    /// changes the most events queued at once. Events already queued past a
+    /// smaller capacity go as new ones arrive, per the drop policy.
+    ///
+    /// * `capacity`: the new capacity; at least one.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+    }
+
+    /// whether the receiver has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().receiver_gone
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.shared.lock().sender_gone = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+/// The receiving end of a bounded channel.
+///
+/// * `shared`: the queue.
+/// * `thread`: the receive thread feeding the channel, if it came from `Client::deliver`.
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Result<(), AcError>>>,
+}
+
+impl EventReceiver {
+    /// waits for the next event. Returns `None` once the sender is gone and the queue is drained.
+    pub fn recv(&self) -> Option<Event> {
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+
+            if state.sender_gone {
+                return None;
+            }
+
+            state = self
+                .shared
+                .ready
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// waits up to `timeout` for the next event.
+    ///
+    /// * `timeout`: the longest to wait.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                return Some(event);
+            }
+
+            let now = Instant::now();
+            if state.sender_gone || now >= deadline {
+                return None;
+            }
+
+            state = self
+                .shared
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    /// takes the next event, if one is queued.
+    pub fn try_recv(&self) -> Option<Event> {
+        self.shared.lock().events.pop_front()
+    }
+
+    /// how many events are queued.
+    pub fn len(&self) -> usize {
+        self.shared.lock().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the counters of the client feeding this channel, including its drops and coalesces.
+    pub fn stats(&self) -> ClientStats {
+        self.shared.stats.snapshot()
+    }
+
+    /// the feeding client's bytes and packets per second, in and out.
+    pub fn bandwidth(&self) -> Bandwidth {
+        self.shared.stats.traffic.snapshot()
+    }
+
+    /// stops the receive thread feeding this channel, if any, and waits for it,
+    /// returning the socket error that stopped it early.
+    pub fn close(mut self) -> Result<(), AcError> {
+        self.shared.lock().receiver_gone = true;
+
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| io::Error::other("delivery thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl EventReceiver {
+    /// ties a receive thread's lifetime to this receiver.
+    pub(crate) fn with_thread(mut self, thread: JoinHandle<Result<(), AcError>>) -> Self {
+        self.thread = Some(thread);
+        self
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_gone = true;
+    }
+}
+
+impl Client {
+    /// moves the client onto a dedicated receive thread that delivers events over a
+    /// bounded channel, applying `policy` whenever the consumer falls behind.
+    /// Drops and coalesces are counted in the receiver's `stats()`.
+    ///
+    /// Failures go to the client's `RecoveryHook`: by default packets that fail to
+    /// parse are skipped (and captured, if capture is enabled), transient socket
+    /// errors are retried, and anything else stops the thread.
+    ///
+    /// * `capacity`: the most events queued at once.
+    /// * `policy`: what to do with new events when full.
+    pub fn deliver(self, capacity: usize, policy: DropPolicy) -> Result<EventReceiver, AcError> {
+        let (sender, receiver) = channel(capacity, policy, Arc::clone(&self.stats));
+        let thread = self.spawn_delivery(sender, |_, _| true)?;
+
+        Ok(receiver.with_thread(thread))
+    }
+
+    /// like `deliver`, with the channel sized to hold `latency` worth of
+    /// events at the packet rate actually coming in, and resized as it
+    /// changes, so a 30 Hz phone client and a 300 Hz desktop one both get a
+    /// sensible buffer. The rate is in the receiver's `stats().packet_rate`.
+    ///
+    /// * `policy`: what to do with new events when full.
+    /// * `latency`: how far behind the consumer may fall before events are dropped.
+    pub fn deliver_adaptive(
+        mut self,
+        policy: DropPolicy,
+        latency: Duration,
+    ) -> Result<EventReceiver, AcError> {
+        let capacity = adaptive_capacity(ASSUMED_RATE, latency);
+        let (mut sender, receiver) = channel(capacity, policy, Arc::clone(&self.stats));
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-delivery".to_string())
+            .spawn(move || {
+                let mut failures = 0;
+                let mut resized = Instant::now();
+
+                while !sender.is_closed() {
+                    if resized.elapsed() >= Duration::from_secs(1) {
+                        resized = Instant::now();
+                        let rate = self.stats.traffic.snapshot().packets_in.last;
+                        if rate > 0 {
+                            sender.set_capacity(adaptive_capacity(rate, latency));
+                        }
+                    }
+
+                    let Some(received) = self.recv_next(&mut failures)? else {
+                        continue;
+                    };
+                    if let Some(event) = received.event {
+                        sender.send(event);
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+
+    /// starts the receive thread behind `deliver`.
+    ///
+    /// * `sender`: where to deliver events.
+    /// * `hook`: sees every received packet, with its event if it parsed,
+    ///   and returns whether to deliver that event.
+    pub(crate) fn spawn_delivery<F>(
+        mut self,
+        sender: EventSender,
+        mut hook: F,
+    ) -> Result<JoinHandle<Result<(), AcError>>, AcError>
+    where
+        F: FnMut(&[u8], Option<&Event>) -> bool + Send + 'static,
+    {
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        thread::Builder::new()
+            .name("ac_lib-delivery".to_string())
+            .spawn(move || {
+                let mut failures = 0;
+
+                while !sender.is_closed() {
+                    let Some(received) = self.recv_next(&mut failures)? else {
+                        continue;
+                    };
+
+                    match received.event {
+                        Some(event) => {
+                            if hook(&received.packet, Some(&event)) {
+                                sender.send(event);
+                            }
+                        }
+                        None => {
+                            hook(&received.packet, None);
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+            .map_err(AcError::from)
+    }
+}
+
+/// how many events to queue to hold `latency` worth at a packet rate.
+///
+/// * `rate`: packets per second.
+/// * `latency`: how long a consumer may fall behind.
+pub fn adaptive_capacity(rate: u64, latency: Duration) -> usize {
+    let (fewest, most) = ADAPTIVE_CAPACITY;
+    let events = (rate as f64 * latency.as_secs_f64()).ceil() as usize;
+
+    events.clamp(fewest, most)
+}
+
+/// whether a receive error is just the read timeout expiring.
+pub(crate) fn is_timeout(why: &io::Error) -> bool {
+    matches!(
+        why.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod delivery_tests {
+    use std::time::Duration;
+
+    use ac_parser::{CarInfo, Device, Event, LapInfo, Operation};
+
+    use crate::{
+        Client,
+        delivery::{DropPolicy, adaptive_capacity, bounded},
+        testing::MockServer,
+    };
+
+    fn car(gear: i32) -> Event {
+        Event::CarInfo(CarInfo {
+            gear,
+            ..CarInfo::default()
+        })
+    }
+
+    fn gears(events: impl Iterator<Item = Event>) -> Vec<i32> {
+        events
+            .filter_map(|event| match event {
+                Event::CarInfo(car) => Some(car.gear),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adaptive_channels_hold_their_latency_at_the_measured_rate() {
+        let ms = Duration::from_millis;
+        assert_eq!(adaptive_capacity(333, ms(250)), 84);
+        assert_eq!(adaptive_capacity(30, ms(100)), 8, "never below the floor");
+        assert_eq!(adaptive_capacity(333, Duration::from_secs(60)), 8192);
+
+        let (mut sender, receiver) = bounded(8, DropPolicy::DropOldest);
+        (0..6).for_each(|gear| assert!(sender.send(car(gear))));
+        sender.set_capacity(2);
+        sender.send(car(6));
+        assert_eq!(
+            gears(std::iter::from_fn(|| receiver.try_recv())),
+            vec![5, 6]
+        );
+        assert_eq!(receiver.stats().dropped, 5);
+    }
+
+    #[test]
+    fn full_channels_apply_their_policy() {
+        let (sender, receiver) = bounded(2, DropPolicy::DropOldest);
+        (0..4).for_each(|gear| assert!(sender.send(car(gear))));
+        assert_eq!(
+            gears(std::iter::from_fn(|| receiver.try_recv())),
+            vec![2, 3]
+        );
+        assert_eq!(receiver.stats().dropped, 2);
+
+        let (sender, receiver) = bounded(2, DropPolicy::DropNewest);
+        (0..4).for_each(|gear| assert!(sender.send(car(gear))));
+        assert_eq!(
+            gears(std::iter::from_fn(|| receiver.try_recv())),
+            vec![0, 1]
+        );
+        assert_eq!(receiver.stats().dropped, 2);
+
+        let (sender, receiver) = bounded(2, DropPolicy::LatestOnly);
+        sender.send(car(0));
+        sender.send(Event::LapInfo(LapInfo::default()));
+        sender.send(car(1));
+        drop(sender);
+
+        let events = std::iter::from_fn(|| receiver.recv()).collect::<Vec<_>>();
+        assert_eq!(events.len(), 2, "one event per kind");
+        assert_eq!(gears(events.into_iter()), vec![1]);
+        assert_eq!(receiver.stats().coalesced, 1);
+    }
+
+    #[test]
+    fn sending_stops_once_the_receiver_is_gone() {
+        let (sender, receiver) = bounded(1, DropPolicy::default());
+        drop(receiver);
+
+        assert!(sender.is_closed());
+        assert!(!sender.send(car(0)));
+    }
+
+    #[test]
+    fn delivered_client_counts_packets() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let receiver = client
+            .deliver(4, DropPolicy::LatestOnly)
+            .expect("failed to start delivery");
+        server
+            .send_event(&car(3), client_addr)
+            .expect("failed to send");
+
+        let event = receiver.recv_timeout(Duration::from_secs(5));
+        assert_eq!(gears(event.into_iter()), vec![3]);
+        assert_eq!(receiver.stats().packets, 1);
+        assert!(receiver.close().is_ok());
+    }
+}
@@ -0,0 +1,304 @@
+//! Working out why a client can't see the game. `Doctor::diagnose` resolves
+//! the address, sends a test handshake and times the answer, subscribes for
+//! a moment to measure the packet rate, and turns whatever went wrong into a
+//! `Diagnosis` of likely causes, for a settings screen or a tool's `doctor`
+//! command to show.
+
+use std::{
+    fmt, io,
+    net::{SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use ac_parser::{Device, HandshakeResponse, Operation};
+
+use crate::{AcError, Client, NoRetry};
+
+/// Handshakes answered slower than this point at the link, not the game.
+pub const SLOW_HANDSHAKE: Duration = Duration::from_millis(100);
+
+/// Something that would keep a client from seeing the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// The host name didn't resolve to any address.
+    Unresolved,
+    /// Nothing answered: a firewall on either machine is dropping the packets.
+    Firewall,
+    /// Nothing answered: the address isn't the machine running AC.
+    WrongAddress,
+    /// Nothing is listening on the port: AC isn't in a session, or its UDP
+    /// telemetry is on another port.
+    NotListening,
+    /// The game handshook but sent no updates: the car isn't on track, or
+    /// the game is paused.
+    NoUpdates,
+    /// The game answered, slowly: a congested or distant Wi-Fi link.
+    SlowLink,
+}
+
+impl Cause {
+    /// what to try, for showing next to the cause.
+    pub fn advice(self) -> &'static str {
+        match self {
+            Cause::Unresolved => "check the host name, or use the IP address",
+            Cause::Firewall => "allow UDP on the AC port through the firewall on both machines",
+            Cause::WrongAddress => "check the IP: it's the address of the machine running AC",
+            Cause::NotListening => "start a session in AC, and check the port (9996 by default)",
+            Cause::NoUpdates => "drive out of the pits and unpause the game",
+            Cause::SlowLink => "move closer to the access point, or use a cable",
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cause = match self {
+            Cause::Unresolved => "the host name didn't resolve",
+            Cause::Firewall => "a firewall is dropping the packets",
+            Cause::WrongAddress => "the address isn't the machine running AC",
+            Cause::NotListening => "nothing is listening on the port",
+            Cause::NoUpdates => "the game sent no updates",
+            Cause::SlowLink => "the link is slow",
+        };
+        write!(f, "{cause}")
+    }
+}
+
+/// What the doctor found.
+///
+/// * `addr`: the address checked, if the host resolved.
+/// * `handshake`: the game's answer to the test handshake, if it came.
+/// * `latency`: how long the answer took.
+/// * `packet_rate`: updates a second while subscribed, if the handshake was answered.
+/// * `causes`: what's likely wrong, most likely first; empty if nothing is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub addr: Option<SocketAddr>,
+    pub handshake: Option<HandshakeResponse>,
+    pub latency: Option<Duration>,
+    pub packet_rate: Option<f32>,
+    pub causes: Vec<Cause>,
+}
+
+impl Diagnosis {
+    /// whether the game answered and sent updates, quickly enough.
+    pub fn is_healthy(&self) -> bool {
+        self.causes.is_empty()
+    }
+
+    fn failed(addr: Option<SocketAddr>, causes: Vec<Cause>) -> Self {
+        Self {
+            addr,
+            handshake: None,
+            latency: None,
+            packet_rate: None,
+            causes,
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            Some(addr) => writeln!(f, "address: {addr}")?,
+            None => writeln!(f, "address: unresolved")?,
+        }
+        match (&self.handshake, self.latency) {
+            (Some(handshake), Some(latency)) => writeln!(
+                f,
+                "handshake: answered in {} ms by {} in the {}",
+                latency.as_millis(),
+                handshake.driver_name.as_str(),
+                handshake.car_name.as_str(),
+            )?,
+            _ => writeln!(f, "handshake: no answer")?,
+        }
+        if let Some(rate) = self.packet_rate {
+            writeln!(f, "updates: {rate:.1} a second")?;
+        }
+
+        if self.causes.is_empty() {
+            return writeln!(f, "no problems found");
+        }
+        writeln!(f, "likely causes:")?;
+        for cause in &self.causes {
+            writeln!(f, "  - {cause}: {}", cause.advice())?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the connection to a game.
+///
+/// * `timeout`: how long to wait for the handshake's answer.
+/// * `sample`: how long to subscribe for when measuring the packet rate.
+#[derive(Debug, Clone, Copy)]
+pub struct Doctor {
+    timeout: Duration,
+    sample: Duration,
+}
+
+impl Default for Doctor {
+    /// waits two seconds for the handshake and samples updates for one.
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            sample: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Doctor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// * `timeout`: how long to wait for the handshake's answer.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// * `sample`: how long to subscribe for when measuring the packet rate.
+    pub fn sample(mut self, sample: Duration) -> Self {
+        self.sample = sample;
+        self
+    }
+
+    /// checks the game at `addr`, once, without retrying anything.
+    ///
+    /// * `addr`: where the game is expected, e.g. `"192.168.1.20:9996"`.
+    pub fn diagnose<A>(&self, addr: A) -> Diagnosis
+    where
+        A: ToSocketAddrs,
+    {
+        let Some(addr) = addr
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+        else {
+            return Diagnosis::failed(None, vec![Cause::Unresolved]);
+        };
+        let Ok(client) = Client::with_retry(addr, Device::default(), NoRetry) else {
+            // A UDP connect only fails when there's no route to the address.
+            return Diagnosis::failed(Some(addr), vec![Cause::WrongAddress]);
+        };
+
+        let sent = Instant::now();
+        let handshake = match client.handshake_packet(self.timeout) {
+            Ok((_, handshake)) => handshake,
+            Err(why) => return Diagnosis::failed(Some(addr), no_answer(&why)),
+        };
+        let latency = sent.elapsed();
+
+        let packet_rate = self.packet_rate(&client).unwrap_or(0.0);
+        let mut causes = Vec::new();
+        if packet_rate == 0.0 {
+            causes.push(Cause::NoUpdates);
+        }
+        if latency > SLOW_HANDSHAKE {
+            causes.push(Cause::SlowLink);
+        }
+
+        Diagnosis {
+            addr: Some(addr),
+            handshake: Some(handshake),
+            latency: Some(latency),
+            packet_rate: Some(packet_rate),
+            causes,
+        }
+    }
+
+    /// subscribes for `sample`, counting the packets that arrive, then dismisses.
+    fn packet_rate(&self, client: &Client) -> Result<f32, AcError> {
+        client.send_message(Operation::SubscribeUpdate)?;
+        let started = Instant::now();
+        let mut packets = 0;
+
+        while let Some(remaining) = self.sample.checked_sub(started.elapsed())
+            && !remaining.is_zero()
+        {
+            client.set_read_timeout(Some(remaining))?;
+            match client.recv_packet() {
+                Ok(_) => packets += 1,
+                Err(AcError::Io(why)) if is_timeout(&why) => break,
+                Err(why) => return Err(why),
+            }
+        }
+
+        client.send_message(Operation::Dismiss)?;
+        Ok(packets as f32 / self.sample.as_secs_f32())
+    }
+}
+
+/// why a handshake might have gone unanswered, most likely first.
+fn no_answer(why: &AcError) -> Vec<Cause> {
+    let AcError::Io(why) = why else {
+        // Something answered, just not with a handshake response.
+        return vec![Cause::WrongAddress];
+    };
+
+    match why.kind() {
+        // The host sent back port unreachable; Windows reports it as a reset.
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {
+            vec![Cause::NotListening]
+        }
+        _ if is_timeout(why) => vec![Cause::Firewall, Cause::WrongAddress, Cause::NotListening],
+        _ => vec![Cause::WrongAddress],
+    }
+}
+
+fn is_timeout(why: &io::Error) -> bool {
+    matches!(
+        why.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod doctor_tests {
+    use std::{net::UdpSocket, thread, time::Duration};
+
+    use ac_parser::{CarInfo, Event, HandshakeResponse, Operation};
+
+    use crate::{
+        doctor::{Cause, Doctor},
+        testing::MockServer,
+    };
+
+    #[test]
+    fn diagnoses_a_live_game_and_a_closed_port() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+        let game = thread::spawn(move || {
+            let (_, client) = server.recv_operation().expect("no handshake");
+            let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+            server
+                .send_event(&handshake, client)
+                .expect("failed to send");
+
+            let (operation, _) = server.recv_operation().expect("no subscription");
+            assert!(matches!(operation, Operation::SubscribeUpdate));
+            for _ in 0..5 {
+                let car = Event::CarInfo(CarInfo::default());
+                server.send_event(&car, client).expect("failed to send");
+            }
+            let (operation, _) = server.recv_operation().expect("no dismiss");
+            assert!(matches!(operation, Operation::Dismiss));
+        });
+
+        let doctor = Doctor::new().sample(Duration::from_millis(250));
+        let diagnosis = doctor.diagnose(addr);
+        game.join().expect("mock game panicked");
+        assert!(diagnosis.is_healthy(), "{diagnosis}");
+        assert_eq!(diagnosis.packet_rate, Some(20.0));
+        assert!(diagnosis.handshake.is_some());
+
+        let closed = UdpSocket::bind("127.0.0.1:0").expect("bound");
+        let addr = closed.local_addr().expect("no local addr");
+        drop(closed);
+        let diagnosis = doctor.timeout(Duration::from_millis(500)).diagnose(addr);
+        assert_eq!(diagnosis.causes, [Cause::NotListening]);
+        assert!(diagnosis.to_string().contains("start a session in AC"));
+    }
+}
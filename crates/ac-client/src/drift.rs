@@ -0,0 +1,215 @@
+//! How late packets arrive, and how far the game's clock runs from ours.
+//! AC stamps every CarInfo with `lap_time`, so the gap between when a packet
+//! arrives and how much game time has passed says how long it was held up on
+//! the way. The smallest such gap each second is a packet that got straight
+//! through; a line fitted through those over the last minute gives the
+//! drift between the clocks, and how far any packet sits above the line is
+//! its latency.
+//!
+//! This is latency beyond the quickest packet seen, the queueing a laggy
+//! Wi-Fi link adds; the fixed part every packet pays can't be told apart
+//! from an offset between the clocks. Taking it off arrival times gives the
+//! time a packet would have arrived on a quiet link, which is what recording
+//! and lap deltas want.
+
+use std::{collections::VecDeque, time::Duration};
+
+use ac_parser::CarInfo;
+
+/// How many seconds of per-second minimums the drift is fitted over.
+const FIT_SECONDS: usize = 60;
+
+/// Going back further than this within a lap is a restart, not a late packet.
+const RESTART_MS: u32 = 1000;
+
+/// Estimates packet latency and clock drift from CarInfo `lap_time`.
+///
+/// * `game`: game time elapsed since the first packet, on the game's clock.
+/// * `last`: the last in-order packet's arrival, lap count and lap time.
+/// * `second`: the whole second of arrival being collected, and its lowest
+///   offset with when that packet arrived.
+/// * `minima`: the lowest offset of each earlier second, with when it arrived.
+/// * `fit`: the offset line through `minima`, as intercept and slope.
+/// * `latency`: the last packet's latency.
+#[derive(Debug, Clone, Default)]
+pub struct ClockSync {
+    game: Duration,
+    last: Option<(Duration, u32, u32)>,
+    second: Option<(u64, f64, f64)>,
+    minima: VecDeque<(f64, f64)>,
+    fit: Option<(f64, f64)>,
+    latency: Duration,
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// takes a CarInfo as it arrives, returning its latency.
+    ///
+    /// * `at`: when it arrived, on our clock.
+    /// * `car`: the packet.
+    pub fn observe(&mut self, at: Duration, car: &CarInfo) -> Duration {
+        let Some(game) = self.advance(at, car) else {
+            *self = Self::default();
+            self.last = Some((at, car.lap_count, car.lap_time));
+            return Duration::ZERO;
+        };
+
+        let at_secs = at.as_secs_f64();
+        let offset = at_secs - game.as_secs_f64();
+        self.collect(at.as_secs(), at_secs, offset);
+
+        let lowest = match (self.fit, self.second) {
+            (Some((intercept, slope)), _) => intercept + slope * at_secs,
+            (None, Some((_, lowest, _))) => lowest,
+            (None, None) => offset,
+        };
+        self.latency = Duration::from_secs_f64((offset - lowest).max(0.0));
+        self.latency
+    }
+
+    /// the last packet's latency.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// how much faster our clock runs than the game's, in parts per million;
+    /// negative if it's slower. Zero until a couple of seconds are in.
+    pub fn drift_ppm(&self) -> f64 {
+        self.fit.map_or(0.0, |(_, slope)| slope * 1e6)
+    }
+
+    /// an arrival time with the last packet's latency taken off.
+    ///
+    /// * `at`: when it arrived.
+    pub fn corrected(&self, at: Duration) -> Duration {
+        at.saturating_sub(self.latency)
+    }
+
+    /// the game time `car` was sent at, or `None` if the session restarted.
+    fn advance(&mut self, at: Duration, car: &CarInfo) -> Option<Duration> {
+        let (last_at, lap, time) = self.last?;
+        let ms = |ms: u32| Duration::from_millis(ms.into());
+
+        if car.lap_count == lap && car.lap_time < time {
+            // Overtaken on the way: it belongs back in the past, and doesn't move the clock.
+            return (time - car.lap_time <= RESTART_MS)
+                .then(|| self.game.saturating_sub(ms(time - car.lap_time)));
+        }
+
+        if car.lap_count == lap && car.lap_time == time {
+            // Paused, or a repeat: game time is taken to keep up with ours.
+            self.game += at.saturating_sub(last_at);
+        } else if car.lap_count == lap {
+            self.game += ms(car.lap_time - time);
+        } else if car.lap_count == lap + 1 {
+            self.game += ms(car.last_lap.saturating_sub(time) + car.lap_time);
+        } else {
+            return None;
+        }
+
+        self.last = Some((at, car.lap_count, car.lap_time));
+        Some(self.game)
+    }
+
+    /// keeps the lowest offset of each second, refitting the line when one ends.
+    fn collect(&mut self, second: u64, at: f64, offset: f64) {
+        match &mut self.second {
+            Some((current, lowest, lowest_at)) if *current == second => {
+                if offset < *lowest {
+                    (*lowest, *lowest_at) = (offset, at);
+                }
+                return;
+            }
+            Some((_, lowest, lowest_at)) => {
+                self.minima.push_back((*lowest_at, *lowest));
+                if self.minima.len() > FIT_SECONDS {
+                    self.minima.pop_front();
+                }
+                self.fit = fit(&self.minima);
+            }
+            None => {}
+        }
+        self.second = Some((second, offset, at));
+    }
+}
+
+/// the least-squares line through `points`, if there are at least two.
+fn fit(points: &VecDeque<(f64, f64)>) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(c, v), (x, y)| {
+        (c + (x - mean_x) * (y - mean_y), v + (x - mean_x).powi(2))
+    });
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+
+    Some((mean_y - slope * mean_x, slope))
+}
+
+#[cfg(test)]
+mod drift_tests {
+    use std::time::Duration;
+
+    use ac_parser::CarInfo;
+
+    use crate::drift::ClockSync;
+
+    #[test]
+    fn queueing_and_drift_are_measured_against_lap_time() {
+        let mut clock = ClockSync::new();
+        let (mut lap_count, mut last_lap) = (0, 0);
+        let mut jittered = Duration::ZERO;
+
+        // 30 s of packets every 10 ms of game time, over 10 s laps, our
+        // clock 50 ppm fast, every 100th packet held up 20 ms.
+        for packet in 0..3000u64 {
+            let game = packet * 10;
+            if game / 10_000 > lap_count as u64 {
+                (lap_count, last_lap) = (lap_count + 1, 10_000);
+            }
+            let car = CarInfo {
+                lap_count,
+                lap_time: (game % 10_000) as u32,
+                last_lap,
+                ..CarInfo::default()
+            };
+
+            let mut at =
+                Duration::from_millis(game).mul_f64(1.0 + 50e-6) + Duration::from_millis(5);
+            if packet % 100 == 50 {
+                at += Duration::from_millis(20);
+            }
+            let latency = clock.observe(at, &car);
+            if packet == 2950 {
+                jittered = latency;
+                assert_eq!(clock.corrected(at), at - latency);
+            } else if packet > 300 && packet % 100 != 50 {
+                assert!(
+                    latency < Duration::from_millis(1),
+                    "{latency:?} at {packet}"
+                );
+            }
+        }
+
+        assert!(
+            (jittered.as_secs_f64() - 0.020).abs() < 0.001,
+            "{jittered:?}"
+        );
+        assert!(
+            (clock.drift_ppm() - 50.0).abs() < 5.0,
+            "{}",
+            clock.drift_ppm()
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! The error the client's public API returns. Callers match on it to tell a
+//! socket that closed from a packet that didn't parse, without downcasting.
+
+use std::io;
+
+use ac_parser::ParserError;
+use thiserror::Error;
+
+/// Why a client call failed.
+#[derive(Debug, Error)]
+pub enum AcError {
+    /// The socket failed: closed, timed out, refused or unreachable.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A packet's size doesn't match any known event.
+    #[error("No matching event for packet of size: {0}")]
+    UnknownPacketSize(usize),
+
+    /// A field would be read past the end of the packet.
+    #[error("Buffer too short: needed {needed} bytes at offset {offset}")]
+    Truncated { offset: usize, needed: usize },
+
+    /// A packet was the right size, but a field in it didn't convert.
+    #[error("Invalid field: {0}")]
+    InvalidField(ParserError),
+}
+
+impl AcError {
+    /// whether the server sent something that isn't a valid packet, rather
+    /// than the socket failing.
+    pub fn is_malformed_packet(&self) -> bool {
+        !matches!(self, AcError::Io(_))
+    }
+}
+
+impl From<ParserError> for AcError {
+    fn from(why: ParserError) -> Self {
+        match why {
+            ParserError::UnknownPacketSize(size) => AcError::UnknownPacketSize(size),
+            ParserError::Truncated { offset, needed } => AcError::Truncated { offset, needed },
+            other => AcError::InvalidField(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use std::io;
+
+    use ac_parser::{Event, ParserError};
+
+    use crate::error::AcError;
+
+    #[test]
+    fn parse_failures_keep_their_kind() {
+        let unknown = AcError::from(Event::try_parse(&[0; 13]).unwrap_err());
+        assert!(matches!(unknown, AcError::UnknownPacketSize(13)));
+        assert!(unknown.is_malformed_packet());
+
+        let field = AcError::from(ParserError::CharConversionFailed("empty".to_string()));
+        assert!(matches!(field, AcError::InvalidField(_)));
+
+        let closed = AcError::from(io::Error::from(io::ErrorKind::NotConnected));
+        assert!(!closed.is_malformed_packet());
+        assert!(matches!(closed, AcError::Io(why) if why.kind() == io::ErrorKind::NotConnected));
+    }
+}
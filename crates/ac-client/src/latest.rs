@@ -0,0 +1,211 @@
+//! The current values, for render loops. A GUI drawing at 60 fps doesn't want
+//! every CarInfo, only the newest one when it draws, and it can't block on a
+//! channel to get it. `Client::latest` pushes into a `tokio::sync::watch`
+//! that always holds the most recent CarInfo, connection state and lap
+//! summary: reading it is synchronous and consumes nothing, and async code
+//! can still await changes through `LatestState::watch`.
+
+use std::{sync::Arc, time::Duration};
+
+use ac_parser::{CarInfo, Operation};
+use tokio::sync::watch;
+
+use crate::{
+    AcError, Client, ConnectionState,
+    push::{Callbacks, PushHandle},
+    stats::ClientStats,
+};
+
+/// Where the player is in the session, from the last CarInfo.
+///
+/// * `lap`: laps completed.
+/// * `current`: time into the current lap.
+/// * `last`: the last lap's time, if one's been completed.
+/// * `best`: the best lap's time, if one's been completed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LapSummary {
+    pub lap: u32,
+    pub current: Duration,
+    pub last: Option<Duration>,
+    pub best: Option<Duration>,
+}
+
+impl LapSummary {
+    /// * `car`: the CarInfo to summarise.
+    pub fn of(car: &CarInfo) -> Self {
+        let time = |ms: u32| (ms > 0).then(|| Duration::from_millis(ms.into()));
+
+        Self {
+            lap: car.lap_count,
+            current: Duration::from_millis(car.lap_time.into()),
+            last: time(car.last_lap),
+            best: time(car.best_lap),
+        }
+    }
+}
+
+/// The most recent of everything.
+///
+/// * `car`: the last CarInfo, if any has arrived.
+/// * `connection`: the connection state.
+/// * `laps`: the lap summary of the last CarInfo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Latest {
+    pub car: Option<CarInfo>,
+    pub connection: ConnectionState,
+    pub laps: LapSummary,
+}
+
+impl Default for Latest {
+    fn default() -> Self {
+        Self {
+            car: None,
+            connection: ConnectionState::Connecting,
+            laps: LapSummary::default(),
+        }
+    }
+}
+
+/// A handle on a client's latest values, kept current by its receive thread.
+///
+/// * `receiver`: the watch the receive thread writes to.
+/// * `handle`: the pushing client.
+pub struct LatestState {
+    receiver: watch::Receiver<Latest>,
+    handle: PushHandle,
+}
+
+impl LatestState {
+    /// a copy of everything as it is now.
+    pub fn get(&self) -> Latest {
+        self.receiver.borrow().clone()
+    }
+
+    /// the last CarInfo, if any has arrived.
+    pub fn car(&self) -> Option<CarInfo> {
+        self.receiver.borrow().car.clone()
+    }
+
+    /// the connection state.
+    pub fn connection(&self) -> ConnectionState {
+        self.receiver.borrow().connection
+    }
+
+    /// the lap summary of the last CarInfo.
+    pub fn laps(&self) -> LapSummary {
+        self.receiver.borrow().laps
+    }
+
+    /// another receiver on the watch, for async code to await changes with.
+    pub fn watch(&self) -> watch::Receiver<Latest> {
+        self.receiver.clone()
+    }
+
+    /// the counters of the client.
+    pub fn stats(&self) -> ClientStats {
+        self.handle.stats()
+    }
+
+    /// stops the receive thread and waits for it, returning the socket error
+    /// that stopped it early, if any.
+    pub fn stop(self) -> Result<(), AcError> {
+        self.handle.stop()
+    }
+}
+
+impl Client {
+    /// pushes on an internal receive thread, as `push` does, keeping a
+    /// `LatestState` current instead of calling back.
+    ///
+    /// * `subscription`: what to subscribe to.
+    pub fn latest(self, subscription: Operation) -> Result<LatestState, AcError> {
+        let (sender, receiver) = watch::channel(Latest::default());
+        let sender = Arc::new(sender);
+
+        let (cars, states) = (Arc::clone(&sender), sender);
+        let callbacks = Callbacks::new()
+            .on_car_info(move |car| {
+                cars.send_modify(|latest| {
+                    latest.laps = LapSummary::of(car);
+                    latest.car = Some(car.clone());
+                });
+            })
+            .on_connection_state(move |state| {
+                states.send_modify(|latest| latest.connection = state);
+            });
+
+        Ok(LatestState {
+            receiver,
+            handle: self.push(subscription, callbacks)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod latest_tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, Operation};
+
+    use crate::{Client, ConnectionState, latest::LapSummary, testing::MockServer};
+
+    #[test]
+    fn the_latest_values_are_read_without_consuming() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+
+        let state = client
+            .latest(Operation::SubscribeUpdate)
+            .expect("failed to start");
+        let (_, client_addr) = server.recv_operation().expect("no handshake");
+        server.recv_operation().expect("no subscription");
+        assert!(state.car().is_none());
+
+        server
+            .send_event(
+                &Event::HandshakeResponse(HandshakeResponse::default()),
+                client_addr,
+            )
+            .expect("failed to send");
+        for lap_time in [100, 200, 300] {
+            let car = Event::CarInfo(CarInfo {
+                lap_count: 2,
+                lap_time,
+                last_lap: 95_000,
+                ..CarInfo::default()
+            });
+            server
+                .send_event(&car, client_addr)
+                .expect("failed to send");
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.laps().current != Duration::from_millis(300) {
+            assert!(Instant::now() < deadline, "the last CarInfo never arrived");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(state.connection(), ConnectionState::Connected);
+        assert_eq!(
+            state.laps(),
+            LapSummary {
+                lap: 2,
+                current: Duration::from_millis(300),
+                last: Some(Duration::from_secs(95)),
+                best: None,
+            }
+        );
+        assert_eq!(state.get(), state.get(), "reading doesn't consume");
+
+        let watch = state.watch();
+        state.stop().expect("push failed");
+        assert_eq!(watch.borrow().connection, ConnectionState::Disconnected);
+    }
+}
@@ -0,0 +1,668 @@
+//! The UDP client for the Assetto Corsa telemetry server, and the delivery,
+//! recovery and session machinery built on it.
+
+mod aggregate;
+mod bandwidth;
+mod blackbox;
+mod capture;
+#[cfg(feature = "config")]
+mod config;
+mod dejitter;
+mod delivery;
+mod doctor;
+mod drift;
+mod error;
+mod latest;
+mod lifecycle;
+mod peer;
+mod pool;
+mod power;
+mod push;
+mod recovery;
+#[cfg(all(target_os = "linux", feature = "recvmmsg"))]
+mod recvmmsg;
+mod rehandshake;
+mod relay;
+mod resolve;
+mod retry;
+mod roaming;
+mod session;
+#[cfg(feature = "shared-memory")]
+mod shared_memory;
+#[cfg(feature = "spsc")]
+mod spsc;
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+mod subscriptions;
+mod summaries;
+mod supervisor;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod workers;
+
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ac_parser::{
+    Capabilities, ConformanceChecker, ConformanceReport, Device, Event, Handshake,
+    HandshakeResponse, Operation, ParserError,
+};
+use bytes::{BufMut, BytesMut};
+use pool::{BufferPool, MAX_PACKET_LEN};
+use rehandshake::Watchdog;
+use roaming::NetworkHook;
+use stats::StatsCounters;
+use subscriptions::Subscriptions;
+
+pub use aggregate::{
+    Aggregator, AggregatorBuilder, CAR_ID_STRIDE, Source, Tagged, namespaced_car_id, split_car_id,
+};
+pub use bandwidth::{Bandwidth, Rate};
+pub use blackbox::{BlackBox, Incident, IncidentKind, IncidentRules};
+pub use capture::PacketCapture;
+#[cfg(feature = "config")]
+pub use config::{
+    CaptureConfig, Config, ConfigError, DeliveryConfig, RecordingConfig, SinkConfig, Subscription,
+};
+pub use dejitter::Dejitter;
+pub use delivery::{DropPolicy, EventReceiver, EventSender, adaptive_capacity, bounded};
+pub use doctor::{Cause, Diagnosis, Doctor, SLOW_HANDSHAKE};
+pub use drift::ClockSync;
+pub use error::AcError;
+pub use latest::{LapSummary, Latest, LatestState};
+pub use lifecycle::SocketConfig;
+pub use peer::{MAX_SIGNAL_LEN, PeerChannel, Signal, Signaling};
+pub use pool::Packet;
+pub use power::LowPower;
+pub use push::{Callbacks, ConnectionState, PushHandle};
+pub use recovery::{DefaultRecovery, Failure, FailureKind, Recovery, RecoveryHook};
+pub use relay::{Downlink, KEYFRAME_INTERVAL, Relay, Uplink};
+pub use resolve::PROBE_TIMEOUT;
+pub use retry::{Exponential, Fixed, NoRetry, RetryPolicy};
+pub use roaming::NetworkChanged;
+pub use session::TelemetrySession;
+#[cfg(feature = "shared-memory")]
+pub use shared_memory::{
+    Flag, GameStatus, Graphics, Physics, SessionType, SharedFrame, SharedMemory, StaticInfo,
+};
+#[cfg(feature = "spsc")]
+pub use spsc::SpscReceiver;
+pub use stats::ClientStats;
+#[cfg(feature = "stream")]
+pub use stream::EventStream;
+pub use summaries::SummaryReceiver;
+pub use supervisor::{
+    ComponentHealth, ComponentState, STABLE_AFTER, Sink, Supervisor, SupervisorBuilder,
+};
+pub use workers::{ParseWorkers, Parsed};
+
+/// How long a handshake waits for the server's answer before it's retried.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A Client connects to the remote Assetto Corsa UDP server,
+/// allowing the user to receive UDP telemetry updates about the current session.
+///
+/// * `device`: what kind of device is this client running on
+/// * `socket`: the socket for the client to run on.
+/// * `remote`: the server's address, for reconnecting a recreated socket.
+/// * `socket_config`: how the socket is bound, for recreating it.
+/// * `suspended`: whether the client has been suspended and not yet resumed.
+/// * `capture`: where packets that fail to parse are written, if enabled.
+/// * `conformance`: checks every packet received against the spec, if enabled.
+/// * `capabilities`: what the server supports, from its last handshake response.
+/// * `pool`: receive buffers reused across packets.
+/// * `stats`: packet, parse failure and drop counters.
+/// * `recovery`: decides how receive threads recover from failures.
+/// * `network_hook`: told whenever the socket is rebound after a network change.
+/// * `watchdog`: probes and resubscribes to a server gone silent, if enabled.
+/// * `subscription`: the subscriptions requested since the last dismiss, to resubscribe to.
+/// * `retry`: when to retry failed handshakes and resubscribes.
+/// * `created`: when the client was made, the clock packet arrivals are timed on.
+/// * `clock`: latency and drift estimated from CarInfo as it's parsed.
+pub struct Client {
+    device: Device,
+    socket: UdpSocket,
+    remote: SocketAddr,
+    socket_config: SocketConfig,
+    suspended: bool,
+    capture: Option<Mutex<PacketCapture>>,
+    conformance: Option<Mutex<ConformanceChecker>>,
+    capabilities: Mutex<Option<Capabilities>>,
+    pool: BufferPool,
+    stats: Arc<StatsCounters>,
+    recovery: Mutex<Box<dyn RecoveryHook>>,
+    network_hook: Mutex<Option<NetworkHook>>,
+    watchdog: Option<Watchdog>,
+    subscription: Mutex<Subscriptions>,
+    retry: Box<dyn RetryPolicy>,
+    created: Instant,
+    clock: Mutex<ClockSync>,
+}
+
+impl Client {
+    /// creates a new Assetto Corsa UDP Client, retrying resolution and the
+    /// connection with exponential backoff. A host that resolves to several
+    /// addresses is probed for the first that answers a handshake.
+    ///
+    /// * `remote_addr`:  the addr the ACServer is running on
+    /// * `device`:  the device this client is running on
+    pub fn new<A>(remote_addr: A, device: Device) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::with_retry(remote_addr, device, Exponential::default())
+    }
+
+    /// creates a new Assetto Corsa UDP Client that retries connecting,
+    /// handshaking and resubscribing according to `retry`.
+    ///
+    /// * `remote_addr`:  the addr the ACServer is running on
+    /// * `device`:  the device this client is running on
+    /// * `retry`: when to retry failed connects, handshakes and resubscribes.
+    pub fn with_retry<A, R>(remote_addr: A, device: Device, retry: R) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+        R: RetryPolicy + 'static,
+    {
+        Self::with_socket_config(remote_addr, device, retry, SocketConfig::default())
+    }
+
+    /// creates a new Assetto Corsa UDP Client with its socket bound per `socket`,
+    /// e.g. to a fixed local port for mobile networks that need one.
+    ///
+    /// * `remote_addr`:  the addr the ACServer is running on
+    /// * `device`:  the device this client is running on
+    /// * `retry`: when to retry failed connects, handshakes and resubscribes.
+    /// * `socket_config`: how to bind the local socket.
+    pub fn with_socket_config<A, R>(
+        remote_addr: A,
+        device: Device,
+        retry: R,
+        socket_config: SocketConfig,
+    ) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+        R: RetryPolicy + 'static,
+    {
+        // NOTE : (3/22/2025) by default the port needs to be chosen by the OS, or else it
+        // will never pick up. Mobile wrappers can fix it with `SocketConfig::local_port`.
+        let socket = socket_config.bind()?;
+
+        let candidates = resolve::resolve(&remote_addr, &retry)?;
+        let remote = retry::retry(&retry, "connecting", || {
+            resolve::connect_any(&socket, &candidates)
+        })?;
+
+        let mut client = Self {
+            socket,
+            remote,
+            socket_config,
+            suspended: false,
+            device,
+            capture: None,
+            conformance: None,
+            capabilities: Mutex::new(None),
+            pool: BufferPool::new(),
+            stats: Arc::default(),
+            recovery: Mutex::new(Box::new(DefaultRecovery::default())),
+            network_hook: Mutex::new(None),
+            watchdog: None,
+            subscription: Mutex::default(),
+            retry: Box::new(retry),
+            created: Instant::now(),
+            clock: Mutex::default(),
+        };
+        if candidates.len() > 1 {
+            client.probe_candidates(&candidates)?;
+        }
+
+        Ok(client)
+    }
+
+    /// replaces when handshakes and resubscribes are retried.
+    ///
+    /// * `retry`: the new policy.
+    pub fn set_retry_policy<R>(&mut self, retry: R)
+    where
+        R: RetryPolicy + 'static,
+    {
+        self.retry = Box::new(retry);
+    }
+
+    /// writes every packet that fails to parse to the given capture, so it can be
+    /// attached to a bug report.
+    ///
+    /// * `capture`: the capture directory to write malformed packets to.
+    pub fn capture_malformed(&mut self, capture: PacketCapture) {
+        self.capture = Some(Mutex::new(capture));
+    }
+
+    /// checks every packet received against the documented layout in strict
+    /// mode, collecting each deviation as a warning; packets are delivered as
+    /// usual. For mapping what an unfamiliar server variant sends.
+    pub fn check_conformance(&mut self) {
+        self.conformance = Some(Mutex::new(ConformanceChecker::strict()));
+    }
+
+    /// the warnings collected since `check_conformance`, or `None` if it's off.
+    pub fn conformance_report(&self) -> Option<ConformanceReport> {
+        let checker = self.conformance.as_ref()?.lock().ok()?;
+        Some(checker.report().clone())
+    }
+
+    /// the client's counters so far.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// bytes and packets per second in and out, averaged and at their peak.
+    pub fn bandwidth(&self) -> Bandwidth {
+        self.stats.traffic.snapshot()
+    }
+
+    /// sets how long receiving blocks before erroring. `None` blocks forever.
+    ///
+    /// * `timeout`: the longest to wait for a packet.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), AcError> {
+        Ok(self.socket.set_read_timeout(timeout)?)
+    }
+
+    /// sends a message to the udp server.
+    ///
+    /// * `operation`: kind of op we want the udp server to update on.
+    pub fn send_message(&self, operation: Operation) -> Result<usize, AcError> {
+        let msg = self.build_udp_message(operation);
+        let sent = self.socket.send(&msg)?;
+        self.stats.traffic.sent(sent);
+
+        if let Ok(mut subscriptions) = self.subscription.lock() {
+            subscriptions.sent(operation);
+        }
+
+        Ok(sent)
+    }
+
+    /// sends a handshake and waits for the server's answer, resending per the
+    /// client's retry policy if none comes.
+    pub fn handshake(&self) -> Result<HandshakeResponse, AcError> {
+        Ok(self.handshake_packet(HANDSHAKE_TIMEOUT)?.1)
+    }
+
+    /// handshakes, returning the raw answer alongside the parsed one.
+    /// Other packets arriving in the meantime, say from an earlier subscription, are skipped.
+    ///
+    /// * `timeout`: how long each attempt waits for the answer.
+    pub(crate) fn handshake_packet(
+        &self,
+        timeout: Duration,
+    ) -> Result<(Packet, HandshakeResponse), AcError> {
+        let read_timeout = self.socket.read_timeout()?;
+        self.socket.set_read_timeout(Some(timeout))?;
+
+        let answer = retry::retry(&*self.retry, "handshaking", || {
+            self.send_message(Operation::Handshake)?;
+            let deadline = Instant::now() + timeout;
+
+            while Instant::now() < deadline {
+                let packet = self.recv_packet()?;
+                if let Ok(Event::HandshakeResponse(response)) = Event::try_parse(&packet) {
+                    if let Ok(mut capabilities) = self.capabilities.lock() {
+                        *capabilities = Some(response.capabilities());
+                    }
+                    return Ok((packet, response));
+                }
+            }
+
+            Err(AcError::from(io::Error::from(io::ErrorKind::TimedOut)))
+        });
+
+        self.socket.set_read_timeout(read_timeout)?;
+        answer
+    }
+
+    /// handshakes again and repeats every subscription since the last dismiss,
+    /// retrying failed sends per the client's retry policy.
+    /// The server answers the handshake with a fresh `HandshakeResponse`.
+    pub fn resubscribe(&self) -> Result<(), AcError> {
+        let subscriptions = self.subscriptions_now();
+
+        retry::retry(&*self.retry, "resubscribing", || {
+            self.send_message(Operation::Handshake)?;
+            self.repeat_subscriptions(subscriptions)
+        })
+    }
+
+    /// receives the next event on the server.
+    pub fn recv_raw_event_buffer(&self) -> Result<(Event, [u8; MAX_PACKET_LEN]), AcError> {
+        // NOTE: The buffer we write to must be large enough, or else we may not get enough data.
+        let mut buf = [0u8; MAX_PACKET_LEN];
+        let read_size = self.socket.recv(&mut buf)?;
+        StatsCounters::add(&self.stats.packets, 1);
+        self.stats.traffic.received(1, read_size);
+
+        let ac_event = self.parse_packet(&buf[..read_size])?;
+
+        Ok((ac_event, buf))
+    }
+
+    /// receives the next event on the server, reading into a pooled buffer so
+    /// steady-state receiving doesn't allocate.
+    pub fn recv_event(&self) -> Result<Event, AcError> {
+        let packet = self.recv_packet()?;
+        self.parse_packet(&packet)
+    }
+
+    /// receives the next raw datagram into a pooled buffer. The buffer returns to the
+    /// client's pool when the packet is dropped.
+    pub fn recv_packet(&self) -> Result<Packet, AcError> {
+        let mut buf = self.pool.take();
+        let read_size = self.socket.recv(&mut buf)?;
+        StatsCounters::add(&self.stats.packets, 1);
+        self.stats.traffic.received(1, read_size);
+
+        Ok(self.pool.packet(buf, read_size))
+    }
+
+    /// receives every datagram already waiting (up to `max`), blocking until at least one arrives.
+    /// Returns how many packets were appended to `out`.
+    ///
+    /// With the `recvmmsg` feature on Linux this is a single syscall per batch;
+    /// elsewhere it receives one packet per call.
+    ///
+    /// * `out`: received packets are appended here; reuse it across calls.
+    /// * `max`: the most packets to receive in one call.
+    pub fn recv_packets(&self, out: &mut Vec<Packet>, max: usize) -> Result<usize, AcError> {
+        #[cfg(all(target_os = "linux", feature = "recvmmsg"))]
+        {
+            let received = recvmmsg::recv_batch(&self.socket, &self.pool, out, max)?;
+            StatsCounters::add(&self.stats.packets, received as u64);
+            let bytes = out[out.len() - received..].iter().map(|p| p.len()).sum();
+            self.stats.traffic.received(received as u64, bytes);
+            Ok(received)
+        }
+
+        #[cfg(not(all(target_os = "linux", feature = "recvmmsg")))]
+        {
+            let _ = max;
+            out.push(self.recv_packet()?);
+            Ok(1)
+        }
+    }
+
+    /// what the server supports, once it's answered a handshake. CarInfo of
+    /// a size the server's dialect doesn't know, but that give their own
+    /// size, are learned from once enough agree; see `Capabilities::learn`.
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+            .lock()
+            .ok()
+            .and_then(|capabilities| *capabilities)
+    }
+
+    /// parses a received packet, capturing it to disk if it's malformed.
+    ///
+    /// * `packet`: the bytes received.
+    fn parse_packet(&self, packet: &[u8]) -> Result<Event, AcError> {
+        if let Some(Ok(mut checker)) = self.conformance.as_ref().map(Mutex::lock) {
+            checker.check(packet);
+        }
+
+        let parsed = match self.capabilities.lock().as_deref_mut() {
+            Ok(Some(capabilities)) => {
+                capabilities.learn(packet);
+                capabilities.parse(packet)
+            }
+            _ => Event::try_parse(packet),
+        };
+        if let Ok(Event::HandshakeResponse(handshake)) = &parsed
+            && let Ok(mut capabilities) = self.capabilities.lock()
+        {
+            *capabilities = Some(handshake.capabilities());
+        }
+        if let Ok(Event::CarInfo(car)) = &parsed
+            && let Ok(mut clock) = self.clock.lock()
+        {
+            clock.observe(self.created.elapsed(), car);
+            self.stats.clock(&clock);
+        }
+
+        parsed.map_err(|why| {
+            StatsCounters::add(&self.stats.malformed, 1);
+            self.capture_packet(packet, &why);
+            AcError::from(why)
+        })
+    }
+
+    /// writes a malformed packet to the capture directory, if one is set.
+    ///
+    /// * `packet`: the bytes that failed to parse.
+    /// * `why`: the parse failure, recorded as the capture's context.
+    fn capture_packet(&self, packet: &[u8], why: &ParserError) {
+        let Some(capture) = &self.capture else {
+            return;
+        };
+
+        let Ok(mut capture) = capture.lock() else {
+            return;
+        };
+
+        if let Err(err) = capture.record(packet, &why.to_string()) {
+            eprintln!("Error capturing malformed packet: {err}");
+        }
+    }
+
+    /// builds a message to be sent to the Assetto Corsa UDP server.
+    ///
+    /// * `op`: which operation to send
+    /// * `device`: what kind of device is sending this message
+    fn build_udp_message(&self, op: Operation) -> BytesMut {
+        let handshake = Handshake {
+            identifier: self.device,
+            version: 1,
+            operation: op,
+        };
+
+        let mut msg = BytesMut::with_capacity(12);
+        msg.put_i32_le(handshake.identifier as i32);
+        msg.put_i32_le(handshake.version);
+        msg.put_i32_le(handshake.operation as i32);
+
+        msg
+    }
+}
+
+#[cfg(test)]
+mod lib_tests {
+    use ac_parser::Device;
+
+    use crate::{Client, PacketCapture};
+    use std::{fs, net::UdpSocket, time::Duration};
+
+    // Builds a test socket listener to confirm messages, bound to an OS-assigned port.
+    fn build_socket_listener() -> UdpSocket {
+        UdpSocket::bind("127.0.0.1:0").expect("failed to bind UDP socket.")
+    }
+
+    #[test]
+    fn test_connect_to_remote() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let client = Client::new(remote_addr, Device::default());
+        assert!(client.is_ok(), "Expected client to connect");
+    }
+
+    #[test]
+    fn test_send_handshake() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let client =
+            Client::new(remote_addr, Device::default()).expect("failed to connect to remote");
+
+        let send_msg = client.send_message(ac_parser::Operation::Handshake);
+        assert!(send_msg.is_ok(), "Expected message to be sent.");
+        assert_eq!(send_msg.unwrap(), 12, "Sent bytes should be 12");
+    }
+
+    #[test]
+    fn test_recv_message() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let client =
+            Client::new(remote_addr, Device::default()).expect("failed to connect to remote");
+
+        let send_msg = client.send_message(ac_parser::Operation::Handshake);
+        assert!(send_msg.is_ok(), "Expected message to be sent.");
+        assert_eq!(send_msg.unwrap(), 12, "Sent bytes should be 12");
+    }
+
+    #[test]
+    fn test_malformed_packet_is_captured() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let mut client =
+            Client::new(remote_addr, Device::default()).expect("failed to connect to remote");
+
+        let capture_dir =
+            std::env::temp_dir().join(format!("ac_lib-client-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&capture_dir);
+        client.capture_malformed(
+            PacketCapture::new(&capture_dir, 4096).expect("failed to create capture dir"),
+        );
+
+        let client_port = client.socket.local_addr().expect("no local addr").port();
+        remote_socket
+            .send_to(&[0u8; 13], ("127.0.0.1", client_port))
+            .expect("failed to send malformed packet");
+
+        assert!(
+            client.recv_raw_event_buffer().is_err(),
+            "13 bytes is no event"
+        );
+        assert_eq!(client.stats().malformed, 1);
+
+        let captures = fs::read_dir(&capture_dir)
+            .expect("capture dir should exist")
+            .count();
+        assert_eq!(captures, 1, "Malformed packet should be written to disk");
+
+        let _ = fs::remove_dir_all(capture_dir);
+    }
+
+    #[test]
+    fn test_strict_conformance_warns_without_failing() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let mut client =
+            Client::new(remote_addr, Device::default()).expect("failed to connect to remote");
+        assert!(client.conformance_report().is_none());
+        client.check_conformance();
+
+        let mut packet = ac_parser::CarInfo::default().to_bytes().to_vec();
+        packet[24] = 0xff; // padding
+        let client_port = client.socket.local_addr().expect("no local addr").port();
+        remote_socket
+            .send_to(&packet, ("127.0.0.1", client_port))
+            .expect("failed to send packet");
+
+        let event = client.recv_event().expect("still delivered");
+        assert!(matches!(event, ac_parser::Event::CarInfo(_)));
+
+        let report = client.conformance_report().expect("checking is on");
+        assert_eq!(report.packets, 1);
+        assert_eq!(
+            report.violations[0].problem,
+            ac_parser::Problem::PaddingSet(0xff)
+        );
+    }
+
+    #[test]
+    fn test_recv_event_reuses_buffers() {
+        let remote_socket = build_socket_listener();
+
+        let remote_addr = remote_socket
+            .local_addr()
+            .expect("failed to get local addr");
+
+        let client =
+            Client::new(remote_addr, Device::default()).expect("failed to connect to remote");
+        let client_port = client.socket.local_addr().expect("no local addr").port();
+
+        let packet = ac_parser::CarInfo::default().to_bytes();
+        for _ in 0..2 {
+            remote_socket
+                .send_to(&packet, ("127.0.0.1", client_port))
+                .expect("failed to send packet");
+        }
+
+        let first = client.recv_packet().expect("failed to receive");
+        let first_ptr = first.as_ptr();
+        assert_eq!(&*first, &packet[..]);
+        drop(first);
+
+        let event = client.recv_event().expect("failed to receive");
+        assert!(matches!(event, ac_parser::Event::CarInfo(_)));
+
+        let reused = client.pool.take();
+        assert_eq!(reused.as_ptr(), first_ptr, "Buffer should be reused");
+    }
+
+    #[test]
+    fn test_unanswered_handshake_is_retried() {
+        let server = crate::testing::MockServer::bind().expect("failed to bind mock server");
+        let retry = crate::Fixed {
+            retries: 1,
+            delay: Duration::ZERO,
+        };
+        let client = Client::with_retry(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+            retry,
+        )
+        .expect("failed to connect");
+
+        let server_thread = std::thread::spawn(move || {
+            let (_, client_addr) = server.recv_operation().expect("no first handshake");
+            let (_, client_addr2) = server.recv_operation().expect("no retried handshake");
+            assert_eq!(client_addr, client_addr2);
+
+            let response = ac_parser::HandshakeResponse {
+                version: 7,
+                ..Default::default()
+            };
+            server
+                .send_event(&ac_parser::Event::HandshakeResponse(response), client_addr)
+                .expect("failed to answer");
+        });
+
+        let (_, response) = client
+            .handshake_packet(Duration::from_millis(200))
+            .expect("handshake should succeed on the retry");
+        assert_eq!(response.version, 7);
+        server_thread.join().expect("mock server panicked");
+    }
+}
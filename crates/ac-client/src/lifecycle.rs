@@ -0,0 +1,229 @@
+//! Socket setup and app lifecycle, shaped for mobile wrappers. iOS tears down
+//! an app's sockets when it goes to the background, so a wrapper suspends the
+//! client on the way out and resumes it on the way back: the socket is rebound
+//! with the same settings, then the handshake and subscription are repeated.
+
+use std::{
+    fmt, io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::Arc,
+};
+
+use ac_parser::{HandshakeResponse, Operation};
+
+use crate::{AcError, Client, HANDSHAKE_TIMEOUT, retry};
+
+/// Called on every socket the client creates.
+type SocketHook = Arc<dyn Fn(&UdpSocket) -> io::Result<()> + Send + Sync>;
+
+/// How the client's local socket is bound.
+///
+/// * `local_addr`: the local address to bind; port 0 lets the OS pick one.
+/// * `on_create`: called on every new socket, before it connects, e.g. to set
+///   platform socket options.
+#[derive(Clone)]
+pub struct SocketConfig {
+    local_addr: SocketAddr,
+    on_create: Option<SocketHook>,
+}
+
+impl Default for SocketConfig {
+    /// any interface, with a port picked by the OS.
+    fn default() -> Self {
+        Self {
+            local_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            on_create: None,
+        }
+    }
+}
+
+impl fmt::Debug for SocketConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SocketConfig")
+            .field("local_addr", &self.local_addr)
+            .field("on_create", &self.on_create.is_some())
+            .finish()
+    }
+}
+
+impl SocketConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// binds a fixed local port on every interface, for networks where the
+    /// server's replies are only let through to a known port.
+    ///
+    /// * `port`: the local port.
+    pub fn local_port(mut self, port: u16) -> Self {
+        self.local_addr.set_port(port);
+        self
+    }
+
+    /// binds a specific local address.
+    ///
+    /// * `addr`: the local address and port.
+    pub fn local_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_addr = addr;
+        self
+    }
+
+    /// runs a hook on every socket the client creates, including those from
+    /// `Client::recreate_socket`.
+    ///
+    /// * `hook`: called with the new, not yet connected socket.
+    pub fn on_create<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&UdpSocket) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.on_create = Some(Arc::new(hook));
+        self
+    }
+
+    /// binds a new socket and runs the hook on it.
+    pub(crate) fn bind(&self) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind(self.local_addr)?;
+        if let Some(hook) = &self.on_create {
+            hook(&socket)?;
+        }
+
+        Ok(socket)
+    }
+}
+
+impl Client {
+    /// the local address the client's socket is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, AcError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// whether the client is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// replaces the socket with a new one bound and connected the same way,
+    /// keeping its read timeout. Call it when the OS has invalidated the old one.
+    pub fn recreate_socket(&mut self) -> Result<(), AcError> {
+        let read_timeout = self.socket.read_timeout().unwrap_or(None);
+
+        // Close the old socket first, so a fixed local port is free to bind again.
+        let placeholder = match self.socket_config.local_addr {
+            SocketAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            SocketAddr::V6(_) => UdpSocket::bind("[::]:0")?,
+        };
+        drop(std::mem::replace(&mut self.socket, placeholder));
+
+        let socket = self.socket_config.bind()?;
+        retry::retry(&*self.retry, "connecting", || socket.connect(self.remote))?;
+        socket.set_read_timeout(read_timeout)?;
+
+        self.socket = socket;
+        Ok(())
+    }
+
+    /// tells the server to stop sending, e.g. as the app goes to the background.
+    /// The subscription is remembered for `resume`. Sending can fail if the OS
+    /// has already taken the network away; the client is suspended either way.
+    pub fn suspend(&mut self) -> Result<(), AcError> {
+        self.suspended = true;
+        self.pause_updates()
+    }
+
+    /// sends a dismiss without forgetting the subscription, so `resubscribe`
+    /// and `resume` repeat it.
+    pub(crate) fn pause_updates(&self) -> Result<(), AcError> {
+        let dismiss = self.build_udp_message(Operation::Dismiss);
+        let sent = self.socket.send(&dismiss)?;
+        self.stats.traffic.sent(sent);
+
+        Ok(())
+    }
+
+    /// brings a suspended client back, e.g. as the app returns to the foreground:
+    /// recreates the socket, handshakes and repeats its subscriptions.
+    /// Returns the server's fresh handshake response.
+    pub fn resume(&mut self) -> Result<HandshakeResponse, AcError> {
+        self.recreate_socket()?;
+
+        let (_, handshake) = self.handshake_packet(HANDSHAKE_TIMEOUT)?;
+        self.repeat_subscriptions(self.subscriptions_now())?;
+
+        self.suspended = false;
+        Ok(handshake)
+    }
+}
+
+#[cfg(test)]
+mod lifecycle_tests {
+    use std::{
+        net::{SocketAddr, UdpSocket},
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        thread,
+    };
+
+    use ac_parser::{Device, Event, HandshakeResponse, Operation};
+
+    use crate::{Client, Exponential, lifecycle::SocketConfig, testing::MockServer};
+
+    #[test]
+    fn suspend_and_resume_rebuild_the_socket_and_resubscribe() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .expect("no free port")
+            .port();
+        let created = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&created);
+        let socket = SocketConfig::new()
+            .local_addr(SocketAddr::from(([127, 0, 0, 1], port)))
+            .on_create(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            });
+
+        let server_thread = thread::spawn(move || {
+            let mut operations = Vec::new();
+            for _ in 0..4 {
+                let (operation, from) = server.recv_operation().expect("no request");
+                if matches!(operation, Operation::Handshake) {
+                    let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+                    server.send_event(&handshake, from).expect("failed to send");
+                }
+                operations.push((operation, from.port()));
+            }
+            operations
+        });
+
+        let mut client =
+            Client::with_socket_config(addr, Device::default(), Exponential::default(), socket)
+                .expect("failed to connect");
+        assert_eq!(client.local_addr().map(|a| a.port()).ok(), Some(port));
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to subscribe");
+
+        client.suspend().expect("failed to dismiss");
+        assert!(client.is_suspended());
+        client.resume().expect("failed to resume");
+        assert!(!client.is_suspended());
+
+        let operations = server_thread.join().expect("mock server panicked");
+        assert!(matches!(
+            operations[..],
+            [
+                (Operation::SubscribeUpdate, _),
+                (Operation::Dismiss, _),
+                (Operation::Handshake, _),
+                (Operation::SubscribeUpdate, _),
+            ]
+        ));
+        assert!(operations.iter().all(|&(_, from)| from == port));
+        assert_eq!(created.load(Ordering::Relaxed), 2);
+    }
+}
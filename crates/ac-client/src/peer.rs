@@ -0,0 +1,331 @@
+//! Forwarding live telemetry to a remote peer over a message channel the app
+//! opens itself, so a race engineer outside the LAN can watch without port
+//! forwarding. This isn't a WebRTC implementation: connecting the peers, NAT
+//! traversal and encryption belong to whichever library the app already
+//! uses, e.g. a WebRTC stack, which hands over its open channel as a
+//! `PeerChannel`. What such a library needs from us is somewhere to swap its
+//! offer, answer and candidates with the other peer, which `Signaling`
+//! provides over TCP.
+//!
+//! Each message on the channel is one packet exactly as the AC server sent
+//! it, so the far end decodes it with `Event::try_parse` like any datagram.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+};
+
+use crate::{AcError, Client, DropPolicy, EventReceiver, delivery, stats::StatsCounters};
+
+/// The longest signal text accepted from a peer. SDP offers with many
+/// candidates run to a few KiB, so this leaves plenty of room.
+pub const MAX_SIGNAL_LEN: usize = 64 * 1024;
+
+/// The longest `<kind> <length>` header line accepted from a peer.
+const MAX_HEADER_LEN: u64 = 32;
+
+/// An open message channel to the remote peer, e.g. a WebRTC library's
+/// `RTCDataChannel` behind a thin wrapper. Unordered, unreliable channels
+/// suit telemetry best: a late packet is worth less than the next one.
+pub trait PeerChannel: Send {
+    /// sends one message.
+    ///
+    /// * `message`: one telemetry packet.
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+}
+
+impl Client {
+    /// moves the client onto a receive thread that forwards every packet that
+    /// parses to `channel`, and delivers the events locally too. Messages the
+    /// channel fails to send count as dropped.
+    ///
+    /// * `channel`: the open channel to the remote viewer.
+    /// * `capacity`: how many events are queued for the local receiver.
+    pub fn forward_to_peer<C>(
+        self,
+        mut channel: C,
+        capacity: usize,
+    ) -> Result<EventReceiver, AcError>
+    where
+        C: PeerChannel + 'static,
+    {
+        let stats = Arc::clone(&self.stats);
+        let (sender, receiver) =
+            delivery::channel(capacity, DropPolicy::DropOldest, Arc::clone(&stats));
+
+        let thread = self.spawn_delivery(sender, move |packet, event| {
+            if event.is_some() && channel.send(packet).is_err() {
+                StatsCounters::add(&stats.dropped, 1);
+            }
+
+            event.is_some()
+        })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+/// A message between two peers setting up their connection, as WebRTC and
+/// similar libraries exchange them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signal {
+    /// The SDP offer of the peer starting the connection.
+    Offer(String),
+    /// The SDP answer to an offer.
+    Answer(String),
+    /// An ICE candidate, trickled as it's gathered.
+    Candidate(String),
+}
+
+impl Signal {
+    fn kind(&self) -> (&'static str, &str) {
+        match self {
+            Signal::Offer(sdp) => ("offer", sdp),
+            Signal::Answer(sdp) => ("answer", sdp),
+            Signal::Candidate(candidate) => ("candidate", candidate),
+        }
+    }
+
+    /// writes the signal as a `<kind> <length>` line followed by its text.
+    ///
+    /// * `writer`: where it goes.
+    pub fn write_to<W>(&self, mut writer: W) -> Result<(), AcError>
+    where
+        W: Write,
+    {
+        let (kind, text) = self.kind();
+        write!(writer, "{kind} {}\n{text}", text.len())?;
+        Ok(writer.flush()?)
+    }
+
+    /// reads a signal written by `write_to`. Returns `None` if the stream ends
+    /// before the next signal starts, and an `Io` error of kind `InvalidData`
+    /// for a header or text longer than a signal can be.
+    ///
+    /// * `reader`: where to read it from.
+    pub fn read_from<R>(mut reader: R) -> Result<Option<Self>, AcError>
+    where
+        R: BufRead,
+    {
+        let invalid = |why| AcError::from(io::Error::new(io::ErrorKind::InvalidData, why));
+
+        let mut header = String::new();
+        if reader
+            .by_ref()
+            .take(MAX_HEADER_LEN)
+            .read_line(&mut header)?
+            == 0
+        {
+            return Ok(None);
+        }
+        if !header.ends_with('\n') {
+            return Err(invalid("malformed signal header"));
+        }
+
+        let (kind, len) = header
+            .trim_end()
+            .split_once(' ')
+            .ok_or_else(|| invalid("malformed signal header"))?;
+        let len: usize = len
+            .parse()
+            .map_err(|_| invalid("malformed signal length"))?;
+        if len > MAX_SIGNAL_LEN {
+            return Err(invalid("signal too long"));
+        }
+
+        let mut text = vec![0; len];
+        reader.read_exact(&mut text)?;
+        let text = String::from_utf8(text).map_err(|_| invalid("signal isn't UTF-8"))?;
+
+        Ok(Some(match kind {
+            "offer" => Signal::Offer(text),
+            "answer" => Signal::Answer(text),
+            "candidate" => Signal::Candidate(text),
+            _ => return Err(invalid("unknown signal kind")),
+        }))
+    }
+}
+
+/// A TCP connection two peers swap `Signal`s over, either directly or through
+/// a relay both can reach.
+///
+/// * `reader`: the buffered reading half.
+/// * `writer`: the writing half.
+pub struct Signaling {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Signaling {
+    /// connects to the other peer or a relay.
+    ///
+    /// * `addr`: where it's listening.
+    pub fn connect<A>(addr: A) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::from_stream(TcpStream::connect(addr)?)
+    }
+
+    /// waits for the other peer to connect.
+    ///
+    /// * `listener`: where to accept the connection.
+    pub fn accept(listener: &TcpListener) -> Result<Self, AcError> {
+        let (stream, _) = listener.accept()?;
+        Self::from_stream(stream)
+    }
+
+    fn from_stream(stream: TcpStream) -> Result<Self, AcError> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// sends a signal to the other peer.
+    ///
+    /// * `signal`: what to send.
+    pub fn send(&mut self, signal: &Signal) -> Result<(), AcError> {
+        signal.write_to(&mut self.writer)
+    }
+
+    /// waits for the next signal. Returns `None` once the other peer hangs up.
+    pub fn recv(&mut self) -> Result<Option<Signal>, AcError> {
+        Signal::read_from(&mut self.reader)
+    }
+}
+
+#[cfg(test)]
+mod peer_tests {
+    use std::{io, net::TcpListener, sync::mpsc, thread, time::Duration};
+
+    use ac_parser::{CarInfo, Device, Event, Operation};
+
+    use crate::{
+        AcError, Client,
+        peer::{MAX_SIGNAL_LEN, PeerChannel, Signal, Signaling},
+        testing::MockServer,
+    };
+
+    struct Channel(mpsc::Sender<Vec<u8>>);
+
+    impl PeerChannel for Channel {
+        fn send(&mut self, message: &[u8]) -> io::Result<()> {
+            self.0
+                .send(message.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    #[test]
+    fn signals_are_framed_by_kind_and_length() {
+        let signals = [
+            Signal::Offer("v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\n".to_string()),
+            Signal::Answer(String::new()),
+            Signal::Candidate("candidate:1 1 udp 2122260223 10.0.0.2 50000 typ host".to_string()),
+        ];
+        let mut framed = Vec::new();
+        for signal in &signals {
+            signal.write_to(&mut framed).expect("written");
+        }
+        assert!(framed.starts_with(b"offer 29\nv=0\r\n"));
+
+        let mut reader = &framed[..];
+        for signal in &signals {
+            assert_eq!(
+                Signal::read_from(&mut reader).expect("read").as_ref(),
+                Some(signal)
+            );
+        }
+        assert_eq!(
+            Signal::read_from(&mut reader).expect("read"),
+            None,
+            "the stream ended"
+        );
+
+        for malformed in [
+            &b"offer\n"[..],
+            b"offer ten\n",
+            b"hello 0\n",
+            b"offer 4\nv=",
+        ] {
+            assert!(
+                Signal::read_from(malformed).is_err(),
+                "{malformed:?} accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn read_from_caps_the_header_and_text_length() {
+        let too_long = format!("offer {}\n", MAX_SIGNAL_LEN + 1);
+        let endless_header = [b'o'; 4096];
+        for hostile in [too_long.as_bytes(), b"offer 99999999999\n", &endless_header] {
+            let why = Signal::read_from(hostile).expect_err("oversized signal accepted");
+            assert!(matches!(why, AcError::Io(why) if why.kind() == io::ErrorKind::InvalidData));
+        }
+
+        let mut longest = format!("candidate {MAX_SIGNAL_LEN}\n").into_bytes();
+        longest.resize(longest.len() + MAX_SIGNAL_LEN, b'x');
+        assert!(matches!(
+            Signal::read_from(&longest[..]),
+            Ok(Some(Signal::Candidate(text))) if text.len() == MAX_SIGNAL_LEN
+        ));
+    }
+
+    #[test]
+    fn packets_are_forwarded_after_signaling() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to listen");
+        let addr = listener.local_addr().expect("no local addr");
+        let viewer = thread::spawn(move || {
+            let mut signaling = Signaling::accept(&listener).expect("failed to accept");
+            let offer = signaling.recv().expect("failed to receive");
+            signaling
+                .send(&Signal::Answer(
+                    "v=0\r\no=- 2 2 IN IP4 0.0.0.0\r\n".to_string(),
+                ))
+                .expect("failed to answer");
+            (offer, signaling.recv().expect("failed to receive"))
+        });
+
+        let mut signaling = Signaling::connect(addr).expect("failed to connect");
+        let offer = Signal::Offer("v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\n".to_string());
+        signaling.send(&offer).expect("failed to offer");
+        let answer = signaling.recv().expect("failed to receive");
+        assert!(matches!(answer, Some(Signal::Answer(sdp)) if sdp.starts_with("v=0")));
+        drop(signaling);
+        assert_eq!(viewer.join().expect("viewer panicked"), (Some(offer), None));
+
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let (forwarded, remote) = mpsc::channel();
+        let receiver = client
+            .forward_to_peer(Channel(forwarded), 8)
+            .expect("failed to start forwarding");
+        let car = Event::CarInfo(CarInfo {
+            gear: 4,
+            ..CarInfo::default()
+        });
+        server
+            .send_event(&car, client_addr)
+            .expect("failed to send");
+
+        let message = remote
+            .recv_timeout(Duration::from_secs(5))
+            .expect("nothing forwarded");
+        assert!(matches!(Event::try_parse(&message), Ok(Event::CarInfo(car)) if car.gear == 4));
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_some());
+        receiver.close().expect("delivery failed");
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+/// Largest datagram the client reads; every AC packet fits with room to spare.
+pub(crate) const MAX_PACKET_LEN: usize = 1024;
+
+/// Buffers kept around for reuse once released. More than this many packets
+/// held at once by the caller means the extras are freed when dropped.
+const MAX_POOLED: usize = 64;
+
+/// A free list of receive buffers, shared by the socket reader and whoever
+/// holds the packets it hands out, so steady-state receiving doesn't allocate.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BufferPool {
+    free: Arc<Mutex<Vec<Box<[u8]>>>>,
+}
+
+impl BufferPool {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// takes a buffer from the pool, allocating only if none are free.
+    pub(crate) fn take(&self) -> Box<[u8]> {
+        self.free
+            .lock()
+            .ok()
+            .and_then(|mut free| free.pop())
+            .unwrap_or_else(|| vec![0u8; MAX_PACKET_LEN].into_boxed_slice())
+    }
+
+    /// wraps a filled buffer as a packet that returns it to the pool when dropped.
+    ///
+    /// * `buf`: a buffer from `take`.
+    /// * `len`: how many bytes of it hold the packet.
+    pub(crate) fn packet(&self, buf: Box<[u8]>, len: usize) -> Packet {
+        Packet {
+            buf: Some(buf),
+            len,
+            pool: self.clone(),
+        }
+    }
+
+    /// returns a buffer from `take` that was never filled.
+    ///
+    /// * `buf`: the unused buffer.
+    pub(crate) fn release(&self, buf: Box<[u8]>) {
+        if let Ok(mut free) = self.free.lock()
+            && free.len() < MAX_POOLED
+        {
+            free.push(buf);
+        }
+    }
+}
+
+/// A received datagram. Derefs to the packet's bytes; its buffer goes back to
+/// the client's pool when dropped.
+#[derive(Debug)]
+pub struct Packet {
+    buf: Option<Box<[u8]>>,
+    len: usize,
+    pool: BufferPool,
+}
+
+impl Deref for Packet {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf
+            .as_deref()
+            .map(|buf| &buf[..self.len])
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Packet {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.release(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use crate::pool::{BufferPool, MAX_PACKET_LEN};
+
+    #[test]
+    fn dropped_packets_return_their_buffer() {
+        let pool = BufferPool::new();
+
+        let mut buf = pool.take();
+        buf[..3].copy_from_slice(&[1, 2, 3]);
+        let first_ptr = buf.as_ptr();
+
+        let packet = pool.packet(buf, 3);
+        assert_eq!(&*packet, &[1, 2, 3]);
+        drop(packet);
+
+        let reused = pool.take();
+        assert_eq!(reused.as_ptr(), first_ptr, "Buffer should be reused");
+        assert_eq!(reused.len(), MAX_PACKET_LEN);
+    }
+
+    #[test]
+    fn held_packets_force_new_buffers() {
+        let pool = BufferPool::new();
+
+        let held = pool.packet(pool.take(), 0);
+        let other = pool.take();
+        assert_ne!(held.as_ptr(), other.as_ptr());
+    }
+}
@@ -0,0 +1,189 @@
+//! Low-power delivery for phone dashboards showing slow-changing values. The
+//! server has no rate setting, so the client duty-cycles instead: it keeps the
+//! subscription for a short burst, dismisses it, and handshakes and resubscribes
+//! once the quiet period is over. Events are coalesced to the latest of each
+//! kind, so a consumer that only redraws now and then never works through a backlog.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ac_parser::Event;
+
+use crate::{
+    AcError, Client, FailureKind,
+    delivery::{self, DropPolicy, EventReceiver, POLL_INTERVAL},
+};
+
+/// How long a low-power client stays subscribed in each cycle, by default.
+const DEFAULT_AWAKE: Duration = Duration::from_millis(200);
+
+/// How a low-power client duty-cycles its subscription.
+///
+/// * `awake`: how long to stay subscribed in each cycle.
+/// * `asleep`: how long to stay dismissed in each cycle; zero never dismisses,
+///   leaving just the latest-only coalescing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowPower {
+    pub awake: Duration,
+    pub asleep: Duration,
+}
+
+impl Default for LowPower {
+    /// a burst of updates about once a second.
+    fn default() -> Self {
+        Self::every(Duration::from_secs(1))
+    }
+}
+
+impl LowPower {
+    /// a short burst of updates once per `interval`.
+    ///
+    /// * `interval`: how often to wake up.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            awake: DEFAULT_AWAKE.min(interval),
+            asleep: interval.saturating_sub(DEFAULT_AWAKE),
+        }
+    }
+
+    /// stays subscribed, only coalescing events to the latest of each kind.
+    pub fn coalesce_only() -> Self {
+        Self {
+            awake: Duration::MAX,
+            asleep: Duration::ZERO,
+        }
+    }
+}
+
+impl Client {
+    /// moves the client onto a receive thread that duty-cycles its last
+    /// subscription per `power`, delivering only the latest event of each kind.
+    /// The handshake responses from waking up aren't delivered.
+    ///
+    /// * `power`: how long to stay subscribed and dismissed in each cycle.
+    pub fn deliver_low_power(mut self, power: LowPower) -> Result<EventReceiver, AcError> {
+        // Coalescing keeps at most one event of each kind queued.
+        let (sender, receiver) =
+            delivery::channel(16, DropPolicy::LatestOnly, Arc::clone(&self.stats));
+        let poll = POLL_INTERVAL.min(power.awake).max(Duration::from_millis(1));
+        self.set_read_timeout(Some(poll))?;
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-low-power".to_string())
+            .spawn(move || {
+                let mut failures = 0;
+                let mut awake = true;
+                let mut phase_ends = Instant::now().checked_add(power.awake);
+
+                while !sender.is_closed() {
+                    if let Some(ends) = phase_ends
+                        && Instant::now() >= ends
+                        && !power.asleep.is_zero()
+                    {
+                        let sent = if awake {
+                            self.pause_updates()
+                        } else {
+                            self.resubscribe()
+                        };
+                        // The network may have changed while asleep.
+                        if let Err(why) = sent
+                            && !self.recover(FailureKind::of(&why), &why, &mut failures)?
+                        {
+                            return Err(why);
+                        }
+
+                        let phase = if awake { power.asleep } else { power.awake };
+                        awake = !awake;
+                        phase_ends = Instant::now().checked_add(phase);
+                    }
+
+                    let Some(received) = self.recv_next(&mut failures)? else {
+                        continue;
+                    };
+                    match received.event {
+                        Some(Event::HandshakeResponse(_)) | None => {}
+                        Some(event) => {
+                            sender.send(event);
+                        }
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+#[cfg(test)]
+mod power_tests {
+    use std::{thread, time::Duration};
+
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, Operation};
+
+    use crate::{Client, power::LowPower, testing::MockServer};
+
+    #[test]
+    fn dismisses_between_bursts_and_resubscribes() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+
+        let server_thread = thread::spawn(move || {
+            let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+            let car = |gear| {
+                Event::CarInfo(CarInfo {
+                    gear,
+                    ..CarInfo::default()
+                })
+            };
+
+            let (_, client) = server.recv_operation().expect("no handshake");
+            server
+                .send_event(&handshake, client)
+                .expect("failed to send");
+            let (operation, _) = server.recv_operation().expect("no subscription");
+            assert!(matches!(operation, Operation::SubscribeUpdate));
+            server.send_event(&car(1), client).expect("failed to send");
+
+            let (operation, _) = server.recv_operation().expect("no dismiss");
+            assert!(matches!(operation, Operation::Dismiss));
+            let (operation, _) = server.recv_operation().expect("no wake up");
+            assert!(matches!(operation, Operation::Handshake));
+            server
+                .send_event(&handshake, client)
+                .expect("failed to send");
+            let (operation, _) = server.recv_operation().expect("no resubscribe");
+            assert!(matches!(operation, Operation::SubscribeUpdate));
+            server.send_event(&car(2), client).expect("failed to send");
+        });
+
+        let client = Client::new(addr, Device::default()).expect("failed to connect");
+        client.handshake().expect("no handshake");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to subscribe");
+
+        let power = LowPower {
+            awake: Duration::from_millis(100),
+            asleep: Duration::from_millis(200),
+        };
+        let receiver = client.deliver_low_power(power).expect("failed to start");
+
+        let gear = |event| match event {
+            Some(Event::CarInfo(car)) => Some(car.gear),
+            _ => None,
+        };
+        assert_eq!(gear(receiver.recv_timeout(Duration::from_secs(5))), Some(1));
+        assert_eq!(
+            gear(receiver.recv_timeout(Duration::from_secs(5))),
+            Some(2),
+            "the wake-up handshake isn't delivered"
+        );
+
+        server_thread.join().expect("mock server panicked");
+        receiver.close().expect("receive thread failed");
+    }
+}
@@ -0,0 +1,278 @@
+//! A callback-driven push API for FFI consumers. Swift, Kotlin and C all have
+//! an easier time registering a few callbacks than polling a channel or driving
+//! an async stream, so `Client::push` runs the receive loop on an internal
+//! thread and calls back with each CarInfo, LapInfo and change of connection state.
+
+use std::{
+    io,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+use ac_parser::{CarInfo, Event, LapInfo, Operation};
+
+use crate::{
+    AcError, Client,
+    delivery::POLL_INTERVAL,
+    stats::{ClientStats, StatsCounters},
+};
+
+/// Where a pushing client is with its server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Handshaking and subscribing.
+    Connecting,
+    /// The server answered the handshake; updates are coming in.
+    Connected,
+    /// The network changed and the socket is being rebound.
+    Reconnecting,
+    /// The server went quiet and didn't answer a probe, e.g. because AC was
+    /// restarted. `Connected` follows once it answers again.
+    Lost,
+    /// The receive thread stopped, either on request or after a failure.
+    Disconnected,
+}
+
+/// Called with each packet of one kind.
+type PacketCallback<T> = Box<dyn FnMut(&T) + Send>;
+
+/// The callbacks a pushing client calls, all on its receive thread. Any left
+/// unset are skipped.
+///
+/// * `car_info`: called with every CarInfo.
+/// * `lap`: called with every LapInfo.
+/// * `connection_state`: called whenever the connection state changes.
+#[derive(Default)]
+pub struct Callbacks {
+    car_info: Option<PacketCallback<CarInfo>>,
+    lap: Option<PacketCallback<LapInfo>>,
+    connection_state: Option<Box<dyn FnMut(ConnectionState) + Send>>,
+}
+
+impl Callbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// * `callback`: called with every CarInfo.
+    pub fn on_car_info<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&CarInfo) + Send + 'static,
+    {
+        self.car_info = Some(Box::new(callback));
+        self
+    }
+
+    /// * `callback`: called with every LapInfo.
+    pub fn on_lap<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&LapInfo) + Send + 'static,
+    {
+        self.lap = Some(Box::new(callback));
+        self
+    }
+
+    /// * `callback`: called whenever the connection state changes.
+    pub fn on_connection_state<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(ConnectionState) + Send + 'static,
+    {
+        self.connection_state = Some(Box::new(callback));
+        self
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::CarInfo(car) => {
+                if let Some(callback) = self.car_info.as_mut() {
+                    callback(car);
+                }
+            }
+            Event::LapInfo(info) => {
+                if let Some(callback) = self.lap.as_mut() {
+                    callback(info);
+                }
+            }
+            Event::HandshakeResponse(_) => self.state(ConnectionState::Connected),
+        }
+    }
+
+    fn state(&mut self, state: ConnectionState) {
+        if let Some(callback) = self.connection_state.as_mut() {
+            callback(state);
+        }
+    }
+}
+
+/// A running push client.
+///
+/// * `running`: cleared to stop the receive thread.
+/// * `stats`: the client's counters.
+/// * `thread`: the receive thread, returning why it stopped.
+pub struct PushHandle {
+    running: Arc<AtomicBool>,
+    stats: Arc<StatsCounters>,
+    thread: JoinHandle<Result<(), AcError>>,
+}
+
+impl PushHandle {
+    /// whether the receive thread is still running.
+    pub fn is_running(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// the counters of the pushing client.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// stops the receive thread and waits for it, returning the socket error that
+    /// stopped it early, if any. The connection state callback sees `Disconnected` either way.
+    pub fn stop(self) -> Result<(), AcError> {
+        self.running.store(false, Ordering::Relaxed);
+        self.thread
+            .join()
+            .map_err(|_| io::Error::other("push receive thread panicked"))?
+    }
+}
+
+impl Client {
+    /// moves the client onto an internal receive thread that handshakes,
+    /// subscribes and calls `callbacks` with everything the server sends.
+    /// Failures go to the client's `RecoveryHook` as with `deliver`, a
+    /// rebind after a network change shows up as `Reconnecting`, and with
+    /// `rehandshake_after` a server that stops answering shows up as `Lost`.
+    ///
+    /// * `subscription`: what to subscribe to.
+    /// * `callbacks`: what to call.
+    pub fn push(
+        mut self,
+        subscription: Operation,
+        callbacks: Callbacks,
+    ) -> Result<PushHandle, AcError> {
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let callbacks = Arc::new(Mutex::new(callbacks));
+        let rebinding = Arc::clone(&callbacks);
+        self.on_network_change(move |_| lock(&rebinding).state(ConnectionState::Reconnecting));
+        let losing = Arc::clone(&callbacks);
+        self.on_lost(move |state| lock(&losing).state(state));
+
+        let running = Arc::new(AtomicBool::new(true));
+        let stats = Arc::clone(&self.stats);
+        let keep_running = Arc::clone(&running);
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-push".to_string())
+            .spawn(move || {
+                let result = self.run_push(subscription, &keep_running, &callbacks);
+                lock(&callbacks).state(ConnectionState::Disconnected);
+                result
+            })?;
+
+        Ok(PushHandle {
+            running,
+            stats,
+            thread,
+        })
+    }
+
+    fn run_push(
+        &mut self,
+        subscription: Operation,
+        running: &AtomicBool,
+        callbacks: &Mutex<Callbacks>,
+    ) -> Result<(), AcError> {
+        lock(callbacks).state(ConnectionState::Connecting);
+        self.send_message(Operation::Handshake)?;
+        self.send_message(subscription)?;
+
+        let mut failures = 0;
+        while running.load(Ordering::Relaxed) {
+            if let Some(event) = self.recv_next(&mut failures)?.and_then(|r| r.event) {
+                lock(callbacks).event(&event);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn lock(callbacks: &Mutex<Callbacks>) -> MutexGuard<'_, Callbacks> {
+    // A callback that panicked leaves nothing half-updated behind.
+    callbacks
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod push_tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, LapInfo, Operation};
+
+    use crate::{Client, push::Callbacks, testing::MockServer};
+
+    #[test]
+    fn callbacks_see_cars_laps_and_connection_state() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+
+        let (seen, pushed) = mpsc::channel();
+        let (cars, laps, states) = (seen.clone(), seen.clone(), seen);
+        let callbacks = Callbacks::new()
+            .on_car_info(move |car| {
+                let _ = cars.send(format!("car {}", car.gear));
+            })
+            .on_lap(move |info| {
+                let _ = laps.send(format!("lap {}", info.lap));
+            })
+            .on_connection_state(move |state| {
+                let _ = states.send(format!("{state:?}"));
+            });
+        let handle = client
+            .push(Operation::SubscribeUpdate, callbacks)
+            .expect("failed to start pushing");
+
+        let (operation, client_addr) = server.recv_operation().expect("no handshake");
+        assert!(matches!(operation, Operation::Handshake));
+        let (operation, _) = server.recv_operation().expect("no subscription");
+        assert!(matches!(operation, Operation::SubscribeUpdate));
+
+        let events = [
+            Event::HandshakeResponse(HandshakeResponse::default()),
+            Event::CarInfo(CarInfo {
+                gear: 3,
+                ..CarInfo::default()
+            }),
+            Event::LapInfo(LapInfo {
+                lap: 2,
+                ..LapInfo::default()
+            }),
+        ];
+        for event in &events {
+            server
+                .send_event(event, client_addr)
+                .expect("failed to send");
+        }
+
+        let next = || {
+            pushed
+                .recv_timeout(Duration::from_secs(5))
+                .expect("no callback")
+        };
+        let first: Vec<_> = (0..4).map(|_| next()).collect();
+        assert_eq!(first, ["Connecting", "Connected", "car 3", "lap 2"]);
+
+        assert!(handle.is_running());
+        handle.stop().expect("push failed");
+        assert_eq!(next(), "Disconnected");
+    }
+}
@@ -0,0 +1,338 @@
+//! Failure classification for long-running services: whether a receive error is
+//! worth retrying, calls for resubscribing, or should stop the client. A client's
+//! receive threads (`deliver`, `into_spsc`, `Config::connect`) consult its
+//! `RecoveryHook` on every failure instead of giving up on the first one, and by
+//! default rebind the socket when the network underneath it changes.
+
+use std::{error::Error, io, sync::Mutex};
+
+use ac_parser::Event;
+
+use crate::{AcError, Client, delivery::is_timeout, pool::Packet, stats::StatsCounters};
+
+/// Consecutive transient failures `DefaultRecovery` retries before aborting.
+const DEFAULT_MAX_RETRIES: u32 = 10;
+
+/// What kind of failure happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// A network hiccup that usually clears by itself, like the server restarting
+    /// (connection refused) or a route flapping.
+    Transient,
+    /// The local address or interface went away, e.g. a phone roaming between
+    /// access points; the socket has to be rebound.
+    NetworkChanged,
+    /// The server sent something that isn't a valid packet.
+    Protocol,
+    /// The socket was closed or disconnected underneath the client.
+    Shutdown,
+    /// Anything else, such as a permissions error; retrying won't help.
+    Fatal,
+}
+
+impl FailureKind {
+    /// classifies a socket error.
+    ///
+    /// * `why`: the error.
+    pub fn of_io(why: &io::Error) -> Self {
+        use io::ErrorKind::*;
+
+        match why.kind() {
+            WouldBlock | TimedOut | Interrupted | ConnectionRefused | ConnectionReset
+            | HostUnreachable => FailureKind::Transient,
+            NetworkUnreachable | NetworkDown | AddrNotAvailable => FailureKind::NetworkChanged,
+            InvalidData | UnexpectedEof => FailureKind::Protocol,
+            NotConnected | ConnectionAborted | BrokenPipe => FailureKind::Shutdown,
+            _ => FailureKind::Fatal,
+        }
+    }
+
+    /// classifies an error returned by the client, such as from `Client::recv_event`.
+    ///
+    /// * `why`: the error.
+    pub fn of(why: &AcError) -> Self {
+        match why {
+            AcError::Io(why) => FailureKind::of_io(why),
+            _ => FailureKind::Protocol,
+        }
+    }
+}
+
+/// A failure, as shown to a `RecoveryHook`.
+///
+/// * `kind`: how the failure was classified.
+/// * `error`: the underlying error.
+/// * `consecutive`: how many failures in a row this makes, including this one.
+#[derive(Debug)]
+pub struct Failure<'a> {
+    pub kind: FailureKind,
+    pub error: &'a (dyn Error + 'static),
+    pub consecutive: u32,
+}
+
+/// What to do about a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Carry on receiving; a malformed packet is skipped.
+    Retry,
+    /// Handshake and repeat the subscriptions, then carry on.
+    Resubscribe,
+    /// Recreate the socket, handshake and resubscribe, then carry on.
+    Rebind,
+    /// Stop receiving and report the error.
+    Abort,
+}
+
+/// Decides how a client recovers from receive failures.
+pub trait RecoveryHook: Send {
+    fn on_failure(&mut self, failure: &Failure<'_>) -> Recovery;
+}
+
+impl<F> RecoveryHook for F
+where
+    F: FnMut(&Failure<'_>) -> Recovery + Send,
+{
+    fn on_failure(&mut self, failure: &Failure<'_>) -> Recovery {
+        self(failure)
+    }
+}
+
+/// The hook clients start with: skips malformed packets, retries transient
+/// failures and rebinds after network changes up to a limit, and aborts on
+/// anything else.
+///
+/// * `max_retries`: consecutive transient failures or network changes to
+///   recover from before aborting.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultRecovery {
+    pub max_retries: u32,
+}
+
+impl Default for DefaultRecovery {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+impl RecoveryHook for DefaultRecovery {
+    fn on_failure(&mut self, failure: &Failure<'_>) -> Recovery {
+        match failure.kind {
+            FailureKind::Protocol => Recovery::Retry,
+            FailureKind::Transient if failure.consecutive <= self.max_retries => Recovery::Retry,
+            FailureKind::NetworkChanged if failure.consecutive <= self.max_retries => {
+                Recovery::Rebind
+            }
+            FailureKind::Transient
+            | FailureKind::NetworkChanged
+            | FailureKind::Shutdown
+            | FailureKind::Fatal => Recovery::Abort,
+        }
+    }
+}
+
+/// A packet taken off the socket by a receive thread.
+///
+/// * `packet`: the raw datagram.
+/// * `event`: its event, or `None` if it was malformed and skipped.
+pub(crate) struct Received {
+    pub(crate) packet: Packet,
+    pub(crate) event: Option<Event>,
+}
+
+impl Client {
+    /// replaces how this client's receive threads recover from failures.
+    ///
+    /// * `hook`: decides what to do about each failure.
+    pub fn set_recovery<H>(&mut self, hook: H)
+    where
+        H: RecoveryHook + 'static,
+    {
+        self.recovery = Mutex::new(Box::new(hook));
+    }
+
+    /// receives and parses the next packet for a receive thread, consulting the
+    /// recovery hook on failure. Returns `None` when there's nothing to hand on
+    /// yet, so the caller can check whether it should stop.
+    ///
+    /// * `consecutive`: failures in a row so far, reset on every good packet.
+    pub(crate) fn recv_next(&mut self, consecutive: &mut u32) -> Result<Option<Received>, AcError> {
+        if let Some(handshake) = self.watch_silence()? {
+            return Ok(Some(handshake));
+        }
+
+        let packet = match self.recv_packet() {
+            Ok(packet) => {
+                self.heard();
+                packet
+            }
+            Err(AcError::Io(why)) if is_timeout(&why) => return Ok(None),
+            Err(why) if self.recover(FailureKind::of(&why), &why, consecutive)? => {
+                return Ok(None);
+            }
+            Err(why) => return Err(why),
+        };
+
+        match self.parse_packet(&packet) {
+            Ok(event) => {
+                *consecutive = 0;
+                Ok(Some(Received {
+                    packet,
+                    event: Some(event),
+                }))
+            }
+            Err(why) if self.recover(FailureKind::Protocol, &why, consecutive)? => {
+                Ok(Some(Received {
+                    packet,
+                    event: None,
+                }))
+            }
+            Err(why) => Err(why),
+        }
+    }
+
+    /// asks the hook about a failure and acts on its answer.
+    /// Returns whether to carry on receiving, or the error if resubscribing or
+    /// rebinding failed.
+    ///
+    /// * `kind`: how the failure was classified.
+    /// * `error`: the underlying error.
+    /// * `consecutive`: failures in a row so far, not counting this one.
+    pub(crate) fn recover(
+        &mut self,
+        kind: FailureKind,
+        error: &(dyn Error + 'static),
+        consecutive: &mut u32,
+    ) -> Result<bool, AcError> {
+        *consecutive = consecutive.saturating_add(1);
+        let failure = Failure {
+            kind,
+            error,
+            consecutive: *consecutive,
+        };
+
+        let recovery = match self.recovery.lock() {
+            Ok(mut hook) => hook.on_failure(&failure),
+            Err(_) => Recovery::Abort,
+        };
+
+        match recovery {
+            Recovery::Retry => Ok(true),
+            Recovery::Resubscribe => {
+                StatsCounters::add(&self.stats.resubscribes, 1);
+                self.resubscribe()?;
+                Ok(true)
+            }
+            Recovery::Rebind => {
+                self.rebind(error)?;
+                Ok(true)
+            }
+            Recovery::Abort => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use std::{io, time::Duration};
+
+    use ac_parser::{Device, Event, LapInfo, Operation, ParserError};
+
+    use crate::{
+        AcError, Client,
+        delivery::DropPolicy,
+        recovery::{DefaultRecovery, Failure, FailureKind, Recovery, RecoveryHook},
+        testing::MockServer,
+    };
+
+    #[test]
+    fn errors_are_classified() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(FailureKind::of_io(&refused), FailureKind::Transient);
+        assert_eq!(
+            FailureKind::of_io(&io::Error::from(io::ErrorKind::NotConnected)),
+            FailureKind::Shutdown
+        );
+        assert_eq!(
+            FailureKind::of_io(&io::Error::from(io::ErrorKind::PermissionDenied)),
+            FailureKind::Fatal
+        );
+
+        assert_eq!(
+            FailureKind::of(&AcError::from(ParserError::UnknownPacketSize(13))),
+            FailureKind::Protocol
+        );
+        assert_eq!(
+            FailureKind::of(&AcError::from(refused)),
+            FailureKind::Transient
+        );
+    }
+
+    #[test]
+    fn default_recovery_gives_up_on_repeated_transient_failures() {
+        let error = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let mut hook = DefaultRecovery { max_retries: 2 };
+
+        let decide = |hook: &mut DefaultRecovery, kind, consecutive| {
+            hook.on_failure(&Failure {
+                kind,
+                error: &error,
+                consecutive,
+            })
+        };
+
+        assert_eq!(
+            decide(&mut hook, FailureKind::Transient, 2),
+            Recovery::Retry
+        );
+        assert_eq!(
+            decide(&mut hook, FailureKind::Transient, 3),
+            Recovery::Abort
+        );
+        assert_eq!(
+            decide(&mut hook, FailureKind::Protocol, 50),
+            Recovery::Retry
+        );
+        assert_eq!(decide(&mut hook, FailureKind::Shutdown, 1), Recovery::Abort);
+    }
+
+    #[test]
+    fn hook_can_resubscribe_after_a_malformed_packet() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let mut client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client.set_recovery(|failure: &Failure<'_>| match failure.kind {
+            FailureKind::Protocol => Recovery::Resubscribe,
+            _ => Recovery::Abort,
+        });
+
+        client
+            .send_message(Operation::SubscribeSpot)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let receiver = client
+            .deliver(4, DropPolicy::default())
+            .expect("failed to start delivery");
+        server
+            .send_event(&Event::LapInfo(LapInfo::default()), client_addr)
+            .expect("failed to send");
+        server
+            .send_raw(&[0u8; 13], client_addr)
+            .expect("failed to send");
+
+        let (first, _) = server.recv_operation().expect("no handshake");
+        let (second, _) = server.recv_operation().expect("no resubscribe");
+        assert!(matches!(first, Operation::Handshake));
+        assert!(matches!(second, Operation::SubscribeSpot));
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_some());
+        assert_eq!(receiver.stats().resubscribes, 1);
+        assert_eq!(receiver.stats().malformed, 1);
+        receiver.close().expect("delivery failed");
+    }
+}
@@ -0,0 +1,99 @@
+use std::{io, net::UdpSocket, os::fd::AsRawFd, ptr};
+
+use crate::pool::{BufferPool, Packet};
+
+/// Most datagrams pulled in by a single recvmmsg call.
+pub(crate) const MAX_BATCH: usize = 32;
+
+/// receives up to `max` datagrams with one recvmmsg syscall, blocking until
+/// at least one arrives. Returns how many were appended to `out`.
+///
+/// * `socket`: the connected client socket.
+/// * `pool`: where receive buffers come from.
+/// * `out`: received packets are appended here.
+/// * `max`: the most packets to receive, capped at `MAX_BATCH`.
+pub(crate) fn recv_batch(
+    socket: &UdpSocket,
+    pool: &BufferPool,
+    out: &mut Vec<Packet>,
+    max: usize,
+) -> io::Result<usize> {
+    let max = max.clamp(1, MAX_BATCH);
+
+    let mut bufs: [Option<Box<[u8]>>; MAX_BATCH] =
+        std::array::from_fn(|i| (i < max).then(|| pool.take()));
+
+    let mut iovecs: [libc::iovec; MAX_BATCH] = std::array::from_fn(|i| match &mut bufs[i] {
+        Some(buf) => libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        },
+        None => libc::iovec {
+            iov_base: ptr::null_mut(),
+            iov_len: 0,
+        },
+    });
+
+    // SAFETY: mmsghdr is a plain C struct for which all-zeroes is a valid value.
+    let mut msgs: [libc::mmsghdr; MAX_BATCH] = unsafe { std::mem::zeroed() };
+    for (msg, iovec) in msgs.iter_mut().zip(iovecs.iter_mut()) {
+        msg.msg_hdr.msg_iov = iovec;
+        msg.msg_hdr.msg_iovlen = 1;
+    }
+
+    // SAFETY: the first `max` headers each point at one iovec covering a live,
+    // exclusively borrowed buffer, and all of them outlive the call.
+    let received = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            msgs.as_mut_ptr(),
+            max as libc::c_uint,
+            libc::MSG_WAITFORONE,
+            ptr::null_mut(),
+        )
+    };
+
+    if received < 0 {
+        bufs.into_iter().flatten().for_each(|buf| pool.release(buf));
+        return Err(io::Error::last_os_error());
+    }
+
+    let received = received as usize;
+    for (i, buf) in bufs.into_iter().enumerate() {
+        let Some(buf) = buf else { continue };
+
+        if i < received {
+            out.push(pool.packet(buf, msgs[i].msg_len as usize));
+        } else {
+            pool.release(buf);
+        }
+    }
+
+    Ok(received)
+}
+
+#[cfg(test)]
+mod recvmmsg_tests {
+    use std::net::UdpSocket;
+
+    use crate::{pool::BufferPool, recvmmsg::recv_batch};
+
+    #[test]
+    fn recv_batch_pulls_queued_datagrams_at_once() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").expect("failed to bind");
+        let sender = UdpSocket::bind("127.0.0.1:0").expect("failed to bind");
+        let addr = receiver.local_addr().expect("no local addr");
+
+        for len in [12, 328, 212] {
+            sender.send_to(&vec![7u8; len], addr).expect("send failed");
+        }
+
+        let pool = BufferPool::new();
+        let mut out = Vec::new();
+        let received = recv_batch(&receiver, &pool, &mut out, 8).expect("recv failed");
+
+        assert_eq!(received, 3);
+        let lens = out.iter().map(|p| p.len()).collect::<Vec<_>>();
+        assert_eq!(lens, vec![12, 328, 212]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Picking a session back up after AC itself restarts. A fresh instance knows
+//! nothing of the old subscription, so updates just stop; the socket sees no
+//! error. With `Client::rehandshake_after` set, a receive thread that hears
+//! nothing for that long probes the server with a handshake. An answer means
+//! the server is back without the subscription, so the client dismisses,
+//! resubscribes and passes the new `HandshakeResponse` on. No answer means
+//! it's gone for now: pushing clients see `ConnectionState::Lost` and the
+//! probe is repeated every silence until one is answered.
+
+use std::time::{Duration, Instant};
+
+use ac_parser::{Event, Operation};
+
+use crate::{
+    AcError, Client, ConnectionState, delivery::is_timeout, recovery::Received,
+    stats::StatsCounters,
+};
+
+/// How long a probe waits for the server to answer its handshake.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Called when the watchdog loses the server.
+pub(crate) type StateHook = Box<dyn FnMut(ConnectionState) + Send>;
+
+/// Watches a receive thread for the silence of a restarted server.
+///
+/// * `silence`: how long without a packet before the server is probed.
+/// * `heard`: when the last packet arrived, or the last probe was sent.
+/// * `lost`: whether the last probe went unanswered.
+/// * `hook`: told when the server is lost.
+pub(crate) struct Watchdog {
+    silence: Duration,
+    heard: Instant,
+    lost: bool,
+    hook: Option<StateHook>,
+}
+
+impl Client {
+    /// has receive threads probe the server and redo the handshake and
+    /// subscription after `silence` without a packet, e.g. because AC was
+    /// restarted. It should be longer than any gap a live server leaves.
+    ///
+    /// * `silence`: how long without a packet before probing.
+    pub fn rehandshake_after(&mut self, silence: Duration) {
+        let hook = self.watchdog.take().and_then(|watchdog| watchdog.hook);
+        self.watchdog = Some(Watchdog {
+            silence,
+            heard: Instant::now(),
+            lost: false,
+            hook,
+        });
+    }
+
+    /// has the watchdog, if there is one, report losing the server to `hook`.
+    ///
+    /// * `hook`: called with `ConnectionState::Lost`, on the receive thread.
+    pub(crate) fn on_lost<F>(&mut self, hook: F)
+    where
+        F: FnMut(ConnectionState) + Send + 'static,
+    {
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.hook = Some(Box::new(hook));
+        }
+    }
+
+    /// notes that a packet arrived.
+    pub(crate) fn heard(&mut self) {
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.heard = Instant::now();
+            watchdog.lost = false;
+        }
+    }
+
+    /// probes a server that's gone quiet for too long. Returns its handshake
+    /// response once it's answered and been resubscribed to.
+    pub(crate) fn watch_silence(&mut self) -> Result<Option<Received>, AcError> {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return Ok(None);
+        };
+        if watchdog.heard.elapsed() < watchdog.silence {
+            return Ok(None);
+        }
+        watchdog.heard = Instant::now();
+
+        let subscriptions = self.subscriptions_now();
+        // Failing sends mean the server isn't there either, same as no answer.
+        let answer = self
+            .send_message(Operation::Dismiss)
+            .and_then(|_| self.probe());
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return Ok(None);
+        };
+
+        match answer {
+            Ok(Some(received)) => {
+                watchdog.lost = false;
+                self.repeat_subscriptions(subscriptions)?;
+                StatsCounters::add(&self.stats.rehandshakes, 1);
+                Ok(Some(received))
+            }
+            Ok(None) | Err(_) => {
+                if !watchdog.lost {
+                    watchdog.lost = true;
+                    if let Some(hook) = watchdog.hook.as_mut() {
+                        hook(ConnectionState::Lost);
+                    }
+                }
+                // Kept for the next probe; Dismiss cleared them.
+                *self.subscription.lock().unwrap_or_else(|p| p.into_inner()) = subscriptions;
+                Ok(None)
+            }
+        }
+    }
+
+    /// sends one handshake and waits a moment for the answer, skipping anything else.
+    fn probe(&self) -> Result<Option<Received>, AcError> {
+        let read_timeout = self.socket.read_timeout()?;
+        self.socket.set_read_timeout(Some(PROBE_TIMEOUT))?;
+        self.send_message(Operation::Handshake)?;
+
+        let deadline = Instant::now() + PROBE_TIMEOUT;
+        let mut answer = Ok(None);
+        while Instant::now() < deadline {
+            match self.recv_packet() {
+                Ok(packet) => {
+                    if let Ok(event @ Event::HandshakeResponse(_)) = Event::try_parse(&packet) {
+                        answer = Ok(Some(Received {
+                            packet,
+                            event: Some(event),
+                        }));
+                        break;
+                    }
+                }
+                Err(AcError::Io(why)) if is_timeout(&why) => break,
+                Err(why) => {
+                    answer = Err(why);
+                    break;
+                }
+            }
+        }
+
+        self.socket.set_read_timeout(read_timeout)?;
+        answer
+    }
+}
+
+#[cfg(test)]
+mod rehandshake_tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use ac_parser::{Device, Event, HandshakeResponse, Operation};
+
+    use crate::{Client, push::Callbacks, testing::MockServer};
+
+    #[test]
+    fn silent_servers_are_probed_and_resubscribed() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let mut client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client.rehandshake_after(Duration::from_millis(200));
+
+        let (states, seen) = mpsc::channel();
+        let callbacks = Callbacks::new().on_connection_state(move |state| {
+            let _ = states.send(state);
+        });
+        let handle = client
+            .push(Operation::SubscribeSpot, callbacks)
+            .expect("failed to start pushing");
+
+        let expect = |expected: fn(&Operation) -> bool| {
+            let (operation, client) = server.recv_operation().expect("no operation");
+            assert!(expected(&operation), "unexpected {operation:?}");
+            client
+        };
+        let answer = |client| {
+            server
+                .send_event(
+                    &Event::HandshakeResponse(HandshakeResponse::default()),
+                    client,
+                )
+                .expect("failed to send")
+        };
+
+        let client_addr = expect(|op| matches!(op, Operation::Handshake));
+        expect(|op| matches!(op, Operation::SubscribeSpot));
+        answer(client_addr);
+
+        // The first probe goes unanswered, as if AC were still starting up.
+        expect(|op| matches!(op, Operation::Dismiss));
+        expect(|op| matches!(op, Operation::Handshake));
+        expect(|op| matches!(op, Operation::Dismiss));
+        let client_addr = expect(|op| matches!(op, Operation::Handshake));
+        answer(client_addr);
+        expect(|op| matches!(op, Operation::SubscribeSpot));
+
+        let next = || seen.recv_timeout(Duration::from_secs(5)).expect("no state");
+        let states: Vec<_> = (0..4).map(|_| format!("{:?}", next())).collect();
+        assert_eq!(states, ["Connecting", "Connected", "Lost", "Connected"]);
+
+        let stats = handle.stats();
+        handle.stop().expect("push failed");
+        assert_eq!(stats.rehandshakes, 1);
+    }
+}
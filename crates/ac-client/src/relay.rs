@@ -0,0 +1,460 @@
+//! A relay for remote coaching when neither end can reach the other: the rig
+//! pushes its telemetry up to a `Relay` anyone can reach, and coaches pull it
+//! down from there. Rig, relay and coach all run this crate.
+//!
+//! Everything goes over TCP as frames of a one byte tag, a little-endian u16
+//! length and the payload. A connection opens with a hello frame naming its
+//! role (`U`plink or `D`ownlink) and the session it's for, and an uplink then
+//! sends one frame per packet. Consecutive packets of a kind differ in a few
+//! fields, so each is XORed with the previous one, leaving mostly zeros, and
+//! runs of zeros are squeezed to two bytes; every `KEYFRAME_INTERVAL`th packet
+//! of a kind goes whole so a coach joining mid-session can start decoding.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use ac_parser::Event;
+
+use crate::{AcError, Client, DropPolicy, EventReceiver, delivery, stats::StatsCounters};
+
+/// How many deltas of a packet kind go between two whole packets.
+pub const KEYFRAME_INTERVAL: u32 = 64;
+
+/// How long the relay waits for a new connection's hello before hanging up.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a frame may take to write to a coach before the coach is dropped.
+const COACH_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Opens an uplink connection; the payload is the session name.
+const UPLINK: u8 = b'U';
+/// Opens a downlink connection; the payload is the session name.
+const DOWNLINK: u8 = b'D';
+/// A whole packet, compressed on its own.
+const KEYFRAME: u8 = b'K';
+/// A packet compressed against the previous one of the same length.
+const DELTA: u8 = b'P';
+
+/// Compresses successive packets against the previous one of their kind.
+///
+/// * `previous`: the last packet of each length, and how many went since its keyframe.
+#[derive(Debug, Default)]
+struct Encoder {
+    previous: HashMap<usize, (Vec<u8>, u32)>,
+}
+
+impl Encoder {
+    /// the tag and compressed payload for a packet.
+    fn encode(&mut self, packet: &[u8]) -> (u8, Vec<u8>) {
+        let (previous, since_keyframe) = self
+            .previous
+            .entry(packet.len())
+            .or_insert_with(|| (Vec::new(), KEYFRAME_INTERVAL));
+
+        let tag = if *since_keyframe >= KEYFRAME_INTERVAL {
+            *since_keyframe = 0;
+            previous.clear();
+            previous.resize(packet.len(), 0);
+            KEYFRAME
+        } else {
+            *since_keyframe += 1;
+            DELTA
+        };
+
+        let mut payload = Vec::with_capacity(packet.len() / 4);
+        let mut zeros = 0u8;
+        for (byte, last) in packet.iter().zip(previous.iter()) {
+            match byte ^ last {
+                0 if zeros < u8::MAX => zeros += 1,
+                xor => {
+                    if zeros > 0 {
+                        payload.extend([0, zeros]);
+                        zeros = 0;
+                    }
+                    if xor == 0 {
+                        zeros = 1;
+                    } else {
+                        payload.push(xor);
+                    }
+                }
+            }
+        }
+        if zeros > 0 {
+            payload.extend([0, zeros]);
+        }
+
+        previous.copy_from_slice(packet);
+        (tag, payload)
+    }
+}
+
+/// Undoes an `Encoder`.
+///
+/// * `previous`: the last packet decoded of each length.
+#[derive(Debug, Default)]
+struct Decoder {
+    previous: HashMap<usize, Vec<u8>>,
+}
+
+impl Decoder {
+    /// the packet a frame carries, or `None` for a delta whose keyframe this
+    /// decoder never saw.
+    ///
+    /// * `tag`: `KEYFRAME` or `DELTA`.
+    /// * `payload`: the compressed packet.
+    fn decode(&mut self, tag: u8, payload: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let mut xor = Vec::with_capacity(payload.len() * 4);
+        let mut bytes = payload.iter();
+        while let Some(&byte) = bytes.next() {
+            if byte == 0 {
+                let run = bytes.next().ok_or_else(|| invalid("truncated zero run"))?;
+                xor.resize(xor.len() + usize::from(*run), 0);
+            } else {
+                xor.push(byte);
+            }
+        }
+
+        let previous = match tag {
+            KEYFRAME => self.previous.entry(xor.len()).or_default(),
+            _ => match self.previous.get_mut(&xor.len()) {
+                Some(previous) => previous,
+                None => return Ok(None),
+            },
+        };
+        if tag == KEYFRAME {
+            previous.clear();
+            previous.resize(xor.len(), 0);
+        }
+
+        previous
+            .iter_mut()
+            .zip(&xor)
+            .for_each(|(last, xor)| *last ^= xor);
+        Ok(Some(previous.clone()))
+    }
+}
+
+/// The sending half: pushes one rig's packets to a relay.
+///
+/// * `stream`: the connection to the relay.
+/// * `encoder`: compresses each packet against the last.
+pub struct Uplink {
+    stream: BufWriter<TcpStream>,
+    encoder: Encoder,
+}
+
+impl Uplink {
+    /// connects to a relay and opens `session` on it.
+    ///
+    /// * `relay`: where the relay listens.
+    /// * `session`: the name coaches subscribe to.
+    pub fn connect<A>(relay: A, session: &str) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(relay)?;
+        stream.set_nodelay(true)?;
+        let mut stream = BufWriter::new(stream);
+        write_frame(&mut stream, UPLINK, session.as_bytes())?;
+
+        Ok(Self {
+            stream,
+            encoder: Encoder::default(),
+        })
+    }
+
+    /// sends a packet to the relay.
+    ///
+    /// * `packet`: a packet as the AC server sent it.
+    pub fn send(&mut self, packet: &[u8]) -> Result<(), AcError> {
+        let (tag, payload) = self.encoder.encode(packet);
+        Ok(write_frame(&mut self.stream, tag, &payload)?)
+    }
+}
+
+/// The receiving half: a coach's view of a session on a relay.
+///
+/// * `stream`: the connection to the relay.
+/// * `decoder`: undoes the uplink's compression.
+pub struct Downlink {
+    stream: BufReader<TcpStream>,
+    decoder: Decoder,
+}
+
+impl Downlink {
+    /// connects to a relay and subscribes to `session`.
+    ///
+    /// * `relay`: where the relay listens.
+    /// * `session`: the name the rig's uplink opened.
+    pub fn connect<A>(relay: A, session: &str) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        let mut stream = TcpStream::connect(relay)?;
+        write_frame(&mut stream, DOWNLINK, session.as_bytes())?;
+
+        Ok(Self {
+            stream: BufReader::new(stream),
+            decoder: Decoder::default(),
+        })
+    }
+
+    /// waits for the next event from the rig. Packets that arrive before the
+    /// first keyframe of their kind are skipped. Returns `None` once the relay
+    /// hangs up.
+    pub fn recv(&mut self) -> Result<Option<Event>, AcError> {
+        loop {
+            let Some((tag, payload)) = read_frame(&mut self.stream)? else {
+                return Ok(None);
+            };
+
+            if let Some(packet) = self.decoder.decode(tag, &payload)? {
+                return Ok(Some(Event::try_parse(&packet)?));
+            }
+        }
+    }
+}
+
+/// A coach's connection, locked by whichever uplink is writing a frame to it.
+type Coach = Arc<Mutex<TcpStream>>;
+
+/// The coaches subscribed to each session.
+type Sessions = Mutex<HashMap<String, Vec<Coach>>>;
+
+/// A relay server: hands each uplink's frames on to every downlink of the
+/// same session.
+///
+/// * `listener`: where rigs and coaches connect.
+/// * `sessions`: the coaches subscribed to each session.
+pub struct Relay {
+    listener: TcpListener,
+    sessions: Arc<Sessions>,
+}
+
+impl Relay {
+    /// * `addr`: where to listen, e.g. `"0.0.0.0:9997"`.
+    pub fn bind<A>(addr: A) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            sessions: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, AcError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// accepts connections for as long as the relay runs, serving each on a
+    /// thread of its own. A failed accept, e.g. with no file descriptors
+    /// left, only loses that connection.
+    pub fn serve(self) -> Result<(), AcError> {
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+
+            // The hello is read on the connection's own thread, so a client
+            // that never sends one holds up nobody else. If the thread can't
+            // start, the connection is dropped like a failed accept.
+            let sessions = Arc::clone(&self.sessions);
+            let _ = thread::Builder::new()
+                .name("ac_lib-relay".to_string())
+                .spawn(move || serve_connection(stream, &sessions));
+        }
+
+        Ok(())
+    }
+}
+
+/// reads a connection's hello, then relays its uplink or subscribes its
+/// coach. Connections that stay silent for `HELLO_TIMEOUT` are dropped.
+fn serve_connection(mut stream: TcpStream, sessions: &Sessions) -> Result<(), AcError> {
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+    let Some((role, session)) = read_frame(&mut stream)? else {
+        return Ok(());
+    };
+    let session = String::from_utf8_lossy(&session).into_owned();
+
+    match role {
+        UPLINK => {
+            stream.set_read_timeout(None)?;
+            relay_uplink(stream, &session, sessions);
+        }
+        DOWNLINK => {
+            stream.set_write_timeout(Some(COACH_WRITE_TIMEOUT))?;
+            lock(sessions)
+                .entry(session)
+                .or_default()
+                .push(Arc::new(Mutex::new(stream)));
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// hands every frame from an uplink on to its session's coaches, dropping
+/// those that hang up or take longer than `COACH_WRITE_TIMEOUT`. Frames are
+/// written with the session list unlocked, so a stalled coach only holds up
+/// its own session's uplinks.
+fn relay_uplink(stream: TcpStream, session: &str, sessions: &Sessions) {
+    let mut stream = BufReader::new(stream);
+
+    while let Ok(Some((tag, payload))) = read_frame(&mut stream) {
+        let coaches = lock(sessions).get(session).cloned().unwrap_or_default();
+
+        let gone: Vec<Coach> = coaches
+            .into_iter()
+            .filter(|coach| {
+                let mut coach = coach.lock().unwrap_or_else(|p| p.into_inner());
+                write_frame(&mut *coach, tag, &payload).is_err()
+            })
+            .collect();
+
+        if !gone.is_empty()
+            && let Some(coaches) = lock(sessions).get_mut(session)
+        {
+            coaches.retain(|coach| !gone.iter().any(|gone| Arc::ptr_eq(gone, coach)));
+        }
+    }
+}
+
+fn lock(sessions: &Sessions) -> MutexGuard<'_, HashMap<String, Vec<Coach>>> {
+    // Writers only ever push or drop whole streams, so a panic leaves no half-made entry.
+    sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Client {
+    /// moves the client onto a receive thread that pushes every packet that
+    /// parses up `uplink`, and delivers the events locally too. Packets the
+    /// relay connection fails to take count as dropped.
+    ///
+    /// * `uplink`: the connection to the relay.
+    /// * `capacity`: how many events are queued for the local receiver.
+    pub fn forward_relay(
+        self,
+        mut uplink: Uplink,
+        capacity: usize,
+    ) -> Result<EventReceiver, AcError> {
+        let stats = Arc::clone(&self.stats);
+        let (sender, receiver) =
+            delivery::channel(capacity, DropPolicy::DropOldest, Arc::clone(&stats));
+
+        let thread = self.spawn_delivery(sender, move |packet, event| {
+            if event.is_some() && uplink.send(packet).is_err() {
+                StatsCounters::add(&stats.dropped, 1);
+            }
+
+            event.is_some()
+        })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+fn write_frame<W>(mut writer: W, tag: u8, payload: &[u8]) -> io::Result<()>
+where
+    W: Write,
+{
+    let len = u16::try_from(payload.len()).map_err(|_| invalid("frame too long"))?;
+    writer.write_all(&[tag])?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// the next frame, or `None` if the stream ends before one starts.
+fn read_frame<R>(mut reader: R) -> io::Result<Option<(u8, Vec<u8>)>>
+where
+    R: Read,
+{
+    let mut header = [0u8; 3];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(why) if why.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(why) => return Err(why),
+    }
+
+    let mut payload = vec![0; usize::from(u16::from_le_bytes([header[1], header[2]]))];
+    reader.read_exact(&mut payload)?;
+    Ok(Some((header[0], payload)))
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
+
+#[cfg(test)]
+mod relay_tests {
+    use std::{net::TcpStream, thread, time::Duration};
+
+    use ac_parser::{CarInfo, Event};
+
+    use crate::relay::{DELTA, Decoder, Downlink, Encoder, KEYFRAME, Relay, Uplink};
+
+    fn car(gear: i32) -> Event {
+        Event::CarInfo(CarInfo {
+            gear,
+            speed_kmh: 40.0 * gear as f32,
+            ..CarInfo::default()
+        })
+    }
+
+    #[test]
+    fn coaches_see_the_rig_through_the_relay() {
+        let (mut encoder, mut decoder) = (Encoder::default(), Decoder::default());
+        let (first, second) = (car(2).to_bytes(), car(3).to_bytes());
+        let (tag, payload) = encoder.encode(&first);
+        assert_eq!(tag, KEYFRAME);
+        assert_eq!(
+            decoder.decode(tag, &payload).expect("bad frame"),
+            Some(first.to_vec())
+        );
+        let (tag, payload) = encoder.encode(&second);
+        assert_eq!(tag, DELTA);
+        assert!(
+            payload.len() < 32,
+            "{} bytes for a two field change",
+            payload.len()
+        );
+        assert_eq!(
+            decoder.decode(tag, &payload).expect("bad frame"),
+            Some(second.to_vec())
+        );
+        assert_eq!(
+            Decoder::default().decode(tag, &payload).expect("bad frame"),
+            None
+        );
+
+        let relay = Relay::bind("127.0.0.1:0").expect("failed to bind relay");
+        let addr = relay.local_addr().expect("no local addr");
+        thread::spawn(move || relay.serve());
+
+        // Connects and never says hello; everyone after it must still get in.
+        let _silent = TcpStream::connect(addr).expect("failed to connect");
+        let mut coach = Downlink::connect(addr, "practice").expect("failed to subscribe");
+        // The relay registers the coach on the connection's own thread.
+        thread::sleep(Duration::from_millis(100));
+        let mut rig = Uplink::connect(addr, "practice").expect("failed to open uplink");
+        for gear in 1..=3 {
+            rig.send(&car(gear).to_bytes()).expect("failed to send");
+        }
+
+        for gear in 1..=3 {
+            let event = coach.recv().expect("failed to receive");
+            assert!(matches!(event, Some(Event::CarInfo(car)) if car.gear == gear));
+        }
+        drop(rig);
+    }
+}
@@ -0,0 +1,116 @@
+//! Finding the game behind a host name. A name given to `Client::new` is
+//! resolved again per the client's retry policy while DNS is slow or comes
+//! back empty, and when it resolves to several addresses, say IPv6 and IPv4
+//! or a stale record next to the current one, each is probed with a
+//! handshake and the client keeps the first that answers.
+
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use crate::{Client, NoRetry, RetryPolicy, retry};
+
+/// How long each candidate address gets to answer the probe handshake.
+pub const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// resolves `addr`, retrying per `policy` while resolution fails or yields
+/// no addresses.
+///
+/// * `addr`: the game's host and port.
+/// * `policy`: when to retry.
+pub(crate) fn resolve<A>(addr: &A, policy: &dyn RetryPolicy) -> io::Result<Vec<SocketAddr>>
+where
+    A: ToSocketAddrs,
+{
+    retry::retry(policy, "resolving", || {
+        let candidates: Vec<_> = addr.to_socket_addrs()?.collect();
+        if candidates.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the host resolved to no addresses",
+            ));
+        }
+        Ok(candidates)
+    })
+}
+
+/// connects `socket` to the first candidate it can, skipping any it has no
+/// route to, e.g. IPv6 addresses from an IPv4 socket.
+///
+/// * `socket`: the client's socket.
+/// * `candidates`: the resolved addresses, in resolver order.
+pub(crate) fn connect_any(socket: &UdpSocket, candidates: &[SocketAddr]) -> io::Result<SocketAddr> {
+    let mut last_error = None;
+    for &candidate in candidates {
+        match socket.connect(candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(why) => last_error = Some(why),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::NotFound)))
+}
+
+impl Client {
+    /// the address of the game the client is talking to.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// points the client at the first of `candidates` that answers a single
+    /// handshake within `PROBE_TIMEOUT`. If none answer, say because the
+    /// game isn't running yet, it stays on the one it was connected to.
+    ///
+    /// * `candidates`: the addresses to try, in order.
+    pub(crate) fn probe_candidates(&mut self, candidates: &[SocketAddr]) -> io::Result<()> {
+        let connected = self.remote;
+        let retry = std::mem::replace(&mut self.retry, Box::new(NoRetry));
+
+        let answered = candidates.iter().copied().find(|&candidate| {
+            self.socket.connect(candidate).is_ok() && self.handshake_packet(PROBE_TIMEOUT).is_ok()
+        });
+
+        self.retry = retry;
+        self.remote = answered.unwrap_or(connected);
+        self.socket.connect(self.remote)
+    }
+}
+
+#[cfg(test)]
+mod resolve_tests {
+    use std::{net::UdpSocket, thread};
+
+    use ac_parser::{Device, Event, HandshakeResponse};
+
+    use crate::{Client, NoRetry, resolve::resolve, testing::MockServer};
+
+    #[test]
+    fn the_first_candidate_that_answers_is_kept() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let live = server.local_addr().expect("no local addr");
+        let closed = UdpSocket::bind("127.0.0.1:0").expect("bound");
+        let dead = closed.local_addr().expect("no local addr");
+        drop(closed);
+
+        let candidates = resolve(&[dead, live].as_slice(), &NoRetry).expect("resolved");
+        assert_eq!(candidates, [dead, live]);
+
+        let game = thread::spawn(move || {
+            let (_, client) = server.recv_operation().expect("no probe");
+            let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+            server
+                .send_event(&handshake, client)
+                .expect("failed to send");
+        });
+
+        let mut client = Client::with_retry(dead, Device::default(), NoRetry).expect("connected");
+        client.probe_candidates(&candidates).expect("probed");
+        game.join().expect("mock game panicked");
+        assert_eq!(client.remote_addr(), live);
+
+        let empty: &[std::net::SocketAddr] = &[];
+        assert!(resolve(&empty, &NoRetry).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! How many times, and how far apart, the client retries connecting,
+//! handshaking and resubscribing. Embedders can supply their own `RetryPolicy`
+//! to match the backoff behavior of the rest of their service.
+
+use std::{fmt::Display, thread, time::Duration};
+
+use exponential_backoff::Backoff;
+
+/// Decides whether, and after how long, to retry a failed operation.
+pub trait RetryPolicy: Send + Sync {
+    /// how long to wait before the given retry, or `None` to give up.
+    ///
+    /// * `retry`: which retry this is, starting at 1 after the first failure.
+    fn delay(&self, retry: u32) -> Option<Duration>;
+}
+
+/// Retries with exponentially growing, jittered waits.
+#[derive(Debug, Clone)]
+pub struct Exponential {
+    backoff: Backoff,
+}
+
+impl Exponential {
+    /// * `max_attempts`: how many times to try in total, including the first.
+    /// * `min`: the wait before the first retry.
+    /// * `max`: the longest wait between two attempts.
+    pub fn new(max_attempts: u32, min: Duration, max: Duration) -> Self {
+        Self {
+            backoff: Backoff::new(max_attempts, min, max),
+        }
+    }
+}
+
+impl Default for Exponential {
+    /// three attempts, waiting one to ten seconds between them.
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(1), Duration::from_secs(10))
+    }
+}
+
+impl RetryPolicy for Exponential {
+    fn delay(&self, retry: u32) -> Option<Duration> {
+        // The backoff yields one wait per attempt, and `None` for the last one.
+        self.backoff
+            .iter()
+            .nth(retry.checked_sub(1)? as usize)
+            .flatten()
+    }
+}
+
+/// Retries a set number of times with the same wait in between.
+///
+/// * `retries`: how many times to retry after the first failure.
+/// * `delay`: the wait before each retry.
+#[derive(Debug, Clone, Copy)]
+pub struct Fixed {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl RetryPolicy for Fixed {
+    fn delay(&self, retry: u32) -> Option<Duration> {
+        (retry <= self.retries).then_some(self.delay)
+    }
+}
+
+/// Fails on the first error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn delay(&self, _retry: u32) -> Option<Duration> {
+        None
+    }
+}
+
+/// runs `op` until it succeeds or `policy` gives up, returning the last error.
+///
+/// * `policy`: when to retry.
+/// * `what`: what's being attempted, for the retry log line.
+/// * `op`: the operation.
+pub(crate) fn retry<T, E, F>(policy: &dyn RetryPolicy, what: &str, mut op: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Result<T, E>,
+{
+    let mut retry = 0;
+
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(why) => {
+                retry += 1;
+
+                match policy.delay(retry) {
+                    Some(wait) => {
+                        eprintln!("Error {what}: {why}, retrying...");
+                        thread::sleep(wait);
+                    }
+                    None => return Err(why),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use std::time::Duration;
+
+    use crate::retry::{Exponential, Fixed, NoRetry, RetryPolicy, retry};
+
+    #[test]
+    fn policies_bound_their_retries() {
+        let exponential = Exponential::new(3, Duration::from_millis(10), Duration::from_secs(1));
+        assert!(exponential.delay(1).is_some());
+        assert!(exponential.delay(2).is_some());
+        assert_eq!(exponential.delay(3), None, "three attempts is two retries");
+        assert_eq!(exponential.delay(0), None);
+
+        let fixed = Fixed {
+            retries: 2,
+            delay: Duration::from_millis(5),
+        };
+        assert_eq!(fixed.delay(2), Some(Duration::from_millis(5)));
+        assert_eq!(fixed.delay(3), None);
+
+        assert_eq!(NoRetry.delay(1), None);
+    }
+
+    #[test]
+    fn retry_stops_at_success_or_when_the_policy_gives_up() {
+        let fixed = Fixed {
+            retries: 3,
+            delay: Duration::ZERO,
+        };
+
+        let mut attempts = 0;
+        let res = retry(&fixed, "testing", || {
+            attempts += 1;
+            if attempts < 3 {
+                Err("not yet")
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res, Ok(3));
+
+        let mut attempts = 0;
+        let res: Result<(), _> = retry(&NoRetry, "testing", || {
+            attempts += 1;
+            Err("never")
+        });
+        assert_eq!(res, Err("never"));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,146 @@
+//! Staying connected while the network changes underneath the client. Phones
+//! roam between access points constantly during a long session, and the old
+//! socket's local address goes with the old interface. When a receive thread
+//! sees a failure like that, the default `RecoveryHook` has the client rebind
+//! its socket, handshake and resubscribe, and a `NetworkChanged` goes to the
+//! client's network hook.
+
+use std::{error::Error, io, net::SocketAddr, sync::Mutex};
+
+use crate::{AcError, Client, stats::StatsCounters};
+
+/// Called whenever the client rebinds after a network change.
+pub(crate) type NetworkHook = Box<dyn FnMut(&NetworkChanged) + Send>;
+
+/// The client rebound its socket after the network changed.
+///
+/// * `cause`: the socket error that gave the change away.
+/// * `before`: the local address the old socket was bound to, if it still had one.
+/// * `after`: the local address of the new socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkChanged {
+    pub cause: io::ErrorKind,
+    pub before: Option<SocketAddr>,
+    pub after: SocketAddr,
+}
+
+impl Client {
+    /// runs `hook` every time a receive thread rebinds the socket after a
+    /// network change, e.g. to show a reconnecting banner.
+    ///
+    /// * `hook`: called with the change, on the receive thread.
+    pub fn on_network_change<F>(&mut self, hook: F)
+    where
+        F: FnMut(&NetworkChanged) + Send + 'static,
+    {
+        self.network_hook = Mutex::new(Some(Box::new(hook)));
+    }
+
+    /// recreates the socket, handshakes and resubscribes, then reports the change.
+    /// The server answers the handshake with a fresh `HandshakeResponse`.
+    ///
+    /// * `error`: the failure that gave the network change away.
+    pub(crate) fn rebind(&mut self, error: &(dyn Error + 'static)) -> Result<(), AcError> {
+        let before = self.local_addr().ok();
+
+        self.recreate_socket()?;
+        self.resubscribe()?;
+        StatsCounters::add(&self.stats.network_changes, 1);
+
+        let cause = match error.downcast_ref::<AcError>() {
+            Some(AcError::Io(why)) => Some(why.kind()),
+            _ => error.downcast_ref::<io::Error>().map(io::Error::kind),
+        };
+        let changed = NetworkChanged {
+            cause: cause.unwrap_or(io::ErrorKind::Other),
+            before,
+            after: self.local_addr()?,
+        };
+        if let Ok(mut hook) = self.network_hook.lock()
+            && let Some(hook) = hook.as_mut()
+        {
+            hook(&changed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod roaming_tests {
+    use std::{io, sync::mpsc, time::Duration};
+
+    use ac_parser::{Device, Event, LapInfo, Operation};
+
+    use crate::{
+        Client,
+        delivery::DropPolicy,
+        recovery::{DefaultRecovery, Failure, FailureKind, Recovery, RecoveryHook},
+        testing::MockServer,
+    };
+
+    #[test]
+    fn interface_changes_rebind_and_resubscribe() {
+        let down = io::Error::from(io::ErrorKind::NetworkDown);
+        assert_eq!(FailureKind::of_io(&down), FailureKind::NetworkChanged);
+        let failure = Failure {
+            kind: FailureKind::NetworkChanged,
+            error: &down,
+            consecutive: 1,
+        };
+        assert_eq!(
+            DefaultRecovery::default().on_failure(&failure),
+            Recovery::Rebind
+        );
+
+        // A real interface change can't be staged here, so a malformed packet stands in for it.
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let mut client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client.set_recovery(|failure: &Failure<'_>| match failure.kind {
+            FailureKind::Protocol => Recovery::Rebind,
+            _ => Recovery::Abort,
+        });
+        let (changes, changed) = mpsc::channel();
+        client.on_network_change(move |change| {
+            let _ = changes.send(*change);
+        });
+
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, old_addr) = server.recv_operation().expect("no request");
+
+        let receiver = client
+            .deliver(4, DropPolicy::default())
+            .expect("failed to start delivery");
+        server
+            .send_raw(&[0u8; 13], old_addr)
+            .expect("failed to send");
+
+        let (first, new_addr) = server.recv_operation().expect("no handshake");
+        let (second, _) = server.recv_operation().expect("no resubscribe");
+        assert!(matches!(first, Operation::Handshake));
+        assert!(matches!(second, Operation::SubscribeUpdate));
+        assert_ne!(new_addr, old_addr, "a new socket");
+
+        let change = changed
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no network change reported");
+        assert_eq!(change.before, Some(old_addr));
+        assert_eq!(change.after, new_addr);
+
+        server
+            .send_event(&Event::LapInfo(LapInfo::default()), new_addr)
+            .expect("failed to send");
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Some(Event::LapInfo(_))
+        ));
+        assert_eq!(receiver.stats().network_changes, 1);
+        receiver.close().expect("delivery failed");
+    }
+}
@@ -0,0 +1,255 @@
+//! The simplest way to get telemetry: one call connects, handshakes and
+//! subscribes, and the session keeps the latest car state and lap times up to
+//! date in the background.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), ac_client::AcError> {
+//! let session = ac_client::TelemetrySession::start("192.168.1.20:9996")?;
+//! println!("driving {} at {}", session.handshake().car_name, session.handshake().track_name);
+//!
+//! for _ in session.events() {
+//!     if let Some(car) = session.latest() {
+//!         println!("{:.0} km/h, {} laps done", car.speed_kmh, session.laps().len());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    net::ToSocketAddrs,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use ac_analysis::{LapTime, LapTimer, Output, RestartDetector, SessionSnapshot, Stage};
+use ac_parser::{CarInfo, Device, Event, HandshakeResponse, Operation};
+
+use crate::{
+    AcError, Client, HANDSHAKE_TIMEOUT,
+    delivery::{self, DropPolicy, EventReceiver},
+    stats::ClientStats,
+};
+
+/// How many events a session queues before dropping the oldest.
+const SESSION_CAPACITY: usize = 256;
+
+/// What the receive thread keeps up to date for a session.
+///
+/// * `latest`: the newest CarInfo.
+/// * `laps`: every lap completed since the session started.
+/// * `timer`: times the player's laps.
+/// * `restart`: spots the driver restarting, which clears `laps` and `timer`.
+#[derive(Debug, Default)]
+struct State {
+    latest: Option<CarInfo>,
+    laps: Vec<LapTime>,
+    timer: LapTimer,
+    restart: RestartDetector,
+}
+
+/// A connected, subscribed client with its latest state and lap times.
+///
+/// * `handshake`: the server's handshake response.
+/// * `state`: the latest CarInfo and lap times, updated by the receive thread.
+/// * `receiver`: every event, in arrival order.
+pub struct TelemetrySession {
+    handshake: HandshakeResponse,
+    state: Arc<Mutex<State>>,
+    receiver: EventReceiver,
+}
+
+impl TelemetrySession {
+    /// connects to the server as the default device, handshakes, subscribes to
+    /// CarInfo updates and starts receiving in the background.
+    ///
+    /// * `remote_addr`: the AC server's telemetry address.
+    pub fn start<A>(remote_addr: A) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::resume(remote_addr, SessionSnapshot::default())
+    }
+
+    /// starts a session like `start`, carrying on the lap history and bests of
+    /// an earlier one, e.g. after the OS killed the app mid-session.
+    ///
+    /// * `remote_addr`: the AC server's telemetry address.
+    /// * `snapshot`: what the earlier session's `snapshot()` returned.
+    pub fn resume<A>(remote_addr: A, snapshot: SessionSnapshot) -> Result<Self, AcError>
+    where
+        A: ToSocketAddrs,
+    {
+        let client = Client::new(remote_addr, Device::default())?;
+        let (_, handshake) = client.handshake_packet(HANDSHAKE_TIMEOUT)?;
+        client.send_message(Operation::SubscribeUpdate)?;
+
+        let state = Arc::new(Mutex::new(State {
+            latest: None,
+            timer: snapshot.lap_timer(),
+            laps: snapshot.laps,
+            restart: RestartDetector::new(),
+        }));
+        let (sender, receiver) = delivery::channel(
+            SESSION_CAPACITY,
+            DropPolicy::DropOldest,
+            Arc::clone(&client.stats),
+        );
+
+        let shared = Arc::clone(&state);
+        let start = Instant::now();
+        let mut outputs = Vec::new();
+
+        let thread = client.spawn_delivery(sender, move |_, event| {
+            if let Some(event) = event {
+                let mut state = shared.lock().unwrap_or_else(|p| p.into_inner());
+                if state.restart.observe(start.elapsed(), event).is_some() {
+                    state.laps.clear();
+                    state.timer.reset();
+                }
+                state.timer.process(start.elapsed(), event, &mut outputs);
+
+                if let Event::CarInfo(car) = event {
+                    state.latest = Some(car.clone());
+                }
+                for output in outputs.drain(..) {
+                    if let Output::Lap(lap) = output {
+                        state.laps.push(lap);
+                    }
+                }
+            }
+
+            true
+        })?;
+
+        Ok(Self {
+            handshake,
+            state,
+            receiver: receiver.with_thread(thread),
+        })
+    }
+
+    /// the server's handshake response: car, driver and track.
+    pub fn handshake(&self) -> &HandshakeResponse {
+        &self.handshake
+    }
+
+    /// the newest CarInfo, once one has arrived.
+    pub fn latest(&self) -> Option<CarInfo> {
+        self.lock().latest.clone()
+    }
+
+    /// every lap completed since the session started or the driver last
+    /// restarted it, oldest first.
+    pub fn laps(&self) -> Vec<LapTime> {
+        self.lock().laps.clone()
+    }
+
+    /// the fastest valid lap so far.
+    pub fn best_lap(&self) -> Option<Duration> {
+        self.lock()
+            .laps
+            .iter()
+            .filter(|lap| lap.validity.is_valid())
+            .map(|lap| lap.time)
+            .min()
+    }
+
+    /// how many times the driver has restarted since the session started.
+    /// Laps and bests only go back to the latest restart.
+    pub fn restarts(&self) -> u64 {
+        self.lock().restart.restarts()
+    }
+
+    /// the session's timing state, to save and pass to `resume` later.
+    pub fn snapshot(&self) -> SessionSnapshot {
+        let state = self.lock();
+        SessionSnapshot {
+            laps: state.laps.clone(),
+            lap_count: state.timer.lap_count(),
+        }
+    }
+
+    /// every event as it arrives, blocking until the next one. Ends if the
+    /// receive thread stops.
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        std::iter::from_fn(|| self.receiver.recv())
+    }
+
+    /// the next event, if one arrives within `timeout`.
+    ///
+    /// * `timeout`: the longest to wait.
+    pub fn next_event(&self, timeout: Duration) -> Option<Event> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    pub fn stats(&self) -> ClientStats {
+        self.receiver.stats()
+    }
+
+    /// stops receiving, returning the socket error that stopped it early, if any.
+    pub fn close(self) -> Result<(), AcError> {
+        self.receiver.close()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use std::{thread, time::Duration};
+
+    use ac_parser::{CarInfo, Event, HandshakeResponse, Name, Operation};
+
+    use crate::{session::TelemetrySession, testing::MockServer};
+
+    #[test]
+    fn session_tracks_latest_state_and_laps() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+
+        let server_thread = thread::spawn(move || {
+            let (_, client) = server.recv_operation().expect("no handshake");
+            let handshake = HandshakeResponse {
+                track_name: Name::from("monza").expect("short name"),
+                ..HandshakeResponse::default()
+            };
+            server
+                .send_event(&Event::HandshakeResponse(handshake), client)
+                .expect("failed to send");
+
+            let (operation, client) = server.recv_operation().expect("no subscribe");
+            assert!(matches!(operation, Operation::SubscribeUpdate));
+
+            for (lap_count, last_lap) in [(0, 0), (1, 95_000), (1, 95_000)] {
+                let car = CarInfo {
+                    lap_count,
+                    last_lap,
+                    ..CarInfo::default()
+                };
+                server
+                    .send_event(&Event::CarInfo(car), client)
+                    .expect("failed to send");
+            }
+        });
+
+        let session = TelemetrySession::start(addr).expect("failed to start");
+        assert_eq!(session.handshake().track_name.as_str(), "monza");
+
+        let events = session
+            .events()
+            .take(3)
+            .filter(|event| matches!(event, Event::CarInfo(_)))
+            .count();
+        assert_eq!(events, 3);
+        server_thread.join().expect("mock server panicked");
+
+        assert_eq!(session.latest().map(|car| car.lap_count), Some(1));
+        assert_eq!(session.laps().len(), 1);
+        assert_eq!(session.best_lap(), Some(Duration::from_secs(95)));
+        assert!(session.next_event(Duration::from_millis(10)).is_none());
+        session.close().expect("session failed");
+    }
+}
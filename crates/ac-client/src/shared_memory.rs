@@ -0,0 +1,862 @@
+//! Telemetry from AC's shared memory, for apps running on the same Windows PC
+//! as the game. AC publishes three pages, `acpmf_physics`, `acpmf_graphics`
+//! and `acpmf_static`, rewritten every physics step with no handshake or
+//! socket in between. They carry more than UDP does (tyre temperatures and
+//! wear, fuel, damage, flags, session state), and each page's `packet_id`
+//! says whether it changed since the last poll.
+//!
+//! Polled frames become the same `Event::CarInfo` a UDP client yields, so
+//! lap timers, pipelines and sinks take either source unchanged.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), ac_client::AcError> {
+//! let mut pages = ac_client::SharedMemory::open()?;
+//! let info = pages.static_info()?;
+//! println!("driving {} at {}", info.car_model, info.track);
+//!
+//! loop {
+//!     if let Some(frame) = pages.poll()? {
+//!         let physics = &frame.physics;
+//!         println!("{:.0} km/h, tyres {:?} °C", physics.speed_kmh, physics.tyre_core_temperature);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! The pages only exist on Windows; elsewhere `open` fails with `Unsupported`.
+
+use std::{io, sync::Arc, thread, time::Duration};
+
+use ac_parser::{CarInfo, Event};
+
+use crate::{
+    AcError,
+    delivery::{self, DropPolicy, EventReceiver},
+    stats::StatsCounters,
+};
+
+/// How long a polling thread sleeps while the physics page hasn't changed.
+/// AC steps physics every 3 ms or so; Windows may sleep longer.
+const PAGE_POLL: Duration = Duration::from_millis(2);
+
+/// How many times a page is re-read when AC rewrote it mid-copy.
+const TORN_RETRIES: usize = 3;
+
+const PHYSICS_PAGE: &str = "Local\\acpmf_physics";
+const GRAPHICS_PAGE: &str = "Local\\acpmf_graphics";
+const STATIC_PAGE: &str = "Local\\acpmf_static";
+
+/// Bytes of each page decoded here. AC's pages are longer, with fields
+/// added by later versions after these.
+const PHYSICS_LEN: usize = 368;
+const GRAPHICS_LEN: usize = 284;
+const STATIC_LEN: usize = 452;
+
+/// The physics page: the car's state, every physics step.
+/// Per-wheel arrays are front left, front right, rear left, rear right.
+///
+/// * `packet_id`: bumped by AC on every write.
+/// * `gear`: 0 is reverse, 1 neutral, 2 first.
+/// * `acc_g`: G forces, lateral, vertical, longitudinal.
+/// * `wheel_slip`, `wheel_load`, `wheels_pressure`: per wheel; load in N, pressure in psi.
+/// * `tyre_wear`, `tyre_core_temperature`: per wheel; temperatures in °C.
+/// * `suspension_travel`: per wheel, in m.
+/// * `drs`, `tc`, `abs`: 0 when off, otherwise the system's setting.
+/// * `heading`, `pitch`, `roll`: in radians.
+/// * `car_damage`: front, rear, left, right and centre.
+/// * `number_of_tyres_out`: wheels off the track, for cut detection.
+/// * `air_temp`, `road_temp`, `brake_temp`: in °C.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Physics {
+    pub packet_id: i32,
+    pub gas: f32,
+    pub brake: f32,
+    pub clutch: f32,
+    pub fuel: f32,
+    pub gear: i32,
+    pub rpm: i32,
+    pub steer_angle: f32,
+    pub speed_kmh: f32,
+    pub velocity: [f32; 3],
+    pub acc_g: [f32; 3],
+    pub wheel_slip: [f32; 4],
+    pub wheel_load: [f32; 4],
+    pub wheels_pressure: [f32; 4],
+    pub wheel_angular_speed: [f32; 4],
+    pub tyre_wear: [f32; 4],
+    pub tyre_dirty_level: [f32; 4],
+    pub tyre_core_temperature: [f32; 4],
+    pub camber_rad: [f32; 4],
+    pub suspension_travel: [f32; 4],
+    pub drs: f32,
+    pub tc: f32,
+    pub abs: f32,
+    pub heading: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub cg_height: f32,
+    pub car_damage: [f32; 5],
+    pub number_of_tyres_out: i32,
+    pub pit_limiter_on: bool,
+    pub turbo_boost: f32,
+    pub air_temp: f32,
+    pub road_temp: f32,
+    pub brake_temp: [f32; 4],
+}
+
+impl Physics {
+    /// decodes the physics page.
+    ///
+    /// * `page`: the page's bytes, at least the decoded layout long.
+    pub fn from_bytes(page: &[u8]) -> Result<Self, AcError> {
+        let mut page = Page::new(page);
+
+        let packet_id = page.i32()?;
+        let gas = page.f32()?;
+        let brake = page.f32()?;
+        let fuel = page.f32()?;
+        let gear = page.i32()?;
+        let rpm = page.i32()?;
+        let steer_angle = page.f32()?;
+        let speed_kmh = page.f32()?;
+        let velocity = page.floats()?;
+        let acc_g = page.floats()?;
+        let wheel_slip = page.floats()?;
+        let wheel_load = page.floats()?;
+        let wheels_pressure = page.floats()?;
+        let wheel_angular_speed = page.floats()?;
+        let tyre_wear = page.floats()?;
+        let tyre_dirty_level = page.floats()?;
+        let tyre_core_temperature = page.floats()?;
+        let camber_rad = page.floats()?;
+        let suspension_travel = page.floats()?;
+        let drs = page.f32()?;
+        let tc = page.f32()?;
+        let heading = page.f32()?;
+        let pitch = page.f32()?;
+        let roll = page.f32()?;
+        let cg_height = page.f32()?;
+        let car_damage = page.floats()?;
+        let number_of_tyres_out = page.i32()?;
+        let pit_limiter_on = page.bool()?;
+        let abs = page.f32()?;
+        // kersCharge, kersInput, autoShifterOn, rideHeight[2]
+        page.skip(5)?;
+        let turbo_boost = page.f32()?;
+        // ballast, airDensity
+        page.skip(2)?;
+        let air_temp = page.f32()?;
+        let road_temp = page.f32()?;
+        // localAngularVel[3], finalFF, performanceMeter, engineBrake, the ERS
+        // levels and flags, kersCurrentKJ, drsAvailable, drsEnabled
+        page.skip(13)?;
+        let brake_temp = page.floats()?;
+        let clutch = page.f32()?;
+
+        Ok(Self {
+            packet_id,
+            gas,
+            brake,
+            clutch,
+            fuel,
+            gear,
+            rpm,
+            steer_angle,
+            speed_kmh,
+            velocity,
+            acc_g,
+            wheel_slip,
+            wheel_load,
+            wheels_pressure,
+            wheel_angular_speed,
+            tyre_wear,
+            tyre_dirty_level,
+            tyre_core_temperature,
+            camber_rad,
+            suspension_travel,
+            drs,
+            tc,
+            abs,
+            heading,
+            pitch,
+            roll,
+            cg_height,
+            car_damage,
+            number_of_tyres_out,
+            pit_limiter_on,
+            turbo_boost,
+            air_temp,
+            road_temp,
+            brake_temp,
+        })
+    }
+}
+
+/// Whether the game is running a session, as the graphics page reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameStatus {
+    #[default]
+    Off,
+    Replay,
+    Live,
+    Pause,
+}
+
+impl GameStatus {
+    fn from_i32(status: i32) -> Self {
+        match status {
+            1 => GameStatus::Replay,
+            2 => GameStatus::Live,
+            3 => GameStatus::Pause,
+            _ => GameStatus::Off,
+        }
+    }
+}
+
+/// The kind of session being driven.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionType {
+    #[default]
+    Unknown,
+    Practice,
+    Qualify,
+    Race,
+    Hotlap,
+    TimeAttack,
+    Drift,
+    Drag,
+}
+
+impl SessionType {
+    fn from_i32(session: i32) -> Self {
+        match session {
+            0 => SessionType::Practice,
+            1 => SessionType::Qualify,
+            2 => SessionType::Race,
+            3 => SessionType::Hotlap,
+            4 => SessionType::TimeAttack,
+            5 => SessionType::Drift,
+            6 => SessionType::Drag,
+            _ => SessionType::Unknown,
+        }
+    }
+}
+
+/// The flag shown to the player.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Flag {
+    #[default]
+    None,
+    Blue,
+    Yellow,
+    Black,
+    White,
+    Checkered,
+    Penalty,
+}
+
+impl Flag {
+    fn from_i32(flag: i32) -> Self {
+        match flag {
+            1 => Flag::Blue,
+            2 => Flag::Yellow,
+            3 => Flag::Black,
+            4 => Flag::White,
+            5 => Flag::Checkered,
+            6 => Flag::Penalty,
+            _ => Flag::None,
+        }
+    }
+}
+
+/// The graphics page: session and timing state, every frame.
+///
+/// * `packet_id`: bumped by AC on every write.
+/// * `current_time`, `last_time`, `best_time`, `split`: lap times as the HUD shows them.
+/// * `current_lap`, `last_lap`, `best_lap`: the same in ms.
+/// * `completed_laps`, `position`: the player's laps done and race position.
+/// * `session_time_left`: in ms; negative in sessions without a time limit.
+/// * `distance_traveled`: in m since the session started.
+/// * `last_sector_time`: in ms.
+/// * `normalized_car_position`: 0 to 1 around the lap.
+/// * `car_coordinates`: world position, in m.
+/// * `penalty_time`: seconds of penalty to serve.
+/// * `surface_grip`: the track's grip, 1 being fully rubbered in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Graphics {
+    pub packet_id: i32,
+    pub status: GameStatus,
+    pub session: SessionType,
+    pub current_time: String,
+    pub last_time: String,
+    pub best_time: String,
+    pub split: String,
+    pub completed_laps: i32,
+    pub position: i32,
+    pub current_lap: i32,
+    pub last_lap: i32,
+    pub best_lap: i32,
+    pub session_time_left: f32,
+    pub distance_traveled: f32,
+    pub is_in_pit: bool,
+    pub current_sector_index: i32,
+    pub last_sector_time: i32,
+    pub number_of_laps: i32,
+    pub tyre_compound: String,
+    pub normalized_car_position: f32,
+    pub car_coordinates: [f32; 3],
+    pub penalty_time: f32,
+    pub flag: Flag,
+    pub is_in_pit_lane: bool,
+    pub surface_grip: f32,
+}
+
+impl Graphics {
+    /// decodes the graphics page.
+    ///
+    /// * `page`: the page's bytes, at least the decoded layout long.
+    pub fn from_bytes(page: &[u8]) -> Result<Self, AcError> {
+        let mut page = Page::new(page);
+
+        let packet_id = page.i32()?;
+        let status = GameStatus::from_i32(page.i32()?);
+        let session = SessionType::from_i32(page.i32()?);
+        let current_time = page.wide(15)?;
+        let last_time = page.wide(15)?;
+        let best_time = page.wide(15)?;
+        let split = page.wide(15)?;
+        let completed_laps = page.i32()?;
+        let position = page.i32()?;
+        let current_lap = page.i32()?;
+        let last_lap = page.i32()?;
+        let best_lap = page.i32()?;
+        let session_time_left = page.f32()?;
+        let distance_traveled = page.f32()?;
+        let is_in_pit = page.bool()?;
+        let current_sector_index = page.i32()?;
+        let last_sector_time = page.i32()?;
+        let number_of_laps = page.i32()?;
+        let tyre_compound = page.wide(33)?;
+        // replayTimeMultiplier
+        page.skip(1)?;
+        let normalized_car_position = page.f32()?;
+        let car_coordinates = page.floats()?;
+        let penalty_time = page.f32()?;
+        let flag = Flag::from_i32(page.i32()?);
+        // idealLineOn
+        page.skip(1)?;
+        let is_in_pit_lane = page.bool()?;
+        let surface_grip = page.f32()?;
+
+        Ok(Self {
+            packet_id,
+            status,
+            session,
+            current_time,
+            last_time,
+            best_time,
+            split,
+            completed_laps,
+            position,
+            current_lap,
+            last_lap,
+            best_lap,
+            session_time_left,
+            distance_traveled,
+            is_in_pit,
+            current_sector_index,
+            last_sector_time,
+            number_of_laps,
+            tyre_compound,
+            normalized_car_position,
+            car_coordinates,
+            penalty_time,
+            flag,
+            is_in_pit_lane,
+            surface_grip,
+        })
+    }
+}
+
+/// The static page: what doesn't change within a session, written when one
+/// starts.
+///
+/// * `sm_version`, `ac_version`: the shared memory layout and game versions.
+/// * `number_of_sessions`, `num_cars`: in the event.
+/// * `max_torque`, `max_power`: in Nm and W.
+/// * `max_fuel`: in l.
+/// * `suspension_max_travel`, `tyre_radius`: per wheel, in m.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StaticInfo {
+    pub sm_version: String,
+    pub ac_version: String,
+    pub number_of_sessions: i32,
+    pub num_cars: i32,
+    pub car_model: String,
+    pub track: String,
+    pub player_name: String,
+    pub player_surname: String,
+    pub player_nick: String,
+    pub sector_count: i32,
+    pub max_torque: f32,
+    pub max_power: f32,
+    pub max_rpm: i32,
+    pub max_fuel: f32,
+    pub suspension_max_travel: [f32; 4],
+    pub tyre_radius: [f32; 4],
+}
+
+impl StaticInfo {
+    /// decodes the static page.
+    ///
+    /// * `page`: the page's bytes, at least the decoded layout long.
+    pub fn from_bytes(page: &[u8]) -> Result<Self, AcError> {
+        let mut page = Page::new(page);
+
+        Ok(Self {
+            sm_version: page.wide(15)?,
+            ac_version: page.wide(15)?,
+            number_of_sessions: page.i32()?,
+            num_cars: page.i32()?,
+            car_model: page.wide(33)?,
+            track: page.wide(33)?,
+            player_name: page.wide(33)?,
+            player_surname: page.wide(33)?,
+            player_nick: page.wide(33)?,
+            sector_count: page.i32()?,
+            max_torque: page.f32()?,
+            max_power: page.f32()?,
+            max_rpm: page.i32()?,
+            max_fuel: page.f32()?,
+            suspension_max_travel: page.floats()?,
+            tyre_radius: page.floats()?,
+        })
+    }
+}
+
+/// One poll's worth of changing state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SharedFrame {
+    pub physics: Physics,
+    pub graphics: Graphics,
+}
+
+impl SharedFrame {
+    /// the frame as the CarInfo a UDP client would have received. Fields only
+    /// UDP carries, such as the ABS and TC activity lights, are left zeroed.
+    pub fn car_info(&self) -> CarInfo {
+        let (physics, graphics) = (&self.physics, &self.graphics);
+        let ms = |ms: i32| ms.max(0) as u32;
+
+        CarInfo {
+            speed_kmh: physics.speed_kmh,
+            speed_mph: physics.speed_kmh / 1.609_344,
+            speed_ms: physics.speed_kmh / 3.6,
+            is_abs_enabled: physics.abs > 0.0,
+            is_tc_enabled: physics.tc > 0.0,
+            is_in_pit: graphics.is_in_pit,
+            accg_horizontal: physics.acc_g[0],
+            accg_vertical: physics.acc_g[1],
+            accg_frontal: physics.acc_g[2],
+            lap_time: ms(graphics.current_lap),
+            last_lap: ms(graphics.last_lap),
+            best_lap: ms(graphics.best_lap),
+            lap_count: ms(graphics.completed_laps),
+            gas: physics.gas,
+            brake: physics.brake,
+            clutch: physics.clutch,
+            engine_rpm: physics.rpm as f32,
+            steer: physics.steer_angle,
+            gear: physics.gear,
+            cg_height: physics.cg_height,
+            wheel_angular_speed: physics.wheel_angular_speed,
+            tyre_slip: physics.wheel_slip,
+            load: physics.wheel_load,
+            tyre_dirty_level: physics.tyre_dirty_level,
+            camber_rad: physics.camber_rad,
+            car_pos_normalized: graphics.normalized_car_position,
+            car_coordinates: graphics.car_coordinates,
+            ..CarInfo::default()
+        }
+    }
+}
+
+impl From<SharedFrame> for Event {
+    fn from(frame: SharedFrame) -> Self {
+        Event::CarInfo(frame.car_info())
+    }
+}
+
+/// AC's three shared memory pages, mapped read-only.
+///
+/// * `physics`, `graphics`, `statics`: the mapped pages.
+/// * `last_packet`: the physics `packet_id` of the last frame returned.
+/// * `stats`: frames polled, as `packets`, for `deliver`'s receiver.
+pub struct SharedMemory {
+    physics: Mapping,
+    graphics: Mapping,
+    statics: Mapping,
+    last_packet: Option<i32>,
+    stats: Arc<StatsCounters>,
+}
+
+impl SharedMemory {
+    /// maps the pages of the game running on this PC. Fails with `NotFound`
+    /// if AC isn't running, and `Unsupported` off Windows.
+    pub fn open() -> Result<Self, AcError> {
+        Ok(Self {
+            physics: Mapping::open(PHYSICS_PAGE, PHYSICS_LEN)?,
+            graphics: Mapping::open(GRAPHICS_PAGE, GRAPHICS_LEN)?,
+            statics: Mapping::open(STATIC_PAGE, STATIC_LEN)?,
+            last_packet: None,
+            stats: Arc::default(),
+        })
+    }
+
+    /// the static page, as AC last wrote it. Empty until a session is loaded.
+    pub fn static_info(&self) -> Result<StaticInfo, AcError> {
+        StaticInfo::from_bytes(&self.statics.read())
+    }
+
+    /// the current frame, or `None` if the physics page hasn't changed since
+    /// the last one, e.g. while paused or in the menus.
+    pub fn poll(&mut self) -> Result<Option<SharedFrame>, AcError> {
+        let physics = Physics::from_bytes(&consistent_read(&self.physics))?;
+        if self.last_packet == Some(physics.packet_id) {
+            return Ok(None);
+        }
+
+        let graphics = Graphics::from_bytes(&consistent_read(&self.graphics))?;
+        self.last_packet = Some(physics.packet_id);
+        StatsCounters::add(&self.stats.packets, 1);
+
+        Ok(Some(SharedFrame { physics, graphics }))
+    }
+
+    /// moves the pages onto a polling thread that delivers every new frame as
+    /// `Event::CarInfo` over a bounded channel, like `Client::deliver`.
+    ///
+    /// * `capacity`: the most events queued at once.
+    /// * `policy`: what to do with new events when full.
+    pub fn deliver(
+        mut self,
+        capacity: usize,
+        policy: DropPolicy,
+    ) -> Result<EventReceiver, AcError> {
+        let (sender, receiver) = delivery::channel(capacity, policy, Arc::clone(&self.stats));
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-shared-memory".to_string())
+            .spawn(move || {
+                while !sender.is_closed() {
+                    match self.poll()? {
+                        Some(frame) => {
+                            sender.send(frame.into());
+                        }
+                        None => thread::sleep(PAGE_POLL),
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok(receiver.with_thread(thread))
+    }
+}
+
+#[cfg(feature = "stream")]
+impl SharedMemory {
+    /// moves the pages onto a polling thread that yields every new frame as
+    /// `Event::CarInfo` from a `Stream`, like `Client::into_stream`.
+    ///
+    /// * `capacity`: how many events wait for the stream before the thread does.
+    pub fn into_stream(mut self, capacity: usize) -> Result<crate::EventStream, AcError> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(capacity.max(1));
+        let stats = Arc::clone(&self.stats);
+
+        thread::Builder::new()
+            .name("ac_lib-shared-memory".to_string())
+            .spawn(move || {
+                while !sender.is_closed() {
+                    let event = match self.poll() {
+                        Ok(Some(frame)) => Ok(frame.into()),
+                        Ok(None) => {
+                            thread::sleep(PAGE_POLL);
+                            continue;
+                        }
+                        Err(why) => Err(why),
+                    };
+
+                    let stopped = event.is_err();
+                    if sender.blocking_send(event).is_err() || stopped {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(crate::EventStream { receiver, stats })
+    }
+}
+
+/// a page's bytes, copied again if its `packet_id` moved while copying.
+fn consistent_read(mapping: &Mapping) -> Vec<u8> {
+    let mut bytes = mapping.read();
+    for _ in 0..TORN_RETRIES {
+        if bytes.get(..4) == Some(&mapping.head()[..]) {
+            break;
+        }
+        bytes = mapping.read();
+    }
+
+    bytes
+}
+
+/// Reads fields in order from a page laid out as AC's `#pragma pack(4)` C
+/// structs: little-endian 4-byte numbers and UTF-16 strings.
+struct Page<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Page<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn take(&mut self, needed: usize) -> Result<&'a [u8], AcError> {
+        let field =
+            self.bytes
+                .get(self.offset..self.offset + needed)
+                .ok_or(AcError::Truncated {
+                    offset: self.offset,
+                    needed,
+                })?;
+        self.offset += needed;
+        Ok(field)
+    }
+
+    /// skips `n` 4-byte fields.
+    fn skip(&mut self, n: usize) -> Result<(), AcError> {
+        for _ in 0..n {
+            self.word()?;
+        }
+        Ok(())
+    }
+
+    /// the next 4-byte field, after any padding that aligns it.
+    fn word(&mut self) -> Result<[u8; 4], AcError> {
+        self.offset = self.offset.next_multiple_of(4);
+        Ok(self.take(4)?.try_into().expect("took 4 bytes"))
+    }
+
+    fn i32(&mut self) -> Result<i32, AcError> {
+        self.word().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, AcError> {
+        self.word().map(f32::from_le_bytes)
+    }
+
+    fn bool(&mut self) -> Result<bool, AcError> {
+        Ok(self.i32()? != 0)
+    }
+
+    fn floats<const N: usize>(&mut self) -> Result<[f32; N], AcError> {
+        let mut floats = [0.0; N];
+        for float in &mut floats {
+            *float = self.f32()?;
+        }
+        Ok(floats)
+    }
+
+    /// a NUL-padded `wchar_t[chars]`.
+    fn wide(&mut self, chars: usize) -> Result<String, AcError> {
+        let units: Vec<u16> = self
+            .take(chars * 2)?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+
+        Ok(String::from_utf16_lossy(&units))
+    }
+}
+
+/// A read-only view of one named page.
+#[cfg(windows)]
+struct Mapping {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+    view: windows_sys::Win32::System::Memory::MEMORY_MAPPED_VIEW_ADDRESS,
+    len: usize,
+}
+
+// SAFETY: the handle and view are only read through, and closed once, by
+// whichever thread owns the mapping.
+#[cfg(windows)]
+unsafe impl Send for Mapping {}
+
+#[cfg(windows)]
+impl Mapping {
+    fn open(name: &str, len: usize) -> io::Result<Self> {
+        use windows_sys::Win32::{
+            Foundation::CloseHandle,
+            System::Memory::{FILE_MAP_READ, MapViewOfFile, OpenFileMappingW},
+        };
+
+        let name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+
+        // SAFETY: `name` is NUL-terminated and outlives the call.
+        let handle = unsafe { OpenFileMappingW(FILE_MAP_READ, 0, name.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `handle` is a live file mapping opened for reading.
+        let view = unsafe { MapViewOfFile(handle, FILE_MAP_READ, 0, 0, len) };
+        if view.Value.is_null() {
+            let why = io::Error::last_os_error();
+            // SAFETY: `handle` is live and not used again.
+            unsafe { CloseHandle(handle) };
+            return Err(why);
+        }
+
+        Ok(Self { handle, view, len })
+    }
+
+    /// a copy of the whole view.
+    fn read(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.len];
+        // SAFETY: the view is `len` bytes long and stays mapped while `self` lives.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.view.Value.cast::<u8>(),
+                bytes.as_mut_ptr(),
+                self.len,
+            );
+        }
+        bytes
+    }
+
+    /// the view's first 4 bytes, as they are now.
+    fn head(&self) -> [u8; 4] {
+        // SAFETY: the view is at least 4 bytes long; AC writes it concurrently,
+        // hence the volatile read.
+        unsafe { self.view.Value.cast::<[u8; 4]>().read_volatile() }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        use windows_sys::Win32::{Foundation::CloseHandle, System::Memory::UnmapViewOfFile};
+
+        // SAFETY: both were opened in `open` and are released exactly once.
+        unsafe {
+            UnmapViewOfFile(self.view);
+            CloseHandle(self.handle);
+        }
+    }
+}
+
+/// No pages to map off Windows, so there is never a mapping.
+#[cfg(not(windows))]
+enum Mapping {}
+
+#[cfg(not(windows))]
+impl Mapping {
+    fn open(_name: &str, _len: usize) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "AC shared memory is only published on Windows",
+        ))
+    }
+
+    fn read(&self) -> Vec<u8> {
+        match *self {}
+    }
+
+    fn head(&self) -> [u8; 4] {
+        match *self {}
+    }
+}
+
+#[cfg(test)]
+mod shared_memory_tests {
+    use ac_parser::Event;
+
+    use crate::shared_memory::{
+        Flag, GRAPHICS_LEN, GameStatus, Graphics, PHYSICS_LEN, Physics, STATIC_LEN, SessionType,
+        SharedFrame, StaticInfo,
+    };
+
+    fn put(page: &mut [u8], offset: usize, bytes: &[u8]) {
+        page[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn put_wide(page: &mut [u8], offset: usize, text: &str) {
+        let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        put(page, offset, &bytes);
+    }
+
+    #[test]
+    fn pages_decode_at_ac_offsets_and_convert_to_car_info() {
+        let mut physics = vec![0; PHYSICS_LEN];
+        put(&mut physics, 0, &7i32.to_le_bytes());
+        put(&mut physics, 4, &0.75f32.to_le_bytes());
+        put(&mut physics, 16, &4i32.to_le_bytes());
+        put(&mut physics, 20, &6500i32.to_le_bytes());
+        put(&mut physics, 28, &180.0f32.to_le_bytes());
+        put(&mut physics, 44, &1.5f32.to_le_bytes());
+        put(&mut physics, 164, &92.0f32.to_le_bytes());
+        put(&mut physics, 252, &3.0f32.to_le_bytes());
+        put(&mut physics, 360, &410.0f32.to_le_bytes());
+        put(&mut physics, 364, &0.25f32.to_le_bytes());
+
+        let mut graphics = vec![0; GRAPHICS_LEN];
+        put(&mut graphics, 4, &2i32.to_le_bytes());
+        put(&mut graphics, 8, &2i32.to_le_bytes());
+        put_wide(&mut graphics, 42, "1:52.301");
+        put(&mut graphics, 132, &3i32.to_le_bytes());
+        put(&mut graphics, 144, &112_301i32.to_le_bytes());
+        put_wide(&mut graphics, 176, "Soft");
+        put(&mut graphics, 248, &0.5f32.to_le_bytes());
+        put(&mut graphics, 268, &2i32.to_le_bytes());
+        put(&mut graphics, 280, &0.98f32.to_le_bytes());
+
+        let mut statics = vec![0; STATIC_LEN];
+        put_wide(&mut statics, 68, "ks_mazda_mx5_cup");
+        put_wide(&mut statics, 134, "magione");
+        put(&mut statics, 412, &7500i32.to_le_bytes());
+
+        let physics = Physics::from_bytes(&physics).expect("physics page");
+        assert_eq!((physics.packet_id, physics.gear, physics.rpm), (7, 4, 6500));
+        assert_eq!((physics.gas, physics.clutch), (0.75, 0.25));
+        assert_eq!(physics.tyre_core_temperature[3], 92.0);
+        assert_eq!((physics.abs, physics.brake_temp[3]), (3.0, 410.0));
+
+        let graphics = Graphics::from_bytes(&graphics).expect("graphics page");
+        assert_eq!(graphics.status, GameStatus::Live);
+        assert_eq!(graphics.session, SessionType::Race);
+        assert_eq!(graphics.last_time, "1:52.301");
+        assert_eq!(graphics.tyre_compound, "Soft");
+        assert_eq!((graphics.flag, graphics.surface_grip), (Flag::Yellow, 0.98));
+
+        let statics = StaticInfo::from_bytes(&statics).expect("static page");
+        assert_eq!(statics.car_model, "ks_mazda_mx5_cup");
+        assert_eq!((statics.track.as_str(), statics.max_rpm), ("magione", 7500));
+
+        let Event::CarInfo(car) = Event::from(SharedFrame { physics, graphics }) else {
+            panic!("a frame is a CarInfo");
+        };
+        assert_eq!((car.speed_kmh, car.speed_ms), (180.0, 50.0));
+        assert_eq!(
+            (car.gear, car.engine_rpm, car.accg_horizontal),
+            (4, 6500.0, 1.5)
+        );
+        assert_eq!((car.lap_count, car.last_lap), (3, 112_301));
+        assert!(car.is_abs_enabled);
+        assert_eq!(car.car_pos_normalized, 0.5);
+
+        assert!(Physics::from_bytes(&[0; 100]).is_err_and(|why| why.is_malformed_packet()));
+    }
+}
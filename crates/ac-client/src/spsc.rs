@@ -0,0 +1,166 @@
+//! Lock-free single-producer/single-consumer delivery for latency-sensitive
+//! consumers such as motion rigs. A dedicated thread owns the client and pushes
+//! every parsed event into a wait-free ring buffer, so the consumer never
+//! contends on a lock or wakes an async runtime to get the next packet.
+
+use std::{
+    io,
+    sync::{Arc, atomic::Ordering},
+    thread::{self, JoinHandle},
+};
+
+use rtrb::{Consumer, Producer, PushError, RingBuffer};
+
+use ac_parser::Event;
+
+use crate::{
+    AcError, Client,
+    delivery::POLL_INTERVAL,
+    stats::{ClientStats, StatsCounters},
+};
+
+/// The consuming end of a client's SPSC delivery.
+///
+/// * `consumer`: the ring buffer's read side.
+/// * `stats`: the client's counters, including events dropped because the ring buffer was full.
+/// * `thread`: the receive thread, returning why it stopped.
+pub struct SpscReceiver {
+    consumer: Consumer<Event>,
+    stats: Arc<StatsCounters>,
+    thread: JoinHandle<Result<(), AcError>>,
+}
+
+impl SpscReceiver {
+    /// takes the oldest waiting event, without blocking.
+    pub fn try_recv(&mut self) -> Option<Event> {
+        self.consumer.pop().ok()
+    }
+
+    /// how many events are waiting.
+    pub fn len(&self) -> usize {
+        self.consumer.slots()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+
+    /// how many events were discarded because the consumer fell too far behind.
+    pub fn dropped(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+
+    /// the counters of the client feeding this receiver.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// whether the receive thread has stopped. Events already queued can still be taken.
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_abandoned()
+    }
+
+    /// stops the receive thread and waits for it, returning the socket error that
+    /// stopped it early, if any.
+    pub fn close(self) -> Result<(), AcError> {
+        drop(self.consumer);
+        self.thread
+            .join()
+            .map_err(|_| io::Error::other("spsc receive thread panicked"))?
+    }
+}
+
+impl Client {
+    /// moves the client onto a dedicated receive thread that delivers events over a
+    /// lock-free ring buffer. When the buffer is full, new events are dropped and counted.
+    ///
+    /// Failures go to the client's `RecoveryHook`: by default packets that fail to
+    /// parse are skipped (and captured, if capture is enabled), transient socket
+    /// errors are retried, and anything else stops the thread.
+    ///
+    /// * `capacity`: how many events the ring buffer holds.
+    pub fn into_spsc(self, capacity: usize) -> Result<SpscReceiver, AcError> {
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let (producer, consumer) = RingBuffer::new(capacity);
+        let stats = Arc::clone(&self.stats);
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-spsc".to_string())
+            .spawn(move || self.run_spsc(producer))?;
+
+        Ok(SpscReceiver {
+            consumer,
+            stats,
+            thread,
+        })
+    }
+
+    fn run_spsc(mut self, mut producer: Producer<Event>) -> Result<(), AcError> {
+        let mut failures = 0;
+
+        while !producer.is_abandoned() {
+            let Some(event) = self.recv_next(&mut failures)?.and_then(|r| r.event) else {
+                continue;
+            };
+
+            if let Err(PushError::Full(_)) = producer.push(event) {
+                StatsCounters::add(&self.stats.dropped, 1);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod spsc_tests {
+    use std::time::{Duration, Instant};
+
+    use ac_parser::{CarInfo, Device, Event, Operation};
+
+    use crate::{Client, testing::MockServer};
+
+    #[test]
+    fn events_arrive_in_order_and_overflow_is_counted() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let mut receiver = client.into_spsc(2).expect("failed to start receive thread");
+
+        for gear in 0..3 {
+            let car = CarInfo {
+                gear,
+                ..CarInfo::default()
+            };
+            server
+                .send_event(&Event::CarInfo(car), client_addr)
+                .expect("failed to send");
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while receiver.dropped() == 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let gears = std::iter::from_fn(|| receiver.try_recv())
+            .map(|event| match event {
+                Event::CarInfo(car) => car.gear,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(gears, vec![0, 1]);
+        assert_eq!(receiver.dropped(), 1);
+        assert_eq!(receiver.stats().packets, 3);
+        assert!(receiver.close().is_ok());
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{bandwidth::TrafficMeter, drift::ClockSync};
+
+/// A snapshot of a client's counters.
+///
+/// * `packets`: datagrams received.
+/// * `malformed`: packets that failed to parse.
+/// * `dropped`: events discarded because a consumer fell behind.
+/// * `coalesced`: events replaced by a newer event of the same kind before being consumed.
+/// * `resubscribes`: times a recovery hook had the client handshake and subscribe again.
+/// * `network_changes`: times the client rebound its socket after the network changed.
+/// * `rehandshakes`: times a silent server answered a probe and was subscribed to again.
+/// * `write_errors`: packets a configured recording or sink failed to take.
+/// * `packet_rate`: packets received in the last whole second, the server's actual rate.
+/// * `latency`: how much later than the quickest packets the last CarInfo arrived.
+/// * `drift_ppm`: how much faster our clock runs than the game's, in parts per million.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClientStats {
+    pub packets: u64,
+    pub malformed: u64,
+    pub dropped: u64,
+    pub coalesced: u64,
+    pub resubscribes: u64,
+    pub network_changes: u64,
+    pub rehandshakes: u64,
+    pub write_errors: u64,
+    pub packet_rate: u64,
+    pub latency: Duration,
+    pub drift_ppm: i64,
+}
+
+/// The live counters behind `ClientStats`, shared between a client and whatever
+/// is delivering its events.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCounters {
+    pub(crate) packets: AtomicU64,
+    pub(crate) malformed: AtomicU64,
+    pub(crate) dropped: AtomicU64,
+    pub(crate) coalesced: AtomicU64,
+    pub(crate) resubscribes: AtomicU64,
+    pub(crate) network_changes: AtomicU64,
+    pub(crate) rehandshakes: AtomicU64,
+    pub(crate) write_errors: AtomicU64,
+    pub(crate) traffic: TrafficMeter,
+    pub(crate) latency_us: AtomicU64,
+    pub(crate) drift_ppm: AtomicI64,
+}
+
+impl StatsCounters {
+    pub(crate) fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// publishes the clock estimates after a CarInfo.
+    pub(crate) fn clock(&self, clock: &ClockSync) {
+        let latency = clock.latency().as_micros().try_into().unwrap_or(u64::MAX);
+        self.latency_us.store(latency, Ordering::Relaxed);
+        self.drift_ppm
+            .store(clock.drift_ppm().round() as i64, Ordering::Relaxed);
+    }
+
+    /// the last CarInfo's latency, for taking off arrival times.
+    pub(crate) fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency_us.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        ClientStats {
+            packets: self.packets.load(Ordering::Relaxed),
+            malformed: self.malformed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            resubscribes: self.resubscribes.load(Ordering::Relaxed),
+            network_changes: self.network_changes.load(Ordering::Relaxed),
+            rehandshakes: self.rehandshakes.load(Ordering::Relaxed),
+            write_errors: self.write_errors.load(Ordering::Relaxed),
+            packet_rate: self.traffic.snapshot().packets_in.last,
+            latency: self.latency(),
+            drift_ppm: self.drift_ppm.load(Ordering::Relaxed),
+        }
+    }
+}
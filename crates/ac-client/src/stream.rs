@@ -0,0 +1,192 @@
+//! Events as a `futures::Stream`, for async code that wants combinators like
+//! `filter`, `throttle` or `take_while` over live telemetry. A receive thread
+//! owns the client, recovering from failures as `deliver` does, and hands
+//! each event to the stream over a bounded tokio channel; while the stream
+//! is full the thread waits, and the socket's own buffer takes up the slack.
+
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    thread,
+};
+
+use ac_parser::Event;
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::{
+    AcError, Client,
+    delivery::POLL_INTERVAL,
+    stats::{ClientStats, StatsCounters},
+};
+
+/// A client's events, as a stream. Ends after yielding the error that
+/// stopped the receive thread, if one did; dropping it stops the thread.
+///
+/// * `receiver`: where the receive thread sends events.
+/// * `stats`: the client's counters.
+pub struct EventStream {
+    pub(crate) receiver: mpsc::Receiver<Result<Event, AcError>>,
+    pub(crate) stats: Arc<StatsCounters>,
+}
+
+impl EventStream {
+    /// the counters of the client feeding this stream.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<Event, AcError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Client {
+    /// moves the client onto a receive thread that yields its events as a
+    /// `Stream`. Subscribe first; the stream only receives.
+    ///
+    /// * `capacity`: how many events wait for the stream before the thread does.
+    pub fn into_stream(self, capacity: usize) -> Result<EventStream, AcError> {
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let stats = Arc::clone(&self.stats);
+
+        thread::Builder::new()
+            .name("ac_lib-stream".to_string())
+            .spawn(move || self.run_stream(sender))?;
+
+        Ok(EventStream { receiver, stats })
+    }
+
+    fn run_stream(mut self, sender: mpsc::Sender<Result<Event, AcError>>) {
+        let mut failures = 0;
+
+        while !sender.is_closed() {
+            let event = match self.recv_next(&mut failures) {
+                Ok(received) => match received.and_then(|r| r.event) {
+                    Some(event) => Ok(event),
+                    None => continue,
+                },
+                Err(why) => Err(why),
+            };
+
+            let stopped = event.is_err();
+            if sender.blocking_send(event).is_err() || stopped {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use std::{
+        future,
+        pin::Pin,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use ac_parser::{CarInfo, Device, Event, Operation};
+    use futures_core::Stream;
+
+    use crate::{
+        Client,
+        recovery::{Failure, FailureKind, Recovery},
+        testing::MockServer,
+    };
+
+    #[tokio::test]
+    async fn events_are_yielded_in_order() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let mut stream = client
+            .into_stream(8)
+            .expect("failed to start receive thread");
+        for gear in 1..=3 {
+            let car = Event::CarInfo(CarInfo {
+                gear,
+                ..CarInfo::default()
+            });
+            server
+                .send_event(&car, client_addr)
+                .expect("failed to send");
+        }
+
+        let mut gears = Vec::new();
+        while gears.len() < 3 {
+            let next = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+            match next.expect("stream ended").expect("receive failed") {
+                Event::CarInfo(car) => gears.push(car.gear),
+                other => panic!("unexpected event {other:?}"),
+            }
+        }
+
+        assert_eq!(gears, [1, 2, 3]);
+        assert_eq!(stream.stats().packets, 3);
+    }
+
+    #[tokio::test]
+    async fn streams_end_when_the_client_stops() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let mut client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client.set_recovery(|_: &Failure<'_>| Recovery::Abort);
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let mut stream = client
+            .into_stream(8)
+            .expect("failed to start receive thread");
+        server
+            .send_raw(&[0; 13], client_addr)
+            .expect("failed to send");
+
+        let stopped = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+            .await
+            .expect("the error that stopped it");
+        let why = stopped.expect_err("a malformed packet");
+        assert_eq!(FailureKind::of(&why), FailureKind::Protocol);
+        let after = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        assert!(after.is_none(), "nothing after the error");
+
+        // Dropping a live stream stops its thread, which drops the client.
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        let stream = client
+            .into_stream(8)
+            .expect("failed to start receive thread");
+        let stats = Arc::clone(&stream.stats);
+        drop(stream);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Arc::strong_count(&stats) > 1 {
+            assert!(Instant::now() < deadline, "the receive thread kept running");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Both subscriptions at once. An app showing the player's telemetry next to
+//! everyone's positions wants CarInfo updates and LapInfo spot events from
+//! one client; the packets are told apart by their size, so the two streams
+//! never need to share a channel either. Subscriptions accumulate until a
+//! `Dismiss`, and every resubscribe repeats all of them.
+
+use std::{sync::Arc, thread};
+
+use ac_parser::{Event, Operation};
+
+use crate::{
+    AcError, Client,
+    delivery::{self, DropPolicy, EventReceiver, POLL_INTERVAL},
+};
+
+/// What a client is subscribed to, to repeat when resubscribing.
+///
+/// * `update`: CarInfo updates.
+/// * `spot`: LapInfo spot events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Subscriptions {
+    update: bool,
+    spot: bool,
+}
+
+impl Subscriptions {
+    /// notes an operation sent to the server.
+    ///
+    /// * `operation`: the operation.
+    pub(crate) fn sent(&mut self, operation: Operation) {
+        match operation {
+            Operation::SubscribeUpdate => self.update = true,
+            Operation::SubscribeSpot => self.spot = true,
+            Operation::Dismiss => *self = Self::default(),
+            Operation::Handshake => {}
+        }
+    }
+
+    /// the subscribe operations to repeat, update first.
+    pub(crate) fn operations(self) -> impl Iterator<Item = Operation> {
+        [
+            self.update.then_some(Operation::SubscribeUpdate),
+            self.spot.then_some(Operation::SubscribeSpot),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl Client {
+    /// subscribes to CarInfo updates and LapInfo spot events together.
+    pub fn subscribe_all(&self) -> Result<(), AcError> {
+        self.send_message(Operation::SubscribeUpdate)?;
+        self.send_message(Operation::SubscribeSpot)?;
+
+        Ok(())
+    }
+
+    /// what the client is subscribed to, update first.
+    pub fn subscriptions(&self) -> Vec<Operation> {
+        self.subscriptions_now().operations().collect()
+    }
+
+    /// like `deliver`, with CarInfo and LapInfo in separate channels so a
+    /// flood of one never pushes the other out. Handshake responses go to
+    /// both. The CarInfo receiver owns the receive thread: closing or dropping
+    /// it stops the thread and ends the LapInfo stream too.
+    ///
+    /// * `capacity`: the most events each channel queues.
+    /// * `policy`: what each channel does with new events when full.
+    pub fn deliver_by_kind(
+        mut self,
+        capacity: usize,
+        policy: DropPolicy,
+    ) -> Result<(EventReceiver, EventReceiver), AcError> {
+        let (cars, car_receiver) = delivery::channel(capacity, policy, Arc::clone(&self.stats));
+        let (laps, lap_receiver) = delivery::channel(capacity, policy, Arc::clone(&self.stats));
+        self.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let thread = thread::Builder::new()
+            .name("ac_lib-delivery".to_string())
+            .spawn(move || {
+                let mut failures = 0;
+
+                while !cars.is_closed() {
+                    let Some(received) = self.recv_next(&mut failures)? else {
+                        continue;
+                    };
+                    match received.event {
+                        Some(event @ Event::CarInfo(_)) => {
+                            cars.send(event);
+                        }
+                        Some(event @ Event::LapInfo(_)) => {
+                            laps.send(event);
+                        }
+                        Some(event @ Event::HandshakeResponse(_)) => {
+                            laps.send(event.clone());
+                            cars.send(event);
+                        }
+                        None => {}
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok((car_receiver.with_thread(thread), lap_receiver))
+    }
+
+    /// what the client is subscribed to right now.
+    pub(crate) fn subscriptions_now(&self) -> Subscriptions {
+        self.subscription
+            .lock()
+            .map(|subscriptions| *subscriptions)
+            .unwrap_or_default()
+    }
+
+    /// sends every subscription the client has.
+    pub(crate) fn repeat_subscriptions(&self, subscriptions: Subscriptions) -> Result<(), AcError> {
+        subscriptions
+            .operations()
+            .try_for_each(|operation| self.send_message(operation).map(drop))
+    }
+}
+
+#[cfg(test)]
+mod subscriptions_tests {
+    use std::{thread, time::Duration};
+
+    use ac_parser::{CarInfo, Device, Event, LapInfo, Operation};
+
+    use crate::{Client, delivery::DropPolicy, testing::MockServer};
+
+    #[test]
+    fn both_subscriptions_are_kept_and_split_by_kind() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+
+        client.subscribe_all().expect("failed to subscribe");
+        assert!(matches!(
+            client.subscriptions()[..],
+            [Operation::SubscribeUpdate, Operation::SubscribeSpot]
+        ));
+        client.resubscribe().expect("failed to resubscribe");
+
+        let mut operations = Vec::new();
+        let mut client_addr = None;
+        for _ in 0..5 {
+            let (operation, addr) = server.recv_operation().expect("no operation");
+            operations.push(format!("{operation:?}"));
+            client_addr = Some(addr);
+        }
+        assert_eq!(
+            operations,
+            [
+                "SubscribeUpdate",
+                "SubscribeSpot",
+                "Handshake",
+                "SubscribeUpdate",
+                "SubscribeSpot"
+            ]
+        );
+
+        let (cars, laps) = client
+            .deliver_by_kind(1, DropPolicy::DropOldest)
+            .expect("failed to deliver");
+        let client_addr = client_addr.expect("no client address");
+        let sender = thread::spawn(move || {
+            for lap in 0..3 {
+                let car = Event::CarInfo(CarInfo::default());
+                server
+                    .send_event(&car, client_addr)
+                    .expect("failed to send");
+                let info = Event::LapInfo(LapInfo {
+                    lap,
+                    ..LapInfo::default()
+                });
+                server
+                    .send_event(&info, client_addr)
+                    .expect("failed to send");
+            }
+            server
+        });
+
+        let _server = sender.join().expect("sender panicked");
+        thread::sleep(Duration::from_millis(200));
+        assert!(matches!(cars.try_recv(), Some(Event::CarInfo(_))));
+        assert!(
+            matches!(laps.try_recv(), Some(Event::LapInfo(info)) if info.lap == 2),
+            "CarInfo doesn't push LapInfo out of its channel"
+        );
+        cars.close().expect("delivery failed");
+    }
+}
@@ -0,0 +1,147 @@
+//! A delivery mode that hands on once-per-interval `Summary`s instead of raw
+//! events, for consumers on a battery or bandwidth budget: a watch face, or a
+//! dashboard relaying over a metered link.
+
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, Receiver, RecvTimeoutError, TrySendError},
+    },
+    time::{Duration, Instant},
+};
+
+use ac_analysis::{Summarizer, Summary};
+
+use crate::{
+    AcError, Client, DropPolicy, EventReceiver,
+    delivery::{self, POLL_INTERVAL},
+    stats::{ClientStats, StatsCounters},
+};
+
+/// How many summaries are queued before new ones are dropped.
+const SUMMARY_CAPACITY: usize = 8;
+
+/// The consuming end of a client's summary delivery.
+///
+/// * `summaries`: the summaries, oldest first.
+/// * `receiver`: the receive thread and the client's counters.
+pub struct SummaryReceiver {
+    summaries: Receiver<Summary>,
+    receiver: EventReceiver,
+}
+
+impl SummaryReceiver {
+    /// waits for the next summary. Returns `None` once the receive thread has stopped.
+    pub fn recv(&self) -> Option<Summary> {
+        loop {
+            match self.summaries.recv_timeout(POLL_INTERVAL) {
+                Ok(summary) => return Some(summary),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+
+    /// the next summary, if one arrives within `timeout`.
+    ///
+    /// * `timeout`: the longest to wait.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Summary> {
+        self.summaries.recv_timeout(timeout).ok()
+    }
+
+    /// takes the next summary, if one is queued.
+    pub fn try_recv(&self) -> Option<Summary> {
+        self.summaries.try_recv().ok()
+    }
+
+    /// the counters of the client feeding this receiver; summaries nobody
+    /// took in time count as dropped.
+    pub fn stats(&self) -> ClientStats {
+        self.receiver.stats()
+    }
+
+    /// stops the receive thread and waits for it, returning the socket error that
+    /// stopped it early, if any.
+    pub fn close(self) -> Result<(), AcError> {
+        self.receiver.close()
+    }
+}
+
+impl Client {
+    /// moves the client onto a receive thread that folds every event into
+    /// `summarizer` and delivers only its summaries. A summary is handed on
+    /// once the first CarInfo past the end of its window arrives.
+    ///
+    /// * `summarizer`: the interval and channels to summarize, e.g. `Summarizer::default()`.
+    pub fn deliver_summaries(self, mut summarizer: Summarizer) -> Result<SummaryReceiver, AcError> {
+        let (sender, summaries) = mpsc::sync_channel(SUMMARY_CAPACITY);
+        let stats = Arc::clone(&self.stats);
+        let (unused, receiver) = delivery::channel(1, DropPolicy::DropNewest, Arc::clone(&stats));
+
+        let started = Instant::now();
+        let thread = self.spawn_delivery(unused, move |_, event| {
+            let summary = event.and_then(|event| summarizer.update(started.elapsed(), event));
+            if let Some(summary) = summary
+                && let Err(TrySendError::Full(_)) = sender.try_send(summary)
+            {
+                StatsCounters::add(&stats.dropped, 1);
+            }
+
+            false
+        })?;
+
+        Ok(SummaryReceiver {
+            summaries,
+            receiver: receiver.with_thread(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+mod summaries_tests {
+    use std::{thread, time::Duration};
+
+    use ac_analysis::Summarizer;
+    use ac_parser::{CarInfo, Channel, Device, Event, Operation};
+
+    use crate::{Client, testing::MockServer};
+
+    #[test]
+    fn raw_packets_become_summaries() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let client = Client::new(
+            server.local_addr().expect("no local addr"),
+            Device::default(),
+        )
+        .expect("failed to connect");
+        client
+            .send_message(Operation::SubscribeUpdate)
+            .expect("failed to send");
+        let (_, client_addr) = server.recv_operation().expect("no request");
+
+        let summarizer = Summarizer::new(Duration::from_millis(100), &[Channel::SpeedKmh]);
+        let receiver = client
+            .deliver_summaries(summarizer)
+            .expect("failed to start delivery");
+
+        for speed_kmh in [100.0, 200.0, 300.0] {
+            let car = CarInfo {
+                speed_kmh,
+                ..CarInfo::default()
+            };
+            server
+                .send_event(&Event::CarInfo(car), client_addr)
+                .expect("failed to send");
+            thread::sleep(Duration::from_millis(60));
+        }
+
+        let summary = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no summary");
+        assert_eq!(summary.samples, 2);
+        let speed = summary.stats(Channel::SpeedKmh).expect("summarized");
+        assert_eq!((speed.min, speed.max), (100.0, 200.0));
+        assert_eq!(receiver.try_recv(), None, "300 km/h is still in its window");
+        receiver.close().expect("delivery failed");
+    }
+}
@@ -0,0 +1,492 @@
+//! Keeping a long-running telemetry service up. A `Supervisor` owns the
+//! client, the pipeline processing its events and the sinks the outputs go
+//! to, each on its own thread with its own mailbox. A component that fails,
+//! by returning an error or panicking, is rebuilt from its factory and
+//! restarted when its `RetryPolicy` allows, while the others carry on; its
+//! mail waits for it. `health()` reports how each is doing, and `shutdown`
+//! stops them in order: the client first, then the pipeline once it has
+//! processed everything, then the sinks once they've written it all out.
+
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, SyncSender},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use ac_analysis::{Output, Pipeline, PipelineBuilder};
+use ac_parser::{Event, Operation};
+
+use crate::{AcError, Client, Exponential, RetryPolicy, delivery::POLL_INTERVAL};
+
+/// How long a component has to run before its restarts count from one again.
+pub const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+/// How many messages wait in each mailbox before the sender blocks.
+const MAILBOX_CAPACITY: usize = 1024;
+
+/// Writes pipeline outputs somewhere: a file, a socket, a dashboard.
+pub trait Sink: Send {
+    /// * `output`: one output, in the order the pipeline produced them.
+    fn write(&mut self, output: &Output) -> io::Result<()>;
+
+    /// called once everything has been written, on shutdown.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How a supervised component is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentState {
+    Running,
+    /// It failed and is waiting to be restarted.
+    Restarting,
+    /// It failed and its restart policy gave up on it.
+    Failed,
+    /// It finished, on shutdown or because everything upstream had.
+    Stopped,
+}
+
+/// The health of one component.
+///
+/// * `name`: what it was added as.
+/// * `state`: how it's doing.
+/// * `restarts`: how many times it's been restarted.
+/// * `last_error`: why it last failed, if it has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub state: ComponentState,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+type ClientFactory = Box<dyn FnMut() -> Result<Client, AcError> + Send>;
+type PipelineFactory = Box<dyn FnMut() -> Pipeline + Send>;
+type SinkFactory = Box<dyn FnMut() -> io::Result<Box<dyn Sink>> + Send>;
+
+/// Assembles a `Supervisor`.
+///
+/// * `client`: what the client is called, connects it, and what it subscribes to.
+/// * `pipeline`: builds the pipeline, which passes events straight on if unset.
+/// * `sinks`: each sink's name, and what builds it.
+/// * `restart`: when failed components are restarted.
+#[derive(Default)]
+pub struct SupervisorBuilder {
+    client: Option<(String, ClientFactory, Operation)>,
+    pipeline: Option<PipelineFactory>,
+    sinks: Vec<(String, SinkFactory)>,
+    restart: Option<Arc<dyn RetryPolicy>>,
+}
+
+impl SupervisorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the client, connected afresh by `connect` on every start and restart.
+    ///
+    /// * `name`: what its health is reported under.
+    /// * `subscription`: what to subscribe to once connected.
+    /// * `connect`: makes the client, e.g. `move || Client::new(addr, Device::default())`.
+    pub fn client<F>(mut self, name: impl Into<String>, subscription: Operation, connect: F) -> Self
+    where
+        F: FnMut() -> Result<Client, AcError> + Send + 'static,
+    {
+        self.client = Some((name.into(), Box::new(connect), subscription));
+        self
+    }
+
+    /// the pipeline the client's events go through, rebuilt on every restart.
+    ///
+    /// * `build`: makes it, e.g. `|| PipelineBuilder::dashboard().build()`.
+    pub fn pipeline<F>(mut self, build: F) -> Self
+    where
+        F: FnMut() -> Pipeline + Send + 'static,
+    {
+        self.pipeline = Some(Box::new(build));
+        self
+    }
+
+    /// adds a sink every pipeline output goes to, rebuilt on every restart.
+    ///
+    /// * `name`: what its health is reported under.
+    /// * `open`: makes it, e.g. by opening its file.
+    pub fn sink<F, S>(mut self, name: impl Into<String>, mut open: F) -> Self
+    where
+        F: FnMut() -> io::Result<S> + Send + 'static,
+        S: Sink + 'static,
+    {
+        let open: SinkFactory = Box::new(move || Ok(Box::new(open()?) as Box<dyn Sink>));
+        self.sinks.push((name.into(), open));
+        self
+    }
+
+    /// restarts failed components by `policy`, rather than the default three
+    /// attempts one to ten seconds apart. A component that ran for
+    /// `STABLE_AFTER` before failing starts over at the first retry.
+    ///
+    /// * `policy`: when to restart.
+    pub fn restart<R>(mut self, policy: R) -> Self
+    where
+        R: RetryPolicy + 'static,
+    {
+        self.restart = Some(Arc::new(policy));
+        self
+    }
+
+    /// starts every component on its own thread.
+    pub fn start(self) -> Result<Supervisor, AcError> {
+        let (client_name, mut connect, subscription) = self
+            .client
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no client to supervise"))?;
+        let restart = self
+            .restart
+            .unwrap_or_else(|| Arc::new(Exponential::default()));
+        let running = Arc::new(AtomicBool::new(true));
+        let mut components = Vec::new();
+
+        // Sinks start first so nothing upstream sends into the void.
+        let mut mailboxes = Vec::new();
+        let mut sinks = Vec::new();
+        for (name, mut open) in self.sinks {
+            let (sender, outputs) = mpsc::sync_channel::<Output>(MAILBOX_CAPACITY);
+            mailboxes.push(sender);
+            sinks.push(Component::spawn(name, &restart, move || {
+                let mut sink = open()?;
+                while let Ok(output) = outputs.recv() {
+                    sink.write(&output)?;
+                }
+                Ok(sink.flush()?)
+            })?);
+        }
+
+        let mut build = self
+            .pipeline
+            .unwrap_or_else(|| Box::new(|| PipelineBuilder::new().build()));
+        let (sender, events) = mpsc::sync_channel::<(Duration, Event)>(MAILBOX_CAPACITY);
+        components.push(Component::spawn("pipeline", &restart, move || {
+            let mut pipeline = build();
+            while let Ok((at, event)) = events.recv() {
+                for output in pipeline.push(at, &event) {
+                    // A sink that's given up for good has dropped its mailbox.
+                    mailboxes.retain(|mailbox| mailbox.send(output.clone()).is_ok());
+                }
+            }
+            Ok(pipeline.finish()?)
+        })?);
+
+        let start = Instant::now();
+        let keep_running = Arc::clone(&running);
+        components.insert(
+            0,
+            Component::spawn(client_name, &restart, move || {
+                run_client(&mut connect, subscription, &keep_running, start, &sender)
+            })?,
+        );
+        components.extend(sinks);
+
+        Ok(Supervisor {
+            running,
+            components,
+        })
+    }
+}
+
+/// receives from a fresh client until shut down, or until nothing downstream
+/// is left to take its events.
+fn run_client(
+    connect: &mut ClientFactory,
+    subscription: Operation,
+    running: &AtomicBool,
+    start: Instant,
+    events: &SyncSender<(Duration, Event)>,
+) -> Result<(), AcError> {
+    let mut client = connect()?;
+    client.set_read_timeout(Some(POLL_INTERVAL))?;
+    client.send_message(Operation::Handshake)?;
+    client.send_message(subscription)?;
+
+    let mut failures = 0;
+    while running.load(Ordering::Relaxed) {
+        let Some(event) = client.recv_next(&mut failures)?.and_then(|r| r.event) else {
+            continue;
+        };
+        let at = start.elapsed().saturating_sub(client.stats.latency());
+        if events.send((at, event)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// A component's thread and health.
+struct Component {
+    health: Arc<Mutex<ComponentHealth>>,
+    thread: JoinHandle<()>,
+}
+
+impl Component {
+    /// runs `run` on a thread, restarting it by `policy` whenever it fails.
+    ///
+    /// * `name`: the component's name.
+    /// * `policy`: when to restart.
+    /// * `run`: the component, returning once it's done.
+    fn spawn<F>(
+        name: impl Into<String>,
+        policy: &Arc<dyn RetryPolicy>,
+        mut run: F,
+    ) -> Result<Self, AcError>
+    where
+        F: FnMut() -> Result<(), AcError> + Send + 'static,
+    {
+        let name = name.into();
+        let health = Arc::new(Mutex::new(ComponentHealth {
+            name: name.clone(),
+            state: ComponentState::Running,
+            restarts: 0,
+            last_error: None,
+        }));
+        let reported = Arc::clone(&health);
+        let policy = Arc::clone(policy);
+        let report = move |update: &dyn Fn(&mut ComponentHealth)| {
+            update(&mut reported.lock().unwrap_or_else(|p| p.into_inner()));
+        };
+
+        let thread = thread::Builder::new()
+            .name(format!("ac_lib-{name}"))
+            .spawn(move || {
+                let mut retry = 0;
+                loop {
+                    let started = Instant::now();
+                    let why = match panic::catch_unwind(AssertUnwindSafe(&mut run)) {
+                        Ok(Ok(())) => break report(&|h| h.state = ComponentState::Stopped),
+                        Ok(Err(why)) => why.to_string(),
+                        Err(panic) => panic_message(panic.as_ref()),
+                    };
+                    if started.elapsed() >= STABLE_AFTER {
+                        retry = 0;
+                    }
+                    retry += 1;
+
+                    let Some(delay) = policy.delay(retry) else {
+                        break report(&|h| {
+                            h.state = ComponentState::Failed;
+                            h.last_error = Some(why.clone());
+                        });
+                    };
+                    report(&|h| {
+                        h.state = ComponentState::Restarting;
+                        h.last_error = Some(why.clone());
+                    });
+                    thread::sleep(delay);
+                    report(&|h| {
+                        h.state = ComponentState::Running;
+                        h.restarts += 1;
+                    });
+                }
+            })?;
+
+        Ok(Self { health, thread })
+    }
+
+    fn health(&self) -> ComponentHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => format!("panicked: {message}"),
+        (_, Some(message)) => format!("panicked: {message}"),
+        _ => "panicked".to_string(),
+    }
+}
+
+/// A running, supervised client, pipeline and sinks.
+///
+/// * `running`: cleared to stop the client.
+/// * `components`: the client, the pipeline, then the sinks, in shutdown order.
+pub struct Supervisor {
+    running: Arc<AtomicBool>,
+    components: Vec<Component>,
+}
+
+impl Supervisor {
+    pub fn builder() -> SupervisorBuilder {
+        SupervisorBuilder::new()
+    }
+
+    /// how every component is doing: the client, the pipeline, then the sinks.
+    pub fn health(&self) -> Vec<ComponentHealth> {
+        self.components.iter().map(Component::health).collect()
+    }
+
+    /// whether every component is running.
+    pub fn is_healthy(&self) -> bool {
+        self.health()
+            .iter()
+            .all(|health| health.state == ComponentState::Running)
+    }
+
+    /// stops the client, then waits for the pipeline to process what it had
+    /// and the sinks to write and flush it, returning how each ended up.
+    pub fn shutdown(self) -> Vec<ComponentHealth> {
+        self.running.store(false, Ordering::Relaxed);
+
+        self.components
+            .into_iter()
+            .map(|component| {
+                // Panics are caught inside the thread, so joining can't fail.
+                let Component { health, thread } = component;
+                let _ = thread.join();
+                let health = health.lock().unwrap_or_else(|p| p.into_inner());
+                health.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod supervisor_tests {
+    use std::{
+        io,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use ac_analysis::{Output, PipelineBuilder, Stage};
+    use ac_parser::{CarInfo, Device, Event, HandshakeResponse, Operation};
+
+    use crate::{
+        Client, Fixed,
+        supervisor::{ComponentState, Sink, Supervisor},
+        testing::MockServer,
+    };
+
+    /// panics on the first CarInfo it's ever given.
+    struct PanicOnce(Arc<AtomicBool>);
+
+    impl Stage for PanicOnce {
+        fn name(&self) -> &str {
+            "panic once"
+        }
+
+        fn process(&mut self, _at: Duration, event: &Event, _outputs: &mut Vec<Output>) -> bool {
+            if matches!(event, Event::CarInfo(_)) && !self.0.swap(true, Ordering::Relaxed) {
+                panic!("stage bug");
+            }
+            true
+        }
+    }
+
+    /// keeps the gear of each CarInfo, failing its first write ever.
+    struct Gears {
+        gears: Arc<Mutex<Vec<i32>>>,
+        failed: Arc<AtomicBool>,
+        flushed: Arc<AtomicBool>,
+    }
+
+    impl Sink for Gears {
+        fn write(&mut self, output: &Output) -> io::Result<()> {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                return Err(io::Error::other("disk full"));
+            }
+            if let Output::Event(Event::CarInfo(car)) = output {
+                self.gears.lock().unwrap().push(car.gear);
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_components_restart_and_shut_down_in_order() {
+        let server = MockServer::bind().expect("failed to bind mock server");
+        let addr = server.local_addr().expect("no local addr");
+        let (gears, flushed) = (Arc::default(), Arc::new(AtomicBool::new(false)));
+        let panicked = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+
+        let (sink_gears, sink_flushed) = (Arc::clone(&gears), Arc::clone(&flushed));
+        let supervisor = Supervisor::builder()
+            .client("client", Operation::SubscribeUpdate, move || {
+                Client::new(addr, Device::default())
+            })
+            .pipeline(move || {
+                PipelineBuilder::new()
+                    .stage(PanicOnce(Arc::clone(&panicked)))
+                    .build()
+            })
+            .sink("gears", move || {
+                Ok(Gears {
+                    gears: Arc::clone(&sink_gears),
+                    failed: Arc::clone(&failed),
+                    flushed: Arc::clone(&sink_flushed),
+                })
+            })
+            .restart(Fixed {
+                retries: 3,
+                delay: Duration::from_millis(10),
+            })
+            .start()
+            .expect("failed to start");
+
+        let (operation, client_addr) = server.recv_operation().expect("no handshake");
+        assert!(matches!(operation, Operation::Handshake));
+        server.recv_operation().expect("no subscription");
+
+        // The sink fails on the handshake and the pipeline panics on gear 1.
+        let handshake = Event::HandshakeResponse(HandshakeResponse::default());
+        server
+            .send_event(&handshake, client_addr)
+            .expect("failed to send");
+        for gear in 1..=3 {
+            let car = Event::CarInfo(CarInfo {
+                gear,
+                ..CarInfo::default()
+            });
+            server
+                .send_event(&car, client_addr)
+                .expect("failed to send");
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while gears.lock().unwrap().len() < 2 {
+            assert!(Instant::now() < deadline, "the sink never caught up");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*gears.lock().unwrap(), [2, 3]);
+
+        let health = supervisor.health();
+        let restarts: Vec<_> = health
+            .iter()
+            .map(|h| (h.name.as_str(), h.restarts))
+            .collect();
+        assert_eq!(restarts, [("client", 0), ("pipeline", 1), ("gears", 1)]);
+        assert_eq!(health[1].last_error.as_deref(), Some("panicked: stage bug"));
+        assert!(supervisor.is_healthy());
+
+        let ended = supervisor.shutdown();
+        assert!(ended.iter().all(|h| h.state == ComponentState::Stopped));
+        assert!(flushed.load(Ordering::Relaxed), "sinks flush on shutdown");
+    }
+}
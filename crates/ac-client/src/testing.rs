@@ -0,0 +1,217 @@
+//! Helpers for end-to-end tests against a mocked AC server over loopback.
+//!
+//! Enable with the `testing` feature, usually as a dev-dependency:
+//!
+//! ```
+//! # use ac_client::testing::{ScriptedSession, run_loopback_session};
+//! # use ac_parser::{CarInfo, Event, HandshakeResponse};
+//! # fn main() -> Result<(), ac_client::AcError> {
+//! let session = ScriptedSession::new(HandshakeResponse::default())
+//!     .update(Event::CarInfo(CarInfo { speed_kmh: 120.0, ..CarInfo::default() }));
+//!
+//! let events = run_loopback_session(session)?;
+//! assert!(matches!(events[1], Event::CarInfo(ref c) if c.speed_kmh == 120.0));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use ac_parser::{Device, Event, HandshakeResponse, Operation};
+
+use crate::{AcError, Client};
+
+/// How long either side of a loopback session waits for a packet before giving up.
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A stand-in for the AC UDP server, bound to an OS-assigned loopback port.
+pub struct MockServer {
+    socket: UdpSocket,
+}
+
+impl MockServer {
+    pub fn bind() -> Result<Self, AcError> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.set_read_timeout(Some(LOOPBACK_TIMEOUT))?;
+
+        Ok(Self { socket })
+    }
+
+    /// the address clients should connect to.
+    pub fn local_addr(&self) -> Result<SocketAddr, AcError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// waits for the next request from a client.
+    /// Returns the requested operation and who sent it.
+    pub fn recv_operation(&self) -> Result<(Operation, SocketAddr), AcError> {
+        let mut buf = [0u8; 12];
+        let (size, from) = self.socket.recv_from(&mut buf)?;
+
+        let operation = buf[..size]
+            .get(8..12)
+            .and_then(|op| op.try_into().ok())
+            .map(i32::from_le_bytes)
+            .and_then(operation_from_i32)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a client request"))?;
+
+        Ok((operation, from))
+    }
+
+    /// sends an event to a client as the AC server would encode it.
+    ///
+    /// * `event`: the event to send.
+    /// * `to`: the client's address.
+    pub fn send_event(&self, event: &Event, to: SocketAddr) -> Result<usize, AcError> {
+        Ok(self.socket.send_to(&event.to_bytes(), to)?)
+    }
+
+    /// sends arbitrary bytes to a client, such as a malformed packet.
+    ///
+    /// * `packet`: the datagram to send.
+    /// * `to`: the client's address.
+    pub fn send_raw(&self, packet: &[u8], to: SocketAddr) -> Result<usize, AcError> {
+        Ok(self.socket.send_to(packet, to)?)
+    }
+}
+
+/// What the mock server answers with during a loopback session.
+///
+/// * `handshake`: the reply to the client's handshake.
+/// * `updates`: the events sent, in order, once the client subscribes.
+pub struct ScriptedSession {
+    handshake: HandshakeResponse,
+    updates: Vec<Event>,
+}
+
+impl ScriptedSession {
+    pub fn new(handshake: HandshakeResponse) -> Self {
+        Self {
+            handshake,
+            updates: Vec::new(),
+        }
+    }
+
+    /// appends an event to send after the client subscribes.
+    ///
+    /// * `event`: the event to send.
+    pub fn update(mut self, event: Event) -> Self {
+        self.updates.push(event);
+        self
+    }
+}
+
+/// runs a full session between a `MockServer` and a `Client` over loopback:
+/// handshake, subscribe, receive every scripted update, dismiss.
+/// Returns every event the client received, starting with the handshake response.
+///
+/// * `session`: what the server answers with.
+pub fn run_loopback_session(session: ScriptedSession) -> Result<Vec<Event>, AcError> {
+    let server = MockServer::bind()?;
+    let server_addr = server.local_addr()?;
+    let update_count = session.updates.len();
+
+    let server_thread = thread::spawn(move || serve(&server, &session));
+
+    let client = Client::new(server_addr, Device::default())?;
+    client.set_read_timeout(Some(LOOPBACK_TIMEOUT))?;
+
+    let mut events = Vec::with_capacity(update_count + 1);
+
+    client.send_message(Operation::Handshake)?;
+    events.push(client.recv_raw_event_buffer()?.0);
+
+    client.send_message(Operation::SubscribeUpdate)?;
+    for _ in 0..update_count {
+        events.push(client.recv_raw_event_buffer()?.0);
+    }
+
+    client.send_message(Operation::Dismiss)?;
+
+    server_thread
+        .join()
+        .map_err(|_| io::Error::other("Mock server panicked"))??;
+
+    Ok(events)
+}
+
+/// answers client requests from the script until the client dismisses.
+///
+/// * `server`: the mock server.
+/// * `session`: what to answer with.
+fn serve(server: &MockServer, session: &ScriptedSession) -> Result<(), AcError> {
+    let handshake = Event::HandshakeResponse(session.handshake.clone());
+
+    loop {
+        let (operation, client) = server.recv_operation()?;
+
+        match operation {
+            Operation::Handshake => {
+                server.send_event(&handshake, client)?;
+            }
+            Operation::SubscribeUpdate | Operation::SubscribeSpot => {
+                for event in &session.updates {
+                    server.send_event(event, client)?;
+                }
+            }
+            Operation::Dismiss => return Ok(()),
+        }
+    }
+}
+
+/// maps the wire value of a request back to its operation.
+///
+/// * `op`: the operation field of a client request.
+fn operation_from_i32(op: i32) -> Option<Operation> {
+    match op {
+        0 => Some(Operation::Handshake),
+        1 => Some(Operation::SubscribeUpdate),
+        2 => Some(Operation::SubscribeSpot),
+        3 => Some(Operation::Dismiss),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod testing_tests {
+    use ac_parser::{CarInfo, Event, HandshakeResponse, LapInfo, Name};
+
+    use crate::testing::{ScriptedSession, run_loopback_session};
+
+    #[test]
+    fn loopback_session_yields_scripted_events() {
+        let handshake = HandshakeResponse {
+            track_name: Name::from("monza").unwrap(),
+            ..HandshakeResponse::default()
+        };
+        let car_info = CarInfo {
+            speed_kmh: 120.0,
+            ..CarInfo::default()
+        };
+        let lap_info = LapInfo {
+            lap: 1,
+            time: 110_000,
+            ..LapInfo::default()
+        };
+
+        let session = ScriptedSession::new(handshake.clone())
+            .update(Event::CarInfo(car_info.clone()))
+            .update(Event::LapInfo(lap_info.clone()));
+
+        let events = run_loopback_session(session).expect("session should complete");
+
+        assert_eq!(
+            events,
+            vec![
+                Event::HandshakeResponse(handshake),
+                Event::CarInfo(car_info),
+                Event::LapInfo(lap_info),
+            ]
+        );
+    }
+}
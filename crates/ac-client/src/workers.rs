@@ -0,0 +1,147 @@
+//! Moves packet parsing off the receiving thread onto a small pool of workers,
+//! for setups that aggregate many servers or replay at full speed.
+//!
+//! Each source is pinned to one worker, so events from the same source come out
+//! in the order their packets went in; different sources parse in parallel.
+
+use std::{
+    ops::Deref,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use ac_parser::{Event, ParserError};
+
+use crate::{AcError, pool::Packet};
+
+/// A parsed packet, tagged with the source it came from.
+///
+/// * `source`: the id it was submitted with.
+/// * `event`: the parse result.
+#[derive(Debug)]
+pub struct Parsed {
+    pub source: usize,
+    pub event: Result<Event, ParserError>,
+}
+
+/// A fixed set of parsing threads.
+///
+/// * `jobs`: one queue per worker.
+/// * `results`: where every worker sends its parsed packets.
+/// * `threads`: the workers, joined on drop.
+pub struct ParseWorkers<P = Packet> {
+    jobs: Vec<Sender<(usize, P)>>,
+    results: Receiver<Parsed>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<P> ParseWorkers<P>
+where
+    P: Deref<Target = [u8]> + Send + 'static,
+{
+    /// starts the workers.
+    ///
+    /// * `threads`: how many parsing threads to run; at least one is started.
+    pub fn new(threads: usize) -> Result<Self, AcError> {
+        let (results_tx, results) = mpsc::channel();
+        let mut jobs = Vec::new();
+        let mut handles = Vec::new();
+
+        for i in 0..threads.max(1) {
+            let (tx, rx) = mpsc::channel::<(usize, P)>();
+            let results = results_tx.clone();
+
+            let handle = thread::Builder::new()
+                .name(format!("ac_lib-parse-{i}"))
+                .spawn(move || {
+                    for (source, packet) in rx {
+                        let event = Event::try_parse(&packet);
+                        drop(packet);
+
+                        if results.send(Parsed { source, event }).is_err() {
+                            return;
+                        }
+                    }
+                })?;
+
+            jobs.push(tx);
+            handles.push(handle);
+        }
+
+        Ok(Self {
+            jobs,
+            results,
+            threads: handles,
+        })
+    }
+
+    /// queues a packet for parsing on its source's worker.
+    ///
+    /// * `source`: which server or recording the packet came from.
+    /// * `packet`: the raw datagram.
+    pub fn submit(&self, source: usize, packet: P) {
+        let worker = &self.jobs[source % self.jobs.len()];
+
+        // Workers only exit once their queue is closed, which happens on drop.
+        let _ = worker.send((source, packet));
+    }
+
+    /// waits for the next parsed packet.
+    pub fn recv(&self) -> Option<Parsed> {
+        self.results.recv().ok()
+    }
+
+    /// takes the next parsed packet, if one is ready.
+    pub fn try_recv(&self) -> Option<Parsed> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl<P> Drop for ParseWorkers<P> {
+    fn drop(&mut self) {
+        self.jobs.clear();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod workers_tests {
+    use ac_parser::{CarInfo, Event};
+
+    use crate::workers::ParseWorkers;
+
+    #[test]
+    fn per_source_order_is_preserved() {
+        let workers = ParseWorkers::<Vec<u8>>::new(3).expect("failed to start workers");
+
+        for gear in 0..50 {
+            for source in 0..4 {
+                let car = CarInfo {
+                    gear,
+                    ..CarInfo::default()
+                };
+                workers.submit(source, car.to_bytes().to_vec());
+            }
+        }
+        workers.submit(1, vec![0u8; 13]);
+
+        let mut gears = vec![Vec::new(); 4];
+        let mut errors = 0;
+        for _ in 0..201 {
+            let parsed = workers.recv().expect("workers stopped early");
+            match parsed.event {
+                Ok(Event::CarInfo(car)) => gears[parsed.source].push(car.gear),
+                Ok(other) => panic!("unexpected event {other:?}"),
+                Err(_) => errors += 1,
+            }
+        }
+
+        let expected = (0..50).collect::<Vec<_>>();
+        assert!(gears.iter().all(|g| *g == expected), "{gears:?}");
+        assert_eq!(errors, 1);
+        assert!(workers.try_recv().is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::ParserError;
+
+/// Walks a byte buffer left to right, handing out correctly-sized slices
+/// and primitives without requiring manually computed offsets.
+pub(super) struct ByteCursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    pub(super) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Returns the next `n` bytes and advances the cursor past them.
+    /// Errors instead of panicking if fewer than `n` bytes remain.
+    pub(super) fn take(&mut self, n: usize) -> Result<&'a [u8], ParserError> {
+        let slice = self
+            .pos
+            .checked_add(n)
+            .and_then(|end| self.buf.get(self.pos..end))
+            .ok_or(ParserError::Truncated {
+                offset: self.pos,
+                needed: n,
+            })?;
+
+        self.pos += n;
+        Ok(slice)
+    }
+
+    /// Advances the cursor by `n` bytes without returning them.
+    pub(super) fn skip(&mut self, n: usize) -> Result<(), ParserError> {
+        self.take(n).map(|_| ())
+    }
+
+    /// Returns the next `N` bytes as an array and advances the cursor past them.
+    /// Reading into a fixed-size array keeps the primitive reads free of
+    /// fallible slice-to-array conversions.
+    pub(super) fn array<const N: usize>(&mut self) -> Result<[u8; N], ParserError> {
+        let bytes = self
+            .buf
+            .get(self.pos..)
+            .and_then(<[u8]>::first_chunk::<N>)
+            .ok_or(ParserError::Truncated {
+                offset: self.pos,
+                needed: N,
+            })?;
+
+        self.pos += N;
+        Ok(*bytes)
+    }
+
+    pub(super) fn i32(&mut self) -> Result<i32, ParserError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, ParserError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub(super) fn f32(&mut self) -> Result<f32, ParserError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    pub(super) fn bool(&mut self) -> Result<bool, ParserError> {
+        self.array::<1>().map(|[byte]| byte != 0)
+    }
+
+    pub(super) fn wheels(&mut self) -> Result<[f32; 4], ParserError> {
+        parse_f32_wheels(&self.array::<16>()?)
+    }
+
+    pub(super) fn xyz(&mut self) -> Result<[f32; 3], ParserError> {
+        let x = self.f32()?;
+        let y = self.f32()?;
+        let z = self.f32()?;
+        Ok([x, y, z])
+    }
+}
+
+/// parses a group of wheel stats from a buffer range. Fails with
+/// `Truncated` on fewer than 16 bytes.
+///
+/// * `buf`: the buffer to extract the ranges from.
+fn parse_f32_wheels(buf: &[u8]) -> Result<[f32; 4], ParserError> {
+    let mut cursor = ByteCursor::new(buf);
+
+    let front_left = cursor.f32()?;
+    let front_right = cursor.f32()?;
+    let back_left = cursor.f32()?;
+    let back_right = cursor.f32()?;
+
+    Ok([front_left, front_right, back_left, back_right])
+}
+
+#[cfg(test)]
+mod cursor_tests {
+    use crate::{
+        ParserError,
+        byte_cursor::{ByteCursor, parse_f32_wheels},
+    };
+
+    // Wheels parse on correct input
+    #[test]
+    fn wheels_parse_correct_size_buf() {
+        let front_left: [u8; 5] = [10, 12, 14, 16, 18];
+        let front_right: [u8; 5] = [10, 12, 14, 16, 18];
+        let back_left: [u8; 5] = [10, 12, 14, 16, 18];
+        let back_right: [u8; 5] = [10, 12, 14, 16, 18];
+
+        let buf = [front_left, front_right, back_left, back_right].concat();
+
+        let res = parse_f32_wheels(&buf);
+        assert!(res.is_ok(), "Buffer should be correct size and parse.");
+    }
+    // Wheels dont parse on incorrect input and handles error bounds
+    #[test]
+    fn wheels_parse_incorrect_size_buf() {
+        // parse_f32_wheels reads exactly 16 bytes (4 f32s); one byte short
+        // must be rejected rather than silently truncated/panicking.
+        let buf = vec![0u8; 15];
+
+        let res = parse_f32_wheels(&buf);
+        assert!(
+            matches!(
+                res,
+                Err(ParserError::Truncated {
+                    offset: 12,
+                    needed: 4
+                })
+            ),
+            "Error boundary should be caught"
+        );
+    }
+
+    // Reading past the end of the buffer errors instead of panicking,
+    // and leaves the cursor where it was.
+    #[test]
+    fn take_past_end_is_an_error() {
+        let buf = [1u8, 2, 3];
+        let mut cursor = ByteCursor::new(&buf);
+
+        assert!(cursor.i32().is_err(), "3 bytes cannot hold an i32");
+        assert_eq!(cursor.take(3).ok(), Some(&buf[..]));
+        assert!(cursor.skip(1).is_err(), "Cursor is already at the end");
+    }
+}
@@ -0,0 +1,341 @@
+//! What the server on the other end can send. The handshake response carries
+//! the server's identifier and protocol version; from those a client works
+//! out which dialect it's talking to and which packets, at which sizes, to
+//! expect, rather than assuming every server is desktop AC.
+//!
+//! Assetto Corsa Mobile sends CarInfo of a different size. Its layout isn't
+//! published, but every CarInfo starts with the `a` identifier and its own
+//! size, so `Capabilities::learn` can recognise one of any length, once a few
+//! in a row agree, and `Capabilities::parse` reads the fields it shares with
+//! desktop, by offset; those past the end of a shorter packet read as zero.
+
+use crate::{
+    CAR_INFO_LEN, CarInfo, Event, HANDSHAKE_RES_LEN, HandshakeResponse, IntoEvent, LAP_INFO_LEN,
+    LapInfo, Operation, ParserError, layout::car_info, oversized_layout, parse_identifier,
+};
+
+/// The protocol version desktop Assetto Corsa answers handshakes with.
+const DESKTOP_VERSION: i32 = 1;
+
+/// How many self-sized CarInfo of one size must arrive in a row before
+/// `Capabilities::learn` takes it as the server's, so one stray packet can't
+/// change the dialect.
+pub const LEARN_AFTER: u32 = 3;
+
+/// Which flavour of the remote telemetry protocol a server speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Desktop Assetto Corsa, as the remote telemetry doc describes it.
+    Desktop,
+    /// Assetto Corsa Mobile, or anything else sending CarInfo that describes
+    /// itself with a size other than desktop's, `LEARN_AFTER` times in a
+    /// row; see `Capabilities::learn`.
+    Mobile,
+    /// A server answering with a version nothing here knows; it's assumed to
+    /// use the desktop layout, which it may not.
+    Unknown,
+}
+
+/// A kind of packet the server sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    HandshakeResponse,
+    CarInfo,
+    LapInfo,
+}
+
+/// What a server supports, detected from its handshake response.
+///
+/// * `dialect`: the protocol flavour.
+/// * `identifier`, `version`: as the server sent them.
+/// * `handshake_len`, `car_info_len`, `lap_info_len`: each packet's size in bytes.
+/// * `learned_car_info_len`: another CarInfo size the server has been seen
+///   sending, valid alongside `car_info_len`.
+/// * `update`, `spot`: whether CarInfo updates and LapInfo spot events can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub dialect: Dialect,
+    pub identifier: i32,
+    pub version: i32,
+    pub handshake_len: usize,
+    pub car_info_len: usize,
+    pub learned_car_info_len: Option<usize>,
+    pub lap_info_len: usize,
+    pub update: bool,
+    pub spot: bool,
+    /// the size of the self-sized CarInfo last seen, and how many in a row.
+    streak: Option<(usize, u32)>,
+}
+
+impl Capabilities {
+    /// works out what a server supports from its handshake response.
+    ///
+    /// * `handshake`: the server's answer to the handshake.
+    pub fn detect(handshake: &HandshakeResponse) -> Self {
+        let dialect = match handshake.version {
+            DESKTOP_VERSION => Dialect::Desktop,
+            _ => Dialect::Unknown,
+        };
+
+        Self {
+            dialect,
+            identifier: handshake.identifier,
+            version: handshake.version,
+            handshake_len: HANDSHAKE_RES_LEN,
+            car_info_len: CAR_INFO_LEN,
+            learned_car_info_len: None,
+            lap_info_len: LAP_INFO_LEN,
+            update: true,
+            spot: true,
+            streak: None,
+        }
+    }
+
+    /// which kind of packet a datagram of this size is, if any.
+    ///
+    /// * `len`: the datagram's length.
+    pub fn packet_kind(&self, len: usize) -> Option<PacketKind> {
+        match len {
+            len if len == self.handshake_len => Some(PacketKind::HandshakeResponse),
+            len if len == self.car_info_len || Some(len) == self.learned_car_info_len => {
+                Some(PacketKind::CarInfo)
+            }
+            len if len == self.lap_info_len => Some(PacketKind::LapInfo),
+            _ => None,
+        }
+    }
+
+    /// adapts to packets no known layout fits, if they're CarInfo giving
+    /// their own size. Once `LEARN_AFTER` of one size arrive in a row, that
+    /// size parses as CarInfo alongside desktop's and the dialect becomes
+    /// `Mobile`; anything else in between starts the count again. Returns
+    /// whether this packet's size was learned.
+    ///
+    /// * `packet`: a datagram from the server.
+    pub fn learn(&mut self, packet: &[u8]) -> bool {
+        let len = packet.len();
+        if self.packet_kind(len).is_some() || !is_sized_car_info(packet) {
+            self.streak = None;
+            return false;
+        }
+
+        let seen = match self.streak {
+            Some((size, seen)) if size == len => seen + 1,
+            _ => 1,
+        };
+        if seen < LEARN_AFTER {
+            self.streak = Some((len, seen));
+            return false;
+        }
+
+        self.streak = None;
+        self.learned_car_info_len = Some(len);
+        self.dialect = Dialect::Mobile;
+        true
+    }
+
+    /// parses a packet sent by this server, in its dialect's sizes.
+    ///
+    /// * `packet`: a datagram from the server.
+    pub fn parse(&self, packet: &[u8]) -> Result<Event, ParserError> {
+        match self.packet_kind(packet.len()) {
+            Some(PacketKind::HandshakeResponse) => {
+                HandshakeResponse::from_bytes(&resized(packet, HANDSHAKE_RES_LEN))
+                    .map(Event::HandshakeResponse)
+            }
+            // Longer ones keep what's past the desktop layout in `trailing`.
+            Some(PacketKind::CarInfo) if packet.len() > CAR_INFO_LEN => Event::try_parse(packet),
+            Some(PacketKind::CarInfo) => {
+                CarInfo::from_bytes(&resized(packet, CAR_INFO_LEN)).map(Event::CarInfo)
+            }
+            Some(PacketKind::LapInfo) => {
+                LapInfo::from_bytes(&resized(packet, LAP_INFO_LEN)).map(Event::LapInfo)
+            }
+            // Past every size, by the same rule as `Event::try_parse`.
+            None if oversized_layout(packet).is_some() => Event::try_parse(packet),
+            None => Err(ParserError::UnknownPacketSize(packet.len())),
+        }
+    }
+
+    /// whether the server will answer a subscription.
+    ///
+    /// * `operation`: the operation to send.
+    pub fn supports(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::SubscribeUpdate => self.update,
+            Operation::SubscribeSpot => self.spot,
+            Operation::Handshake | Operation::Dismiss => true,
+        }
+    }
+}
+
+/// whether a packet starts like a CarInfo and gives its own length as its size.
+///
+/// * `packet`: a datagram from the server.
+fn is_sized_car_info(packet: &[u8]) -> bool {
+    let (Some(identifier), Some(size)) = (
+        packet.get(car_info::IDENTIFIER),
+        packet
+            .get(car_info::SIZE)
+            .and_then(|size| size.try_into().ok()),
+    ) else {
+        return false;
+    };
+
+    matches!(parse_identifier(identifier), Ok('a'))
+        && usize::try_from(i32::from_le_bytes(size)).is_ok_and(|size| size == packet.len())
+}
+
+/// a packet cut or zero-padded to a layout's length.
+///
+/// * `packet`: the packet.
+/// * `len`: the layout's length.
+fn resized(packet: &[u8], len: usize) -> Vec<u8> {
+    let mut resized = packet[..packet.len().min(len)].to_vec();
+    resized.resize(len, 0);
+    resized
+}
+
+impl HandshakeResponse {
+    /// what the server that sent this supports.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::detect(self)
+    }
+}
+
+#[cfg(test)]
+mod capabilities_tests {
+    use crate::{
+        CarInfo, Event, HandshakeResponse, LapInfo, Operation,
+        capabilities::{Dialect, LEARN_AFTER, PacketKind},
+    };
+
+    #[test]
+    fn desktop_servers_are_detected_by_version() {
+        let desktop = HandshakeResponse {
+            identifier: 4242,
+            version: 1,
+            ..HandshakeResponse::default()
+        }
+        .capabilities();
+        assert_eq!(desktop.dialect, Dialect::Desktop);
+        assert_eq!(
+            desktop.packet_kind(CarInfo::default().to_bytes().len()),
+            Some(PacketKind::CarInfo)
+        );
+        assert_eq!(desktop.packet_kind(13), None);
+        assert!(desktop.supports(Operation::SubscribeSpot));
+
+        let modded = HandshakeResponse {
+            version: 9,
+            ..HandshakeResponse::default()
+        }
+        .capabilities();
+        assert_eq!((modded.dialect, modded.version), (Dialect::Unknown, 9));
+    }
+
+    /// a 200 byte CarInfo, as a server with a shorter layout sends it.
+    fn short_car_info() -> Vec<u8> {
+        let mut packet = CarInfo {
+            speed_kmh: 180.0,
+            car_pos_normalized: 0.5,
+            ..CarInfo::default()
+        }
+        .to_bytes()[..200]
+            .to_vec();
+        packet[4..8].copy_from_slice(&200i32.to_le_bytes());
+        packet
+    }
+
+    #[test]
+    fn self_sized_car_info_is_learned_as_mobile() {
+        let packet = short_car_info();
+        let mut capabilities = HandshakeResponse::default().capabilities();
+        assert!(capabilities.parse(&packet).is_err());
+        assert!(!capabilities.learn(&[0; 13]), "not a CarInfo");
+        for _ in 1..LEARN_AFTER {
+            assert!(!capabilities.learn(&packet), "learned too soon");
+        }
+        assert!(capabilities.learn(&packet));
+        assert_eq!(capabilities.dialect, Dialect::Mobile);
+        assert_eq!(capabilities.learned_car_info_len, Some(200));
+
+        let Ok(Event::CarInfo(car)) = capabilities.parse(&packet) else {
+            panic!("expected the shorter CarInfo to parse");
+        };
+        assert_eq!((car.speed_kmh, car.size), (180.0, 200));
+        assert_eq!(car.car_pos_normalized, 0.0, "past the end of the packet");
+    }
+
+    #[test]
+    fn desktop_car_info_still_parses_after_learning() {
+        let desktop = CarInfo {
+            speed_kmh: 95.0,
+            ..CarInfo::default()
+        }
+        .to_bytes();
+        let mut capabilities = HandshakeResponse::default().capabilities();
+        for _ in 0..LEARN_AFTER {
+            capabilities.learn(&short_car_info());
+        }
+        assert_eq!(
+            capabilities.packet_kind(desktop.len()),
+            Some(PacketKind::CarInfo)
+        );
+        assert_eq!(capabilities.packet_kind(200), Some(PacketKind::CarInfo));
+        assert!(matches!(
+            capabilities.parse(&desktop),
+            Ok(Event::CarInfo(CarInfo {
+                speed_kmh: 95.0,
+                ..
+            }))
+        ));
+        assert!(
+            !capabilities.learn(&desktop),
+            "desktop's size is already known"
+        );
+    }
+
+    #[test]
+    fn one_stray_packet_leaves_the_dialect_alone() {
+        let desktop = CarInfo::default().to_bytes();
+        let mut capabilities = HandshakeResponse {
+            version: 1,
+            ..HandshakeResponse::default()
+        }
+        .capabilities();
+
+        // Desktop packets in between start the count again.
+        for _ in 0..2 * LEARN_AFTER {
+            assert!(!capabilities.learn(&short_car_info()));
+            assert!(!capabilities.learn(&desktop));
+        }
+        assert_eq!(capabilities.dialect, Dialect::Desktop);
+        assert_eq!(capabilities.learned_car_info_len, None);
+        assert!(capabilities.parse(&short_car_info()).is_err());
+    }
+
+    #[test]
+    fn oversized_packets_parse_as_they_do_without_capabilities() {
+        let capabilities = HandshakeResponse::default().capabilities();
+        let mut lap = LapInfo {
+            lap: 4,
+            ..LapInfo::default()
+        }
+        .to_bytes()
+        .to_vec();
+        lap.extend_from_slice(&[7; 3]);
+        assert_eq!(capabilities.parse(&lap).ok(), Event::try_parse(&lap).ok());
+        assert!(matches!(
+            capabilities.parse(&lap),
+            Ok(Event::LapInfo(LapInfo { lap: 4, .. }))
+        ));
+
+        let mut handshake = HandshakeResponse::default().to_bytes().to_vec();
+        handshake.push(1);
+        assert!(matches!(
+            capabilities.parse(&handshake),
+            Ok(Event::HandshakeResponse(_))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+/// Every field of a CarInfo, for reporting which ones an in-place update changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CarInfoField {
+    Identifier,
+    Size,
+    SpeedKmh,
+    SpeedMph,
+    SpeedMs,
+    IsAbsEnabled,
+    IsAbsInAction,
+    IsTcInAction,
+    IsTcEnabled,
+    IsInPit,
+    IsEngineLimiterOn,
+    AccgVertical,
+    AccgHorizontal,
+    AccgFrontal,
+    LapTime,
+    LastLap,
+    BestLap,
+    LapCount,
+    Gas,
+    Brake,
+    Clutch,
+    EngineRpm,
+    Steer,
+    Gear,
+    CgHeight,
+    WheelAngularSpeed,
+    SlipAngle,
+    SlipAngleContactPatch,
+    SlipRatio,
+    TyreSlip,
+    NdSlip,
+    Load,
+    Dy,
+    Mz,
+    TyreDirtyLevel,
+    CamberRad,
+    TyreRadius,
+    TyreLoadedRadius,
+    SuspensionHeight,
+    CarPosNormalized,
+    CarSlope,
+    CarCoordinates,
+}
+
+impl CarInfoField {
+    pub const ALL: [CarInfoField; 42] = [
+        CarInfoField::Identifier,
+        CarInfoField::Size,
+        CarInfoField::SpeedKmh,
+        CarInfoField::SpeedMph,
+        CarInfoField::SpeedMs,
+        CarInfoField::IsAbsEnabled,
+        CarInfoField::IsAbsInAction,
+        CarInfoField::IsTcInAction,
+        CarInfoField::IsTcEnabled,
+        CarInfoField::IsInPit,
+        CarInfoField::IsEngineLimiterOn,
+        CarInfoField::AccgVertical,
+        CarInfoField::AccgHorizontal,
+        CarInfoField::AccgFrontal,
+        CarInfoField::LapTime,
+        CarInfoField::LastLap,
+        CarInfoField::BestLap,
+        CarInfoField::LapCount,
+        CarInfoField::Gas,
+        CarInfoField::Brake,
+        CarInfoField::Clutch,
+        CarInfoField::EngineRpm,
+        CarInfoField::Steer,
+        CarInfoField::Gear,
+        CarInfoField::CgHeight,
+        CarInfoField::WheelAngularSpeed,
+        CarInfoField::SlipAngle,
+        CarInfoField::SlipAngleContactPatch,
+        CarInfoField::SlipRatio,
+        CarInfoField::TyreSlip,
+        CarInfoField::NdSlip,
+        CarInfoField::Load,
+        CarInfoField::Dy,
+        CarInfoField::Mz,
+        CarInfoField::TyreDirtyLevel,
+        CarInfoField::CamberRad,
+        CarInfoField::TyreRadius,
+        CarInfoField::TyreLoadedRadius,
+        CarInfoField::SuspensionHeight,
+        CarInfoField::CarPosNormalized,
+        CarInfoField::CarSlope,
+        CarInfoField::CarCoordinates,
+    ];
+
+    fn bit(self) -> u64 {
+        1 << self as u64
+    }
+}
+
+/// The set of CarInfo fields an in-place update changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChangedFields(u64);
+
+impl ChangedFields {
+    pub fn contains(self, field: CarInfoField) -> bool {
+        self.0 & field.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// the changed fields, in wire order.
+    pub fn iter(self) -> impl Iterator<Item = CarInfoField> {
+        CarInfoField::ALL
+            .into_iter()
+            .filter(move |field| self.contains(*field))
+    }
+
+    pub(super) fn insert(&mut self, field: CarInfoField) {
+        self.0 |= field.bit();
+    }
+
+    /// stores `value` in `slot`, recording `field` as changed if it differs.
+    ///
+    /// * `slot`: the struct field being updated.
+    /// * `value`: the freshly parsed value.
+    /// * `field`: which field `slot` is.
+    pub(super) fn set<T>(&mut self, slot: &mut T, value: T, field: CarInfoField)
+    where
+        T: PartialEq,
+    {
+        if *slot != value {
+            *slot = value;
+            self.insert(field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod changes_tests {
+    use crate::changes::{CarInfoField, ChangedFields};
+
+    #[test]
+    fn set_records_only_real_changes() {
+        let mut changed = ChangedFields::default();
+        let mut gear = 3;
+        let mut gas = 0.5;
+
+        changed.set(&mut gear, 3, CarInfoField::Gear);
+        changed.set(&mut gas, 0.8, CarInfoField::Gas);
+
+        assert_eq!(changed.len(), 1);
+        assert!(changed.contains(CarInfoField::Gas));
+        assert!(!changed.contains(CarInfoField::Gear));
+        assert_eq!(changed.iter().collect::<Vec<_>>(), vec![CarInfoField::Gas]);
+        assert_eq!(gas, 0.8);
+    }
+}
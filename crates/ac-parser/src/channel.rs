@@ -0,0 +1,167 @@
+use crate::CarInfo;
+
+/// One corner of the car, in the order AC sends per-wheel values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Wheel {
+    FrontLeft = 0,
+    FrontRight = 1,
+    RearLeft = 2,
+    RearRight = 3,
+}
+
+impl Wheel {
+    pub const ALL: [Wheel; 4] = [
+        Wheel::FrontLeft,
+        Wheel::FrontRight,
+        Wheel::RearLeft,
+        Wheel::RearRight,
+    ];
+}
+
+/// A single numeric value that can be read off every CarInfo packet,
+/// so analysis code can work on "a channel" instead of a hard-coded field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Channel {
+    SpeedKmh,
+    SpeedMph,
+    SpeedMs,
+    AccgVertical,
+    AccgHorizontal,
+    AccgFrontal,
+    Gas,
+    Brake,
+    Clutch,
+    EngineRpm,
+    Steer,
+    Gear,
+    CgHeight,
+    CarPosNormalized,
+    CarSlope,
+    WheelAngularSpeed(Wheel),
+    SlipAngle(Wheel),
+    SlipRatio(Wheel),
+    TyreSlip(Wheel),
+    NdSlip(Wheel),
+    Load(Wheel),
+    CamberRad(Wheel),
+    TyreLoadedRadius(Wheel),
+    SuspensionHeight(Wheel),
+}
+
+impl Channel {
+    /// every channel that isn't per-wheel.
+    pub const SCALARS: [Channel; 15] = [
+        Channel::SpeedKmh,
+        Channel::SpeedMph,
+        Channel::SpeedMs,
+        Channel::AccgVertical,
+        Channel::AccgHorizontal,
+        Channel::AccgFrontal,
+        Channel::Gas,
+        Channel::Brake,
+        Channel::Clutch,
+        Channel::EngineRpm,
+        Channel::Steer,
+        Channel::Gear,
+        Channel::CgHeight,
+        Channel::CarPosNormalized,
+        Channel::CarSlope,
+    ];
+
+    /// constructors for every per-wheel channel.
+    pub const PER_WHEEL: [fn(Wheel) -> Channel; 9] = [
+        Channel::WheelAngularSpeed,
+        Channel::SlipAngle,
+        Channel::SlipRatio,
+        Channel::TyreSlip,
+        Channel::NdSlip,
+        Channel::Load,
+        Channel::CamberRad,
+        Channel::TyreLoadedRadius,
+        Channel::SuspensionHeight,
+    ];
+
+    /// every channel, scalars first, then each per-wheel channel for all four wheels.
+    pub fn all() -> impl Iterator<Item = Channel> {
+        let per_wheel = Channel::PER_WHEEL
+            .into_iter()
+            .flat_map(|channel| Wheel::ALL.into_iter().map(channel));
+
+        Channel::SCALARS.into_iter().chain(per_wheel)
+    }
+
+    /// reads this channel's value from a packet.
+    ///
+    /// * `car`: the packet to read from.
+    pub fn value(self, car: &CarInfo) -> f32 {
+        match self {
+            Channel::SpeedKmh => car.speed_kmh,
+            Channel::SpeedMph => car.speed_mph,
+            Channel::SpeedMs => car.speed_ms,
+            Channel::AccgVertical => car.accg_vertical,
+            Channel::AccgHorizontal => car.accg_horizontal,
+            Channel::AccgFrontal => car.accg_frontal,
+            Channel::Gas => car.gas,
+            Channel::Brake => car.brake,
+            Channel::Clutch => car.clutch,
+            Channel::EngineRpm => car.engine_rpm,
+            Channel::Steer => car.steer,
+            Channel::Gear => car.gear as f32,
+            Channel::CgHeight => car.cg_height,
+            Channel::CarPosNormalized => car.car_pos_normalized,
+            Channel::CarSlope => car.car_slope,
+            Channel::WheelAngularSpeed(w) => car.wheel_angular_speed[w as usize],
+            Channel::SlipAngle(w) => car.slip_angle[w as usize],
+            Channel::SlipRatio(w) => car.slip_ratio[w as usize],
+            Channel::TyreSlip(w) => car.tyre_slip[w as usize],
+            Channel::NdSlip(w) => car.nd_slip[w as usize],
+            Channel::Load(w) => car.load[w as usize],
+            Channel::CamberRad(w) => car.camber_rad[w as usize],
+            Channel::TyreLoadedRadius(w) => car.tyre_loaded_radius[w as usize],
+            Channel::SuspensionHeight(w) => car.suspension_height[w as usize],
+        }
+    }
+}
+
+#[cfg(test)]
+mod channel_tests {
+    use crate::{
+        CarInfo,
+        channel::{Channel, Wheel},
+    };
+
+    #[test]
+    fn channels_read_their_field() {
+        let car = CarInfo {
+            speed_kmh: 150.0,
+            gear: 4,
+            suspension_height: [0.1, 0.2, 0.3, 0.4],
+            ..CarInfo::default()
+        };
+
+        assert_eq!(Channel::SpeedKmh.value(&car), 150.0);
+        assert_eq!(Channel::Gear.value(&car), 4.0);
+        assert_eq!(Channel::SuspensionHeight(Wheel::RearLeft).value(&car), 0.3);
+    }
+
+    #[test]
+    fn all_lists_every_channel_once() {
+        let all = Channel::all().collect::<Vec<_>>();
+        assert_eq!(all.len(), 15 + 9 * 4);
+
+        let unique = all.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), all.len());
+    }
+}
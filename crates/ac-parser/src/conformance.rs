@@ -0,0 +1,387 @@
+use std::ops::Range;
+
+use crate::{
+    CAR_INFO_LEN, CarInfo, Event, HANDSHAKE_RES_LEN, LAP_INFO_LEN, LapInfo, ParserError,
+    channel::Channel,
+    layout::{car_info, handshake_response, lap_info},
+    oversized_layout,
+};
+
+/// Violations past this many are counted but not kept.
+const MAX_VIOLATIONS: usize = 1000;
+
+/// Every CarInfo packet's identifier, per the remote telemetry doc.
+const CAR_INFO_IDENTIFIER: char = 'a';
+
+/// Highest gear index AC reports (0 is reverse, 1 neutral).
+const MAX_GEAR: i32 = 10;
+
+/// CarInfo bytes that hold a bool each.
+const CAR_INFO_BOOLS: [(Range<usize>, &str); 6] = [
+    (car_info::IS_ABS_ENABLED, "is_abs_enabled"),
+    (car_info::IS_ABS_IN_ACTION, "is_abs_in_action"),
+    (car_info::IS_TC_IN_ACTION, "is_tc_in_action"),
+    (car_info::IS_TC_ENABLED, "is_tc_enabled"),
+    (car_info::IS_IN_PIT, "is_in_pit"),
+    (car_info::IS_ENGINE_LIMITER_ON, "is_engine_limiter_on"),
+];
+
+/// Fixed-width string fields in each packet that has them.
+const HANDSHAKE_STRINGS: [(Range<usize>, &str); 4] = [
+    (handshake_response::CAR_NAME, "car_name"),
+    (handshake_response::DRIVER_NAME, "driver_name"),
+    (handshake_response::TRACK_NAME, "track_name"),
+    (handshake_response::TRACK_CONFIG, "track_config"),
+];
+const LAP_INFO_STRINGS: [(Range<usize>, &str); 2] = [
+    (lap_info::DRIVER_NAME, "driver_name"),
+    (lap_info::CAR_NAME, "car_name"),
+];
+
+/// What's wrong with a packet or one of its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The packet size doesn't match any known packet.
+    UnknownSize(usize),
+    /// The packet has a known size but failed to parse.
+    Malformed(String),
+    /// A float field is NaN or infinite.
+    NotFinite,
+    /// A value falls outside its documented range.
+    OutOfRange(f32),
+    /// A bool byte holds something other than 0 or 1.
+    NotABool(u8),
+    /// A padding byte is set.
+    PaddingSet(u8),
+    /// A string field fills its whole width with no null terminator.
+    UnterminatedString,
+    /// A string field has bytes set after its null terminator, where the
+    /// spec leaves nulls; only reported in strict mode.
+    BytesAfterTerminator,
+    /// The CarInfo identifier isn't the documented `'a'`.
+    UnexpectedIdentifier(char),
+    /// The CarInfo `size` field disagrees with the packet's length.
+    SizeFieldMismatch(i32),
+    /// The packet runs this many bytes past the layout it starts with; they're
+    /// kept in the event's `trailing`.
+    TrailingBytes(usize),
+}
+
+/// A single deviation from the spec.
+///
+/// * `packet`: index of the offending packet in the checked stream.
+/// * `field`: the field at fault, or `"packet"` for the packet as a whole.
+/// * `problem`: what's wrong with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub packet: usize,
+    pub field: String,
+    pub problem: Problem,
+}
+
+/// The outcome of checking a packet stream.
+///
+/// * `packets`: how many packets were checked.
+/// * `handshake_responses`, `car_infos`, `lap_infos`: packets seen of each kind.
+/// * `unknown`: packets whose size matched no known packet.
+/// * `violation_count`: every violation found, including ones not kept in `violations`.
+/// * `violations`: the first violations found, in stream order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConformanceReport {
+    pub packets: usize,
+    pub handshake_responses: usize,
+    pub car_infos: usize,
+    pub lap_infos: usize,
+    pub unknown: usize,
+    pub violation_count: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl ConformanceReport {
+    pub fn is_conformant(&self) -> bool {
+        self.violation_count == 0
+    }
+}
+
+/// Validates packets against the published AC remote telemetry spec: sizes,
+/// field ranges, bool and padding bytes, and string terminators.
+/// Useful against modified servers or third-party forwarders.
+///
+/// * `strict`: whether deviations the parser shrugs off, like leftover bytes
+///   after a string's terminator, are reported too.
+#[derive(Debug, Default)]
+pub struct ConformanceChecker {
+    report: ConformanceReport,
+    strict: bool,
+}
+
+impl ConformanceChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a checker that reports every deviation from the documented layout,
+    /// however harmless, for mapping undocumented server variants.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// checks the next packet in the stream.
+    ///
+    /// * `packet`: the raw datagram, trimmed to the bytes received.
+    pub fn check(&mut self, packet: &[u8]) {
+        let index = self.report.packets;
+        self.report.packets += 1;
+
+        let packet = match oversized_layout(packet) {
+            Some(layout) => {
+                let extra = packet.len() - layout;
+                self.violation(index, "packet", Problem::TrailingBytes(extra));
+                &packet[..layout]
+            }
+            _ => packet,
+        };
+
+        let strings: &[(Range<usize>, &str)] = match packet.len() {
+            HANDSHAKE_RES_LEN => {
+                self.report.handshake_responses += 1;
+                &HANDSHAKE_STRINGS
+            }
+            CAR_INFO_LEN => {
+                self.report.car_infos += 1;
+                self.check_car_info_bytes(index, packet);
+                &[]
+            }
+            LAP_INFO_LEN => {
+                self.report.lap_infos += 1;
+                &LAP_INFO_STRINGS
+            }
+            len => {
+                self.report.unknown += 1;
+                self.violation(index, "packet", Problem::UnknownSize(len));
+                return;
+            }
+        };
+
+        for (range, field) in strings {
+            let bytes = &packet[range.clone()];
+            match terminator(bytes) {
+                None => self.violation(index, *field, Problem::UnterminatedString),
+                Some(end) if self.strict && bytes[end..].iter().any(|&byte| byte != 0) => {
+                    self.violation(index, *field, Problem::BytesAfterTerminator)
+                }
+                Some(_) => {}
+            }
+        }
+
+        match Event::try_parse(packet) {
+            Ok(Event::CarInfo(car)) => self.check_car_info(index, &car),
+            Ok(Event::LapInfo(lap)) => self.check_lap_info(index, &lap),
+            Ok(Event::HandshakeResponse(_)) => {}
+            Err(why) => self.violation(index, "packet", Problem::Malformed(why.to_string())),
+        }
+    }
+
+    /// the report so far.
+    pub fn report(&self) -> &ConformanceReport {
+        &self.report
+    }
+
+    /// finishes checking, returning the report.
+    pub fn into_report(self) -> ConformanceReport {
+        self.report
+    }
+
+    /// checks the CarInfo bytes that parsing doesn't look at closely: bools and padding.
+    fn check_car_info_bytes(&mut self, index: usize, packet: &[u8]) {
+        for (range, field) in CAR_INFO_BOOLS {
+            let byte = packet[range.start];
+            if byte > 1 {
+                self.violation(index, field, Problem::NotABool(byte));
+            }
+        }
+
+        for &byte in &packet[car_info::PADDING] {
+            if byte != 0 {
+                self.violation(index, "padding", Problem::PaddingSet(byte));
+            }
+        }
+    }
+
+    fn check_car_info(&mut self, index: usize, car: &CarInfo) {
+        if car.identifier != CAR_INFO_IDENTIFIER {
+            self.violation(
+                index,
+                "identifier",
+                Problem::UnexpectedIdentifier(car.identifier),
+            );
+        }
+
+        if car.size != CAR_INFO_LEN as i32 {
+            self.violation(index, "size", Problem::SizeFieldMismatch(car.size));
+        }
+
+        for channel in Channel::all() {
+            let value = channel.value(car);
+            if !value.is_finite() {
+                self.violation(index, format!("{channel:?}"), Problem::NotFinite);
+                continue;
+            }
+
+            let in_range = match channel {
+                Channel::Gas | Channel::Brake | Channel::Clutch | Channel::CarPosNormalized => {
+                    (0.0..=1.0).contains(&value)
+                }
+                Channel::SpeedKmh | Channel::SpeedMph | Channel::SpeedMs | Channel::EngineRpm => {
+                    value >= 0.0
+                }
+                Channel::Gear => (0..=MAX_GEAR).contains(&car.gear),
+                _ => true,
+            };
+
+            if !in_range {
+                self.violation(index, format!("{channel:?}"), Problem::OutOfRange(value));
+            }
+        }
+    }
+
+    fn check_lap_info(&mut self, index: usize, lap: &LapInfo) {
+        if lap.lap < 0 {
+            self.violation(index, "lap", Problem::OutOfRange(lap.lap as f32));
+        }
+
+        if lap.time < 0 {
+            self.violation(index, "time", Problem::OutOfRange(lap.time as f32));
+        }
+    }
+
+    fn violation<F>(&mut self, packet: usize, field: F, problem: Problem)
+    where
+        F: Into<String>,
+    {
+        self.report.violation_count += 1;
+
+        if self.report.violations.len() < MAX_VIOLATIONS {
+            self.report.violations.push(Violation {
+                packet,
+                field: field.into(),
+                problem,
+            });
+        }
+    }
+}
+
+/// where a fixed-width UTF-16 string field's first null code unit starts,
+/// or `None` if it runs to the end.
+///
+/// * `field`: the raw field bytes.
+fn terminator(field: &[u8]) -> Option<usize> {
+    field
+        .chunks_exact(2)
+        .position(|unit| unit == [0, 0])
+        .map(|unit| unit * 2)
+}
+
+impl Event {
+    /// parses a packet like `try_parse`, also returning every deviation from
+    /// the documented layout a strict `ConformanceChecker` finds in it. The
+    /// warnings never stop an event from parsing.
+    ///
+    /// * `buf`: the received packet, trimmed to the number of bytes read.
+    pub fn try_parse_strict(buf: &[u8]) -> (Result<Event, ParserError>, Vec<Violation>) {
+        let mut checker = ConformanceChecker::strict();
+        checker.check(buf);
+
+        (Event::try_parse(buf), checker.into_report().violations)
+    }
+}
+
+#[cfg(test)]
+mod conformance_tests {
+    use crate::{
+        CarInfo, Event, HandshakeResponse, LapInfo, Name,
+        conformance::{ConformanceChecker, Problem},
+    };
+
+    #[test]
+    fn well_formed_stream_is_conformant() {
+        let mut checker = ConformanceChecker::new();
+        checker.check(&HandshakeResponse::default().to_bytes());
+        checker.check(&CarInfo::default().to_bytes());
+        checker.check(&LapInfo::default().to_bytes());
+
+        let report = checker.into_report();
+        assert!(report.is_conformant(), "{:?}", report.violations);
+        assert_eq!(report.packets, 3);
+        assert_eq!(report.car_infos, 1);
+    }
+
+    #[test]
+    fn out_of_spec_fields_are_reported() {
+        let car = CarInfo {
+            gas: 1.5,
+            speed_kmh: f32::NAN,
+            ..CarInfo::default()
+        };
+        let mut packet = car.to_bytes().to_vec();
+        packet[22] = 7; // is_tc_in_action
+        packet[24] = 1; // padding
+
+        let mut checker = ConformanceChecker::new();
+        checker.check(&packet);
+        checker.check(&[0u8; 12]);
+
+        let problems = checker
+            .report()
+            .violations
+            .iter()
+            .map(|v| (v.field.as_str(), v.problem.clone()))
+            .collect::<Vec<_>>();
+
+        assert!(problems.contains(&("Gas", Problem::OutOfRange(1.5))));
+        assert!(problems.contains(&("SpeedKmh", Problem::NotFinite)));
+        assert!(problems.contains(&("is_tc_in_action", Problem::NotABool(7))));
+        assert!(problems.contains(&("padding", Problem::PaddingSet(1))));
+        assert!(problems.contains(&("packet", Problem::UnknownSize(12))));
+    }
+
+    #[test]
+    fn unterminated_strings_are_reported() {
+        let lap = LapInfo {
+            driver_name: Name::from(&"x".repeat(50)).unwrap(),
+            ..LapInfo::default()
+        };
+
+        let mut checker = ConformanceChecker::new();
+        checker.check(&lap.to_bytes());
+
+        let violations = &checker.report().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "driver_name");
+        assert_eq!(violations[0].problem, Problem::UnterminatedString);
+    }
+
+    #[test]
+    fn strict_mode_warns_about_leftover_string_bytes_without_failing() {
+        let handshake = HandshakeResponse {
+            car_name: Name::from("bmw_m3_e30").unwrap(),
+            ..HandshakeResponse::default()
+        };
+        let mut packet = handshake.to_bytes().to_vec();
+        // A stale byte after the car name's terminator, as a server reusing its buffer might leave.
+        packet[60] = b'x';
+
+        let mut lenient = ConformanceChecker::new();
+        lenient.check(&packet);
+        assert!(lenient.report().is_conformant());
+
+        let (event, warnings) = Event::try_parse_strict(&packet);
+        assert!(matches!(event, Ok(Event::HandshakeResponse(_))));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "car_name");
+        assert_eq!(warnings[0].problem, Problem::BytesAfterTerminator);
+    }
+}
@@ -0,0 +1,179 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{
+    CAR_INFO_LEN, CarInfo, Event, HANDSHAKE_RES_LEN, HandshakeResponse, LAP_INFO_LEN, LapInfo,
+    layout::{self, car_info},
+};
+
+impl Event {
+    /// encodes the event into the packet the AC server would send for it.
+    pub fn to_bytes(&self) -> BytesMut {
+        match self {
+            Event::HandshakeResponse(handshake) => handshake.to_bytes(),
+            Event::CarInfo(car_info) => car_info.to_bytes(),
+            Event::LapInfo(lap_info) => lap_info.to_bytes(),
+        }
+    }
+}
+
+impl HandshakeResponse {
+    /// encodes the handshake response into its 408 byte packet, plus any trailing bytes.
+    pub fn to_bytes(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(HANDSHAKE_RES_LEN);
+
+        put_utf16_chars(&mut buf, &self.car_name);
+        put_utf16_chars(&mut buf, &self.driver_name);
+        buf.put_i32_le(self.identifier);
+        buf.put_i32_le(self.version);
+        put_utf16_chars(&mut buf, &self.track_name);
+        put_utf16_chars(&mut buf, &self.track_config);
+        buf.put_slice(&self.trailing);
+
+        buf
+    }
+}
+
+impl CarInfo {
+    /// encodes the car info into its 328 byte packet, plus any trailing bytes.
+    pub fn to_bytes(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(CAR_INFO_LEN);
+
+        let mut identifier = [0u8; 4];
+        self.identifier.encode_utf8(&mut identifier);
+        buf.put_slice(&identifier);
+
+        buf.put_i32_le(self.size);
+        buf.put_f32_le(self.speed_kmh);
+        buf.put_f32_le(self.speed_mph);
+        buf.put_f32_le(self.speed_ms);
+
+        buf.put_u8(self.is_abs_enabled.into());
+        buf.put_u8(self.is_abs_in_action.into());
+        buf.put_u8(self.is_tc_in_action.into());
+        buf.put_u8(self.is_tc_enabled.into());
+        buf.put_bytes(0, car_info::PADDING.len());
+        buf.put_u8(self.is_in_pit.into());
+        buf.put_u8(self.is_engine_limiter_on.into());
+
+        buf.put_f32_le(self.accg_vertical);
+        buf.put_f32_le(self.accg_horizontal);
+        buf.put_f32_le(self.accg_frontal);
+
+        buf.put_u32_le(self.lap_time);
+        buf.put_u32_le(self.last_lap);
+        buf.put_u32_le(self.best_lap);
+        buf.put_u32_le(self.lap_count);
+
+        buf.put_f32_le(self.gas);
+        buf.put_f32_le(self.brake);
+        buf.put_f32_le(self.clutch);
+        buf.put_f32_le(self.engine_rpm);
+        buf.put_f32_le(self.steer);
+        buf.put_i32_le(self.gear);
+        buf.put_f32_le(self.cg_height);
+
+        for wheels in [
+            &self.wheel_angular_speed,
+            &self.slip_angle,
+            &self.slip_angle_contact_patch,
+            &self.slip_ratio,
+            &self.tyre_slip,
+            &self.nd_slip,
+            &self.load,
+            &self.dy,
+            &self.mz,
+            &self.tyre_dirty_level,
+            &self.camber_rad,
+            &self.tyre_radius,
+            &self.tyre_loaded_radius,
+            &self.suspension_height,
+        ] {
+            wheels.iter().for_each(|v| buf.put_f32_le(*v));
+        }
+
+        buf.put_f32_le(self.car_pos_normalized);
+        buf.put_f32_le(self.car_slope);
+        self.car_coordinates.iter().for_each(|v| buf.put_f32_le(*v));
+        buf.put_slice(&self.trailing);
+
+        buf
+    }
+}
+
+impl LapInfo {
+    /// encodes the lap info into its 212 byte packet, plus any trailing bytes.
+    pub fn to_bytes(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(LAP_INFO_LEN);
+
+        buf.put_i32_le(self.car_id_num);
+        buf.put_i32_le(self.lap);
+        put_utf16_chars(&mut buf, &self.driver_name);
+        put_utf16_chars(&mut buf, &self.car_name);
+        buf.put_i32_le(self.time);
+        buf.put_slice(&self.trailing);
+
+        buf
+    }
+}
+
+/// writes a string as a null padded, 100 byte UTF-16LE field, truncating if it doesn't fit.
+///
+/// * `buf`: the packet being built.
+/// * `s`: the string to write.
+fn put_utf16_chars(buf: &mut BytesMut, s: &str) {
+    let mut written = 0;
+
+    for unit in s.encode_utf16().take(layout::NAME / 2) {
+        buf.put_u16_le(unit);
+        written += 2;
+    }
+
+    buf.put_bytes(0, layout::NAME - written);
+}
+
+#[cfg(test)]
+mod encode_tests {
+    use crate::{CarInfo, Event, HandshakeResponse, LapInfo, Name};
+
+    #[test]
+    fn car_info_round_trips() {
+        let car_info = CarInfo {
+            speed_kmh: 212.5,
+            gear: 5,
+            lap_count: 3,
+            is_in_pit: true,
+            suspension_height: [0.1, 0.2, 0.3, 0.4],
+            car_coordinates: [1.0, 2.0, 3.0],
+            ..CarInfo::default()
+        };
+
+        let parsed = Event::try_parse(&car_info.to_bytes()).expect("should parse");
+        assert_eq!(parsed, Event::CarInfo(car_info));
+    }
+
+    #[test]
+    fn handshake_and_lap_info_round_trip() {
+        let handshake = HandshakeResponse {
+            car_name: Name::from("ks_mazda_mx5_cup").unwrap(),
+            driver_name: Name::from("Driver").unwrap(),
+            identifier: 4242,
+            version: 1,
+            track_name: Name::from("magione").unwrap(),
+            track_config: Name::new(),
+            trailing: Vec::new(),
+        };
+        let parsed = Event::try_parse(&handshake.to_bytes()).expect("should parse");
+        assert_eq!(parsed, Event::HandshakeResponse(handshake));
+
+        let lap_info = LapInfo {
+            car_id_num: 2,
+            lap: 7,
+            time: 95_123,
+            car_name: Name::from("ks_mazda_mx5_cup").unwrap(),
+            driver_name: Name::from("Driver").unwrap(),
+            trailing: Vec::new(),
+        };
+        let parsed = Event::try_parse(&lap_info.to_bytes()).expect("should parse");
+        assert_eq!(parsed, Event::LapInfo(lap_info));
+    }
+}
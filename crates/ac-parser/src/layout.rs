@@ -0,0 +1,119 @@
+//! Byte offsets of every field in each packet, generated from the fields'
+//! widths in wire order. A `const` assertion checks that the widths add up to
+//! the packet's length, so a layout mistake fails the build instead of
+//! silently shifting every field after it.
+
+use crate::{CAR_INFO_LEN, HANDSHAKE_RES_LEN, LAP_INFO_LEN};
+
+/// Declares a module of `Range<usize>` consts, one per field, laid out back to back,
+/// plus an `END` const, and asserts at compile time that `END` equals the packet length.
+macro_rules! layout {
+    ($name:ident, len = $len:expr; $($field:ident: $width:expr),+ $(,)?) => {
+        // Not every field is addressed by offset; the full table is what the
+        // length assertion checks.
+        #[allow(dead_code)]
+        pub(crate) mod $name {
+            layout!(@fields 0; $($field: $width),+);
+        }
+
+        const _: () = assert!(
+            $name::END == $len,
+            concat!(stringify!($name), " field widths don't add up to the packet length"),
+        );
+    };
+    (@fields $at:expr; $field:ident: $width:expr $(, $rest:ident: $rest_width:expr)*) => {
+        pub(crate) const $field: std::ops::Range<usize> = $at..$at + $width;
+        layout!(@fields $at + $width; $($rest: $rest_width),*);
+    };
+    (@fields $at:expr;) => {
+        pub(crate) const END: usize = $at;
+    };
+}
+
+/// Width of every fixed-size string field.
+pub(crate) const NAME: usize = 100;
+/// Width of a per-wheel group of four f32s.
+pub(crate) const WHEELS: usize = 16;
+
+layout! {
+    handshake_response, len = HANDSHAKE_RES_LEN;
+    CAR_NAME: super::NAME,
+    DRIVER_NAME: super::NAME,
+    IDENTIFIER: 4,
+    VERSION: 4,
+    TRACK_NAME: super::NAME,
+    TRACK_CONFIG: super::NAME,
+}
+
+layout! {
+    car_info, len = CAR_INFO_LEN;
+    IDENTIFIER: 4,
+    SIZE: 4,
+    SPEED_KMH: 4,
+    SPEED_MPH: 4,
+    SPEED_MS: 4,
+    IS_ABS_ENABLED: 1,
+    IS_ABS_IN_ACTION: 1,
+    IS_TC_IN_ACTION: 1,
+    IS_TC_ENABLED: 1,
+    PADDING: 2,
+    IS_IN_PIT: 1,
+    IS_ENGINE_LIMITER_ON: 1,
+    ACCG_VERTICAL: 4,
+    ACCG_HORIZONTAL: 4,
+    ACCG_FRONTAL: 4,
+    LAP_TIME: 4,
+    LAST_LAP: 4,
+    BEST_LAP: 4,
+    LAP_COUNT: 4,
+    GAS: 4,
+    BRAKE: 4,
+    CLUTCH: 4,
+    ENGINE_RPM: 4,
+    STEER: 4,
+    GEAR: 4,
+    CG_HEIGHT: 4,
+    WHEEL_ANGULAR_SPEED: super::WHEELS,
+    SLIP_ANGLE: super::WHEELS,
+    SLIP_ANGLE_CONTACT_PATCH: super::WHEELS,
+    SLIP_RATIO: super::WHEELS,
+    TYRE_SLIP: super::WHEELS,
+    ND_SLIP: super::WHEELS,
+    LOAD: super::WHEELS,
+    DY: super::WHEELS,
+    MZ: super::WHEELS,
+    TYRE_DIRTY_LEVEL: super::WHEELS,
+    CAMBER_RAD: super::WHEELS,
+    TYRE_RADIUS: super::WHEELS,
+    TYRE_LOADED_RADIUS: super::WHEELS,
+    SUSPENSION_HEIGHT: super::WHEELS,
+    CAR_POS_NORMALIZED: 4,
+    CAR_SLOPE: 4,
+    CAR_COORDINATES: 12,
+}
+
+layout! {
+    lap_info, len = LAP_INFO_LEN;
+    CAR_ID_NUM: 4,
+    LAP: 4,
+    DRIVER_NAME: super::NAME,
+    CAR_NAME: super::NAME,
+    TIME: 4,
+}
+
+#[cfg(test)]
+mod layout_tests {
+    use crate::layout::{car_info, handshake_response, lap_info};
+
+    // Spot checks against the offsets in the remote telemetry doc.
+    #[test]
+    fn offsets_match_the_documented_layout() {
+        assert_eq!(car_info::PADDING, 24..26);
+        assert_eq!(car_info::LAP_COUNT, 52..56);
+        assert_eq!(car_info::WHEEL_ANGULAR_SPEED.start, 84);
+        assert_eq!(car_info::CAR_COORDINATES, 316..328);
+
+        assert_eq!(handshake_response::TRACK_NAME.start, 208);
+        assert_eq!(lap_info::TIME, 208..212);
+    }
+}
@@ -0,0 +1,1055 @@
+//! The Assetto Corsa UDP telemetry wire format: parsing and encoding packets,
+//! plus the recording file format. Nothing here touches the network.
+//!
+//! reference for data: https://docs.google.com/document/d/1KfkZiIluXZ6mMhLWfDX1qAGbvhGRC3ZUzjVIt5FQpp4/pub
+//! also referrence: https://github.com/rickwest/ac-remote-telemetry-client/blob/master/src/parsers/RTCarInfoParser.js
+
+mod byte_cursor;
+mod capabilities;
+mod changes;
+mod channel;
+mod conformance;
+mod encode;
+pub(crate) mod layout;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod recording;
+mod redact;
+#[cfg(feature = "schema")]
+mod schema;
+mod timestamp;
+mod units;
+use arrayvec::ArrayString;
+use thiserror::Error;
+
+pub use capabilities::{Capabilities, Dialect, LEARN_AFTER, PacketKind};
+pub use changes::{CarInfoField, ChangedFields};
+pub use channel::{Channel, Wheel};
+pub use conformance::{ConformanceChecker, ConformanceReport, Problem, Violation};
+#[cfg(feature = "mmap")]
+pub use mapped::{FrameRef, MappedRecording};
+pub use recording::{Frame, Mark, MarkKind, Recording, RecordingWriter};
+pub use redact::{Redaction, Redactor};
+#[cfg(feature = "schema")]
+pub use schema::event_schema;
+pub use timestamp::Timestamp;
+pub use units::Units;
+
+use crate::{
+    byte_cursor::ByteCursor,
+    layout::{car_info, handshake_response, lap_info},
+};
+
+pub(crate) const LAP_INFO_LEN: usize = 212;
+pub(crate) const CAR_INFO_LEN: usize = 328;
+pub(crate) const HANDSHAKE_RES_LEN: usize = 408;
+
+/// A car, driver or track name, stored inline rather than on the heap.
+/// Names arrive as fixed 100 byte fields, so any real name fits;
+/// one that would decode longer is truncated at a char boundary.
+pub type Name = ArrayString<{ layout::NAME }>;
+
+/// module errors
+#[derive(Error, Debug)]
+pub enum ParserError {
+    /// If a parsing function receives an incorrect buffer sizing
+    #[error("Received incorrect size of buffer: {0}")]
+    IncorrectBufferSize(usize),
+
+    /// If a packet's size doesn't match any known event
+    #[error("No matching event for packet of size: {0}")]
+    UnknownPacketSize(usize),
+
+    /// If a field would be read past the end of the buffer
+    #[error("Buffer too short: needed {needed} bytes at offset {offset}")]
+    Truncated { offset: usize, needed: usize },
+
+    #[error("i32 failed to convert: {0}")]
+    I32ConversionFailed(String),
+
+    #[error("u32 failed to convert: {0}")]
+    U32ConversionFailed(String),
+
+    #[error("f32 failed to convert: {0}")]
+    F32ConversionFailed(String),
+
+    #[error("bool failed to convert: {0}")]
+    BoolConversionFailed(String),
+
+    #[error("wheel stats failed to convert: {0}")]
+    WheelsConversionFailed(String),
+
+    #[error("Char failed to convert: {0}")]
+    CharConversionFailed(String),
+}
+
+/// `ParserError` by the name `Event::try_parse` callers tend to reach for.
+pub type ParseError = ParserError;
+
+/// Trait that maps to converting into an event struct
+trait IntoEvent {
+    fn from_bytes(buf: &[u8]) -> Result<Self, ParserError>
+    where
+        Self: Sized;
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// An identifier for the current device this library is running on.
+/// Currently not used by AC, but required anyway.
+pub enum Device {
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "iphone"))]
+    IPhone = 0,
+    #[cfg_attr(feature = "serde", serde(rename = "ipad"))]
+    IPad = 1,
+    AndroidPhone = 2,
+    AndroidTablet = 3,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+/// our requested action to listen to or inform the UDP server of.
+pub enum Operation {
+    Handshake = 0,
+    SubscribeUpdate = 1,
+    SubscribeSpot = 2,
+    Dismiss = 3,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HandshakeResponse {
+    pub car_name: Name,
+    pub driver_name: Name,
+    pub identifier: i32,
+    pub version: i32,
+    pub track_name: Name,
+    pub track_config: Name,
+    /// bytes past the end of the known layout, from a modded or newer server.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub trailing: Vec<u8>,
+}
+
+impl IntoEvent for HandshakeResponse {
+    fn from_bytes(buf: &[u8]) -> Result<HandshakeResponse, ParserError> {
+        if buf.len() != HANDSHAKE_RES_LEN {
+            return Err(ParserError::IncorrectBufferSize(buf.len()));
+        }
+        let mut cursor = ByteCursor::new(buf);
+
+        let car_name = parse_utf8_chars(cursor.take(handshake_response::CAR_NAME.len())?);
+        let driver_name = parse_utf8_chars(cursor.take(handshake_response::DRIVER_NAME.len())?);
+
+        let identifier = cursor.i32()?;
+        let version = cursor.i32()?;
+
+        let track_name = parse_to_utf16_chars(cursor.take(handshake_response::TRACK_NAME.len())?);
+        let track_config =
+            parse_to_utf16_chars(cursor.take(handshake_response::TRACK_CONFIG.len())?);
+
+        Ok(HandshakeResponse {
+            car_name,
+            driver_name,
+            identifier,
+            version,
+            track_name,
+            track_config,
+            trailing: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CarInfo {
+    pub identifier: char,
+    pub size: i32,
+    pub speed_kmh: f32,
+    pub speed_mph: f32,
+    pub speed_ms: f32,
+    pub is_abs_enabled: bool,
+    pub is_abs_in_action: bool,
+    pub is_tc_in_action: bool,
+    pub is_tc_enabled: bool,
+    pub is_in_pit: bool,
+    pub is_engine_limiter_on: bool,
+    pub accg_vertical: f32,
+    pub accg_horizontal: f32,
+    pub accg_frontal: f32,
+    pub lap_time: u32,
+    pub last_lap: u32,
+    pub best_lap: u32,
+    pub lap_count: u32,
+    pub gas: f32,
+    pub brake: f32,
+    pub clutch: f32,
+    pub engine_rpm: f32,
+    pub steer: f32,
+    pub gear: i32,
+    pub cg_height: f32,
+    pub wheel_angular_speed: [f32; 4],
+    pub slip_angle: [f32; 4],
+    pub slip_angle_contact_patch: [f32; 4],
+    pub slip_ratio: [f32; 4],
+    pub tyre_slip: [f32; 4],
+    pub nd_slip: [f32; 4],
+    pub load: [f32; 4],
+    pub dy: [f32; 4],
+    pub mz: [f32; 4],
+    pub tyre_dirty_level: [f32; 4],
+    pub camber_rad: [f32; 4],
+    pub tyre_radius: [f32; 4],
+    pub tyre_loaded_radius: [f32; 4],
+    pub suspension_height: [f32; 4],
+    pub car_pos_normalized: f32,
+    pub car_slope: f32,
+    pub car_coordinates: [f32; 3],
+    /// bytes past the end of the known layout, from a modded or newer server.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub trailing: Vec<u8>,
+}
+
+/// An idle car: everything zeroed, with the `'a'` identifier AC sends on every CarInfo packet.
+impl Default for CarInfo {
+    fn default() -> Self {
+        Self {
+            identifier: 'a',
+            size: CAR_INFO_LEN as i32,
+            speed_kmh: 0.0,
+            speed_mph: 0.0,
+            speed_ms: 0.0,
+            is_abs_enabled: false,
+            is_abs_in_action: false,
+            is_tc_in_action: false,
+            is_tc_enabled: false,
+            is_in_pit: false,
+            is_engine_limiter_on: false,
+            accg_vertical: 0.0,
+            accg_horizontal: 0.0,
+            accg_frontal: 0.0,
+            lap_time: 0,
+            last_lap: 0,
+            best_lap: 0,
+            lap_count: 0,
+            gas: 0.0,
+            brake: 0.0,
+            clutch: 0.0,
+            engine_rpm: 0.0,
+            steer: 0.0,
+            gear: 0,
+            cg_height: 0.0,
+            wheel_angular_speed: [0.0; 4],
+            slip_angle: [0.0; 4],
+            slip_angle_contact_patch: [0.0; 4],
+            slip_ratio: [0.0; 4],
+            tyre_slip: [0.0; 4],
+            nd_slip: [0.0; 4],
+            load: [0.0; 4],
+            dy: [0.0; 4],
+            mz: [0.0; 4],
+            tyre_dirty_level: [0.0; 4],
+            camber_rad: [0.0; 4],
+            tyre_radius: [0.0; 4],
+            tyre_loaded_radius: [0.0; 4],
+            suspension_height: [0.0; 4],
+            car_pos_normalized: 0.0,
+            car_slope: 0.0,
+            car_coordinates: [0.0; 3],
+            trailing: Vec::new(),
+        }
+    }
+}
+
+impl IntoEvent for CarInfo {
+    fn from_bytes(buf: &[u8]) -> Result<Self, ParserError> {
+        let mut car_info = CarInfo::default();
+        car_info.parse_into(buf)?;
+        Ok(car_info)
+    }
+}
+
+impl CarInfo {
+    /// parses a CarInfo packet into this struct in place, for high-rate consumers that
+    /// keep one struct per car instead of building a new event per packet.
+    /// Returns which fields changed. On error the struct is left as it was.
+    ///
+    /// * `buf`: the 328 byte packet.
+    pub fn parse_into(&mut self, buf: &[u8]) -> Result<ChangedFields, ParserError> {
+        if buf.len() != CAR_INFO_LEN {
+            return Err(ParserError::IncorrectBufferSize(buf.len()));
+        }
+        let mut c = ByteCursor::new(buf);
+        let mut changed = ChangedFields::default();
+
+        // With the length checked, every read after the identifier is in bounds,
+        // so the identifier is the only field that can fail.
+        let identifier = parse_identifier(c.take(car_info::IDENTIFIER.len())?)?;
+        changed.set(&mut self.identifier, identifier, CarInfoField::Identifier);
+        self.trailing.clear();
+
+        changed.set(&mut self.size, c.i32()?, CarInfoField::Size);
+        changed.set(&mut self.speed_kmh, c.f32()?, CarInfoField::SpeedKmh);
+        changed.set(&mut self.speed_mph, c.f32()?, CarInfoField::SpeedMph);
+        changed.set(&mut self.speed_ms, c.f32()?, CarInfoField::SpeedMs);
+        changed.set(
+            &mut self.is_abs_enabled,
+            c.bool()?,
+            CarInfoField::IsAbsEnabled,
+        );
+        changed.set(
+            &mut self.is_abs_in_action,
+            c.bool()?,
+            CarInfoField::IsAbsInAction,
+        );
+        changed.set(
+            &mut self.is_tc_in_action,
+            c.bool()?,
+            CarInfoField::IsTcInAction,
+        );
+        changed.set(
+            &mut self.is_tc_enabled,
+            c.bool()?,
+            CarInfoField::IsTcEnabled,
+        );
+        c.skip(car_info::PADDING.len())?;
+        changed.set(&mut self.is_in_pit, c.bool()?, CarInfoField::IsInPit);
+        changed.set(
+            &mut self.is_engine_limiter_on,
+            c.bool()?,
+            CarInfoField::IsEngineLimiterOn,
+        );
+
+        changed.set(
+            &mut self.accg_vertical,
+            c.f32()?,
+            CarInfoField::AccgVertical,
+        );
+        changed.set(
+            &mut self.accg_horizontal,
+            c.f32()?,
+            CarInfoField::AccgHorizontal,
+        );
+        changed.set(&mut self.accg_frontal, c.f32()?, CarInfoField::AccgFrontal);
+
+        changed.set(&mut self.lap_time, c.u32()?, CarInfoField::LapTime);
+        changed.set(&mut self.last_lap, c.u32()?, CarInfoField::LastLap);
+        changed.set(&mut self.best_lap, c.u32()?, CarInfoField::BestLap);
+        changed.set(&mut self.lap_count, c.u32()?, CarInfoField::LapCount);
+
+        changed.set(&mut self.gas, c.f32()?, CarInfoField::Gas);
+        changed.set(&mut self.brake, c.f32()?, CarInfoField::Brake);
+        changed.set(&mut self.clutch, c.f32()?, CarInfoField::Clutch);
+        changed.set(&mut self.engine_rpm, c.f32()?, CarInfoField::EngineRpm);
+        changed.set(&mut self.steer, c.f32()?, CarInfoField::Steer);
+        changed.set(&mut self.gear, c.i32()?, CarInfoField::Gear);
+        changed.set(&mut self.cg_height, c.f32()?, CarInfoField::CgHeight);
+
+        changed.set(
+            &mut self.wheel_angular_speed,
+            c.wheels()?,
+            CarInfoField::WheelAngularSpeed,
+        );
+        changed.set(&mut self.slip_angle, c.wheels()?, CarInfoField::SlipAngle);
+        changed.set(
+            &mut self.slip_angle_contact_patch,
+            c.wheels()?,
+            CarInfoField::SlipAngleContactPatch,
+        );
+        changed.set(&mut self.slip_ratio, c.wheels()?, CarInfoField::SlipRatio);
+        changed.set(&mut self.tyre_slip, c.wheels()?, CarInfoField::TyreSlip);
+        changed.set(&mut self.nd_slip, c.wheels()?, CarInfoField::NdSlip);
+        changed.set(&mut self.load, c.wheels()?, CarInfoField::Load);
+        changed.set(&mut self.dy, c.wheels()?, CarInfoField::Dy);
+        changed.set(&mut self.mz, c.wheels()?, CarInfoField::Mz);
+        changed.set(
+            &mut self.tyre_dirty_level,
+            c.wheels()?,
+            CarInfoField::TyreDirtyLevel,
+        );
+        changed.set(&mut self.camber_rad, c.wheels()?, CarInfoField::CamberRad);
+        changed.set(&mut self.tyre_radius, c.wheels()?, CarInfoField::TyreRadius);
+        changed.set(
+            &mut self.tyre_loaded_radius,
+            c.wheels()?,
+            CarInfoField::TyreLoadedRadius,
+        );
+        changed.set(
+            &mut self.suspension_height,
+            c.wheels()?,
+            CarInfoField::SuspensionHeight,
+        );
+        changed.set(
+            &mut self.car_pos_normalized,
+            c.f32()?,
+            CarInfoField::CarPosNormalized,
+        );
+        changed.set(&mut self.car_slope, c.f32()?, CarInfoField::CarSlope);
+        changed.set(
+            &mut self.car_coordinates,
+            c.xyz()?,
+            CarInfoField::CarCoordinates,
+        );
+
+        Ok(changed)
+    }
+}
+
+/// A lap completed by a car on track, sent for every car with `SubscribeSpot`.
+///
+/// * `car_id_num`: which car, stable for the session.
+/// * `lap`: the lap number just completed.
+/// * `time`: the lap time, in milliseconds.
+/// * `car_name`, `driver_name`: the car's model and its driver.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LapInfo {
+    pub car_id_num: i32,
+    pub lap: i32,
+    pub time: i32,
+    pub car_name: Name,
+    pub driver_name: Name,
+    /// bytes past the end of the known layout, from a modded or newer server.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub trailing: Vec<u8>,
+}
+impl IntoEvent for LapInfo {
+    fn from_bytes(buf: &[u8]) -> Result<Self, ParserError> {
+        if buf.len() != LAP_INFO_LEN {
+            return Err(ParserError::IncorrectBufferSize(buf.len()));
+        }
+
+        let mut c = ByteCursor::new(buf);
+
+        let car_id_num = c.i32()?;
+        let lap = c.i32()?;
+        let driver_name = parse_to_utf16_chars(c.take(lap_info::DRIVER_NAME.len())?);
+        let car_name = parse_to_utf16_chars(c.take(lap_info::CAR_NAME.len())?);
+        let time = c.i32()?;
+
+        Ok(LapInfo {
+            car_id_num,
+            time,
+            lap,
+            car_name,
+            driver_name,
+            trailing: Vec::new(),
+        })
+    }
+}
+
+// the kind of message we can receive from the UDP server
+// reference for parsing: https://docs.google.com/spreadsheets/d/1PhWgG1B7cv38OEummTZOOItrE-yYRBpMI2nV92BfDFU/pubhtml?gid=0&single=true
+// CarInfo is by far the most frequent event, so it stays inline rather than
+// boxed to avoid an allocation per packet.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+// Tagged inline, e.g. `{"kind": "car_info", "speed_kmh": 180.0, ..}`, for JSON consumers.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Event {
+    HandshakeResponse(HandshakeResponse),
+    CarInfo(CarInfo),
+    LapInfo(LapInfo),
+}
+
+impl From<HandshakeResponse> for Event {
+    fn from(response: HandshakeResponse) -> Self {
+        Event::HandshakeResponse(response)
+    }
+}
+
+impl From<CarInfo> for Event {
+    fn from(car: CarInfo) -> Self {
+        Event::CarInfo(car)
+    }
+}
+
+impl From<LapInfo> for Event {
+    fn from(lap: LapInfo) -> Self {
+        Event::LapInfo(lap)
+    }
+}
+
+impl Event {
+    /// parses a raw UDP packet into the matching event, dispatching on its size.
+    /// A packet longer than a known layout parses as that layout, with the
+    /// rest kept in the event's `trailing`; see `oversized_layout`.
+    ///
+    /// Never panics: any input, however garbled, yields either an event or an error.
+    ///
+    /// * `buf`: the received packet, trimmed to the number of bytes read.
+    pub fn try_parse(buf: &[u8]) -> Result<Event, ParserError> {
+        match buf.len() {
+            HANDSHAKE_RES_LEN => HandshakeResponse::from_bytes(buf).map(Event::HandshakeResponse),
+            CAR_INFO_LEN => CarInfo::from_bytes(buf).map(Event::CarInfo),
+            LAP_INFO_LEN => LapInfo::from_bytes(buf).map(Event::LapInfo),
+            len => {
+                let layout = oversized_layout(buf).ok_or(ParserError::UnknownPacketSize(len))?;
+                let (known, trailing) = buf.split_at(layout);
+                let mut event = Event::try_parse(known)?;
+                *event.trailing_mut() = trailing.to_vec();
+                Ok(event)
+            }
+        }
+    }
+
+    /// bytes the server sent past the end of the known layout.
+    pub fn trailing(&self) -> &[u8] {
+        match self {
+            Event::HandshakeResponse(handshake) => &handshake.trailing,
+            Event::CarInfo(car) => &car.trailing,
+            Event::LapInfo(info) => &info.trailing,
+        }
+    }
+
+    fn trailing_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            Event::HandshakeResponse(handshake) => &mut handshake.trailing,
+            Event::CarInfo(car) => &mut car.trailing,
+            Event::LapInfo(info) => &mut info.trailing,
+        }
+    }
+}
+
+/// the length of the known layout an oversized packet starts with, if the
+/// packet confirms it. A CarInfo does with its `'a'` identifier and a size
+/// of either its desktop layout or the whole packet. Handshake responses and
+/// LapInfo carry neither, so they're confirmed by their name fields, which
+/// must each hold a null or `%` padded UTF-16 name, and by not starting like
+/// a CarInfo; anything else past a known size is left unknown rather than
+/// guessed at.
+///
+/// * `buf`: a packet of any size; those of a known size are never oversized.
+pub(crate) fn oversized_layout(buf: &[u8]) -> Option<usize> {
+    let len = buf.len();
+    if matches!(len, HANDSHAKE_RES_LEN | CAR_INFO_LEN | LAP_INFO_LEN) {
+        return None;
+    }
+    let starts_like_car = buf
+        .get(car_info::IDENTIFIER)
+        .is_some_and(|identifier| matches!(parse_identifier(identifier), Ok('a')));
+    let names = |fields: &[std::ops::Range<usize>]| {
+        !starts_like_car
+            && fields
+                .iter()
+                .all(|field| buf.get(field.clone()).is_some_and(is_padded_name))
+    };
+
+    if len > CAR_INFO_LEN && starts_like_car && is_self_sized(buf) {
+        Some(CAR_INFO_LEN)
+    } else if len > HANDSHAKE_RES_LEN
+        && names(&[
+            handshake_response::CAR_NAME,
+            handshake_response::DRIVER_NAME,
+            handshake_response::TRACK_NAME,
+            handshake_response::TRACK_CONFIG,
+        ])
+    {
+        Some(HANDSHAKE_RES_LEN)
+    } else if len > LAP_INFO_LEN && names(&[lap_info::DRIVER_NAME, lap_info::CAR_NAME]) {
+        Some(LAP_INFO_LEN)
+    } else {
+        None
+    }
+}
+
+/// whether a CarInfo's size field gives either desktop's layout or the
+/// packet's own length.
+///
+/// * `buf`: a packet starting like a CarInfo.
+fn is_self_sized(buf: &[u8]) -> bool {
+    buf.get(car_info::SIZE)
+        .and_then(|size| size.try_into().ok())
+        .and_then(|size| usize::try_from(i32::from_le_bytes(size)).ok())
+        .is_some_and(|size| size == CAR_INFO_LEN || size == buf.len())
+}
+
+/// whether a fixed width field holds a UTF-16LE name: printable chars, then
+/// at least one null or `%` and nothing but those to the end.
+///
+/// * `field`: the name field's bytes.
+fn is_padded_name(field: &[u8]) -> bool {
+    let units = || {
+        field
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+    };
+    let is_padding = |unit: u16| unit == 0 || unit == u16::from(b'%');
+    let Some(end) = units().position(is_padding) else {
+        return false;
+    };
+
+    units().skip(end).all(is_padding)
+        && char::decode_utf16(units().take(end)).all(|c| c.is_ok_and(|c| !c.is_control()))
+}
+
+/// decodes a packet however it was captured, e.g. read out of a pcap file or
+/// off a socket of your own. The same as `Event::try_parse`.
+///
+/// * `buf`: the UDP payload, trimmed to the number of bytes read.
+pub fn decode(buf: &[u8]) -> Result<Event, ParserError> {
+    Event::try_parse(buf)
+}
+
+/// A central data structure that is used to communicate event subscriptions with the AC server.
+///
+/// * `identifier`: the kind of device this client is running on.
+/// * `version`: the AC version (apparently not used with the current UDP impl).
+/// * `operation`: the Kind of the operation we want to request from the UDP socket.
+#[derive(Debug)]
+pub struct Handshake {
+    pub identifier: Device,
+    pub version: i32,
+    pub operation: Operation,
+}
+
+/// whether a decoded char is part of a name, rather than padding or AC's `%` filler.
+fn is_name_char(v: &char) -> bool {
+    v.ne(&'\0') && v.ne(&'%')
+}
+
+/// parses the single char identifier at the start of a CarInfo packet,
+/// without allocating.
+///
+/// * `buf`: the identifier field.
+fn parse_identifier(buf: &[u8]) -> Result<char, ParserError> {
+    let decoded = String::from_utf8_lossy(buf);
+    let mut chars = decoded.chars().filter(is_name_char);
+
+    match (chars.next(), chars.next()) {
+        (Some(identifier), None) => Ok(identifier),
+        (None, _) => Err(ParserError::CharConversionFailed(
+            "cannot parse char from empty string".to_string(),
+        )),
+        (Some(_), Some(_)) => Err(ParserError::CharConversionFailed(
+            "too many characters in string".to_string(),
+        )),
+    }
+}
+
+/// parses a bunch of chars from the UDP server and converts them to correct format (utf8).
+///
+/// * `buf`: the slice of data to convert to string.
+fn parse_utf8_chars(buf: &[u8]) -> Name {
+    // ASCII decodes the same either way, and skips UTF-8 validation.
+    if buf.is_ascii() {
+        return parse_to_utf16_chars(buf);
+    }
+
+    collect_name(String::from_utf8_lossy(buf).chars())
+}
+
+/// parses a buffer of UTF-16LE chars one byte at a time.
+///
+/// Every byte widens to a code unit below 0x100, which is always a valid char
+/// on its own, so this decodes straight to chars without an intermediate buffer.
+///
+/// * `buf`: the slice of data to convert to string.
+fn parse_to_utf16_chars(buf: &[u8]) -> Name {
+    collect_name(buf.iter().map(|&v| char::from(v)))
+}
+
+/// collects decoded chars into a name, dropping padding and stopping once it's full.
+///
+/// * `chars`: the decoded field.
+fn collect_name<I>(chars: I) -> Name
+where
+    I: Iterator<Item = char>,
+{
+    let mut out = Name::new();
+    for v in chars.filter(is_name_char) {
+        if out.try_push(v).is_err() {
+            break;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod parser_tests {
+
+    use crate::{
+        CAR_INFO_LEN, CarInfo, CarInfoField, Event, HANDSHAKE_RES_LEN, HandshakeResponse,
+        IntoEvent, LAP_INFO_LEN, LapInfo, Name, ParserError, decode,
+    };
+
+    fn put_f32(buf: &mut [u8], offset: usize, val: f32) {
+        buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    fn put_i32(buf: &mut [u8], offset: usize, val: i32) {
+        buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+        buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    // Builds a 328-byte CarInfo buffer where every distinct field/element is
+    // a marker float/int equal to its own byte offset, so a wrong offset
+    // reads a value that doesn't match its expected position.
+    fn marker_car_info_buf() -> Vec<u8> {
+        let mut buf = vec![0u8; CAR_INFO_LEN];
+        // identifier: ASCII 'C' at byte 0, rest of the 4-byte field stays null
+        buf[0] = b'C';
+
+        put_i32(&mut buf, 4, 4); // size
+        put_f32(&mut buf, 8, 8.0); // speed_kmh
+        put_f32(&mut buf, 12, 12.0); // speed_mph
+        put_f32(&mut buf, 16, 16.0); // speed_ms
+
+        buf[20] = 1; // is_abs_enabled
+        buf[21] = 1; // is_abs_in_action
+        buf[22] = 1; // is_tc_in_action
+        buf[23] = 1; // is_tc_enabled
+        // 24, 25 = padding gap
+        buf[26] = 1; // is_in_pit
+        buf[27] = 1; // is_engine_limiter_on
+
+        put_f32(&mut buf, 28, 28.0); // accg_vertical
+        put_f32(&mut buf, 32, 32.0); // accg_horizontal
+        put_f32(&mut buf, 36, 36.0); // accg_frontal
+
+        put_u32(&mut buf, 40, 40); // lap_time
+        put_u32(&mut buf, 44, 44); // last_lap
+        put_u32(&mut buf, 48, 48); // best_lap
+        put_u32(&mut buf, 52, 52); // lap_count
+
+        put_f32(&mut buf, 56, 56.0); // gas
+        put_f32(&mut buf, 60, 60.0); // brake
+        put_f32(&mut buf, 64, 64.0); // clutch
+        put_f32(&mut buf, 68, 68.0); // engine_rpm
+        put_f32(&mut buf, 72, 72.0); // steer
+        put_i32(&mut buf, 76, 76); // gear
+        put_f32(&mut buf, 80, 80.0); // cg_height
+
+        // 14 wheel groups of 16 bytes each start at 84, end at 84 + 14*16 = 308
+        for i in 0..14 {
+            let base = 84 + i * 16;
+            for w in 0..4 {
+                put_f32(&mut buf, base + w * 4, (base + w * 4) as f32);
+            }
+        }
+
+        put_f32(&mut buf, 308, 308.0); // car_pos_normalized
+        put_f32(&mut buf, 312, 312.0); // car_slope
+        put_f32(&mut buf, 316, 316.0); // car_coordinates[0]
+        put_f32(&mut buf, 320, 320.0); // car_coordinates[1]
+        put_f32(&mut buf, 324, 324.0); // car_coordinates[2]
+
+        buf
+    }
+
+    #[test]
+    fn car_info_fields_land_on_documented_offsets() {
+        let buf = marker_car_info_buf();
+        let info = CarInfo::from_bytes(&buf).expect("328-byte buffer should parse");
+
+        assert_eq!(info.size, 4);
+        assert_eq!(info.speed_kmh, 8.0);
+        assert_eq!(info.speed_mph, 12.0);
+        assert_eq!(info.speed_ms, 16.0);
+
+        assert!(info.is_abs_enabled);
+        assert!(info.is_abs_in_action);
+        assert!(info.is_tc_in_action);
+        assert!(info.is_tc_enabled);
+        assert!(info.is_in_pit);
+        assert!(info.is_engine_limiter_on);
+
+        assert_eq!(info.accg_vertical, 28.0);
+        assert_eq!(info.accg_horizontal, 32.0);
+        assert_eq!(info.accg_frontal, 36.0);
+
+        assert_eq!(info.lap_time, 40);
+        assert_eq!(info.last_lap, 44);
+        assert_eq!(info.best_lap, 48);
+        assert_eq!(info.lap_count, 52); // guards the historical off-by-10 bug
+
+        assert_eq!(info.gas, 56.0);
+        assert_eq!(info.brake, 60.0);
+        assert_eq!(info.clutch, 64.0);
+        assert_eq!(info.engine_rpm, 68.0);
+        assert_eq!(info.steer, 72.0);
+        assert_eq!(info.gear, 76);
+        assert_eq!(info.cg_height, 80.0);
+
+        assert_eq!(info.wheel_angular_speed, [84.0, 88.0, 92.0, 96.0]);
+        assert_eq!(info.suspension_height, [292.0, 296.0, 300.0, 304.0]);
+
+        assert_eq!(info.car_pos_normalized, 308.0);
+        assert_eq!(info.car_slope, 312.0);
+        assert_eq!(info.car_coordinates, [316.0, 320.0, 324.0]);
+    }
+
+    #[test]
+    fn car_info_identifier_must_be_a_single_char() {
+        let buf = marker_car_info_buf();
+        let info = CarInfo::from_bytes(&buf).expect("328-byte buffer should parse");
+        assert_eq!(info.identifier, 'C');
+
+        let mut empty = buf.clone();
+        empty[0] = 0;
+        assert!(CarInfo::from_bytes(&empty).is_err(), "No identifier char");
+
+        let mut doubled = buf;
+        doubled[1] = b'D';
+        assert!(
+            CarInfo::from_bytes(&doubled).is_err(),
+            "Two identifier chars"
+        );
+    }
+
+    #[test]
+    fn parse_into_updates_in_place_and_reports_changes() {
+        let mut info = CarInfo::from_bytes(&marker_car_info_buf()).expect("should parse");
+
+        let unchanged = info
+            .parse_into(&marker_car_info_buf())
+            .expect("should parse");
+        assert!(unchanged.is_empty(), "Same packet changes nothing");
+
+        let mut buf = marker_car_info_buf();
+        put_f32(&mut buf, 56, 0.75); // gas
+        put_i32(&mut buf, 76, 3); // gear
+
+        let changed = info.parse_into(&buf).expect("should parse");
+        assert_eq!(
+            changed.iter().collect::<Vec<_>>(),
+            vec![CarInfoField::Gas, CarInfoField::Gear]
+        );
+        assert_eq!(info.gas, 0.75);
+        assert_eq!(info.gear, 3);
+
+        let before = info.clone();
+        let mut bad = buf;
+        bad[0] = 0; // no identifier
+        assert!(info.parse_into(&bad).is_err());
+        assert_eq!(info, before, "Failed parse leaves the struct untouched");
+    }
+
+    #[test]
+    fn car_info_rejects_wrong_size_buffer() {
+        let buf = vec![0u8; CAR_INFO_LEN - 1];
+        assert!(CarInfo::from_bytes(&buf).is_err());
+    }
+
+    // Builds a 212-byte LapInfo buffer with distinct marker values so a wrong
+    // offset reads a value that doesn't match its expected field.
+    fn marker_lap_info_buf() -> Vec<u8> {
+        let mut buf = vec![0u8; LAP_INFO_LEN];
+
+        put_i32(&mut buf, 0, 7); // car_id_num
+        put_i32(&mut buf, 4, 3); // lap
+
+        buf[8] = b'D'; // driver_name
+        buf[108] = b'C'; // car_name
+
+        put_i32(&mut buf, 208, 12345); // time
+
+        buf
+    }
+
+    #[test]
+    fn lap_info_fields_land_on_documented_offsets() {
+        let buf = marker_lap_info_buf();
+        let info = LapInfo::from_bytes(&buf).expect("212-byte buffer should parse");
+
+        assert_eq!(info.car_id_num, 7);
+        assert_eq!(info.lap, 3);
+        assert_eq!(info.driver_name.as_str(), "D");
+        assert_eq!(info.car_name.as_str(), "C");
+        assert_eq!(info.time, 12345);
+    }
+
+    // Invalid UTF-8 decodes to three byte replacement chars, more than a name holds.
+    #[test]
+    fn overlong_names_are_truncated_at_a_char_boundary() {
+        let mut buf = [0u8; HANDSHAKE_RES_LEN];
+        buf[..100].fill(0xFF); // car_name
+
+        let response = HandshakeResponse::from_bytes(&buf).expect("should parse");
+        assert_eq!(response.car_name.len(), 99);
+        assert!(
+            response
+                .car_name
+                .chars()
+                .all(|c| c == char::REPLACEMENT_CHARACTER)
+        );
+    }
+
+    #[test]
+    fn lap_info_rejects_wrong_size_buffer() {
+        let buf = vec![0u8; LAP_INFO_LEN - 1];
+        assert!(LapInfo::from_bytes(&buf).is_err());
+    }
+
+    #[test]
+    fn try_parse_dispatches_on_packet_size() {
+        assert!(matches!(
+            Event::try_parse(&marker_car_info_buf()),
+            Ok(Event::CarInfo(_))
+        ));
+        assert!(matches!(
+            Event::try_parse(&marker_lap_info_buf()),
+            Ok(Event::LapInfo(_))
+        ));
+        assert!(matches!(
+            Event::try_parse(&[0u8; HANDSHAKE_RES_LEN]),
+            Ok(Event::HandshakeResponse(_))
+        ));
+        assert!(matches!(
+            Event::try_parse(&[0u8; 13]),
+            Err(ParserError::UnknownPacketSize(13))
+        ));
+        assert!(matches!(
+            decode(&marker_lap_info_buf()),
+            Ok(Event::LapInfo(LapInfo { lap: 3, .. }))
+        ));
+        assert_eq!(
+            Event::from(CarInfo::default()),
+            Event::CarInfo(CarInfo::default())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn events_round_trip_through_json() {
+        let event = Event::from(LapInfo::from_bytes(&marker_lap_info_buf()).expect("parses"));
+
+        let json = serde_json::to_string(&event).expect("serializes");
+        assert!(
+            json.starts_with(r#"{"kind":"lap_info","car_id_num":7,"lap":3"#),
+            "{json}"
+        );
+        assert!(
+            !json.contains("trailing"),
+            "empty trailing bytes are left out"
+        );
+        assert_eq!(
+            serde_json::from_str::<Event>(&json).expect("deserializes"),
+            event
+        );
+
+        let car = Event::from(CarInfo::default());
+        let json = serde_json::to_string(&car).expect("serializes");
+        assert_eq!(
+            serde_json::from_str::<Event>(&json).expect("deserializes"),
+            car
+        );
+    }
+
+    #[test]
+    fn oversized_packets_keep_their_trailing_bytes() {
+        let mut packet = CarInfo::default().to_bytes().to_vec();
+        packet.extend_from_slice(&[1, 2, 3, 4]);
+
+        let event = Event::try_parse(&packet).expect("should parse");
+        assert!(matches!(event, Event::CarInfo(_)));
+        assert_eq!(event.trailing(), [1, 2, 3, 4]);
+        assert_eq!(
+            event.to_bytes().to_vec(),
+            packet,
+            "re-encodes byte for byte"
+        );
+
+        let mut packet = marker_lap_info_buf().to_vec();
+        packet.push(9);
+        let Ok(Event::LapInfo(info)) = Event::try_parse(&packet) else {
+            panic!("expected a LapInfo");
+        };
+        assert_eq!(info.trailing, [9]);
+
+        let handshake = HandshakeResponse {
+            car_name: Name::from("ks_porsche_911_gt3_r_2016").expect("fits"),
+            track_name: Name::from("spa").expect("fits"),
+            ..HandshakeResponse::default()
+        };
+        let mut packet = handshake.to_bytes().to_vec();
+        packet.extend_from_slice(&[0xff; 8]);
+        let Ok(Event::HandshakeResponse(parsed)) = Event::try_parse(&packet) else {
+            panic!("expected a HandshakeResponse");
+        };
+        assert_eq!(
+            (parsed.car_name, parsed.trailing.len()),
+            (handshake.car_name, 8)
+        );
+    }
+
+    // Past every known size, only a packet that confirms its layout parses.
+    #[test]
+    fn oversized_garbage_is_an_unknown_size() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        };
+
+        let capabilities = HandshakeResponse::default().capabilities();
+        let sizes =
+            (LAP_INFO_LEN + 1..=600).filter(|&len| len != CAR_INFO_LEN && len != HANDSHAKE_RES_LEN);
+        for len in sizes {
+            for _ in 0..20 {
+                let packet: Vec<u8> = (0..len).map(|_| random()).collect();
+                assert!(
+                    matches!(Event::try_parse(&packet), Err(ParserError::UnknownPacketSize(n)) if n == len),
+                    "{len} random bytes parsed"
+                );
+                assert!(
+                    matches!(capabilities.parse(&packet), Err(ParserError::UnknownPacketSize(n)) if n == len),
+                    "{len} random bytes parsed by the server's capabilities"
+                );
+            }
+        }
+
+        // An `'a'` alone isn't a CarInfo without a size to back it.
+        let mut packet = CarInfo::default().to_bytes().to_vec();
+        packet[4..8].copy_from_slice(&1234i32.to_le_bytes());
+        packet.push(0);
+        assert!(matches!(
+            Event::try_parse(&packet),
+            Err(ParserError::UnknownPacketSize(329))
+        ));
+    }
+
+    // Arbitrary bytes of every size up to the largest packet must never panic.
+    #[test]
+    fn try_parse_never_panics_on_garbage() {
+        let garbage: Vec<u8> = (0..HANDSHAKE_RES_LEN + 1)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+
+        for len in 0..=garbage.len() {
+            let _ = Event::try_parse(&garbage[..len]);
+        }
+    }
+}
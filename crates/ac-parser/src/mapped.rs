@@ -0,0 +1,250 @@
+//! Memory-mapped access to recordings too big to load, such as multi-hour
+//! endurance sessions. Opening a recording only scans the frame headers to build
+//! an index; packets stay on disk until a frame is asked for, and are never copied.
+
+use std::{fs::File, io, path::Path, time::Duration};
+
+use memmap2::Mmap;
+
+use crate::{
+    Event, ParserError,
+    recording::{
+        FRAME_HEADER_LEN, Frame, MARK_FLAG, Mark, anchored_wall_clock, header_len, read_mark,
+        read_started_at,
+    },
+    timestamp::Timestamp,
+};
+
+/// A frame borrowed straight from the mapped file.
+///
+/// * `at`: when it arrived, relative to the start of the recording.
+/// * `packet`: the raw bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRef<'a> {
+    pub at: Duration,
+    pub packet: &'a [u8],
+}
+
+impl FrameRef<'_> {
+    pub fn parse(&self) -> Result<Event, ParserError> {
+        Event::try_parse(self.packet)
+    }
+
+    /// copies the frame out of the file.
+    pub fn to_frame(&self) -> Frame {
+        Frame {
+            at: self.at,
+            packet: self.packet.to_vec(),
+        }
+    }
+}
+
+/// A recording file mapped into memory, with random access to its frames by index.
+///
+/// * `map`: the file's bytes.
+/// * `offsets`: where each frame's header starts in `map`.
+/// * `started_at`: the wall-clock time frame times are relative to, if recorded.
+/// * `marks`: wall-clock anchors and sync marks, decoded while indexing; there are few.
+pub struct MappedRecording {
+    map: Mmap,
+    offsets: Vec<usize>,
+    started_at: Option<Timestamp>,
+    marks: Vec<Mark>,
+}
+
+impl MappedRecording {
+    /// maps a recording written with `Recording::write_to` and indexes its frames.
+    ///
+    /// The file must not be modified or truncated while it's mapped.
+    ///
+    /// * `path`: the recording file.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path)?;
+
+        // SAFETY: the mapping is read-only, and changing the file underneath it is
+        // documented above as unsupported.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let (started_at, offsets, marks) = index_frames(&map)?;
+        Ok(Self {
+            map,
+            offsets,
+            started_at,
+            marks,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    pub fn started_at(&self) -> Option<Timestamp> {
+        self.started_at
+    }
+
+    /// when a frame arrived in wall-clock time, if the recording's start is known.
+    ///
+    /// * `frame`: a frame of this recording.
+    pub fn wall_clock(&self, frame: &FrameRef<'_>) -> Option<Timestamp> {
+        anchored_wall_clock(&self.marks, self.started_at, frame.at)
+    }
+
+    /// wall-clock anchors and sync marks, in time order.
+    pub fn marks(&self) -> &[Mark] {
+        &self.marks
+    }
+
+    /// the frame at `index`, read from the file without copying.
+    ///
+    /// * `index`: the frame's position in the recording.
+    pub fn frame(&self, index: usize) -> Option<FrameRef<'_>> {
+        let offset = *self.offsets.get(index)?;
+        let (at, len) = read_frame_header(&self.map[offset..offset + FRAME_HEADER_LEN]);
+        let start = offset + FRAME_HEADER_LEN;
+
+        Some(FrameRef {
+            at,
+            packet: &self.map[start..start + len],
+        })
+    }
+
+    /// every frame, in arrival order.
+    pub fn frames(&self) -> impl Iterator<Item = FrameRef<'_>> {
+        (0..self.len()).filter_map(|index| self.frame(index))
+    }
+
+    /// the arrival time of the last frame.
+    pub fn duration(&self) -> Duration {
+        self.len()
+            .checked_sub(1)
+            .and_then(|last| self.frame(last))
+            .map_or(Duration::ZERO, |frame| frame.at)
+    }
+
+    /// the index of the first frame that arrived at or after `at`, or `len()` if none did.
+    ///
+    /// * `at`: time relative to the start of the recording.
+    pub fn position_at(&self, at: Duration) -> usize {
+        self.offsets.partition_point(|&offset| {
+            read_frame_header(&self.map[offset..offset + FRAME_HEADER_LEN]).0 < at
+        })
+    }
+}
+
+/// checks the file header, reads the session start, finds where every frame
+/// starts and decodes the marks between them.
+///
+/// * `bytes`: the whole recording.
+fn index_frames(bytes: &[u8]) -> io::Result<(Option<Timestamp>, Vec<usize>, Vec<Mark>)> {
+    let invalid = |why| io::Error::new(io::ErrorKind::InvalidData, why);
+
+    let header_len = header_len(bytes)?;
+    let header = bytes
+        .get(..header_len)
+        .ok_or_else(|| invalid("recording ends partway through its header"))?;
+    let started_at = read_started_at(header);
+
+    let mut offsets = Vec::new();
+    let mut marks = Vec::new();
+    let mut offset = header_len;
+
+    // A partial frame header at the end is ignored, as `Recording::read_from` does.
+    while let Some(header) = bytes.get(offset..offset + FRAME_HEADER_LEN) {
+        let (at, len) = read_frame_header(header);
+        let is_mark = len & MARK_FLAG as usize != 0;
+        let start = offset + FRAME_HEADER_LEN;
+        let end = start + (len & !MARK_FLAG as usize);
+
+        if end > bytes.len() {
+            return Err(invalid("recording ends partway through a frame"));
+        }
+
+        if is_mark {
+            marks.push(read_mark(at, &bytes[start..end])?);
+        } else {
+            offsets.push(offset);
+        }
+        offset = end;
+    }
+
+    Ok((started_at, offsets, marks))
+}
+
+/// * `header`: a frame's 12 header bytes.
+fn read_frame_header(header: &[u8]) -> (Duration, usize) {
+    let (nanos, len) = header.split_at(8);
+    let nanos = u64::from_le_bytes(nanos.try_into().expect("split at 8"));
+    let len = u32::from_le_bytes(len.try_into().expect("split at 8 of 12"));
+
+    (Duration::from_nanos(nanos), len as usize)
+}
+
+#[cfg(test)]
+mod mapped_tests {
+    use std::{fs, time::Duration};
+
+    use crate::{
+        CarInfo, Event, mapped::MappedRecording, recording::Recording, timestamp::Timestamp,
+    };
+
+    #[test]
+    fn frames_are_read_lazily_by_index() {
+        let mut recording = Recording::new();
+        recording.set_started_at(Timestamp::from_unix_nanos(1_000_000_000));
+        for gear in 0..5 {
+            let car = CarInfo {
+                gear,
+                ..CarInfo::default()
+            };
+            recording.push(Duration::from_millis(100 * gear as u64), &car.to_bytes());
+        }
+
+        let path = std::env::temp_dir().join(format!("ac_lib-mapped-{}.acrc", std::process::id()));
+        recording
+            .write_to(fs::File::create(&path).expect("failed to create"))
+            .expect("failed to write");
+
+        let mapped = MappedRecording::open(&path).expect("failed to map");
+        assert_eq!(mapped.len(), 5);
+        assert_eq!(mapped.duration(), Duration::from_millis(400));
+        assert_eq!(
+            mapped.frames().map(|f| f.to_frame()).collect::<Vec<_>>(),
+            recording.frames()
+        );
+
+        let index = mapped.position_at(Duration::from_millis(250));
+        assert_eq!(index, 3);
+        let frame = mapped.frame(index).expect("frame exists");
+        assert!(matches!(frame.parse(), Ok(Event::CarInfo(car)) if car.gear == 3));
+        assert!(mapped.frame(5).is_none());
+        assert_eq!(
+            mapped.wall_clock(&frame).map(Timestamp::unix_nanos),
+            Some(1_300_000_000)
+        );
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let mut recording = Recording::new();
+        recording.push(Duration::ZERO, &[1, 2, 3, 4]);
+        let mut bytes = Vec::new();
+        recording.write_to(&mut bytes).expect("failed to write");
+        bytes.pop();
+
+        let path =
+            std::env::temp_dir().join(format!("ac_lib-truncated-{}.acrc", std::process::id()));
+        fs::write(&path, bytes).expect("failed to write");
+
+        assert!(MappedRecording::open(&path).is_err());
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use crate::timestamp::Timestamp;
+
+/// Identifies a recording file, followed by a one byte format version.
+pub(crate) const MAGIC: &[u8; 4] = b"ACRC";
+/// Version 2 added the session start time to the header, version 3 marks.
+pub(crate) const VERSION: u8 = 3;
+/// The magic and version.
+const PREFIX_LEN: usize = 5;
+/// The magic, version and u64 session start in Unix nanoseconds, zero if unknown.
+pub(crate) const HEADER_LEN: usize = 13;
+/// Each frame's u64 arrival time and u32 length.
+pub(crate) const FRAME_HEADER_LEN: usize = 12;
+/// Set in a frame's length when it holds a mark rather than a packet.
+pub(crate) const MARK_FLAG: u32 = 1 << 31;
+/// The first byte of a mark's payload, saying which kind it is.
+const ANCHOR_MARK: u8 = 0;
+const SYNC_MARK: u8 = 1;
+
+/// A single datagram as it was received.
+///
+/// * `at`: when it arrived, relative to the start of the recording.
+/// * `packet`: the raw bytes, trimmed to the bytes received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub at: Duration,
+    pub packet: Vec<u8>,
+}
+
+/// Something noted on a recording's timeline besides a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkKind {
+    /// The wall-clock time at this point, so a long recording's wall-clock
+    /// times don't drift with the receiving machine's monotonic clock.
+    Anchor(Timestamp),
+    /// A mark set by the user, e.g. when flashing the headlights at a camera,
+    /// for lining the recording up with video of the same moment.
+    Sync(String),
+}
+
+/// A mark on a recording's timeline.
+///
+/// * `at`: when, relative to the start of the recording.
+/// * `kind`: what it marks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mark {
+    pub at: Duration,
+    pub kind: MarkKind,
+}
+
+/// A session's raw packets in arrival order, kept unparsed so a recording can be
+/// replayed through newer versions of the parser.
+///
+/// * `started_at`: the wall-clock time frame times are relative to, if known.
+/// * `marks`: wall-clock anchors and sync marks, in time order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    frames: Vec<Frame>,
+    started_at: Option<Timestamp>,
+    marks: Vec<Mark>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// appends a packet to the recording.
+    ///
+    /// * `at`: arrival time relative to the start of the recording.
+    /// * `packet`: the raw datagram.
+    pub fn push(&mut self, at: Duration, packet: &[u8]) {
+        self.frames.push(Frame {
+            at,
+            packet: packet.to_vec(),
+        });
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn started_at(&self) -> Option<Timestamp> {
+        self.started_at
+    }
+
+    /// anchors the recording to wall-clock time.
+    ///
+    /// * `started_at`: when the first frame's zero point was, usually the handshake.
+    pub fn set_started_at(&mut self, started_at: Timestamp) {
+        self.started_at = Some(started_at);
+    }
+
+    /// notes a wall-clock anchor or sync mark on the timeline.
+    ///
+    /// * `at`: when, relative to the start of the recording.
+    /// * `kind`: what it marks.
+    pub fn push_mark(&mut self, at: Duration, kind: MarkKind) {
+        let index = self.marks.partition_point(|mark| mark.at <= at);
+        self.marks.insert(index, Mark { at, kind });
+    }
+
+    /// every mark, in time order.
+    pub fn marks(&self) -> &[Mark] {
+        &self.marks
+    }
+
+    /// the user's sync marks, in time order.
+    pub fn sync_marks(&self) -> impl Iterator<Item = (Duration, &str)> {
+        self.marks.iter().filter_map(|mark| match &mark.kind {
+            MarkKind::Sync(label) => Some((mark.at, label.as_str())),
+            MarkKind::Anchor(_) => None,
+        })
+    }
+
+    /// when a frame arrived in wall-clock time, if the recording's start is known.
+    ///
+    /// * `frame`: a frame of this recording.
+    pub fn wall_clock(&self, frame: &Frame) -> Option<Timestamp> {
+        self.wall_clock_at(frame.at)
+    }
+
+    /// a point on the recording's timeline in wall-clock time, counted from the
+    /// last anchor before it, or from the start if there's none.
+    ///
+    /// * `at`: time relative to the start of the recording.
+    pub fn wall_clock_at(&self, at: Duration) -> Option<Timestamp> {
+        anchored_wall_clock(&self.marks, self.started_at, at)
+    }
+
+    /// writes the recording in its binary file format:
+    /// a header, then per frame a u64 arrival time in nanoseconds, a u32 length and the packet.
+    /// Marks are frames too, their length's top bit set, in time order among the packets.
+    ///
+    /// * `writer`: where to write the recording.
+    pub fn write_to<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut writer = RecordingWriter::with_start(writer, self.started_at)?;
+        let mut marks = self.marks.iter().peekable();
+
+        for frame in &self.frames {
+            while let Some(mark) = marks.next_if(|mark| mark.at <= frame.at) {
+                writer.write_mark(mark.at, &mark.kind)?;
+            }
+            writer.write_frame(frame.at, &frame.packet)?;
+        }
+        for mark in marks {
+            writer.write_mark(mark.at, &mark.kind)?;
+        }
+
+        writer.flush()
+    }
+
+    /// reads a recording previously written with `write_to`.
+    ///
+    /// * `reader`: where to read the recording from.
+    pub fn read_from<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header[..PREFIX_LEN])?;
+        let len = header_len(&header[..PREFIX_LEN])?;
+        reader.read_exact(&mut header[PREFIX_LEN..len])?;
+        let started_at = read_started_at(&header[..len]);
+
+        let mut frames = Vec::new();
+        let mut marks = Vec::new();
+        let mut frame_header = [0u8; FRAME_HEADER_LEN];
+
+        loop {
+            match reader.read_exact(&mut frame_header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+
+            let (nanos, len) = frame_header.split_at(8);
+            let nanos = u64::from_le_bytes(nanos.try_into().expect("split at 8"));
+            let len = u32::from_le_bytes(len.try_into().expect("split at 8 of 12"));
+
+            let mut packet = vec![0u8; (len & !MARK_FLAG) as usize];
+            reader.read_exact(&mut packet)?;
+            let at = Duration::from_nanos(nanos);
+
+            if len & MARK_FLAG != 0 {
+                marks.push(read_mark(at, &packet)?);
+            } else {
+                frames.push(Frame { at, packet });
+            }
+        }
+
+        Ok(Self {
+            frames,
+            started_at,
+            marks,
+        })
+    }
+}
+
+/// how long a recording's file header is, from its magic and version.
+/// Version 1 recordings have no start time.
+///
+/// * `prefix`: the file's first bytes, at least the magic and version.
+pub(crate) fn header_len(prefix: &[u8]) -> io::Result<usize> {
+    match prefix.get(..PREFIX_LEN) {
+        Some([m0, m1, m2, m3, version]) if [*m0, *m1, *m2, *m3] == *MAGIC => match version {
+            1 => Ok(PREFIX_LEN),
+            2 | 3 => Ok(HEADER_LEN),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported recording version {version}"),
+            )),
+        },
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an ac_lib recording",
+        )),
+    }
+}
+
+/// the session start stored in a file header, if it has one.
+///
+/// * `header`: the whole header, as long as `header_len` says.
+pub(crate) fn read_started_at(header: &[u8]) -> Option<Timestamp> {
+    let nanos = header.get(PREFIX_LEN..HEADER_LEN)?;
+    let nanos = u64::from_le_bytes(nanos.try_into().expect("8 byte start"));
+
+    (nanos != 0).then(|| Timestamp::from_unix_nanos(nanos))
+}
+
+/// a time in wall-clock time, counted from the last anchor before it or else the start.
+///
+/// * `marks`: the recording's marks, in time order.
+/// * `started_at`: the recording's start, if known.
+/// * `at`: time relative to the start of the recording.
+pub(crate) fn anchored_wall_clock(
+    marks: &[Mark],
+    started_at: Option<Timestamp>,
+    at: Duration,
+) -> Option<Timestamp> {
+    let anchor = marks.iter().rev().find_map(|mark| match mark.kind {
+        MarkKind::Anchor(wall) if mark.at <= at => Some((mark.at, wall)),
+        _ => None,
+    });
+
+    match anchor {
+        Some((anchored, wall)) => Some(wall.offset(at - anchored)),
+        None => started_at.map(|start| start.offset(at)),
+    }
+}
+
+/// decodes a mark's payload.
+///
+/// * `at`: the mark's time.
+/// * `payload`: its kind byte and data.
+pub(crate) fn read_mark(at: Duration, payload: &[u8]) -> io::Result<Mark> {
+    let kind = match payload {
+        [ANCHOR_MARK, nanos @ ..] if nanos.len() == 8 => MarkKind::Anchor(
+            Timestamp::from_unix_nanos(u64::from_le_bytes(nanos.try_into().expect("8 bytes"))),
+        ),
+        [SYNC_MARK, label @ ..] => MarkKind::Sync(String::from_utf8_lossy(label).into_owned()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unrecognised recording mark",
+            ));
+        }
+    };
+
+    Ok(Mark { at, kind })
+}
+
+/// Streams frames to a recording file as they arrive, for sessions too long to
+/// keep in memory. The output reads back with `Recording::read_from`.
+pub struct RecordingWriter<W> {
+    writer: W,
+}
+
+impl<W> RecordingWriter<W>
+where
+    W: Write,
+{
+    /// writes the file header, with no wall-clock start.
+    ///
+    /// * `writer`: where to write the recording.
+    pub fn new(writer: W) -> io::Result<Self> {
+        Self::with_start(writer, None)
+    }
+
+    /// writes the file header.
+    ///
+    /// * `writer`: where to write the recording.
+    /// * `started_at`: the wall-clock time frame times are relative to, if known.
+    pub fn with_start(mut writer: W, started_at: Option<Timestamp>) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&started_at.map_or(0, Timestamp::unix_nanos).to_le_bytes())?;
+
+        Ok(Self { writer })
+    }
+
+    /// appends a packet to the recording.
+    ///
+    /// * `at`: arrival time relative to the start of the recording.
+    /// * `packet`: the raw datagram.
+    pub fn write_frame(&mut self, at: Duration, packet: &[u8]) -> io::Result<()> {
+        self.writer
+            .write_all(&(at.as_nanos() as u64).to_le_bytes())?;
+        self.writer
+            .write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer.write_all(packet)
+    }
+
+    /// appends a wall-clock anchor or sync mark.
+    ///
+    /// * `at`: time relative to the start of the recording.
+    /// * `kind`: what it marks.
+    pub fn write_mark(&mut self, at: Duration, kind: &MarkKind) -> io::Result<()> {
+        let payload = match kind {
+            MarkKind::Anchor(wall) => {
+                [&[ANCHOR_MARK][..], &wall.unix_nanos().to_le_bytes()].concat()
+            }
+            MarkKind::Sync(label) => [&[SYNC_MARK][..], label.as_bytes()].concat(),
+        };
+
+        self.writer
+            .write_all(&(at.as_nanos() as u64).to_le_bytes())?;
+        self.writer
+            .write_all(&(payload.len() as u32 | MARK_FLAG).to_le_bytes())?;
+        self.writer.write_all(&payload)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod recording_tests {
+    use std::time::Duration;
+
+    use crate::{
+        recording::{MAGIC, MarkKind, Recording},
+        timestamp::Timestamp,
+    };
+
+    #[test]
+    fn recording_round_trips_through_bytes() {
+        let mut recording = Recording::new();
+        recording.push(Duration::ZERO, &[1, 2, 3]);
+        recording.push(Duration::from_millis(16), &[4, 5]);
+
+        let mut bytes = Vec::new();
+        recording.write_to(&mut bytes).expect("failed to write");
+
+        let read = Recording::read_from(bytes.as_slice()).expect("failed to read");
+        assert_eq!(read, recording);
+    }
+
+    #[test]
+    fn start_time_is_kept_and_version_1_files_still_read() {
+        let mut recording = Recording::new();
+        recording.push(Duration::from_millis(250), &[1]);
+        recording.set_started_at(Timestamp::from_unix_nanos(1_700_000_000_000_000_000));
+
+        let mut bytes = Vec::new();
+        recording.write_to(&mut bytes).expect("failed to write");
+        let read = Recording::read_from(bytes.as_slice()).expect("failed to read");
+        assert_eq!(read.started_at(), recording.started_at());
+        assert_eq!(
+            read.wall_clock(&read.frames()[0])
+                .map(Timestamp::unix_nanos),
+            Some(1_700_000_000_250_000_000)
+        );
+
+        let mut v1 = MAGIC.to_vec();
+        v1.push(1);
+        v1.extend_from_slice(&0u64.to_le_bytes());
+        v1.extend_from_slice(&1u32.to_le_bytes());
+        v1.push(7);
+        let read = Recording::read_from(v1.as_slice()).expect("failed to read v1");
+        assert_eq!(read.started_at(), None);
+        assert_eq!(read.frames()[0].packet, [7]);
+    }
+
+    #[test]
+    fn marks_round_trip_and_anchors_correct_the_wall_clock() {
+        let mut recording = Recording::new();
+        recording.set_started_at(Timestamp::from_unix_nanos(1_000_000_000_000));
+        recording.push(Duration::from_secs(1), &[1]);
+        recording.push(Duration::from_secs(2), &[2]);
+        recording.push_mark(Duration::from_millis(1500), MarkKind::Sync("flash".into()));
+        // An hour in, the wall clock says 10 ms more than the arrival times do.
+        recording.push_mark(
+            Duration::from_secs(3600),
+            MarkKind::Anchor(Timestamp::from_unix_nanos(
+                1_000_000_000_000 + 3_600_010_000_000,
+            )),
+        );
+
+        let mut bytes = Vec::new();
+        recording.write_to(&mut bytes).expect("failed to write");
+        let read = Recording::read_from(bytes.as_slice()).expect("failed to read");
+        assert_eq!(read, recording);
+        assert_eq!(read.frames().len(), 2);
+        assert_eq!(
+            read.sync_marks().collect::<Vec<_>>(),
+            [(Duration::from_millis(1500), "flash")]
+        );
+
+        let wall = |at| read.wall_clock_at(at).map(Timestamp::unix_nanos);
+        assert_eq!(wall(Duration::from_secs(2)), Some(1_002_000_000_000));
+        assert_eq!(
+            wall(Duration::from_secs(3601)),
+            Some(1_000_000_000_000 + 3_601_010_000_000)
+        );
+    }
+
+    #[test]
+    fn read_rejects_foreign_files() {
+        let res = Recording::read_from(&b"PK\x03\x04\x14"[..]);
+        assert!(res.is_err(), "Zip header is not a recording");
+    }
+}
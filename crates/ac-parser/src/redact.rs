@@ -0,0 +1,168 @@
+//! Taking names out of data before it's shared, publicly or with a rival
+//! team. Driver names travel in the handshake response and every LapInfo,
+//! and the handshake also carries the server's identifier and version; a
+//! `Redactor` rewrites those packets and leaves the rest byte for byte, so a
+//! redacted recording replays and analyses just like the original.
+
+use std::collections::BTreeMap;
+
+use crate::{Event, Name, Recording};
+
+/// What happens to a driver's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redaction {
+    /// Blanked, so nobody can be told apart.
+    Strip,
+    /// Replaced by `Driver 1`, `Driver 2` and so on, the same name getting the
+    /// same pseudonym everywhere it appears, so battles and standings still read.
+    Pseudonymize,
+}
+
+/// Rewrites events and recordings without driver names or server identifiers.
+/// Reuse one redactor across the recordings of a shared set for pseudonyms
+/// that agree between them.
+///
+/// * `redaction`: what happens to driver names.
+/// * `pseudonyms`: each driver's number, in the order they were first seen.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    redaction: Redaction,
+    pseudonyms: BTreeMap<Name, usize>,
+}
+
+impl Redactor {
+    /// * `redaction`: what happens to driver names.
+    pub fn new(redaction: Redaction) -> Self {
+        Self {
+            redaction,
+            pseudonyms: BTreeMap::new(),
+        }
+    }
+
+    /// the name to show in a driver's place. Empty names stay empty.
+    ///
+    /// * `name`: the driver's real name.
+    pub fn driver(&mut self, name: &Name) -> Name {
+        if name.is_empty() || self.redaction == Redaction::Strip {
+            return Name::new();
+        }
+
+        let next = self.pseudonyms.len() + 1;
+        let number = *self.pseudonyms.entry(*name).or_insert(next);
+        Name::from(&format!("Driver {number}")).expect("a pseudonym fits a name")
+    }
+
+    /// an event without driver names or server identifiers. Cars and tracks are kept.
+    ///
+    /// * `event`: the event to redact.
+    pub fn event(&mut self, event: &Event) -> Event {
+        match event {
+            Event::HandshakeResponse(handshake) => {
+                let mut handshake = handshake.clone();
+                handshake.driver_name = self.driver(&handshake.driver_name);
+                handshake.identifier = 0;
+                handshake.version = 0;
+                Event::HandshakeResponse(handshake)
+            }
+            Event::LapInfo(info) => {
+                let mut info = info.clone();
+                info.driver_name = self.driver(&info.driver_name);
+                Event::LapInfo(info)
+            }
+            Event::CarInfo(car) => Event::CarInfo(car.clone()),
+        }
+    }
+
+    /// a copy of a recording with its handshake and LapInfo packets redacted.
+    /// Everything else, marks and timing included, is kept as it was.
+    ///
+    /// * `recording`: the recording to share.
+    pub fn recording(&mut self, recording: &Recording) -> Recording {
+        let mut redacted = Recording::new();
+        if let Some(started_at) = recording.started_at() {
+            redacted.set_started_at(started_at);
+        }
+
+        for frame in recording.frames() {
+            match Event::try_parse(&frame.packet) {
+                Ok(event @ (Event::HandshakeResponse(_) | Event::LapInfo(_))) => {
+                    redacted.push(frame.at, &self.event(&event).to_bytes());
+                }
+                _ => redacted.push(frame.at, &frame.packet),
+            }
+        }
+        for mark in recording.marks() {
+            redacted.push_mark(mark.at, mark.kind.clone());
+        }
+
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod redact_tests {
+    use std::time::Duration;
+
+    use crate::{
+        CarInfo, Event, HandshakeResponse, LapInfo, Name, Recording,
+        redact::{Redaction, Redactor},
+    };
+
+    #[test]
+    fn names_are_pseudonymized_consistently_and_telemetry_is_untouched() {
+        let name = |name| Name::from(name).expect("short name");
+        let lap = |car_id_num, driver| {
+            Event::LapInfo(LapInfo {
+                car_id_num,
+                lap: 1,
+                time: 90_000,
+                car_name: name("ks_ferrari_488_gt3"),
+                driver_name: name(driver),
+                trailing: Vec::new(),
+            })
+        };
+        let handshake = Event::HandshakeResponse(HandshakeResponse {
+            driver_name: name("Ben"),
+            identifier: 4242,
+            track_name: name("monza"),
+            ..HandshakeResponse::default()
+        });
+        let car = CarInfo {
+            speed_kmh: 250.0,
+            ..CarInfo::default()
+        }
+        .to_bytes();
+
+        let mut recording = Recording::new();
+        for (ms, event) in [
+            (0, &handshake),
+            (20, &lap(1, "Alice")),
+            (40, &lap(0, "Ben")),
+        ] {
+            recording.push(Duration::from_millis(ms), &event.to_bytes());
+        }
+        recording.push(Duration::from_millis(60), &car);
+
+        let redacted = Redactor::new(Redaction::Pseudonymize).recording(&recording);
+        let events: Vec<Event> = redacted
+            .frames()
+            .iter()
+            .map(|frame| Event::try_parse(&frame.packet).expect("parses"))
+            .collect();
+
+        let Event::HandshakeResponse(handshake) = &events[0] else {
+            panic!("expected the handshake first");
+        };
+        assert_eq!(handshake.driver_name.as_str(), "Driver 1");
+        assert_eq!(
+            (handshake.identifier, handshake.track_name.as_str()),
+            (0, "monza")
+        );
+        assert_eq!(events[1], lap(1, "Driver 2"));
+        assert_eq!(events[2], lap(0, "Driver 1"));
+        assert_eq!(redacted.frames()[3].packet, car.to_vec());
+
+        let mut strip = Redactor::new(Redaction::Strip);
+        assert_eq!(strip.event(&lap(1, "Alice")), lap(1, ""));
+    }
+}
@@ -0,0 +1,46 @@
+//! JSON Schema for the serde representation of events, so teams reading
+//! forwarded JSON from other languages can generate their models instead of
+//! writing them by hand. `schema/event.schema.json` at the repository root is
+//! this schema, checked in; the test fails once it goes stale, and rewrites
+//! it when run with `AC_LIB_WRITE_SCHEMA=1`.
+
+use schemars::Schema;
+
+use crate::Event;
+
+/// the JSON Schema of `Event` as the `serde` feature serializes it.
+pub fn event_schema() -> Schema {
+    schemars::schema_for!(Event)
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use std::{env, fs, path::Path};
+
+    use crate::{CarInfo, Event, schema::event_schema};
+
+    #[test]
+    fn checked_in_schema_matches_events() {
+        let schema = serde_json::to_string_pretty(&event_schema()).expect("serializes") + "\n";
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../schema/event.schema.json");
+        if env::var_os("AC_LIB_WRITE_SCHEMA").is_some() {
+            fs::write(&path, &schema).expect("failed to write schema");
+        }
+
+        let checked_in = fs::read_to_string(&path).expect("no checked-in schema");
+        assert_eq!(
+            checked_in, schema,
+            "event.schema.json is stale; rerun with AC_LIB_WRITE_SCHEMA=1"
+        );
+
+        let car = serde_json::to_value(Event::CarInfo(CarInfo::default())).expect("serializes");
+        let kinds: Vec<_> = event_schema().as_value()["oneOf"]
+            .as_array()
+            .expect("one schema per variant")
+            .iter()
+            .map(|variant| variant["properties"]["kind"]["const"].clone())
+            .collect();
+        assert_eq!(kinds, ["handshake_response", "car_info", "lap_info"]);
+        assert!(kinds.contains(&car["kind"]));
+    }
+}
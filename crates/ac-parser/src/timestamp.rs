@@ -0,0 +1,97 @@
+//! Wall-clock time for recordings, so exported data lines up with real-world
+//! time and with video of the session. Frame times stay relative to the start
+//! of the recording; a `Timestamp` anchors them to the calendar. With the
+//! `chrono` or `time` features, timestamps convert to those crates' types.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A point in wall-clock time, to nanosecond precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(SystemTime);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(SystemTime::now())
+    }
+
+    /// * `nanos`: nanoseconds since the Unix epoch.
+    pub fn from_unix_nanos(nanos: u64) -> Self {
+        Self(UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+
+    /// nanoseconds since the Unix epoch, or zero for times before it.
+    pub fn unix_nanos(self) -> u64 {
+        self.0
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64)
+    }
+
+    pub fn system_time(self) -> SystemTime {
+        self.0
+    }
+
+    /// the wall-clock time `offset` after this one.
+    ///
+    /// * `offset`: a frame or sample time relative to this timestamp.
+    pub fn offset(self, offset: Duration) -> Self {
+        Self(self.0 + offset)
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn to_chrono(self) -> chrono::DateTime<chrono::Utc> {
+        self.0.into()
+    }
+
+    #[cfg(feature = "time")]
+    pub fn to_time(self) -> time::OffsetDateTime {
+        self.0.into()
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        Self(time)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.to_chrono()
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<Timestamp> for time::OffsetDateTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.to_time()
+    }
+}
+
+#[cfg(test)]
+mod timestamp_tests {
+    use std::time::Duration;
+
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn timestamps_round_trip_through_unix_nanos() {
+        let start = Timestamp::from_unix_nanos(1_700_000_000_123_456_789);
+        assert_eq!(start.unix_nanos(), 1_700_000_000_123_456_789);
+
+        let later = start.offset(Duration::from_millis(16));
+        assert_eq!(later.unix_nanos() - start.unix_nanos(), 16_000_000);
+        assert!(later > start);
+
+        #[cfg(feature = "chrono")]
+        assert_eq!(start.to_chrono().timestamp(), 1_700_000_000);
+        #[cfg(feature = "time")]
+        assert_eq!(start.to_time().unix_timestamp(), 1_700_000_000);
+    }
+}
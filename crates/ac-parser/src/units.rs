@@ -0,0 +1,113 @@
+//! Which units values are shown in. AC sends metric, and packets, recordings
+//! and data files stay that way; what a person reads, such as a report's top
+//! speeds, follows one preference set for the whole process with
+//! `Units::set_preferred`, so every consumer doesn't convert on its own.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the preference is imperial; metric is the default.
+static IMPERIAL: AtomicBool = AtomicBool::new(false);
+
+const MPH_PER_KMH: f32 = 0.621_371;
+const FEET_PER_METRE: f32 = 3.280_84;
+const GALLONS_PER_LITRE: f32 = 0.264_172;
+
+/// A system of units to show values in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Units {
+    /// km/h, metres and litres.
+    #[default]
+    Metric,
+    /// mph, feet and US gallons.
+    Imperial,
+}
+
+impl Units {
+    /// the process-wide preference, metric unless set otherwise.
+    pub fn preferred() -> Self {
+        if IMPERIAL.load(Ordering::Relaxed) {
+            Units::Imperial
+        } else {
+            Units::Metric
+        }
+    }
+
+    /// makes these the process-wide preference.
+    pub fn set_preferred(self) {
+        IMPERIAL.store(self == Units::Imperial, Ordering::Relaxed);
+    }
+
+    /// * `kmh`: a speed in km/h.
+    pub fn speed(self, kmh: f32) -> f32 {
+        match self {
+            Units::Metric => kmh,
+            Units::Imperial => kmh * MPH_PER_KMH,
+        }
+    }
+
+    pub fn speed_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    /// * `metres`: a length in metres.
+    pub fn length(self, metres: f32) -> f32 {
+        match self {
+            Units::Metric => metres,
+            Units::Imperial => metres * FEET_PER_METRE,
+        }
+    }
+
+    pub fn length_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "m",
+            Units::Imperial => "ft",
+        }
+    }
+
+    /// * `litres`: a volume in litres, e.g. of fuel.
+    pub fn volume(self, litres: f32) -> f32 {
+        match self {
+            Units::Metric => litres,
+            Units::Imperial => litres * GALLONS_PER_LITRE,
+        }
+    }
+
+    pub fn volume_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "l",
+            Units::Imperial => "gal",
+        }
+    }
+
+    /// a speed with its unit, to one decimal, e.g. "180.0 km/h".
+    ///
+    /// * `kmh`: the speed in km/h.
+    pub fn format_speed(self, kmh: f32) -> String {
+        format!("{:.1} {}", self.speed(kmh), self.speed_unit())
+    }
+}
+
+#[cfg(test)]
+mod units_tests {
+    use crate::units::Units;
+
+    #[test]
+    fn imperial_converts_and_metric_passes_through() {
+        assert_eq!(Units::Metric.format_speed(180.0), "180.0 km/h");
+        assert_eq!(Units::Imperial.format_speed(180.0), "111.8 mph");
+        assert!((Units::Imperial.length(100.0) - 328.084).abs() < 1e-3);
+        assert!((Units::Imperial.volume(10.0) - 2.64172).abs() < 1e-5);
+        assert_eq!(Units::Metric.length(100.0), 100.0);
+        assert_eq!(Units::Imperial.length_unit(), "ft");
+        assert_eq!(Units::Metric.volume_unit(), "l");
+        assert_eq!(Units::default(), Units::Metric);
+    }
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Event::try_parse must never panic, whatever arrives on the socket.
+fuzz_target!(|data: &[u8]| {
+    let _ = ac_parser::Event::try_parse(data);
+});